@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{Audio, AudioSprite, Bus},
+    versioned::{self, Versioned},
+};
+
+pub const ANNOUNCER_KEY: &str = "walk_the_dog.announcer";
+
+// Coins in a row that earn a jingle, and its clip in the sprite.
+const MILESTONES: [(u32, &str); 3] = [(5, "x5"), (10, "x10"), (20, "x20")];
+const NOTE_SECONDS: f32 = 0.09;
+const FRAMES_PER_SECOND: f64 = 60.0;
+// C5, E5, G5 and C6: each milestone climbs one note higher.
+const NOTES: [f32; 4] = [523.25, 659.25, 783.99, 1046.5];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum AnnouncerSetting {
+    #[default]
+    On,
+    Off,
+}
+
+impl Versioned for AnnouncerSetting {
+    const VERSION: u32 = 1;
+}
+
+impl AnnouncerSetting {
+    pub fn load() -> Self {
+        versioned::load(ANNOUNCER_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the default announcer setting {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(ANNOUNCER_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            AnnouncerSetting::On => AnnouncerSetting::Off,
+            AnnouncerSetting::Off => AnnouncerSetting::On,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AnnouncerSetting::On => "On",
+            AnnouncerSetting::Off => "Off",
+        }
+    }
+}
+
+// Coins collected in a row. Letting one scroll past starts it over.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Combo {
+    count: u32,
+}
+
+impl Combo {
+    // Adds `collected` coins and returns the milestone just reached, if any.
+    pub fn extend(&mut self, collected: u32) -> Option<u32> {
+        let before = self.count;
+        self.count += collected;
+        MILESTONES
+            .iter()
+            .rev()
+            .map(|(milestone, _clip)| *milestone)
+            .find(|milestone| before < *milestone && self.count >= *milestone)
+    }
+
+    pub fn break_off(&mut self) {
+        self.count = 0;
+    }
+}
+
+fn clip_name(milestone: u32) -> Option<&'static str> {
+    MILESTONES
+        .iter()
+        .find(|(reached, _clip)| *reached == milestone)
+        .map(|(_milestone, clip)| *clip)
+}
+
+// A rising arpeggio, one note longer for each milestone.
+fn jingle(notes: usize, sample_rate: f32) -> Vec<f32> {
+    let note_length = (NOTE_SECONDS * sample_rate) as usize;
+    NOTES
+        .iter()
+        .take(notes)
+        .flat_map(|frequency| {
+            (0..note_length).map(move |index| {
+                let time = index as f32 / sample_rate;
+                let envelope = 1.0 - index as f32 / note_length as f32;
+                (time * frequency * std::f32::consts::TAU).sin() * envelope * 0.3
+            })
+        })
+        .collect()
+}
+
+// Only one cue plays at a time. A milestone reached while another is playing
+// waits for it to finish, and a newer one replaces whatever was waiting.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Voice {
+    frames_left: u32,
+    waiting: Option<u32>,
+}
+
+impl Voice {
+    fn queue(&mut self, milestone: u32) {
+        self.waiting = Some(milestone);
+    }
+
+    // The milestone to start this frame, given how long each one lasts.
+    fn next(&mut self, frames: impl Fn(u32) -> u32) -> Option<u32> {
+        self.frames_left = self.frames_left.saturating_sub(1);
+        if self.frames_left > 0 {
+            return None;
+        }
+        let milestone = self.waiting.take()?;
+        self.frames_left = frames(milestone);
+        Some(milestone)
+    }
+}
+
+// Calls out combo milestones. Without audio it stays quiet.
+#[derive(Clone, Default)]
+pub struct Announcer {
+    sprite: Option<AudioSprite>,
+    voice: Voice,
+}
+
+impl Announcer {
+    pub fn synthesize(audio: &Audio) -> Self {
+        let sample_rate = audio.sample_rate();
+        let clips: Vec<(&str, Vec<f32>)> = MILESTONES
+            .iter()
+            .enumerate()
+            .map(|(index, (_milestone, clip))| (*clip, jingle(index + 2, sample_rate)))
+            .collect();
+        let sprite = match AudioSprite::synthesize(audio, &clips) {
+            Ok(sprite) => Some(sprite),
+            Err(err) => {
+                log!("Could not make the announcer jingles {:#?}", err);
+                None
+            }
+        };
+        Announcer {
+            sprite,
+            voice: Voice::default(),
+        }
+    }
+
+    pub fn announce(&mut self, milestone: u32) {
+        if AnnouncerSetting::load() == AnnouncerSetting::On {
+            self.voice.queue(milestone);
+        }
+    }
+
+    pub fn update(&mut self, audio: &Audio) {
+        let sprite = match &self.sprite {
+            Some(sprite) => sprite,
+            None => return,
+        };
+        let frames = |milestone| {
+            clip_name(milestone)
+                .and_then(|name| sprite.clip(name))
+                .map_or(0, |clip| (clip.duration * FRAMES_PER_SECOND).ceil() as u32)
+        };
+        if let Some(name) = self.voice.next(frames).and_then(clip_name) {
+            let caption = format!("[combo {}]", name);
+            match sprite.play(audio, name, Some(&caption), Bus::Voice) {
+                Ok(()) => audio.duck_music(sprite.clip(name).map_or(0.0, |clip| clip.duration)),
+                Err(err) => {
+                    log!("Could not play the announcer {:#?}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn milestones_are_reached_once_per_combo() {
+        let mut combo = Combo::default();
+
+        assert_eq!(combo.extend(4), None);
+        assert_eq!(combo.extend(1), Some(5));
+        assert_eq!(combo.extend(1), None);
+        assert_eq!(combo.extend(14), Some(20));
+
+        combo.break_off();
+        assert_eq!(combo.extend(5), Some(5));
+    }
+
+    #[test]
+    fn cues_never_overlap() {
+        let mut voice = Voice::default();
+        voice.queue(5);
+        assert_eq!(voice.next(|_| 3), Some(5));
+
+        voice.queue(10);
+        assert_eq!(voice.next(|_| 3), None);
+        voice.queue(20);
+        assert_eq!(voice.next(|_| 3), None);
+        assert_eq!(voice.next(|_| 3), Some(20));
+        assert_eq!(voice.next(|_| 3), None);
+    }
+
+    #[test]
+    fn longer_combos_get_longer_jingles() {
+        assert_eq!(jingle(2, 1000.0).len(), 180);
+        assert_eq!(jingle(4, 1000.0).len(), 360);
+    }
+}
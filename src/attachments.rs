@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{Point, SpriteSheet},
+    game::Cell,
+    versioned::{self, Versioned},
+};
+
+// From the top left of the boy's whole, untrimmed frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Offset {
+    pub x: i16,
+    pub y: i16,
+}
+
+// Named points on the boy, like "head" and "hand". As with hitboxes, a
+// sprite frame's own entry wins over its state's, and states without an
+// entry use the default.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Anchors {
+    #[serde(default)]
+    pub default: BTreeMap<String, Offset>,
+    #[serde(default)]
+    pub states: BTreeMap<String, BTreeMap<String, Offset>>,
+    #[serde(default)]
+    pub frames: BTreeMap<String, BTreeMap<String, Offset>>,
+}
+
+impl Anchors {
+    pub fn point(&self, anchor: &str, state: &str, frame_name: Option<&str>) -> Option<Offset> {
+        let frame = frame_name.and_then(|frame_name| self.frames.get(frame_name));
+        [frame, self.states.get(state), Some(&self.default)]
+            .iter()
+            .flatten()
+            .find_map(|points| points.get(anchor))
+            .copied()
+    }
+}
+
+// A sprite from the atlas drawn with its top left `offset` from an anchor.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub anchor: String,
+    pub sprite: String,
+    #[serde(default)]
+    pub offset: Offset,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttachmentData {
+    pub anchors: Anchors,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl Versioned for AttachmentData {
+    const VERSION: u32 = 1;
+}
+
+pub async fn load_attachments(path: &str) -> Result<AttachmentData> {
+    versioned::fetch(path).await
+}
+
+// What the boy is wearing, with each sprite looked up in the atlas.
+#[derive(Clone, Default)]
+pub struct Outfit {
+    anchors: Anchors,
+    worn: Vec<(Attachment, Cell)>,
+}
+
+impl Outfit {
+    // Attachments whose sprite is not in the atlas are left off.
+    pub fn new(data: AttachmentData, atlas: &SpriteSheet) -> Self {
+        let mut outfit = Outfit {
+            anchors: data.anchors,
+            worn: vec![],
+        };
+        data.attachments.into_iter().for_each(|attachment| {
+            if let Err(err) = outfit.wear(attachment, atlas) {
+                log!("Leaving off an attachment {:#?}", err);
+            }
+        });
+        outfit
+    }
+
+    pub fn wear(&mut self, attachment: Attachment, atlas: &SpriteSheet) -> Result<()> {
+        let cell = atlas
+            .cell(&attachment.sprite)
+            .cloned()
+            .ok_or_else(|| anyhow!("No sprite {} in the atlas", attachment.sprite))?;
+        self.worn.push((attachment, cell));
+        Ok(())
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn take_off_all(&mut self) {
+        self.worn.clear();
+    }
+
+    // Where each worn sprite goes on the boy drawn at `position`. Anything
+    // hanging from an anchor the current frame does not have is not drawn.
+    pub fn placements(
+        &self,
+        state: &str,
+        frame_name: Option<&str>,
+        position: &Point,
+    ) -> Vec<(&Cell, Point)> {
+        self.worn
+            .iter()
+            .filter_map(|(attachment, cell)| {
+                self.anchors
+                    .point(&attachment.anchor, state, frame_name)
+                    .map(|point| {
+                        let at = Point {
+                            x: position.x + point.x + attachment.offset.x,
+                            y: position.y + point.y + attachment.offset.y,
+                        };
+                        (cell, at)
+                    })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors() -> Anchors {
+        serde_json::from_str(
+            r#"{
+                "default": { "head": { "x": 90, "y": 10 }, "hand": { "x": 100, "y": 70 } },
+                "states": { "Sliding": { "head": { "x": 110, "y": 34 } } },
+                "frames": { "Jump (6).png": { "head": { "x": 92, "y": 2 } } }
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn cell() -> Cell {
+        serde_json::from_str(r#"{ "frame": { "x": 0, "y": 0, "w": 10, "h": 10 } }"#).unwrap()
+    }
+
+    #[test]
+    fn frames_win_over_states_which_win_over_the_default() {
+        let anchors = anchors();
+
+        assert_eq!(
+            anchors.point("head", "Jumping", Some("Jump (6).png")),
+            Some(Offset { x: 92, y: 2 })
+        );
+        assert_eq!(
+            anchors.point("head", "Sliding", Some("Slide (1).png")),
+            Some(Offset { x: 110, y: 34 })
+        );
+        assert_eq!(
+            anchors.point("hand", "Sliding", None),
+            Some(Offset { x: 100, y: 70 })
+        );
+        assert_eq!(anchors.point("tail", "Running", None), None);
+    }
+
+    #[test]
+    fn attachments_follow_their_anchor() {
+        let hat = Attachment {
+            anchor: "head".to_string(),
+            sprite: "tiles/hat.png".to_string(),
+            offset: Offset { x: -5, y: -8 },
+        };
+        let outfit = Outfit {
+            anchors: anchors(),
+            worn: vec![(hat, cell())],
+        };
+        let position = Point { x: 100, y: 400 };
+        let at = |state: &str| {
+            outfit
+                .placements(state, None, &position)
+                .iter()
+                .map(|(_cell, at)| *at)
+                .collect::<Vec<Point>>()
+        };
+
+        assert_eq!(at("Running"), vec![Point { x: 185, y: 402 }]);
+        assert_eq!(at("Sliding"), vec![Point { x: 205, y: 426 }]);
+    }
+}
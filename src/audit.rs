@@ -0,0 +1,282 @@
+use std::cell::Cell;
+
+// A check every second at the default tick rate.
+pub const DEFAULT_INTERVAL: u32 = 60;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// Things the simulation reads that do not come from the seed or the inputs,
+// so two runs of the same seed and inputs can still come apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    // `thread_rng` rather than the run's seeded rng.
+    ThreadRng,
+    // An update lasting a different time from the one before, which anything
+    // timed in milliseconds rather than frames notices.
+    FrameTime,
+}
+
+impl Source {
+    const ALL: [Source; 2] = [Source::ThreadRng, Source::FrameTime];
+
+    fn bit(&self) -> u8 {
+        match self {
+            Source::ThreadRng => 1,
+            Source::FrameTime => 1 << 1,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::ThreadRng => "thread_rng",
+            Source::FrameTime => "frame time",
+        }
+    }
+}
+
+thread_local! {
+    static FLAGGED: Cell<u8> = const { Cell::new(0) };
+}
+
+// Notes that `source` was read since the last check.
+pub fn flag(source: Source) {
+    FLAGGED.with(|flagged| flagged.set(flagged.get() | source.bit()));
+}
+
+// Everything flagged since the last call.
+pub fn take_flagged() -> Vec<Source> {
+    let flagged = FLAGGED.with(|flagged| flagged.replace(0));
+    Source::ALL
+        .into_iter()
+        .filter(|source| flagged & source.bit() != 0)
+        .collect()
+}
+
+// FNV-1a, written out so the hash of a state is the same in every build.
+#[derive(Clone, Copy, Debug)]
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(FNV_OFFSET)
+    }
+}
+
+impl StateHasher {
+    // The hash of whatever `write` writes.
+    pub fn digest(write: impl FnOnce(&mut StateHasher)) -> u64 {
+        let mut hasher = StateHasher::default();
+        write(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes.iter().fold(self.0, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+        });
+    }
+
+    pub fn write_i16(&mut self, value: i16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// A digest of the world on one tick, kept in parts so that two which differ
+// say what came apart as well as when.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WorldHash {
+    pub positions: u64,
+    pub velocities: u64,
+    pub rng: u64,
+    pub obstacles: u64,
+    // Animation, counters and everything else that is neither moving nor random.
+    pub progress: u64,
+}
+
+impl WorldHash {
+    fn parts(&self) -> [(&'static str, u64); 5] {
+        [
+            ("positions", self.positions),
+            ("velocities", self.velocities),
+            ("rng", self.rng),
+            ("obstacles", self.obstacles),
+            ("progress", self.progress),
+        ]
+    }
+
+    pub fn combined(&self) -> u64 {
+        let mut hasher = StateHasher::default();
+        self.parts()
+            .iter()
+            .for_each(|(_name, part)| hasher.write_u64(*part));
+        hasher.finish()
+    }
+
+    // The low half of `combined`, as replays keep it.
+    pub fn short(&self) -> u32 {
+        self.combined() as u32
+    }
+
+    // The names of the parts that differ from `other`.
+    pub fn differences(&self, other: &WorldHash) -> Vec<&'static str> {
+        self.parts()
+            .iter()
+            .zip(other.parts())
+            .filter(|((_name, part), (_other_name, other_part))| part != other_part)
+            .map(|((name, _part), _other)| *name)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for WorldHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:08x}", self.short())
+    }
+}
+
+// A shadow copy of the simulation fed the same inputs as the live one, with
+// the two compared every `interval` frames. The shadow is dropped whenever
+// the live run jumps somewhere the inputs did not take it, and copied again
+// on the next frame.
+pub struct Audit<T> {
+    interval: u32,
+    shadow: Option<Box<T>>,
+}
+
+impl<T> Audit<T> {
+    pub fn new(interval: u32) -> Self {
+        Audit {
+            interval: interval.max(1),
+            shadow: None,
+        }
+    }
+
+    // From `?audit` or `?audit=<frames>`.
+    pub fn from_param(param: &str) -> Self {
+        Audit::new(param.parse().unwrap_or(DEFAULT_INTERVAL))
+    }
+
+    pub fn shadow_mut(&mut self) -> Option<&mut T> {
+        self.shadow.as_deref_mut()
+    }
+
+    pub fn follow(&mut self, shadow: T) {
+        self.shadow = Some(Box::new(shadow));
+    }
+
+    pub fn forget(&mut self) {
+        self.shadow = None;
+    }
+
+    pub fn due(&self, frame: u32) -> bool {
+        frame.is_multiple_of(self.interval)
+    }
+}
+
+// Panics on the first frame the two hashes differ, after logging which parts
+// differ and what was flagged since the last check as the likely cause.
+pub fn check(frame: u32, live: &WorldHash, shadow: &WorldHash) {
+    let flagged = take_flagged();
+    if live == shadow {
+        return;
+    }
+    let causes: Vec<&str> = flagged.iter().map(Source::name).collect();
+    log!(
+        "Determinism audit: the shadow run came apart by frame {} in {} (flagged: {})",
+        frame,
+        live.differences(shadow).join(", "),
+        if causes.is_empty() {
+            "nothing".to_string()
+        } else {
+            causes.join(", ")
+        }
+    );
+    assert_eq!(
+        live, shadow,
+        "Live and shadow runs differ at frame {}",
+        frame
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(values: &[i16]) -> u64 {
+        let mut hasher = StateHasher::default();
+        values.iter().for_each(|value| hasher.write_i16(*value));
+        hasher.finish()
+    }
+
+    #[test]
+    fn hashes_depend_on_every_value_and_their_order() {
+        assert_eq!(hash(&[1, 2, 3]), hash(&[1, 2, 3]));
+        assert_ne!(hash(&[1, 2, 3]), hash(&[1, 2, 4]));
+        assert_ne!(hash(&[1, 2, 3]), hash(&[3, 2, 1]));
+    }
+
+    #[test]
+    fn flags_are_cleared_once_taken() {
+        take_flagged();
+        flag(Source::FrameTime);
+        flag(Source::ThreadRng);
+        flag(Source::FrameTime);
+
+        assert_eq!(take_flagged(), vec![Source::ThreadRng, Source::FrameTime]);
+        assert!(take_flagged().is_empty());
+    }
+
+    #[test]
+    fn checks_fall_due_every_interval() {
+        let audit: Audit<()> = Audit::from_param("30");
+
+        assert!(audit.due(0));
+        assert!(!audit.due(29));
+        assert!(audit.due(60));
+        assert!(Audit::<()>::from_param("").due(DEFAULT_INTERVAL));
+    }
+
+    #[test]
+    fn matching_checks_clear_the_flags() {
+        let world = WorldHash::default();
+        flag(Source::ThreadRng);
+        check(10, &world, &world);
+
+        assert!(take_flagged().is_empty());
+    }
+
+    #[test]
+    fn world_hashes_name_the_parts_that_differ() {
+        let world = WorldHash {
+            positions: hash(&[1, 2]),
+            rng: 7,
+            ..WorldHash::default()
+        };
+        let desynced = WorldHash {
+            rng: 8,
+            obstacles: 1,
+            ..world
+        };
+
+        assert_eq!(world.differences(&world), Vec::<&str>::new());
+        assert_eq!(world.differences(&desynced), vec!["rng", "obstacles"]);
+        assert_ne!(world.combined(), desynced.combined());
+        assert_eq!(world.to_string().len(), 8);
+    }
+}
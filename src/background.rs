@@ -0,0 +1,286 @@
+use crate::critters::CritterKind;
+use crate::engine::{Color, Image, Point, Rect, Renderer};
+
+const WIDTH: i16 = 600;
+const HEIGHT: i16 = 600;
+
+const SKY_TOP: Color = Color::rgb(92, 160, 230);
+const SKY_BOTTOM: Color = Color::rgb(200, 230, 250);
+const CLOUD_COLOR: Color = Color::rgba(255, 255, 255, 0.85);
+const SILHOUETTE_COLOR: Color = Color::rgb(120, 150, 170);
+
+const CLOUD_COUNT: u32 = 4;
+const CLOUD_DRIFT: i16 = 1;
+const CLOUD_MIN_Y: i16 = 40;
+const CLOUD_RANGE_Y: i16 = 180;
+
+const SILHOUETTE_STEP: i16 = 20;
+const SILHOUETTE_BASE: i16 = 480;
+const SILHOUETTE_HEIGHT: f32 = 140.0;
+const SILHOUETTE_SCALE: f32 = 160.0;
+const SILHOUETTE_PARALLAX: i16 = 4;
+// The background moves this many times slower than the camera when it
+// follows the boy upwards.
+const VERTICAL_PARALLAX: i16 = 4;
+
+// Relative spawn weights of the ambient critters that suit each theme.
+const MEADOW_CRITTERS: [(CritterKind, u32); 2] =
+    [(CritterKind::Bird, 3), (CritterKind::Butterfly, 2)];
+const TOWN_CRITTERS: [(CritterKind, u32); 2] = [(CritterKind::Bird, 3), (CritterKind::Cat, 1)];
+
+#[derive(Clone)]
+pub enum Background {
+    Image([Image; 2]),
+    Procedural(ProceduralBackground),
+}
+
+impl Background {
+    pub fn update(&mut self, walking_speed: i16) {
+        match self {
+            Background::Image(backgrounds) => {
+                let [first_background, second_background] = backgrounds;
+                first_background.move_horizontally(walking_speed);
+                second_background.move_horizontally(walking_speed);
+
+                if first_background.right() < 0 {
+                    first_background.set_x(second_background.right());
+                }
+
+                if second_background.right() < 0 {
+                    second_background.set_x(first_background.right());
+                }
+
+                backgrounds.iter_mut().for_each(|background| {
+                    background.move_horizontally(walking_speed);
+                });
+            }
+            Background::Procedural(background) => background.update(walking_speed),
+        }
+    }
+
+    pub fn critter_kinds(&self) -> &'static [(CritterKind, u32)] {
+        match self {
+            Background::Image(_) => &MEADOW_CRITTERS,
+            Background::Procedural(_) => &TOWN_CRITTERS,
+        }
+    }
+
+    // Draws the background for a camera raised `camera_y` pixels (negative is
+    // up), filling the sky it uncovers above the scenery.
+    pub fn draw_from(&self, renderer: &Renderer, camera_y: i16) {
+        let shift = -camera_y / VERTICAL_PARALLAX;
+        if shift <= 0 {
+            self.draw(renderer);
+            return;
+        }
+        renderer.fill_rect(&Rect::new_from_x_y(0, 0, WIDTH, shift), &SKY_TOP);
+        renderer.draw_translated(&Point { x: 0, y: shift }, || self.draw(renderer));
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        match self {
+            Background::Image(backgrounds) => backgrounds.iter().for_each(|background| {
+                background.draw(renderer);
+            }),
+            Background::Procedural(background) => background.draw(renderer),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Cloud {
+    position: Point,
+    size: i16,
+}
+
+impl Cloud {
+    fn spawn(seed: u32, index: u32, x: i16) -> Self {
+        Cloud {
+            position: Point {
+                x,
+                y: CLOUD_MIN_Y + (hash(seed, index) * CLOUD_RANGE_Y as f32) as i16,
+            },
+            size: 20 + (hash(seed.wrapping_add(1), index) * 20.0) as i16,
+        }
+    }
+
+    fn right(&self) -> i16 {
+        self.position.x + self.size * 3
+    }
+
+    fn draw(&self, renderer: &Renderer) -> anyhow::Result<()> {
+        let Point { x, y } = self.position;
+        renderer.fill_circle(&Point { x, y }, self.size, &CLOUD_COLOR)?;
+        renderer.fill_circle(
+            &Point {
+                x: x + self.size,
+                y: y - self.size / 2,
+            },
+            self.size,
+            &CLOUD_COLOR,
+        )?;
+        renderer.fill_circle(
+            &Point {
+                x: x + self.size * 2,
+                y,
+            },
+            self.size,
+            &CLOUD_COLOR,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct ProceduralBackground {
+    seed: u32,
+    sky_top: Color,
+    sky_bottom: Color,
+    clouds: Vec<Cloud>,
+    clouds_spawned: u32,
+    scrolled: i32,
+}
+
+impl ProceduralBackground {
+    pub fn new(seed: u32) -> Self {
+        let clouds = (0..CLOUD_COUNT)
+            .map(|index| Cloud::spawn(seed, index, (index as i16) * (WIDTH / CLOUD_COUNT as i16)))
+            .collect();
+        ProceduralBackground {
+            seed,
+            sky_top: SKY_TOP,
+            sky_bottom: SKY_BOTTOM,
+            clouds,
+            clouds_spawned: CLOUD_COUNT,
+            scrolled: 0,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_sky(&mut self, top: Color, bottom: Color) {
+        self.sky_top = top;
+        self.sky_bottom = bottom;
+    }
+
+    fn update(&mut self, walking_speed: i16) {
+        self.scrolled -= walking_speed as i32;
+
+        let drift = walking_speed / SILHOUETTE_PARALLAX - CLOUD_DRIFT;
+        self.clouds
+            .iter_mut()
+            .for_each(|cloud| cloud.position.x += drift);
+
+        let (seed, spawned) = (self.seed, &mut self.clouds_spawned);
+        self.clouds.iter_mut().for_each(|cloud| {
+            if cloud.right() < 0 {
+                *cloud = Cloud::spawn(seed, *spawned, WIDTH);
+                *spawned += 1;
+            }
+        });
+    }
+
+    fn silhouette(&self) -> Vec<Point> {
+        let offset = self.scrolled / SILHOUETTE_PARALLAX as i32;
+        let mut points: Vec<Point> = (0..=WIDTH / SILHOUETTE_STEP)
+            .map(|step| {
+                let x = step * SILHOUETTE_STEP;
+                let height = noise(self.seed, (offset + x as i32) as f32 / SILHOUETTE_SCALE);
+                Point {
+                    x,
+                    y: SILHOUETTE_BASE - (height * SILHOUETTE_HEIGHT) as i16,
+                }
+            })
+            .collect();
+        points.push(Point {
+            x: WIDTH,
+            y: HEIGHT,
+        });
+        points.push(Point { x: 0, y: HEIGHT });
+        points
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        if let Err(err) = renderer.fill_vertical_gradient(
+            &Rect::new_from_x_y(0, 0, WIDTH, HEIGHT),
+            &self.sky_top,
+            &self.sky_bottom,
+        ) {
+            log!("Could not draw sky {:#?}", err);
+        }
+
+        if !renderer.detail().parallax {
+            return;
+        }
+        self.clouds.iter().for_each(|cloud| {
+            if let Err(err) = cloud.draw(renderer) {
+                log!("Could not draw cloud {:#?}", err);
+            }
+        });
+
+        renderer.fill_polygon(&self.silhouette(), &SILHOUETTE_COLOR);
+    }
+}
+
+// Deterministic pseudo random value in 0..1 for a lattice point.
+fn hash(seed: u32, index: u32) -> f32 {
+    let mut value = index.wrapping_mul(0x9E37_79B1) ^ seed.wrapping_mul(0x85EB_CA6B);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x2C1B_3C6D);
+    value ^= value >> 12;
+    (value & 0xFFFF) as f32 / 0xFFFF as f32
+}
+
+// Smoothly interpolated value noise in 0..1.
+fn noise(seed: u32, x: f32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+    let smooth = t * t * (3.0 - 2.0 * t);
+    let left = hash(seed, cell as i32 as u32);
+    let right = hash(seed, (cell as i32 + 1) as u32);
+    left + (right - left) * smooth
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_deterministic_and_bounded() {
+        (0..500).for_each(|step| {
+            let x = step as f32 * 0.37 - 50.0;
+            let value = noise(7, x);
+            assert!((0.0..=1.0).contains(&value));
+            assert_eq!(value, noise(7, x));
+        });
+    }
+
+    #[test]
+    fn noise_matches_lattice_values_at_integers() {
+        assert_eq!(noise(3, 4.0), hash(3, 4));
+        assert_eq!(noise(3, -2.0), hash(3, (-2i32) as u32));
+    }
+
+    #[test]
+    fn silhouette_spans_the_screen_and_scrolls() {
+        let mut background = ProceduralBackground::new(11);
+        let before = background.silhouette();
+        background.update(-40);
+        let after = background.silhouette();
+
+        assert_eq!(before.first().unwrap().x, 0);
+        assert_eq!(before[before.len() - 3].x, WIDTH);
+        assert_ne!(
+            before.iter().map(|point| point.y).collect::<Vec<i16>>(),
+            after.iter().map(|point| point.y).collect::<Vec<i16>>()
+        );
+    }
+
+    #[test]
+    fn clouds_respawn_on_the_right() {
+        let mut background = ProceduralBackground::new(5);
+        (0..400).for_each(|_| background.update(-3));
+
+        assert_eq!(background.clouds.len(), CLOUD_COUNT as usize);
+        assert!(background.clouds_spawned > CLOUD_COUNT);
+        assert!(background.clouds.iter().all(|cloud| cloud.right() >= 0));
+    }
+}
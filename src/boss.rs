@@ -0,0 +1,392 @@
+use crate::{
+    engine::{self, Color, Point, Rect, Renderer},
+    game::{DeathCause, RedHatBoy},
+    surfaces::Surface,
+    warnings::Threat,
+};
+
+const WIDTH: i16 = 600;
+const GROUND: i16 = 600;
+
+const MILESTONE_DISTANCE: i32 = 6000;
+
+const BOSS_WIDTH: i16 = 120;
+const BOSS_HEIGHT: i16 = 150;
+const BOSS_HEALTH: u8 = 3;
+const HOME_X: i16 = 440;
+// The boss walks in from this far past the edge, so there is time to warn
+// the player.
+const OFFSCREEN_LEAD: i16 = 120;
+const WALK_SPEED: i16 = 3;
+const CHARGE_SPEED: i16 = 5;
+const FALL_SPEED: i16 = 8;
+const ATTACK_COOLDOWN: u16 = 80;
+const HURT_FRAMES: u16 = 40;
+
+const PROJECTILE_SIZE: i16 = 24;
+const PROJECTILE_SPEED: i16 = 5;
+const SHOCKWAVE_WIDTH: i16 = 40;
+const SHOCKWAVE_HEIGHT: i16 = 30;
+const SHOCKWAVE_SPEED: i16 = 4;
+
+pub const REWARD_SHIELD_FRAMES: u16 = 300;
+
+const BOSS_COLOR: Color = Color::rgb(110, 60, 140);
+const HURT_COLOR: Color = Color::rgb(230, 120, 120);
+const HEAD_COLOR: Color = Color::rgb(240, 200, 60);
+const PROJECTILE_COLOR: Color = Color::rgb(240, 110, 40);
+const SHOCKWAVE_COLOR: Color = Color::rgba(200, 180, 140, 0.9);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Attack {
+    Throw,
+    Slam,
+    Charge,
+}
+
+const PATTERN: [Attack; 3] = [Attack::Throw, Attack::Slam, Attack::Charge];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BossState {
+    Entering,
+    Waiting(u16),
+    Charging,
+    Returning,
+    Hurt(u16),
+    Defeated,
+}
+
+#[derive(Clone)]
+struct Hazard {
+    bounding_box: Rect,
+    speed: i16,
+    color: Color,
+}
+
+#[derive(Clone)]
+struct Boss {
+    position: Point,
+    health: u8,
+    state: BossState,
+    next_attack: usize,
+    hazards: Vec<Hazard>,
+}
+
+impl Boss {
+    fn new() -> Self {
+        Boss {
+            position: Point {
+                x: WIDTH + OFFSCREEN_LEAD,
+                y: GROUND - BOSS_HEIGHT,
+            },
+            health: BOSS_HEALTH,
+            state: BossState::Entering,
+            next_attack: 0,
+            hazards: vec![],
+        }
+    }
+
+    fn bounding_box(&self) -> Rect {
+        Rect::new(self.position, BOSS_WIDTH, BOSS_HEIGHT)
+    }
+
+    // Each lost hit point speeds up the attacks, which gives the fight its phases.
+    fn phase(&self) -> i16 {
+        (BOSS_HEALTH - self.health) as i16
+    }
+
+    fn defeated(&self) -> bool {
+        self.state == BossState::Defeated
+    }
+
+    fn gone(&self) -> bool {
+        self.defeated() && self.position.y > GROUND
+    }
+
+    fn attack(&mut self) {
+        let attack = PATTERN[self.next_attack % PATTERN.len()];
+        self.next_attack += 1;
+        let speed_bonus = self.phase();
+
+        self.state = match attack {
+            Attack::Throw => {
+                self.hazards.push(Hazard {
+                    bounding_box: Rect::new_from_x_y(
+                        self.position.x - PROJECTILE_SIZE,
+                        GROUND - PROJECTILE_SIZE * 2,
+                        PROJECTILE_SIZE,
+                        PROJECTILE_SIZE,
+                    ),
+                    speed: PROJECTILE_SPEED + speed_bonus,
+                    color: PROJECTILE_COLOR,
+                });
+                BossState::Waiting(ATTACK_COOLDOWN)
+            }
+            Attack::Slam => {
+                self.hazards.push(Hazard {
+                    bounding_box: Rect::new_from_x_y(
+                        self.position.x - SHOCKWAVE_WIDTH,
+                        GROUND - SHOCKWAVE_HEIGHT,
+                        SHOCKWAVE_WIDTH,
+                        SHOCKWAVE_HEIGHT,
+                    ),
+                    speed: SHOCKWAVE_SPEED + speed_bonus,
+                    color: SHOCKWAVE_COLOR,
+                });
+                BossState::Waiting(ATTACK_COOLDOWN)
+            }
+            Attack::Charge => BossState::Charging,
+        };
+    }
+
+    fn update(&mut self) {
+        self.state = match self.state {
+            BossState::Entering | BossState::Returning => {
+                self.position.x = (self.position.x - WALK_SPEED).max(HOME_X);
+                if self.position.x == HOME_X {
+                    BossState::Waiting(ATTACK_COOLDOWN)
+                } else {
+                    self.state
+                }
+            }
+            BossState::Waiting(0) => {
+                self.attack();
+                self.state
+            }
+            BossState::Waiting(frames) => BossState::Waiting(frames - 1),
+            BossState::Charging | BossState::Hurt(_) => {
+                self.position.x -= CHARGE_SPEED + self.phase();
+                if self.position.x + BOSS_WIDTH < 0 {
+                    self.position.x = WIDTH + OFFSCREEN_LEAD;
+                    BossState::Returning
+                } else {
+                    match self.state {
+                        BossState::Hurt(frames) if frames > 0 => BossState::Hurt(frames - 1),
+                        _ => BossState::Charging,
+                    }
+                }
+            }
+            BossState::Defeated => {
+                self.position.y += FALL_SPEED;
+                BossState::Defeated
+            }
+        };
+
+        self.hazards.iter_mut().for_each(|hazard| {
+            let x = hazard.bounding_box.x();
+            hazard.bounding_box.set_x(x - hazard.speed);
+        });
+        self.hazards
+            .retain(|hazard| hazard.bounding_box.right() > 0 && hazard.bounding_box.x() < WIDTH);
+    }
+
+    // The boss itself, while it walks back in from off screen.
+    fn threat(&self) -> Option<Threat> {
+        match self.state {
+            BossState::Entering | BossState::Returning if self.position.x >= WIDTH => {
+                Some(Threat {
+                    bounding_box: self.bounding_box(),
+                    speed: WALK_SPEED,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // Returns true if the boy landed on the boss's head.
+    fn check_intersection(&mut self, boy: &mut RedHatBoy) -> bool {
+        let boy_box = boy.bounding_box();
+
+        if self
+            .hazards
+            .iter()
+            .any(|hazard| boy_box.intersects(&hazard.bounding_box))
+        {
+            boy.knock_out(DeathCause::Boss);
+        }
+
+        let tangible = !matches!(self.state, BossState::Hurt(_) | BossState::Defeated);
+        if tangible && boy_box.intersects(&self.bounding_box()) {
+            if boy.velocity_y() > 0 && boy.pos_y() < self.position.y {
+                boy.land_on(self.position.y, Surface::Normal);
+                boy.jump();
+                self.health -= 1;
+                self.state = if self.health == 0 {
+                    self.hazards.clear();
+                    BossState::Defeated
+                } else {
+                    BossState::Hurt(HURT_FRAMES)
+                };
+                return true;
+            } else {
+                boy.knock_out(DeathCause::Boss);
+            }
+        }
+        false
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let color = match self.state {
+            BossState::Hurt(frames) if frames % 8 < 4 => HURT_COLOR,
+            _ => BOSS_COLOR,
+        };
+        engine::draw_shadow(renderer, &self.bounding_box(), GROUND);
+        renderer.fill_rect(&self.bounding_box(), &color);
+        (0..self.health as i16).for_each(|point| {
+            renderer.fill_rect(
+                &Rect::new_from_x_y(
+                    self.position.x + 20 + point * 30,
+                    self.position.y - 14,
+                    20,
+                    10,
+                ),
+                &HEAD_COLOR,
+            );
+        });
+        self.hazards.iter().for_each(|hazard| {
+            renderer.fill_rect(&hazard.bounding_box, &hazard.color);
+        });
+    }
+}
+
+#[derive(Clone)]
+pub struct BossEncounter {
+    interval: i32,
+    next_milestone: i32,
+    boss: Option<Boss>,
+    stomped: bool,
+}
+
+impl BossEncounter {
+    pub fn new() -> Self {
+        BossEncounter::with_interval(MILESTONE_DISTANCE)
+    }
+
+    // A boss appears every `interval` pixels walked.
+    pub fn with_interval(interval: i32) -> Self {
+        BossEncounter {
+            interval,
+            next_milestone: interval,
+            boss: None,
+            stomped: false,
+        }
+    }
+
+    pub fn scroll_locked(&self) -> bool {
+        self.boss.as_ref().map_or(false, |boss| !boss.defeated())
+    }
+
+    // Returns true on the frame the boss is beaten so the caller can hand out the reward.
+    pub fn update(&mut self, distance: i32, boy: &mut RedHatBoy) -> bool {
+        if self.boss.is_none() && distance >= self.next_milestone {
+            self.boss = Some(Boss::new());
+            self.next_milestone += self.interval;
+        }
+
+        let mut beaten = false;
+        self.stomped = false;
+        if let Some(boss) = &mut self.boss {
+            let was_defeated = boss.defeated();
+            boss.update();
+            self.stomped = boss.check_intersection(boy);
+            beaten = !was_defeated && boss.defeated();
+        }
+
+        if self.boss.as_ref().map_or(false, Boss::gone) {
+            self.boss = None;
+        }
+        beaten
+    }
+
+    // Whether the boy landed on the boss this frame.
+    pub fn stomped(&self) -> bool {
+        self.stomped
+    }
+
+    pub fn threats(&self) -> Vec<Threat> {
+        self.boss.iter().filter_map(Boss::threat).collect()
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        if let Some(boss) = &self.boss {
+            boss.draw(renderer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waiting_boss() -> Boss {
+        let mut boss = Boss::new();
+        boss.position.x = HOME_X;
+        boss.state = BossState::Waiting(0);
+        boss
+    }
+
+    #[test]
+    fn boss_walks_in_and_waits() {
+        let mut boss = Boss::new();
+        (0..100).for_each(|_| boss.update());
+
+        assert_eq!(boss.position.x, HOME_X);
+        assert!(matches!(boss.state, BossState::Waiting(_)));
+    }
+
+    #[test]
+    fn attacks_follow_the_pattern() {
+        let mut boss = waiting_boss();
+        boss.update();
+        assert_eq!(boss.hazards.len(), 1);
+        assert_eq!(boss.hazards[0].bounding_box.height, PROJECTILE_SIZE);
+
+        boss.state = BossState::Waiting(0);
+        boss.update();
+        assert_eq!(boss.hazards[1].bounding_box.height, SHOCKWAVE_HEIGHT);
+
+        boss.state = BossState::Waiting(0);
+        boss.update();
+        assert_eq!(boss.state, BossState::Charging);
+    }
+
+    #[test]
+    fn charging_boss_loops_back_home() {
+        let mut boss = waiting_boss();
+        boss.next_attack = 2;
+        (0..400).for_each(|_| boss.update());
+
+        assert_eq!(boss.position.x, HOME_X);
+    }
+
+    #[test]
+    fn boss_is_a_threat_until_it_comes_into_view() {
+        let mut boss = Boss::new();
+        assert!(boss.threat().is_some());
+
+        (0..OFFSCREEN_LEAD / WALK_SPEED + 1).for_each(|_| boss.update());
+        assert!(boss.threat().is_none());
+    }
+
+    #[test]
+    fn later_phases_are_faster() {
+        let mut boss = waiting_boss();
+        boss.health = 1;
+        boss.update();
+
+        assert_eq!(boss.hazards[0].speed, PROJECTILE_SPEED + 2);
+    }
+
+    #[test]
+    fn encounters_start_at_milestones() {
+        let mut encounter = BossEncounter::new();
+        assert!(!encounter.scroll_locked());
+
+        encounter.next_milestone = 0;
+        encounter.boss = Some(Boss::new());
+        assert!(encounter.scroll_locked());
+
+        encounter.boss.as_mut().unwrap().state = BossState::Defeated;
+        assert!(!encounter.scroll_locked());
+    }
+}
@@ -1,16 +1,21 @@
+use std::{any::Any, cell::RefCell, collections::HashMap};
+
 use anyhow::{anyhow, Result};
 use futures::Future;
 use js_sys::ArrayBuffer;
+use serde::{de::DeserializeOwned, Serialize};
 use wasm_bindgen::{
     closure::{WasmClosure, WasmClosureFnOnce},
     JsCast, JsValue,
 };
 use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    CanvasRenderingContext2d, Document, Element, HtmlCanvasElement, HtmlElement, HtmlImageElement,
-    Response, Window,
+    BatteryManager, CanvasRenderingContext2d, Document, Element, EventTarget, Gamepad,
+    HtmlCanvasElement, HtmlElement, HtmlImageElement, HtmlInputElement, HtmlMediaElement,
+    HtmlTextAreaElement, Node, Request, Response, Storage, UrlSearchParams, Window,
 };
 
+use crate::engine::memory::{self, Tracked};
 use wasm_bindgen::prelude::*;
 
 macro_rules! log {
@@ -19,6 +24,10 @@ macro_rules! log {
     }
 }
 
+pub mod asset_config;
+pub mod idb;
+pub mod socket;
+
 pub fn window() -> Result<Window> {
     web_sys::window().ok_or_else(|| anyhow!("No Window Found"))
 }
@@ -29,16 +38,52 @@ pub fn document() -> Result<Document> {
         .ok_or_else(|| anyhow!("No Document Found"))
 }
 
-pub fn canvas() -> Result<HtmlCanvasElement> {
+pub const DEFAULT_CANVAS_ID: &str = "canvas";
+pub const DEFAULT_UI_ID: &str = "ui";
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Target {
+    pub canvas_id: String,
+    pub ui_id: String,
+}
+
+impl Target {
+    pub fn new(canvas_id: &str, ui_id: &str) -> Self {
+        Target {
+            canvas_id: canvas_id.into(),
+            ui_id: ui_id.into(),
+        }
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::new(DEFAULT_CANVAS_ID, DEFAULT_UI_ID)
+    }
+}
+
+pub fn canvas(id: &str) -> Result<HtmlCanvasElement> {
     document()?
-        .get_element_by_id("canvas")
-        .ok_or_else(|| anyhow!("No canvas Element found with ID 'Canvas'"))?
+        .get_element_by_id(id)
+        .ok_or_else(|| anyhow!("No canvas Element found with ID '{}'", id))?
         .dyn_into::<web_sys::HtmlCanvasElement>()
         .map_err(|element| anyhow!("Error conversting {:#?} to HtmlCanvasElement", element))
 }
 
-pub fn context() -> Result<CanvasRenderingContext2d> {
-    canvas()?
+// A canvas that is never added to the page, for drawing off screen.
+pub fn new_canvas(width: u32, height: u32) -> Result<HtmlCanvasElement> {
+    let canvas = document()?
+        .create_element("canvas")
+        .map_err(|err| anyhow!("Could not create canvas {:#?}", err))?
+        .dyn_into::<HtmlCanvasElement>()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlCanvasElement", element))?;
+    canvas.set_width(width);
+    canvas.set_height(height);
+    Ok(canvas)
+}
+
+pub fn context(canvas: &HtmlCanvasElement) -> Result<CanvasRenderingContext2d> {
+    canvas
         .get_context("2d")
         .map_err(|js_value| {
             anyhow!(
@@ -78,8 +123,28 @@ pub async fn fetch_response(resource: &str) -> Result<Response> {
         .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))
 }
 
+pub async fn fetch_request(request: &Request) -> Result<Response> {
+    JsFuture::from(window()?.fetch_with_request(request))
+        .await
+        .map_err(|err| anyhow!("error fetching {:#?}", err))?
+        .dyn_into()
+        .map_err(|err| anyhow!("error converting fetch to Response {:#?}", err))
+}
+
+pub async fn response_text(response: &Response) -> Result<String> {
+    JsFuture::from(
+        response
+            .text()
+            .map_err(|err| anyhow!("Could not get text from response {:#?}", err))?,
+    )
+    .await
+    .map_err(|err| anyhow!("error reading response text {:#?}", err))?
+    .as_string()
+    .ok_or_else(|| anyhow!("Response text was not a string"))
+}
+
 pub async fn fetch_array_buffer(resource: &str) -> Result<ArrayBuffer> {
-    let array_buffer = fetch_response(resource)
+    let array_buffer = fetch_response(&asset_config::url(resource))
         .await?
         .array_buffer()
         .map_err(|err| anyhow!("Error loading array buffer {:#?}", err))?;
@@ -92,7 +157,7 @@ pub async fn fetch_array_buffer(resource: &str) -> Result<ArrayBuffer> {
 }
 
 pub async fn fetch_json(json_path: &str) -> Result<JsValue> {
-    let resp_value = fetch_response(json_path).await?;
+    let resp_value = fetch_response(&asset_config::url(json_path)).await?;
     let resp: Response = resp_value
         .dyn_into()
         .map_err(|element| anyhow!("Error converting {:#?} to Response", element))?;
@@ -122,14 +187,162 @@ pub fn request_animation_frame(callback: &LoopClosure) -> Result<i32> {
         .map_err(|err| anyhow!("Cannot request animation frame {:#?}", err))
 }
 
+pub async fn next_animation_frame() -> Result<f64> {
+    let (frame_tx, frame_rx) = futures::channel::oneshot::channel();
+    let callback: LoopClosure = closure_once(move |perf: f64| {
+        frame_tx.send(perf).ok();
+    });
+    request_animation_frame(&callback)?;
+    frame_rx
+        .await
+        .map_err(|err| anyhow!("Animation frame was cancelled {:#?}", err))
+}
+
+pub fn has_animation_frame() -> Result<bool> {
+    js_sys::Reflect::has(
+        &window()?.into(),
+        &JsValue::from_str("requestAnimationFrame"),
+    )
+    .map_err(|err| anyhow!("Could not check for requestAnimationFrame {:#?}", err))
+}
+
+pub fn set_interval(callback: &Closure<dyn FnMut()>, timeout: i32) -> Result<i32> {
+    window()?
+        .set_interval_with_callback_and_timeout_and_arguments_0(
+            callback.as_ref().unchecked_ref(),
+            timeout,
+        )
+        .map_err(|err| anyhow!("Cannot set interval {:#?}", err))
+}
+
+pub fn clear_interval(id: i32) {
+    if let Ok(window) = window() {
+        window.clear_interval_with_handle(id);
+    }
+}
+
 pub fn create_raf_closure(f: impl FnMut(f64) + 'static) -> LoopClosure {
     closure_wrap(Box::new(f))
 }
 
 pub fn closure_wrap<T: WasmClosure + ?Sized>(data: Box<T>) -> Closure<T> {
+    memory::add(Tracked::Closures, 1);
     Closure::wrap(data)
 }
 
+struct Listener {
+    target: EventTarget,
+    event: &'static str,
+    callback: js_sys::Function,
+    // Only held so the callback lives as long as it listens.
+    _closure: Box<dyn Any>,
+}
+
+impl Listener {
+    fn take_off(self) {
+        if let Err(err) = self
+            .target
+            .remove_event_listener_with_callback(self.event, &self.callback)
+        {
+            log!("Could not remove {} listener {:#?}", self.event, err);
+        }
+        memory::subtract(Tracked::Closures, 1);
+    }
+}
+
+// Owns the closures behind the listeners it adds instead of forgetting them,
+// so they can be taken off again. Dropping the registry takes off every
+// listener still in it, so a game started again does not hear events twice.
+#[derive(Default)]
+pub struct EventListenerRegistry {
+    listeners: Vec<Listener>,
+}
+
+impl EventListenerRegistry {
+    pub fn add<T: WasmClosure + ?Sized + 'static>(
+        &mut self,
+        target: &EventTarget,
+        event: &'static str,
+        closure: Closure<T>,
+    ) -> Result<()> {
+        let callback = closure.as_ref().unchecked_ref::<js_sys::Function>().clone();
+        target
+            .add_event_listener_with_callback(event, &callback)
+            .map_err(|err| anyhow!("Could not add {} listener {:#?}", event, err))?;
+        self.listeners.push(Listener {
+            target: target.clone(),
+            event,
+            callback,
+            _closure: Box::new(closure),
+        });
+        Ok(())
+    }
+
+    // Takes off the listeners on `node` and anything inside it, as it leaves
+    // the page.
+    pub fn remove_within(&mut self, node: &Node) {
+        let (inside, kept): (Vec<Listener>, Vec<Listener>) = std::mem::take(&mut self.listeners)
+            .into_iter()
+            .partition(|listener| node.contains(listener.target.dyn_ref::<Node>()));
+        self.listeners = kept;
+        inside.into_iter().for_each(Listener::take_off);
+    }
+}
+
+impl Drop for EventListenerRegistry {
+    fn drop(&mut self) {
+        self.listeners.drain(..).for_each(Listener::take_off);
+    }
+}
+
+thread_local! {
+    // Listeners each instance adds to the page outside its game loop, such as
+    // on UI buttons. Those in the UI are taken off as `hide_ui` removes them,
+    // and the rest when the instance is released.
+    static PAGE_LISTENERS: RefCell<HashMap<Target, EventListenerRegistry>> =
+        RefCell::new(HashMap::new());
+}
+
+pub fn add_page_listener<T: WasmClosure + ?Sized + 'static>(
+    target: &Target,
+    event_target: &EventTarget,
+    event: &'static str,
+    closure: Closure<T>,
+) -> Result<()> {
+    PAGE_LISTENERS.with(|listeners| {
+        listeners
+            .borrow_mut()
+            .entry(target.clone())
+            .or_default()
+            .add(event_target, event, closure)
+    })
+}
+
+// Takes off every listener the instance on `target` added to the page, along
+// with its live region, whose focus listener is one of them.
+pub fn release(target: &Target) {
+    PAGE_LISTENERS.with(|listeners| listeners.borrow_mut().remove(target));
+    if let Some(region) = document()
+        .ok()
+        .and_then(|doc| doc.get_element_by_id(&live_region_id(target)))
+    {
+        region.remove();
+    }
+}
+
+// Calls `on_change` with whether the page is now hidden, as when switching
+// to another tab, which also stops the game loop.
+pub fn on_visibility_change(
+    target: &Target,
+    mut on_change: impl FnMut(bool) + 'static,
+) -> Result<()> {
+    let document = document()?;
+    let changed = document.clone();
+    let on_visibility =
+        closure_wrap(Box::new(move || on_change(changed.hidden())) as Box<dyn FnMut()>);
+    add_page_listener(target, &document, "visibilitychange", on_visibility)
+}
+
 pub fn now() -> Result<f64> {
     Ok(window()?
         .performance()
@@ -137,20 +350,276 @@ pub fn now() -> Result<f64> {
         .now())
 }
 
-pub fn draw_ui(html: &str) -> Result<()> {
-    find_ui()?
+// How big the wasm heap has grown. It never shrinks.
+pub fn memory_bytes() -> Result<u32> {
+    let memory = wasm_bindgen::memory()
+        .dyn_into::<js_sys::WebAssembly::Memory>()
+        .map_err(|err| anyhow!("Could not find wasm memory {:#?}", err))?;
+    memory
+        .buffer()
+        .dyn_into::<ArrayBuffer>()
+        .map(|buffer| buffer.byte_length())
+        .map_err(|err| anyhow!("Wasm memory had no buffer {:#?}", err))
+}
+
+// 1 for January, by the player's own clock.
+pub fn month() -> u32 {
+    js_sys::Date::new_0().get_month() + 1
+}
+
+pub fn query_param(name: &str) -> Result<Option<String>> {
+    let search = window()?
+        .location()
+        .search()
+        .map_err(|err| anyhow!("Could not read location search {:#?}", err))?;
+    let params = UrlSearchParams::new_with_str(&search)
+        .map_err(|err| anyhow!("Could not parse query string {:#?}", err))?;
+    Ok(params.get(name))
+}
+
+// The page's size in CSS pixels.
+pub fn viewport_size() -> Result<(f64, f64)> {
+    let window = window()?;
+    let dimension = |value: std::result::Result<JsValue, JsValue>, name: &str| {
+        value
+            .map_err(|err| anyhow!("Could not read {} {:#?}", name, err))?
+            .as_f64()
+            .ok_or_else(|| anyhow!("{} is not a number", name))
+    };
+    Ok((
+        dimension(window.inner_width(), "innerWidth")?,
+        dimension(window.inner_height(), "innerHeight")?,
+    ))
+}
+
+// The `env(safe-area-inset-*)` values, in CSS pixels, as (top, right, bottom,
+// left). They can only be read through a style, so a hidden element is padded
+// with them and measured.
+pub fn safe_area_insets() -> Result<[f64; 4]> {
+    let document = document()?;
+    let body = document
+        .body()
+        .ok_or_else(|| anyhow!("No body to measure in"))?;
+    let probe = document
+        .create_element("div")
+        .map_err(|err| anyhow!("Could not create safe area probe {:#?}", err))?;
+    probe
+        .set_attribute(
+            "style",
+            "position: fixed; visibility: hidden; padding: env(safe-area-inset-top) env(safe-area-inset-right) env(safe-area-inset-bottom) env(safe-area-inset-left)",
+        )
+        .map_err(|err| anyhow!("Could not style safe area probe {:#?}", err))?;
+    body.append_child(&probe)
+        .map_err(|err| anyhow!("Could not add safe area probe {:#?}", err))?;
+    let style = window()?
+        .get_computed_style(&probe)
+        .map_err(|err| anyhow!("Could not read safe area probe {:#?}", err))?
+        .ok_or_else(|| anyhow!("Safe area probe has no style"));
+    probe.remove();
+    let style = style?;
+    let inset = |side: &str| -> Result<f64> {
+        let value = style
+            .get_property_value(&format!("padding-{}", side))
+            .map_err(|err| anyhow!("Could not read {} safe area {:#?}", side, err))?;
+        Ok(value.trim_end_matches("px").parse().unwrap_or(0.0))
+    };
+    Ok([
+        inset("top")?,
+        inset("right")?,
+        inset("bottom")?,
+        inset("left")?,
+    ])
+}
+
+pub struct Connection {
+    pub effective_type: Option<String>,
+    pub save_data: bool,
+}
+
+// `navigator.connection` is missing in some browsers, so it is read reflectively.
+pub fn connection() -> Result<Option<Connection>> {
+    let connection = js_sys::Reflect::get(&window()?.navigator(), &"connection".into())
+        .map_err(|err| anyhow!("Could not read navigator.connection {:#?}", err))?;
+    if connection.is_undefined() || connection.is_null() {
+        return Ok(None);
+    }
+    let field = |name: &str| {
+        js_sys::Reflect::get(&connection, &name.into())
+            .map_err(|err| anyhow!("Could not read connection {} {:#?}", name, err))
+    };
+    Ok(Some(Connection {
+        effective_type: field("effectiveType")?.as_string(),
+        save_data: field("saveData")?.as_bool().unwrap_or(false),
+    }))
+}
+
+// Connected gamepads. The list has holes where pads were unplugged.
+pub fn gamepads() -> Result<Vec<Gamepad>> {
+    let pads = window()?
+        .navigator()
+        .get_gamepads()
+        .map_err(|err| anyhow!("Could not read gamepads {:#?}", err))?;
+    Ok(pads
+        .iter()
+        .filter_map(|pad| pad.dyn_into::<Gamepad>().ok())
+        .filter(|pad| pad.connected())
+        .collect())
+}
+
+// `getBattery` is missing from web-sys's `Navigator`, so it is called
+// reflectively. Browsers without the Battery Status API give `None`.
+pub async fn battery() -> Result<Option<BatteryManager>> {
+    let navigator = window()?.navigator();
+    let get_battery = js_sys::Reflect::get(&navigator, &"getBattery".into())
+        .map_err(|err| anyhow!("Could not read getBattery {:#?}", err))?;
+    let get_battery = match get_battery.dyn_into::<js_sys::Function>() {
+        Ok(get_battery) => get_battery,
+        Err(_value) => return Ok(None),
+    };
+    let promise: js_sys::Promise = get_battery
+        .call0(&navigator)
+        .map_err(|err| anyhow!("Could not call getBattery {:#?}", err))?
+        .dyn_into()
+        .map_err(|value| anyhow!("getBattery did not return a promise {:#?}", value))?;
+    let battery = JsFuture::from(promise)
+        .await
+        .map_err(|err| anyhow!("Could not get the battery {:#?}", err))?;
+    Ok(Some(battery.unchecked_into()))
+}
+
+// `vibrationActuator` is missing from web-sys, so the rumble is played
+// reflectively. Pads without one are left alone.
+pub fn dual_rumble(gamepad: &Gamepad, duration: f64, strong: f64, weak: f64) -> Result<()> {
+    let actuator = js_sys::Reflect::get(gamepad, &"vibrationActuator".into())
+        .map_err(|err| anyhow!("Could not read vibrationActuator {:#?}", err))?;
+    if actuator.is_undefined() || actuator.is_null() {
+        return Ok(());
+    }
+    let play_effect: js_sys::Function = js_sys::Reflect::get(&actuator, &"playEffect".into())
+        .map_err(|err| anyhow!("Could not read playEffect {:#?}", err))?
+        .dyn_into()
+        .map_err(|value| anyhow!("playEffect is not a function {:#?}", value))?;
+    let params = js_sys::Object::new();
+    [
+        ("duration", duration),
+        ("strongMagnitude", strong),
+        ("weakMagnitude", weak),
+    ]
+    .iter()
+    .try_for_each(|(name, value)| {
+        js_sys::Reflect::set(&params, &(*name).into(), &(*value).into())
+            .map(|_set| ())
+            .map_err(|err| anyhow!("Could not set rumble {} {:#?}", name, err))
+    })?;
+    play_effect
+        .call2(&actuator, &"dual-rumble".into(), &params)
+        .map_err(|err| anyhow!("Could not play rumble {:#?}", err))?;
+    Ok(())
+}
+
+// Saves `contents` as a file through a link to it, as if the player had
+// clicked it.
+#[cfg(debug_assertions)]
+pub fn download(file_name: &str, mime_type: &str, contents: &str) -> Result<()> {
+    let link: HtmlElement = document()?
+        .create_element("a")
+        .map_err(|err| anyhow!("Could not create link {:#?}", err))?
+        .dyn_into()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlElement", element))?;
+    let href = format!(
+        "data:{};charset=utf-8,{}",
+        mime_type,
+        js_sys::encode_uri_component(contents)
+    );
+    link.set_attribute("href", &href)
+        .and_then(|_unit| link.set_attribute("download", file_name))
+        .map_err(|err| anyhow!("Could not point link at {} {:#?}", file_name, err))?;
+    link.click();
+    Ok(())
+}
+
+pub fn can_play_type(mime_type: &str) -> Result<bool> {
+    let audio: HtmlMediaElement = document()?
+        .create_element("audio")
+        .map_err(|err| anyhow!("Could not create audio element {:#?}", err))?
+        .dyn_into()
+        .map_err(|element| anyhow!("Error converting {:#?} to HtmlMediaElement", element))?;
+    Ok(!audio.can_play_type(mime_type).is_empty())
+}
+
+fn local_storage() -> Result<Storage> {
+    window()?
+        .local_storage()
+        .map_err(|err| anyhow!("Could not access local storage {:#?}", err))?
+        .ok_or_else(|| anyhow!("No local storage found"))
+}
+
+pub fn load_item(key: &str) -> Result<Option<String>> {
+    local_storage()?
+        .get_item(key)
+        .map_err(|err| anyhow!("Could not read {} from local storage {:#?}", key, err))
+}
+
+pub fn save_item(key: &str, value: &str) -> Result<()> {
+    local_storage()?
+        .set_item(key, value)
+        .map_err(|err| anyhow!("Could not write {} to local storage {:#?}", key, err))
+}
+
+pub fn to_json<T: Serialize>(value: &T) -> Result<String> {
+    let value =
+        JsValue::from_serde(value).map_err(|err| anyhow!("Could not serialize {:#?}", err))?;
+    Ok(js_sys::JSON::stringify(&value)
+        .map_err(|err| anyhow!("Could not stringify {:#?}", err))?
+        .into())
+}
+
+pub fn from_json<T: DeserializeOwned>(json: &str) -> Result<T> {
+    js_sys::JSON::parse(json)
+        .map_err(|err| anyhow!("Could not parse JSON {:#?}", err))?
+        .into_serde::<T>()
+        .map_err(|err| anyhow!("Could not read JSON {:#?}", err))
+}
+
+pub fn load_json<T: DeserializeOwned>(key: &str) -> Result<Option<T>> {
+    load_item(key)?.map(|json| from_json(&json)).transpose()
+}
+
+pub fn save_json<T: Serialize>(key: &str, value: &T) -> Result<()> {
+    save_item(key, &to_json(value)?)
+}
+
+pub fn find_ui_text_area(target: &Target, selector: &str) -> Result<HtmlTextAreaElement> {
+    find_ui_element(target, selector)?
+        .dyn_into::<HtmlTextAreaElement>()
+        .map_err(|err| anyhow!("Could not cast into HtmlTextAreaElement {:#?}", err))
+}
+
+pub fn find_ui_input(target: &Target, selector: &str) -> Result<HtmlInputElement> {
+    find_ui_element(target, selector)?
+        .dyn_into::<HtmlInputElement>()
+        .map_err(|err| anyhow!("Could not cast into HtmlInputElement {:#?}", err))
+}
+
+pub fn draw_ui(target: &Target, html: &str) -> Result<()> {
+    find_ui(target)?
         .insert_adjacent_html("afterbegin", html)
         .map_err(|err| anyhow!("Could not insert html {:#?}", err))
 }
 
-pub fn hide_ui() -> Result<()> {
-    let ui = find_ui()?;
+pub fn hide_ui(target: &Target) -> Result<()> {
+    let ui = find_ui(target)?;
     if let Some(child) = ui.first_child() {
+        PAGE_LISTENERS.with(|listeners| {
+            if let Some(listeners) = listeners.borrow_mut().get_mut(target) {
+                listeners.remove_within(&child);
+            }
+        });
         ui.remove_child(&child)
             .map(|_removed_child| ())
             .map_err(|err| anyhow!("Failed to remove child {:#?}", err))
             .and_then(|_unit| {
-                canvas()?
+                canvas(&target.canvas_id)?
                     .focus()
                     .map_err(|err| anyhow!("Could not set focus to canvas! {:#?}", err))
             })
@@ -159,24 +628,102 @@ pub fn hide_ui() -> Result<()> {
     }
 }
 
-fn find_ui() -> Result<Element> {
+pub fn find_ui(target: &Target) -> Result<Element> {
     document().and_then(|doc| {
-        doc.get_element_by_id("ui")
+        doc.get_element_by_id(&target.ui_id)
             .ok_or_else(|| anyhow!("UI element not found"))
     })
 }
 
-pub fn find_html_element_by_id(id: &str) -> Result<HtmlElement> {
-    document()
-        .and_then(|doc| {
-            doc.get_element_by_id(id)
-                .ok_or_else(|| anyhow!("Element with id {} not found", id))
-        })
-        .and_then(|element| {
-            element
-                .dyn_into::<HtmlElement>()
-                .map_err(|err| anyhow!("Could not cast into HtmlElement {:#?}", err))
-        })
+// Kept on screen for screen readers but out of sight and out of the way.
+const LIVE_REGION_STYLE: [(&str, &str); 6] = [
+    ("position", "absolute"),
+    ("width", "1px"),
+    ("height", "1px"),
+    ("overflow", "hidden"),
+    ("clip", "rect(0 0 0 0)"),
+    ("white-space", "nowrap"),
+];
+
+// Screen readers read out whatever is put in here. Repeating a message
+// changes it slightly, as they skip text that has not changed.
+pub fn announce(target: &Target, message: &str) -> Result<()> {
+    let region = find_live_region(target)?;
+    if region.text_content().as_deref() == Some(message) {
+        region.set_text_content(Some(&format!("{}\u{a0}", message)));
+    } else {
+        region.set_text_content(Some(message));
+    }
+    Ok(())
+}
+
+// Made next to the UI on first use, when it also starts reading out each
+// button as it gets focus.
+fn find_live_region(target: &Target) -> Result<HtmlElement> {
+    let doc = document()?;
+    let id = live_region_id(target);
+    if let Some(region) = doc.get_element_by_id(&id) {
+        return region
+            .dyn_into::<HtmlElement>()
+            .map_err(|err| anyhow!("Could not cast into HtmlElement {:#?}", err));
+    }
+    let region = doc
+        .create_element("div")
+        .map_err(|err| anyhow!("Could not create live region {:#?}", err))?
+        .dyn_into::<HtmlElement>()
+        .map_err(|err| anyhow!("Could not cast into HtmlElement {:#?}", err))?;
+    region.set_id(&id);
+    [
+        ("role", "status"),
+        ("aria-live", "polite"),
+        ("aria-atomic", "true"),
+    ]
+    .iter()
+    .try_for_each(|(name, value)| region.set_attribute(name, value))
+    .map_err(|err| anyhow!("Could not set live region attribute {:#?}", err))?;
+    LIVE_REGION_STYLE
+        .iter()
+        .try_for_each(|(name, value)| region.style().set_property(name, value))
+        .map_err(|err| anyhow!("Could not style live region {:#?}", err))?;
+    let ui = find_ui(target)?;
+    ui.after_with_node_1(&region)
+        .map_err(|err| anyhow!("Could not add live region {:#?}", err))?;
+
+    let focused_target = target.clone();
+    let on_focus = closure_wrap(Box::new(move |event: web_sys::Event| {
+        let button = event
+            .target()
+            .and_then(|element| element.dyn_into::<HtmlElement>().ok())
+            .filter(|element| element.tag_name() == "BUTTON");
+        if let Some(button) = button {
+            if let Err(err) = announce(&focused_target, &button.inner_text()) {
+                log!("Could not announce focused button {:#?}", err);
+            }
+        }
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    add_page_listener(target, &ui, "focusin", on_focus)?;
+    Ok(region)
+}
+
+fn live_region_id(target: &Target) -> String {
+    format!("{}_live", target.ui_id)
+}
+
+// Puts keyboard focus on the first button, so a menu can be worked from the
+// keyboard as soon as it is shown.
+pub fn focus_ui(target: &Target) -> Result<()> {
+    find_ui_element(target, "button")?
+        .focus()
+        .map_err(|err| anyhow!("Could not focus UI {:#?}", err))
+}
+
+pub fn find_ui_element(target: &Target, selector: &str) -> Result<HtmlElement> {
+    find_ui(target)?
+        .query_selector(selector)
+        .map_err(|err| anyhow!("Invalid selector {} {:#?}", selector, err))?
+        .ok_or_else(|| anyhow!("Element matching {} not found in UI", selector))?
+        .dyn_into::<HtmlElement>()
+        .map_err(|err| anyhow!("Could not cast into HtmlElement {:#?}", err))
 }
 
 #[cfg(test)]
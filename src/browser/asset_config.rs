@@ -0,0 +1,135 @@
+use std::cell::RefCell;
+
+use super::query_param;
+
+// Prefixes of paths that already say where they live.
+const ABSOLUTE_PREFIXES: [&str; 5] = ["data:", "blob:", "http://", "https://", "//"];
+
+// Where assets are fetched from. Paths are resolved against `base_url`, so the
+// game can be hosted under a subpath or load its assets from a CDN, and a
+// `cache_bust` version is added to every request so a new release is not
+// served from an old cache.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssetConfig {
+    pub base_url: String,
+    pub cache_bust: Option<String>,
+}
+
+thread_local! {
+    static CONFIG: RefCell<Option<AssetConfig>> = RefCell::new(None);
+}
+
+impl AssetConfig {
+    // `?asset_base=<url>` and `?cache_bust=<version>`, when the embedding page
+    // has not set a config of its own.
+    fn from_browser() -> Self {
+        let param = |name| {
+            query_param(name).unwrap_or_else(|err| {
+                log!("Could not read {} {:#?}", name, err);
+                None
+            })
+        };
+        AssetConfig {
+            base_url: param("asset_base").unwrap_or_default(),
+            cache_bust: param("cache_bust"),
+        }
+    }
+
+    pub fn resolve(&self, path: &str) -> String {
+        if ABSOLUTE_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            return path.to_string();
+        }
+
+        let url = if self.base_url.is_empty() {
+            path.to_string()
+        } else {
+            format!(
+                "{}/{}",
+                self.base_url.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            )
+        };
+        match &self.cache_bust {
+            Some(version) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{}{}v={}", url, separator, version)
+            }
+            None => url,
+        }
+    }
+}
+
+pub fn set(config: AssetConfig) {
+    CONFIG.with(|current| *current.borrow_mut() = Some(config));
+}
+
+// Where to fetch the asset at `path` from.
+pub fn url(path: &str) -> String {
+    CONFIG.with(|current| {
+        current
+            .borrow_mut()
+            .get_or_insert_with(AssetConfig::from_browser)
+            .resolve(path)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_are_left_alone_by_default() {
+        assert_eq!(AssetConfig::default().resolve("BG.png"), "BG.png");
+    }
+
+    #[test]
+    fn paths_are_joined_to_the_base_url() {
+        let config = AssetConfig {
+            base_url: "https://cdn.example.com/walk/".to_string(),
+            cache_bust: None,
+        };
+
+        assert_eq!(
+            config.resolve("/assets.json"),
+            "https://cdn.example.com/walk/assets.json"
+        );
+        assert_eq!(
+            config.resolve("tiles.json"),
+            "https://cdn.example.com/walk/tiles.json"
+        );
+    }
+
+    #[test]
+    fn cache_bust_is_added_as_a_parameter() {
+        let config = AssetConfig {
+            base_url: "/games/walk".to_string(),
+            cache_bust: Some("42".to_string()),
+        };
+
+        assert_eq!(config.resolve("BG.png"), "/games/walk/BG.png?v=42");
+        assert_eq!(
+            config.resolve("levels.json?lang=en"),
+            "/games/walk/levels.json?lang=en&v=42"
+        );
+    }
+
+    #[test]
+    fn data_and_absolute_urls_are_not_rewritten() {
+        let config = AssetConfig {
+            base_url: "/games/walk".to_string(),
+            cache_bust: Some("42".to_string()),
+        };
+
+        assert_eq!(
+            config.resolve("data:image/png;base64,AA=="),
+            "data:image/png;base64,AA=="
+        );
+        assert_eq!(
+            config.resolve("https://example.com/a.png"),
+            "https://example.com/a.png"
+        );
+    }
+}
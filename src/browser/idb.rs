@@ -0,0 +1,139 @@
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode};
+
+use super::window;
+
+const DATABASE_NAME: &str = "walk_the_dog";
+const DATABASE_VERSION: u32 = 1;
+
+pub const SHEETS: &str = "sheets";
+pub const REPLAYS: &str = "replays";
+const STORES: [&str; 2] = [SHEETS, REPLAYS];
+
+// IndexedDB reports through callbacks, so each request is wrapped in a promise.
+async fn request_result(request: &IdbRequest) -> Result<JsValue> {
+    let promise = js_sys::Promise::new(&mut |resolve, reject| {
+        let success_request = request.clone();
+        let onsuccess = Closure::once_into_js(move || {
+            let result = success_request.result().unwrap_or(JsValue::UNDEFINED);
+            resolve.call1(&JsValue::NULL, &result).ok();
+        });
+        let onerror = Closure::once_into_js(move |event: JsValue| {
+            reject.call1(&JsValue::NULL, &event).ok();
+        });
+        request.set_onsuccess(Some(onsuccess.unchecked_ref()));
+        request.set_onerror(Some(onerror.unchecked_ref()));
+    });
+    JsFuture::from(promise)
+        .await
+        .map_err(|err| anyhow!("IndexedDB request failed {:#?}", err))
+}
+
+pub struct Database {
+    db: IdbDatabase,
+}
+
+impl Database {
+    pub async fn open() -> Result<Self> {
+        let request = window()?
+            .indexed_db()
+            .map_err(|err| anyhow!("Could not access IndexedDB {:#?}", err))?
+            .ok_or_else(|| anyhow!("No IndexedDB found"))?
+            .open_with_u32(DATABASE_NAME, DATABASE_VERSION)
+            .map_err(|err| anyhow!("Could not open {} {:#?}", DATABASE_NAME, err))?;
+
+        let upgrade_request = request.clone();
+        let onupgradeneeded = Closure::once_into_js(move || {
+            if let Err(err) = upgrade_request
+                .result()
+                .and_then(|db| db.dyn_into::<IdbDatabase>())
+                .and_then(|db| {
+                    STORES
+                        .iter()
+                        .filter(|store| !db.object_store_names().contains(store))
+                        .try_for_each(|store| db.create_object_store(store).map(|_| ()))
+                })
+            {
+                log!("Could not upgrade {} {:#?}", DATABASE_NAME, err);
+            }
+        });
+        request.set_onupgradeneeded(Some(onupgradeneeded.unchecked_ref()));
+
+        let db = request_result(&request)
+            .await?
+            .dyn_into()
+            .map_err(|err| anyhow!("Error converting {:#?} to IdbDatabase", err))?;
+        Ok(Database { db })
+    }
+
+    fn store(&self, name: &str, mode: IdbTransactionMode) -> Result<IdbObjectStore> {
+        self.db
+            .transaction_with_str_and_mode(name, mode)
+            .and_then(|transaction| transaction.object_store(name))
+            .map_err(|err| anyhow!("Could not open store {} {:#?}", name, err))
+    }
+
+    pub async fn get(&self, store: &str, key: &str) -> Result<Option<JsValue>> {
+        let request = self
+            .store(store, IdbTransactionMode::Readonly)?
+            .get(&JsValue::from_str(key))
+            .map_err(|err| anyhow!("Could not read {} from {} {:#?}", key, store, err))?;
+        let value = request_result(&request).await?;
+        Ok(if value.is_undefined() {
+            None
+        } else {
+            Some(value)
+        })
+    }
+
+    pub async fn put(&self, store: &str, key: &str, value: &JsValue) -> Result<()> {
+        let request = self
+            .store(store, IdbTransactionMode::Readwrite)?
+            .put_with_key(value, &JsValue::from_str(key))
+            .map_err(|err| anyhow!("Could not write {} to {} {:#?}", key, store, err))?;
+        request_result(&request).await.map(|_| ())
+    }
+
+    #[allow(dead_code)]
+    pub async fn delete(&self, store: &str, key: &str) -> Result<()> {
+        let request = self
+            .store(store, IdbTransactionMode::Readwrite)?
+            .delete(&JsValue::from_str(key))
+            .map_err(|err| anyhow!("Could not delete {} from {} {:#?}", key, store, err))?;
+        request_result(&request).await.map(|_| ())
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, store: &str, key: &str) -> Result<Option<T>> {
+        self.get(store, key)
+            .await?
+            .map(|value| {
+                value
+                    .into_serde()
+                    .map_err(|err| anyhow!("Could not read {} from {} {:#?}", key, store, err))
+            })
+            .transpose()
+    }
+
+    pub async fn put_json<T: Serialize>(&self, store: &str, key: &str, value: &T) -> Result<()> {
+        let value = JsValue::from_serde(value)
+            .map_err(|err| anyhow!("Could not serialize {} {:#?}", key, err))?;
+        self.put(store, key, &value).await
+    }
+
+    // Replays can outgrow local storage, so they are kept here as raw bytes.
+    #[allow(dead_code)]
+    pub async fn get_bytes(&self, store: &str, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .get(store, key)
+            .await?
+            .map(|value| js_sys::Uint8Array::new(&value).to_vec()))
+    }
+
+    pub async fn put_bytes(&self, store: &str, key: &str, bytes: &[u8]) -> Result<()> {
+        self.put(store, key, &js_sys::Uint8Array::from(bytes).into())
+            .await
+    }
+}
@@ -0,0 +1,90 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use wasm_bindgen::JsCast;
+use web_sys::{MessageEvent, WebSocket};
+
+use super::closure_wrap;
+
+// A WebSocket carrying text. Messages sent while it is still connecting are
+// held back and sent once it opens, so callers never have to wait for it.
+// Cloning gives another handle on the same socket.
+#[derive(Clone)]
+pub struct Socket {
+    socket: WebSocket,
+    outbox: Rc<RefCell<Vec<String>>>,
+}
+
+impl Socket {
+    // Incoming messages arrive on the receiver, which ends when the socket closes.
+    pub fn open(url: &str) -> Result<(Socket, UnboundedReceiver<String>)> {
+        let socket =
+            WebSocket::new(url).map_err(|err| anyhow!("Could not open socket {:#?}", err))?;
+        let outbox: Rc<RefCell<Vec<String>>> = Rc::new(RefCell::new(vec![]));
+
+        let (sender, receiver) = unbounded();
+        let sender: Rc<RefCell<Option<UnboundedSender<String>>>> =
+            Rc::new(RefCell::new(Some(sender)));
+        let message_sender = sender.clone();
+        let on_message = closure_wrap(Box::new(move |event: MessageEvent| {
+            match (
+                event.data().as_string(),
+                message_sender.borrow_mut().as_mut(),
+            ) {
+                (Some(text), Some(sender)) => {
+                    if let Err(err) = sender.start_send(text) {
+                        log!("Could not pass on socket message {:#?}", err);
+                    }
+                }
+                (None, _) => {
+                    log!("Ignoring a socket message that is not text");
+                }
+                (Some(_), None) => {}
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        on_message.forget();
+
+        let on_close = closure_wrap(Box::new(move || {
+            sender.borrow_mut().take();
+        }) as Box<dyn FnMut()>);
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+        on_close.forget();
+
+        let open_socket = socket.clone();
+        let open_outbox = outbox.clone();
+        let on_open = closure_wrap(Box::new(move || {
+            open_outbox
+                .borrow_mut()
+                .drain(..)
+                .for_each(|text| send_now(&open_socket, &text));
+        }) as Box<dyn FnMut()>);
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        on_open.forget();
+
+        Ok((Socket { socket, outbox }, receiver))
+    }
+
+    pub fn send(&self, text: &str) {
+        match self.socket.ready_state() {
+            WebSocket::OPEN => send_now(&self.socket, text),
+            WebSocket::CONNECTING => self.outbox.borrow_mut().push(text.to_string()),
+            _ => {
+                log!("Dropping a message for a closed socket");
+            }
+        }
+    }
+
+    pub fn close(&self) {
+        if let Err(err) = self.socket.close() {
+            log!("Could not close socket {:#?}", err);
+        }
+    }
+}
+
+fn send_now(socket: &WebSocket, text: &str) {
+    if let Err(err) = socket.send_with_str(text) {
+        log!("Could not send socket message {:#?}", err);
+    }
+}
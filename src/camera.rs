@@ -0,0 +1,70 @@
+use crate::engine::Rect;
+
+// The boy can move this far up or down the screen before the camera follows.
+const DEAD_ZONE_TOP: i16 = 120;
+const DEAD_ZONE_BOTTOM: i16 = 480;
+// The camera closes this fraction of the gap to where it wants to be each frame.
+const FOLLOW_DIVISOR: i16 = 8;
+
+// Follows the boy up and down for levels with platforms above the view.
+// Sideways panning is left to pan zones. The camera never goes below the
+// ground, so `y` is zero or negative.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Camera {
+    y: i16,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Camera::default()
+    }
+
+    pub fn follow(&mut self, target: &Rect) {
+        let top = target.y() - self.y;
+        let bottom = target.bottom() - self.y;
+        let wanted = if top < DEAD_ZONE_TOP {
+            target.y() - DEAD_ZONE_TOP
+        } else if bottom > DEAD_ZONE_BOTTOM {
+            target.bottom() - DEAD_ZONE_BOTTOM
+        } else {
+            self.y
+        }
+        .min(0);
+
+        let gap = wanted - self.y;
+        let step = gap / FOLLOW_DIVISOR;
+        self.y += if step == 0 { gap.signum() } else { step };
+    }
+
+    pub fn y(&self) -> i16 {
+        self.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stays_put_inside_the_dead_zone() {
+        let mut camera = Camera::new();
+
+        camera.follow(&Rect::new_from_x_y(0, 300, 50, 120));
+        assert_eq!(camera.y(), 0);
+    }
+
+    #[test]
+    fn eases_up_and_back_down_to_the_ground() {
+        let mut camera = Camera::new();
+        let high = Rect::new_from_x_y(0, -200, 50, 120);
+
+        camera.follow(&high);
+        assert_eq!(camera.y(), -40);
+        (0..100).for_each(|_| camera.follow(&high));
+        assert_eq!(camera.y(), -320);
+
+        let low = Rect::new_from_x_y(0, 480, 50, 120);
+        (0..100).for_each(|_| camera.follow(&low));
+        assert_eq!(camera.y(), 0);
+    }
+}
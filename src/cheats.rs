@@ -0,0 +1,102 @@
+use anyhow::{anyhow, Result};
+
+// Debug switches shared by every system that honours them. Runs with any
+// cheat on are kept off the leaderboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Cheats {
+    pub invincible: bool,
+    pub noclip: bool,
+    pub show_hitboxes: bool,
+    pub fixed_seed: Option<u64>,
+}
+
+impl Cheats {
+    // Comma separated names, as given in the `cheats` query parameter, e.g.
+    // `invincible,show_hitboxes,seed=42`.
+    pub fn from_names(names: &str) -> Self {
+        let mut cheats = Cheats::default();
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .for_each(|entry| {
+                let (name, value) = match entry.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (entry, None),
+                };
+                if let Err(err) = cheats.set(name, value) {
+                    log!("Ignoring cheat {} {:#?}", entry, err);
+                }
+            });
+        cheats
+    }
+
+    // Switches take an optional `on` or `off`, toggling when it is left out.
+    pub fn set(&mut self, name: &str, value: Option<&str>) -> Result<()> {
+        let switch = match name {
+            "invincible" => &mut self.invincible,
+            "noclip" => &mut self.noclip,
+            "show_hitboxes" => &mut self.show_hitboxes,
+            "seed" => {
+                self.fixed_seed = match value {
+                    Some("off") => None,
+                    Some(seed) => Some(
+                        seed.parse()
+                            .map_err(|_err| anyhow!("Invalid seed {}", seed))?,
+                    ),
+                    None => return Err(anyhow!("The seed cheat needs a value")),
+                };
+                return Ok(());
+            }
+            _ => return Err(anyhow!("Unknown cheat {}", name)),
+        };
+        *switch = match value {
+            None => !*switch,
+            Some("on") => true,
+            Some("off") => false,
+            Some(value) => return Err(anyhow!("Expected on or off, got {}", value)),
+        };
+        Ok(())
+    }
+
+    pub fn any(&self) -> bool {
+        *self != Cheats::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_query_names() {
+        let cheats = Cheats::from_names("invincible, seed=42,,show_hitboxes=off");
+
+        assert!(cheats.invincible);
+        assert!(!cheats.show_hitboxes);
+        assert_eq!(cheats.fixed_seed, Some(42));
+        assert!(cheats.any());
+        assert!(!Cheats::from_names("").any());
+    }
+
+    #[test]
+    fn switches_toggle_or_take_a_value() {
+        let mut cheats = Cheats::default();
+
+        cheats.set("show_hitboxes", None).unwrap();
+        assert!(cheats.show_hitboxes);
+        cheats.set("show_hitboxes", None).unwrap();
+        assert!(!cheats.show_hitboxes);
+
+        cheats.set("noclip", Some("on")).unwrap();
+        cheats.set("noclip", Some("on")).unwrap();
+        assert!(cheats.noclip);
+
+        cheats.set("seed", Some("7")).unwrap();
+        cheats.set("seed", Some("off")).unwrap();
+        assert_eq!(cheats.fixed_seed, None);
+        assert!(cheats.set("seed", None).is_err());
+        assert!(cheats.set("noclip", Some("maybe")).is_err());
+        assert!(cheats.set("fly", None).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use wasm_bindgen::JsValue;
+use web_sys::{Request, RequestInit};
+
+use crate::{
+    browser,
+    consent::Consent,
+    save::{self, SaveData},
+};
+
+// Kept apart from the save keys so the token never ends up in an exported save.
+pub const TOKEN_KEY: &str = "walk_the_dog.cloud_token";
+const NOT_FOUND: u16 = 404;
+
+#[async_trait(?Send)]
+pub trait CloudBackend {
+    async fn pull(&self) -> Result<Option<SaveData>>;
+    async fn push(&self, save: &SaveData) -> Result<()>;
+}
+
+// Stores the save as JSON at a single URL, authorized with a bearer token.
+pub struct RestBackend {
+    endpoint: String,
+    token: String,
+}
+
+impl RestBackend {
+    pub fn new(endpoint: &str, token: &str) -> Self {
+        RestBackend {
+            endpoint: endpoint.to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    // Enabled with `?cloud_save=<endpoint>` once a token has been stored and
+    // the player has agreed to go online.
+    pub fn from_browser() -> Result<Option<Self>> {
+        if !Consent::load().allows_online() {
+            return Ok(None);
+        }
+        let endpoint = match browser::query_param("cloud_save")? {
+            Some(endpoint) => endpoint,
+            None => return Ok(None),
+        };
+        Ok(browser::load_item(TOKEN_KEY)?.map(|token| RestBackend::new(&endpoint, &token)))
+    }
+
+    fn request(&self, method: &str, body: Option<&str>) -> Result<Request> {
+        let mut init = RequestInit::new();
+        init.method(method);
+        if let Some(body) = body {
+            init.body(Some(&JsValue::from_str(body)));
+        }
+        let request = Request::new_with_str_and_init(&self.endpoint, &init)
+            .map_err(|err| anyhow!("Could not create cloud save request {:#?}", err))?;
+        let headers = request.headers();
+        headers
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .and_then(|_| headers.set("Content-Type", "application/json"))
+            .map_err(|err| anyhow!("Could not set cloud save headers {:#?}", err))?;
+        Ok(request)
+    }
+}
+
+#[async_trait(?Send)]
+impl CloudBackend for RestBackend {
+    async fn pull(&self) -> Result<Option<SaveData>> {
+        let response = browser::fetch_request(&self.request("GET", None)?).await?;
+        if response.status() == NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.ok() {
+            return Err(anyhow!("Cloud save pull failed {}", response.status()));
+        }
+        browser::from_json(&browser::response_text(&response).await?).map(Some)
+    }
+
+    async fn push(&self, save: &SaveData) -> Result<()> {
+        let body = browser::to_json(save)?;
+        let response = browser::fetch_request(&self.request("PUT", Some(&body))?).await?;
+        if response.ok() {
+            Ok(())
+        } else {
+            Err(anyhow!("Cloud save push failed {}", response.status()))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    Upload,
+    Download,
+    InSync,
+}
+
+// The newest save wins; a missing remote save is always replaced by the local one.
+fn resolve(local: &SaveData, remote: Option<&SaveData>) -> Resolution {
+    match remote {
+        None => Resolution::Upload,
+        Some(remote) if remote.newer_than(local) => Resolution::Download,
+        Some(remote) if local.newer_than(remote) => Resolution::Upload,
+        Some(_) => Resolution::InSync,
+    }
+}
+
+pub async fn sync(backend: &dyn CloudBackend) -> Result<()> {
+    let local = save::snapshot()?;
+    let remote = backend.pull().await?;
+    match resolve(&local, remote.as_ref()) {
+        Resolution::Upload => backend.push(&local).await,
+        Resolution::Download => match &remote {
+            Some(remote) => save::restore(remote),
+            None => Ok(()),
+        },
+        Resolution::InSync => Ok(()),
+    }
+}
+
+// Cloud saves are optional, so this does nothing unless a backend is configured.
+pub fn sync_in_background() {
+    match RestBackend::from_browser() {
+        Ok(Some(backend)) => browser::spawn_local(async move {
+            if let Err(err) = sync(&backend).await {
+                log!("Could not sync cloud save {:#?}", err);
+            }
+        }),
+        Ok(None) => {}
+        Err(err) => {
+            log!("Could not set up cloud save {:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save(saved_at: u64) -> SaveData {
+        serde_json::from_str(&format!(
+            r#"{{"version": 1, "saved_at": {}, "items": {{}}}}"#,
+            saved_at
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn newest_save_wins() {
+        assert_eq!(resolve(&save(10), None), Resolution::Upload);
+        assert_eq!(resolve(&save(10), Some(&save(5))), Resolution::Upload);
+        assert_eq!(resolve(&save(10), Some(&save(20))), Resolution::Download);
+        assert_eq!(resolve(&save(10), Some(&save(10))), Resolution::InSync);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::engine::{Color, Point, Rect, Renderer};
+
+const COIN_RADIUS: i16 = 10;
+const COIN_COLOR: Color = Color::rgb(245, 200, 40);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Coin {
+    center: Point,
+}
+
+impl Coin {
+    pub fn new(center: Point) -> Self {
+        Coin { center }
+    }
+
+    fn hitbox(&self) -> Rect {
+        Rect::new_from_x_y(
+            self.center.x - COIN_RADIUS,
+            self.center.y - COIN_RADIUS,
+            COIN_RADIUS * 2,
+            COIN_RADIUS * 2,
+        )
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.center.x += x;
+    }
+
+    pub fn right(&self) -> i16 {
+        self.center.x + COIN_RADIUS
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        if let Err(err) = renderer.fill_circle(&self.center, COIN_RADIUS, &COIN_COLOR) {
+            log!("Could not draw coin {:#?}", err);
+        }
+    }
+}
+
+// A row of `count` coins starting at `start`, `spacing` pixels apart.
+pub fn trail(start: Point, count: i16, spacing: i16) -> Vec<Coin> {
+    (0..count)
+        .map(|index| {
+            Coin::new(Point {
+                x: start.x + index * spacing,
+                y: start.y,
+            })
+        })
+        .collect()
+}
+
+// Takes the coins the boy is touching and returns how many there were.
+pub fn collect(coins: &mut Vec<Coin>, boy: &Rect) -> u32 {
+    let before = coins.len();
+    coins.retain(|coin| !coin.hitbox().intersects(boy));
+    (before - coins.len()) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collects_only_touched_coins() {
+        let mut coins = trail(Point { x: 100, y: 300 }, 3, 50);
+        let boy = Rect::new_from_x_y(100, 250, 60, 100);
+
+        assert_eq!(collect(&mut coins, &boy), 2);
+        assert_eq!(coins, vec![Coin::new(Point { x: 200, y: 300 })]);
+        assert_eq!(collect(&mut coins, &boy), 0);
+    }
+}
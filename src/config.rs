@@ -0,0 +1,278 @@
+// How many fixed updates the simulation runs a second.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TickRate {
+    Hz30,
+    #[default]
+    Hz60,
+    Hz120,
+}
+
+impl TickRate {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "30" => Some(TickRate::Hz30),
+            "60" => Some(TickRate::Hz60),
+            "120" => Some(TickRate::Hz120),
+            _ => None,
+        }
+    }
+
+    pub fn hz(&self) -> u8 {
+        match self {
+            TickRate::Hz30 => 30,
+            TickRate::Hz60 => 60,
+            TickRate::Hz120 => 120,
+        }
+    }
+
+    // Milliseconds each update stands for.
+    pub fn frame_size(&self) -> f32 {
+        1000.0 / f32::from(self.hz())
+    }
+}
+
+// The tuning values in pixels a second, and a second squared for gravity,
+// so they mean the same whatever the tick rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rates {
+    pub gravity: f64,
+    pub terminal_velocity: f64,
+    pub jump_speed: f64,
+    pub running_speed: f64,
+}
+
+impl Default for Rates {
+    fn default() -> Self {
+        Rates {
+            gravity: 3600.0,
+            terminal_velocity: 1200.0,
+            jump_speed: -1500.0,
+            running_speed: 180.0,
+        }
+    }
+}
+
+// `Rates` worked out for one tick, in whole pixels so every run at the same
+// tick rate plays out the same.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Physics {
+    pub gravity: i16,
+    pub gravity_period: u8,
+    pub terminal_velocity: i16,
+    pub jump_speed: i16,
+    pub running_speed: i16,
+}
+
+impl Physics {
+    // Gravity too weak for a pixel every tick pulls a pixel every few ticks
+    // instead.
+    pub fn per_tick(rates: &Rates, tick_rate: TickRate) -> Self {
+        let dt = 1.0 / f64::from(tick_rate.hz());
+        let gravity = rates.gravity * dt * dt;
+        let (gravity, gravity_period) = if gravity >= 1.0 {
+            (gravity.round() as i16, 1)
+        } else {
+            (
+                1,
+                (1.0 / gravity.max(f64::EPSILON)).round().min(255.0) as u8,
+            )
+        };
+        Physics {
+            gravity,
+            gravity_period,
+            terminal_velocity: (rates.terminal_velocity * dt).round() as i16,
+            jump_speed: (rates.jump_speed * dt).round() as i16,
+            running_speed: (rates.running_speed * dt).round() as i16,
+        }
+    }
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics::per_tick(&Rates::default(), TickRate::default())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Mutator {
+    LowGravity,
+    DoubleSpeed,
+    HazardsOnly,
+    Mirror,
+}
+
+impl Mutator {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "low_gravity" => Some(Mutator::LowGravity),
+            "double_speed" => Some(Mutator::DoubleSpeed),
+            "hazards_only" => Some(Mutator::HazardsOnly),
+            "mirror" => Some(Mutator::Mirror),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mutator::LowGravity => "low_gravity",
+            Mutator::DoubleSpeed => "double_speed",
+            Mutator::HazardsOnly => "hazards_only",
+            Mutator::Mirror => "mirror",
+        }
+    }
+
+    fn apply(&self, mut config: GameConfig) -> GameConfig {
+        match self {
+            Mutator::LowGravity => {
+                config.physics.gravity_period *= 2;
+                config.physics.jump_speed = config.physics.jump_speed * 2 / 3;
+            }
+            Mutator::DoubleSpeed => config.physics.running_speed *= 2,
+            Mutator::HazardsOnly => config.hazards_only = true,
+            Mutator::Mirror => config.mirrored = !config.mirrored,
+        }
+        config
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameConfig {
+    pub physics: Physics,
+    pub tick_rate: TickRate,
+    pub hazards_only: bool,
+    pub mirrored: bool,
+    mutators: Vec<Mutator>,
+}
+
+impl GameConfig {
+    pub fn with_mutators(mutators: &[Mutator]) -> Self {
+        let mut mutators = mutators.to_vec();
+        mutators.sort();
+        mutators.dedup();
+
+        let config = GameConfig {
+            mutators: mutators.clone(),
+            ..GameConfig::default()
+        };
+        mutators
+            .iter()
+            .fold(config, |config, mutator| mutator.apply(config))
+    }
+
+    // The physics are worked out again for the new rate, mutators and all.
+    pub fn with_tick_rate(self, tick_rate: TickRate) -> Self {
+        let config = GameConfig {
+            physics: Physics::per_tick(&Rates::default(), tick_rate),
+            tick_rate,
+            mutators: self.mutators.clone(),
+            ..GameConfig::default()
+        };
+        self.mutators
+            .iter()
+            .fold(config, |config, mutator| mutator.apply(config))
+    }
+
+    pub fn mutators(&self) -> &[Mutator] {
+        &self.mutators
+    }
+
+    // Comma separated mutator names, as given in the `mutators` query parameter.
+    pub fn from_names(names: &str) -> Self {
+        let mutators: Vec<Mutator> = names
+            .split(',')
+            .filter_map(|name| {
+                let mutator = Mutator::from_name(name.trim());
+                if mutator.is_none() {
+                    log!("Ignoring unknown mutator {}", name);
+                }
+                mutator
+            })
+            .collect();
+        GameConfig::with_mutators(&mutators)
+    }
+
+    // The reverse of `from_names`.
+    pub fn names(&self) -> String {
+        self.mutators
+            .iter()
+            .map(Mutator::name)
+            .collect::<Vec<&str>>()
+            .join(",")
+    }
+
+    // Runs with different mutators are ranked separately, and so are runs
+    // at other tick rates, as whole pixels a tick round differently.
+    pub fn leaderboard_bucket(&self) -> String {
+        let bucket = if self.mutators.is_empty() {
+            "standard".to_string()
+        } else {
+            self.mutators
+                .iter()
+                .map(Mutator::name)
+                .collect::<Vec<&str>>()
+                .join("+")
+        };
+        if self.tick_rate == TickRate::default() {
+            bucket
+        } else {
+            format!("{}@{}hz", bucket, self.tick_rate.hz())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_matches_the_original_physics() {
+        let config = GameConfig::with_mutators(&[]);
+
+        assert_eq!(config.physics, Physics::default());
+        assert!(!config.hazards_only);
+        assert!(!config.mirrored);
+        assert_eq!(config.leaderboard_bucket(), "standard");
+    }
+
+    #[test]
+    fn mutators_compose() {
+        let config = GameConfig::with_mutators(&[Mutator::DoubleSpeed, Mutator::LowGravity]);
+
+        assert_eq!(config.physics.running_speed, 6);
+        assert_eq!(config.physics.gravity_period, 2);
+        assert_eq!(config.names(), "low_gravity,double_speed");
+        assert_eq!(config.physics.jump_speed, -16);
+    }
+
+    #[test]
+    fn buckets_ignore_order_and_duplicates() {
+        let first = GameConfig::from_names("mirror,double_speed");
+        let second = GameConfig::from_names("double_speed, mirror,mirror");
+
+        assert_eq!(first.leaderboard_bucket(), "double_speed+mirror");
+        assert_eq!(first, second);
+        assert!(first.mirrored);
+    }
+
+    #[test]
+    fn tick_rates_scale_the_physics() {
+        let slow = GameConfig::default().with_tick_rate(TickRate::Hz30);
+        let fast = GameConfig::default().with_tick_rate(TickRate::Hz120);
+
+        assert_eq!(slow.physics.gravity, 4);
+        assert_eq!(slow.physics.jump_speed, -50);
+        assert_eq!(slow.physics.running_speed, 6);
+        assert_eq!(fast.physics.gravity_period, 4);
+        assert_eq!(fast.physics.terminal_velocity, 10);
+        assert_eq!(fast.leaderboard_bucket(), "standard@120hz");
+    }
+
+    #[test]
+    fn tick_rates_keep_mutators() {
+        let config = GameConfig::from_names("low_gravity").with_tick_rate(TickRate::Hz30);
+
+        assert_eq!(config.physics.gravity, 4);
+        assert_eq!(config.physics.gravity_period, 2);
+        assert_eq!(config.names(), "low_gravity");
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::versioned::{self, Versioned};
+
+pub const CONSENT_KEY: &str = "walk_the_dog.consent";
+
+// What the player has agreed to send over the network. Anything that talks to
+// a server checks this first; until the player answers, nothing is sent.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Consent {
+    #[default]
+    Offline,
+    // Answers from when usage data was also offered still read as sharing
+    // scores.
+    #[serde(alias = "OnlineWithTelemetry")]
+    Online,
+}
+
+impl Versioned for Consent {
+    const VERSION: u32 = 1;
+}
+
+impl Consent {
+    // `None` until the player has answered the prompt.
+    pub fn stored() -> Option<Self> {
+        versioned::load(CONSENT_KEY).unwrap_or_else(|err| {
+            log!("Could not read consent {:#?}", err);
+            None
+        })
+    }
+
+    pub fn load() -> Self {
+        Consent::stored().unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(CONSENT_KEY, self)
+    }
+
+    // Online leaderboards and cloud saves.
+    pub fn allows_online(&self) -> bool {
+        *self != Consent::Offline
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Consent::Offline => Consent::Online,
+            Consent::Online => Consent::Offline,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Consent::Offline => "Offline",
+            Consent::Online => "Leaderboards",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_online_allows_online() {
+        assert!(!Consent::default().allows_online());
+        assert!(Consent::Online.allows_online());
+    }
+
+    #[test]
+    fn answers_that_included_usage_data_still_share_scores() {
+        let consent: Consent = serde_json::from_str(r#""OnlineWithTelemetry""#).unwrap();
+
+        assert_eq!(consent, Consent::Online);
+    }
+
+    #[test]
+    fn cycles_back_to_offline() {
+        let mut consent = Consent::Offline;
+        (0..2).for_each(|_| consent = consent.next());
+
+        assert_eq!(consent, Consent::Offline);
+    }
+}
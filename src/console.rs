@@ -0,0 +1,182 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use crate::browser::{self, Target};
+use crate::engine::KeyState;
+
+const TOGGLE_KEY: &str = "Backquote";
+const SUBMIT_KEY: &str = "Enter";
+const LOG_LINES: usize = 8;
+
+type Handler<T> = Box<dyn Fn(&mut T, &[&str]) -> Result<String>>;
+
+// Systems register a handler per command name; the handler gets the words
+// after the name and returns what to print.
+pub struct CommandRegistry<T> {
+    commands: HashMap<String, Handler<T>>,
+}
+
+impl<T> CommandRegistry<T> {
+    pub fn new() -> Self {
+        CommandRegistry {
+            commands: HashMap::new(),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &str,
+        handler: impl Fn(&mut T, &[&str]) -> Result<String> + 'static,
+    ) {
+        self.commands.insert(name.to_string(), Box::new(handler));
+    }
+
+    pub fn run(&self, subject: &mut T, line: &str) -> Result<String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or_else(|| anyhow!("Empty command"))?;
+        if name == "help" {
+            return Ok(self.names().join(" "));
+        }
+        let args: Vec<&str> = words.collect();
+        let handler = self
+            .commands
+            .get(name)
+            .ok_or_else(|| anyhow!("Unknown command {}", name))?;
+        handler(subject, &args)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+pub fn arg<N: FromStr>(args: &[&str], index: usize, name: &str) -> Result<N> {
+    args.get(index)
+        .ok_or_else(|| anyhow!("Missing {}", name))?
+        .parse()
+        .map_err(|_err| anyhow!("Invalid {} {}", name, args[index]))
+}
+
+// A text console over the canvas, opened with the backtick key. The game is
+// paused while it is open.
+pub struct Console<T> {
+    registry: CommandRegistry<T>,
+    open: bool,
+    toggle_held: bool,
+    submit_held: bool,
+    log: Vec<String>,
+}
+
+impl<T> Console<T> {
+    pub fn new(registry: CommandRegistry<T>) -> Self {
+        Console {
+            registry,
+            open: false,
+            toggle_held: false,
+            submit_held: false,
+            log: vec![],
+        }
+    }
+
+    // Returns true while the console is open.
+    pub fn update(&mut self, keystate: &KeyState, target: &Target, subject: &mut T) -> bool {
+        let toggle = keystate.is_pressed(TOGGLE_KEY) && !self.toggle_held;
+        self.toggle_held = keystate.is_pressed(TOGGLE_KEY);
+        if toggle {
+            self.open = !self.open;
+            if let Err(err) = self.show(target) {
+                log!("Could not toggle console {:#?}", err);
+            }
+        }
+
+        let submit = keystate.is_pressed(SUBMIT_KEY) && !self.submit_held;
+        self.submit_held = keystate.is_pressed(SUBMIT_KEY);
+        if self.open && submit {
+            if let Err(err) = self.submit(target, subject) {
+                log!("Could not run console command {:#?}", err);
+            }
+        }
+        self.open
+    }
+
+    fn show(&self, target: &Target) -> Result<()> {
+        if !self.open {
+            return browser::hide_ui(target);
+        }
+        browser::draw_ui(
+            target,
+            "<div class='console'><pre class='console_log'></pre><textarea class='console_input' rows='1' cols='50'></textarea></div>",
+        )?;
+        self.show_log(target)?;
+        browser::find_ui_text_area(target, ".console_input")?
+            .focus()
+            .map_err(|err| anyhow!("Could not focus console {:#?}", err))
+    }
+
+    fn submit(&mut self, target: &Target, subject: &mut T) -> Result<()> {
+        let input = browser::find_ui_text_area(target, ".console_input")?;
+        let line = input.value().trim().to_string();
+        input.set_value("");
+        if line.is_empty() {
+            return Ok(());
+        }
+        let output = self
+            .registry
+            .run(subject, &line)
+            .unwrap_or_else(|err| err.to_string());
+        self.log.push(format!("> {}", line));
+        self.log.push(output);
+        self.show_log(target)
+    }
+
+    fn show_log(&self, target: &Target) -> Result<()> {
+        let start = self.log.len().saturating_sub(LOG_LINES);
+        browser::find_ui_element(target, ".console_log")?
+            .set_inner_text(&self.log[start..].join("\n"));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> CommandRegistry<i32> {
+        let mut registry = CommandRegistry::new();
+        registry.register("add", |total: &mut i32, args: &[&str]| {
+            *total += arg::<i32>(args, 0, "amount")?;
+            Ok(format!("total {}", total))
+        });
+        registry.register("reset", |total: &mut i32, _args: &[&str]| {
+            *total = 0;
+            Ok("reset".to_string())
+        });
+        registry
+    }
+
+    #[test]
+    fn runs_registered_commands_with_arguments() {
+        let registry = registry();
+        let mut total = 1;
+
+        assert_eq!(registry.run(&mut total, "add 41").unwrap(), "total 42");
+        assert_eq!(total, 42);
+        assert_eq!(registry.run(&mut total, "  reset ").unwrap(), "reset");
+        assert_eq!(registry.run(&mut total, "help").unwrap(), "add reset");
+    }
+
+    #[test]
+    fn reports_bad_input() {
+        let registry = registry();
+        let mut total = 0;
+
+        assert!(registry.run(&mut total, "").is_err());
+        assert!(registry.run(&mut total, "jump").is_err());
+        assert!(registry.run(&mut total, "add").is_err());
+        assert!(registry.run(&mut total, "add lots").is_err());
+        assert_eq!(total, 0);
+    }
+}
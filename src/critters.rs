@@ -0,0 +1,249 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::engine::{Animation, Color, Playback, Point, Rect, Renderer};
+
+const WIDTH: i16 = 600;
+const DESPAWN_X: i16 = -120;
+const MAX_CRITTERS: usize = 3;
+const SPAWN_CHANCE: f64 = 0.005;
+// Critters live in the mid layer, which scrolls at half the ground's speed.
+const MID_LAYER_PARALLAX: i16 = 2;
+
+const BIRD_SPEED: i16 = 2;
+const BIRD_MIN_Y: i16 = 60;
+const BIRD_RANGE_Y: i16 = 160;
+const BUTTERFLY_Y: i16 = 430;
+const FENCE_Y: i16 = 470;
+
+const BIRD_COLOR: Color = Color::rgb(40, 40, 50);
+const BUTTERFLY_COLOR: Color = Color::rgb(240, 160, 40);
+const CAT_COLOR: Color = Color::rgb(60, 50, 45);
+const FENCE_COLOR: Color = Color::rgb(150, 110, 70);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CritterKind {
+    Bird,
+    Butterfly,
+    Cat,
+}
+
+impl CritterKind {
+    // Poses are played through the shared animation component, one pose per frame name.
+    fn animation(&self) -> Animation {
+        let poses = match self {
+            CritterKind::Bird => vec![("up", 150), ("down", 150)],
+            CritterKind::Butterfly => vec![("open", 100), ("closed", 100)],
+            CritterKind::Cat => vec![("left", 600), ("right", 600)],
+        };
+        Animation::new(
+            poses
+                .into_iter()
+                .map(|(pose, duration)| (pose.to_string(), duration))
+                .collect(),
+        )
+    }
+}
+
+struct Critter {
+    kind: CritterKind,
+    position: Point,
+    animation: Animation,
+    // Milliseconds into the current pose's animation.
+    elapsed: f64,
+    age: u16,
+}
+
+impl Critter {
+    fn new(kind: CritterKind, y: i16) -> Self {
+        Critter {
+            kind,
+            position: Point { x: WIDTH, y },
+            animation: kind.animation(),
+            elapsed: 0.0,
+            age: 0,
+        }
+    }
+
+    fn pose(&self) -> &str {
+        self.animation.frame_name(self.elapsed).unwrap_or_default()
+    }
+
+    fn update(&mut self, walking_speed: i16, delta: f64) {
+        let drift = walking_speed / MID_LAYER_PARALLAX;
+        self.age = self.age.wrapping_add(1);
+        self.elapsed = match self.animation.advance(self.elapsed, delta) {
+            Playback::Playing(elapsed) => elapsed,
+            Playback::Completed => 0.0,
+        };
+        match self.kind {
+            CritterKind::Bird => self.position.x += drift - BIRD_SPEED,
+            CritterKind::Butterfly => {
+                self.position.x += drift + (self.age % 2) as i16;
+                self.position.y = BUTTERFLY_Y + ((self.age as f32 / 15.0).sin() * 20.0) as i16;
+            }
+            CritterKind::Cat => self.position.x += drift,
+        }
+    }
+
+    fn gone(&self) -> bool {
+        self.position.x < DESPAWN_X || self.position.x > WIDTH * 2
+    }
+
+    fn draw(&self, renderer: &Renderer) -> anyhow::Result<()> {
+        let Point { x, y } = self.position;
+        match self.kind {
+            CritterKind::Bird => {
+                let tip = if self.pose() == "up" { y - 8 } else { y + 8 };
+                renderer.fill_polygon(
+                    &[
+                        Point { x, y: tip },
+                        Point { x: x + 12, y },
+                        Point { x: x + 24, y: tip },
+                        Point {
+                            x: x + 12,
+                            y: y + 3,
+                        },
+                    ],
+                    &BIRD_COLOR,
+                );
+            }
+            CritterKind::Butterfly => {
+                let wing = if self.pose() == "open" { 6 } else { 3 };
+                renderer.fill_circle(&Point { x: x - 4, y }, wing, &BUTTERFLY_COLOR)?;
+                renderer.fill_circle(&Point { x: x + 4, y }, wing, &BUTTERFLY_COLOR)?;
+            }
+            CritterKind::Cat => {
+                renderer.fill_rect(&Rect::new_from_x_y(x - 40, FENCE_Y, 120, 8), &FENCE_COLOR);
+                renderer.fill_rect(&Rect::new_from_x_y(x - 30, FENCE_Y, 8, 40), &FENCE_COLOR);
+                renderer.fill_rect(&Rect::new_from_x_y(x + 60, FENCE_Y, 8, 40), &FENCE_COLOR);
+                renderer.fill_rect(&Rect::new_from_x_y(x, FENCE_Y - 22, 24, 22), &CAT_COLOR);
+                renderer.fill_circle(
+                    &Point {
+                        x: x + 20,
+                        y: FENCE_Y - 28,
+                    },
+                    9,
+                    &CAT_COLOR,
+                )?;
+                let tail_x = if self.pose() == "left" { x - 6 } else { x - 2 };
+                renderer.fill_rect(&Rect::new_from_x_y(tail_x, FENCE_Y - 2, 4, 20), &CAT_COLOR);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Non-interactive wildlife; which kinds appear, and how often, comes from the background theme.
+pub struct Critters {
+    kinds: &'static [(CritterKind, u32)],
+    rng: StdRng,
+    active: Vec<Critter>,
+}
+
+impl Critters {
+    pub fn new(kinds: &'static [(CritterKind, u32)], seed: u64) -> Self {
+        Critters {
+            kinds,
+            rng: StdRng::seed_from_u64(seed),
+            active: vec![],
+        }
+    }
+
+    fn pick_kind(&mut self) -> Option<CritterKind> {
+        let total: u32 = self.kinds.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = self.rng.gen_range(0..total);
+        self.kinds.iter().find_map(|(kind, weight)| {
+            if roll < *weight {
+                Some(*kind)
+            } else {
+                roll -= weight;
+                None
+            }
+        })
+    }
+
+    fn spawn(&mut self) {
+        if let Some(kind) = self.pick_kind() {
+            let y = match kind {
+                CritterKind::Bird => BIRD_MIN_Y + self.rng.gen_range(0..BIRD_RANGE_Y),
+                CritterKind::Butterfly => BUTTERFLY_Y,
+                CritterKind::Cat => FENCE_Y,
+            };
+            self.active.push(Critter::new(kind, y));
+        }
+    }
+
+    pub fn update(&mut self, walking_speed: i16, delta: f64) {
+        if self.active.len() < MAX_CRITTERS && self.rng.gen_bool(SPAWN_CHANCE) {
+            self.spawn();
+        }
+        self.active
+            .iter_mut()
+            .for_each(|critter| critter.update(walking_speed, delta));
+        self.active.retain(|critter| !critter.gone());
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        if !renderer.detail().parallax {
+            return;
+        }
+        self.active.iter().for_each(|critter| {
+            if let Err(err) = critter.draw(renderer) {
+                log!("Could not draw critter {:#?}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TICK: f64 = 1000.0 / 60.0;
+
+    #[test]
+    fn spawns_only_the_theme_kinds() {
+        let mut critters = Critters::new(&[(CritterKind::Cat, 1), (CritterKind::Bird, 0)], 7);
+        (0..10).for_each(|_| critters.spawn());
+
+        assert!(critters
+            .active
+            .iter()
+            .all(|critter| critter.kind == CritterKind::Cat));
+        assert!(Critters::new(&[], 7).pick_kind().is_none());
+    }
+
+    #[test]
+    fn critters_scroll_off_and_despawn() {
+        let mut critters = Critters::new(&[(CritterKind::Cat, 1)], 7);
+        critters.spawn();
+        critters.active[0].update(-6, TICK);
+        assert_eq!(critters.active[0].position.x, WIDTH - 3);
+
+        critters.active[0].position.x = DESPAWN_X;
+        critters.update(-6, TICK);
+        assert!(critters
+            .active
+            .iter()
+            .all(|critter| critter.position.x > DESPAWN_X));
+    }
+
+    #[test]
+    fn poses_follow_the_animation() {
+        let mut bird = Critter::new(CritterKind::Bird, 100);
+        assert_eq!(bird.pose(), "up");
+
+        (0..10).for_each(|_| bird.update(0, TICK));
+        assert_eq!(bird.pose(), "down");
+
+        (0..10).for_each(|_| bird.update(0, TICK));
+        assert_eq!(bird.pose(), "up");
+
+        // Half as many updates twice as long apart play it just the same.
+        (0..5).for_each(|_| bird.update(0, 2.0 * TICK));
+        assert_eq!(bird.pose(), "down");
+    }
+}
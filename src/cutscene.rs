@@ -0,0 +1,385 @@
+use std::collections::VecDeque;
+
+use anyhow::{anyhow, Result};
+
+use crate::{
+    background::Background,
+    engine::{self, Color, Point, Rect, Renderer, SpeechBubble, SpriteSheet},
+};
+
+const WIDTH: i16 = 600;
+const HEIGHT: i16 = 600;
+
+const LETTERBOX_HEIGHT: i16 = 60;
+const LETTERBOX_FRAMES: i16 = 20;
+const LETTERBOX_COLOR: Color = Color::rgb(0, 0, 0);
+
+const TYPEWRITER_FRAMES_PER_CHAR: u16 = 2;
+
+const DOG_FLOOR: i16 = 470;
+const DOG_COLOR: Color = Color::rgb(150, 100, 60);
+
+// How scripted scenes stage the dog, and how long each line stays up once typed.
+const DOG_MARK: i16 = 220;
+const DOG_ENTER_SPEED: i16 = 3;
+const DOG_LEAVE_SPEED: i16 = 6;
+const SCRIPT_HOLD: u16 = 60;
+
+pub enum Step {
+    Wait(u16),
+    Pan {
+        speed: i16,
+        frames: u16,
+    },
+    DogWalk {
+        to: i16,
+        speed: i16,
+    },
+    Say {
+        speaker: Speaker,
+        text: String,
+        hold: u16,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speaker {
+    Boy,
+    Dog,
+}
+
+struct Dog {
+    position: Point,
+    facing_left: bool,
+    frame: u16,
+}
+
+impl Dog {
+    fn walk_towards(&mut self, to: i16, speed: i16) -> bool {
+        let distance = to - self.position.x;
+        self.facing_left = distance < 0;
+        self.frame = self.frame.wrapping_add(1);
+        self.position.x += distance.signum() * speed.min(distance.abs());
+        self.position.x == to
+    }
+
+    fn head(&self) -> Point {
+        Point {
+            x: if self.facing_left {
+                self.position.x - 10
+            } else {
+                self.position.x + 60
+            },
+            y: self.position.y - 45,
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let Point { x, y } = self.position;
+        let step = if (self.frame / 8) % 2 == 0 { 0 } else { 4 };
+
+        engine::draw_shadow(renderer, &Rect::new_from_x_y(x - 10, y - 60, 85, 60), y);
+        renderer.fill_rect(&Rect::new_from_x_y(x, y - 40, 60, 25), &DOG_COLOR);
+        renderer.fill_rect(&Rect::new_from_x_y(x + 4 + step, y - 15, 8, 15), &DOG_COLOR);
+        renderer.fill_rect(
+            &Rect::new_from_x_y(x + 48 - step, y - 15, 8, 15),
+            &DOG_COLOR,
+        );
+        if let Err(err) = renderer.fill_circle(&self.head(), 15, &DOG_COLOR) {
+            log!("Could not draw dog {:#?}", err);
+        }
+    }
+}
+
+struct Dialog {
+    bubble: SpeechBubble,
+    length: u16,
+    hold: u16,
+    frames: u16,
+}
+
+impl Dialog {
+    fn shown(&self) -> usize {
+        (self.frames / TYPEWRITER_FRAMES_PER_CHAR) as usize
+    }
+
+    fn finished(&self) -> bool {
+        let typed = self.length * TYPEWRITER_FRAMES_PER_CHAR;
+        self.frames >= typed.saturating_add(self.hold)
+    }
+
+    fn draw(&self, renderer: &Renderer, sheet: &SpriteSheet) {
+        if let Err(err) = self.bubble.draw(renderer, sheet, self.shown()) {
+            log!("Could not draw dialog {:#?}", err);
+        }
+    }
+}
+
+pub struct Cutscene {
+    steps: VecDeque<Step>,
+    elapsed: u16,
+    dog: Dog,
+    dialog: Option<Dialog>,
+    boy_anchor: Point,
+    letterbox: i16,
+}
+
+impl Cutscene {
+    pub fn new(steps: Vec<Step>) -> Self {
+        Cutscene {
+            steps: steps.into(),
+            elapsed: 0,
+            dog: Dog {
+                position: Point {
+                    x: WIDTH,
+                    y: DOG_FLOOR,
+                },
+                facing_left: true,
+                frame: 0,
+            },
+            dialog: None,
+            boy_anchor: Point::default(),
+            letterbox: 0,
+        }
+    }
+
+    pub fn intro() -> Self {
+        Cutscene::new(vec![
+            Step::Pan {
+                speed: -3,
+                frames: 90,
+            },
+            Step::DogWalk {
+                to: DOG_MARK,
+                speed: DOG_ENTER_SPEED,
+            },
+            Step::Say {
+                speaker: Speaker::Dog,
+                text: "Woof!".into(),
+                hold: 40,
+            },
+            Step::Say {
+                speaker: Speaker::Boy,
+                text: "There you are! Ready for a walk?".into(),
+                hold: 60,
+            },
+            Step::DogWalk {
+                to: WIDTH + 40,
+                speed: DOG_LEAVE_SPEED,
+            },
+            Step::Wait(20),
+        ])
+    }
+
+    // A scene written a step to a line, as designers can type it into a map
+    // editor: `Boy: <line>` or `Dog: <line>` to speak, `Wait <frames>`, and
+    // `Dog enters` or `Dog leaves`.
+    pub fn from_script(script: &str) -> Result<Self> {
+        let steps = script
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(script_step)
+            .collect::<Result<Vec<Step>>>()?;
+        Ok(Cutscene::new(steps))
+    }
+
+    pub fn finished(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    fn anchor(&self, speaker: Speaker) -> Point {
+        match speaker {
+            Speaker::Boy => self.boy_anchor,
+            Speaker::Dog => self.dog.head(),
+        }
+    }
+
+    pub fn update(&mut self, background: &mut Background, boy: &Rect) {
+        self.boy_anchor = Point {
+            x: boy.x() + boy.width / 2,
+            y: boy.y(),
+        };
+
+        if self.letterbox < LETTERBOX_FRAMES {
+            self.letterbox += 1;
+        }
+
+        let done = match self.steps.front() {
+            Some(Step::Wait(frames)) => self.elapsed >= *frames,
+            Some(Step::Pan { speed, frames }) => {
+                background.update(*speed);
+                self.elapsed >= *frames
+            }
+            Some(Step::DogWalk { to, speed }) => self.dog.walk_towards(*to, *speed),
+            Some(Step::Say {
+                speaker,
+                text,
+                hold,
+            }) => {
+                let anchor = self.anchor(*speaker);
+                let dialog = self.dialog.get_or_insert_with(|| Dialog {
+                    bubble: SpeechBubble::new(text, anchor),
+                    length: text.chars().count() as u16,
+                    hold: *hold,
+                    frames: 0,
+                });
+                dialog.bubble.set_anchor(anchor);
+                dialog.frames += 1;
+                dialog.finished()
+            }
+            None => false,
+        };
+
+        self.elapsed += 1;
+        if done {
+            self.steps.pop_front();
+            self.elapsed = 0;
+            self.dialog = None;
+        }
+    }
+
+    // Speech bubbles are drawn from the panel in `sheet`.
+    pub fn draw(&self, renderer: &Renderer, sheet: &SpriteSheet) {
+        self.dog.draw(renderer);
+
+        let height = LETTERBOX_HEIGHT * self.letterbox / LETTERBOX_FRAMES;
+        renderer.fill_rect(&Rect::new_from_x_y(0, 0, WIDTH, height), &LETTERBOX_COLOR);
+        renderer.fill_rect(
+            &Rect::new_from_x_y(0, HEIGHT - height, WIDTH, height),
+            &LETTERBOX_COLOR,
+        );
+
+        if let Some(dialog) = &self.dialog {
+            dialog.draw(renderer, sheet);
+        }
+    }
+}
+
+fn script_step(line: &str) -> Result<Step> {
+    let say = |speaker, text: &str| Step::Say {
+        speaker,
+        text: text.trim().to_string(),
+        hold: SCRIPT_HOLD,
+    };
+    if let Some((speaker, text)) = line.split_once(':') {
+        return match speaker.trim().to_ascii_lowercase().as_str() {
+            "boy" => Ok(say(Speaker::Boy, text)),
+            "dog" => Ok(say(Speaker::Dog, text)),
+            _ => Err(anyhow!("Unknown speaker in {}", line)),
+        };
+    }
+    match line
+        .to_ascii_lowercase()
+        .split_whitespace()
+        .collect::<Vec<&str>>()[..]
+    {
+        ["wait", frames] => frames
+            .parse()
+            .map(Step::Wait)
+            .map_err(|_err| anyhow!("Invalid wait in {}", line)),
+        ["dog", "enters"] => Ok(Step::DogWalk {
+            to: DOG_MARK,
+            speed: DOG_ENTER_SPEED,
+        }),
+        ["dog", "leaves"] => Ok(Step::DogWalk {
+            to: WIDTH + 40,
+            speed: DOG_LEAVE_SPEED,
+        }),
+        _ => Err(anyhow!("Unknown cutscene step {}", line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn background() -> Background {
+        Background::Procedural(crate::background::ProceduralBackground::new(1))
+    }
+
+    #[test]
+    fn dialog_types_one_character_at_a_time() {
+        let mut dialog = Dialog {
+            bubble: SpeechBubble::new("Woof", Point::default()),
+            length: 4,
+            hold: 4,
+            frames: 0,
+        };
+        assert_eq!(dialog.shown(), 0);
+
+        dialog.frames = TYPEWRITER_FRAMES_PER_CHAR * 2;
+        assert_eq!(dialog.shown(), 2);
+        assert!(!dialog.finished());
+
+        dialog.frames = TYPEWRITER_FRAMES_PER_CHAR * 4 + 4;
+        assert!(dialog.finished());
+    }
+
+    #[test]
+    fn speech_follows_the_speaker() {
+        let mut background = background();
+        let boy = Rect::new_from_x_y(0, 300, 100, 180);
+        let mut cutscene = Cutscene::new(vec![Step::Say {
+            speaker: Speaker::Boy,
+            text: "Hi".into(),
+            hold: 10,
+        }]);
+
+        cutscene.update(&mut background, &boy);
+        assert_eq!(cutscene.anchor(Speaker::Boy), Point { x: 50, y: 300 });
+        assert!(cutscene.dialog.is_some());
+    }
+
+    #[test]
+    fn steps_run_in_order_until_finished() {
+        let mut background = background();
+        let mut cutscene = Cutscene::new(vec![
+            Step::Wait(3),
+            Step::DogWalk {
+                to: WIDTH - 10,
+                speed: 4,
+            },
+        ]);
+
+        (0..4).for_each(|_| cutscene.update(&mut background, &Rect::default()));
+        assert_eq!(cutscene.steps.len(), 1);
+        assert_eq!(cutscene.dog.position.x, WIDTH);
+
+        (0..3).for_each(|_| cutscene.update(&mut background, &Rect::default()));
+        assert_eq!(cutscene.dog.position.x, WIDTH - 10);
+        assert!(cutscene.finished());
+    }
+
+    #[test]
+    fn scripts_read_a_step_a_line() {
+        let cutscene =
+            Cutscene::from_script("Dog enters\n\nDOG: Woof!\nwait 15\nBoy: Good boy").unwrap();
+        let steps: Vec<&Step> = cutscene.steps.iter().collect();
+
+        assert_eq!(steps.len(), 4);
+        assert!(matches!(steps[0], Step::DogWalk { to: DOG_MARK, .. }));
+        assert!(
+            matches!(steps[1], Step::Say { speaker: Speaker::Dog, text, .. } if text == "Woof!")
+        );
+        assert!(matches!(steps[2], Step::Wait(15)));
+        assert!(matches!(
+            steps[3],
+            Step::Say {
+                speaker: Speaker::Boy,
+                ..
+            }
+        ));
+        assert!(Cutscene::from_script("Cat: Meow").is_err());
+        assert!(Cutscene::from_script("wait a moment").is_err());
+    }
+
+    #[test]
+    fn intro_finishes() {
+        let mut background = background();
+        let mut cutscene = Cutscene::intro();
+
+        (0..2000).for_each(|_| cutscene.update(&mut background, &Rect::default()));
+        assert!(cutscene.finished());
+    }
+}
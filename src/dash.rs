@@ -0,0 +1,176 @@
+use crate::{
+    engine::{Align, Color, KeyState, Point, Rect, Renderer, TextStyle},
+    i18n::text,
+};
+
+// Dashes come from tapping right twice or from a key of their own.
+pub const DASH_KEY: &str = "KeyX";
+// Replays and spectators hold this on the frames dashes started on, since
+// both of the others depend on what came before.
+pub const RECORDED_DASH: &str = "Dash";
+const TAP_KEY: &str = "ArrowRight";
+// The second tap has to come within this many frames of the first.
+const DOUBLE_TAP_FRAMES: u8 = 15;
+// Frames after a dash starts before he can dash again.
+const COOLDOWN_FRAMES: u16 = 150;
+
+const METER_WIDTH: i16 = 80;
+const METER_HEIGHT: i16 = 10;
+const METER_BACK: Color = Color::rgba(0, 0, 0, 0.4);
+const METER_CHARGING: Color = Color::rgb(150, 150, 160);
+const METER_READY: Color = Color::rgb(250, 170, 40);
+const METER_TEXT: Color = Color::rgb(255, 255, 255);
+
+// Whether the player asked for a dash this frame, by either key.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DashInput {
+    double_tap: DoubleTap,
+    key_held: bool,
+}
+
+impl DashInput {
+    pub fn update(&mut self, keystate: &KeyState) -> bool {
+        let double_tapped = self.double_tap.update(keystate.is_pressed(TAP_KEY));
+        let key_pressed = keystate.is_pressed(DASH_KEY);
+        let key_tapped = key_pressed && !self.key_held;
+        self.key_held = key_pressed;
+        double_tapped || key_tapped || keystate.is_pressed(RECORDED_DASH)
+    }
+}
+
+// Spots a key being tapped twice in quick succession.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct DoubleTap {
+    held: bool,
+    // Frames since the last tap, while a second one would still count.
+    since_tap: Option<u8>,
+}
+
+impl DoubleTap {
+    // Called every frame with whether the key is down. True on the frame it
+    // goes down for the second time.
+    fn update(&mut self, pressed: bool) -> bool {
+        let tapped = pressed && !self.held;
+        self.held = pressed;
+        if !tapped {
+            self.since_tap = self
+                .since_tap
+                .map(|frames| frames + 1)
+                .filter(|frames| *frames <= DOUBLE_TAP_FRAMES);
+            return false;
+        }
+        match self.since_tap.take() {
+            Some(_frames) => true,
+            None => {
+                self.since_tap = Some(0);
+                false
+            }
+        }
+    }
+}
+
+// Fills back up after every dash, which can only start once it is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DashMeter {
+    cooldown: u16,
+}
+
+impl DashMeter {
+    pub fn ready(&self) -> bool {
+        self.cooldown == 0
+    }
+
+    pub fn spend(&mut self) {
+        self.cooldown = COOLDOWN_FRAMES;
+    }
+
+    pub fn update(&mut self) {
+        self.cooldown = self.cooldown.saturating_sub(1);
+    }
+
+    // How full it is, from 0 to 1.
+    fn charge(&self) -> f64 {
+        1.0 - f64::from(self.cooldown) / f64::from(COOLDOWN_FRAMES)
+    }
+
+    // With its top right corner at `corner`.
+    pub fn draw(&self, renderer: &Renderer, corner: Point) {
+        let back = Rect::new_from_x_y(corner.x - METER_WIDTH, corner.y, METER_WIDTH, METER_HEIGHT);
+        renderer.fill_rect(&back, &METER_BACK);
+        let (filled, color) = if self.ready() {
+            (METER_WIDTH, METER_READY)
+        } else {
+            (
+                (f64::from(METER_WIDTH) * self.charge()) as i16,
+                METER_CHARGING,
+            )
+        };
+        renderer.fill_rect(&Rect::new(back.position, filled, METER_HEIGHT), &color);
+        let label = Point {
+            x: back.x() - 6,
+            y: back.bottom(),
+        };
+        let style = TextStyle::new()
+            .with_color(METER_TEXT)
+            .with_align(Align::Right);
+        if let Err(err) = renderer.draw_styled_text(text("Dash"), &label, &style) {
+            log!("Could not draw dash meter {:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn taps(presses: &[bool]) -> Vec<bool> {
+        let mut double_tap = DoubleTap::default();
+        presses
+            .iter()
+            .map(|pressed| double_tap.update(*pressed))
+            .collect()
+    }
+
+    #[test]
+    fn two_quick_taps_dash_once() {
+        assert_eq!(
+            taps(&[true, false, true, true, false, true]),
+            vec![false, false, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn holding_or_slow_taps_do_not_dash() {
+        assert!(!taps(&[true; 30]).contains(&true));
+
+        let mut slow = vec![true];
+        slow.extend([false; DOUBLE_TAP_FRAMES as usize + 1]);
+        slow.push(true);
+        assert!(!taps(&slow).contains(&true));
+    }
+
+    #[test]
+    fn the_dash_key_dashes_once_per_press() {
+        let mut input = DashInput::default();
+        let presses: Vec<bool> = [&[DASH_KEY][..], &[DASH_KEY], &[], &[DASH_KEY]]
+            .iter()
+            .map(|keys| input.update(&KeyState::holding(keys)))
+            .collect();
+
+        assert_eq!(presses, vec![true, false, false, true]);
+    }
+
+    #[test]
+    fn the_meter_fills_back_up_after_a_dash() {
+        let mut meter = DashMeter::default();
+        assert!(meter.ready());
+
+        meter.spend();
+        assert!(!meter.ready());
+        assert_eq!(meter.charge(), 0.0);
+        (0..COOLDOWN_FRAMES / 2).for_each(|_| meter.update());
+        assert_eq!(meter.charge(), 0.5);
+        (0..COOLDOWN_FRAMES / 2).for_each(|_| meter.update());
+        assert!(meter.ready());
+    }
+}
@@ -0,0 +1,171 @@
+use std::f32::consts::TAU;
+
+use crate::engine::{Color, Composite, Point, Rect, Renderer};
+
+const WIDTH: i16 = 600;
+const HEIGHT: i16 = 600;
+
+// A full day, noon to noon, takes a minute and a half of walking.
+const CYCLE_FRAMES: u32 = 60 * 90;
+const DUSK_WIDTH: f32 = 0.15;
+
+const WHITE: Color = Color::rgb(255, 255, 255);
+const NIGHT_TINT: Color = Color::rgb(70, 80, 150);
+const DUSK_COLOR: Color = Color::rgb(255, 130, 50);
+const DUSK_ALPHA: f32 = 0.3;
+
+const LAMP_SPACING: i32 = 450;
+const LAMP_TOP: i16 = 370;
+const LAMP_HEIGHT: i16 = 110;
+const LAMP_WIDTH: i16 = 6;
+const LAMP_PARALLAX: i32 = 2;
+const LAMP_COLOR: Color = Color::rgb(50, 50, 60);
+const GLOW_RADIUS: i16 = 80;
+const GLOW_COLOR: Color = Color::rgba(255, 220, 140, 0.8);
+
+pub struct DayCycle {
+    frame: u32,
+    scrolled: i32,
+}
+
+impl DayCycle {
+    pub fn new() -> Self {
+        DayCycle {
+            frame: 0,
+            scrolled: 0,
+        }
+    }
+
+    pub fn update(&mut self, walking_speed: i16) {
+        self.frame = (self.frame + 1) % CYCLE_FRAMES;
+        self.scrolled -= walking_speed as i32;
+    }
+
+    // 1.0 at noon, when the cycle starts, down to 0.0 at midnight.
+    fn daylight(&self) -> f32 {
+        let phase = self.frame as f32 / CYCLE_FRAMES as f32;
+        0.5 + 0.5 * (TAU * phase).cos()
+    }
+
+    // Only the night half of the cycle is darkened.
+    fn darkness(&self) -> f32 {
+        ((0.5 - self.daylight()) * 2.0).clamp(0.0, 1.0)
+    }
+
+    fn warmth(&self) -> f32 {
+        (1.0 - (self.daylight() - 0.5).abs() / DUSK_WIDTH).clamp(0.0, 1.0)
+    }
+
+    fn tints(&self) -> Vec<(Composite, Color)> {
+        let mut tints = vec![];
+        let darkness = self.darkness();
+        if darkness > 0.0 {
+            tints.push((Composite::Multiply, WHITE.lerp(&NIGHT_TINT, darkness)));
+        }
+        let warmth = self.warmth();
+        if warmth > 0.0 {
+            tints.push((
+                Composite::Screen,
+                Color {
+                    a: DUSK_ALPHA * warmth,
+                    ..DUSK_COLOR
+                },
+            ));
+        }
+        tints
+    }
+
+    // Lamps stand in the mid layer, so they scroll at half the ground's speed.
+    fn lamps(&self) -> Vec<i16> {
+        let offset = self.scrolled / LAMP_PARALLAX;
+        let first = (offset - GLOW_RADIUS as i32).div_euclid(LAMP_SPACING);
+        (first..)
+            .map(|index| (index * LAMP_SPACING - offset) as i16)
+            .skip_while(|x| *x < -GLOW_RADIUS)
+            .take_while(|x| *x < WIDTH + GLOW_RADIUS)
+            .collect()
+    }
+
+    pub fn draw_lamps(&self, renderer: &Renderer) {
+        if !renderer.detail().parallax {
+            return;
+        }
+        self.lamps().iter().for_each(|x| {
+            renderer.fill_rect(
+                &Rect::new_from_x_y(*x, LAMP_TOP, LAMP_WIDTH, LAMP_HEIGHT),
+                &LAMP_COLOR,
+            );
+        });
+    }
+
+    // Drawn over the whole scene, after everything else in the world.
+    // `view` is the top left of the visible part of the world, so the tint
+    // covers the screen however the camera has moved.
+    pub fn draw_overlay(&self, renderer: &Renderer, view: &Point) {
+        self.tints().iter().for_each(|(composite, color)| {
+            renderer.draw_composited(*composite, || {
+                renderer.fill_rect(&Rect::new_from_x_y(view.x, view.y, WIDTH, HEIGHT), color);
+            });
+        });
+
+        let darkness = self.darkness();
+        if darkness == 0.0 || !renderer.detail().parallax {
+            return;
+        }
+        let inner = Color {
+            a: GLOW_COLOR.a * darkness,
+            ..GLOW_COLOR
+        };
+        let outer = Color { a: 0.0, ..inner };
+        renderer.draw_composited(Composite::Lighter, || {
+            self.lamps().iter().for_each(|x| {
+                let center = Point {
+                    x: x + LAMP_WIDTH / 2,
+                    y: LAMP_TOP,
+                };
+                if let Err(err) =
+                    renderer.fill_radial_gradient(&center, GLOW_RADIUS, &inner, &outer)
+                {
+                    log!("Could not draw lamp glow {:#?}", err);
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at_frame(frame: u32) -> DayCycle {
+        DayCycle { frame, scrolled: 0 }
+    }
+
+    #[test]
+    fn noon_is_untinted_and_midnight_is_multiplied() {
+        assert!(at_frame(0).tints().is_empty());
+
+        let midnight = at_frame(CYCLE_FRAMES / 2);
+        assert_eq!(midnight.darkness(), 1.0);
+        assert_eq!(midnight.tints(), vec![(Composite::Multiply, NIGHT_TINT)]);
+    }
+
+    #[test]
+    fn dusk_screens_a_warm_tint() {
+        let dusk = at_frame(CYCLE_FRAMES / 4 - 10);
+        let tints = dusk.tints();
+
+        assert_eq!(tints.len(), 1);
+        assert_eq!(tints[0].0, Composite::Screen);
+        assert!(tints[0].1.a > 0.0);
+    }
+
+    #[test]
+    fn lamps_scroll_at_half_speed() {
+        let mut cycle = DayCycle::new();
+        assert_eq!(cycle.lamps(), vec![0, 450]);
+
+        (0..10).for_each(|_| cycle.update(-6));
+        assert_eq!(cycle.lamps(), vec![-30, 420]);
+    }
+}
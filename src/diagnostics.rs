@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use crate::{
+    engine::{Animations, Rect},
+    game::{Cell, Sheet},
+    hitboxes::{Hitboxes, Insets},
+};
+
+// Something wrong with a data file, found as the game loads rather than left
+// to show up later as a sprite that never draws or a box nothing hits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub file: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    problems: Vec<Problem>,
+}
+
+// Names are sorted so the same files always report the same way.
+fn sorted_names(frames: &HashMap<String, Cell>) -> Vec<&String> {
+    let mut names: Vec<&String> = frames.keys().collect();
+    names.sort();
+    names
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&#39;")
+        .replace('"', "&quot;")
+}
+
+fn inside(outer: &Rect, inner: &Rect) -> bool {
+    inner.x() >= outer.x()
+        && inner.y() >= outer.y()
+        && inner.right() <= outer.right()
+        && inner.bottom() <= outer.bottom()
+}
+
+// Insets that reach outside the sprite, or leave nothing of a `width` by
+// `height` one.
+fn bad_insets(insets: &Insets, width: i16, height: i16) -> Option<String> {
+    let Insets {
+        left,
+        top,
+        right,
+        bottom,
+    } = *insets;
+    if left.min(top).min(right).min(bottom) < 0 {
+        Some("reaches outside the sprite".to_string())
+    } else if left + right >= width || top + bottom >= height {
+        Some(format!("leaves no box on a {}x{} sprite", width, height))
+    } else {
+        None
+    }
+}
+
+impl Report {
+    pub fn add(&mut self, file: &str, message: String) {
+        self.problems.push(Problem {
+            file: file.to_string(),
+            message,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    // Sprites that `user` draws by name and `file` has no cell for.
+    pub fn check_cells(
+        &mut self,
+        file: &str,
+        frames: &HashMap<String, Cell>,
+        names: &[&str],
+        user: &str,
+    ) {
+        names
+            .iter()
+            .filter(|name| !frames.contains_key(**name))
+            .for_each(|name| self.add(file, format!("{} needs a sprite named {}", user, name)));
+    }
+
+    // Cells whose region runs past the edge of the `width` by `height` image
+    // they are cut from.
+    pub fn check_frames_in_image(
+        &mut self,
+        file: &str,
+        frames: &HashMap<String, Cell>,
+        width: i16,
+        height: i16,
+    ) {
+        let image = Rect::new_from_x_y(0, 0, width, height);
+        sorted_names(frames)
+            .into_iter()
+            .filter(|name| !inside(&image, &frames[*name].atlas_rect()))
+            .for_each(|name| {
+                self.add(
+                    file,
+                    format!("{} lies outside its {}x{} image", name, width, height),
+                )
+            });
+    }
+
+    // Animations in `names` that the sheet has no frames for, frame tags with
+    // no frames, and tags naming frames the sheet lacks.
+    pub fn check_animations(&mut self, file: &str, sheet: &Sheet, names: &[&str]) {
+        let animations = Animations::from_sheet(sheet);
+        names.iter().for_each(|name| match animations.get(name) {
+            Some(animation) if !animation.frame_names().is_empty() => {}
+            _ => self.add(file, format!("The {} animation has no frames", name)),
+        });
+        sheet.tags.iter().for_each(|tag| {
+            if tag.frames.is_empty() {
+                self.add(file, format!("Frame tag {} has no frames", tag.name));
+            }
+            tag.frames
+                .iter()
+                .filter(|frame| !sheet.frames.contains_key(*frame))
+                .for_each(|frame| {
+                    self.add(
+                        file,
+                        format!("Frame tag {} names a missing frame {}", tag.name, frame),
+                    )
+                });
+        });
+    }
+
+    // Collision insets that reach outside their sprites or leave nothing of
+    // them. Frames are checked against their own size and the default and
+    // state insets against the smallest frame.
+    pub fn check_hitboxes(&mut self, file: &str, hitboxes: &Hitboxes, sheet: &Sheet) {
+        let smallest = sheet.frames.values().fold(None, |smallest, cell| {
+            let (width, height) = smallest.unwrap_or((cell.frame.w, cell.frame.h));
+            Some((width.min(cell.frame.w), height.min(cell.frame.h)))
+        });
+        if let Some((width, height)) = smallest {
+            let shared = std::iter::once(("default", &hitboxes.default)).chain(
+                hitboxes
+                    .states
+                    .iter()
+                    .map(|(state, insets)| (state.as_str(), insets)),
+            );
+            shared.for_each(|(name, insets)| {
+                if let Some(problem) = bad_insets(insets, width, height) {
+                    self.add(file, format!("The {} hitbox {}", name, problem));
+                }
+            });
+        }
+        hitboxes
+            .frames
+            .iter()
+            .for_each(|(name, insets)| match sheet.frames.get(name) {
+                Some(cell) => {
+                    if let Some(problem) = bad_insets(insets, cell.frame.w, cell.frame.h) {
+                        self.add(file, format!("The hitbox for {} {}", name, problem));
+                    }
+                }
+                None => self.add(
+                    file,
+                    format!("There is no frame {} to fit a hitbox to", name),
+                ),
+            });
+    }
+
+    // Bounding boxes sticking out past `bounds`, the sprites drawn for them.
+    pub fn check_bounding_boxes(&mut self, file: &str, user: &str, bounds: &Rect, boxes: &[Rect]) {
+        boxes
+            .iter()
+            .filter(|bounding_box| !inside(bounds, bounding_box))
+            .for_each(|bounding_box| {
+                self.add(
+                    file,
+                    format!(
+                        "{} has a bounding box at {}, {} sized {}x{} outside its {}x{} sprites",
+                        user,
+                        bounding_box.x(),
+                        bounding_box.y(),
+                        bounding_box.width,
+                        bounding_box.height,
+                        bounds.width,
+                        bounds.height
+                    ),
+                )
+            });
+    }
+
+    pub fn html(&self) -> String {
+        let problems: String = self
+            .problems
+            .iter()
+            .map(|problem| {
+                format!(
+                    "<li><b>{}</b>: {}</li>",
+                    escape(&problem.file),
+                    escape(&problem.message)
+                )
+            })
+            .collect();
+        format!(
+            "<div class='diagnostics'><h2>Problems with the game data</h2><ul>{}</ul><button class='play_anyway'>Play anyway</button></div>",
+            problems
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{FrameTag, SheetRect};
+
+    fn cell(x: i16, y: i16, w: i16, h: i16) -> Cell {
+        Cell {
+            frame: SheetRect { x, y, w, h },
+            sprite_source_size: SheetRect::default(),
+            rotated: false,
+            trimmed: false,
+            duration: None,
+        }
+    }
+
+    fn sheet() -> Sheet {
+        Sheet {
+            frames: [
+                ("Run (1).png".to_string(), cell(0, 0, 100, 100)),
+                ("Run (2).png".to_string(), cell(100, 0, 100, 100)),
+                ("Jump (1).png".to_string(), cell(200, 0, 80, 120)),
+            ]
+            .into_iter()
+            .collect(),
+            tags: vec![
+                FrameTag {
+                    name: "Blink".to_string(),
+                    frames: vec![],
+                },
+                FrameTag {
+                    name: "Yawn".to_string(),
+                    frames: vec!["Run (1).png".to_string(), "Yawn (1).png".to_string()],
+                },
+            ],
+        }
+    }
+
+    fn messages(report: &Report) -> Vec<&str> {
+        report
+            .problems
+            .iter()
+            .map(|problem| problem.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn missing_cells_and_frames_off_the_image_are_reported() {
+        let sheet = sheet();
+        let mut report = Report::default();
+        report.check_cells(
+            "tiles.json",
+            &sheet.frames,
+            &["Run (1).png", "13.png"],
+            "Platforms",
+        );
+        report.check_frames_in_image("rhb.json", &sheet.frames, 250, 120);
+
+        assert_eq!(
+            messages(&report),
+            vec![
+                "Platforms needs a sprite named 13.png",
+                "Jump (1).png lies outside its 250x120 image",
+            ]
+        );
+        assert_eq!(report.problems[1].file, "rhb.json");
+    }
+
+    #[test]
+    fn animations_need_frames_that_exist() {
+        let mut report = Report::default();
+        report.check_animations("rhb.json", &sheet(), &["Run", "Slide"]);
+
+        assert_eq!(
+            messages(&report),
+            vec![
+                "The Slide animation has no frames",
+                "Frame tag Blink has no frames",
+                "Frame tag Yawn names a missing frame Yawn (1).png",
+            ]
+        );
+    }
+
+    #[test]
+    fn hitboxes_must_fit_their_sprites() {
+        let mut hitboxes = Hitboxes::default();
+        hitboxes.states.insert(
+            "Sliding".to_string(),
+            Insets {
+                left: -4,
+                top: 0,
+                right: 0,
+                bottom: 0,
+            },
+        );
+        hitboxes.frames.insert(
+            "Run (2).png".to_string(),
+            Insets {
+                left: 60,
+                top: 0,
+                right: 40,
+                bottom: 0,
+            },
+        );
+        hitboxes
+            .frames
+            .insert("Dead (1).png".to_string(), Insets::default());
+        let mut report = Report::default();
+        report.check_hitboxes("hitboxes.json", &hitboxes, &sheet());
+
+        assert_eq!(
+            messages(&report),
+            vec![
+                "The Sliding hitbox reaches outside the sprite",
+                "There is no frame Dead (1).png to fit a hitbox to",
+                "The hitbox for Run (2).png leaves no box on a 100x100 sprite",
+            ]
+        );
+    }
+
+    #[test]
+    fn bounding_boxes_stay_on_their_sprites() {
+        let mut report = Report::default();
+        let bounds = Rect::new_from_x_y(0, 0, 384, 93);
+        report.check_bounding_boxes(
+            "segments",
+            "Platform",
+            &bounds,
+            &[
+                Rect::new_from_x_y(0, 0, 60, 54),
+                Rect::new_from_x_y(324, 0, 70, 54),
+            ],
+        );
+
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].message.contains("324, 0 sized 70x54"));
+    }
+
+    #[test]
+    fn reports_escape_what_they_show() {
+        let mut report = Report::default();
+        report.add("<pack>", "A & B".to_string());
+
+        assert!(report.html().contains("<b>&lt;pack&gt;</b>: A &amp; B"));
+    }
+}
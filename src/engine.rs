@@ -1,100 +1,440 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
+
+mod animation;
+mod assets;
+mod atlas;
+mod audio_sprite;
+mod boot;
+mod collision_mask;
+mod color;
+mod detail;
+mod event_bus;
+mod frame_cap;
+mod gamepad;
+mod hud_layout;
+mod layers;
+pub mod memory;
+mod mixer;
+mod nine_slice;
+pub mod physics;
+pub mod placeholder;
+mod shadow;
+mod speech_bubble;
+mod splash;
+mod text;
+mod transition;
+
+pub use animation::{Animation, Animations, Playback};
+pub use assets::{Assets, Quality};
+pub use atlas::AtlasPage;
+pub use audio_sprite::{AudioSprite, Clip};
+use boot::BootScene;
+pub use collision_mask::CollisionMask;
+pub use color::Color;
+pub use detail::{AutoDetail, Detail, GraphicsSetting};
+pub use event_bus::EventBus;
+use frame_cap::FrameCap;
+pub use frame_cap::FrameRateSetting;
+use gamepad::GamepadInput;
+pub use gamepad::{Haptics, Impact, Rumble, RumbleSetting};
+pub use hud_layout::{Anchor, HudLayout};
+pub use layers::{DrawList, Layer, Layers};
+use memory::Tracked;
+use mixer::LiveSources;
+pub use mixer::{Bus, Fader, MixerSetting, PauseMusicSetting};
+pub use nine_slice::NineSlice;
+pub use shadow::draw_shadow;
+pub use speech_bubble::SpeechBubble;
+pub use text::{wrap_text, Align, TextStyle};
+pub use transition::{Transition, TransitionEffect};
 
 use crate::{
-    browser::{self, LoopClosure},
-    game::{Cell, Sheet},
+    browser::{self, EventListenerRegistry, LoopClosure, Target},
+    game::{Cell, FrameTag, Sheet},
     sound::{self},
 };
 use anyhow::*;
 use async_trait::async_trait;
-use futures::channel::{
-    mpsc::{unbounded, UnboundedReceiver},
-    oneshot::channel,
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot::channel,
+    },
+    future::{select, Either},
 };
 
 use std::result::Result::Ok;
 use std::sync::Mutex;
 use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
-use web_sys::{AudioBuffer, AudioContext, CanvasRenderingContext2d, HtmlElement, HtmlImageElement};
+use web_sys::{
+    AudioBuffer, AudioBufferSourceNode, AudioContext, CanvasRenderingContext2d, GainNode,
+    HtmlCanvasElement, HtmlElement, HtmlImageElement,
+};
 
 #[async_trait(?Send)]
 pub trait Game {
     async fn initialize(&self) -> Result<Box<dyn Game>>;
-    fn update(&mut self, keystate: &KeyState);
+    // `delta` is how many milliseconds the update stands for.
+    fn update(&mut self, keystate: &KeyState, delta: f64);
     fn draw(&self, renderer: &Renderer);
+    // Milliseconds each fixed update stands for, read once the game is
+    // initialized.
+    fn frame_size(&self) -> f32 {
+        FRAME_SIZE
+    }
 }
 
-const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
+const TEXT_FONT: &str = "16pt serif";
+const FLOATING_TEXT_OUTLINE: Color = Color::rgb(255, 255, 255);
+// How loud the music stays while paused, when it is lowered.
+const PAUSED_MUSIC_GAIN: f32 = 0.3;
+// The step at the default tick rate of 60Hz.
+pub const FRAME_SIZE: f32 = 1.0 / 60.0 * 1000.0;
 pub struct GameLoop {
     last_frame: f64,
     accumulated_delta: f32,
+    frame_rate: FrameRate,
+    auto_detail: AutoDetail,
+    frame_cap: FrameCap,
+    context_lost: bool,
+    // Input and window listeners, taken off when the loop is dropped.
+    _listeners: EventListenerRegistry,
 }
 
 type SharedLoopClosure = Rc<RefCell<Option<LoopClosure>>>;
+type SharedTimerClosure = Rc<RefCell<Option<Closure<dyn FnMut()>>>>;
+
+// Stops the game loop it was given to. The loop lets go of its own closure
+// the next time it would run, so the game and its listeners are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct LoopHandle {
+    stopped: Rc<std::cell::Cell<bool>>,
+}
+
+impl LoopHandle {
+    pub fn stop(&self) {
+        self.stopped.set(true);
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped.get()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoopDriver {
+    AnimationFrame,
+    Timer { interval: i32 },
+}
+
+impl LoopDriver {
+    // "animation_frame" or "timer", as an embedding page or `?driver=` names them.
+    pub fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "animation_frame" => Ok(LoopDriver::AnimationFrame),
+            "timer" => Ok(LoopDriver::Timer {
+                interval: FRAME_SIZE as i32,
+            }),
+            _ => Err(anyhow!("Unknown loop driver {}", name)),
+        }
+    }
+
+    pub fn detect() -> Result<Self> {
+        if browser::has_animation_frame()? {
+            Ok(LoopDriver::AnimationFrame)
+        } else {
+            Ok(LoopDriver::Timer {
+                interval: FRAME_SIZE as i32,
+            })
+        }
+    }
+}
 
 impl GameLoop {
-    pub async fn start(game: impl Game + 'static) -> Result<()> {
-        let mut keyevent_receiver = prepare_input()?;
-        let mut game = game.initialize().await?;
+    pub async fn start(
+        game: impl Game + 'static,
+        canvas: HtmlCanvasElement,
+        driver: LoopDriver,
+        handle: LoopHandle,
+    ) -> Result<()> {
+        let mut listeners = EventListenerRegistry::default();
+        let mut keyevent_receiver = prepare_input(&canvas, &mut listeners)?;
+        let mut renderer = Renderer {
+            context: browser::context(&canvas)?,
+            detail: RefCell::new(Detail::default()),
+            hud: RefCell::new(HudLayout::default()),
+        };
+        let mut resize_receiver = add_resize_handler(&mut listeners)?;
+        let mut context_receiver = add_context_handler(&canvas, &mut listeners)?;
+        renderer.measure_hud(&canvas);
+        let mut keystate = KeyState::new();
+        let mut gamepad_input = GamepadInput::default();
+
+        let mut game = match (&driver, BootScene::load("boot.json").await) {
+            (LoopDriver::AnimationFrame, Ok(mut scene)) => {
+                let boot =
+                    run_boot_scene(&mut scene, &renderer, &mut keystate, &mut keyevent_receiver);
+                let game = match select(Box::pin(game.initialize()), Box::pin(boot)).await {
+                    Either::Left((game, _boot)) => game?,
+                    Either::Right((err, _initialize)) => return Err(err),
+                };
+                // A game that loads quickly still lets the splash finish.
+                while scene.splash_playing() {
+                    boot_frame(&mut scene, &renderer, &mut keystate, &mut keyevent_receiver)
+                        .await?;
+                }
+                game
+            }
+            (_, boot) => {
+                if let Err(err) = boot {
+                    log!("Loading without a boot scene {:#?}", err);
+                }
+                game.initialize().await?
+            }
+        };
 
+        let battery = browser::battery().await.unwrap_or_else(|err| {
+            log!("Running without battery status {:#?}", err);
+            None
+        });
+        let mut frame_cap = FrameCap::new(battery);
+        frame_cap.update(FrameRateSetting::load());
         let mut game_loop = GameLoop {
             last_frame: browser::now()?,
             accumulated_delta: 0.0,
+            frame_rate: FrameRate::default(),
+            auto_detail: AutoDetail::new(GraphicsSetting::load()),
+            frame_cap,
+            context_lost: false,
+            _listeners: listeners,
         };
 
-        let renderer = Renderer {
-            context: browser::context()?,
-        };
-
-        let f: SharedLoopClosure = Rc::new(RefCell::new(None));
-        let g = f.clone();
-
-        let mut keystate = KeyState::new();
-        *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
+        let frame_size = game.frame_size();
+        let mut frame = move |perf: f64| {
+            if game_loop.frame_cap.skip() {
+                return;
+            }
             process_input(&mut keystate, &mut keyevent_receiver);
-            
+            gamepad_input.poll(&mut keystate);
+            // Turning a phone moves the notch, so the safe area is measured again.
+            let mut resized = false;
+            while let Ok(Some(())) = resize_receiver.try_next() {
+                resized = true;
+            }
+            if resized {
+                renderer.measure_hud(&canvas);
+            }
+            // Nothing is drawn or simulated while the canvas has no context,
+            // and the time it was gone is not caught up on when it returns.
+            let (lost, restored) = context_changes(
+                game_loop.context_lost,
+                std::iter::from_fn(|| context_receiver.try_next().ok().flatten()),
+            );
+            game_loop.context_lost = lost;
+            if lost {
+                game_loop.last_frame = perf;
+                return;
+            }
+            if restored {
+                if let Err(err) = renderer.reacquire(&canvas) {
+                    log!("Could not get the canvas context back {:#?}", err);
+                }
+                game_loop.last_frame = perf;
+            }
+
             let frame_time = perf - game_loop.last_frame;
             game_loop.accumulated_delta += frame_time as f32;
 
-            while game_loop.accumulated_delta > FRAME_SIZE {
-                game.update(&keystate);
-                game_loop.accumulated_delta -= FRAME_SIZE;
+            while game_loop.accumulated_delta > frame_size {
+                game.update(&keystate, f64::from(frame_size));
+                keystate.clear_clicks();
+                game_loop.accumulated_delta -= frame_size;
             }
 
             game_loop.last_frame = perf;
             game.draw(&renderer);
 
-            if cfg!(debug_assertions) {
-                unsafe {
-                    draw_frame_rate(&renderer, frame_time);
+            // The settings are re-read once per window so changes in the settings
+            // screen apply without a restart. A capped frame rate is not a
+            // slow one, so detail is judged per animation frame.
+            if let Some(average_frame_time) = game_loop.frame_rate.update(frame_time) {
+                game_loop.auto_detail.set_setting(GraphicsSetting::load());
+                game_loop
+                    .auto_detail
+                    .record(average_frame_time / game_loop.frame_cap.interval());
+                game_loop.frame_cap.update(FrameRateSetting::load());
+                renderer.set_detail(game_loop.auto_detail.detail());
+                match browser::memory_bytes() {
+                    Ok(bytes) => memory::set(Tracked::WasmKb, bytes as usize / 1024),
+                    Err(err) => {
+                        log!("Could not measure wasm memory {:#?}", err);
+                    }
                 }
             }
+            if cfg!(debug_assertions) {
+                game_loop.frame_rate.draw(&renderer);
+                memory::draw(&renderer);
+            }
+        };
 
-            browser::request_animation_frame(f.borrow().as_ref().unwrap())
-                .expect("Error in Requesting frame animation from browser!");
-        }));
+        match driver {
+            LoopDriver::AnimationFrame => {
+                let f: SharedLoopClosure = Rc::new(RefCell::new(None));
+                let g = f.clone();
 
-        browser::request_animation_frame(
-            g.borrow()
-                .as_ref()
-                .ok_or_else(|| anyhow!("GameLoop: Loop is None"))?,
-        )?;
+                *g.borrow_mut() = Some(browser::create_raf_closure(move |perf: f64| {
+                    if handle.stopped() {
+                        // Breaks the cycle, so the loop is dropped once this returns.
+                        f.borrow_mut().take();
+                        return;
+                    }
+                    frame(perf);
+                    browser::request_animation_frame(f.borrow().as_ref().unwrap())
+                        .expect("Error in Requesting frame animation from browser!");
+                }));
+
+                browser::request_animation_frame(
+                    g.borrow()
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("GameLoop: Loop is None"))?,
+                )?;
+            }
+            LoopDriver::Timer { interval } => {
+                let timer: SharedTimerClosure = Rc::new(RefCell::new(None));
+                let interval_id = Rc::new(std::cell::Cell::new(None));
+                let (own_timer, own_interval_id) = (timer.clone(), interval_id.clone());
+
+                let on_interval = browser::closure_wrap(Box::new(move || {
+                    if handle.stopped() {
+                        if let Some(id) = own_interval_id.get() {
+                            browser::clear_interval(id);
+                        }
+                        own_timer.borrow_mut().take();
+                        return;
+                    }
+                    frame(browser::now().expect("Could not read the current time"));
+                }) as Box<dyn FnMut()>);
+                *timer.borrow_mut() = Some(on_interval);
+
+                interval_id.set(Some(browser::set_interval(
+                    timer
+                        .borrow()
+                        .as_ref()
+                        .ok_or_else(|| anyhow!("GameLoop: Timer is None"))?,
+                    interval,
+                )?));
+            }
+        }
         Ok(())
     }
+
+    #[allow(dead_code)]
+    pub async fn headless(game: impl Game + 'static) -> Result<HeadlessLoop> {
+        Ok(HeadlessLoop {
+            game: game.initialize().await?,
+            keystate: KeyState::new(),
+        })
+    }
 }
 
-#[derive(Default)]
+// Steps the game one fixed update at a time without a canvas or a browser clock,
+// for tests and benchmarks.
+#[allow(dead_code)]
+pub struct HeadlessLoop {
+    game: Box<dyn Game>,
+    keystate: KeyState,
+}
+
+#[allow(dead_code)]
+impl HeadlessLoop {
+    pub fn press(&mut self, code: &str) {
+        self.keystate.set_pressed(code);
+    }
+
+    pub fn release(&mut self, code: &str) {
+        self.keystate.set_released(code);
+    }
+
+    // The click is seen by the next tick only.
+    pub fn click(&mut self, position: Point) {
+        self.keystate.add_click(position);
+    }
+
+    pub fn tick(&mut self) {
+        let frame_size = self.game.frame_size();
+        self.game.update(&self.keystate, f64::from(frame_size));
+        self.keystate.clear_clicks();
+    }
+
+    pub fn run(&mut self, ticks: u32) {
+        (0..ticks).for_each(|_| self.tick());
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Rect {
     pub position: Point,
     pub width: i16,
     pub height: i16,
 }
 
+// Canvas blend modes used by lighting passes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Composite {
+    Multiply,
+    Screen,
+    Lighter,
+}
+
+impl Composite {
+    fn operation(&self) -> &'static str {
+        match self {
+            Composite::Multiply => "multiply",
+            Composite::Screen => "screen",
+            Composite::Lighter => "lighter",
+        }
+    }
+}
+
 pub struct Renderer {
     context: CanvasRenderingContext2d,
+    detail: RefCell<Detail>,
+    hud: RefCell<HudLayout>,
 }
 
 impl Renderer {
+    // Optional effects check this before drawing.
+    pub fn detail(&self) -> Detail {
+        *self.detail.borrow()
+    }
+
+    fn set_detail(&self, detail: Detail) {
+        *self.detail.borrow_mut() = detail;
+    }
+
+    // Where HUD elements go on the canvas as it is shown now.
+    pub fn hud(&self) -> HudLayout {
+        *self.hud.borrow()
+    }
+
+    // A restored canvas hands its context back with every setting reset.
+    // Each draw sets what it needs, and images are drawn from their elements
+    // rather than kept on the canvas, so the next frame redraws in full.
+    fn reacquire(&mut self, canvas: &HtmlCanvasElement) -> Result<()> {
+        self.context = browser::context(canvas)?;
+        self.measure_hud(canvas);
+        Ok(())
+    }
+
+    fn measure_hud(&self, canvas: &HtmlCanvasElement) {
+        match HudLayout::measure(canvas) {
+            Ok(hud) => *self.hud.borrow_mut() = hud,
+            Err(err) => {
+                log!("Could not measure the safe area {:#?}", err);
+            }
+        }
+    }
+
     pub fn clear(&self, rect: &Rect) {
         self.context.clear_rect(
             rect.x().into(),
@@ -110,13 +450,14 @@ impl Renderer {
         frame: &Rect,
         destination: &Rect,
     ) -> Result<()> {
+        let scale = source_scale(image);
         self.context
             .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
                 &image,
-                frame.x().into(),
-                frame.y().into(),
-                frame.width.into(),
-                frame.height.into(),
+                f64::from(frame.x()) * scale,
+                f64::from(frame.y()) * scale,
+                f64::from(frame.width) * scale,
+                f64::from(frame.height) * scale,
                 destination.x().into(),
                 destination.y().into(),
                 destination.width.into(),
@@ -125,20 +466,440 @@ impl Renderer {
             .expect("Drawing is throwing exception! Unrecoverable error.");
         Ok(())
     }
+    fn draw_rotated_image(
+        &self,
+        image: &HtmlImageElement,
+        frame: &Rect,
+        destination: &Rect,
+    ) -> Result<()> {
+        self.context.save();
+        self.context
+            .translate(destination.x().into(), destination.bottom().into())
+            .map_err(|err| anyhow!("Error translating canvas {:#?}", err))?;
+        self.context
+            .rotate(-std::f64::consts::FRAC_PI_2)
+            .map_err(|err| anyhow!("Error rotating canvas {:#?}", err))?;
+        let scale = source_scale(image);
+        let drawn = self
+            .context
+            .draw_image_with_html_image_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                image,
+                f64::from(frame.x()) * scale,
+                f64::from(frame.y()) * scale,
+                f64::from(frame.width) * scale,
+                f64::from(frame.height) * scale,
+                0.0,
+                0.0,
+                destination.height.into(),
+                destination.width.into(),
+            )
+            .map_err(|err| anyhow!("Error drawing rotated image {:#?}", err));
+        self.context.restore();
+        drawn
+    }
+
+    pub fn draw_cell(&self, image: &HtmlImageElement, cell: &Cell, position: &Point) -> Result<()> {
+        self.draw_cell_into(image, cell, &cell.destination(position))
+    }
+
+    // Stretches the cell over `destination`, for zoomed in views.
+    pub fn draw_cell_into(
+        &self,
+        image: &HtmlImageElement,
+        cell: &Cell,
+        destination: &Rect,
+    ) -> Result<()> {
+        if cell.rotated {
+            self.draw_rotated_image(image, &cell.atlas_rect(), destination)
+        } else {
+            self.draw_image(image, &cell.atlas_rect(), destination)
+        }
+    }
+
+    pub fn draw_nine_slice(
+        &self,
+        image: &HtmlImageElement,
+        source: &Rect,
+        slice: &NineSlice,
+        destination: &Rect,
+    ) -> Result<()> {
+        slice
+            .slices(source, destination)
+            .iter()
+            .try_for_each(|(source, destination)| self.draw_image(image, source, destination))
+    }
+
+    pub fn draw_tiled(
+        &self,
+        image: &HtmlImageElement,
+        source: &Rect,
+        destination: &Rect,
+    ) -> Result<()> {
+        tile_rects(source, destination)
+            .iter()
+            .try_for_each(|(source, destination)| self.draw_image(image, source, destination))
+    }
+
+    #[allow(dead_code)]
+    pub fn fill_rect(&self, rect: &Rect, color: &Color) {
+        self.context.save();
+        self.context
+            .set_fill_style(&JsValue::from_str(&color.to_css()));
+        self.context.fill_rect(
+            rect.x().into(),
+            rect.y().into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+        self.context.restore();
+    }
+
+    pub fn stroke_rect(&self, rect: &Rect, color: &Color) {
+        self.context.save();
+        self.context
+            .set_stroke_style(&JsValue::from_str(&color.to_css()));
+        self.context.stroke_rect(
+            rect.x().into(),
+            rect.y().into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+        self.context.restore();
+    }
+
+    pub fn fill_vertical_gradient(&self, rect: &Rect, top: &Color, bottom: &Color) -> Result<()> {
+        let gradient =
+            self.context
+                .create_linear_gradient(0.0, rect.y().into(), 0.0, rect.bottom().into());
+        gradient
+            .add_color_stop(0.0, &top.to_css())
+            .map_err(|err| anyhow!("Error adding gradient color stop {:#?}", err))?;
+        gradient
+            .add_color_stop(1.0, &bottom.to_css())
+            .map_err(|err| anyhow!("Error adding gradient color stop {:#?}", err))?;
+        self.context.save();
+        self.context.set_fill_style(&gradient);
+        self.context.fill_rect(
+            rect.x().into(),
+            rect.y().into(),
+            rect.width.into(),
+            rect.height.into(),
+        );
+        self.context.restore();
+        Ok(())
+    }
+
+    pub fn fill_radial_gradient(
+        &self,
+        center: &Point,
+        radius: i16,
+        inner: &Color,
+        outer: &Color,
+    ) -> Result<()> {
+        let (x, y) = (center.x.into(), center.y.into());
+        let gradient = self
+            .context
+            .create_radial_gradient(x, y, 0.0, x, y, radius.into())
+            .map_err(|err| anyhow!("Error creating radial gradient {:#?}", err))?;
+        gradient
+            .add_color_stop(0.0, &inner.to_css())
+            .map_err(|err| anyhow!("Error adding gradient color stop {:#?}", err))?;
+        gradient
+            .add_color_stop(1.0, &outer.to_css())
+            .map_err(|err| anyhow!("Error adding gradient color stop {:#?}", err))?;
+        self.context.save();
+        self.context.set_fill_style(&gradient);
+        self.context.fill_rect(
+            (center.x - radius).into(),
+            (center.y - radius).into(),
+            (radius * 2).into(),
+            (radius * 2).into(),
+        );
+        self.context.restore();
+        Ok(())
+    }
+
+    pub fn fill_ellipse(
+        &self,
+        center: &Point,
+        radius_x: i16,
+        radius_y: i16,
+        color: &Color,
+    ) -> Result<()> {
+        self.context.begin_path();
+        self.context
+            .ellipse(
+                center.x.into(),
+                center.y.into(),
+                radius_x.max(0).into(),
+                radius_y.max(0).into(),
+                0.0,
+                0.0,
+                std::f64::consts::TAU,
+            )
+            .map_err(|err| anyhow!("Error drawing ellipse {:#?}", err))?;
+        self.context.save();
+        self.context
+            .set_fill_style(&JsValue::from_str(&color.to_css()));
+        self.context.fill();
+        self.context.restore();
+        Ok(())
+    }
+
+    pub fn fill_circle(&self, center: &Point, radius: i16, color: &Color) -> Result<()> {
+        self.context.begin_path();
+        self.context
+            .arc(
+                center.x.into(),
+                center.y.into(),
+                radius.into(),
+                0.0,
+                std::f64::consts::TAU,
+            )
+            .map_err(|err| anyhow!("Error drawing arc {:#?}", err))?;
+        self.context.save();
+        self.context
+            .set_fill_style(&JsValue::from_str(&color.to_css()));
+        self.context.fill();
+        self.context.restore();
+        Ok(())
+    }
+
+    // Fills everything but the circle, by drawing the circle the other way
+    // round so it cuts a hole in the canvas-sized rectangle.
+    pub fn fill_outside_circle(&self, center: &Point, radius: i16, color: &Color) -> Result<()> {
+        let canvas = self
+            .context
+            .canvas()
+            .ok_or_else(|| anyhow!("No canvas to fill"))?;
+        self.context.begin_path();
+        self.context
+            .rect(0.0, 0.0, canvas.width().into(), canvas.height().into());
+        self.context
+            .arc_with_anticlockwise(
+                center.x.into(),
+                center.y.into(),
+                radius.max(0).into(),
+                0.0,
+                std::f64::consts::TAU,
+                true,
+            )
+            .map_err(|err| anyhow!("Error drawing arc {:#?}", err))?;
+        self.context.save();
+        self.context
+            .set_fill_style(&JsValue::from_str(&color.to_css()));
+        self.context.fill();
+        self.context.restore();
+        Ok(())
+    }
+
+    pub fn fill_polygon(&self, points: &[Point], color: &Color) {
+        if let Some((first, rest)) = points.split_first() {
+            self.context.begin_path();
+            self.context.move_to(first.x.into(), first.y.into());
+            rest.iter()
+                .for_each(|point| self.context.line_to(point.x.into(), point.y.into()));
+            self.context.close_path();
+            self.context.save();
+            self.context
+                .set_fill_style(&JsValue::from_str(&color.to_css()));
+            self.context.fill();
+            self.context.restore();
+        }
+    }
+
+    pub fn draw_line(&self, from: &Point, to: &Point, width: f64, color: &Color) {
+        self.context.save();
+        self.context
+            .set_stroke_style(&JsValue::from_str(&color.to_css()));
+        self.context.set_line_width(width);
+        self.context.begin_path();
+        self.context.move_to(from.x.into(), from.y.into());
+        self.context.line_to(to.x.into(), to.y.into());
+        self.context.stroke();
+        self.context.restore();
+    }
+
+    // Blends everything drawn inside `draw` onto the canvas with `composite`.
+    pub fn draw_composited(&self, composite: Composite, draw: impl FnOnce()) {
+        self.context.save();
+        if let Err(err) = self
+            .context
+            .set_global_composite_operation(composite.operation())
+        {
+            log!("Could not set composite operation {:#?}", err);
+        }
+        draw();
+        self.context.restore();
+    }
+
+    // Draws everything inside `draw` at `alpha` opacity, from 0.0 to 1.0.
+    pub fn draw_with_alpha(&self, alpha: f64, draw: impl FnOnce()) {
+        self.context.save();
+        self.context.set_global_alpha(alpha.clamp(0.0, 1.0));
+        draw();
+        self.context.restore();
+    }
+
+    // Flips everything drawn inside `draw` around the vertical center line.
+    // Shifts everything drawn inside `draw` by `offset`, like moving a camera
+    // the other way.
+    pub fn draw_translated(&self, offset: &Point, draw: impl FnOnce()) {
+        self.context.save();
+        if let Err(err) = self.context.translate(offset.x.into(), offset.y.into()) {
+            log!("Could not translate canvas {:#?}", err);
+        }
+        draw();
+        self.context.restore();
+    }
+
+    pub fn draw_mirrored(&self, width: i16, draw: impl FnOnce()) {
+        self.context.save();
+        if let Err(err) = self
+            .context
+            .translate(width.into(), 0.0)
+            .and_then(|_unit| self.context.scale(-1.0, 1.0))
+        {
+            log!("Could not mirror canvas {:#?}", err);
+        }
+        draw();
+        self.context.restore();
+    }
+
     pub fn draw_entire_image(&self, image: &HtmlImageElement, position: &Point) {
         self.context
-            .draw_image_with_html_image_element(image, position.x.into(), position.y.into())
+            .draw_image_with_html_image_element_and_dw_and_dh(
+                image,
+                position.x.into(),
+                position.y.into(),
+                image.width().into(),
+                image.height().into(),
+            )
             .expect("Drawing is throwing exceptions! Unrecoverable error.");
     }
 
-    #[allow(dead_code)]
     pub fn draw_text(&self, text: &str, location: &Point) -> Result<()> {
-        self.context.set_font("16pt serif");
+        self.context.set_font(TEXT_FONT);
         self.context
             .fill_text(text, location.x.into(), location.y.into())
             .map_err(|err| anyhow!("Error filling text {:#?}", err))?;
         Ok(())
     }
+
+    pub fn measure_text(&self, text: &str) -> Result<i16> {
+        self.context.set_font(TEXT_FONT);
+        let metrics = self
+            .context
+            .measure_text(text)
+            .map_err(|err| anyhow!("Error measuring text {:#?}", err))?;
+        Ok(metrics.width().ceil() as i16)
+    }
+
+    // Draws each line `style.line_height` apart from `location`, which is the
+    // left, center or right of the first baseline depending on the alignment.
+    // Returns the height the lines took up.
+    pub fn draw_styled_text(&self, text: &str, location: &Point, style: &TextStyle) -> Result<i16> {
+        let lines = match style.max_width {
+            Some(max_width) => wrap_text(text, max_width, |line| self.measure_text(line))?,
+            None => text.lines().map(str::to_string).collect(),
+        };
+
+        self.context.save();
+        self.context.set_font(TEXT_FONT);
+        self.context.set_text_align(style.align.css());
+        let drawn = lines.iter().enumerate().try_for_each(|(index, line)| {
+            let x: f64 = location.x.into();
+            let y = f64::from(location.y + index as i16 * style.line_height);
+            if let Some((color, offset)) = &style.shadow {
+                self.context
+                    .set_fill_style(&JsValue::from_str(&color.to_css()));
+                self.context
+                    .fill_text(line, x + f64::from(offset.x), y + f64::from(offset.y))
+                    .map_err(|err| anyhow!("Error filling text shadow {:#?}", err))?;
+            }
+            if let Some((color, width)) = &style.outline {
+                self.context
+                    .set_stroke_style(&JsValue::from_str(&color.to_css()));
+                self.context.set_line_width(f64::from(*width));
+                self.context.set_line_join("round");
+                self.context
+                    .stroke_text(line, x, y)
+                    .map_err(|err| anyhow!("Error stroking text {:#?}", err))?;
+            }
+            self.context
+                .set_fill_style(&JsValue::from_str(&style.color.to_css()));
+            self.context
+                .fill_text(line, x, y)
+                .map_err(|err| anyhow!("Error filling text {:#?}", err))
+        });
+        self.context.restore();
+        drawn?;
+        Ok(lines.len() as i16 * style.line_height)
+    }
+}
+
+// Reduced quality images are smaller than the sheets describing them but keep
+// the original size as their width, so source rectangles are scaled to match.
+fn source_scale(image: &HtmlImageElement) -> f64 {
+    if image.width() == 0 {
+        1.0
+    } else {
+        f64::from(image.natural_width()) / f64::from(image.width())
+    }
+}
+
+// Plays the boot scene once per animation frame until the caller stops polling,
+// which happens as soon as the game has finished loading.
+async fn run_boot_scene(
+    scene: &mut BootScene,
+    renderer: &Renderer,
+    keystate: &mut KeyState,
+    keyevent_receiver: &mut UnboundedReceiver<KeyPress>,
+) -> Error {
+    loop {
+        if let Err(err) = boot_frame(scene, renderer, keystate, keyevent_receiver).await {
+            return err;
+        }
+    }
+}
+
+async fn boot_frame(
+    scene: &mut BootScene,
+    renderer: &Renderer,
+    keystate: &mut KeyState,
+    keyevent_receiver: &mut UnboundedReceiver<KeyPress>,
+) -> Result<()> {
+    browser::next_animation_frame().await?;
+    process_input(keystate, keyevent_receiver);
+    scene.update(keystate);
+    keystate.clear_clicks();
+    scene.draw(renderer);
+    Ok(())
+}
+
+// Repeats the source across the destination, cropping the tiles on the right
+// and bottom edges instead of squashing them.
+fn tile_rects(source: &Rect, destination: &Rect) -> Vec<(Rect, Rect)> {
+    if source.width <= 0 || source.height <= 0 {
+        return vec![];
+    }
+
+    let mut tiles = vec![];
+    let mut y = 0;
+    while y < destination.height {
+        let height = source.height.min(destination.height - y);
+        let mut x = 0;
+        while x < destination.width {
+            let width = source.width.min(destination.width - x);
+            tiles.push((
+                Rect::new(source.position, width, height),
+                Rect::new_from_x_y(destination.x() + x, destination.y() + y, width, height),
+            ));
+            x += source.width;
+        }
+        y += source.height;
+    }
+    tiles
 }
 
 pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
@@ -162,23 +923,40 @@ pub async fn load_image(source: &str) -> Result<HtmlImageElement> {
     });
     image.set_onload(Some(success_callback.as_ref().unchecked_ref()));
     image.set_onerror(Some(error_callback.as_ref().unchecked_ref()));
-    image.set_src(source);
+    image.set_src(&browser::asset_config::url(source));
     complete_rx.await??;
     Ok(image)
 }
 
+// Keys the game uses in debug builds that the browser would otherwise act on.
+#[cfg(debug_assertions)]
+const DEVELOPER_KEYS: [&str; 2] = ["F5", "F9"];
+
 enum KeyPress {
     KeyUp(web_sys::KeyboardEvent),
     KeyDown(web_sys::KeyboardEvent),
+    Click(Point),
+    PointerMove(Point),
+    PointerUp,
 }
 
-fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
+fn prepare_input(
+    canvas: &HtmlCanvasElement,
+    listeners: &mut EventListenerRegistry,
+) -> Result<UnboundedReceiver<KeyPress>> {
     let (keydown_sender, keyevent_receiver) = unbounded();
 
     let keydown_sender = Rc::new(RefCell::new(keydown_sender));
     let keyup_receiver = Rc::clone(&keydown_sender);
+    let click_sender = Rc::clone(&keydown_sender);
+    let move_sender = Rc::clone(&keydown_sender);
+    let release_sender = Rc::clone(&keydown_sender);
 
     let onkeydown = browser::closure_wrap(Box::new(move |keycode: web_sys::KeyboardEvent| {
+        #[cfg(debug_assertions)]
+        if DEVELOPER_KEYS.contains(&keycode.code().as_str()) {
+            keycode.prevent_default();
+        }
         keydown_sender
             .borrow_mut()
             .start_send(KeyPress::KeyDown(keycode))
@@ -190,14 +968,56 @@ fn prepare_input() -> Result<UnboundedReceiver<KeyPress>> {
             .start_send(KeyPress::KeyUp(keycode))
             .expect("Error in Registering Keyup");
     }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+    let clicked_canvas = canvas.clone();
+    let onmousedown = browser::closure_wrap(Box::new(move |event: web_sys::MouseEvent| {
+        click_sender
+            .borrow_mut()
+            .start_send(KeyPress::Click(canvas_position(&clicked_canvas, &event)))
+            .expect("Error in Registering Click");
+    }) as Box<dyn FnMut(web_sys::MouseEvent)>);
+    let moved_canvas = canvas.clone();
+    let onmousemove = browser::closure_wrap(Box::new(move |event: web_sys::MouseEvent| {
+        move_sender
+            .borrow_mut()
+            .start_send(KeyPress::PointerMove(canvas_position(
+                &moved_canvas,
+                &event,
+            )))
+            .expect("Error in Registering Pointer Move");
+    }) as Box<dyn FnMut(web_sys::MouseEvent)>);
+    let onmouseup = browser::closure_wrap(Box::new(move |_event: web_sys::MouseEvent| {
+        release_sender
+            .borrow_mut()
+            .start_send(KeyPress::PointerUp)
+            .expect("Error in Registering Pointer Up");
+    }) as Box<dyn FnMut(web_sys::MouseEvent)>);
 
-    browser::window()?.set_onkeydown(Some(onkeydown.as_ref().unchecked_ref()));
-    browser::window()?.set_onkeyup(Some(onkeyup.as_ref().unchecked_ref()));
-    onkeydown.forget();
-    onkeyup.forget();
+    let window = browser::window()?;
+    listeners.add(&window, "keydown", onkeydown)?;
+    listeners.add(&window, "keyup", onkeyup)?;
+    listeners.add(canvas, "mousedown", onmousedown)?;
+    listeners.add(canvas, "mousemove", onmousemove)?;
+    // Released outside the canvas still counts, so a drag never gets stuck.
+    listeners.add(&window, "mouseup", onmouseup)?;
     Ok(keyevent_receiver)
 }
 
+// The canvas may be scaled by the page, so clicks are mapped back from CSS
+// pixels to canvas pixels.
+fn canvas_position(canvas: &HtmlCanvasElement, event: &web_sys::MouseEvent) -> Point {
+    let scale = |size: u32, client_size: i32| {
+        if client_size > 0 {
+            size as f32 / client_size as f32
+        } else {
+            1.0
+        }
+    };
+    Point {
+        x: (event.offset_x() as f32 * scale(canvas.width(), canvas.client_width())) as i16,
+        y: (event.offset_y() as f32 * scale(canvas.height(), canvas.client_height())) as i16,
+    }
+}
+
 fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedReceiver<KeyPress>) {
     loop {
         match keyevent_receiver.try_next() {
@@ -205,7 +1025,10 @@ fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedReceiver
             Err(_err) => break,
             Ok(Some(evt)) => match evt {
                 KeyPress::KeyUp(evt) => state.set_released(&evt.code()),
-                KeyPress::KeyDown(evt) => state.set_pressed(&evt.code(), evt),
+                KeyPress::KeyDown(evt) => state.set_pressed(&evt.code()),
+                KeyPress::Click(position) => state.add_click(position),
+                KeyPress::PointerMove(position) => state.pointer = Some(position),
+                KeyPress::PointerUp => state.pointer_held = false,
             },
         };
     }
@@ -213,34 +1036,100 @@ fn process_input(state: &mut KeyState, keyevent_receiver: &mut UnboundedReceiver
 
 #[derive(Debug)]
 pub struct KeyState {
-    pressed_keys: HashMap<String, web_sys::KeyboardEvent>,
+    pressed_keys: HashSet<String>,
+    clicks: Vec<Point>,
+    pointer: Option<Point>,
+    pointer_held: bool,
 }
 impl KeyState {
     fn new() -> Self {
         KeyState {
-            pressed_keys: HashMap::new(),
+            pressed_keys: HashSet::new(),
+            clicks: vec![],
+            pointer: None,
+            pointer_held: false,
         }
     }
+    // Input for scripted players, holding down the given keys.
+    #[allow(dead_code)]
+    pub fn holding(codes: &[&str]) -> Self {
+        let mut keystate = KeyState::new();
+        codes.iter().for_each(|code| keystate.set_pressed(code));
+        keystate
+    }
     pub fn is_pressed(&self, code: &str) -> bool {
-        self.pressed_keys.contains_key(code)
+        self.pressed_keys.contains(code)
     }
-    fn set_pressed(&mut self, code: &str, event: web_sys::KeyboardEvent) {
-        self.pressed_keys.insert(code.into(), event);
+    pub fn any_pressed(&self) -> bool {
+        !self.pressed_keys.is_empty()
+    }
+    fn set_pressed(&mut self, code: &str) {
+        self.pressed_keys.insert(code.into());
     }
     fn set_released(&mut self, code: &str) {
-        self.pressed_keys.remove(code.into());
+        self.pressed_keys.remove(code);
+    }
+    // Clicks on the canvas since the last update, in canvas coordinates.
+    pub fn clicks(&self) -> &[Point] {
+        &self.clicks
+    }
+    // The last position of the mouse over the canvas.
+    pub fn pointer(&self) -> Option<Point> {
+        self.pointer
+    }
+    pub fn pointer_held(&self) -> bool {
+        self.pointer_held
+    }
+    fn add_click(&mut self, position: Point) {
+        self.clicks.push(position);
+        self.pointer = Some(position);
+        self.pointer_held = true;
+    }
+    fn clear_clicks(&mut self) {
+        self.clicks.clear();
     }
 }
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Point {
     pub x: i16,
     pub y: i16,
 }
 
+// An image loaded with an optional collision mask, shared by every obstacle
+// drawn from it.
+#[derive(Clone)]
+pub struct MaskedImage {
+    element: HtmlImageElement,
+    mask: Option<Rc<CollisionMask>>,
+}
+
+impl MaskedImage {
+    // Without a mask, images drawn from it collide by their bounding box.
+    #[allow(dead_code)]
+    pub fn new(element: HtmlImageElement) -> Self {
+        MaskedImage {
+            element,
+            mask: None,
+        }
+    }
+
+    pub fn with_mask(element: HtmlImageElement) -> Self {
+        let mask = CollisionMask::from_image(&element)
+            .map_err(|err| {
+                log!("Colliding by bounding box {:#?}", err);
+            })
+            .ok()
+            .map(Rc::new);
+        MaskedImage { element, mask }
+    }
+}
+
+#[derive(Clone)]
 pub struct Image {
     element: HtmlImageElement,
     bounding_box: Rect,
+    mask: Option<Rc<CollisionMask>>,
 }
 impl Image {
     pub fn new(element: HtmlImageElement, position: Point) -> Self {
@@ -252,6 +1141,14 @@ impl Image {
         Self {
             element,
             bounding_box,
+            mask: None,
+        }
+    }
+
+    pub fn masked(image: MaskedImage, position: Point) -> Self {
+        Self {
+            mask: image.mask,
+            ..Image::new(image.element, position)
         }
     }
 
@@ -259,6 +1156,14 @@ impl Image {
         renderer.draw_entire_image(&self.element, &self.bounding_box.position)
     }
 
+    // The narrow phase after bounding boxes overlap. Without a mask the whole
+    // image is solid.
+    pub fn overlaps(&self, rect: &Rect) -> bool {
+        self.mask.as_ref().map_or(true, |mask| {
+            mask.overlaps(&self.bounding_box.position, rect)
+        })
+    }
+
     pub fn bounding_box(&self) -> &Rect {
         &self.bounding_box
     }
@@ -318,6 +1223,11 @@ impl Rect {
     pub fn set_y(&mut self, y: i16) {
         self.position.y = y;
     }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, point: &Point) -> bool {
+        (self.x()..self.right()).contains(&point.x) && (self.y()..self.bottom()).contains(&point.y)
+    }
 }
 
 pub struct SpriteSheet {
@@ -334,63 +1244,526 @@ impl SpriteSheet {
         self.sheet.frames.get(name)
     }
 
+    pub fn image(&self) -> &HtmlImageElement {
+        &self.image
+    }
+
+    pub fn sheet(&self) -> &Sheet {
+        &self.sheet
+    }
+
+    // The frames and tags filed under `namespace` in a merged atlas, with the
+    // namespace taken off their names, for code that wants one page's own names.
+    pub fn namespace(&self, namespace: &str) -> Sheet {
+        let prefix = atlas::namespaced(namespace, "");
+        let strip = |name: &str| name.strip_prefix(&prefix).map(str::to_string);
+        Sheet {
+            frames: self
+                .sheet
+                .frames
+                .iter()
+                .filter_map(|(name, cell)| strip(name).map(|name| (name, cell.clone())))
+                .collect(),
+            tags: self
+                .sheet
+                .tags
+                .iter()
+                .filter_map(|tag| {
+                    strip(&tag.name).map(|name| FrameTag {
+                        name,
+                        frames: tag.frames.iter().filter_map(|frame| strip(frame)).collect(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
     pub fn draw(&self, renderer: &Renderer, source: &Rect, destination: &Rect) {
         renderer
             .draw_image(&self.image, source, destination)
             .expect("Failed to Render Sprite Sheet.");
     }
+
+    pub fn draw_nine_slice(
+        &self,
+        renderer: &Renderer,
+        name: &str,
+        slice: &NineSlice,
+        destination: &Rect,
+    ) -> Result<()> {
+        let cell = self
+            .cell(name)
+            .ok_or_else(|| anyhow!("No cell named {} for nine slice", name))?;
+        renderer.draw_nine_slice(&self.image, &cell.atlas_rect(), slice, destination)
+    }
+
+    pub fn draw_tiled(&self, renderer: &Renderer, cell: &Cell, destination: &Rect) {
+        renderer
+            .draw_tiled(&self.image, &cell.atlas_rect(), destination)
+            .expect("Failed to Render Sprite Sheet.");
+    }
 }
 
 #[derive(Clone)]
 pub struct Audio {
     context: AudioContext,
+    master: GainNode,
+    // One per bus, indexed by `Bus::index`, each feeding `master`.
+    buses: Vec<GainNode>,
+    // Streamed music goes through this on its way to the music bus, so
+    // ducking it leaves the music volume alone.
+    music: GainNode,
+    // Shared with every copy, so any of them can pause what the others played.
+    live: Rc<RefCell<LiveSources<AudioBufferSourceNode>>>,
+    paused: Rc<std::cell::Cell<bool>>,
+    captions: Option<UnboundedSender<String>>,
+    muted: bool,
 }
 #[derive(Clone)]
 pub struct Sound {
     pub buffer: AudioBuffer,
+    pub caption: Option<String>,
+}
+
+impl Sound {
+    pub fn with_caption(mut self, caption: &str) -> Self {
+        self.caption = Some(caption.into());
+        self
+    }
 }
 
 impl Audio {
     pub fn new() -> Result<Self> {
-        Ok(Audio {
-            context: sound::create_audio_context()?,
+        let context = sound::create_audio_context()?;
+        let master = sound::create_gain(&context, &context.destination())?;
+        let buses = Bus::ALL
+            .iter()
+            .map(|_bus| sound::create_gain(&context, &master))
+            .collect::<Result<Vec<GainNode>>>()?;
+        let music = sound::create_gain(&context, &buses[Bus::Music.index()])?;
+        let audio = Audio {
+            context,
+            master,
+            buses,
+            music,
+            live: Rc::new(RefCell::new(LiveSources::default())),
+            paused: Rc::new(std::cell::Cell::new(false)),
+            captions: None,
+            muted: false,
+        };
+        audio.set_levels(&MixerSetting::load());
+        Ok(audio)
+    }
+
+    // Muted copies share the same buses, so this reaches them too.
+    pub fn set_levels(&self, mixer: &MixerSetting) {
+        sound::set_gain(&self.master, mixer.gain(Fader::Master));
+        Bus::ALL.iter().for_each(|bus| {
+            sound::set_gain(&self.buses[bus.index()], mixer.gain(Fader::Bus(*bus)))
+        });
+    }
+
+    fn bus(&self, bus: Bus) -> &GainNode {
+        &self.buses[bus.index()]
+    }
+
+    fn track(&self, source: AudioBufferSourceNode, bus: Bus, duration: f64) {
+        let now = self.context.current_time();
+        let mut live = self.live.borrow_mut();
+        live.track(source, bus, now + duration, now);
+        memory::set(Tracked::AudioSources, live.len());
+    }
+
+    // Cuts off the sound effects and voice still playing, and lowers the
+    // music unless the settings say to keep it.
+    pub fn pause(&self) {
+        if self.paused.replace(true) {
+            return;
+        }
+        let now = self.context.current_time();
+        self.live.borrow_mut().pause(now).iter().for_each(|source| {
+            if let Err(err) = sound::stop(source) {
+                log!("Could not pause a sound {:#?}", err);
+            }
+        });
+        if PauseMusicSetting::load() == PauseMusicSetting::Lower {
+            if let Err(err) = sound::hold_gain(&self.context, &self.music, PAUSED_MUSIC_GAIN) {
+                log!("Could not lower the music {:#?}", err);
+            }
+        }
+    }
+
+    // The game loop stops while the page is hidden, so its sounds pause too.
+    pub fn pause_when_hidden(&self, target: &Target) -> Result<()> {
+        let audio = self.clone();
+        browser::on_visibility_change(target, move |hidden| {
+            if hidden {
+                audio.pause();
+            } else {
+                audio.resume();
+            }
         })
     }
 
-    pub async fn load_sound(&self, filename: &str) -> Result<Sound> {
+    pub fn resume(&self) {
+        if !self.paused.replace(false) {
+            return;
+        }
+        if let Err(err) = sound::hold_gain(&self.context, &self.music, 1.0) {
+            log!("Could not restore the music {:#?}", err);
+        }
+    }
+
+    // A copy that plays nothing, for simulated runs and replays.
+    pub fn muted(&self) -> Self {
+        Audio {
+            muted: true,
+            ..self.clone()
+        }
+    }
+
+    pub fn enable_captions(&mut self) -> UnboundedReceiver<String> {
+        let (caption_sender, caption_receiver) = unbounded();
+        self.captions = Some(caption_sender);
+        caption_receiver
+    }
+
+    fn send_caption(&self, caption: Option<&str>) {
+        if let (Some(captions), Some(caption)) = (&self.captions, caption) {
+            if let Err(err) = captions.unbounded_send(caption.to_string()) {
+                log!("Could not send caption {:#?}", err);
+            }
+        }
+    }
+
+    // Candidates are tried in order; ones the browser says it cannot play are
+    // skipped, and a failed download or decode moves on to the next.
+    pub async fn load_sound(&self, candidates: &[&str]) -> Result<Sound> {
+        let mut last_error = anyhow!("No playable sound in {:?}", candidates);
+        for filename in candidates {
+            let playable = sound::mime_type(filename)
+                .map_or(Ok(true), browser::can_play_type)
+                .unwrap_or(true);
+            if !playable {
+                continue;
+            }
+            match self.load_sound_file(filename).await {
+                Ok(sound) => return Ok(sound),
+                Err(err) => {
+                    log!("Could not load {} {:#?}", filename, err);
+                    last_error = err;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn load_sound_file(&self, filename: &str) -> Result<Sound> {
         let array_buffer = browser::fetch_array_buffer(filename).await?;
 
         let audio_buffer = sound::decode_audio_data(&self.context, &array_buffer).await?;
 
         Ok(Sound {
             buffer: audio_buffer,
+            caption: None,
         })
     }
 
-    pub fn play_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::NO)
+    pub fn sample_rate(&self) -> f32 {
+        self.context.sample_rate()
+    }
+
+    // A sound made from raw samples rather than loaded from a file.
+    pub fn synthesize(&self, samples: &[f32]) -> Result<Sound> {
+        Ok(Sound {
+            buffer: sound::create_buffer(&self.context, samples)?,
+            caption: None,
+        })
+    }
+
+    pub fn play_sound(&self, sound: &Sound, bus: Bus) -> Result<()> {
+        if self.muted {
+            return Ok(());
+        }
+        self.send_caption(sound.caption.as_deref());
+        let source = sound::play_sound(
+            &self.context,
+            &sound.buffer,
+            sound::LOOPING::NO,
+            self.bus(bus),
+        )?;
+        self.track(source, bus, sound.buffer.duration());
+        Ok(())
+    }
+
+    pub fn play_clip(
+        &self,
+        sound: &Sound,
+        clip: &Clip,
+        caption: Option<&str>,
+        bus: Bus,
+    ) -> Result<()> {
+        if self.muted {
+            return Ok(());
+        }
+        self.send_caption(caption);
+        let source = sound::play_clip(
+            &self.context,
+            &sound.buffer,
+            clip.start,
+            clip.duration,
+            self.bus(bus),
+        )?;
+        self.track(source, bus, clip.duration);
+        Ok(())
+    }
+
+    // Unlike sounds, streamed music does not wait for a full download and decode.
+    pub fn stream_music(&self, url: &str, caption: Option<&str>) -> Result<()> {
+        self.send_caption(caption);
+        sound::stream_looping(&self.context, &browser::asset_config::url(url), &self.music)
+            .map(|_element| ())
+    }
+
+    // Turns the music down under a cue that must be heard, for `hold` seconds
+    // and then a short ramp back up.
+    pub fn duck_music(&self, hold: f64) {
+        if self.muted || self.paused.get() {
+            return;
+        }
+        if let Err(err) = sound::duck(&self.context, &self.music, hold) {
+            log!("Could not duck the music {:#?}", err);
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn play_looping_sound(&self, sound: &Sound, bus: Bus) -> Result<()> {
+        self.send_caption(sound.caption.as_deref());
+        sound::play_sound(
+            &self.context,
+            &sound.buffer,
+            sound::LOOPING::YES,
+            self.bus(bus),
+        )
+        .map(|_source| ())
+    }
+}
+
+pub struct FloatingText {
+    text: String,
+    position: Point,
+    frames_left: u8,
+}
+
+impl FloatingText {
+    pub fn new(text: &str, position: Point, frames: u8) -> Self {
+        FloatingText {
+            text: text.into(),
+            position,
+            frames_left: frames,
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.frames_left = self.frames_left.saturating_sub(1);
+    }
+
+    pub fn expired(&self) -> bool {
+        self.frames_left == 0
+    }
+
+    pub fn set_y(&mut self, y: i16) {
+        self.position.y = y;
     }
 
-    pub fn play_looping_sound(&self, sound: &Sound) -> Result<()> {
-        sound::play_sound(&self.context, &sound.buffer, sound::LOOPING::YES)
+    pub fn draw(&self, renderer: &Renderer) {
+        let style = TextStyle::new().with_outline(FLOATING_TEXT_OUTLINE, 4);
+        if let Err(err) = renderer.draw_styled_text(&self.text, &self.position, &style) {
+            log!("Could not draw floating text {:#?}", err);
+        }
     }
 }
 
-pub fn add_click_handler(elem: HtmlElement) -> UnboundedReceiver<()> {
+fn add_resize_handler(listeners: &mut EventListenerRegistry) -> Result<UnboundedReceiver<()>> {
+    let (mut resize_sender, resize_receiver) = unbounded();
+    let on_resize = browser::closure_wrap(Box::new(move || {
+        if let Err(err) = resize_sender.start_send(()) {
+            log!("Could not send resize {:#?}", err);
+        }
+    }) as Box<dyn FnMut()>);
+    let window = browser::window()?;
+    listeners.add(&window, "resize", on_resize)?;
+    Ok(resize_receiver)
+}
+
+// Sends true when the browser takes the canvas context away, as it can for a
+// GPU reset or a sleeping laptop, and false when it gives it back.
+fn add_context_handler(
+    canvas: &HtmlCanvasElement,
+    listeners: &mut EventListenerRegistry,
+) -> Result<UnboundedReceiver<bool>> {
+    let (mut lost_sender, context_receiver) = unbounded();
+    let mut restored_sender = lost_sender.clone();
+    let on_lost = browser::closure_wrap(Box::new(move |event: web_sys::Event| {
+        // Without this the browser never restores the context.
+        event.prevent_default();
+        if let Err(err) = lost_sender.start_send(true) {
+            log!("Could not send context lost {:#?}", err);
+        }
+    }) as Box<dyn FnMut(web_sys::Event)>);
+    let on_restored = browser::closure_wrap(Box::new(move || {
+        if let Err(err) = restored_sender.start_send(false) {
+            log!("Could not send context restored {:#?}", err);
+        }
+    }) as Box<dyn FnMut()>);
+    listeners.add(canvas, "contextlost", on_lost)?;
+    listeners.add(canvas, "contextrestored", on_restored)?;
+    Ok(context_receiver)
+}
+
+// Whether the context is lost after `changes`, starting from `lost`, and
+// whether it came back along the way.
+fn context_changes(lost: bool, changes: impl Iterator<Item = bool>) -> (bool, bool) {
+    changes.fold((lost, false), |(was_lost, restored), now_lost| {
+        (now_lost, restored || (was_lost && !now_lost))
+    })
+}
+
+pub fn add_click_handler(target: &Target, elem: HtmlElement) -> UnboundedReceiver<()> {
     let (mut click_sender, click_receiver) = unbounded();
     let on_click = browser::closure_wrap(Box::new(move || {
         click_sender
             .start_send(())
             .expect("Failed to Send Message to event handler!!");
     }) as Box<dyn FnMut()>);
-    elem.set_onclick(Some(on_click.as_ref().unchecked_ref()));
-    on_click.forget();
+    if let Err(err) = browser::add_page_listener(target, &elem, "click", on_click) {
+        log!("Could not listen for clicks {:#?}", err);
+    }
     click_receiver
 }
 
+// Fires whenever the value of an input such as a slider changes.
+pub fn add_input_handler(target: &Target, elem: HtmlElement) -> UnboundedReceiver<()> {
+    let (mut input_sender, input_receiver) = unbounded();
+    let on_input = browser::closure_wrap(Box::new(move || {
+        if let Err(err) = input_sender.start_send(()) {
+            log!("Could not send input {:#?}", err);
+        }
+    }) as Box<dyn FnMut()>);
+    if let Err(err) = browser::add_page_listener(target, &elem, "input", on_input) {
+        log!("Could not listen for input {:#?}", err);
+    }
+    input_receiver
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::executor::block_on;
+    use std::cell::Cell;
+
+    #[test]
+    fn loop_drivers_are_chosen_by_name() {
+        assert_eq!(
+            LoopDriver::from_name("animation_frame").unwrap(),
+            LoopDriver::AnimationFrame
+        );
+        assert_eq!(
+            LoopDriver::from_name("timer").unwrap(),
+            LoopDriver::Timer {
+                interval: FRAME_SIZE as i32
+            }
+        );
+        assert!(LoopDriver::from_name("vsync").is_err());
+    }
+
+    struct CountingGame {
+        updates: Rc<Cell<u32>>,
+        jumps: Rc<Cell<u32>>,
+        clicks: Rc<Cell<u32>>,
+    }
+
+    #[async_trait(?Send)]
+    impl Game for CountingGame {
+        async fn initialize(&self) -> Result<Box<dyn Game>> {
+            Ok(Box::new(CountingGame {
+                updates: self.updates.clone(),
+                jumps: self.jumps.clone(),
+                clicks: self.clicks.clone(),
+            }))
+        }
+
+        fn update(&mut self, keystate: &KeyState, _delta: f64) {
+            self.updates.set(self.updates.get() + 1);
+            if keystate.is_pressed("Space") {
+                self.jumps.set(self.jumps.get() + 1);
+            }
+            self.clicks
+                .set(self.clicks.get() + keystate.clicks().len() as u32);
+        }
+
+        fn draw(&self, _renderer: &Renderer) {}
+    }
+
+    #[test]
+    fn headless_loop_runs_one_update_per_tick() {
+        let updates = Rc::new(Cell::new(0));
+        let jumps = Rc::new(Cell::new(0));
+        let mut headless = block_on(GameLoop::headless(CountingGame {
+            updates: updates.clone(),
+            jumps: jumps.clone(),
+            clicks: Rc::new(Cell::new(0)),
+        }))
+        .unwrap();
+
+        headless.run(1000);
+        headless.press("Space");
+        headless.run(3);
+        headless.release("Space");
+        headless.tick();
+
+        assert_eq!(updates.get(), 1004);
+        assert_eq!(jumps.get(), 3);
+    }
+
+    #[test]
+    fn clicks_are_seen_by_one_update() {
+        let clicks = Rc::new(Cell::new(0));
+        let mut headless = block_on(GameLoop::headless(CountingGame {
+            updates: Rc::new(Cell::new(0)),
+            jumps: Rc::new(Cell::new(0)),
+            clicks: clicks.clone(),
+        }))
+        .unwrap();
+
+        headless.click(Point { x: 10, y: 20 });
+        headless.click(Point { x: 30, y: 40 });
+        headless.run(3);
+
+        assert_eq!(clicks.get(), 2);
+    }
+
+    #[test]
+    fn rects_contain_points_inside_their_edges() {
+        let rect = Rect::new_from_x_y(10, 20, 30, 40);
+
+        assert!(rect.contains(&Point { x: 10, y: 20 }));
+        assert!(rect.contains(&Point { x: 39, y: 59 }));
+        assert!(!rect.contains(&Point { x: 40, y: 30 }));
+        assert!(!rect.contains(&Point { x: 20, y: 19 }));
+    }
+
+    #[test]
+    fn tiles_repeat_and_crop_at_the_far_edges() {
+        let source = Rect::new_from_x_y(5, 5, 128, 93);
+        let destination = Rect::new_from_x_y(100, 400, 300, 93);
+        let tiles = tile_rects(&source, &destination);
+
+        assert_eq!(tiles.len(), 3);
+        assert_eq!(tiles[1].1, Rect::new_from_x_y(228, 400, 128, 93));
+        assert_eq!(tiles[2].0, Rect::new_from_x_y(5, 5, 44, 93));
+        assert_eq!(tiles[2].1, Rect::new_from_x_y(356, 400, 44, 93));
+    }
 
     #[test]
     fn two_rects_that_intersects_on_the_left() {
@@ -462,23 +1835,48 @@ mod tests {
         };
         assert_eq!(rect2.intersects(&rect1), false);
     }
+    #[test]
+    fn the_loop_resumes_once_the_context_is_restored() {
+        assert_eq!(context_changes(false, [true].into_iter()), (true, false));
+        assert_eq!(context_changes(true, std::iter::empty()), (true, false));
+        assert_eq!(context_changes(true, [false].into_iter()), (false, true));
+        // Lost again before the frame that would have redrawn.
+        assert_eq!(
+            context_changes(true, [false, true].into_iter()),
+            (true, true)
+        );
+    }
 }
 
-unsafe fn draw_frame_rate(renderer: &Renderer, frame_time: f64) {
-    static mut FRAMES_COUNTED: i32 = 0;
-    static mut TOTAL_FRAME_TIME: f64 = 0.0;
-    static mut FRAME_RATE: i32 = 0;
-    FRAMES_COUNTED += 1;
-    TOTAL_FRAME_TIME += frame_time;
-    if TOTAL_FRAME_TIME > 1000.0 {
-        FRAME_RATE = FRAMES_COUNTED;
-        TOTAL_FRAME_TIME = 0.0;
-        FRAMES_COUNTED = 0;
+#[derive(Default)]
+struct FrameRate {
+    frames_counted: i32,
+    total_frame_time: f64,
+    frame_rate: i32,
+}
+
+impl FrameRate {
+    // Returns the average frame time whenever a one second window closes.
+    fn update(&mut self, frame_time: f64) -> Option<f64> {
+        self.frames_counted += 1;
+        self.total_frame_time += frame_time;
+        if self.total_frame_time > 1000.0 {
+            let average_frame_time = self.total_frame_time / self.frames_counted as f64;
+            self.frame_rate = self.frames_counted;
+            self.total_frame_time = 0.0;
+            self.frames_counted = 0;
+            Some(average_frame_time)
+        } else {
+            None
+        }
     }
-    if let Err(err) = renderer.draw_text(
-        &format!("Frame Rate {}", FRAME_RATE),
-        &Point { x: 400, y: 100 },
-    ) {
-        panic!("Could not draw text {:#?}", err);
+
+    fn draw(&self, renderer: &Renderer) {
+        if let Err(err) = renderer.draw_text(
+            &format!("Frame Rate {}", self.frame_rate),
+            &Point { x: 400, y: 100 },
+        ) {
+            panic!("Could not draw text {:#?}", err);
+        }
     }
 }
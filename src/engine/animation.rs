@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use crate::game::{FrameTag, Sheet};
+
+// TexturePacker sheets carry no timing, so their frames play at the
+// original three updates per sprite at 60Hz.
+const DEFAULT_FRAME_DURATION: u16 = 50;
+// Sixtieths of a second do not add up to whole milliseconds exactly, so
+// times this close count as reached.
+const EPSILON: f64 = 1e-6;
+
+// What one more update of an animation does: moves on to a later time in it,
+// in milliseconds, or finishes the animation so whoever is playing it can
+// pick what comes next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Playback {
+    Playing(f64),
+    Completed,
+}
+
+#[derive(Clone)]
+struct AnimationFrame {
+    name: String,
+    duration: f64,
+}
+
+fn reached(elapsed: f64, time: f64) -> bool {
+    elapsed + EPSILON >= time
+}
+
+#[derive(Clone, Default)]
+pub struct Animation {
+    frames: Vec<AnimationFrame>,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<(String, u16)>) -> Self {
+        Animation {
+            frames: frames
+                .into_iter()
+                .map(|(name, duration)| AnimationFrame {
+                    name,
+                    duration: f64::from(duration.max(1)),
+                })
+                .collect(),
+        }
+    }
+
+    fn from_tag(tag: &FrameTag, sheet: &Sheet) -> Self {
+        Animation::new(
+            tag.frames
+                .iter()
+                .map(|name| {
+                    let duration = sheet
+                        .frames
+                        .get(name)
+                        .and_then(|cell| cell.duration)
+                        .unwrap_or(DEFAULT_FRAME_DURATION);
+                    (name.clone(), duration)
+                })
+                .collect(),
+        )
+    }
+
+    // Milliseconds the animation plays for before it loops.
+    pub fn length(&self) -> f64 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    // `delta` is how long the update lasts, so the sprites keep their pace
+    // whatever rate the game updates at.
+    pub fn advance(&self, elapsed: f64, delta: f64) -> Playback {
+        if reached(elapsed + delta, self.length()) {
+            Playback::Completed
+        } else {
+            Playback::Playing(elapsed + delta)
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn frame_names(&self) -> Vec<&str> {
+        self.frames
+            .iter()
+            .map(|frame| frame.name.as_str())
+            .collect()
+    }
+
+    // The frame that came up during the update of `delta` that reached
+    // `elapsed`, for things that happen as a frame comes up, like a foot
+    // touching the ground.
+    pub fn frame_started(&self, elapsed: f64, delta: f64) -> Option<&str> {
+        let mut start = 0.0;
+        self.frames
+            .iter()
+            .find(|frame| {
+                let started = reached(elapsed, start) && !reached(elapsed - delta, start);
+                start += frame.duration;
+                started
+            })
+            .map(|frame| frame.name.as_str())
+    }
+
+    pub fn frame_name(&self, elapsed: f64) -> Option<&str> {
+        let mut end = 0.0;
+        self.frames
+            .iter()
+            .find(|frame| {
+                end += frame.duration;
+                !reached(elapsed, end)
+            })
+            .or_else(|| self.frames.last())
+            .map(|frame| frame.name.as_str())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Animations {
+    animations: HashMap<String, Animation>,
+}
+
+impl Animations {
+    // Frames are grouped by the "Name (n).png" file naming convention, and
+    // frame tags add animations of their own, replacing any group with the
+    // same name. Aseprite sheets are all tags; TexturePacker sheets can carry
+    // a few extra tags made from their numbered frames.
+    pub fn from_sheet(sheet: &Sheet) -> Self {
+        let mut animations: HashMap<String, Animation> = numbered_frames(sheet)
+            .into_iter()
+            .map(|(name, mut frames)| {
+                frames.sort_by_key(|(number, _)| *number);
+                let frames = frames
+                    .into_iter()
+                    .map(|(_, frame)| (frame, DEFAULT_FRAME_DURATION))
+                    .collect();
+                (name, Animation::new(frames))
+            })
+            .collect();
+        animations.extend(
+            sheet
+                .tags
+                .iter()
+                .map(|tag| (tag.name.clone(), Animation::from_tag(tag, sheet))),
+        );
+        Animations { animations }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Animation> {
+        self.animations.get(name)
+    }
+
+    #[allow(dead_code)]
+    pub fn length(&self, name: &str) -> f64 {
+        self.get(name).map_or(0.0, Animation::length)
+    }
+
+    // A missing animation completes straight away.
+    pub fn advance(&self, name: &str, elapsed: f64, delta: f64) -> Playback {
+        self.get(name).map_or(Playback::Completed, |animation| {
+            animation.advance(elapsed, delta)
+        })
+    }
+
+    pub fn finishing(&self, name: &str, elapsed: f64, delta: f64) -> bool {
+        self.advance(name, elapsed, delta) == Playback::Completed
+    }
+}
+
+fn numbered_frames(sheet: &Sheet) -> HashMap<String, Vec<(u16, String)>> {
+    let mut groups: HashMap<String, Vec<(u16, String)>> = HashMap::new();
+    sheet.frames.keys().for_each(|frame| {
+        let numbered = frame
+            .strip_suffix(").png")
+            .and_then(|stem| stem.rsplit_once(" ("))
+            .and_then(|(name, number)| Some((name, number.parse::<u16>().ok()?)));
+        if let Some((name, number)) = numbered {
+            groups
+                .entry(name.into())
+                .or_default()
+                .push((number, frame.clone()));
+        }
+    });
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{engine::FRAME_SIZE, game::Cell};
+
+    const TICK: f64 = FRAME_SIZE as f64;
+
+    fn cell(duration: Option<u16>) -> Cell {
+        let mut cell: Cell = serde_json::from_str(
+            r#"{"frame": {"x":0,"y":0,"w":1,"h":1}, "spriteSourceSize": {"x":0,"y":0,"w":1,"h":1}}"#,
+        )
+        .unwrap();
+        cell.duration = duration;
+        cell
+    }
+
+    #[test]
+    fn numbered_frames_play_in_order_for_fifty_milliseconds_each() {
+        let sheet = Sheet {
+            frames: ["Run (2).png", "Run (10).png", "Run (1).png", "Stone.png"]
+                .iter()
+                .map(|name| (name.to_string(), cell(None)))
+                .collect(),
+            tags: vec![],
+        };
+        let animations = Animations::from_sheet(&sheet);
+        let run = animations.get("Run").unwrap();
+
+        assert_eq!(run.length(), 150.0);
+        assert_eq!(run.frame_name(0.0), Some("Run (1).png"));
+        assert_eq!(run.frame_name(3.0 * TICK), Some("Run (2).png"));
+        assert_eq!(run.frame_name(8.0 * TICK), Some("Run (10).png"));
+        assert_eq!(run.frame_started(3.0 * TICK, TICK), Some("Run (2).png"));
+        assert_eq!(run.frame_started(4.0 * TICK, TICK), None);
+        assert!(animations.get("Stone.png").is_none());
+    }
+
+    #[test]
+    fn tagged_frames_use_their_durations() {
+        let sheet = Sheet {
+            frames: [("a", Some(100)), ("b", Some(17))]
+                .iter()
+                .map(|(name, duration)| (name.to_string(), cell(*duration)))
+                .collect(),
+            tags: vec![FrameTag {
+                name: "Jump".into(),
+                frames: vec!["a".into(), "b".into()],
+            }],
+        };
+        let animations = Animations::from_sheet(&sheet);
+
+        assert_eq!(animations.length("Jump"), 117.0);
+        assert_eq!(animations.get("Jump").unwrap().frame_name(99.0), Some("a"));
+        assert_eq!(animations.get("Jump").unwrap().frame_name(100.0), Some("b"));
+        assert_eq!(animations.length("Missing"), 0.0);
+    }
+
+    #[test]
+    fn tags_are_added_to_numbered_frames() {
+        let sheet = Sheet {
+            frames: ["Idle (1).png", "Idle (2).png"]
+                .iter()
+                .map(|name| (name.to_string(), cell(None)))
+                .collect(),
+            tags: vec![FrameTag {
+                name: "Blink".into(),
+                frames: vec!["Idle (2).png".into()],
+            }],
+        };
+        let animations = Animations::from_sheet(&sheet);
+
+        assert_eq!(animations.length("Idle"), 100.0);
+        assert_eq!(animations.length("Blink"), 50.0);
+    }
+
+    #[test]
+    fn advancing_past_the_last_tick_completes() {
+        let sheet = Sheet {
+            frames: [("Wave (1).png".to_string(), cell(None))]
+                .into_iter()
+                .collect(),
+            tags: vec![],
+        };
+        let animations = Animations::from_sheet(&sheet);
+
+        assert_eq!(
+            animations.advance("Wave", 0.0, TICK),
+            Playback::Playing(TICK)
+        );
+        assert!(!animations.finishing("Wave", TICK, TICK));
+        assert_eq!(
+            animations.advance("Wave", 2.0 * TICK, TICK),
+            Playback::Completed
+        );
+        assert_eq!(
+            animations.advance("Missing", 0.0, TICK),
+            Playback::Completed
+        );
+    }
+
+    #[test]
+    fn sprites_keep_their_pace_at_any_update_rate() {
+        let run = Animation::new(
+            ["a", "b", "c"]
+                .iter()
+                .map(|name| (name.to_string(), DEFAULT_FRAME_DURATION))
+                .collect(),
+        );
+        let played = |delta: f64, updates: u32| {
+            (0..updates).fold(0.0, |elapsed, _update| match run.advance(elapsed, delta) {
+                Playback::Playing(elapsed) => elapsed,
+                Playback::Completed => 0.0,
+            })
+        };
+
+        assert_eq!(run.frame_name(played(TICK, 4)), Some("b"));
+        assert_eq!(run.frame_name(played(2.0 * TICK, 2)), Some("b"));
+        assert_eq!(run.frame_name(played(2.0 * TICK, 5)), Some("a"));
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+use web_sys::HtmlImageElement;
+
+use super::{atlas, load_image, AtlasPage, SpriteSheet};
+use crate::{
+    browser::{self, Connection},
+    diagnostics::Report,
+    seasons::Season,
+    sheets,
+    versioned::{self, Versioned},
+};
+
+const SLOW_CONNECTIONS: [&str; 3] = ["slow-2g", "2g", "3g"];
+
+const AVIF_PROBE: &str = "data:image/avif;base64,AAAAIGZ0eXBhdmlmAAAAAGF2aWZtaWYxbWlhZk1BMUIAAADybWV0YQAAAAAAAAAoaGRscgAAAAAAAAAAcGljdAAAAAAAAAAAAAAAAGxpYmF2aWYAAAAADnBpdG0AAAAAAAEAAAAeaWxvYwAAAABEAAABAAEAAAABAAABGgAAAB0AAAAoaWluZgAAAAAAAQAAABppbmZlAgAAAAABAABhdjAxQ29sb3IAAAAAamlwcnAAAABLaXBjbwAAABRpc3BlAAAAAAAAAAIAAAACAAAAEHBpeGkAAAAAAwgICAAAAAxhdjFDgQ0MAAAAABNjb2xybmNseAACAAIAAYAAAAAXaXBtYQAAAAAAAAABAAEEAQKDBAAAACVtZGF0EgAKCBgANogQEAwgMg8f8D///8WfhwB8+ErK42A=";
+const WEBP_PROBE: &str = "data:image/webp;base64,UklGRhoAAABXRUJQVlA4TA0AAAAvAAAAEAcQERGIiP4HAA==";
+
+// Listed from most to least preferred.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ImageFormat {
+    Avif,
+    Webp,
+}
+
+const IMAGE_FORMATS: [ImageFormat; 2] = [ImageFormat::Avif, ImageFormat::Webp];
+
+impl ImageFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Avif => "avif",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    fn probe(&self) -> &'static str {
+        match self {
+            ImageFormat::Avif => AVIF_PROBE,
+            ImageFormat::Webp => WEBP_PROBE,
+        }
+    }
+}
+
+// A browser supports a format if it can decode a one pixel image in it.
+async fn supported_formats() -> Vec<ImageFormat> {
+    let mut formats = vec![];
+    for format in IMAGE_FORMATS.iter() {
+        if load_image(format.probe()).await.is_ok() {
+            formats.push(*format);
+        }
+    }
+    formats
+}
+
+fn with_extension(path: &str, extension: &str) -> String {
+    match path.rsplit_once('.') {
+        Some((stem, _)) => format!("{}.{}", stem, extension),
+        None => format!("{}.{}", path, extension),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quality {
+    Full,
+    Low,
+}
+
+impl Quality {
+    // `?quality=low` or `?quality=full` overrides what the connection reports.
+    pub fn detect() -> Self {
+        match browser::query_param("quality") {
+            Ok(Some(quality)) if quality == "low" => return Quality::Low,
+            Ok(Some(quality)) if quality == "full" => return Quality::Full,
+            Ok(_) => {}
+            Err(err) => {
+                log!("Could not read quality override {:#?}", err);
+            }
+        }
+        match browser::connection() {
+            Ok(connection) => Quality::for_connection(connection.as_ref()),
+            Err(err) => {
+                log!("Could not detect connection, using full quality {:#?}", err);
+                Quality::Full
+            }
+        }
+    }
+
+    fn for_connection(connection: Option<&Connection>) -> Self {
+        match connection {
+            Some(connection)
+                if connection.save_data
+                    || connection
+                        .effective_type
+                        .as_deref()
+                        .map_or(false, |kind| SLOW_CONNECTIONS.contains(&kind)) =>
+            {
+                Quality::Low
+            }
+            _ => Quality::Full,
+        }
+    }
+}
+
+// A tier maps asset names to smaller variants; images in it are `scale` times
+// the size of the originals.
+#[derive(Deserialize)]
+struct Tier {
+    scale: f32,
+    #[serde(default)]
+    files: HashMap<String, String>,
+}
+
+// A sheet and image to merge into the atlas, under `namespace`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+struct AtlasEntry {
+    namespace: String,
+    sheet: String,
+    image: String,
+}
+
+// `variants` lists the extra formats each image path is also published in.
+#[derive(Deserialize, Default)]
+struct Manifest {
+    low: Option<Tier>,
+    #[serde(default)]
+    variants: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    atlas: Vec<AtlasEntry>,
+    #[serde(default)]
+    seasons: Vec<Season>,
+}
+
+impl Versioned for Manifest {
+    const VERSION: u32 = 1;
+}
+
+#[derive(Default)]
+pub struct Assets {
+    tier: Option<Tier>,
+    variants: HashMap<String, Vec<String>>,
+    formats: Vec<ImageFormat>,
+    atlas: Vec<AtlasEntry>,
+    season: Option<Season>,
+}
+
+impl Assets {
+    // Seasons from packs come before the manifest's, so they win when both
+    // claim the same month.
+    pub async fn load(
+        manifest_path: &str,
+        quality: Quality,
+        pack_seasons: Vec<Season>,
+    ) -> Result<Self> {
+        let manifest: Manifest = versioned::fetch(manifest_path).await?;
+        let mut seasons = pack_seasons;
+        seasons.extend(manifest.seasons);
+        let formats = if manifest.variants.is_empty() {
+            vec![]
+        } else {
+            supported_formats().await
+        };
+        Ok(Assets {
+            tier: manifest.low.filter(|_| quality == Quality::Low),
+            variants: manifest.variants,
+            formats,
+            atlas: manifest.atlas,
+            season: Season::detect(seasons),
+        })
+    }
+
+    pub fn season(&self) -> Option<&Season> {
+        self.season.as_ref()
+    }
+
+    fn seasonal(&self, name: &str) -> Option<&str> {
+        self.season
+            .as_ref()
+            .and_then(|season| season.files.get(name))
+            .map(String::as_str)
+    }
+
+    // Loads every sheet listed in the manifest's atlas and merges them into
+    // one, with frames named "<namespace>/<frame>". New art only needs an
+    // entry in the manifest. Frames cut from outside their page's image are
+    // added to `report`.
+    pub async fn load_atlas(&self, report: &mut Report) -> Result<SpriteSheet> {
+        let mut pages = vec![];
+        for entry in self.atlas.iter() {
+            let sheet = sheets::load_cached_sheet(&entry.sheet).await?;
+            let image = self.load_image(&entry.image).await?;
+            report.check_frames_in_image(
+                &entry.sheet,
+                &sheet.frames,
+                image.width() as i16,
+                image.height() as i16,
+            );
+            pages.push(AtlasPage {
+                namespace: entry.namespace.clone(),
+                sheet,
+                image,
+            });
+        }
+        atlas::merge(pages).await
+    }
+
+    // A seasonal swap wins over the quality tier.
+    pub fn path<'a>(&'a self, name: &'a str) -> &'a str {
+        self.seasonal(name).unwrap_or_else(|| {
+            self.tier
+                .as_ref()
+                .and_then(|tier| tier.files.get(name))
+                .map_or(name, String::as_str)
+        })
+    }
+
+    // Tier images keep the original size as their width and height, so the
+    // renderer can tell how far to scale their source rectangles.
+    pub async fn load_image(&self, name: &str) -> Result<HtmlImageElement> {
+        if let Some(seasonal) = self.seasonal(name) {
+            match self.load_best_variant(seasonal).await {
+                Ok(image) => return Ok(image),
+                Err(err) => {
+                    log!("Falling back from the seasonal {} {:#?}", name, err);
+                }
+            }
+        }
+        let tier = match self
+            .tier
+            .as_ref()
+            .filter(|tier| tier.files.contains_key(name))
+        {
+            Some(tier) => tier,
+            None => return self.load_best_variant(name).await,
+        };
+        match self.load_best_variant(self.path(name)).await {
+            Ok(image) => {
+                image.set_width((image.natural_width() as f32 / tier.scale).round() as u32);
+                image.set_height((image.natural_height() as f32 / tier.scale).round() as u32);
+                Ok(image)
+            }
+            Err(err) => {
+                log!("Falling back to the original {} {:#?}", name, err);
+                self.load_best_variant(name).await
+            }
+        }
+    }
+
+    fn candidates(&self, path: &str) -> Vec<String> {
+        let published = match self.variants.get(path) {
+            Some(published) => published,
+            None => return vec![],
+        };
+        self.formats
+            .iter()
+            .filter(|format| published.iter().any(|ext| ext == format.extension()))
+            .map(|format| with_extension(path, format.extension()))
+            .collect()
+    }
+
+    async fn load_best_variant(&self, path: &str) -> Result<HtmlImageElement> {
+        for candidate in self.candidates(path) {
+            match load_image(&candidate).await {
+                Ok(image) => return Ok(image),
+                Err(err) => {
+                    log!("Could not load {} {:#?}", candidate, err);
+                }
+            }
+        }
+        load_image(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection(effective_type: &str, save_data: bool) -> Connection {
+        Connection {
+            effective_type: Some(effective_type.to_string()),
+            save_data,
+        }
+    }
+
+    #[test]
+    fn slow_or_metered_connections_use_low_quality() {
+        assert_eq!(Quality::for_connection(None), Quality::Full);
+        assert_eq!(
+            Quality::for_connection(Some(&connection("4g", false))),
+            Quality::Full
+        );
+        assert_eq!(
+            Quality::for_connection(Some(&connection("2g", false))),
+            Quality::Low
+        );
+        assert_eq!(
+            Quality::for_connection(Some(&connection("4g", true))),
+            Quality::Low
+        );
+    }
+
+    #[test]
+    fn tier_paths_fall_back_to_the_original() {
+        let manifest: Manifest =
+            serde_json::from_str(r#"{"low": {"scale": 0.5, "files": {"rhb.png": "low/rhb.png"}}}"#)
+                .unwrap();
+        let assets = Assets {
+            tier: manifest.low,
+            ..Assets::default()
+        };
+
+        assert_eq!(assets.path("rhb.png"), "low/rhb.png");
+        assert_eq!(assets.path("BG.png"), "BG.png");
+        assert_eq!(Assets::default().path("rhb.png"), "rhb.png");
+    }
+
+    #[test]
+    fn seasonal_swaps_win_over_the_tier() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{
+                "low": {"scale": 0.5, "files": {"BG.png": "low/BG.png", "rhb.png": "low/rhb.png"}},
+                "seasons": [{"name": "winter", "months": [12], "files": {"BG.png": "winter/BG.png"}}]
+            }"#,
+        )
+        .unwrap();
+        let assets = Assets {
+            tier: manifest.low,
+            season: manifest.seasons.into_iter().next(),
+            ..Assets::default()
+        };
+
+        assert_eq!(assets.path("BG.png"), "winter/BG.png");
+        assert_eq!(assets.path("rhb.png"), "low/rhb.png");
+    }
+
+    #[test]
+    fn prefers_supported_published_formats() {
+        let manifest: Manifest = serde_json::from_str(
+            r#"{"variants": {"BG.png": ["webp", "avif"], "low/BG.png": ["webp"]}}"#,
+        )
+        .unwrap();
+        let assets = Assets {
+            variants: manifest.variants,
+            formats: IMAGE_FORMATS.to_vec(),
+            ..Assets::default()
+        };
+
+        assert_eq!(assets.candidates("BG.png"), vec!["BG.avif", "BG.webp"]);
+        assert_eq!(assets.candidates("low/BG.png"), vec!["low/BG.webp"]);
+        assert!(assets.candidates("tiles.png").is_empty());
+
+        let webp_only = Assets {
+            formats: vec![ImageFormat::Webp],
+            ..assets
+        };
+        assert_eq!(webp_only.candidates("BG.png"), vec!["BG.webp"]);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use web_sys::HtmlImageElement;
+
+use super::{load_image, SpriteSheet};
+use crate::{
+    browser,
+    game::{FrameTag, Sheet},
+};
+
+// One sheet and its image, with the namespace its frames are filed under.
+pub struct AtlasPage {
+    pub namespace: String,
+    pub sheet: Sheet,
+    pub image: HtmlImageElement,
+}
+
+pub fn namespaced(namespace: &str, name: &str) -> String {
+    format!("{}/{}", namespace, name)
+}
+
+// Renames every frame and tag to "<namespace>/<name>" and moves the frames
+// down by their page's offset, as the pages are stacked top to bottom.
+fn merge_sheets(pages: &[(&str, &Sheet, i16)]) -> Sheet {
+    let mut merged = Sheet {
+        frames: Default::default(),
+        tags: vec![],
+    };
+    pages.iter().for_each(|(namespace, sheet, offset_y)| {
+        merged
+            .frames
+            .extend(sheet.frames.iter().map(|(name, cell)| {
+                let mut cell = cell.clone();
+                cell.frame.y += offset_y;
+                (namespaced(namespace, name), cell)
+            }));
+        merged.tags.extend(sheet.tags.iter().map(|tag| {
+            FrameTag {
+                name: namespaced(namespace, &tag.name),
+                frames: tag
+                    .frames
+                    .iter()
+                    .map(|frame| namespaced(namespace, frame))
+                    .collect(),
+            }
+        }));
+    });
+    merged
+}
+
+// Draws every page into one image, so everything can be looked up and drawn
+// through a single sheet.
+pub async fn merge(pages: Vec<AtlasPage>) -> Result<SpriteSheet> {
+    let width = pages
+        .iter()
+        .map(|page| page.image.width())
+        .max()
+        .unwrap_or(0);
+    let height = pages.iter().map(|page| page.image.height()).sum();
+    let canvas = browser::new_canvas(width, height)?;
+    let context = browser::context(&canvas)?;
+
+    let mut offset_y = 0;
+    let mut layout = vec![];
+    for page in pages.iter() {
+        context
+            .draw_image_with_html_image_element_and_dw_and_dh(
+                &page.image,
+                0.0,
+                offset_y as f64,
+                page.image.width().into(),
+                page.image.height().into(),
+            )
+            .map_err(|err| {
+                anyhow!(
+                    "Could not draw {} into the atlas {:#?}",
+                    page.namespace,
+                    err
+                )
+            })?;
+        layout.push((page.namespace.as_str(), &page.sheet, offset_y as i16));
+        offset_y += page.image.height();
+    }
+
+    let url = canvas
+        .to_data_url()
+        .map_err(|err| anyhow!("Could not read the merged atlas {:#?}", err))?;
+    Ok(SpriteSheet::new(
+        merge_sheets(&layout),
+        load_image(&url).await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sheet(json: &str) -> Sheet {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn pages_are_namespaced_and_stacked() {
+        let tiles =
+            sheet(r#"{"frames": {"13.png": {"frame": {"x":0,"y":10,"w":64,"h":64}}}, "tags": []}"#);
+        let rhb = sheet(
+            r#"{
+                "frames": {"Run (1).png": {"frame": {"x":5,"y":0,"w":70,"h":115}}},
+                "tags": [{"name": "Run", "frames": ["Run (1).png"]}]
+            }"#,
+        );
+
+        let merged = merge_sheets(&[("tiles", &tiles, 0), ("rhb", &rhb, 200)]);
+
+        assert_eq!(merged.frames["tiles/13.png"].frame.y, 10);
+        assert_eq!(merged.frames["rhb/Run (1).png"].frame.y, 200);
+        assert_eq!(merged.frames["rhb/Run (1).png"].frame.x, 5);
+        assert_eq!(merged.tags[0].name, "rhb/Run");
+        assert_eq!(merged.tags[0].frames, vec!["rhb/Run (1).png"]);
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+
+use super::{Audio, Bus, Sound};
+
+// Seconds of silence between clips, so a clip never bleeds into the next.
+const GAP_SECONDS: f32 = 0.05;
+
+// Where one clip sits in the shared buffer, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Clip {
+    pub start: f64,
+    pub duration: f64,
+}
+
+// Lays `clips` end to end with a short gap between each, returning the samples
+// for one buffer and where each clip landed.
+pub fn pack(clips: &[(&str, Vec<f32>)], sample_rate: f32) -> (Vec<f32>, HashMap<String, Clip>) {
+    let gap = (GAP_SECONDS * sample_rate) as usize;
+    let mut samples = vec![];
+    let mut placed = HashMap::new();
+    clips.iter().for_each(|(name, clip)| {
+        placed.insert(
+            name.to_string(),
+            Clip {
+                start: samples.len() as f64 / f64::from(sample_rate),
+                duration: clip.len() as f64 / f64::from(sample_rate),
+            },
+        );
+        samples.extend(clip);
+        samples.resize(samples.len() + gap, 0.0);
+    });
+    (samples, placed)
+}
+
+// Several short sounds in one buffer, each played by name.
+#[derive(Clone)]
+pub struct AudioSprite {
+    sound: Sound,
+    clips: HashMap<String, Clip>,
+}
+
+impl AudioSprite {
+    pub fn synthesize(audio: &Audio, clips: &[(&str, Vec<f32>)]) -> Result<Self> {
+        let (samples, clips) = pack(clips, audio.sample_rate());
+        Ok(AudioSprite {
+            sound: audio.synthesize(&samples)?,
+            clips,
+        })
+    }
+
+    pub fn clip(&self, name: &str) -> Option<Clip> {
+        self.clips.get(name).copied()
+    }
+
+    pub fn play(&self, audio: &Audio, name: &str, caption: Option<&str>, bus: Bus) -> Result<()> {
+        let clip = self
+            .clip(name)
+            .ok_or_else(|| anyhow!("No clip named {} in the audio sprite", name))?;
+        audio.play_clip(&self.sound, &clip, caption, bus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clips_are_packed_end_to_end_with_gaps() {
+        let (samples, clips) = pack(&[("a", vec![0.5; 100]), ("b", vec![0.25; 200])], 1000.0);
+
+        assert_eq!(
+            clips["a"],
+            Clip {
+                start: 0.0,
+                duration: 0.1
+            }
+        );
+        assert_eq!(
+            clips["b"],
+            Clip {
+                start: 0.15,
+                duration: 0.2
+            }
+        );
+        assert_eq!(samples.len(), 400);
+        assert_eq!(samples[120], 0.0);
+        assert_eq!(samples[150], 0.25);
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+use serde::Deserialize;
+use web_sys::HtmlImageElement;
+
+use super::{load_image, splash::Splash, KeyState, Point, Rect, Renderer};
+use crate::versioned::{self, Versioned};
+
+const CANVAS_SIZE: i16 = 600;
+const FLOOR: i16 = 450;
+const GRAVITY: i16 = 1;
+const BOUNCE_SPEED: i16 = -18;
+const MESSAGE: &str = "Loading... press Space to bounce";
+
+// The boot set is kept tiny so it arrives long before the main assets.
+#[derive(Deserialize)]
+struct BootAssets {
+    placeholder: String,
+    splash: Option<SplashAssets>,
+}
+
+impl Versioned for BootAssets {
+    const VERSION: u32 = 1;
+}
+
+#[derive(Deserialize)]
+struct SplashAssets {
+    logo: String,
+    caption: String,
+}
+
+// Shows the splash first, if there is one, then a bouncing placeholder for
+// as long as the main assets take after that.
+pub struct BootScene {
+    splash: Option<Splash>,
+    image: HtmlImageElement,
+    ground: i16,
+    y: i16,
+    velocity: i16,
+}
+
+impl BootScene {
+    pub async fn load(manifest_path: &str) -> Result<Self> {
+        let assets: BootAssets = versioned::fetch(manifest_path).await?;
+        let image = load_image(&assets.placeholder).await?;
+        let splash = match assets.splash {
+            Some(splash) => match load_image(&splash.logo).await {
+                Ok(logo) => Some(Splash::new(logo, &splash.caption)),
+                Err(err) => {
+                    log!("Booting without a splash {:#?}", err);
+                    None
+                }
+            },
+            None => None,
+        };
+        let ground = FLOOR - image.height() as i16;
+        Ok(BootScene {
+            splash,
+            image,
+            ground,
+            y: ground,
+            velocity: 0,
+        })
+    }
+
+    pub fn splash_playing(&self) -> bool {
+        self.splash
+            .as_ref()
+            .map_or(false, |splash| !splash.finished())
+    }
+
+    pub fn update(&mut self, keystate: &KeyState) {
+        if let Some(splash) = self.splash.as_mut().filter(|splash| !splash.finished()) {
+            splash.update(keystate);
+            return;
+        }
+        if keystate.is_pressed("Space") && self.y == self.ground {
+            self.velocity = BOUNCE_SPEED;
+        }
+        self.velocity += GRAVITY;
+        self.y = (self.y + self.velocity).min(self.ground);
+        if self.y == self.ground {
+            self.velocity = 0;
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        if let Some(splash) = self.splash.as_ref().filter(|_| self.splash_playing()) {
+            splash.draw(renderer);
+            return;
+        }
+        renderer.clear(&Rect::new_from_x_y(0, 0, CANVAS_SIZE, CANVAS_SIZE));
+        renderer.draw_entire_image(
+            &self.image,
+            &Point {
+                x: (CANVAS_SIZE - self.image.width() as i16) / 2,
+                y: self.y,
+            },
+        );
+        if let Err(err) = renderer.draw_text(
+            MESSAGE,
+            &Point {
+                x: 150,
+                y: FLOOR + 50,
+            },
+        ) {
+            log!("Could not draw loading message {:#?}", err);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Result};
+use web_sys::HtmlImageElement;
+
+use crate::browser;
+
+use super::{Point, Rect};
+
+// Pixels at least this opaque count as solid.
+const ALPHA_THRESHOLD: u8 = 128;
+
+// Which pixels of a sprite are solid, at the sprite's drawn size. Used as a
+// narrow phase after bounding boxes overlap, so jagged sprites only collide
+// where they are actually drawn.
+#[derive(Debug, PartialEq)]
+pub struct CollisionMask {
+    width: i16,
+    height: i16,
+    solid: Vec<bool>,
+}
+
+impl CollisionMask {
+    pub fn from_alpha(width: i16, height: i16, rgba: &[u8]) -> Self {
+        CollisionMask {
+            width,
+            height,
+            solid: rgba
+                .chunks_exact(4)
+                .map(|pixel| pixel[3] >= ALPHA_THRESHOLD)
+                .collect(),
+        }
+    }
+
+    // Draws the image onto an offscreen canvas and reads its alpha back, so
+    // it has to run once the image has loaded.
+    pub fn from_image(image: &HtmlImageElement) -> Result<Self> {
+        let (width, height) = (image.width(), image.height());
+        let canvas = browser::new_canvas(width, height)?;
+        let context = browser::context(&canvas)?;
+        context
+            .draw_image_with_html_image_element_and_dw_and_dh(
+                image,
+                0.0,
+                0.0,
+                width.into(),
+                height.into(),
+            )
+            .map_err(|err| anyhow!("Could not draw image for its mask {:#?}", err))?;
+        let pixels = context
+            .get_image_data(0.0, 0.0, width.into(), height.into())
+            .map_err(|err| anyhow!("Could not read image pixels {:#?}", err))?;
+        Ok(CollisionMask::from_alpha(
+            width as i16,
+            height as i16,
+            &pixels.data(),
+        ))
+    }
+
+    fn is_solid(&self, x: i16, y: i16) -> bool {
+        self.solid[(y as usize) * (self.width as usize) + x as usize]
+    }
+
+    // Whether any solid pixel of the mask, drawn at `position`, lies inside `rect`.
+    pub fn overlaps(&self, position: &Point, rect: &Rect) -> bool {
+        let left = (rect.x() - position.x).max(0);
+        let right = (rect.right() - position.x).min(self.width);
+        let top = (rect.y() - position.y).max(0);
+        let bottom = (rect.bottom() - position.y).min(self.height);
+        (top..bottom).any(|y| (left..right).any(|x| self.is_solid(x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 4x4 mask with only the bottom right quarter solid.
+    fn mask() -> CollisionMask {
+        let rgba: Vec<u8> = (0..16)
+            .flat_map(|index| {
+                let solid = index % 4 >= 2 && index / 4 >= 2;
+                [0, 0, 0, if solid { 255 } else { 0 }]
+            })
+            .collect();
+        CollisionMask::from_alpha(4, 4, &rgba)
+    }
+
+    #[test]
+    fn reads_solid_pixels_from_alpha() {
+        let mask = mask();
+
+        assert!(mask.is_solid(3, 3));
+        assert!(!mask.is_solid(1, 3));
+        assert_eq!(mask.solid.iter().filter(|solid| **solid).count(), 4);
+    }
+
+    #[test]
+    fn only_solid_pixels_overlap() {
+        let mask = mask();
+        let position = Point { x: 100, y: 200 };
+
+        assert!(!mask.overlaps(&position, &Rect::new_from_x_y(90, 190, 12, 12)));
+        assert!(mask.overlaps(&position, &Rect::new_from_x_y(90, 190, 13, 13)));
+        assert!(mask.overlaps(&position, &Rect::new_from_x_y(103, 203, 50, 50)));
+        assert!(!mask.overlaps(&position, &Rect::new_from_x_y(104, 200, 10, 10)));
+    }
+}
@@ -0,0 +1,54 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: f32,
+}
+
+impl Color {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: f32) -> Self {
+        Color { r, g, b, a }
+    }
+
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let channel =
+            |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * t).round() as u8;
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+            a: self.a + (other.a - self.a) * t,
+        }
+    }
+
+    pub fn to_css(&self) -> String {
+        format!("rgba({}, {}, {}, {})", self.r, self.g, self.b, self.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lerp_blends_each_channel() {
+        let night = Color::rgb(0, 0, 40);
+        let day = Color::rgba(100, 200, 240, 0.5);
+
+        assert_eq!(night.lerp(&day, 0.0), night);
+        assert_eq!(night.lerp(&day, 1.0), day);
+        assert_eq!(night.lerp(&day, 0.5), Color::rgba(50, 100, 140, 0.75));
+        assert_eq!(night.lerp(&day, 2.0), day);
+    }
+
+    #[test]
+    fn formats_as_css_rgba() {
+        assert_eq!(Color::rgba(1, 2, 3, 0.5).to_css(), "rgba(1, 2, 3, 0.5)");
+    }
+}
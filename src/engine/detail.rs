@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+use crate::versioned::{self, Versioned};
+
+pub const GRAPHICS_KEY: &str = "walk_the_dog.graphics";
+
+// Average frame times, in milliseconds, that count as too slow or as having
+// room to spare.
+const SLOW_FRAME_TIME: f64 = 1000.0 / 50.0;
+const FAST_FRAME_TIME: f64 = 1000.0 / 58.0;
+const SLOW_WINDOWS_TO_DROP: u8 = 2;
+const FAST_WINDOWS_TO_RAISE: u8 = 5;
+const MAX_LEVEL: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GraphicsSetting {
+    Auto,
+    High,
+    Low,
+}
+
+impl Default for GraphicsSetting {
+    fn default() -> Self {
+        GraphicsSetting::Auto
+    }
+}
+
+impl Versioned for GraphicsSetting {
+    const VERSION: u32 = 1;
+}
+
+impl GraphicsSetting {
+    pub fn load() -> Self {
+        versioned::load(GRAPHICS_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using automatic graphics {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(GRAPHICS_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            GraphicsSetting::Auto => GraphicsSetting::High,
+            GraphicsSetting::High => GraphicsSetting::Low,
+            GraphicsSetting::Low => GraphicsSetting::Auto,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GraphicsSetting::Auto => "Auto",
+            GraphicsSetting::High => "High",
+            GraphicsSetting::Low => "Low",
+        }
+    }
+}
+
+// Optional effects, dropped in this order as the detail level falls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detail {
+    pub particles: bool,
+    pub parallax: bool,
+    pub shadows: bool,
+}
+
+impl Detail {
+    fn at_level(level: u8) -> Self {
+        Detail {
+            particles: level >= 3,
+            parallax: level >= 2,
+            shadows: level >= 1,
+        }
+    }
+}
+
+impl Default for Detail {
+    fn default() -> Self {
+        Detail::at_level(MAX_LEVEL)
+    }
+}
+
+pub struct AutoDetail {
+    setting: GraphicsSetting,
+    level: u8,
+    slow_windows: u8,
+    fast_windows: u8,
+}
+
+impl AutoDetail {
+    pub fn new(setting: GraphicsSetting) -> Self {
+        AutoDetail {
+            setting,
+            level: MAX_LEVEL,
+            slow_windows: 0,
+            fast_windows: 0,
+        }
+    }
+
+    pub fn set_setting(&mut self, setting: GraphicsSetting) {
+        self.setting = setting;
+    }
+
+    // Called with the average frame time of each measuring window. Several
+    // windows in a row are needed before the level moves, so it does not flicker.
+    pub fn record(&mut self, average_frame_time: f64) {
+        if average_frame_time > SLOW_FRAME_TIME {
+            self.slow_windows += 1;
+            self.fast_windows = 0;
+        } else if average_frame_time < FAST_FRAME_TIME {
+            self.fast_windows += 1;
+            self.slow_windows = 0;
+        } else {
+            self.slow_windows = 0;
+            self.fast_windows = 0;
+        }
+
+        if self.slow_windows >= SLOW_WINDOWS_TO_DROP && self.level > 0 {
+            self.level -= 1;
+            self.slow_windows = 0;
+        }
+        if self.fast_windows >= FAST_WINDOWS_TO_RAISE && self.level < MAX_LEVEL {
+            self.level += 1;
+            self.fast_windows = 0;
+        }
+    }
+
+    pub fn detail(&self) -> Detail {
+        match self.setting {
+            GraphicsSetting::Auto => Detail::at_level(self.level),
+            GraphicsSetting::High => Detail::at_level(MAX_LEVEL),
+            GraphicsSetting::Low => Detail::at_level(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_frames_drop_effects_one_at_a_time() {
+        let mut auto = AutoDetail::new(GraphicsSetting::Auto);
+        auto.record(30.0);
+        assert_eq!(auto.detail(), Detail::default());
+
+        auto.record(30.0);
+        assert!(!auto.detail().particles);
+        assert!(auto.detail().parallax);
+
+        (0..4).for_each(|_| auto.record(30.0));
+        assert_eq!(auto.detail(), Detail::at_level(0));
+    }
+
+    #[test]
+    fn headroom_restores_effects() {
+        let mut auto = AutoDetail::new(GraphicsSetting::Auto);
+        auto.level = 0;
+        (0..FAST_WINDOWS_TO_RAISE).for_each(|_| auto.record(16.7));
+        assert_eq!(auto.level, 1);
+
+        auto.record(18.0);
+        (0..FAST_WINDOWS_TO_RAISE - 1).for_each(|_| auto.record(16.7));
+        assert_eq!(auto.level, 1);
+    }
+
+    #[test]
+    fn fixed_settings_ignore_frame_times() {
+        let mut auto = AutoDetail::new(GraphicsSetting::High);
+        (0..10).for_each(|_| auto.record(50.0));
+        assert_eq!(auto.detail(), Detail::default());
+
+        auto.set_setting(GraphicsSetting::Low);
+        assert_eq!(auto.detail(), Detail::at_level(0));
+        assert_eq!(GraphicsSetting::Low.next(), GraphicsSetting::Auto);
+    }
+}
@@ -0,0 +1,35 @@
+// Events published while a frame is updated, handled once by whoever drains
+// the bus, so publishers never need a reference to the systems reacting.
+pub struct EventBus<E> {
+    queue: Vec<E>,
+}
+
+impl<E> EventBus<E> {
+    pub fn new() -> Self {
+        EventBus { queue: vec![] }
+    }
+
+    pub fn publish(&mut self, event: E) {
+        self.queue.push(event);
+    }
+
+    // Hands back the events in the order they were published.
+    pub fn drain(&mut self) -> Vec<E> {
+        std::mem::take(&mut self.queue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_drained_once_in_order() {
+        let mut bus = EventBus::new();
+        bus.publish(1);
+        bus.publish(2);
+
+        assert_eq!(bus.drain(), vec![1, 2]);
+        assert!(bus.drain().is_empty());
+    }
+}
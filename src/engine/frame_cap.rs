@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use web_sys::BatteryManager;
+
+use crate::versioned::{self, Versioned};
+
+pub const FRAME_RATE_KEY: &str = "walk_the_dog.frame_rate";
+
+// At or below this charge, and not charging, the automatic setting draws at
+// half rate to save battery.
+const LOW_BATTERY: f64 = 0.2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum FrameRateSetting {
+    #[default]
+    Auto,
+    Full,
+    Saver,
+}
+
+impl Versioned for FrameRateSetting {
+    const VERSION: u32 = 1;
+}
+
+impl FrameRateSetting {
+    pub fn load() -> Self {
+        versioned::load(FRAME_RATE_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the automatic frame rate {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(FRAME_RATE_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            FrameRateSetting::Auto => FrameRateSetting::Full,
+            FrameRateSetting::Full => FrameRateSetting::Saver,
+            FrameRateSetting::Saver => FrameRateSetting::Auto,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FrameRateSetting::Auto => "Auto",
+            FrameRateSetting::Full => "60 FPS",
+            FrameRateSetting::Saver => "30 FPS",
+        }
+    }
+
+    // `battery` is the charge from 0 to 1 and whether it is charging, when
+    // the browser says.
+    fn halved(&self, battery: Option<(f64, bool)>) -> bool {
+        match self {
+            FrameRateSetting::Auto => {
+                battery.map_or(false, |(level, charging)| !charging && level <= LOW_BATTERY)
+            }
+            FrameRateSetting::Full => false,
+            FrameRateSetting::Saver => true,
+        }
+    }
+}
+
+// Skips every other animation frame while halved. The game still updates at
+// 60Hz, since the time a skipped frame would have taken is caught up on the
+// next one.
+#[derive(Debug, Default)]
+pub struct FrameCap {
+    battery: Option<BatteryManager>,
+    halved: bool,
+    skipped: bool,
+}
+
+impl FrameCap {
+    pub fn new(battery: Option<BatteryManager>) -> Self {
+        FrameCap {
+            battery,
+            ..FrameCap::default()
+        }
+    }
+
+    // The battery is read live, so this only needs calling now and then.
+    pub fn update(&mut self, setting: FrameRateSetting) {
+        let battery = self
+            .battery
+            .as_ref()
+            .map(|battery| (battery.level(), battery.charging()));
+        self.halved = setting.halved(battery);
+    }
+
+    // Whether to skip this animation frame.
+    pub fn skip(&mut self) -> bool {
+        self.skipped = self.halved && !self.skipped;
+        self.skipped
+    }
+
+    // Animation frames per frame drawn.
+    pub fn interval(&self) -> f64 {
+        if self.halved {
+            2.0
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_saves_battery_only_when_low_and_unplugged() {
+        let auto = FrameRateSetting::Auto;
+
+        assert!(auto.halved(Some((0.15, false))));
+        assert!(!auto.halved(Some((0.15, true))));
+        assert!(!auto.halved(Some((0.8, false))));
+        assert!(!auto.halved(None));
+        assert!(FrameRateSetting::Saver.halved(None));
+        assert!(!FrameRateSetting::Full.halved(Some((0.05, false))));
+    }
+
+    #[test]
+    fn halved_caps_skip_every_other_frame() {
+        let mut cap = FrameCap::default();
+        assert!(!cap.skip());
+
+        cap.update(FrameRateSetting::Saver);
+        let skips: Vec<bool> = (0..4).map(|_frame| cap.skip()).collect();
+        assert_eq!(skips, vec![true, false, true, false]);
+        assert_eq!(cap.interval(), 2.0);
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton};
+
+use super::KeyState;
+use crate::{
+    browser,
+    versioned::{self, Versioned},
+};
+
+pub const RUMBLE_KEY: &str = "walk_the_dog.rumble";
+
+// Buttons in the standard gamepad layout and the keys they stand in for.
+const BUTTON_KEYS: [(usize, &str); 4] = [
+    (0, "Space"),       // A / cross
+    (9, "Escape"),      // Start
+    (12, "Space"),      // D-pad up
+    (15, "ArrowRight"), // D-pad right
+];
+// How far the left stick has to be pushed right to count.
+const STICK_THRESHOLD: f64 = 0.5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum RumbleSetting {
+    #[default]
+    On,
+    Off,
+}
+
+impl Versioned for RumbleSetting {
+    const VERSION: u32 = 1;
+}
+
+impl RumbleSetting {
+    pub fn load() -> Self {
+        versioned::load(RUMBLE_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the default rumble setting {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(RUMBLE_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            RumbleSetting::On => RumbleSetting::Off,
+            RumbleSetting::Off => RumbleSetting::On,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RumbleSetting::On => "On",
+            RumbleSetting::Off => "Off",
+        }
+    }
+}
+
+// Keys held down by any connected pad.
+fn held_keys(buttons: &[bool], axes: &[f64]) -> HashSet<&'static str> {
+    let mut keys: HashSet<&'static str> = BUTTON_KEYS
+        .iter()
+        .filter(|(button, _key)| buttons.get(*button).copied().unwrap_or(false))
+        .map(|(_button, key)| *key)
+        .collect();
+    if axes.first().map_or(false, |x| *x > STICK_THRESHOLD) {
+        keys.insert("ArrowRight");
+    }
+    keys
+}
+
+// Presses and releases keys in a `KeyState` as gamepad buttons change, so
+// the game reads a pad the same way as the keyboard.
+#[derive(Debug, Default)]
+pub struct GamepadInput {
+    held: HashSet<&'static str>,
+}
+
+impl GamepadInput {
+    // Pads are polled rather than evented, so this runs every frame.
+    pub fn poll(&mut self, keystate: &mut KeyState) {
+        let pads = browser::gamepads().unwrap_or_else(|err| {
+            log!("Could not poll gamepads {:#?}", err);
+            vec![]
+        });
+        let held = pads
+            .iter()
+            .flat_map(|pad| held_keys(&pressed_buttons(pad), &axes(pad)))
+            .collect();
+        self.apply(held, keystate);
+    }
+
+    // Only keys a pad pressed are released, so the keyboard is left alone.
+    fn apply(&mut self, held: HashSet<&'static str>, keystate: &mut KeyState) {
+        self.held
+            .difference(&held)
+            .for_each(|key| keystate.set_released(key));
+        held.difference(&self.held)
+            .for_each(|key| keystate.set_pressed(key));
+        self.held = held;
+    }
+}
+
+fn pressed_buttons(pad: &Gamepad) -> Vec<bool> {
+    pad.buttons()
+        .iter()
+        .map(|button| {
+            button
+                .dyn_into::<GamepadButton>()
+                .map_or(false, |button| button.pressed())
+        })
+        .collect()
+}
+
+fn axes(pad: &Gamepad) -> Vec<f64> {
+    pad.axes()
+        .iter()
+        .map(|axis| axis.as_f64().unwrap_or(0.0))
+        .collect()
+}
+
+// What the boy ran into, from a light tap on landing to a knockout.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Impact {
+    Landing,
+    Damage,
+    KnockOut,
+}
+
+impl Impact {
+    // Milliseconds, the weakest pulse and how much of it the heavy motor
+    // takes on.
+    fn shape(&self) -> (f64, f64, f64) {
+        match self {
+            Impact::Landing => (80.0, 0.0, 0.3),
+            Impact::Damage => (150.0, 0.4, 0.6),
+            Impact::KnockOut => (300.0, 0.7, 1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rumble {
+    pub duration: f64,
+    pub strong: f64,
+    pub weak: f64,
+}
+
+impl Rumble {
+    // Harder the closer `velocity` gets to `terminal_velocity`.
+    pub fn for_impact(impact: Impact, velocity: i16, terminal_velocity: i16) -> Self {
+        let (duration, floor, heavy) = impact.shape();
+        let speed = f64::from(velocity.unsigned_abs()) / f64::from(terminal_velocity.max(1));
+        let weak = floor + (1.0 - floor) * speed.min(1.0);
+        Rumble {
+            duration,
+            strong: weak * heavy,
+            weak,
+        }
+    }
+}
+
+// Rumbles connected pads, unless turned off in the settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Haptics {
+    muted: bool,
+}
+
+impl Haptics {
+    // A copy that rumbles nothing, for simulated runs and replays.
+    pub fn muted(&self) -> Self {
+        Haptics { muted: true }
+    }
+
+    pub fn play(&self, rumble: Rumble) {
+        if self.muted || RumbleSetting::load() == RumbleSetting::Off {
+            return;
+        }
+        let result = browser::gamepads().and_then(|pads| {
+            pads.iter().try_for_each(|pad| {
+                browser::dual_rumble(pad, rumble.duration, rumble.strong, rumble.weak)
+            })
+        });
+        if let Err(err) = result {
+            log!("Could not rumble {:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buttons_and_stick_stand_in_for_keys() {
+        let mut buttons = vec![false; 16];
+        buttons[0] = true;
+        buttons[12] = true;
+        let keys = held_keys(&buttons, &[0.9, 0.0]);
+
+        assert_eq!(keys, HashSet::from(["Space", "ArrowRight"]));
+        assert!(held_keys(&[], &[0.2]).is_empty());
+    }
+
+    #[test]
+    fn pads_only_release_keys_they_pressed() {
+        let mut input = GamepadInput::default();
+        let mut keystate = KeyState::holding(&["ArrowRight"]);
+        input.apply(HashSet::from(["Space"]), &mut keystate);
+        assert!(keystate.is_pressed("Space"));
+
+        input.apply(HashSet::new(), &mut keystate);
+        assert!(!keystate.is_pressed("Space"));
+        assert!(keystate.is_pressed("ArrowRight"));
+    }
+
+    #[test]
+    fn harder_impacts_rumble_harder() {
+        let soft = Rumble::for_impact(Impact::Landing, 4, 20);
+        let hard = Rumble::for_impact(Impact::Landing, 20, 20);
+        assert!(soft.weak < hard.weak);
+        assert_eq!(hard.weak, 1.0);
+
+        let knockout = Rumble::for_impact(Impact::KnockOut, 0, 20);
+        assert!(knockout.strong > Rumble::for_impact(Impact::Damage, 0, 20).strong);
+        assert!(Rumble::for_impact(Impact::KnockOut, -40, 20).weak <= 1.0);
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::Result;
+use web_sys::HtmlCanvasElement;
+
+use super::{Align, Point};
+use crate::browser;
+
+const CANVAS_SIZE: i16 = 600;
+
+// Which corner or edge of the canvas a HUD element hangs from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopCenter,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    // Text hanging from the right edge grows leftwards, and so on.
+    pub fn align(&self) -> Align {
+        match self {
+            Anchor::TopLeft | Anchor::BottomLeft => Align::Left,
+            Anchor::TopCenter => Align::Center,
+            Anchor::TopRight | Anchor::BottomRight => Align::Right,
+        }
+    }
+}
+
+// Canvas pixels along each edge hidden behind a notch or rounded corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Insets {
+    pub top: i16,
+    pub right: i16,
+    pub bottom: i16,
+    pub left: i16,
+}
+
+impl Insets {
+    // `safe_area` is the page's safe-area insets and `canvas` the canvas's
+    // edges, both in CSS pixels from the top left of a `viewport` sized page,
+    // as (top, right, bottom, left). Only the part of an inset that reaches
+    // over the canvas counts, scaled to canvas pixels.
+    fn covering(safe_area: [f64; 4], canvas: [f64; 4], viewport: (f64, f64), scale: f64) -> Self {
+        let [safe_top, safe_right, safe_bottom, safe_left] = safe_area;
+        let [top, right, bottom, left] = canvas;
+        let (width, height) = viewport;
+        let overlap = |inset: f64, gap: f64| ((inset - gap).max(0.0) * scale).round() as i16;
+        Insets {
+            top: overlap(safe_top, top),
+            right: overlap(safe_right, width - right),
+            bottom: overlap(safe_bottom, height - bottom),
+            left: overlap(safe_left, left),
+        }
+    }
+}
+
+// Places HUD elements relative to the edges of the canvas, clear of any safe
+// area insets, whatever size the canvas is shown at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HudLayout {
+    width: i16,
+    height: i16,
+    insets: Insets,
+}
+
+impl Default for HudLayout {
+    fn default() -> Self {
+        HudLayout::new(CANVAS_SIZE, CANVAS_SIZE, Insets::default())
+    }
+}
+
+impl HudLayout {
+    pub fn new(width: i16, height: i16, insets: Insets) -> Self {
+        HudLayout {
+            width,
+            height,
+            insets,
+        }
+    }
+
+    // Reads the safe area from the page's `env(safe-area-inset-*)` values.
+    pub fn measure(canvas: &HtmlCanvasElement) -> Result<Self> {
+        let bounds = canvas.get_bounding_client_rect();
+        let scale = if bounds.width() > 0.0 {
+            f64::from(canvas.width()) / bounds.width()
+        } else {
+            1.0
+        };
+        Ok(HudLayout::new(
+            canvas.width() as i16,
+            canvas.height() as i16,
+            Insets::covering(
+                browser::safe_area_insets()?,
+                [bounds.top(), bounds.right(), bounds.bottom(), bounds.left()],
+                browser::viewport_size()?,
+                scale,
+            ),
+        ))
+    }
+
+    // Where something `offset` in from `anchor` goes. Offsets always point
+    // into the canvas: down from the top, left from the right and so on.
+    pub fn position(&self, anchor: Anchor, offset: Point) -> Point {
+        let left = self.insets.left + offset.x;
+        let right = self.width - self.insets.right - offset.x;
+        let top = self.insets.top + offset.y;
+        let bottom = self.height - self.insets.bottom - offset.y;
+        match anchor {
+            Anchor::TopLeft => Point { x: left, y: top },
+            Anchor::TopCenter => Point {
+                x: (self.insets.left + self.width - self.insets.right) / 2 + offset.x,
+                y: top,
+            },
+            Anchor::TopRight => Point { x: right, y: top },
+            Anchor::BottomLeft => Point { x: left, y: bottom },
+            Anchor::BottomRight => Point {
+                x: right,
+                y: bottom,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchors_sit_at_their_edges() {
+        let layout = HudLayout::default();
+        let offset = Point { x: 20, y: 30 };
+
+        assert_eq!(
+            layout.position(Anchor::TopLeft, offset),
+            Point { x: 20, y: 30 }
+        );
+        assert_eq!(
+            layout.position(Anchor::TopRight, offset),
+            Point { x: 580, y: 30 }
+        );
+        assert_eq!(
+            layout.position(Anchor::BottomRight, offset),
+            Point { x: 580, y: 570 }
+        );
+        assert_eq!(
+            layout.position(Anchor::TopCenter, Point { x: 0, y: 30 }),
+            Point { x: 300, y: 30 }
+        );
+    }
+
+    #[test]
+    fn insets_push_elements_inwards() {
+        let insets = Insets {
+            top: 40,
+            right: 0,
+            bottom: 0,
+            left: 60,
+        };
+        let layout = HudLayout::new(600, 600, insets);
+
+        assert_eq!(
+            layout.position(Anchor::TopLeft, Point { x: 20, y: 30 }),
+            Point { x: 80, y: 70 }
+        );
+        assert_eq!(
+            layout.position(Anchor::TopCenter, Point { x: 0, y: 0 }),
+            Point { x: 330, y: 40 }
+        );
+    }
+
+    #[test]
+    fn only_insets_reaching_the_canvas_count() {
+        // A 300px wide canvas shown 100px from the left of a phone whose notch
+        // covers 120px, drawing 600 canvas pixels across.
+        let insets = Insets::covering(
+            [44.0, 0.0, 0.0, 120.0],
+            [0.0, 400.0, 300.0, 100.0],
+            (800.0, 400.0),
+            2.0,
+        );
+
+        assert_eq!(
+            insets,
+            Insets {
+                top: 88,
+                right: 0,
+                bottom: 0,
+                left: 40
+            }
+        );
+    }
+}
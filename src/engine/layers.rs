@@ -0,0 +1,190 @@
+#[cfg(debug_assertions)]
+use anyhow::{anyhow, Result};
+
+use super::{Point, Renderer};
+
+// Back to front by default. Only the middle three scroll with the camera:
+// the background does its own parallax and the HUD stays put.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Background,
+    Midground,
+    Entities,
+    Fx,
+    Hud,
+}
+
+impl Layer {
+    #[cfg(debug_assertions)]
+    pub const ALL: [Layer; 5] = [
+        Layer::Background,
+        Layer::Midground,
+        Layer::Entities,
+        Layer::Fx,
+        Layer::Hud,
+    ];
+
+    #[cfg(debug_assertions)]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Layer::ALL
+            .iter()
+            .copied()
+            .find(|layer| layer.name() == name)
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::Background => "background",
+            Layer::Midground => "midground",
+            Layer::Entities => "entities",
+            Layer::Fx => "fx",
+            Layer::Hud => "hud",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Layer::Background => 0,
+            Layer::Midground => 1,
+            Layer::Entities => 2,
+            Layer::Fx => 3,
+            Layer::Hud => 4,
+        }
+    }
+
+    fn in_world(&self) -> bool {
+        matches!(self, Layer::Midground | Layer::Entities | Layer::Fx)
+    }
+}
+
+// Each layer's z-index and whether it is drawn at all.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layers {
+    z: [i16; 5],
+    visible: [bool; 5],
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers {
+            z: [0, 100, 200, 300, 400],
+            visible: [true; 5],
+        }
+    }
+}
+
+impl Layers {
+    pub fn z(&self, layer: Layer) -> i16 {
+        self.z[layer.index()]
+    }
+
+    pub fn visible(&self, layer: Layer) -> bool {
+        self.visible[layer.index()]
+    }
+
+    // `on`, `off` or a z-index, as typed into the console.
+    #[cfg(debug_assertions)]
+    pub fn set(&mut self, layer: Layer, value: &str) -> Result<()> {
+        let index = layer.index();
+        match value {
+            "on" => self.visible[index] = true,
+            "off" => self.visible[index] = false,
+            z => {
+                self.z[index] = z
+                    .parse()
+                    .map_err(|err| anyhow!("{} is not on, off or a z-index {:#?}", z, err))?
+            }
+        }
+        Ok(())
+    }
+}
+
+type Draw<'a> = Box<dyn FnOnce(&Renderer) + 'a>;
+
+// Draws collected over a frame and played back by layer, so a system only
+// says which layer it belongs on rather than where in the frame it goes.
+// Draws on the same layer keep the order they were pushed in.
+#[derive(Default)]
+pub struct DrawList<'a> {
+    draws: Vec<(Layer, Draw<'a>)>,
+}
+
+impl<'a> DrawList<'a> {
+    pub fn new() -> Self {
+        DrawList::default()
+    }
+
+    pub fn push(&mut self, layer: Layer, draw: impl FnOnce(&Renderer) + 'a) {
+        self.draws.push((layer, Box::new(draw)));
+    }
+
+    fn sorted(self, layers: &Layers) -> Vec<(Layer, Draw<'a>)> {
+        let mut draws: Vec<(Layer, Draw<'a>)> = self
+            .draws
+            .into_iter()
+            .filter(|(layer, _draw)| layers.visible(*layer))
+            .collect();
+        draws.sort_by_key(|(layer, _draw)| layers.z(*layer));
+        draws
+    }
+
+    // World layers are drawn shifted by `camera`.
+    pub fn draw(self, renderer: &Renderer, layers: &Layers, camera: &Point) {
+        self.sorted(layers).into_iter().for_each(|(layer, draw)| {
+            if layer.in_world() {
+                renderer.draw_translated(camera, || draw(renderer));
+            } else {
+                draw(renderer);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(draws: DrawList, layers: &Layers) -> Vec<Layer> {
+        draws
+            .sorted(layers)
+            .into_iter()
+            .map(|(layer, _draw)| layer)
+            .collect()
+    }
+
+    #[test]
+    fn draws_play_back_to_front_by_layer() {
+        let mut draws = DrawList::new();
+        draws.push(Layer::Hud, |_renderer| {});
+        draws.push(Layer::Entities, |_renderer| {});
+        draws.push(Layer::Background, |_renderer| {});
+
+        assert_eq!(
+            order(draws, &Layers::default()),
+            vec![Layer::Background, Layer::Entities, Layer::Hud]
+        );
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn z_indices_reorder_and_hidden_layers_are_skipped() {
+        let mut layers = Layers::default();
+        layers.set(Layer::Fx, "150").unwrap();
+        layers.set(Layer::Background, "off").unwrap();
+        assert!(layers.set(Layer::Hud, "top").is_err());
+        let mut draws = DrawList::new();
+        draws.push(Layer::Background, |_renderer| {});
+        draws.push(Layer::Entities, |_renderer| {});
+        draws.push(Layer::Fx, |_renderer| {});
+
+        assert_eq!(order(draws, &layers), vec![Layer::Fx, Layer::Entities]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn layers_are_named_for_the_console() {
+        assert_eq!(Layer::from_name("fx"), Some(Layer::Fx));
+        assert_eq!(Layer::from_name("sky"), None);
+    }
+}
@@ -0,0 +1,155 @@
+use std::cell::RefCell;
+
+use super::{Color, Point, Renderer, TextStyle};
+
+const TRACKED: [Tracked; 5] = [
+    Tracked::Closures,
+    Tracked::AudioSources,
+    Tracked::Obstacles,
+    Tracked::Particles,
+    Tracked::WasmKb,
+];
+// A count higher at each of this many restarts than at the one before is
+// warned about. Anything the game lets go of on a restart stays level.
+const TREND_RESTARTS: usize = 3;
+const POSITION: Point = Point { x: 400, y: 200 };
+const LINE_HEIGHT: i16 = 20;
+const GROWING_COLOR: Color = Color::rgb(255, 80, 80);
+
+// What the debug overlay keeps a count of, to catch leaks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tracked {
+    // Listeners handed to the browser and not yet taken back. Forgotten ones
+    // never are.
+    Closures,
+    AudioSources,
+    Obstacles,
+    Particles,
+    WasmKb,
+}
+
+impl Tracked {
+    fn name(&self) -> &'static str {
+        match self {
+            Tracked::Closures => "closures",
+            Tracked::AudioSources => "audio sources",
+            Tracked::Obstacles => "obstacles",
+            Tracked::Particles => "particles",
+            Tracked::WasmKb => "wasm KB",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Tracked::Closures => 0,
+            Tracked::AudioSources => 1,
+            Tracked::Obstacles => 2,
+            Tracked::Particles => 3,
+            Tracked::WasmKb => 4,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    now: [usize; 5],
+    // The counts at the last few restarts, oldest first.
+    restarts: Vec<[usize; 5]>,
+}
+
+impl Counts {
+    fn restart(&mut self) {
+        if self.restarts.len() > TREND_RESTARTS {
+            self.restarts.remove(0);
+        }
+        self.restarts.push(self.now);
+    }
+
+    fn growing(&self, tracked: Tracked) -> bool {
+        let index = tracked.index();
+        self.restarts.len() > TREND_RESTARTS
+            && self
+                .restarts
+                .windows(2)
+                .all(|pair| pair[1][index] > pair[0][index])
+    }
+}
+
+thread_local! {
+    static COUNTS: RefCell<Counts> = RefCell::new(Counts::default());
+}
+
+pub fn add(tracked: Tracked, count: usize) {
+    COUNTS.with(|counts| counts.borrow_mut().now[tracked.index()] += count);
+}
+
+pub fn subtract(tracked: Tracked, count: usize) {
+    COUNTS.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        let now = &mut counts.now[tracked.index()];
+        *now = now.saturating_sub(count);
+    });
+}
+
+pub fn set(tracked: Tracked, count: usize) {
+    COUNTS.with(|counts| counts.borrow_mut().now[tracked.index()] = count);
+}
+
+// Takes the counts as a run starts over, to compare with later restarts.
+pub fn restarted() {
+    COUNTS.with(|counts| counts.borrow_mut().restart());
+}
+
+// Under the entity counts, turning red for any count that keeps growing
+// from one restart to the next.
+pub fn draw(renderer: &Renderer) {
+    COUNTS.with(|counts| {
+        let counts = counts.borrow();
+        TRACKED.iter().zip(0..).for_each(|(tracked, line)| {
+            let mut style = TextStyle::default();
+            let mut text = format!("{} {}", tracked.name(), counts.now[tracked.index()]);
+            if counts.growing(*tracked) {
+                style = style.with_color(GROWING_COLOR);
+                text.push_str(" (growing every restart)");
+            }
+            let position = Point {
+                x: POSITION.x,
+                y: POSITION.y + line * LINE_HEIGHT,
+            };
+            if let Err(err) = renderer.draw_styled_text(&text, &position, &style) {
+                log!("Could not draw memory counts {:#?}", err);
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_that_rise_at_every_restart_are_growing() {
+        let mut counts = Counts::default();
+        (0..6).for_each(|run| {
+            counts.now[Tracked::Closures.index()] += 3;
+            counts.now[Tracked::Obstacles.index()] = 4 + run % 2;
+            counts.restart();
+        });
+
+        assert_eq!(counts.restarts.len(), TREND_RESTARTS + 1);
+        assert!(counts.growing(Tracked::Closures));
+        assert!(!counts.growing(Tracked::Obstacles));
+        assert!(!counts.growing(Tracked::WasmKb));
+    }
+
+    #[test]
+    fn growth_is_only_judged_after_enough_restarts() {
+        let mut counts = Counts::default();
+        (0..TREND_RESTARTS).for_each(|_run| {
+            counts.now[Tracked::Closures.index()] += 1;
+            counts.restart();
+        });
+
+        assert!(!counts.growing(Tracked::Closures));
+    }
+}
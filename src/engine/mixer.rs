@@ -0,0 +1,262 @@
+use serde::{Deserialize, Serialize};
+
+use crate::versioned::{self, Versioned};
+
+pub const MIXER_KEY: &str = "walk_the_dog.mixer";
+pub const PAUSE_MUSIC_KEY: &str = "walk_the_dog.pause_music";
+const FULL_VOLUME: u8 = 100;
+// Buses whose sounds are cut off by a pause. Interface sounds carry on, as
+// the menus still work while paused.
+const PAUSED_BUSES: [Bus; 2] = [Bus::Sfx, Bus::Voice];
+
+// Every sound plays on one of these, each with its own volume on the way to
+// the master volume.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bus {
+    Music,
+    Sfx,
+    Ui,
+    Voice,
+}
+
+impl Bus {
+    pub const ALL: [Bus; 4] = [Bus::Music, Bus::Sfx, Bus::Ui, Bus::Voice];
+
+    pub fn index(&self) -> usize {
+        match self {
+            Bus::Music => 0,
+            Bus::Sfx => 1,
+            Bus::Ui => 2,
+            Bus::Voice => 3,
+        }
+    }
+}
+
+// One slider in the settings: a bus, or the master volume they all go through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fader {
+    Master,
+    Bus(Bus),
+}
+
+impl Fader {
+    pub const ALL: [Fader; 5] = [
+        Fader::Master,
+        Fader::Bus(Bus::Music),
+        Fader::Bus(Bus::Sfx),
+        Fader::Bus(Bus::Ui),
+        Fader::Bus(Bus::Voice),
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Fader::Master => "Master volume",
+            Fader::Bus(Bus::Music) => "Music",
+            Fader::Bus(Bus::Sfx) => "Sound effects",
+            Fader::Bus(Bus::Ui) => "Interface",
+            Fader::Bus(Bus::Voice) => "Voice",
+        }
+    }
+
+    // The class of its slider in the settings.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Fader::Master => "volume_master",
+            Fader::Bus(Bus::Music) => "volume_music",
+            Fader::Bus(Bus::Sfx) => "volume_sfx",
+            Fader::Bus(Bus::Ui) => "volume_ui",
+            Fader::Bus(Bus::Voice) => "volume_voice",
+        }
+    }
+}
+
+// Volumes in percent.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MixerSetting {
+    master: u8,
+    music: u8,
+    sfx: u8,
+    ui: u8,
+    voice: u8,
+}
+
+impl Default for MixerSetting {
+    fn default() -> Self {
+        MixerSetting {
+            master: FULL_VOLUME,
+            music: FULL_VOLUME,
+            sfx: FULL_VOLUME,
+            ui: FULL_VOLUME,
+            voice: FULL_VOLUME,
+        }
+    }
+}
+
+impl Versioned for MixerSetting {
+    const VERSION: u32 = 1;
+}
+
+impl MixerSetting {
+    pub fn load() -> Self {
+        versioned::load(MIXER_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the default mixer setting {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(MIXER_KEY, self)
+    }
+
+    pub fn level(&self, fader: Fader) -> u8 {
+        match fader {
+            Fader::Master => self.master,
+            Fader::Bus(Bus::Music) => self.music,
+            Fader::Bus(Bus::Sfx) => self.sfx,
+            Fader::Bus(Bus::Ui) => self.ui,
+            Fader::Bus(Bus::Voice) => self.voice,
+        }
+    }
+
+    pub fn set_level(&mut self, fader: Fader, level: u8) {
+        let level = level.min(FULL_VOLUME);
+        match fader {
+            Fader::Master => self.master = level,
+            Fader::Bus(Bus::Music) => self.music = level,
+            Fader::Bus(Bus::Sfx) => self.sfx = level,
+            Fader::Bus(Bus::Ui) => self.ui = level,
+            Fader::Bus(Bus::Voice) => self.voice = level,
+        }
+    }
+
+    // What the fader's gain node is set to.
+    pub fn gain(&self, fader: Fader) -> f32 {
+        f32::from(self.level(fader)) / f32::from(FULL_VOLUME)
+    }
+}
+
+// What happens to the music while the game is paused.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum PauseMusicSetting {
+    #[default]
+    Lower,
+    Keep,
+}
+
+impl Versioned for PauseMusicSetting {
+    const VERSION: u32 = 1;
+}
+
+impl PauseMusicSetting {
+    pub fn load() -> Self {
+        versioned::load(PAUSE_MUSIC_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the default pause music setting {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(PAUSE_MUSIC_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            PauseMusicSetting::Lower => PauseMusicSetting::Keep,
+            PauseMusicSetting::Keep => PauseMusicSetting::Lower,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PauseMusicSetting::Lower => "Lower",
+            PauseMusicSetting::Keep => "Keep",
+        }
+    }
+}
+
+// One-shot sounds still playing, with the bus each is on and when it ends in
+// audio context seconds.
+#[derive(Debug)]
+pub struct LiveSources<T> {
+    sources: Vec<(T, Bus, f64)>,
+}
+
+impl<T> Default for LiveSources<T> {
+    fn default() -> Self {
+        LiveSources { sources: vec![] }
+    }
+}
+
+impl<T> LiveSources<T> {
+    // Sounds that have finished by `now` are forgotten as new ones start.
+    pub fn track(&mut self, source: T, bus: Bus, ends_at: f64, now: f64) {
+        self.sources
+            .retain(|(_source, _bus, ends_at)| *ends_at > now);
+        self.sources.push((source, bus, ends_at));
+    }
+
+    // How many were still playing when the last one started.
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    // The sounds a pause cuts off, no longer tracked.
+    pub fn pause(&mut self, now: f64) -> Vec<T> {
+        let (paused, kept) = std::mem::take(&mut self.sources)
+            .into_iter()
+            .filter(|(_source, _bus, ends_at)| *ends_at > now)
+            .partition(|(_source, bus, _ends_at)| PAUSED_BUSES.contains(bus));
+        self.sources = kept;
+        paused
+            .into_iter()
+            .map(|(source, _bus, _ends_at)| source)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_are_kept_per_fader_and_capped() {
+        let mut mixer = MixerSetting::default();
+        mixer.set_level(Fader::Bus(Bus::Music), 40);
+        mixer.set_level(Fader::Master, 250);
+
+        assert_eq!(mixer.level(Fader::Bus(Bus::Music)), 40);
+        assert_eq!(mixer.level(Fader::Bus(Bus::Voice)), 100);
+        assert_eq!(mixer.gain(Fader::Master), 1.0);
+        assert_eq!(mixer.gain(Fader::Bus(Bus::Music)), 0.4);
+    }
+
+    #[test]
+    fn pauses_cut_off_sounds_still_playing() {
+        let mut live = LiveSources::default();
+        live.track("jump", Bus::Sfx, 1.0, 0.0);
+        live.track("jingle", Bus::Voice, 3.0, 0.5);
+        live.track("click", Bus::Ui, 3.0, 0.5);
+        live.track("footstep", Bus::Sfx, 3.0, 2.0);
+
+        assert_eq!(live.sources.len(), 3);
+        assert_eq!(live.pause(2.5), vec!["jingle", "footstep"]);
+        assert!(live.pause(2.5).is_empty());
+        assert_eq!(live.sources.len(), 1);
+    }
+
+    #[test]
+    fn every_fader_has_its_own_slider() {
+        let classes: std::collections::HashSet<&str> =
+            Fader::ALL.iter().map(Fader::class).collect();
+
+        assert_eq!(classes.len(), Fader::ALL.len());
+        assert!(Bus::ALL
+            .iter()
+            .enumerate()
+            .all(|(index, bus)| bus.index() == index));
+    }
+}
@@ -0,0 +1,105 @@
+use super::Rect;
+
+// Border widths of a frame. Corners are drawn unscaled, edges stretch along
+// one axis and the center stretches along both.
+#[derive(Clone, Copy)]
+pub struct NineSlice {
+    pub left: i16,
+    pub top: i16,
+    pub right: i16,
+    pub bottom: i16,
+}
+
+impl NineSlice {
+    pub const fn uniform(border: i16) -> Self {
+        NineSlice {
+            left: border,
+            top: border,
+            right: border,
+            bottom: border,
+        }
+    }
+
+    pub fn slices(&self, source: &Rect, destination: &Rect) -> Vec<(Rect, Rect)> {
+        let source_columns = spans(source.x(), source.width, self.left, self.right);
+        let source_rows = spans(source.y(), source.height, self.top, self.bottom);
+        let destination_columns = spans(destination.x(), destination.width, self.left, self.right);
+        let destination_rows = spans(destination.y(), destination.height, self.top, self.bottom);
+
+        source_rows
+            .iter()
+            .zip(destination_rows.iter())
+            .flat_map(|(source_row, destination_row)| {
+                source_columns.iter().zip(destination_columns.iter()).map(
+                    move |(source_column, destination_column)| {
+                        (
+                            Rect::new_from_x_y(
+                                source_column.0,
+                                source_row.0,
+                                source_column.1,
+                                source_row.1,
+                            ),
+                            Rect::new_from_x_y(
+                                destination_column.0,
+                                destination_row.0,
+                                destination_column.1,
+                                destination_row.1,
+                            ),
+                        )
+                    },
+                )
+            })
+            .filter(|(source, destination)| {
+                source.width > 0
+                    && source.height > 0
+                    && destination.width > 0
+                    && destination.height > 0
+            })
+            .collect()
+    }
+}
+
+// Splits a length into (start, length) pairs for the leading border, the
+// stretchable middle and the trailing border.
+fn spans(start: i16, length: i16, leading: i16, trailing: i16) -> [(i16, i16); 3] {
+    let middle = (length - leading - trailing).max(0);
+    [
+        (start, leading),
+        (start + leading, middle),
+        (start + leading + middle, trailing),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn corners_keep_their_size_and_center_stretches() {
+        let source = Rect::new_from_x_y(10, 10, 30, 30);
+        let destination = Rect::new_from_x_y(0, 0, 200, 100);
+        let slices = NineSlice::uniform(10).slices(&source, &destination);
+
+        assert_eq!(slices.len(), 9);
+        assert_eq!(slices[0].0, Rect::new_from_x_y(10, 10, 10, 10));
+        assert_eq!(slices[0].1, Rect::new_from_x_y(0, 0, 10, 10));
+        assert_eq!(slices[4].0, Rect::new_from_x_y(20, 20, 10, 10));
+        assert_eq!(slices[4].1, Rect::new_from_x_y(10, 10, 180, 80));
+        assert_eq!(slices[8].1, Rect::new_from_x_y(190, 90, 10, 10));
+    }
+
+    #[test]
+    fn empty_borders_are_skipped() {
+        let source = Rect::new_from_x_y(0, 0, 30, 30);
+        let destination = Rect::new_from_x_y(0, 0, 60, 60);
+        let slices = NineSlice {
+            left: 10,
+            top: 0,
+            right: 10,
+            bottom: 0,
+        }
+        .slices(&source, &destination);
+
+        assert_eq!(slices.len(), 3);
+    }
+}
@@ -0,0 +1,398 @@
+use serde::Deserialize;
+
+use crate::{
+    config::Physics,
+    engine::{Point, Rect},
+};
+
+// Long enough for any jump, so a fall that never lands still ends.
+const MAX_TRAJECTORY_FRAMES: u8 = u8::MAX;
+
+// Something moving under the game's physics, with its velocity in pixels a
+// frame. Down is positive, as on the canvas.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Body {
+    pub position: Point,
+    pub velocity: Point,
+}
+
+// What a force field does to a body inside it.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Force {
+    // Holds a fall to `fall` pixels a frame, so the body drifts down and stays
+    // in the air longer. Rising bodies are left alone.
+    Updraft { fall: i16 },
+    // Takes `drag` off the speed across, though never below nothing.
+    Headwind { drag: i16 },
+}
+
+// Constants that differ from the usual ones somewhere, like underwater.
+// Anything left out stays as it was.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub struct Overrides {
+    pub gravity: Option<i16>,
+    pub gravity_period: Option<u8>,
+    pub terminal_velocity: Option<i16>,
+    pub jump_speed: Option<i16>,
+}
+
+impl Overrides {
+    pub fn apply(&self, physics: Physics) -> Physics {
+        Physics {
+            gravity: self.gravity.unwrap_or(physics.gravity),
+            gravity_period: self.gravity_period.unwrap_or(physics.gravity_period),
+            terminal_velocity: self.terminal_velocity.unwrap_or(physics.terminal_velocity),
+            jump_speed: self.jump_speed.unwrap_or(physics.jump_speed),
+            ..physics
+        }
+    }
+}
+
+// A region of the world where a force acts.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForceField {
+    pub force: Force,
+    pub area: Rect,
+}
+
+impl ForceField {
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.area.set_x(self.area.x() + x);
+    }
+
+    pub fn right(&self) -> i16 {
+        self.area.right()
+    }
+}
+
+// Something swinging on the end of a leash tied to `anchor`, as an angle from
+// straight down, positive when it is to the right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pendulum {
+    pub anchor: Point,
+    length: f64,
+    angle: f64,
+    // Radians a frame.
+    angular_velocity: f64,
+}
+
+impl Pendulum {
+    // Catches `body` on a leash pulled taut, keeping the part of its velocity
+    // across the leash and losing the part along it.
+    pub fn attach(anchor: Point, body: Body) -> Self {
+        let x = f64::from(body.position.x - anchor.x);
+        let y = f64::from(body.position.y - anchor.y);
+        let length = x.hypot(y).max(1.0);
+        let angle = x.atan2(y);
+        let across =
+            f64::from(body.velocity.x) * angle.cos() - f64::from(body.velocity.y) * angle.sin();
+        Pendulum {
+            anchor,
+            length,
+            angle,
+            angular_velocity: across / length,
+        }
+    }
+
+    // One frame under `gravity` pixels a frame per frame.
+    pub fn swing(self, gravity: f64) -> Self {
+        let angular_velocity = self.angular_velocity - gravity / self.length * self.angle.sin();
+        Pendulum {
+            angle: self.angle + angular_velocity,
+            angular_velocity,
+            ..self
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        Point {
+            x: self.anchor.x + (self.length * self.angle.sin()).round() as i16,
+            y: self.anchor.y + (self.length * self.angle.cos()).round() as i16,
+        }
+    }
+
+    // How fast the end is going, for letting go.
+    pub fn velocity(&self) -> Point {
+        let speed = self.angular_velocity * self.length;
+        Point {
+            x: (speed * self.angle.cos()).round() as i16,
+            y: (-speed * self.angle.sin()).round() as i16,
+        }
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.anchor.x += x;
+    }
+}
+
+// How hard gravity pulls on average, for motion worked out in fractions.
+pub fn mean_gravity(physics: &Physics) -> f64 {
+    f64::from(physics.gravity) / f64::from(physics.gravity_period.max(1))
+}
+
+// The push upwards that starts a jump.
+pub fn jump(body: Body, physics: &Physics) -> Body {
+    Body {
+        velocity: Point {
+            x: body.velocity.x,
+            y: physics.jump_speed,
+        },
+        ..body
+    }
+}
+
+// Gravity builds up to terminal velocity on every `gravity_period`th
+// animation frame.
+pub fn apply_gravity(body: Body, physics: &Physics, frame: u8) -> Body {
+    let falling = body.velocity.y < physics.terminal_velocity
+        && frame.is_multiple_of(physics.gravity_period.max(1));
+    if !falling {
+        return body;
+    }
+    Body {
+        velocity: Point {
+            x: body.velocity.x,
+            y: body.velocity.y + physics.gravity,
+        },
+        ..body
+    }
+}
+
+pub fn integrate(body: Body) -> Body {
+    Body {
+        position: Point {
+            x: body.position.x + body.velocity.x,
+            y: body.position.y + body.velocity.y,
+        },
+        ..body
+    }
+}
+
+// One frame: gravity first, then the move.
+pub fn step(body: Body, physics: &Physics, frame: u8) -> Body {
+    integrate(apply_gravity(body, physics, frame))
+}
+
+pub fn apply_force(body: Body, force: &Force) -> Body {
+    let velocity = match *force {
+        Force::Updraft { fall } => Point {
+            x: body.velocity.x,
+            y: body.velocity.y.min(fall),
+        },
+        Force::Headwind { drag } => Point {
+            x: slow(body.velocity.x, drag),
+            y: body.velocity.y,
+        },
+    };
+    Body { velocity, ..body }
+}
+
+// Takes `drag` off `speed` in whichever direction, though never below nothing.
+pub fn slow(speed: i16, drag: i16) -> i16 {
+    speed.signum() * (speed.abs() - drag).max(0)
+}
+
+// Every field `bounds` overlaps acts on the body.
+pub fn apply_fields(body: Body, bounds: &Rect, fields: &[ForceField]) -> Body {
+    fields
+        .iter()
+        .filter(|field| field.area.intersects(bounds))
+        .fold(body, |body, field| apply_force(body, &field.force))
+}
+
+// Where `body` will be on each frame until it comes down to `floor`. `frame`
+// is the animation frame it is on, and the frames after it are counted on
+// from there.
+pub fn trajectory(body: Body, physics: &Physics, frame: u8, floor: i16) -> Vec<Point> {
+    let mut body = body;
+    (1..=MAX_TRAJECTORY_FRAMES)
+        .map_while(|count| {
+            body = step(body, physics, frame.wrapping_add(count));
+            (body.position.y < floor).then_some(body.position)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump_from(position: Point, physics: &Physics) -> Vec<Point> {
+        let body = Body {
+            position,
+            velocity: Point { x: 4, y: 0 },
+        };
+        trajectory(jump(body, physics), physics, 0, position.y)
+    }
+
+    #[test]
+    fn jumps_peak_at_the_sum_of_their_speeds() {
+        let physics = Physics::default();
+        let arc = jump_from(Point { x: 100, y: 479 }, &physics);
+        let apex = arc.iter().map(|point| point.y).min().unwrap();
+
+        assert_eq!(arc[0], Point { x: 104, y: 455 });
+        assert_eq!(479 - apex, 24 * 25 / 2);
+    }
+
+    #[test]
+    fn jumps_land_where_the_speed_carries_them() {
+        let physics = Physics::default();
+        let arc = jump_from(Point { x: 100, y: 479 }, &physics);
+
+        assert_eq!(arc.len(), 49);
+        assert_eq!(arc.last().unwrap().x, 100 + 49 * 4);
+    }
+
+    #[test]
+    fn slower_gravity_jumps_higher_and_longer() {
+        let physics = Physics::default();
+        let floaty = Physics {
+            gravity_period: 2,
+            ..physics
+        };
+
+        let arc = jump_from(Point::default(), &physics);
+        let floaty_arc = jump_from(Point::default(), &floaty);
+        let apex = |arc: &[Point]| arc.iter().map(|point| point.y).min().unwrap();
+
+        assert!(floaty_arc.len() > arc.len());
+        assert!(apex(&floaty_arc) < apex(&arc));
+    }
+
+    #[test]
+    fn updrafts_keep_jumps_in_the_air_longer() {
+        let physics = Physics::default();
+        let updraft = ForceField {
+            force: Force::Updraft { fall: 2 },
+            area: Rect::new_from_x_y(0, -400, 100, 400),
+        };
+        let mut body = jump(Body::default(), &physics);
+        let frames = (1..)
+            .find(|frame| {
+                body = step(body, &physics, *frame);
+                let bounds = Rect::new(body.position, 10, 10);
+                body = apply_fields(body, &bounds, std::slice::from_ref(&updraft));
+                body.position.y >= 0
+            })
+            .unwrap();
+
+        assert!(usize::from(frames) > jump_from(Point::default(), &physics).len() + 1);
+    }
+
+    #[test]
+    fn headwinds_slow_but_never_reverse() {
+        let body = Body {
+            position: Point::default(),
+            velocity: Point { x: -4, y: 3 },
+        };
+        let headwind = |drag| apply_force(body, &Force::Headwind { drag }).velocity;
+
+        assert_eq!(headwind(1), Point { x: -3, y: 3 });
+        assert_eq!(headwind(9), Point { x: 0, y: 3 });
+    }
+
+    #[test]
+    fn overrides_only_change_what_they_name() {
+        let physics = Physics::default();
+        let underwater = Overrides {
+            gravity_period: Some(3),
+            terminal_velocity: Some(4),
+            ..Overrides::default()
+        };
+
+        assert_eq!(
+            underwater.apply(physics),
+            Physics {
+                gravity_period: 3,
+                terminal_velocity: 4,
+                ..physics
+            }
+        );
+        assert_eq!(Overrides::default().apply(physics), physics);
+    }
+
+    #[test]
+    fn fields_only_act_on_bodies_inside() {
+        let field = ForceField {
+            force: Force::Updraft { fall: 1 },
+            area: Rect::new_from_x_y(100, 0, 50, 600),
+        };
+        let body = Body {
+            position: Point::default(),
+            velocity: Point { x: 0, y: 10 },
+        };
+        let fall = |x| {
+            let bounds = Rect::new_from_x_y(x, 300, 20, 20);
+            apply_fields(body, &bounds, std::slice::from_ref(&field))
+                .velocity
+                .y
+        };
+
+        assert_eq!(fall(0), 10);
+        assert_eq!(fall(90), 1);
+    }
+
+    #[test]
+    fn pendulums_swing_through_and_back_up() {
+        let anchor = Point { x: 0, y: 0 };
+        let body = Body {
+            position: Point { x: -100, y: 100 },
+            velocity: Point::default(),
+        };
+        let mut pendulum = Pendulum::attach(anchor, body);
+        let start = pendulum.position();
+        assert_eq!(start, body.position);
+
+        let mut lowest = start;
+        let mut highest_right = None;
+        (0..200).for_each(|_frame| {
+            pendulum = pendulum.swing(1.0);
+            let position = pendulum.position();
+            if position.y > lowest.y {
+                lowest = position;
+            }
+            if position.x > 0 && pendulum.velocity().x <= 0 && highest_right.is_none() {
+                highest_right = Some(position);
+            }
+        });
+
+        assert!(lowest.x.abs() <= 10);
+        let highest_right = highest_right.unwrap();
+        assert!((highest_right.y - start.y).abs() <= 10);
+        assert!((highest_right.x + start.x).abs() <= 10);
+    }
+
+    #[test]
+    fn pendulums_keep_speed_across_the_leash() {
+        let anchor = Point { x: 0, y: 0 };
+        let below = Body {
+            position: Point { x: 0, y: 150 },
+            velocity: Point { x: 6, y: 4 },
+        };
+        let pendulum = Pendulum::attach(anchor, below);
+
+        assert_eq!(pendulum.velocity(), Point { x: 6, y: 0 });
+    }
+
+    #[test]
+    fn falls_stop_at_terminal_velocity() {
+        let physics = Physics::default();
+        let body = Body {
+            position: Point::default(),
+            velocity: Point {
+                x: 0,
+                y: physics.terminal_velocity,
+            },
+        };
+
+        assert_eq!(
+            step(body, &physics, 0).velocity.y,
+            physics.terminal_velocity
+        );
+        assert_eq!(
+            step(body, &physics, 0).position.y,
+            physics.terminal_velocity
+        );
+    }
+}
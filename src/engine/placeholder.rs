@@ -0,0 +1,66 @@
+use std::{cell::RefCell, collections::HashSet};
+
+#[cfg(debug_assertions)]
+use super::{Align, Color, Point, TextStyle};
+use super::{Rect, Renderer};
+
+// How big a missing sprite is taken to be when nothing else says.
+pub const SIZE: i16 = 64;
+#[cfg(debug_assertions)]
+const FILL: Color = Color::rgba(255, 0, 255, 0.6);
+#[cfg(debug_assertions)]
+const OUTLINE: Color = Color::rgb(255, 0, 255);
+#[cfg(debug_assertions)]
+const LABEL_COLOR: Color = Color::rgb(255, 255, 255);
+#[cfg(debug_assertions)]
+const LABEL_OFFSET: i16 = 16;
+
+thread_local! {
+    static MISSED: RefCell<HashSet<String>> = RefCell::new(HashSet::new());
+}
+
+// Whether this is the first time `name` has gone missing.
+fn first_miss(name: &str) -> bool {
+    MISSED.with(|missed| missed.borrow_mut().insert(name.to_string()))
+}
+
+// Stands in for a sprite its sheet has no cell for, at `destination`. Debug
+// builds draw a magenta box with the name in it, so the mistake shows, and
+// release builds draw nothing. Each name is logged the first time only.
+pub fn draw_missing(renderer: &Renderer, name: &str, destination: &Rect) {
+    if first_miss(name) {
+        log!("No sprite named {}, drawing a placeholder", name);
+    }
+    #[cfg(debug_assertions)]
+    {
+        renderer.fill_rect(destination, &FILL);
+        renderer.stroke_rect(destination, &OUTLINE);
+        let label = TextStyle {
+            color: LABEL_COLOR,
+            align: Align::Center,
+            max_width: Some(destination.width),
+            ..TextStyle::default()
+        };
+        let center = Point {
+            x: destination.x() + destination.width / 2,
+            y: destination.y() + LABEL_OFFSET,
+        };
+        if let Err(err) = renderer.draw_styled_text(name, &center, &label) {
+            log!("Could not label a missing sprite {:#?}", err);
+        }
+    }
+    #[cfg(not(debug_assertions))]
+    let _ = (renderer, destination);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_name_is_missed_once() {
+        assert!(first_miss("tiles/missing.png"));
+        assert!(!first_miss("tiles/missing.png"));
+        assert!(first_miss("rhb/Missing (1).png"));
+    }
+}
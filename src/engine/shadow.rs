@@ -0,0 +1,74 @@
+use super::{Color, Point, Rect, Renderer};
+
+// Heights above the ground at which a shadow has shrunk and faded the most.
+const MAX_HEIGHT: i16 = 300;
+const MIN_SCALE: f32 = 0.4;
+const RADIUS_Y: i16 = 8;
+const ALPHA: f32 = 0.35;
+// The light comes from the upper left, so higher casters throw their shadow further right.
+const OFFSET_PER_HEIGHT: i16 = 12;
+
+#[derive(Debug, PartialEq)]
+struct Shadow {
+    center: Point,
+    radius_x: i16,
+    radius_y: i16,
+    color: Color,
+}
+
+fn shadow_for(caster: &Rect, ground: i16) -> Shadow {
+    let height = (ground - caster.bottom()).clamp(0, MAX_HEIGHT);
+    let scale = 1.0 - (1.0 - MIN_SCALE) * height as f32 / MAX_HEIGHT as f32;
+    let radius_y = (RADIUS_Y as f32 * scale).round() as i16;
+    Shadow {
+        center: Point {
+            x: caster.x() + caster.width / 2 + height / OFFSET_PER_HEIGHT,
+            y: ground - radius_y,
+        },
+        radius_x: (caster.width as f32 / 2.0 * scale).round() as i16,
+        radius_y,
+        color: Color::rgba(0, 0, 0, ALPHA * scale),
+    }
+}
+
+// Draws an elliptical drop shadow on the ground beneath the caster.
+pub fn draw_shadow(renderer: &Renderer, caster: &Rect, ground: i16) {
+    if !renderer.detail().shadows {
+        return;
+    }
+    let shadow = shadow_for(caster, ground);
+    if let Err(err) = renderer.fill_ellipse(
+        &shadow.center,
+        shadow.radius_x,
+        shadow.radius_y,
+        &shadow.color,
+    ) {
+        log!("Could not draw shadow {:#?}", err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grounded_casters_get_a_full_shadow() {
+        let shadow = shadow_for(&Rect::new_from_x_y(100, 500, 80, 100), 600);
+
+        assert_eq!(shadow.center, Point { x: 140, y: 592 });
+        assert_eq!(shadow.radius_x, 40);
+        assert_eq!(shadow.color.a, ALPHA);
+    }
+
+    #[test]
+    fn higher_casters_get_smaller_fainter_offset_shadows() {
+        let grounded = shadow_for(&Rect::new_from_x_y(100, 500, 80, 100), 600);
+        let jumping = shadow_for(&Rect::new_from_x_y(100, 350, 80, 100), 600);
+        let highest = shadow_for(&Rect::new_from_x_y(100, -500, 80, 100), 600);
+
+        assert!(jumping.radius_x < grounded.radius_x);
+        assert!(jumping.color.a < grounded.color.a);
+        assert!(jumping.center.x > grounded.center.x);
+        assert_eq!(highest.radius_x, 16);
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+
+use super::{wrap_text, Color, NineSlice, Point, Rect, Renderer, SpriteSheet};
+
+const SCREEN_WIDTH: i16 = 600;
+const MAX_TEXT_WIDTH: i16 = 240;
+const PADDING: i16 = 10;
+const LINE_HEIGHT: i16 = 24;
+const TEXT_DESCENT: i16 = 6;
+const MARGIN: i16 = 8;
+const TAIL_WIDTH: i16 = 16;
+const TAIL_HEIGHT: i16 = 18;
+
+// The panel's rounded corners are kept whole however big the bubble gets.
+const PANEL: &str = "ui/bubble.png";
+const PANEL_SLICE: NineSlice = NineSlice::uniform(8);
+// The tail is drawn in the panel's colors, over its outline where they meet.
+const BUBBLE_COLOR: Color = Color::rgba(255, 255, 255, 0.95);
+const OUTLINE_COLOR: Color = Color::rgb(40, 40, 40);
+const OUTLINE: i16 = 2;
+
+// A bubble sits above the speaker's anchor, sized to the wrapped text and kept
+// on screen, with its tail pointing back down at the anchor.
+pub struct SpeechBubble {
+    text: String,
+    anchor: Point,
+}
+
+impl SpeechBubble {
+    pub fn new(text: &str, anchor: Point) -> Self {
+        SpeechBubble {
+            text: text.into(),
+            anchor,
+        }
+    }
+
+    pub fn set_anchor(&mut self, anchor: Point) {
+        self.anchor = anchor;
+    }
+
+    pub fn draw(&self, renderer: &Renderer, sheet: &SpriteSheet, shown: usize) -> Result<()> {
+        let lines = wrap_text(&self.text, MAX_TEXT_WIDTH, |text| {
+            renderer.measure_text(text)
+        })?;
+        let widest = lines
+            .iter()
+            .map(|line| renderer.measure_text(line))
+            .collect::<Result<Vec<i16>>>()?
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+
+        let body = body_rect(&self.anchor, widest, lines.len() as i16);
+        let tail = tail_points(&body, &self.anchor);
+
+        sheet.draw_nine_slice(renderer, PANEL, &PANEL_SLICE, &body)?;
+        renderer.fill_polygon(&tail, &OUTLINE_COLOR);
+        renderer.fill_polygon(&inset_tail(&tail), &BUBBLE_COLOR);
+
+        let mut remaining = shown;
+        for (index, line) in lines.iter().enumerate() {
+            let visible: String = line.chars().take(remaining).collect();
+            remaining = remaining.saturating_sub(line.chars().count());
+            renderer.draw_text(
+                &visible,
+                &Point {
+                    x: body.x() + PADDING,
+                    y: body.y() + PADDING + LINE_HEIGHT * (index as i16 + 1) - TEXT_DESCENT,
+                },
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn body_rect(anchor: &Point, text_width: i16, line_count: i16) -> Rect {
+    let width = text_width + PADDING * 2;
+    let height = LINE_HEIGHT * line_count + PADDING * 2;
+    let x = (anchor.x - width / 2)
+        .min(SCREEN_WIDTH - MARGIN - width)
+        .max(MARGIN);
+    Rect::new_from_x_y(x, anchor.y - TAIL_HEIGHT - height, width, height)
+}
+
+fn tail_points(body: &Rect, anchor: &Point) -> [Point; 3] {
+    let base_x = anchor
+        .x
+        .min(body.right() - PADDING - TAIL_WIDTH / 2)
+        .max(body.x() + PADDING + TAIL_WIDTH / 2);
+    [
+        Point {
+            x: base_x - TAIL_WIDTH / 2,
+            y: body.bottom(),
+        },
+        Point {
+            x: base_x + TAIL_WIDTH / 2,
+            y: body.bottom(),
+        },
+        *anchor,
+    ]
+}
+
+fn inset_tail(tail: &[Point; 3]) -> [Point; 3] {
+    let [left, right, tip] = *tail;
+    [
+        Point {
+            x: left.x + OUTLINE,
+            y: left.y - OUTLINE,
+        },
+        Point {
+            x: right.x - OUTLINE,
+            y: right.y - OUTLINE,
+        },
+        Point {
+            x: tip.x,
+            y: tip.y - OUTLINE * 2,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bubble_stays_on_screen_and_tail_points_at_speaker() {
+        let anchor = Point { x: 590, y: 300 };
+        let body = body_rect(&anchor, 100, 2);
+
+        assert_eq!(body.right(), SCREEN_WIDTH - MARGIN);
+        assert_eq!(body.bottom(), anchor.y - TAIL_HEIGHT);
+
+        let tail = tail_points(&body, &anchor);
+        assert_eq!(tail[2], anchor);
+        assert!(tail[1].x <= body.right() - PADDING);
+    }
+}
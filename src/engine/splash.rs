@@ -0,0 +1,93 @@
+use web_sys::HtmlImageElement;
+
+use super::{Align, Color, KeyState, Point, Rect, Renderer, TextStyle};
+
+const CANVAS_SIZE: i16 = 600;
+const FADE_IN_FRAMES: u32 = 30;
+const HOLD_FRAMES: u32 = 90;
+const FADE_OUT_FRAMES: u32 = 30;
+const TOTAL_FRAMES: u32 = FADE_IN_FRAMES + HOLD_FRAMES + FADE_OUT_FRAMES;
+const CAPTION_GAP: i16 = 40;
+const BACKGROUND: Color = Color::rgb(255, 255, 255);
+
+// The logo shown while the game loads, fading in and out. Any key or click
+// skips it.
+pub struct Splash {
+    logo: HtmlImageElement,
+    caption: String,
+    frame: u32,
+}
+
+impl Splash {
+    pub fn new(logo: HtmlImageElement, caption: &str) -> Self {
+        Splash {
+            logo,
+            caption: caption.to_string(),
+            frame: 0,
+        }
+    }
+
+    pub fn update(&mut self, keystate: &KeyState) {
+        if keystate.any_pressed() || !keystate.clicks().is_empty() {
+            self.frame = TOTAL_FRAMES;
+        } else {
+            self.frame = (self.frame + 1).min(TOTAL_FRAMES);
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= TOTAL_FRAMES
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        renderer.fill_rect(
+            &Rect::new_from_x_y(0, 0, CANVAS_SIZE, CANVAS_SIZE),
+            &BACKGROUND,
+        );
+        let logo_y = (CANVAS_SIZE - self.logo.height() as i16) / 2;
+        renderer.draw_with_alpha(alpha(self.frame), || {
+            renderer.draw_entire_image(
+                &self.logo,
+                &Point {
+                    x: (CANVAS_SIZE - self.logo.width() as i16) / 2,
+                    y: logo_y,
+                },
+            );
+            if let Err(err) = renderer.draw_styled_text(
+                &self.caption,
+                &Point {
+                    x: CANVAS_SIZE / 2,
+                    y: logo_y + self.logo.height() as i16 + CAPTION_GAP,
+                },
+                &TextStyle::new().with_align(Align::Center),
+            ) {
+                log!("Could not draw splash caption {:#?}", err);
+            }
+        });
+    }
+}
+
+fn alpha(frame: u32) -> f64 {
+    if frame < FADE_IN_FRAMES {
+        f64::from(frame) / f64::from(FADE_IN_FRAMES)
+    } else if frame < FADE_IN_FRAMES + HOLD_FRAMES {
+        1.0
+    } else {
+        f64::from(TOTAL_FRAMES.saturating_sub(frame)) / f64::from(FADE_OUT_FRAMES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_in_holds_and_fades_out() {
+        assert_eq!(alpha(0), 0.0);
+        assert_eq!(alpha(FADE_IN_FRAMES / 2), 0.5);
+        assert_eq!(alpha(FADE_IN_FRAMES), 1.0);
+        assert_eq!(alpha(FADE_IN_FRAMES + HOLD_FRAMES - 1), 1.0);
+        assert_eq!(alpha(TOTAL_FRAMES - FADE_OUT_FRAMES / 2), 0.5);
+        assert_eq!(alpha(TOTAL_FRAMES), 0.0);
+    }
+}
@@ -0,0 +1,151 @@
+use anyhow::Result;
+
+use super::{Color, Point};
+
+const LINE_HEIGHT: i16 = 24;
+const TEXT_COLOR: Color = Color::rgb(0, 0, 0);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub fn css(&self) -> &'static str {
+        match self {
+            Align::Left => "left",
+            Align::Center => "center",
+            Align::Right => "right",
+        }
+    }
+}
+
+// How draw_styled_text lays out and decorates its text. Text is drawn in
+// `color`, over an optional outline and shadow so it stays readable on top of
+// the busier backgrounds. With a `max_width` it wraps between words.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    pub color: Color,
+    pub align: Align,
+    pub max_width: Option<i16>,
+    pub line_height: i16,
+    pub outline: Option<(Color, i16)>,
+    pub shadow: Option<(Color, Point)>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            color: TEXT_COLOR,
+            align: Align::Left,
+            max_width: None,
+            line_height: LINE_HEIGHT,
+            outline: None,
+            shadow: None,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn new() -> Self {
+        TextStyle::default()
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: i16) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_line_height(mut self, line_height: i16) -> Self {
+        self.line_height = line_height;
+        self
+    }
+
+    pub fn with_outline(mut self, color: Color, width: i16) -> Self {
+        self.outline = Some((color, width));
+        self
+    }
+
+    pub fn with_shadow(mut self, color: Color, offset: Point) -> Self {
+        self.shadow = Some((color, offset));
+        self
+    }
+}
+
+// Breaks text into lines no wider than `max_width`, keeping explicit line
+// breaks. A word wider than `max_width` gets a line to itself.
+pub fn wrap_text(
+    text: &str,
+    max_width: i16,
+    measure: impl Fn(&str) -> Result<i16>,
+) -> Result<Vec<String>> {
+    let mut lines: Vec<String> = vec![];
+
+    for paragraph in text.lines() {
+        let mut current = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+
+            if current.is_empty() || measure(&candidate)? <= max_width {
+                current = candidate;
+            } else {
+                lines.push(current);
+                current = word.to_string();
+            }
+        }
+        lines.push(current);
+    }
+
+    while lines.last().map_or(false, String::is_empty) {
+        lines.pop();
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure(text: &str) -> Result<i16> {
+        Ok(text.chars().count() as i16 * 10)
+    }
+
+    #[test]
+    fn wraps_on_word_boundaries() {
+        let lines = wrap_text("There you are! Ready for a walk?", 120, measure).unwrap();
+
+        assert_eq!(lines, vec!["There you", "are! Ready", "for a walk?"]);
+    }
+
+    #[test]
+    fn long_words_get_their_own_line() {
+        let lines = wrap_text("a extraordinarily b", 50, measure).unwrap();
+
+        assert_eq!(lines, vec!["a", "extraordinarily", "b"]);
+    }
+
+    #[test]
+    fn keeps_explicit_line_breaks() {
+        let lines = wrap_text("Game over\n\nDistance 120 meters\n", 100, measure).unwrap();
+
+        assert_eq!(lines, vec!["Game over", "", "Distance", "120 meters"]);
+    }
+}
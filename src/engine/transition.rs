@@ -0,0 +1,97 @@
+use super::{Color, Point, Rect, Renderer};
+
+const CANVAS_SIZE: i16 = 600;
+const TRANSITION_FRAMES: u16 = 30;
+const COVER: Color = Color::rgb(0, 0, 0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransitionEffect {
+    Fade,
+    Wipe,
+    Iris,
+}
+
+// Covers the screen and uncovers it again. Whatever is drawn underneath
+// changes at the halfway point, while the screen is fully covered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transition {
+    effect: TransitionEffect,
+    frame: u16,
+    // Where the iris closes to.
+    focus: Point,
+}
+
+impl Transition {
+    pub fn new(effect: TransitionEffect, focus: Point) -> Self {
+        Transition {
+            effect,
+            frame: 0,
+            focus,
+        }
+    }
+
+    pub fn update(&mut self) {
+        self.frame = (self.frame + 1).min(TRANSITION_FRAMES);
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frame >= TRANSITION_FRAMES
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let coverage = coverage(self.frame);
+        match self.effect {
+            TransitionEffect::Fade => renderer.draw_with_alpha(coverage, || {
+                renderer.fill_rect(&Rect::new_from_x_y(0, 0, CANVAS_SIZE, CANVAS_SIZE), &COVER);
+            }),
+            TransitionEffect::Wipe => renderer.fill_rect(
+                &Rect::new_from_x_y(
+                    0,
+                    0,
+                    (f64::from(CANVAS_SIZE) * coverage) as i16,
+                    CANVAS_SIZE,
+                ),
+                &COVER,
+            ),
+            TransitionEffect::Iris => {
+                // Far enough from any point on the canvas to uncover all of it.
+                let open = f64::from(CANVAS_SIZE) * std::f64::consts::SQRT_2;
+                let radius = (open * (1.0 - coverage)) as i16;
+                if let Err(err) = renderer.fill_outside_circle(&self.focus, radius, &COVER) {
+                    log!("Could not draw iris {:#?}", err);
+                }
+            }
+        }
+    }
+}
+
+// How much of the screen is covered: rising to all of it at the halfway point
+// and falling back to none.
+fn coverage(frame: u16) -> f64 {
+    let progress = f64::from(frame) / f64::from(TRANSITION_FRAMES);
+    1.0 - (progress * 2.0 - 1.0).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screen_is_covered_halfway_through() {
+        assert_eq!(coverage(0), 0.0);
+        assert!(coverage(TRANSITION_FRAMES / 4) < coverage(TRANSITION_FRAMES / 3));
+        assert_eq!(coverage(TRANSITION_FRAMES / 2), 1.0);
+        assert!((coverage(5) - coverage(TRANSITION_FRAMES - 5)).abs() < 1e-9);
+        assert_eq!(coverage(TRANSITION_FRAMES), 0.0);
+    }
+
+    #[test]
+    fn transitions_finish_after_their_length() {
+        let mut transition = Transition::new(TransitionEffect::Fade, Point { x: 0, y: 0 });
+        (0..TRANSITION_FRAMES - 1).for_each(|_| transition.update());
+        assert!(!transition.finished());
+
+        transition.update();
+        assert!(transition.finished());
+    }
+}
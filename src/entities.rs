@@ -0,0 +1,168 @@
+use crate::engine::EventBus;
+#[cfg(debug_assertions)]
+use crate::engine::{Color, Point, Renderer, TextStyle};
+
+const KINDS: [EntityKind; 3] = [EntityKind::Obstacle, EntityKind::Coin, EntityKind::Trigger];
+
+// The things the world scrolls past and removes once they are behind the boy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntityKind {
+    Obstacle,
+    Coin,
+    Trigger,
+}
+
+impl EntityKind {
+    // No segment comes close to these, so more alive at once means
+    // something is not being removed.
+    pub fn cap(&self) -> usize {
+        match self {
+            EntityKind::Obstacle => 64,
+            EntityKind::Coin => 64,
+            EntityKind::Trigger => 32,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntityKind::Obstacle => "obstacles",
+            EntityKind::Coin => "coins",
+            EntityKind::Trigger => "triggers",
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            EntityKind::Obstacle => 0,
+            EntityKind::Coin => 1,
+            EntityKind::Trigger => 2,
+        }
+    }
+}
+
+// An entity removed from the world, at the index it had before removal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Despawn {
+    pub kind: EntityKind,
+    pub index: usize,
+}
+
+// Like `retain`, but publishes a `Despawn` for each entity `gone` picks out
+// and hands them back so anything tied to them can be released.
+pub fn despawn<T>(
+    entities: &mut Vec<T>,
+    kind: EntityKind,
+    events: &mut EventBus<Despawn>,
+    gone: impl Fn(&T) -> bool,
+) -> Vec<T> {
+    entities
+        .iter()
+        .enumerate()
+        .filter(|(_index, entity)| gone(entity))
+        .for_each(|(index, _entity)| events.publish(Despawn { kind, index }));
+    let (removed, kept): (Vec<T>, Vec<T>) = entities.drain(..).partition(|entity| gone(entity));
+    entities.extend(kept);
+    removed
+}
+
+// How many of each kind are alive and how many have been removed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EntityCounts {
+    live: [usize; 3],
+    despawned: [u32; 3],
+}
+
+impl EntityCounts {
+    // Returns whether `kind` has just gone over its cap.
+    pub fn record(&mut self, kind: EntityKind, live: usize) -> bool {
+        let was_over = self.over_cap(kind);
+        self.live[kind.index()] = live;
+        !was_over && self.over_cap(kind)
+    }
+
+    pub fn record_despawns(&mut self, despawns: &[Despawn]) {
+        despawns
+            .iter()
+            .for_each(|despawn| self.despawned[despawn.kind.index()] += 1);
+    }
+
+    fn over_cap(&self, kind: EntityKind) -> bool {
+        self.live[kind.index()] > kind.cap()
+    }
+
+    // Nothing more is spawned while any kind is at its cap.
+    pub fn full(&self) -> bool {
+        KINDS
+            .iter()
+            .any(|kind| self.live[kind.index()] >= kind.cap())
+    }
+
+    // Under the frame rate, turning red for any kind over its cap.
+    #[cfg(debug_assertions)]
+    pub fn draw(&self, renderer: &Renderer) {
+        const POSITION: Point = Point { x: 400, y: 130 };
+        const LINE_HEIGHT: i16 = 20;
+        const OVER_CAP_COLOR: Color = Color::rgb(255, 80, 80);
+        KINDS.iter().zip(0..).for_each(|(kind, line)| {
+            let mut style = TextStyle::default();
+            if self.over_cap(*kind) {
+                style = style.with_color(OVER_CAP_COLOR);
+            }
+            let text = format!(
+                "{} {}/{} ({} gone)",
+                kind.name(),
+                self.live[kind.index()],
+                kind.cap(),
+                self.despawned[kind.index()]
+            );
+            let position = Point {
+                x: POSITION.x,
+                y: POSITION.y + line * LINE_HEIGHT,
+            };
+            if let Err(err) = renderer.draw_styled_text(&text, &position, &style) {
+                log!("Could not draw entity counts {:#?}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn despawned_entities_are_reported_with_their_old_index() {
+        let mut events = EventBus::new();
+        let mut xs = vec![-5, 10, -1, 20];
+        let removed = despawn(&mut xs, EntityKind::Coin, &mut events, |x| *x < 0);
+
+        assert_eq!(xs, vec![10, 20]);
+        assert_eq!(removed, vec![-5, -1]);
+        assert_eq!(
+            events.drain(),
+            vec![
+                Despawn {
+                    kind: EntityKind::Coin,
+                    index: 0
+                },
+                Despawn {
+                    kind: EntityKind::Coin,
+                    index: 2
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn going_over_a_cap_is_reported_once() {
+        let mut counts = EntityCounts::default();
+        let cap = EntityKind::Obstacle.cap();
+
+        assert!(!counts.record(EntityKind::Obstacle, cap));
+        assert!(counts.full());
+        assert!(counts.record(EntityKind::Obstacle, cap + 1));
+        assert!(!counts.record(EntityKind::Obstacle, cap + 2));
+        assert!(!counts.record(EntityKind::Obstacle, 1));
+        assert!(!counts.full());
+    }
+}
@@ -0,0 +1,162 @@
+use serde::Deserialize;
+
+use crate::engine::{physics::Overrides, Color, Point, Rect, Renderer};
+
+const HEIGHT: i16 = 600;
+const BUBBLES_PER_ZONE: i64 = 16;
+const BUBBLE_RISE: i64 = 2;
+const BUBBLE_RADIUS: i16 = 3;
+const BUBBLE_WOBBLE: f64 = 4.0;
+const BUBBLE_COLOR: Color = Color::rgba(230, 245, 255, 0.7);
+const UNDERWATER_TINT: Color = Color::rgba(40, 110, 220, 0.3);
+const LOW_GRAVITY_TINT: Color = Color::rgba(150, 110, 230, 0.2);
+
+// What a zone looks like. How it plays is up to its placement.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Environment {
+    Underwater,
+    LowGravity,
+}
+
+impl Environment {
+    fn tint(&self) -> Color {
+        match self {
+            Environment::Underwater => UNDERWATER_TINT,
+            Environment::LowGravity => LOW_GRAVITY_TINT,
+        }
+    }
+}
+
+// An environment as placed in segment data, relative to the start of its
+// segment, with the physics it changes while the boy is inside and how much
+// it slows him. Zones cover the full height unless told otherwise.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct EnvironmentPlacement {
+    pub environment: Environment,
+    #[serde(default)]
+    pub physics: Overrides,
+    #[serde(default)]
+    pub drag: i16,
+    pub x: i16,
+    pub width: i16,
+    #[serde(default)]
+    pub y: i16,
+    #[serde(default = "full_height")]
+    pub height: i16,
+}
+
+fn full_height() -> i16 {
+    HEIGHT
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvironmentZone {
+    environment: Environment,
+    pub physics: Overrides,
+    pub drag: i16,
+    area: Rect,
+}
+
+impl EnvironmentZone {
+    pub fn new(placement: &EnvironmentPlacement, offset_x: i16) -> Self {
+        EnvironmentZone {
+            environment: placement.environment,
+            physics: placement.physics,
+            drag: placement.drag,
+            area: Rect::new_from_x_y(
+                offset_x + placement.x,
+                placement.y,
+                placement.width,
+                placement.height,
+            ),
+        }
+    }
+
+    pub fn contains(&self, boy: &Rect) -> bool {
+        self.area.intersects(boy)
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.area.set_x(self.area.x() + x);
+    }
+
+    pub fn right(&self) -> i16 {
+        self.area.right()
+    }
+
+    // The bubbles drawn for the zone at full detail.
+    #[cfg(debug_assertions)]
+    pub fn particle_count(&self) -> usize {
+        if self.environment == Environment::Underwater {
+            BUBBLES_PER_ZONE as usize
+        } else {
+            0
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer, frame: u32) {
+        renderer.fill_rect(&self.area, &self.environment.tint());
+        if self.environment == Environment::Underwater && renderer.detail().particles {
+            self.bubbles(frame).iter().for_each(|bubble| {
+                if let Err(err) = renderer.fill_circle(bubble, BUBBLE_RADIUS, &BUBBLE_COLOR) {
+                    log!("Could not draw bubble {:#?}", err);
+                }
+            });
+        }
+    }
+
+    // Worked out from the frame rather than kept, so rewinds and replays show
+    // them the same way.
+    fn bubbles(&self, frame: u32) -> Vec<Point> {
+        let area = &self.area;
+        let width = i64::from(area.width.max(1));
+        let height = i64::from(area.height.max(1));
+        let risen = i64::from(frame) * BUBBLE_RISE;
+        (0..BUBBLES_PER_ZONE)
+            .map(|index| {
+                let wobble = (f64::from(frame) / 10.0 + index as f64).sin() * BUBBLE_WOBBLE;
+                Point {
+                    x: area.x() + (index * 41 % width) as i16 + wobble as i16,
+                    y: area.bottom() - ((risen + index * 59) % height) as i16,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placements_read_from_segment_data() {
+        let placement: EnvironmentPlacement = serde_json::from_str(
+            r#"{"environment": "Underwater", "physics": {"gravity_period": 3}, "drag": 1, "x": 50, "width": 400}"#,
+        )
+        .unwrap();
+        let zone = EnvironmentZone::new(&placement, 1000);
+
+        assert_eq!(zone.physics.gravity_period, Some(3));
+        assert_eq!(zone.physics.jump_speed, None);
+        assert_eq!(zone.area, Rect::new_from_x_y(1050, 0, 400, HEIGHT));
+        assert!(zone.contains(&Rect::new_from_x_y(1440, 480, 50, 120)));
+    }
+
+    #[test]
+    fn bubbles_rise_through_the_zone() {
+        let placement = EnvironmentPlacement {
+            environment: Environment::Underwater,
+            physics: Overrides::default(),
+            drag: 0,
+            x: 0,
+            width: 300,
+            y: 200,
+            height: 400,
+        };
+        let zone = EnvironmentZone::new(&placement, 0);
+
+        let first = zone.bubbles(0);
+        assert!(first.iter().all(|bubble| bubble.y > 200 && bubble.y <= 600));
+        assert!(zone.bubbles(1)[0].y < first[0].y);
+    }
+}
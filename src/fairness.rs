@@ -0,0 +1,111 @@
+use crate::{
+    config::Physics,
+    engine::physics::{self, Body},
+};
+
+// How high a jump carries the boy, frame by frame, and how far the world
+// scrolls under him meanwhile. Used to check that an obstacle can be jumped
+// at all, and where the jump has to start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JumpEnvelope {
+    // Height of his feet above the ground on each frame after the jump, until
+    // he lands.
+    lift: Vec<i16>,
+    speed: i16,
+}
+
+impl JumpEnvelope {
+    // Follows the boy's own trajectory, starting on the frame he jumps.
+    pub fn new(physics: &Physics, speed: i16) -> Self {
+        let jump = physics::jump(Body::default(), physics);
+        let lift = physics::trajectory(jump, physics, 0, 0)
+            .iter()
+            .map(|point| -point.y)
+            .collect();
+        JumpEnvelope { lift, speed }
+    }
+
+    // How far the world scrolls while he is in the air.
+    pub fn landing_distance(&self) -> i16 {
+        (self.lift.len() as i16 + 1) * self.speed
+    }
+
+    // Whether jumping `distance` pixels before an obstacle `width` wide and
+    // `height` tall keeps a `reach` wide boy above it the whole way across.
+    fn clears(&self, distance: i16, width: i16, height: i16, reach: i16) -> bool {
+        (1..)
+            .map(|frame| frame * self.speed)
+            .take_while(|scrolled| *scrolled < distance + width + reach)
+            .enumerate()
+            .all(|(index, scrolled)| {
+                scrolled <= distance || self.lift.get(index).map_or(false, |lift| *lift > height)
+            })
+    }
+
+    // The nearest and farthest distances before an obstacle to jump from, or
+    // `None` if it cannot be cleared at this speed.
+    pub fn takeoff_window(&self, width: i16, height: i16, reach: i16) -> Option<(i16, i16)> {
+        if self.speed <= 0 {
+            return None;
+        }
+        let distances: Vec<i16> = (0..=self.landing_distance())
+            .filter(|distance| self.clears(*distance, width, height, reach))
+            .collect();
+        Some((*distances.first()?, *distances.last()?))
+    }
+
+    // `count` points spread evenly through the jump, each as how far the
+    // world has scrolled since takeoff and how high his feet are.
+    pub fn arc(&self, count: usize) -> Vec<(i16, i16)> {
+        (1..=count)
+            .map(|point| point * self.lift.len() / (count + 1))
+            .filter_map(|index| {
+                let lift = *self.lift.get(index)?;
+                Some(((index as i16 + 1) * self.speed, lift))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelope_rises_and_falls_back_to_the_ground() {
+        let envelope = JumpEnvelope::new(&Physics::default(), 4);
+        let apex = *envelope.lift.iter().max().unwrap();
+
+        assert_eq!(apex, (24 * 25) / 2);
+        assert_eq!(envelope.lift.len(), 49);
+        assert_eq!(envelope.landing_distance(), 200);
+    }
+
+    #[test]
+    fn stones_can_be_cleared_from_a_window_of_distances() {
+        let envelope = JumpEnvelope::new(&Physics::default(), 4);
+        let (nearest, farthest) = envelope.takeoff_window(90, 54, 50).unwrap();
+
+        assert!(nearest < farthest);
+        assert!(envelope.clears(nearest, 90, 54, 50));
+        assert!(!envelope.clears(farthest + 1, 90, 54, 50));
+    }
+
+    #[test]
+    fn arcs_spread_points_over_the_jump() {
+        let envelope = JumpEnvelope::new(&Physics::default(), 4);
+        let arc = envelope.arc(3);
+
+        assert_eq!(arc.len(), 3);
+        assert_eq!(arc[1], (100, envelope.lift[24]));
+        assert!(arc[0].1 < arc[1].1 && arc[2].1 < arc[1].1);
+    }
+
+    #[test]
+    fn walls_too_tall_or_wide_cannot_be_cleared() {
+        let envelope = JumpEnvelope::new(&Physics::default(), 4);
+
+        assert_eq!(envelope.takeoff_window(90, 400, 50), None);
+        assert_eq!(envelope.takeoff_window(300, 54, 50), None);
+    }
+}
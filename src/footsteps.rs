@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use crate::{
+    engine::{Audio, Bus, Sound},
+    surfaces::Surface,
+};
+
+const STEP_SECONDS: f32 = 0.05;
+const LANDING_SECONDS: f32 = 0.12;
+const WOOD_KNOCK_HZ: f32 = 180.0;
+
+// What the boy's feet hit: the grass along the bottom, or the top of a
+// platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Footing {
+    Grass,
+    Stone,
+    Wood,
+}
+
+impl Footing {
+    const ALL: [Footing; 3] = [Footing::Grass, Footing::Stone, Footing::Wood];
+
+    // `support` is the top of the platform he stands on, if he is on one.
+    pub fn under(support: Option<i16>, surface: Surface) -> Self {
+        match (support, surface) {
+            (None, _) => Footing::Grass,
+            (Some(_), Surface::Normal | Surface::Icy) => Footing::Stone,
+            (Some(_), Surface::ConveyorLeft | Surface::ConveyorRight | Surface::Sticky) => {
+                Footing::Wood
+            }
+        }
+    }
+
+    // Grass is a soft rustle, stone a sharp tap and wood a hollow knock.
+    fn smoothing(&self) -> f32 {
+        match self {
+            Footing::Grass => 0.85,
+            Footing::Stone => 0.1,
+            Footing::Wood => 0.5,
+        }
+    }
+
+    fn gain(&self) -> f32 {
+        match self {
+            Footing::Grass => 0.25,
+            Footing::Stone => 0.35,
+            Footing::Wood => 0.4,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Footfall {
+    Step,
+    Landing,
+}
+
+impl Footfall {
+    const ALL: [Footfall; 2] = [Footfall::Step, Footfall::Landing];
+
+    fn seconds(&self) -> f32 {
+        match self {
+            Footfall::Step => STEP_SECONDS,
+            Footfall::Landing => LANDING_SECONDS,
+        }
+    }
+}
+
+// The assets have no footstep recordings, so each cue is a burst of shaped
+// noise. The noise is seeded, so a cue sounds the same every time.
+fn samples(footing: Footing, footfall: Footfall, sample_rate: f32) -> Vec<f32> {
+    let length = (footfall.seconds() * sample_rate) as usize;
+    let mut noise: u32 = 0x9e37_79b9;
+    let mut smoothed = 0.0;
+    (0..length)
+        .map(|index| {
+            noise ^= noise << 13;
+            noise ^= noise >> 17;
+            noise ^= noise << 5;
+            let white = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+            smoothed = smoothed * footing.smoothing() + white * (1.0 - footing.smoothing());
+            let time = index as f32 / sample_rate;
+            let knock = if footing == Footing::Wood {
+                (time * WOOD_KNOCK_HZ * std::f32::consts::TAU).sin() * 0.5
+            } else {
+                0.0
+            };
+            let envelope = 1.0 - index as f32 / length as f32;
+            (smoothed + knock) * envelope * envelope * footing.gain()
+        })
+        .collect()
+}
+
+// One cue for each footing and footfall. Without audio it plays nothing.
+#[derive(Clone, Default)]
+pub struct FootstepCues {
+    sounds: HashMap<(Footing, Footfall), Sound>,
+}
+
+impl FootstepCues {
+    pub fn synthesize(audio: &Audio) -> Self {
+        let sample_rate = audio.sample_rate();
+        let sounds = Footing::ALL
+            .iter()
+            .flat_map(|footing| {
+                Footfall::ALL
+                    .iter()
+                    .map(move |footfall| (*footing, *footfall))
+            })
+            .filter_map(|(footing, footfall)| {
+                match audio.synthesize(&samples(footing, footfall, sample_rate)) {
+                    Ok(sound) => Some(((footing, footfall), sound)),
+                    Err(err) => {
+                        log!(
+                            "Could not make {:?} {:?} sound {:#?}",
+                            footing,
+                            footfall,
+                            err
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+        FootstepCues { sounds }
+    }
+
+    pub fn play(&self, audio: &Audio, footing: Footing, footfall: Footfall) {
+        if let Some(sound) = self.sounds.get(&(footing, footfall)) {
+            if let Err(err) = audio.play_sound(sound, Bus::Sfx) {
+                log!("Could not play footstep {:#?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_platforms_are_stone_or_wood() {
+        assert_eq!(Footing::under(None, Surface::Icy), Footing::Grass);
+        assert_eq!(Footing::under(Some(400), Surface::Normal), Footing::Stone);
+        assert_eq!(Footing::under(Some(400), Surface::Sticky), Footing::Wood);
+    }
+
+    #[test]
+    fn cues_fade_out_within_their_length() {
+        let step = samples(Footing::Stone, Footfall::Step, 1000.0);
+        let landing = samples(Footing::Stone, Footfall::Landing, 1000.0);
+
+        assert_eq!(step.len(), 50);
+        assert_eq!(landing.len(), 120);
+        assert!(step.iter().all(|sample| sample.abs() <= 1.0));
+        assert!(step.last().unwrap().abs() < 0.001);
+    }
+
+    #[test]
+    fn footings_sound_different() {
+        let grass = samples(Footing::Grass, Footfall::Step, 1000.0);
+        let wood = samples(Footing::Wood, Footfall::Step, 1000.0);
+
+        assert_ne!(grass, wood);
+        assert_eq!(grass, samples(Footing::Grass, Footfall::Step, 1000.0));
+    }
+}
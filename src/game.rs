@@ -3,28 +3,133 @@ use std::{collections::HashMap, rc::Rc};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc::UnboundedReceiver;
-use rand::{thread_rng, Rng};
-use serde::Deserialize;
+use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use web_sys::HtmlImageElement;
 
 use self::red_hat_boy_states::*;
 use crate::{
-    engine::{self, Audio, Game, Image, KeyState, Point, Rect, Renderer, Sound, SpriteSheet},
-    segments::{platform_and_stone, stone_and_platform},
+    announcer::{Announcer, AnnouncerSetting, Combo},
+    attachments::{self, AttachmentData, Outfit},
+    audit::{self, Audit, Source, StateHasher, WorldHash},
+    background::{Background, ProceduralBackground},
+    boss::{BossEncounter, REWARD_SHIELD_FRAMES},
+    camera::Camera,
+    cheats::Cheats,
+    cloud_save,
+    coins::{self, Coin},
+    config::{GameConfig, Physics, TickRate},
+    consent::Consent,
+    critters::Critters,
+    cutscene::Cutscene,
+    dash::{DashInput, DashMeter, RECORDED_DASH},
+    day_cycle::DayCycle,
+    diagnostics::Report,
+    engine::{
+        self, memory,
+        physics::{self, Body, ForceField, Overrides, Pendulum},
+        placeholder, Anchor, Animations, Assets, Audio, Color, DrawList, EventBus, Fader,
+        FloatingText, FrameRateSetting, Game, GraphicsSetting, Haptics, Image, Impact, KeyState,
+        Layer, Layers, MaskedImage, MixerSetting, PauseMusicSetting, Point, Quality, Rect,
+        Renderer, Rumble, RumbleSetting, Sound, SpriteSheet, TextStyle, Transition,
+        TransitionEffect,
+    },
+    entities::{self, Despawn, EntityCounts, EntityKind},
+    environments::EnvironmentZone,
+    fairness::JumpEnvelope,
+    footsteps::FootstepCues,
+    gems::{self, Flash, GemChain},
+    hitboxes::{self, Hitboxes},
+    i18n::{self, text, Locale},
+    leaderboard::{self, Input, Leaderboard, Replay, Submission, HASH_INTERVAL},
+    levels::{self, Segment, SegmentKind, Story},
+    lifecycle::{Entrance, Lifecycle, Phase},
+    menus::{MenuStyle, PageMenu},
+    modes::GameMode,
+    narration, packs,
+    permalink::{Character, RunLink},
+    practice::{self, Practice},
+    race::{self, Race},
+    replay_file::{RunRecord, LAST_RUN_KEY},
+    replay_viewer::{Action, Control, ReplayViewer},
+    rewind::{Rewind, REWIND_SECONDS},
+    riding::{Controller, DogSprint},
+    routes::{self, LOW_LANE},
+    save,
+    seasons::{Snowfall, Treats},
+    segments::{self, platform_and_stone, stone_and_platform, stones},
+    spectate::{self, Broadcast, Cue, Spectator},
+    stats::LifetimeStats,
+    surfaces::Surface,
+    swings::{Swing, SwingPoint, GRAB_KEY},
+    telegraph::{RunHistory, Telegraph},
+    time_scale::{TimeScale, HIT_STOP_FRAMES},
+    triggers::{TriggerKind, TriggerZone, ZoneEffects, ZoneEvent},
+    warnings, wind,
 };
 
-use crate::browser;
+use crate::browser::{self, Target};
+#[cfg(debug_assertions)]
+use crate::{
+    attachments::{Attachment, Offset},
+    console::{self, CommandRegistry, Console},
+    engine::memory::Tracked,
+    heatmap::{self, Heatmap, SegmentSpan},
+    hitbox_editor::HitboxEditor,
+    inspector::{self, Inspector, Nudge, Selection},
+    state_graph::{self, StateGraph},
+};
+#[cfg(debug_assertions)]
+use std::collections::VecDeque;
 
+#[derive(Clone)]
 pub struct Platform {
     sheet: Rc<SpriteSheet>,
     pub position: Point,
     bounding_boxes: Vec<Rect>,
-    sprites: Vec<Cell>,
+    sprites: Vec<PlatformSprite>,
+    surface: Surface,
+    lifecycle: Lifecycle,
+}
+
+// One of the sprites a platform is drawn from, `width` wide. Names the sheet
+// has no cell for are kept, to be drawn as placeholders.
+#[derive(Clone)]
+struct PlatformSprite {
+    name: String,
+    cell: Option<Cell>,
+    width: i16,
+}
+
+impl PlatformSprite {
+    fn new(sheet: &SpriteSheet, name: &str, width: Option<i16>) -> Self {
+        let cell = sheet.cell(name).cloned();
+        let width =
+            width.unwrap_or_else(|| cell.as_ref().map_or(placeholder::SIZE, |cell| cell.frame.w));
+        PlatformSprite {
+            name: name.to_string(),
+            cell,
+            width,
+        }
+    }
+
+    fn height(&self) -> i16 {
+        self.cell
+            .as_ref()
+            .map_or(placeholder::SIZE, |cell| cell.frame.h)
+    }
 }
 
+const WIDTH: i16 = 600;
 const HEIGHT: i16 = 600;
 
+// The tile the old three-wide platform was drawn from.
+const PLATFORM_TILE: &str = "tiles/13.png";
+
+// Sprites the sheet lacks are drawn as placeholders, and the diagnostics
+// screen lists them when the game loads, through `segments::check_platforms`.
 impl Platform {
+    #[allow(dead_code)]
     pub fn new(
         sheet: Rc<SpriteSheet>,
         position: Point,
@@ -33,8 +138,37 @@ impl Platform {
     ) -> Self {
         let sprites = sprite_names
             .iter()
-            .filter_map(|sprite_name| sheet.cell(sprite_name).cloned())
+            .map(|sprite_name| PlatformSprite::new(&sheet, sprite_name, None))
             .collect();
+        Platform::from_sprites(sheet, position, sprites, bounding_boxes)
+    }
+
+    // Draws the end caps once and repeats the middle sprite across whatever
+    // width is left between them.
+    pub fn with_width(
+        sheet: Rc<SpriteSheet>,
+        position: Point,
+        [left, middle, right]: [&str; 3],
+        width: i16,
+        bounding_boxes: &[Rect],
+    ) -> Self {
+        let left = PlatformSprite::new(&sheet, left, None);
+        let right = PlatformSprite::new(&sheet, right, None);
+        let middle_width = (width - left.width - right.width).max(0);
+        let sprites = vec![
+            left,
+            PlatformSprite::new(&sheet, middle, Some(middle_width)),
+            right,
+        ];
+        Platform::from_sprites(sheet, position, sprites, bounding_boxes)
+    }
+
+    fn from_sprites(
+        sheet: Rc<SpriteSheet>,
+        position: Point,
+        sprites: Vec<PlatformSprite>,
+        bounding_boxes: &[Rect],
+    ) -> Self {
         let bounding_boxes = bounding_boxes
             .iter()
             .map(|bounding_box| {
@@ -51,26 +185,57 @@ impl Platform {
             position,
             sprites,
             bounding_boxes,
+            surface: Surface::Normal,
+            lifecycle: Lifecycle::default(),
         }
     }
 
+    pub fn with_entrance(mut self, entrance: Entrance) -> Self {
+        self.lifecycle = Lifecycle::new(Some(entrance));
+        self
+    }
+
+    // Everything it draws, from the top of its highest sprite.
+    fn bounds(&self) -> Rect {
+        let height = self
+            .sprites
+            .iter()
+            .map(PlatformSprite::height)
+            .max()
+            .unwrap_or(0);
+        Rect::new(self.position, self.right() - self.position.x, height)
+    }
+
+    #[allow(dead_code)]
+    pub fn with_surface(mut self, surface: Surface) -> Self {
+        self.surface = surface;
+        self
+    }
+
     pub fn bounding_boxes(&self) -> &Vec<Rect> {
         &self.bounding_boxes
     }
 
     #[allow(dead_code)]
     pub fn destination_box(&self) -> Rect {
-        let platform = self.sheet.cell("13.png").expect("13.png does not exist");
-        Rect::new(
-            self.position,
-            (platform.frame.w * 3).into(),
-            platform.frame.h.into(),
-        )
+        let (width, height) = self
+            .sheet
+            .cell(PLATFORM_TILE)
+            .map_or((placeholder::SIZE, placeholder::SIZE), |platform| {
+                (platform.frame.w, platform.frame.h)
+            });
+        Rect::new(self.position, width * 3, height)
     }
 
     #[allow(dead_code)]
     pub fn draw(&self, renderer: &Renderer) {
-        let platform = self.sheet.cell("13.png").expect("13.png does not exists");
+        let platform = match self.sheet.cell(PLATFORM_TILE) {
+            Some(platform) => platform,
+            None => {
+                placeholder::draw_missing(renderer, PLATFORM_TILE, &self.destination_box());
+                return;
+            }
+        };
 
         let _ = &self.sheet.draw(
             renderer,
@@ -85,51 +250,221 @@ impl Platform {
     }
 }
 
+// The parts of the boy that change during a run.
+#[derive(Clone)]
+struct BoySnapshot {
+    state_machine: RedHatBoyStateMachine,
+    shield_frames: u16,
+    dash_meter: DashMeter,
+    stagger_frames: u8,
+    jumps: u32,
+    death_cause: Option<DeathCause>,
+}
+
+#[derive(Clone)]
 pub struct RedHatBoy {
     state_machine: RedHatBoyStateMachine,
     sprite_sheet: Sheet,
+    // Frames named like his own, of him on the dog, for while he rides it.
+    riding_sheet: Sheet,
     image: HtmlImageElement,
+    shield_frames: u16,
+    dash_meter: DashMeter,
+    // Frames left of the slowdown after smashing through something.
+    stagger_frames: u8,
+    // Whether he broke something this frame.
+    smashed: bool,
+    riding: Option<DogSprint>,
+    jumps: u32,
+    death_cause: Option<DeathCause>,
+    invincible: bool,
+    hitboxes: Hitboxes,
+    outfit: Outfit,
+    // Whether something hit him this frame and the one before, so touching
+    // the same thing for several frames only counts as one hit.
+    struck: bool,
+    was_struck: bool,
+    // Kept through rewinds, so a knock-out taken back still counts.
+    hits: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeathCause {
+    Stone,
+    Wood,
+    Platform,
+    Boss,
+}
+
+impl DeathCause {
+    fn name(&self) -> &'static str {
+        match self {
+            DeathCause::Stone => "stone",
+            DeathCause::Wood => "wood",
+            DeathCause::Platform => "platform",
+            DeathCause::Boss => "boss",
+        }
+    }
 }
 
+const SHIELD_COLOR: Color = Color::rgba(120, 200, 255, 0.35);
+const AFTERIMAGES: i16 = 2;
+const AFTERIMAGE_SPACING: i16 = 14;
+const AFTERIMAGE_ALPHA: f64 = 0.25;
+// How much faster the world scrolls while dashing, and how much slower for a
+// while after smashing through something.
+const DASH_BOOST: i16 = 3;
+const STAGGER_DRAG: i16 = 3;
+const STAGGER_FRAMES: u8 = 45;
+// Seconds the music stays down after a knockout before it comes back.
+const KNOCK_OUT_DUCK_SECONDS: f64 = 0.3;
+
 impl RedHatBoy {
-    pub fn new(sheet: Sheet, image: HtmlImageElement, audio: Audio, sound: Sound) -> Self {
+    pub fn new(
+        sheet: Sheet,
+        image: HtmlImageElement,
+        audio: Audio,
+        sound: Sound,
+        physics: Physics,
+        tick: f64,
+    ) -> Self {
+        let animations = Rc::new(Animations::from_sheet(&sheet));
         RedHatBoy {
-            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(audio, sound)),
+            state_machine: RedHatBoyStateMachine::Idle(RedHatBoyState::new(
+                audio, sound, animations, physics, tick,
+            )),
             sprite_sheet: sheet,
+            riding_sheet: Sheet {
+                frames: HashMap::new(),
+                tags: vec![],
+            },
             image,
+            shield_frames: 0,
+            dash_meter: DashMeter::default(),
+            stagger_frames: 0,
+            smashed: false,
+            riding: None,
+            jumps: 0,
+            death_cause: None,
+            invincible: false,
+            hitboxes: Hitboxes::default(),
+            outfit: Outfit::default(),
+            struck: false,
+            was_struck: false,
+            hits: 0,
         }
     }
 
-    pub fn draw(&self, renderer: &Renderer) {
-        let sprite = self.current_sprite().expect("Cell not found");
+    pub fn with_hitboxes(mut self, hitboxes: Hitboxes) -> Self {
+        self.hitboxes = hitboxes;
+        self
+    }
+
+    pub fn with_riding_sheet(mut self, sheet: Sheet) -> Self {
+        self.riding_sheet = sheet;
+        self
+    }
+
+    pub fn with_outfit(mut self, outfit: Outfit) -> Self {
+        self.outfit = outfit;
+        self
+    }
+
+    #[cfg(debug_assertions)]
+    fn outfit_mut(&mut self) -> &mut Outfit {
+        &mut self.outfit
+    }
+
+    pub fn with_footsteps(mut self, footsteps: Rc<FootstepCues>) -> Self {
+        self.state_machine.context_mut().footsteps = footsteps;
+        self
+    }
 
+    // The frame he is on, with afterimages trailing behind while he dashes.
+    fn draw_sprite(&self, renderer: &Renderer, sprite: &Cell, position: &Point) {
+        if self.dashing() {
+            renderer.draw_with_alpha(AFTERIMAGE_ALPHA, || {
+                (1..=AFTERIMAGES).for_each(|afterimage| {
+                    let behind = Point {
+                        x: position.x - afterimage * AFTERIMAGE_SPACING,
+                        ..*position
+                    };
+                    if let Err(err) = renderer.draw_cell(&self.image, sprite, &behind) {
+                        log!("Could not draw afterimage {:#?}", err);
+                    }
+                });
+            });
+        }
         renderer
-            .draw_image(
-                &self.image,
-                &Rect::new_from_x_y(
-                    sprite.frame.x.into(),
-                    sprite.frame.y.into(),
-                    sprite.frame.w.into(),
-                    sprite.frame.h.into(),
-                ),
-                &&Rect::new_from_x_y(
-                    (self.state_machine.context().position().x
-                        + sprite.sprite_source_size.x as i16)
-                        .into(),
-                    (self.state_machine.context().position().y
-                        + sprite.sprite_source_size.y as i16)
-                        .into(),
-                    sprite.frame.w.into(),
-                    sprite.frame.h.into(),
-                ),
-            )
+            .draw_cell(&self.image, sprite, position)
             .expect("Expected to draw Image");
     }
 
+    pub fn draw(&self, renderer: &Renderer) {
+        let position = self.state_machine.context().position();
+        match self.current_sprite() {
+            Some(sprite) => self.draw_sprite(renderer, sprite, &position),
+            None => placeholder::draw_missing(
+                renderer,
+                self.frame_name().unwrap_or(self.state_machine.frame_name()),
+                &self.destination_box(),
+            ),
+        }
+        if let Some(sprint) = &self.riding {
+            let drawn = self
+                .frame_name()
+                .is_some_and(|frame_name| self.riding_sheet.frames.contains_key(frame_name));
+            if !drawn {
+                sprint.draw_dog(renderer, &self.bounding_box());
+            }
+        }
+        self.outfit
+            .placements(self.state_machine.name(), self.frame_name(), &position)
+            .iter()
+            .for_each(|(cell, at)| {
+                if let Err(err) = renderer.draw_cell(&self.image, cell, at) {
+                    log!("Could not draw attachment {:#?}", err);
+                }
+            });
+
+        if self.shield_frames > 0 {
+            let bounding_box = self.bounding_box();
+            let center = Point {
+                x: bounding_box.x() + bounding_box.width / 2,
+                y: bounding_box.y() + bounding_box.height / 2,
+            };
+            if let Err(err) =
+                renderer.fill_circle(&center, bounding_box.height / 2 + 10, &SHIELD_COLOR)
+            {
+                log!("Could not draw shield {:#?}", err);
+            }
+        }
+    }
+
+    // How long each update lasts, so his animations keep their pace whatever
+    // rate the game loop runs at.
+    pub fn set_tick(&mut self, delta: f64) {
+        self.state_machine.context_mut().tick = delta;
+    }
+
     pub fn update(&mut self) {
+        self.was_struck = self.struck;
+        self.struck = false;
+        self.shield_frames = self.shield_frames.saturating_sub(1);
+        self.smashed = false;
+        self.dash_meter.update();
+        self.stagger_frames = self.stagger_frames.saturating_sub(1);
         self.state_machine = self.state_machine.clone().update();
     }
 
+    fn set_invincible(&mut self, invincible: bool) {
+        self.invincible = invincible;
+    }
+
+    pub fn shield(&mut self, frames: u16) {
+        self.shield_frames = frames;
+    }
+
     pub fn run_right(&mut self) {
         self.state_machine = self.state_machine.clone().transition(Event::Run);
     }
@@ -138,50 +473,185 @@ impl RedHatBoy {
         self.state_machine = self.state_machine.clone().transition(Event::Slide);
     }
 
+    // A burst of speed along the ground that nothing can knock him out of
+    // and that breaks weak things, once the meter has filled back up.
+    pub fn dash(&mut self) {
+        let running = matches!(self.state_machine, RedHatBoyStateMachine::Running(_));
+        if running && self.dash_meter.ready() && self.stagger_frames == 0 {
+            self.dash_meter.spend();
+            self.state_machine = self.state_machine.clone().transition(Event::Dash);
+        }
+    }
+
+    pub fn dashing(&self) -> bool {
+        matches!(self.state_machine, RedHatBoyStateMachine::Dashing(_))
+    }
+
+    pub fn dash_meter(&self) -> &DashMeter {
+        &self.dash_meter
+    }
+
+    // Breaking through something ends the dash and slows him down.
+    pub fn smash(&mut self) {
+        self.smashed = true;
+        self.stagger_frames = STAGGER_FRAMES;
+        self.state_machine = self.state_machine.clone().transition(Event::Stumble);
+    }
+
+    fn smashed(&self) -> bool {
+        self.smashed
+    }
+
+    // Some things stop a dash dead, and hurt as if he had been running.
+    pub fn crash(&mut self, cause: DeathCause) {
+        self.state_machine = self.state_machine.clone().transition(Event::Stumble);
+        self.knock_out(cause);
+    }
+
+    // The scroll speed once any dash or stagger has had its say.
+    fn momentum(&self, walking_speed: i16) -> i16 {
+        if self.dashing() {
+            walking_speed - walking_speed.signum() * DASH_BOOST
+        } else if self.stagger_frames > 0 {
+            physics::slow(walking_speed, STAGGER_DRAG)
+        } else {
+            walking_speed
+        }
+    }
+
+    // Catches hold of a swing point by his hands, only from the air.
+    pub fn grab(&mut self, anchor: Point) -> Option<Swing> {
+        if !matches!(self.state_machine, RedHatBoyStateMachine::Jumping(_)) {
+            return None;
+        }
+        self.state_machine = self.state_machine.clone().transition(Event::Grab);
+        let hands = self.hands();
+        let context = self.state_machine.context();
+        let body = Body {
+            position: hands,
+            velocity: Point {
+                x: context.ground_speed(),
+                y: context.velocity().y,
+            },
+        };
+        Some(Swing::new(
+            Pendulum::attach(anchor, body),
+            hands.y - context.position().y,
+        ))
+    }
+
+    pub fn swinging(&self) -> bool {
+        matches!(self.state_machine, RedHatBoyStateMachine::Swinging(_))
+    }
+
+    fn swing_to(&mut self, y: i16, velocity: Point) {
+        self.state_machine.context_mut().swing_to(y, velocity);
+    }
+
+    pub fn release(&mut self, velocity: Point) {
+        self.state_machine = self
+            .state_machine
+            .clone()
+            .transition(Event::Release(velocity));
+    }
+
+    // The top middle of him, which is what reaches for swing points.
+    fn hands(&self) -> Point {
+        let bounds = self.bounding_box();
+        Point {
+            x: bounds.x() + bounds.width / 2,
+            y: bounds.y(),
+        }
+    }
+
     pub fn jump(&mut self) {
+        if matches!(
+            self.state_machine,
+            RedHatBoyStateMachine::Running(_) | RedHatBoyStateMachine::Dashing(_)
+        ) {
+            self.jumps += 1;
+        }
         self.state_machine = self.state_machine.clone().transition(Event::Jump);
     }
 
-    fn frame_name(&self) -> String {
-        format!(
-            "{} ({}).png",
-            self.state_machine.frame_name(),
-            (self.state_machine.context().frame() / 3) + 1
-        )
+    fn frame_name(&self) -> Option<&str> {
+        let context = self.state_machine.context();
+        context
+            .animations
+            .get(self.state_machine.frame_name())
+            .and_then(|animation| animation.frame_name(context.elapsed()))
     }
 
     fn current_sprite(&self) -> Option<&Cell> {
-        self.sprite_sheet.frames.get(&self.frame_name())
+        self.frame_name().and_then(|frame_name| {
+            self.riding
+                .as_ref()
+                .and_then(|_sprint| self.riding_sheet.frames.get(frame_name))
+                .or_else(|| self.sprite_sheet.frames.get(frame_name))
+        })
     }
 
+    // The sprint he is on, if any, which changes how he is drawn.
+    fn ride(&mut self, sprint: Option<&DogSprint>) {
+        self.riding = sprint.cloned();
+    }
+
+    // A frame the sheet lacks takes up a placeholder's space.
     pub fn destination_box(&self) -> Rect {
-        let sprite = self.current_sprite().expect("Cell not found");
-        Rect::new_from_x_y(
-            (self.state_machine.context().position().x + sprite.sprite_source_size.x as i16).into(),
-            (self.state_machine.context().position().y + sprite.sprite_source_size.y as i16).into(),
-            sprite.frame.w.into(),
-            sprite.frame.h.into(),
-        )
+        let position = self.state_machine.context().position();
+        match self.current_sprite() {
+            Some(sprite) => sprite.destination(&position),
+            None => Rect::new(position, placeholder::SIZE, placeholder::SIZE),
+        }
     }
 
     pub fn bounding_box(&self) -> Rect {
-        const X_OFFSET: i16 = 18;
-        const Y_OFFSET: i16 = 14;
-        const WIDTH_OFFSET: i16 = 28;
-        let mut bounding_box = self.destination_box();
-        bounding_box.set_x(bounding_box.x() + X_OFFSET);
-        bounding_box.width -= WIDTH_OFFSET;
-        bounding_box.set_y(bounding_box.y() + Y_OFFSET);
-        bounding_box.height -= Y_OFFSET;
-        bounding_box
-    }
-
-    pub fn knock_out(&mut self) {
+        self.hitboxes
+            .insets(self.state_machine.name(), self.frame_name())
+            .apply(&self.destination_box())
+    }
+
+    // Everything that has hit him this run, shielded or not.
+    pub fn hits(&self) -> u32 {
+        self.hits
+    }
+
+    pub fn knock_out(&mut self, cause: DeathCause) {
+        let first_touch = !self.struck && !self.was_struck;
+        self.struck = true;
+        if first_touch {
+            self.hits += 1;
+        }
+        if self.shield_frames > 0 || self.invincible || self.dashing() {
+            if first_touch {
+                self.rumble(Impact::Damage);
+            }
+            return;
+        }
+        self.death_cause.get_or_insert(cause);
+        if first_touch {
+            self.rumble(Impact::KnockOut);
+            self.audio().duck_music(KNOCK_OUT_DUCK_SECONDS);
+        }
         self.state_machine = self.state_machine.clone().transition(Event::KnockOut);
     }
 
-    pub fn land_on(&mut self, position: i16) {
-        self.state_machine = self.state_machine.clone().transition(Event::Land(position));
+    pub fn land_on(&mut self, position: i16, surface: Surface) {
+        self.state_machine = self
+            .state_machine
+            .clone()
+            .transition(Event::Land(position, surface));
+    }
+
+    // Runs off whatever he was standing on, leaving him in the air.
+    pub fn fall_off(&mut self) {
+        self.state_machine = self.state_machine.clone().transition(Event::FallOff);
+    }
+
+    // The top of the platform he is standing on, or nothing on the floor and
+    // in the air.
+    pub fn support(&self) -> Option<i16> {
+        self.state_machine.context().support()
     }
 
     pub fn pos_y(&self) -> i16 {
@@ -191,21 +661,266 @@ impl RedHatBoy {
         self.state_machine.context().velocity().y
     }
 
+    // How fast he is actually going, once the surface under him has had its say.
     pub fn walking_speed(&self) -> i16 {
-        self.state_machine.context().velocity().x
+        self.state_machine.context().ground_speed()
     }
 
     fn knocked_out(&self) -> bool {
         self.state_machine.knocked_out()
     }
 
-    fn reset(boy: Self) -> Self {
-        RedHatBoy::new(
-            boy.sprite_sheet,
-            boy.image,
-            boy.state_machine.context().audio.clone(),
-            boy.state_machine.context().jump_sound.clone(),
+    // The zone he is in, if any, changes his physics until he leaves it.
+    fn enter_environment(&mut self, environment: Overrides) {
+        self.state_machine
+            .context_mut()
+            .enter_environment(environment);
+    }
+
+    // Force fields he is in push on him. Headwinds work against the world
+    // scrolling at `walking_speed`, and the speed left is handed back.
+    fn feel_forces(&mut self, walking_speed: i16, fields: &[ForceField]) -> i16 {
+        let bounds = self.bounding_box();
+        self.state_machine
+            .context_mut()
+            .feel_forces(walking_speed, &bounds, fields)
+    }
+
+    // The rest of his jump while he is in the air, or the one he would make
+    // now while running, as points under the middle of his feet.
+    fn jump_preview(&self, scroll: i16) -> Vec<Point> {
+        let context = self.state_machine.context();
+        let velocity_y = match self.state_machine {
+            RedHatBoyStateMachine::Running(_) | RedHatBoyStateMachine::Dashing(_) => {
+                context.physics_here().jump_speed
+            }
+            RedHatBoyStateMachine::Jumping(_) => context.velocity().y,
+            _ => return vec![],
+        };
+        let feet = self.bounding_box();
+        let position = context.position();
+        let offset = Point {
+            x: feet.x() + feet.width / 2 - position.x,
+            y: feet.bottom() - position.y,
+        };
+        context
+            .trajectory(velocity_y, scroll)
+            .iter()
+            .map(|point| Point {
+                x: point.x + offset.x,
+                y: point.y + offset.y,
+            })
+            .collect()
+    }
+
+    fn airborne(&self) -> bool {
+        matches!(
+            self.state_machine,
+            RedHatBoyStateMachine::Jumping(_)
+                | RedHatBoyStateMachine::Swinging(_)
+                | RedHatBoyStateMachine::Falling(_)
+        )
+    }
+
+    // True on the first frame he touches something that hurts, shield or not.
+    fn just_struck(&self) -> bool {
+        self.struck && !self.was_struck
+    }
+
+    // Running into something hurts as much as falling onto it.
+    fn rumble(&self, impact: Impact) {
+        let context = self.state_machine.context();
+        let velocity = context.velocity();
+        let speed = velocity.x.abs().max(velocity.y.abs());
+        context.haptics.play(Rumble::for_impact(
+            impact,
+            speed,
+            context.physics.terminal_velocity,
+        ));
+    }
+
+    fn audio(&self) -> &Audio {
+        &self.state_machine.context().audio
+    }
+
+    fn physics(&self) -> Physics {
+        self.state_machine.context().physics
+    }
+
+    fn physics_here(&self) -> Physics {
+        self.state_machine.context().physics_here()
+    }
+
+    fn physics_mut(&mut self) -> &mut Physics {
+        &mut self.state_machine.context_mut().physics
+    }
+
+    // Silences him, handing back what he played through so it can be put back.
+    fn mute(&mut self) -> (Audio, Haptics) {
+        let context = self.state_machine.context_mut();
+        let (audio, haptics) = (context.audio.muted(), context.haptics.muted());
+        (
+            std::mem::replace(&mut context.audio, audio),
+            std::mem::replace(&mut context.haptics, haptics),
+        )
+    }
+
+    fn unmute(&mut self, (audio, haptics): (Audio, Haptics)) {
+        let context = self.state_machine.context_mut();
+        context.audio = audio;
+        context.haptics = haptics;
+    }
+
+    // Every state he can reach from standing idle, found by trying each event
+    // in turn on a muted copy of him.
+    #[cfg(debug_assertions)]
+    fn transition_graph(&self) -> StateGraph {
+        let context = self.state_machine.context();
+        let mut idle: RedHatBoyStateMachine = RedHatBoyState::new(
+            context.audio.muted(),
+            context.jump_sound.clone(),
+            context.animations.clone(),
+            context.physics,
+            context.tick,
+        )
+        .into();
+        idle.context_mut().haptics = context.haptics.muted();
+
+        let mut graph = StateGraph::new("RedHatBoy");
+        let mut seen = vec![idle.name()];
+        let mut queue = VecDeque::from([idle]);
+        while let Some(state_machine) = queue.pop_front() {
+            graph.add_state(state_machine.name());
+            probe_events().into_iter().for_each(|(label, event)| {
+                let next = match event {
+                    Event::Update => state_machine.clone().update_until_changed(),
+                    event => state_machine.clone().transition(event),
+                };
+                graph.add_edge(state_machine.name(), label, next.name());
+                if !seen.contains(&next.name()) {
+                    seen.push(next.name());
+                    queue.push_back(next);
+                }
+            });
+        }
+        graph
+    }
+
+    #[cfg(debug_assertions)]
+    fn nudge(&mut self, nudge: Nudge) {
+        let context = self.state_machine.context_mut();
+        match nudge {
+            Nudge::Position(offset) => context.nudge(offset, Point::default()),
+            Nudge::Velocity(offset) => context.nudge(Point::default(), offset),
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn hitboxes(&self) -> &Hitboxes {
+        &self.hitboxes
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn hitboxes_mut(&mut self) -> &mut Hitboxes {
+        &mut self.hitboxes
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn image(&self) -> &HtmlImageElement {
+        &self.image
+    }
+
+    // The sprites of the animation a state plays, in order.
+    #[cfg(debug_assertions)]
+    pub fn state_frames(&self, state: &str) -> Vec<(&str, &Cell)> {
+        STATE_ANIMATIONS
+            .iter()
+            .find(|(name, _animation)| *name == state)
+            .and_then(|(_name, animation)| self.state_machine.context().animations.get(animation))
+            .map_or(vec![], |animation| {
+                animation
+                    .frame_names()
+                    .into_iter()
+                    .filter_map(|frame_name| {
+                        self.sprite_sheet
+                            .frames
+                            .get(frame_name)
+                            .map(|cell| (frame_name, cell))
+                    })
+                    .collect()
+            })
+    }
+
+    #[cfg(debug_assertions)]
+    fn inspect(&self) -> Vec<String> {
+        let context = self.state_machine.context();
+        let position = context.position();
+        let velocity = context.velocity();
+        vec![
+            format!("Boy: {}", self.state_machine.name()),
+            format!("Position: {}, {}", position.x, position.y),
+            format!("Velocity: {}, {}", velocity.x, velocity.y),
+            format!(
+                "Surface: {:?} ({})",
+                context.surface(),
+                context.ground_speed()
+            ),
+            inspector::describe_box("Sprite", &self.destination_box()),
+            inspector::describe_box("Hitbox", &self.bounding_box()),
+            format!(
+                "Frame: {} ({:.0}ms)",
+                self.frame_name().unwrap_or("none"),
+                context.elapsed()
+            ),
+        ]
+    }
+
+    fn snapshot(&self) -> BoySnapshot {
+        BoySnapshot {
+            state_machine: self.state_machine.clone(),
+            shield_frames: self.shield_frames,
+            dash_meter: self.dash_meter,
+            stagger_frames: self.stagger_frames,
+            jumps: self.jumps,
+            death_cause: self.death_cause,
+        }
+    }
+
+    fn restore(&mut self, snapshot: BoySnapshot) {
+        self.state_machine = snapshot.state_machine;
+        self.shield_frames = snapshot.shield_frames;
+        self.dash_meter = snapshot.dash_meter;
+        self.stagger_frames = snapshot.stagger_frames;
+        self.jumps = snapshot.jumps;
+        self.death_cause = snapshot.death_cause;
+    }
+
+    // Back to standing at the start, keeping his animations, sounds and
+    // hitboxes rather than building them again.
+    fn restart(&mut self) {
+        let context = self.state_machine.context();
+        let mut idle: RedHatBoyStateMachine = RedHatBoyState::new(
+            context.audio.clone(),
+            context.jump_sound.clone(),
+            context.animations.clone(),
+            context.physics,
+            context.tick,
         )
+        .into();
+        let restarted = idle.context_mut();
+        restarted.footsteps = context.footsteps.clone();
+        restarted.haptics = context.haptics;
+        self.state_machine = idle;
+        self.shield_frames = 0;
+        self.dash_meter = DashMeter::default();
+        self.stagger_frames = 0;
+        self.smashed = false;
+        self.riding = None;
+        self.jumps = 0;
+        self.death_cause = None;
+        self.struck = false;
+        self.was_struck = false;
+        self.hits = 0;
     }
 }
 
@@ -215,17 +930,44 @@ enum RedHatBoyStateMachine {
     Running(RedHatBoyState<Running>),
     Sliding(RedHatBoyState<Sliding>),
     Jumping(RedHatBoyState<Jumping>),
+    Dashing(RedHatBoyState<Dashing>),
+    Swinging(RedHatBoyState<Swinging>),
     Falling(RedHatBoyState<Falling>),
     KnockOut(RedHatBoyState<KnockOut>),
 }
 
+// One of each event, landing on the floor, labelled for the transition graph.
+#[cfg(debug_assertions)]
+fn probe_events() -> [(&'static str, Event); 11] {
+    [
+        ("Run", Event::Run),
+        ("Slide", Event::Slide),
+        ("Update", Event::Update),
+        ("KnockOut", Event::KnockOut),
+        ("Jump", Event::Jump),
+        ("Land", Event::Land(HEIGHT, Surface::Normal)),
+        ("FallOff", Event::FallOff),
+        ("Dash", Event::Dash),
+        ("Stumble", Event::Stumble),
+        ("Grab", Event::Grab),
+        ("Release", Event::Release(Point::default())),
+    ]
+}
+
 pub enum Event {
     Run,
     Slide,
     Update,
     KnockOut,
     Jump,
-    Land(i16),
+    Land(i16, Surface),
+    FallOff,
+    Dash,
+    // Cuts a dash short.
+    Stumble,
+    Grab,
+    // Lets go of a swing going this fast.
+    Release(Point),
 }
 
 impl RedHatBoyStateMachine {
@@ -234,23 +976,43 @@ impl RedHatBoyStateMachine {
             (RedHatBoyStateMachine::Idle(state), Event::Run) => state.run().into(),
             (RedHatBoyStateMachine::Running(state), Event::Jump) => state.jump().into(),
             (RedHatBoyStateMachine::Running(state), Event::Slide) => state.slide().into(),
+            (RedHatBoyStateMachine::Running(state), Event::FallOff) => state.fall_off().into(),
+            (RedHatBoyStateMachine::Sliding(state), Event::FallOff) => state.fall_off().into(),
             (RedHatBoyStateMachine::Running(state), Event::KnockOut) => state.knock_out().into(),
-            (RedHatBoyStateMachine::Running(state), Event::Land(position)) => {
-                state.land_on(position).into()
+            (RedHatBoyStateMachine::Running(state), Event::Land(position, surface)) => {
+                state.land_on(position, surface).into()
             }
-            (RedHatBoyStateMachine::Jumping(state), Event::Land(position)) => {
-                state.land_on(position).into()
+            (RedHatBoyStateMachine::Jumping(state), Event::Land(position, surface)) => {
+                state.land_on(position, surface).into()
             }
             (RedHatBoyStateMachine::Jumping(state), Event::KnockOut) => state.knock_out().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::KnockOut) => state.knock_out().into(),
-            (RedHatBoyStateMachine::Sliding(state), Event::Land(position)) => {
-                state.land_on(position).into()
+            (RedHatBoyStateMachine::Sliding(state), Event::Land(position, surface)) => {
+                state.land_on(position, surface).into()
+            }
+            (RedHatBoyStateMachine::Running(state), Event::Dash) => state.dash().into(),
+            (RedHatBoyStateMachine::Dashing(state), Event::Jump) => state.jump().into(),
+            (RedHatBoyStateMachine::Dashing(state), Event::FallOff) => state.fall_off().into(),
+            (RedHatBoyStateMachine::Dashing(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Dashing(state), Event::Stumble) => state.stop().into(),
+            (RedHatBoyStateMachine::Dashing(state), Event::Land(position, surface)) => {
+                state.land_on(position, surface).into()
+            }
+            (RedHatBoyStateMachine::Jumping(state), Event::Grab) => state.grab().into(),
+            (RedHatBoyStateMachine::Swinging(state), Event::Release(velocity)) => {
+                state.release(velocity).into()
+            }
+            (RedHatBoyStateMachine::Swinging(state), Event::KnockOut) => state.knock_out().into(),
+            (RedHatBoyStateMachine::Swinging(state), Event::Land(position, surface)) => {
+                state.land_on(position, surface).into()
             }
             (RedHatBoyStateMachine::Idle(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Running(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Jumping(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Sliding(state), Event::Update) => state.update().into(),
             (RedHatBoyStateMachine::Falling(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Dashing(state), Event::Update) => state.update().into(),
+            (RedHatBoyStateMachine::Swinging(state), Event::Update) => state.update().into(),
             _ => self,
         }
     }
@@ -261,6 +1023,8 @@ impl RedHatBoyStateMachine {
             RedHatBoyStateMachine::Running(state) => state.frame_name(),
             RedHatBoyStateMachine::Sliding(state) => state.frame_name(),
             RedHatBoyStateMachine::Jumping(state) => state.frame_name(),
+            RedHatBoyStateMachine::Dashing(state) => state.frame_name(),
+            RedHatBoyStateMachine::Swinging(state) => state.frame_name(),
             RedHatBoyStateMachine::KnockOut(state) => state.frame_name(),
             RedHatBoyStateMachine::Falling(state) => state.frame_name(),
         }
@@ -271,15 +1035,58 @@ impl RedHatBoyStateMachine {
             RedHatBoyStateMachine::Running(state) => &state.context(),
             RedHatBoyStateMachine::Sliding(state) => &state.context(),
             RedHatBoyStateMachine::Jumping(state) => &state.context(),
+            RedHatBoyStateMachine::Dashing(state) => state.context(),
+            RedHatBoyStateMachine::Swinging(state) => state.context(),
             RedHatBoyStateMachine::KnockOut(state) => &state.context(),
             RedHatBoyStateMachine::Falling(state) => &state.context(),
         }
     }
 
+    fn name(&self) -> &'static str {
+        match self {
+            RedHatBoyStateMachine::Idle(_) => "Idle",
+            RedHatBoyStateMachine::Running(_) => "Running",
+            RedHatBoyStateMachine::Sliding(_) => "Sliding",
+            RedHatBoyStateMachine::Jumping(_) => "Jumping",
+            RedHatBoyStateMachine::Dashing(_) => "Dashing",
+            RedHatBoyStateMachine::Swinging(_) => "Swinging",
+            RedHatBoyStateMachine::KnockOut(_) => "KnockOut",
+            RedHatBoyStateMachine::Falling(_) => "Falling",
+        }
+    }
+
+    fn context_mut(&mut self) -> &mut RedHatBoyContext {
+        match self {
+            RedHatBoyStateMachine::Idle(state) => &mut state.context,
+            RedHatBoyStateMachine::Running(state) => &mut state.context,
+            RedHatBoyStateMachine::Sliding(state) => &mut state.context,
+            RedHatBoyStateMachine::Jumping(state) => &mut state.context,
+            RedHatBoyStateMachine::Dashing(state) => &mut state.context,
+            RedHatBoyStateMachine::Swinging(state) => &mut state.context,
+            RedHatBoyStateMachine::KnockOut(state) => &mut state.context,
+            RedHatBoyStateMachine::Falling(state) => &mut state.context,
+        }
+    }
+
     fn update(self) -> Self {
         self.transition(Event::Update)
     }
 
+    // Some states only end with time, so updates go on until he leaves this
+    // one or it is clear he never will.
+    #[cfg(debug_assertions)]
+    fn update_until_changed(self) -> Self {
+        let name = self.name();
+        let mut state_machine = self;
+        for _ in 0..u8::MAX {
+            state_machine = state_machine.update();
+            if state_machine.name() != name {
+                break;
+            }
+        }
+        state_machine
+    }
+
     fn knocked_out(&self) -> bool {
         matches!(self, RedHatBoyStateMachine::KnockOut(_))
     }
@@ -309,6 +1116,36 @@ impl From<RedHatBoyState<Jumping>> for RedHatBoyStateMachine {
     }
 }
 
+impl From<RedHatBoyState<Dashing>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Dashing>) -> Self {
+        RedHatBoyStateMachine::Dashing(state)
+    }
+}
+
+impl From<DashingEndState> for RedHatBoyStateMachine {
+    fn from(end_state: DashingEndState) -> Self {
+        match end_state {
+            DashingEndState::Complete(running_state) => running_state.into(),
+            DashingEndState::Dashing(dashing_state) => dashing_state.into(),
+        }
+    }
+}
+
+impl From<RedHatBoyState<Swinging>> for RedHatBoyStateMachine {
+    fn from(state: RedHatBoyState<Swinging>) -> Self {
+        RedHatBoyStateMachine::Swinging(state)
+    }
+}
+
+impl From<SwingingEndState> for RedHatBoyStateMachine {
+    fn from(end_state: SwingingEndState) -> Self {
+        match end_state {
+            SwingingEndState::Landing(running_state) => running_state.into(),
+            SwingingEndState::Swinging(swinging_state) => swinging_state.into(),
+        }
+    }
+}
+
 impl From<RedHatBoyState<Falling>> for RedHatBoyStateMachine {
     fn from(state: RedHatBoyState<Falling>) -> Self {
         RedHatBoyStateMachine::Falling(state)
@@ -349,8 +1186,21 @@ impl From<FallingEndState> for RedHatBoyStateMachine {
 }
 
 mod red_hat_boy_states {
+    use std::rc::Rc;
+
+    use rand::{seq::SliceRandom, thread_rng};
+
     use super::HEIGHT;
-    use crate::engine::{Audio, Point, Sound};
+    use crate::{
+        audit::{self, Source},
+        config::Physics,
+        engine::{
+            physics::{self, Body, ForceField, Overrides},
+            Animations, Audio, Bus, Haptics, Impact, Playback, Point, Rect, Rumble, Sound,
+        },
+        footsteps::{Footfall, Footing, FootstepCues},
+        surfaces::Surface,
+    };
 
     const FLOOR: i16 = 479;
     const PLAYER_HEIGHT: i16 = HEIGHT - FLOOR;
@@ -358,35 +1208,69 @@ mod red_hat_boy_states {
     const IDLE_FRAME_NAME: &str = "Idle";
     const RUN_FRAME_NAME: &str = "Run";
 
-    pub const IDLE_FRAMES: u8 = 29;
-    pub const RUNNING_FRAMES: u8 = 23;
-
-    const RUNNING_SPEED: i16 = 3;
-    pub const SLIDING_FRAMES: u8 = 14;
     const SLIDING_FRAME_NAME: &str = "Slide";
 
-    pub const JUMPING_FRAMES: u8 = 12;
-    const JUMP_SPEED: i16 = -25;
     const JUMPING_FRAME_NAME: &str = "Jump";
 
-    const FALLING_FRAMES: u8 = 29;
     const FALLING_FRAME_NAME: &str = "Dead";
 
-    const TERMINAL_VELOCITY: i16 = 20;
-
-    const GRAVITY: i16 = 1;
+    // Every animation a state plays, which the sheet has to have.
+    pub const REQUIRED_ANIMATIONS: [&str; 5] = [
+        IDLE_FRAME_NAME,
+        RUN_FRAME_NAME,
+        SLIDING_FRAME_NAME,
+        JUMPING_FRAME_NAME,
+        FALLING_FRAME_NAME,
+    ];
+
+    // Hanging on looks like the top of a jump.
+    const SWINGING_FRAME_NAME: &str = JUMPING_FRAME_NAME;
+
+    // Dashing is running, only faster.
+    const DASHING_FRAME_NAME: &str = RUN_FRAME_NAME;
+    const DASH_FRAMES: u8 = 20;
+
+    // After standing still this long he fidgets now and then, with whichever
+    // of these the sheet has tags for.
+    const IDLE_VARIATION_DELAY: u16 = 300;
+    const IDLE_VARIATIONS: [&str; 3] = ["Blink", "Yawn", "LookAround"];
+    // The run frames where a foot comes down.
+    const FOOTSTEP_FRAMES: [&str; 2] = ["Run (3).png", "Run (7).png"];
+
+    #[cfg(debug_assertions)]
+    pub const STATE_ANIMATIONS: [(&str, &str); 8] = [
+        ("Idle", IDLE_FRAME_NAME),
+        ("Running", RUN_FRAME_NAME),
+        ("Sliding", SLIDING_FRAME_NAME),
+        ("Jumping", JUMPING_FRAME_NAME),
+        ("Dashing", DASHING_FRAME_NAME),
+        ("Swinging", SWINGING_FRAME_NAME),
+        ("Falling", FALLING_FRAME_NAME),
+        ("KnockOut", FALLING_FRAME_NAME),
+    ];
 
     #[derive(Clone, Copy)]
     pub struct Sliding;
 
-    #[derive(Copy, Clone)]
-    pub struct Idle;
+    #[derive(Copy, Clone, Default)]
+    pub struct Idle {
+        idle_frames: u16,
+        variation: Option<&'static str>,
+    }
     #[derive(Copy, Clone)]
     pub struct Running;
 
     #[derive(Copy, Clone)]
     pub struct Jumping;
 
+    #[derive(Copy, Clone)]
+    pub struct Dashing {
+        frames: u8,
+    }
+
+    #[derive(Copy, Clone)]
+    pub struct Swinging;
+
     #[derive(Copy, Clone)]
     pub struct KnockOut;
 
@@ -401,37 +1285,84 @@ mod red_hat_boy_states {
 
     #[derive(Clone)]
     pub struct RedHatBoyContext {
+        // Updates since the state or its animation started, which gravity
+        // counts in.
         frame: u8,
+        // Milliseconds into the current animation.
+        elapsed: f64,
+        // Milliseconds each update lasts, as the game loop runs them.
+        pub tick: f64,
+        completed: bool,
         position: Point,
         velocity: Point,
+        surface: Surface,
+        support: Option<i16>,
+        ground_speed: i16,
         pub audio: Audio,
+        pub haptics: Haptics,
         pub jump_sound: Sound,
+        pub footsteps: Rc<FootstepCues>,
+        pub animations: Rc<Animations>,
+        pub physics: Physics,
+        // What the zone he is in changes about `physics`.
+        environment: Overrides,
     }
 
     impl RedHatBoyContext {
-        pub fn update(mut self, frame_count: u8) -> Self {
-            if self.frame < frame_count {
-                self.frame += 1;
-            } else {
-                self.frame = 0;
-            }
+        pub fn update(self, animation: &str) -> Self {
+            self.animate(animation).fall()
+        }
 
-            if self.velocity.y < TERMINAL_VELOCITY {
-                self.velocity.y += GRAVITY;
-            }
+        // Plays on without moving him, for when something else does.
+        fn animate(mut self, animation: &str) -> Self {
+            let playback = self.animations.advance(animation, self.elapsed, self.tick);
+            self.completed = playback == Playback::Completed;
+            (self.elapsed, self.frame) = match playback {
+                Playback::Playing(elapsed) => (elapsed, self.frame.wrapping_add(1)),
+                Playback::Completed => (0.0, 0),
+            };
+            self
+        }
 
-            // self.position.x += self.velocity.x;
-            self.position.y += self.velocity.y;
+        fn fall(mut self) -> Self {
+            // He stays put across, with the world scrolling past him instead.
+            let upright = Body {
+                velocity: Point {
+                    x: 0,
+                    y: self.velocity.y,
+                },
+                ..self.body()
+            };
+            let body = physics::step(upright, &self.physics_here(), self.frame);
+            self.position = body.position;
+            self.velocity.y = body.velocity.y;
 
             if self.position.y > FLOOR {
                 self.position.y = FLOOR;
             }
+            // The floor is never landed on while running, so reaching it is
+            // what takes him off a platform's surface.
+            if self.position.y == FLOOR {
+                self.surface = Surface::Normal;
+            }
+            self.ground_speed = self.surface.settle(self.ground_speed, self.velocity.x);
 
             self
         }
 
-        pub fn frame(&self) -> u8 {
-            self.frame
+        pub fn elapsed(&self) -> f64 {
+            self.elapsed
+        }
+
+        // Whether the last update finished the animation and started it over.
+        fn completed(&self) -> bool {
+            self.completed
+        }
+
+        // Whether the next update ends `animation`.
+        fn finishing(&self, animation: &str) -> bool {
+            self.animations
+                .finishing(animation, self.elapsed, self.tick)
         }
 
         pub fn position(&self) -> Point {
@@ -442,13 +1373,70 @@ mod red_hat_boy_states {
             self.velocity
         }
 
-        fn reset_frame(mut self) -> Self {
-            self.frame = 0;
-            self
+        // Returns how fast the world scrolls once the fields have had their say.
+        pub fn feel_forces(&mut self, scroll: i16, bounds: &Rect, fields: &[ForceField]) -> i16 {
+            let body = Body {
+                position: self.position,
+                velocity: Point {
+                    x: scroll,
+                    y: self.velocity.y,
+                },
+            };
+            let pushed = physics::apply_fields(body, bounds, fields);
+            self.velocity.y = pushed.velocity.y;
+            pushed.velocity.x
         }
 
-        fn run_right(mut self) -> Self {
-            self.velocity.x += RUNNING_SPEED;
+        pub fn physics_here(&self) -> Physics {
+            self.environment.apply(self.physics)
+        }
+
+        pub fn enter_environment(&mut self, environment: Overrides) {
+            self.environment = environment;
+        }
+
+        fn body(&self) -> Body {
+            Body {
+                position: self.position,
+                velocity: self.velocity,
+            }
+        }
+
+        // Where he will be on each frame of going up or down at `velocity_y`
+        // from here until the floor, with the world scrolling `scroll` a frame.
+        pub fn trajectory(&self, velocity_y: i16, scroll: i16) -> Vec<Point> {
+            let body = Body {
+                position: self.position,
+                velocity: Point {
+                    x: scroll,
+                    y: velocity_y,
+                },
+            };
+            physics::trajectory(body, &self.physics_here(), self.frame, FLOOR)
+        }
+
+        #[cfg(debug_assertions)]
+        pub fn surface(&self) -> Surface {
+            self.surface
+        }
+
+        pub fn ground_speed(&self) -> i16 {
+            self.ground_speed
+        }
+
+        pub fn support(&self) -> Option<i16> {
+            self.support
+        }
+
+        fn reset_frame(mut self) -> Self {
+            self.frame = 0;
+            self.elapsed = 0.0;
+            self.completed = false;
+            self
+        }
+
+        fn run_right(mut self) -> Self {
+            self.velocity.x += self.physics.running_speed;
             self
         }
 
@@ -457,20 +1445,85 @@ mod red_hat_boy_states {
             self
         }
 
+        fn jump(mut self) -> Self {
+            self.velocity = physics::jump(self.body(), &self.physics_here()).velocity;
+            self
+        }
+
+        fn leave_support(mut self) -> Self {
+            self.support = None;
+            self
+        }
+
+        // Where a swing has carried him, and how fast it is going.
+        pub fn swing_to(&mut self, y: i16, velocity: Point) {
+            self.position.y = y;
+            self.velocity = velocity;
+        }
+
+        // Letting go of a swing keeps its speed, though the world never
+        // scrolls back the way he came.
+        fn fling(mut self, velocity: Point) -> Self {
+            self.velocity = Point {
+                x: velocity.x.max(0),
+                y: velocity.y,
+            };
+            self
+        }
+
+        // A swing can fling him off faster or slower than he runs, so his
+        // feet find his own pace again.
+        fn keep_pace(mut self) -> Self {
+            self.velocity.x = self.physics.running_speed;
+            self
+        }
+
         fn stop(mut self) -> Self {
             self.velocity.x = 0;
             self.velocity.y = 0;
+            self.surface = Surface::Normal;
+            self.support = None;
+            self.ground_speed = 0;
+            self
+        }
+
+        // Standing stops the fall, so gravity builds up again from nothing
+        // when he next leaves the ground.
+        fn set_on(mut self, top: i16, surface: Surface) -> Self {
+            self.position.y = top - PLAYER_HEIGHT;
+            self.velocity.y = 0;
+            self.surface = surface;
+            self.support = if top < HEIGHT { Some(top) } else { None };
             self
         }
 
-        fn set_on(mut self, position: i16) -> Self {
-            let position = position - PLAYER_HEIGHT;
-            self.position.y = position;
+        #[cfg(debug_assertions)]
+        pub fn nudge(&mut self, position: Point, velocity: Point) {
+            self.position.x += position.x;
+            self.position.y += position.y;
+            self.velocity.x += velocity.x;
+            self.velocity.y += velocity.y;
+        }
+
+        fn play_footsteps(self, animation: &str) -> Self {
+            let stepped = self
+                .animations
+                .get(animation)
+                .and_then(|animation| animation.frame_started(self.elapsed, self.tick))
+                .map_or(false, |frame| FOOTSTEP_FRAMES.contains(&frame));
+            if stepped {
+                self.play_footfall(Footfall::Step);
+            }
             self
         }
 
+        fn play_footfall(&self, footfall: Footfall) {
+            let footing = Footing::under(self.support, self.surface);
+            self.footsteps.play(&self.audio, footing, footfall);
+        }
+
         fn play_jump_sound(self) -> Self {
-            if let Err(err) = self.audio.play_sound(&self.jump_sound) {
+            if let Err(err) = self.audio.play_sound(&self.jump_sound, Bus::Sfx) {
                 log!("Error playing jump sound {:#?}", err);
             }
             self
@@ -492,28 +1545,69 @@ mod red_hat_boy_states {
         }
 
         pub fn frame_name(&self) -> &str {
-            IDLE_FRAME_NAME
+            self._state.variation.unwrap_or(IDLE_FRAME_NAME)
         }
 
+        // Variations play once each, and only start when the idle loop ends,
+        // so he never jumps from one pose to another mid-animation.
         pub fn update(mut self) -> Self {
-            self.context = self.context.update(IDLE_FRAMES);
+            let animation = self._state.variation.unwrap_or(IDLE_FRAME_NAME);
+            self.context = self.context.update(animation);
+            self._state.idle_frames = self._state.idle_frames.saturating_add(1);
+            if self.context.completed() {
+                self._state = match self._state.variation {
+                    Some(_) => Idle::default(),
+                    None if self._state.idle_frames >= IDLE_VARIATION_DELAY => Idle {
+                        variation: self.pick_variation(),
+                        ..self._state
+                    },
+                    None => self._state,
+                };
+            }
             self
         }
+
+        fn pick_variation(&self) -> Option<&'static str> {
+            let available: Vec<&'static str> = IDLE_VARIATIONS
+                .iter()
+                .copied()
+                .filter(|name| self.context.animations.get(name).is_some())
+                .collect();
+            audit::flag(Source::ThreadRng);
+            available.choose(&mut thread_rng()).copied()
+        }
     }
     impl RedHatBoyState<Idle> {
-        pub fn new(audio: Audio, jump_sound: Sound) -> Self {
+        pub fn new(
+            audio: Audio,
+            jump_sound: Sound,
+            animations: Rc<Animations>,
+            physics: Physics,
+            tick: f64,
+        ) -> Self {
             RedHatBoyState {
                 context: RedHatBoyContext {
                     frame: 0,
+                    elapsed: 0.0,
+                    tick,
+                    completed: false,
                     position: Point {
                         x: STARTING_POINT,
                         y: FLOOR,
                     },
                     velocity: Point { x: 0, y: 0 },
+                    surface: Surface::Normal,
+                    support: None,
+                    ground_speed: 0,
                     audio,
+                    haptics: Haptics::default(),
                     jump_sound,
+                    footsteps: Rc::new(FootstepCues::default()),
+                    animations,
+                    physics,
+                    environment: Overrides::default(),
                 },
-                _state: Idle {},
+                _state: Idle::default(),
             }
         }
     }
@@ -524,7 +1618,10 @@ mod red_hat_boy_states {
         }
 
         pub fn update(mut self) -> Self {
-            self.context = self.context.update(RUNNING_FRAMES);
+            self.context = self
+                .context
+                .update(RUN_FRAME_NAME)
+                .play_footsteps(RUN_FRAME_NAME);
             self
         }
 
@@ -539,15 +1636,29 @@ mod red_hat_boy_states {
                 context: self
                     .context
                     .reset_frame()
-                    .set_vertical_velocity(JUMP_SPEED)
+                    .jump()
+                    .leave_support()
                     .play_jump_sound(),
                 _state: Jumping {},
             }
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+        // Dropping off an edge is a jump with no push upwards, so it lands
+        // the same way.
+        pub fn fall_off(self) -> RedHatBoyState<Jumping> {
+            RedHatBoyState {
+                context: self
+                    .context
+                    .reset_frame()
+                    .set_vertical_velocity(0)
+                    .leave_support(),
+                _state: Jumping {},
+            }
+        }
+
+        pub fn land_on(self, position: i16, surface: Surface) -> RedHatBoyState<Running> {
             RedHatBoyState {
-                context: self.context.reset_frame().set_on(position),
+                context: self.context.reset_frame().set_on(position, surface),
                 _state: Running {},
             }
         }
@@ -558,6 +1669,60 @@ mod red_hat_boy_states {
                 _state: Falling {},
             }
         }
+
+        // Keeps the run animation going rather than starting it over.
+        pub fn dash(self) -> RedHatBoyState<Dashing> {
+            RedHatBoyState {
+                context: self.context,
+                _state: Dashing { frames: 0 },
+            }
+        }
+    }
+
+    impl RedHatBoyState<Dashing> {
+        pub fn frame_name(&self) -> &str {
+            DASHING_FRAME_NAME
+        }
+
+        pub fn update(mut self) -> DashingEndState {
+            self.context = self
+                .context
+                .update(DASHING_FRAME_NAME)
+                .play_footsteps(DASHING_FRAME_NAME);
+            self._state.frames += 1;
+            if self._state.frames >= DASH_FRAMES {
+                DashingEndState::Complete(self.stop())
+            } else {
+                DashingEndState::Dashing(self)
+            }
+        }
+
+        pub fn stop(self) -> RedHatBoyState<Running> {
+            RedHatBoyState {
+                context: self.context,
+                _state: Running,
+            }
+        }
+
+        pub fn jump(self) -> RedHatBoyState<Jumping> {
+            self.stop().jump()
+        }
+
+        pub fn fall_off(self) -> RedHatBoyState<Jumping> {
+            self.stop().fall_off()
+        }
+
+        pub fn knock_out(self) -> RedHatBoyState<Falling> {
+            self.stop().knock_out()
+        }
+
+        // Landing on a platform mid-dash carries on dashing along it.
+        pub fn land_on(self, position: i16, surface: Surface) -> RedHatBoyState<Dashing> {
+            RedHatBoyState {
+                context: self.context.set_on(position, surface),
+                _state: self._state,
+            }
+        }
     }
 
     impl RedHatBoyState<Sliding> {
@@ -565,6 +1730,17 @@ mod red_hat_boy_states {
             SLIDING_FRAME_NAME
         }
 
+        pub fn fall_off(self) -> RedHatBoyState<Jumping> {
+            RedHatBoyState {
+                context: self
+                    .context
+                    .reset_frame()
+                    .set_vertical_velocity(0)
+                    .leave_support(),
+                _state: Jumping {},
+            }
+        }
+
         pub fn stand(self) -> RedHatBoyState<Running> {
             RedHatBoyState {
                 context: self.context().clone().reset_frame(),
@@ -573,9 +1749,9 @@ mod red_hat_boy_states {
         }
 
         pub fn update(mut self) -> SlidingEndState {
-            self.context = self.context.update(SLIDING_FRAMES);
+            self.context = self.context.update(SLIDING_FRAME_NAME);
 
-            if self.context.frame >= SLIDING_FRAMES {
+            if self.context.finishing(SLIDING_FRAME_NAME) {
                 SlidingEndState::Complete(self.stand())
             } else {
                 SlidingEndState::Sliding(self)
@@ -589,9 +1765,9 @@ mod red_hat_boy_states {
             }
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+        pub fn land_on(self, position: i16, surface: Surface) -> RedHatBoyState<Running> {
             RedHatBoyState {
-                context: self.context.reset_frame().set_on(position),
+                context: self.context.reset_frame().set_on(position, surface),
                 _state: Running,
             }
         }
@@ -603,9 +1779,9 @@ mod red_hat_boy_states {
         }
 
         pub fn update(mut self) -> JumpingEndState {
-            self.context = self.context.update(JUMPING_FRAMES);
+            self.context = self.context.update(JUMPING_FRAME_NAME);
             if self.context.position.y >= FLOOR {
-                JumpingEndState::Landing(self.land_on(HEIGHT.into()))
+                JumpingEndState::Landing(self.land_on(HEIGHT, Surface::Normal))
             } else {
                 JumpingEndState::Jumping(self)
             }
@@ -618,14 +1794,72 @@ mod red_hat_boy_states {
             }
         }
 
-        pub fn land_on(self, position: i16) -> RedHatBoyState<Running> {
+        pub fn land_on(self, position: i16, surface: Surface) -> RedHatBoyState<Running> {
+            let impact = self.context.velocity.y;
+            let context = self
+                .context
+                .reset_frame()
+                .set_on(position, surface)
+                .keep_pace();
+            context.play_footfall(Footfall::Landing);
+            context.haptics.play(Rumble::for_impact(
+                Impact::Landing,
+                impact,
+                context.physics.terminal_velocity,
+            ));
             RedHatBoyState {
-                context: self.context.reset_frame().set_on(position as i16),
+                context,
                 _state: Running,
             }
         }
     }
 
+    impl RedHatBoyState<Jumping> {
+        // Keeps the jump animation going, since swinging looks the same.
+        pub fn grab(self) -> RedHatBoyState<Swinging> {
+            RedHatBoyState {
+                context: self.context,
+                _state: Swinging,
+            }
+        }
+    }
+
+    impl RedHatBoyState<Swinging> {
+        pub fn frame_name(&self) -> &str {
+            SWINGING_FRAME_NAME
+        }
+
+        // The swing moves him rather than gravity, until it drags him along
+        // the floor.
+        pub fn update(mut self) -> SwingingEndState {
+            self.context = self.context.animate(SWINGING_FRAME_NAME);
+            if self.context.position.y >= FLOOR {
+                SwingingEndState::Landing(self.land_on(HEIGHT, Surface::Normal))
+            } else {
+                SwingingEndState::Swinging(self)
+            }
+        }
+
+        pub fn release(self, velocity: Point) -> RedHatBoyState<Jumping> {
+            RedHatBoyState {
+                context: self.context.fling(velocity),
+                _state: Jumping,
+            }
+        }
+
+        pub fn knock_out(self) -> RedHatBoyState<Falling> {
+            RedHatBoyState {
+                context: self.context.reset_frame().stop(),
+                _state: Falling {},
+            }
+        }
+
+        pub fn land_on(self, position: i16, surface: Surface) -> RedHatBoyState<Running> {
+            let velocity = self.context.velocity;
+            self.release(velocity).land_on(position, surface)
+        }
+    }
+
     impl RedHatBoyState<KnockOut> {
         pub fn frame_name(&self) -> &str {
             FALLING_FRAME_NAME
@@ -645,8 +1879,8 @@ mod red_hat_boy_states {
         }
 
         pub fn update(mut self) -> FallingEndState {
-            self.context = self.context.update(FALLING_FRAMES);
-            if self.context.frame >= FALLING_FRAMES {
+            self.context = self.context.update(FALLING_FRAME_NAME);
+            if self.context.finishing(FALLING_FRAME_NAME) {
                 FallingEndState::KnockOut(self.knock_out())
             } else {
                 FallingEndState::Falling(self)
@@ -658,6 +1892,14 @@ mod red_hat_boy_states {
         Complete(RedHatBoyState<Running>),
         Sliding(RedHatBoyState<Sliding>),
     }
+    pub enum DashingEndState {
+        Complete(RedHatBoyState<Running>),
+        Dashing(RedHatBoyState<Dashing>),
+    }
+    pub enum SwingingEndState {
+        Landing(RedHatBoyState<Running>),
+        Swinging(RedHatBoyState<Swinging>),
+    }
     pub enum JumpingEndState {
         Landing(RedHatBoyState<Running>),
         Jumping(RedHatBoyState<Jumping>),
@@ -669,45 +1911,99 @@ mod red_hat_boy_states {
 }
 
 pub trait Obstacle {
-    fn check_intersection(&self, bot: &mut RedHatBoy);
+    fn check_intersection(&mut self, bot: &mut RedHatBoy);
     fn draw(&self, renderer: &Renderer);
     fn move_horizontally(&mut self, x: i16);
     fn right(&self) -> i16;
+
+    // The highest surface under `caster` that it could stand on, if this obstacle has one.
+    fn surface_below(&self, _caster: &Rect) -> Option<i16> {
+        None
+    }
+
+    // Changes what any standable tops of this obstacle are made of.
+    fn set_surface(&mut self, _surface: Surface) {}
+
+    fn clone_box(&self) -> Box<dyn Obstacle>;
+
+    fn hitboxes(&self) -> Vec<Rect>;
+
+    // What has to be jumped over, for obstacles that can only be jumped.
+    fn jump_over(&self) -> Option<Rect> {
+        None
+    }
+
+    // Lets go of anything tied to it once it has scrolled out of the world.
+    fn despawn(&mut self) {}
+
+    // Moves it through its lifecycle after it has moved for the frame.
+    fn update_lifecycle(&mut self) {}
+
+    fn phase(&self) -> Phase {
+        if self.right() <= 0 {
+            Phase::Despawning
+        } else {
+            Phase::Active
+        }
+    }
+}
+
+impl Clone for Box<dyn Obstacle> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
 }
 
 impl Obstacle for Platform {
+    fn clone_box(&self) -> Box<dyn Obstacle> {
+        Box::new(self.clone())
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        self.bounding_boxes().clone()
+    }
+
     fn draw(&self, renderer: &Renderer) {
-        let mut x = 0;
-        self.sprites.iter().for_each(|sprite| {
-            self.sheet.draw(
-                renderer,
-                &Rect::new_from_x_y(
-                    sprite.frame.x,
-                    sprite.frame.y,
-                    sprite.frame.w,
-                    sprite.frame.h,
-                ),
-                &Rect::new_from_x_y(
+        self.lifecycle.draw(renderer, &self.bounds(), HEIGHT, || {
+            let mut x = 0;
+            self.sprites.iter().for_each(|sprite| {
+                let destination = Rect::new_from_x_y(
                     self.position.x + x,
                     self.position.y,
-                    sprite.frame.w,
-                    sprite.frame.h,
-                ),
-            );
-            x += sprite.frame.w;
-        })
+                    sprite.width,
+                    sprite.height(),
+                );
+                match &sprite.cell {
+                    Some(cell) => self.sheet.draw_tiled(renderer, cell, &destination),
+                    None => placeholder::draw_missing(renderer, &sprite.name, &destination),
+                }
+                x += sprite.width;
+            });
+            self.bounding_boxes
+                .iter()
+                .for_each(|top| self.surface.draw_strip(renderer, top));
+        });
+    }
+
+    fn update_lifecycle(&mut self) {
+        let bounds = self.bounds();
+        self.lifecycle.update(&bounds, WIDTH);
+    }
+
+    fn phase(&self) -> Phase {
+        self.lifecycle.phase()
     }
 
-    fn check_intersection(&self, boy: &mut RedHatBoy) {
+    fn check_intersection(&mut self, boy: &mut RedHatBoy) {
         if let Some(box_to_land_on) = self
             .bounding_boxes()
             .iter()
             .find(|&bounding_box| boy.bounding_box().intersects(bounding_box))
         {
             if boy.velocity_y() > 0 && boy.pos_y() < self.position.y {
-                boy.land_on(box_to_land_on.y());
+                boy.land_on(box_to_land_on.y(), self.surface);
             } else {
-                boy.knock_out();
+                boy.knock_out(DeathCause::Platform);
             }
         }
     }
@@ -719,6 +2015,22 @@ impl Obstacle for Platform {
             .right()
     }
 
+    fn surface_below(&self, caster: &Rect) -> Option<i16> {
+        self.bounding_boxes()
+            .iter()
+            .filter(|bounding_box| {
+                bounding_box.x() < caster.right()
+                    && bounding_box.right() > caster.x()
+                    && bounding_box.y() >= caster.y()
+            })
+            .map(Rect::y)
+            .min()
+    }
+
+    fn set_surface(&mut self, surface: Surface) {
+        self.surface = surface;
+    }
+
     fn move_horizontally(&mut self, x: i16) {
         self.position.x += x;
         self.bounding_boxes.iter_mut().for_each(|bounding_box| {
@@ -727,19 +2039,84 @@ impl Obstacle for Platform {
     }
 }
 
+// What a barrier is made of decides what running into it does.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Material {
+    Stone,
+    Wood,
+}
+
+impl Material {
+    fn death_cause(&self) -> DeathCause {
+        match self {
+            Material::Stone => DeathCause::Stone,
+            Material::Wood => DeathCause::Wood,
+        }
+    }
+}
+
+const WOOD_HIT_POINTS: u8 = 6;
+const WOOD_COLOR: Color = Color::rgba(150, 95, 45, 0.75);
+const PLANK_COLOR: Color = Color::rgb(90, 55, 25);
+const PLANKS: i16 = 3;
+
+#[derive(Clone)]
 pub struct Barrier {
     image: Image,
+    lifecycle: Lifecycle,
+    material: Material,
+    // Barriers with hit points lose one for every frame a dash pushes into
+    // them and break at none. The rest can only be jumped.
+    hit_points: Option<u8>,
 }
 
 impl Obstacle for Barrier {
-    fn check_intersection(&self, boy: &mut RedHatBoy) {
-        if boy.bounding_box().intersects(self.image.bounding_box()) {
-            boy.knock_out();
+    fn clone_box(&self) -> Box<dyn Obstacle> {
+        Box::new(self.clone())
+    }
+
+    fn hitboxes(&self) -> Vec<Rect> {
+        if self.broken() {
+            return vec![];
+        }
+        vec![*self.image.bounding_box()]
+    }
+
+    fn jump_over(&self) -> Option<Rect> {
+        (!self.broken()).then_some(*self.image.bounding_box())
+    }
+
+    fn check_intersection(&mut self, boy: &mut RedHatBoy) {
+        let boy_box = boy.bounding_box();
+        if self.broken()
+            || !boy_box.intersects(self.image.bounding_box())
+            || !self.image.overlaps(&boy_box)
+        {
+            return;
+        }
+        match self.hit_points.as_mut() {
+            Some(hit_points) if boy.dashing() => {
+                *hit_points = hit_points.saturating_sub(1);
+                if *hit_points == 0 {
+                    boy.smash();
+                }
+            }
+            // Dashes stop dead at anything they cannot break.
+            _ => boy.crash(self.material.death_cause()),
         }
     }
 
     fn draw(&self, renderer: &Renderer) {
-        self.image.draw(renderer)
+        if self.broken() {
+            return;
+        }
+        self.lifecycle
+            .draw(renderer, self.image.bounding_box(), HEIGHT, || {
+                self.image.draw(renderer);
+                if self.material == Material::Wood {
+                    self.draw_planks(renderer);
+                }
+            });
     }
 
     fn move_horizontally(&mut self, x: i16) {
@@ -747,13 +2124,58 @@ impl Obstacle for Barrier {
     }
 
     fn right(&self) -> i16 {
-        250
+        self.image.right()
+    }
+
+    fn update_lifecycle(&mut self) {
+        self.lifecycle.update(self.image.bounding_box(), WIDTH);
+    }
+
+    fn phase(&self) -> Phase {
+        self.lifecycle.phase()
     }
 }
 
 impl Barrier {
     pub fn new(image: Image) -> Self {
-        Self { image }
+        Self {
+            image,
+            lifecycle: Lifecycle::default(),
+            material: Material::Stone,
+            hit_points: None,
+        }
+    }
+
+    // A weak barrier that a dash can smash through.
+    pub fn wooden(image: Image) -> Self {
+        Self {
+            material: Material::Wood,
+            hit_points: Some(WOOD_HIT_POINTS),
+            ..Barrier::new(image)
+        }
+    }
+
+    fn broken(&self) -> bool {
+        self.hit_points == Some(0)
+    }
+
+    // Until there is art for it, wood is the stone stained brown with planks
+    // across it.
+    fn draw_planks(&self, renderer: &Renderer) {
+        let bounds = self.image.bounding_box();
+        renderer.fill_rect(bounds, &WOOD_COLOR);
+        (1..=PLANKS).for_each(|plank| {
+            let y = bounds.y() + bounds.height * plank / (PLANKS + 1);
+            renderer.fill_rect(
+                &Rect::new_from_x_y(bounds.x(), y, bounds.width, 2),
+                &PLANK_COLOR,
+            );
+        });
+    }
+
+    pub fn with_entrance(mut self, entrance: Entrance) -> Self {
+        self.lifecycle = Lifecycle::new(Some(entrance));
+        self
     }
 }
 
@@ -765,32 +2187,162 @@ pub fn rightmost(obstacle_list: &Vec<Box<dyn Obstacle>>) -> i16 {
         .unwrap_or(0)
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SheetRect {
-    x: i16,
-    y: i16,
-    w: i16,
-    h: i16,
+    pub x: i16,
+    pub y: i16,
+    pub w: i16,
+    pub h: i16,
 }
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Cell {
     pub frame: SheetRect,
+    #[serde(default)]
     pub sprite_source_size: SheetRect,
+    #[serde(default)]
+    pub rotated: bool,
+    #[serde(default)]
+    pub trimmed: bool,
+    #[serde(default)]
+    pub duration: Option<u16>,
+}
+
+impl Cell {
+    // Packers store rotated frames turned a quarter clockwise, so the region
+    // in the atlas has the frame's width and height swapped.
+    pub fn atlas_rect(&self) -> Rect {
+        if self.rotated {
+            Rect::new_from_x_y(self.frame.x, self.frame.y, self.frame.h, self.frame.w)
+        } else {
+            Rect::new_from_x_y(self.frame.x, self.frame.y, self.frame.w, self.frame.h)
+        }
+    }
+
+    pub fn destination(&self, position: &Point) -> Rect {
+        let (trim_x, trim_y) = if self.trimmed {
+            (self.sprite_source_size.x, self.sprite_source_size.y)
+        } else {
+            (0, 0)
+        };
+        Rect::new_from_x_y(
+            position.x + trim_x,
+            position.y + trim_y,
+            self.frame.w,
+            self.frame.h,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct FrameTag {
+    pub name: String,
+    pub frames: Vec<String>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Sheet {
     pub frames: HashMap<String, Cell>,
+    pub tags: Vec<FrameTag>,
 }
 
 pub struct Walk {
     boy: RedHatBoy,
-    backgrounds: [Image; 2],
+    background: Background,
+    critters: Critters,
+    day_cycle: DayCycle,
     obstacles: Vec<Box<dyn Obstacle>>,
     obstacle_sheet: Rc<SpriteSheet>,
-    stone: HtmlImageElement,
+    stone: MaskedImage,
+    timeline: i16,
+    target: Target,
+    story: Option<Story>,
+    // Extra segments for endless runs, from the packs the player loaded.
+    pack_segments: Rc<Vec<Segment>>,
+    distance: i32,
+    boss: BossEncounter,
+    config: GameConfig,
+    mode: GameMode,
+    elapsed_frames: u32,
+    rng: StdRng,
+    // What the rng was seeded with and the jumps since, so a score can be
+    // checked by playing the run again.
+    seed: u64,
+    replay: Replay,
+    dash_input: DashInput,
+    // Swapped for the dog's on sprints.
+    controller: Controller,
+    race: Option<Race>,
+    broadcast: Option<Broadcast>,
+    rewind: Rewind<WalkSnapshot>,
+    cheats: Cheats,
+    triggers: Vec<TriggerZone>,
+    force_fields: Vec<ForceField>,
+    environments: Vec<EnvironmentZone>,
+    swing_points: Vec<SwingPoint>,
+    swing: Option<Swing>,
+    zone_events: EventBus<ZoneEvent>,
+    despawn_events: EventBus<Despawn>,
+    entity_counts: EntityCounts,
+    zone_effects: ZoneEffects,
+    camera: Camera,
+    layers: Layers,
+    coins: Vec<Coin>,
+    coins_collected: u32,
+    gem_chains: Vec<GemChain>,
+    flash: Flash,
+    treats: Option<Treats>,
+    snowfall: Option<Snowfall>,
+    combo: Combo,
+    announcer: Announcer,
+    time_scale: TimeScale,
+    // Milliseconds each update lasts, as the game loop runs them.
+    tick: f64,
+    obstacles_cleared: u32,
+    telegraph: Telegraph,
+    // A shadow run checked against this one, with `?audit`.
+    audit: Option<Audit<Walk>>,
+    // Shadow runs keep checkpoints to themselves.
+    narrating: bool,
+    // Where the high road of the last branching section ends, while the
+    // generator is laying one.
+    lane: Option<i16>,
+    #[cfg(debug_assertions)]
+    save_state: Option<WalkSnapshot>,
+    #[cfg(debug_assertions)]
+    inspector: Inspector,
+    #[cfg(debug_assertions)]
+    segment_spans: Vec<SegmentSpan>,
+    #[cfg(debug_assertions)]
+    heatmap: Option<Heatmap>,
+}
+
+// Everything a rewind puts back. Images, sheets and sounds are shared handles,
+// so copying them is cheap.
+#[derive(Clone)]
+struct WalkSnapshot {
+    boy: BoySnapshot,
+    background: Background,
+    obstacles: Vec<Box<dyn Obstacle>>,
     timeline: i16,
+    distance: i32,
+    elapsed_frames: u32,
+    boss: BossEncounter,
+    rng: StdRng,
+    triggers: Vec<TriggerZone>,
+    force_fields: Vec<ForceField>,
+    environments: Vec<EnvironmentZone>,
+    swing_points: Vec<SwingPoint>,
+    swing: Option<Swing>,
+    controller: Controller,
+    zone_effects: ZoneEffects,
+    camera: Camera,
+    coins: Vec<Coin>,
+    coins_collected: u32,
+    gem_chains: Vec<GemChain>,
+    treats: Option<Treats>,
+    obstacles_cleared: u32,
+    lane: Option<i16>,
 }
 
 impl Walk {
@@ -799,103 +2351,2463 @@ impl Walk {
     }
 
     pub fn generate_next_segment(&mut self) {
-        let mut rng = thread_rng();
-        let next_segment = rng.gen_range(0..2);
-
-        let mut next_obstacles = match next_segment {
-            0 => stone_and_platform(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
+        if let Some(story) = self.story.as_mut().filter(|story| story.playing()) {
+            match story.next_segment() {
+                Some(segment) => self.lay_placed_segment(&segment),
+                None => story.place_goal(self.timeline + GOAL_BUFFER),
+            }
+            return;
+        }
+
+        match self.mode {
+            GameMode::Practice(kind) => {
+                let offset_x = self.timeline + OBSTACLE_BUFFER;
+                let next_obstacles =
+                    kind.build(self.stone.clone(), self.obstacle_sheet.clone(), offset_x);
+                self.swing_points.extend(kind.swing_points(offset_x));
+                self.lay_segment(kind.label(), next_obstacles);
+                return;
+            }
+            GameMode::BossRush => return,
+            GameMode::Endless | GameMode::TimeAttack | GameMode::Race => {}
+        }
+
+        if !self.config.hazards_only && self.generate_branch() {
+            return;
+        }
+
+        // Segments from packs are picked as often as each built in one, and
+        // runs without any draw from the rng just as they always have.
+        let next_segment = if self.config.hazards_only {
+            2
+        } else {
+            self.rng.gen_range(0..2 + self.pack_segments.len())
+        };
+        if let Some(segment) = next_segment
+            .checked_sub(2)
+            .filter(|_| !self.config.hazards_only)
+            .and_then(|index| self.pack_segments.get(index))
+            .cloned()
+        {
+            self.lay_placed_segment(&segment);
+            return;
+        }
+
+        let (label, next_obstacles) = match next_segment {
+            0 => (
+                SegmentKind::StoneAndPlatform.label(),
+                stone_and_platform(
+                    self.stone.clone(),
+                    self.obstacle_sheet.clone(),
+                    self.timeline + OBSTACLE_BUFFER,
+                ),
             ),
-            1 => platform_and_stone(
-                self.stone.clone(),
-                self.obstacle_sheet.clone(),
-                self.timeline + OBSTACLE_BUFFER,
+            1 => (
+                SegmentKind::PlatformAndStone.label(),
+                platform_and_stone(
+                    self.stone.clone(),
+                    self.obstacle_sheet.clone(),
+                    self.timeline + OBSTACLE_BUFFER,
+                ),
+            ),
+            2 => (
+                "Stones",
+                stones(self.stone.clone(), self.timeline + OBSTACLE_BUFFER),
             ),
-            _ => vec![],
+            _ => ("Nothing", vec![]),
         };
-        self.timeline = rightmost(&next_obstacles);
-        self.obstacles.append(&mut next_obstacles);
+        self.lay_segment(label, next_obstacles);
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        self.backgrounds.iter().for_each(|background| {
-            background.draw(renderer);
-        });
-        self.boy.draw(renderer);
-        self.obstacles.iter().for_each(|obstacle| {
-            obstacle.draw(renderer);
+    // Lays a segment from level or pack data, with everything placed on it.
+    fn lay_placed_segment(&mut self, segment: &Segment) {
+        let offset_x = self.timeline + OBSTACLE_BUFFER;
+        let next_obstacles =
+            segment.build(self.stone.clone(), self.obstacle_sheet.clone(), offset_x);
+        self.triggers.extend(segment.trigger_zones(offset_x));
+        self.force_fields.extend(segment.force_fields(offset_x));
+        self.environments
+            .extend(segment.environment_zones(offset_x));
+        self.swing_points.extend(segment.swing_points(offset_x));
+        self.lay_segment(segment.kind.label(), next_obstacles);
+    }
+
+    // Adds a generated segment's obstacles after the last ones. Debug builds
+    // also note where it lies, for the death heatmap.
+    fn lay_segment(&mut self, _label: &'static str, mut obstacles: Vec<Box<dyn Obstacle>>) {
+        #[cfg(debug_assertions)]
+        self.segment_spans.push(SegmentSpan {
+            label: _label,
+            start: self.distance + (self.timeline + OBSTACLE_BUFFER) as i32,
+            end: self.distance + rightmost(&obstacles) as i32,
         });
+        self.timeline = rightmost(&obstacles);
+        if !self.config.hazards_only {
+            self.lay_pickups(&obstacles);
+        }
+        self.obstacles.append(&mut obstacles);
     }
 
-    fn knocked_out(&self) -> bool {
-        self.boy.knocked_out()
+    // Now and then strings gems along the jump over something in a new
+    // segment. Jumps without them are offered to the season's collectible,
+    // which leaves the rng alone so seeded runs lay out the same all year.
+    fn lay_pickups(&mut self, obstacles: &[Box<dyn Obstacle>]) {
+        let envelope = JumpEnvelope::new(&self.boy.physics(), self.boy.walking_speed());
+        let boy = self.boy.bounding_box();
+        obstacles
+            .iter()
+            .filter_map(|obstacle| obstacle.jump_over())
+            .for_each(|obstacle| {
+                if self.rng.gen_ratio(1, GEM_CHAIN_ODDS) {
+                    self.gem_chains
+                        .extend(GemChain::over(&obstacle, &boy, &envelope));
+                } else if let Some(treats) = self.treats.as_mut() {
+                    if let Some(apex) = gems::jump_path(&obstacle, &boy, &envelope, 1)
+                        .and_then(|path| path.first().copied())
+                    {
+                        treats.offer(apex);
+                    }
+                }
+            });
     }
 
-    fn reset(walk: Self) -> Self {
-        let starting_obstacles =
-            stone_and_platform(walk.stone.clone(), walk.obstacle_sheet.clone(), 0);
-        let timeline = rightmost(&starting_obstacles);
+    // Lays the next stretch of a branching route, starting one now and then
+    // and otherwise carrying on from where the last left off. Returns false
+    // when a plain segment should come next.
+    fn generate_branch(&mut self) -> bool {
+        let branch = match self.lane {
+            Some(lane) => routes::next_branch(lane, &mut self.rng),
+            None if self.rng.gen_ratio(1, 3) => routes::next_branch(LOW_LANE, &mut self.rng),
+            None => None,
+        };
+        self.lane = branch.map(|branch| branch.exit);
+        let branch = match branch {
+            Some(branch) => branch,
+            None => return false,
+        };
 
-        Walk {
-            boy: RedHatBoy::reset(walk.boy),
-            backgrounds: walk.backgrounds,
-            obstacle_sheet: walk.obstacle_sheet,
-            obstacles: starting_obstacles,
-            stone: walk.stone,
-            timeline,
-        }
+        let lanes = branch.build(
+            self.stone.clone(),
+            self.obstacle_sheet.clone(),
+            self.timeline + OBSTACLE_BUFFER,
+        );
+        let label = if branch.entry == branch.exit {
+            "Branch"
+        } else {
+            "Branch step"
+        };
+        self.coins.extend(lanes.coins);
+        self.lay_segment(label, lanes.obstacles);
+        true
     }
-}
 
-pub struct WalkTheDog {
-    machine: Option<WalkTheDogStateMachine>,
+    // Ground height query for anything that casts a shadow or needs to know
+    // what it would land on.
+    pub fn ground_below(&self, caster: &Rect) -> i16 {
+        self.obstacles
+            .iter()
+            .filter_map(|obstacle| obstacle.surface_below(caster))
+            .min()
+            .unwrap_or(HEIGHT)
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        let mut draws = DrawList::new();
+        draws.push(Layer::Background, |renderer| {
+            self.background.draw_from(renderer, self.camera.y())
+        });
+        self.push_world_draws(&mut draws);
+        if let Some(snowfall) = &self.snowfall {
+            draws.push(Layer::Hud, |renderer| snowfall.draw(renderer));
+        }
+        draws.push(Layer::Hud, |renderer| self.draw_hud(renderer));
+        let camera = Point {
+            x: -self.zone_effects.camera_x(),
+            y: -self.camera.y(),
+        };
+        draws.draw(renderer, &self.layers, &camera);
+    }
+
+    fn draw_hud(&self, renderer: &Renderer) {
+        self.flash
+            .draw(renderer, &Rect::new_from_x_y(0, 0, WIDTH, HEIGHT));
+        self.zone_effects.draw(renderer);
+        let hud = renderer.hud();
+        if self.coins_collected > 0 {
+            if let Err(err) = renderer.draw_styled_text(
+                &format!("Coins: {}", self.coins_collected),
+                &hud.position(
+                    Anchor::TopRight,
+                    Point {
+                        x: HUD_MARGIN,
+                        y: COINS_Y,
+                    },
+                ),
+                &hud_style().with_align(Anchor::TopRight.align()),
+            ) {
+                log!("Could not draw coin count {:#?}", err);
+            }
+        }
+        if let Some(label) = self.treats.as_ref().and_then(Treats::label) {
+            if let Err(err) = renderer.draw_styled_text(
+                &label,
+                &hud.position(
+                    Anchor::TopRight,
+                    Point {
+                        x: HUD_MARGIN,
+                        y: TREATS_Y,
+                    },
+                ),
+                &hud_style().with_align(Anchor::TopRight.align()),
+            ) {
+                log!("Could not draw seasonal count {:#?}", err);
+            }
+        }
+        if self.boy.state_machine.name() != "Idle" {
+            self.boy.dash_meter().draw(
+                renderer,
+                hud.position(
+                    Anchor::TopRight,
+                    Point {
+                        x: HUD_MARGIN,
+                        y: DASH_METER_Y,
+                    },
+                ),
+            );
+        }
+        if let Some(limit) = self.mode.time_limit() {
+            let seconds_left = limit.saturating_sub(self.elapsed_frames) / 60;
+            if let Err(err) = renderer.draw_styled_text(
+                &format!("Time: {}", seconds_left),
+                &hud.position(
+                    Anchor::TopRight,
+                    Point {
+                        x: HUD_MARGIN,
+                        y: TIMER_Y,
+                    },
+                ),
+                &hud_style().with_align(Anchor::TopRight.align()),
+            ) {
+                log!("Could not draw timer {:#?}", err);
+            }
+        }
+        #[cfg(debug_assertions)]
+        {
+            self.entity_counts.draw(renderer);
+            self.track_memory(renderer);
+        }
+    }
+
+    // Feeds the counts the game owns to the leak overlay.
+    #[cfg(debug_assertions)]
+    fn track_memory(&self, renderer: &Renderer) {
+        memory::set(Tracked::Obstacles, self.obstacles.len());
+        let particles = if renderer.detail().particles {
+            wind::particle_count(&self.force_fields)
+                + self
+                    .environments
+                    .iter()
+                    .map(EnvironmentZone::particle_count)
+                    .sum::<usize>()
+        } else {
+            0
+        };
+        memory::set(Tracked::Particles, particles);
+    }
+
+    // Where a jump would take him past the obstacles, which come at him as
+    // fast as the world scrolls.
+    fn jump_preview(&self) -> Vec<Point> {
+        let scroll = if self.boss.scroll_locked() {
+            0
+        } else {
+            -self.zone_effects.walking_speed(self.velocity())
+        };
+        self.boy.jump_preview(scroll)
+    }
+
+    fn push_world_draws<'a>(&'a self, draws: &mut DrawList<'a>) {
+        draws.push(Layer::Midground, |renderer| {
+            self.critters.draw(renderer);
+            self.day_cycle.draw_lamps(renderer);
+            if let Some(story) = &self.story {
+                story.draw(renderer);
+            }
+        });
+        let boy = self.boy.bounding_box();
+        draws.push(Layer::Entities, move |renderer| {
+            engine::draw_shadow(renderer, &boy, self.ground_below(&boy));
+            if let Some(race) = &self.race {
+                race.rival().draw(renderer, boy.x(), self.distance);
+            }
+            let hands = self.boy.hands();
+            self.swing_points
+                .iter()
+                .for_each(|point| point.draw(renderer, &hands));
+            if let Some(swing) = &self.swing {
+                swing.draw(renderer);
+            }
+            self.boy.draw(renderer);
+            self.obstacles.iter().for_each(|obstacle| {
+                obstacle.draw(renderer);
+            });
+            self.coins.iter().for_each(|coin| coin.draw(renderer));
+            self.gem_chains
+                .iter()
+                .for_each(|chain| chain.draw(renderer));
+            if let Some(treats) = &self.treats {
+                treats.draw(renderer);
+            }
+            self.boss.draw(renderer);
+        });
+        if !self.boss.scroll_locked() {
+            draws.push(Layer::Fx, move |renderer| {
+                let jumpable: Vec<Rect> = self
+                    .obstacles
+                    .iter()
+                    .filter_map(|obstacle| obstacle.jump_over())
+                    .collect();
+                self.telegraph.draw(
+                    renderer,
+                    &jumpable,
+                    &boy,
+                    &self.boy.physics(),
+                    self.boy.walking_speed(),
+                );
+            });
+        }
+        draws.push(Layer::Fx, |renderer| {
+            let scroll_speed = if self.boss.scroll_locked() {
+                0
+            } else {
+                self.boy.walking_speed()
+            };
+            let warning_heights =
+                warnings::warning_heights(&self.boss.threats(), WIDTH, scroll_speed);
+            warnings::draw(renderer, &warning_heights, WIDTH, self.elapsed_frames);
+            self.day_cycle.draw_overlay(
+                renderer,
+                &Point {
+                    x: self.zone_effects.camera_x(),
+                    y: self.camera.y(),
+                },
+            );
+        });
+        draws.push(Layer::Fx, |renderer| {
+            self.environments
+                .iter()
+                .for_each(|zone| zone.draw(renderer, self.elapsed_frames));
+            wind::draw(renderer, &self.force_fields, self.elapsed_frames)
+        });
+        if matches!(self.mode, GameMode::Practice(_)) {
+            draws.push(Layer::Fx, |renderer| {
+                practice::draw_trajectory(renderer, &self.jump_preview())
+            });
+        }
+        if self.cheats.show_hitboxes {
+            draws.push(Layer::Fx, |renderer| self.draw_hitboxes(renderer));
+        }
+        #[cfg(debug_assertions)]
+        draws.push(Layer::Fx, |renderer| {
+            self.inspector
+                .draw_selection(renderer, &self.selected_boxes());
+            if let Some(heatmap) = &self.heatmap {
+                heatmap.draw(renderer, &self.segment_spans, self.distance);
+            }
+        });
+    }
+
+    fn draw_hitboxes(&self, renderer: &Renderer) {
+        renderer.stroke_rect(&self.boy.bounding_box(), &HITBOX_COLOR);
+        self.obstacles
+            .iter()
+            .flat_map(|obstacle| obstacle.hitboxes())
+            .for_each(|hitbox| renderer.stroke_rect(&hitbox, &HITBOX_COLOR));
+        self.triggers
+            .iter()
+            .for_each(|trigger| trigger.draw_outline(renderer));
+    }
+
+    fn reseed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.seed = seed;
+    }
+
+    fn run_link(&self) -> RunLink {
+        RunLink {
+            seed: self.seed,
+            config: self.config.clone(),
+            character: Character::RedHatBoy,
+        }
+    }
+
+    fn run_record(&self) -> RunRecord {
+        RunRecord {
+            seed: self.seed,
+            frames: self.elapsed_frames,
+            replay: self.replay.clone(),
+        }
+    }
+
+    // Both racers run the seed the host picked.
+    fn join_race(&mut self, race: Race, seed: u64) {
+        self.reseed(seed);
+        self.race = Some(race);
+    }
+
+    // Only plain endless runs are broadcast, since a spectator can rebuild
+    // those from the seed and mutators alone.
+    fn start_broadcast(&mut self) {
+        let plain = self.mode == GameMode::Endless && self.story.is_none() && !self.cheats.any();
+        if let Some(broadcast) = self.broadcast.as_mut().filter(|_| plain) {
+            broadcast.start(self.seed, &self.config);
+        }
+    }
+
+    // A fresh endless run matching the one being watched.
+    fn watch(mut walk: Self, seed: u64, config: GameConfig) -> Self {
+        *walk.boy.physics_mut() = config.physics;
+        walk.config = config;
+        walk.mode = GameMode::Endless;
+        walk.story = None;
+        let mut walk = Walk::reset(walk);
+        walk.reseed(seed);
+        walk
+    }
+
+    // Cheats reach the systems that check them from here.
+    fn set_cheats(&mut self, cheats: Cheats) {
+        if let Some(seed) = cheats
+            .fixed_seed
+            .filter(|seed| self.cheats.fixed_seed != Some(*seed))
+        {
+            self.rng = StdRng::seed_from_u64(seed);
+            self.seed = seed;
+        }
+        self.boy.set_invincible(cheats.invincible);
+        self.cheats = cheats;
+    }
+
+    // Holding the grab key in the air catches hold of any swing point in
+    // reach, and letting go flies off it. Landing or being knocked off ends
+    // the swing too.
+    fn hold_on(&mut self, grabbing: bool) {
+        if !self.boy.swinging() {
+            self.swing = None;
+        }
+        match &self.swing {
+            Some(swing) if !grabbing => {
+                self.boy.release(swing.velocity());
+                self.swing = None;
+            }
+            None if grabbing => {
+                let hands = self.boy.hands();
+                let anchor = self
+                    .swing_points
+                    .iter()
+                    .find(|point| point.in_reach(&hands))
+                    .map(SwingPoint::anchor);
+                self.swing = anchor.and_then(|anchor| self.boy.grab(anchor));
+            }
+            _ => {}
+        }
+    }
+
+    // Moves the world forward by one frame of play.
+    fn step(&mut self, keystate: &KeyState) {
+        // Spectators hit the same stops, so they get every frame, frozen or not.
+        let input = self.controller.read(keystate, &mut self.dash_input);
+        if let Some(broadcast) = &mut self.broadcast {
+            broadcast.step(input);
+        }
+        if !self.time_scale.should_step() {
+            return;
+        }
+
+        if self.rewind.snapshot_due() {
+            let snapshot = self.snapshot();
+            self.rewind.record(snapshot);
+        }
+
+        let boy = self.boy.bounding_box();
+        let environment = self.environments.iter().find(|zone| zone.contains(&boy));
+        let overrides = environment.map(|zone| zone.physics).unwrap_or_default();
+        let overrides = match self.controller.sprint() {
+            Some(sprint) => sprint.overrides(overrides, self.boy.physics()),
+            None => overrides,
+        };
+        self.boy.enter_environment(overrides);
+        let drag = environment.map_or(0, |zone| zone.drag);
+
+        self.replay.record(self.elapsed_frames, input);
+        if input.jumped {
+            self.boy.jump();
+        }
+        if input.dashed {
+            self.boy.dash();
+        }
+
+        self.boy.update();
+        self.hold_on(input.grabbing);
+
+        let walking_speed = if self.boss.scroll_locked() {
+            0
+        } else {
+            self.zone_effects.walking_speed(self.velocity())
+        };
+        let walking_speed = self
+            .boy
+            .feel_forces(physics::slow(walking_speed, drag), &self.force_fields);
+        let walking_speed = self.boy.momentum(walking_speed);
+        let walking_speed = self
+            .controller
+            .sprint()
+            .map_or(walking_speed, |sprint| sprint.walking_speed(walking_speed));
+        // Swinging carries him along at whatever speed the swing is going.
+        let walking_speed = match &mut self.swing {
+            Some(swing) => {
+                let (scroll, y) = swing.update(physics::mean_gravity(&self.boy.physics_here()));
+                self.boy.swing_to(y, swing.velocity());
+                scroll
+            }
+            None => walking_speed,
+        };
+        self.distance -= walking_speed as i32;
+        self.elapsed_frames += 1;
+        self.controller.update(walking_speed);
+        if let Some(race) = &mut self.race {
+            race.update();
+            race.send_position(self.distance, self.boy.bounding_box().y());
+        }
+        self.background.update(walking_speed);
+        self.critters.update(walking_speed, self.tick);
+        self.day_cycle.update(walking_speed);
+        if let Some(story) = &mut self.story {
+            story.update(walking_speed);
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            let removed: Vec<bool> = self
+                .obstacles
+                .iter()
+                .map(|obstacle| obstacle.phase() == Phase::Despawning)
+                .collect();
+            self.inspector.obstacles_removed(&removed);
+        }
+        let mut removed = entities::despawn(
+            &mut self.obstacles,
+            EntityKind::Obstacle,
+            &mut self.despawn_events,
+            |obstacle| obstacle.phase() == Phase::Despawning,
+        );
+        self.obstacles_cleared += removed
+            .iter()
+            .filter(|obstacle| obstacle.jump_over().is_some())
+            .count() as u32;
+        removed.iter_mut().for_each(|obstacle| obstacle.despawn());
+
+        let noclip = self.cheats.noclip;
+        self.obstacles.iter_mut().for_each(|obstacle| {
+            obstacle.move_horizontally(walking_speed);
+            obstacle.update_lifecycle();
+            if !noclip {
+                obstacle.check_intersection(&mut self.boy);
+            }
+        });
+        // Smashing through something keeps a combo going like a coin does.
+        if self.boy.smashed() {
+            if let Some(milestone) = self.combo.extend(1) {
+                self.announcer.announce(milestone);
+            }
+        }
+
+        let missed = entities::despawn(
+            &mut self.coins,
+            EntityKind::Coin,
+            &mut self.despawn_events,
+            |coin| coin.right() <= 0,
+        );
+        if !missed.is_empty() {
+            self.combo.break_off();
+        }
+        self.coins
+            .iter_mut()
+            .for_each(|coin| coin.move_horizontally(walking_speed));
+        let collected = coins::collect(&mut self.coins, &self.boy.bounding_box());
+        self.coins_collected += collected;
+        if let Some(milestone) = self.combo.extend(collected) {
+            self.announcer.announce(milestone);
+        }
+
+        self.gem_chains.retain(|chain| chain.right() > 0);
+        let (boy, airborne) = (self.boy.bounding_box(), self.boy.airborne());
+        self.flash.update();
+        self.gem_chains.iter_mut().for_each(|chain| {
+            chain.move_horizontally(walking_speed);
+            let progress = chain.collect(&boy, airborne);
+            self.coins_collected += progress.collected + progress.bonus;
+            if progress.bonus > 0 {
+                self.flash.start();
+            }
+        });
+        if let Some(treats) = self.treats.as_mut() {
+            treats.update(walking_speed, &boy);
+        }
+        if let Some(snowfall) = self.snowfall.as_mut() {
+            snowfall.update(walking_speed);
+        }
+        self.announcer.update(self.boy.audio());
+
+        entities::despawn(
+            &mut self.triggers,
+            EntityKind::Trigger,
+            &mut self.despawn_events,
+            |trigger| trigger.right() <= 0,
+        );
+        let boy = self.boy.bounding_box();
+        self.triggers.iter_mut().for_each(|trigger| {
+            trigger.move_horizontally(walking_speed);
+            trigger.update(&boy, &mut self.zone_events);
+        });
+        self.force_fields
+            .iter_mut()
+            .for_each(|field| field.move_horizontally(walking_speed));
+        self.force_fields.retain(|field| field.right() > 0);
+        self.environments
+            .iter_mut()
+            .for_each(|zone| zone.move_horizontally(walking_speed));
+        self.environments.retain(|zone| zone.right() > 0);
+        self.swing_points
+            .iter_mut()
+            .for_each(|point| point.move_horizontally(walking_speed));
+        self.swing_points.retain(|point| point.right() > 0);
+        if let Some(swing) = &mut self.swing {
+            swing.move_horizontally(walking_speed);
+        }
+        let distance = self.distance;
+        self.zone_events.drain().iter().for_each(|event| {
+            if *event == ZoneEvent::Entered(TriggerKind::Checkpoint) && self.narrating {
+                narration::announce(&self.target, &narration::checkpoint(distance));
+            }
+            self.zone_effects.handle(event, distance)
+        });
+        if let Some(distance) = self.zone_effects.take_sprint() {
+            self.controller.mount(distance);
+        }
+        // Anything that knocks him out knocks him off the dog too.
+        if self.boy.death_cause.is_some() {
+            self.controller.dismount();
+        }
+        self.boy.ride(self.controller.sprint());
+        self.zone_effects.update();
+        self.camera.follow(&self.boy.bounding_box());
+
+        // Platforms only catch him while he overlaps them, so running past an
+        // edge has to be noticed here.
+        if let Some(support) = self.boy.support() {
+            if self.ground_below(&self.boy.bounding_box()) != support {
+                self.boy.fall_off();
+            }
+        }
+
+        if self.boss.update(self.distance, &mut self.boy) {
+            self.boy.shield(REWARD_SHIELD_FRAMES);
+        }
+        if self.boy.just_struck() || self.boss.stomped() {
+            self.time_scale.hit_stop(HIT_STOP_FRAMES);
+        }
+
+        self.count_entities();
+        if self.timeline < TIMELINE_MINIMUM && !self.entity_counts.full() {
+            self.generate_next_segment();
+        } else {
+            self.timeline += walking_speed;
+        }
+        // Kept with the replay and sent to spectators, to find where playing
+        // the run again stops matching.
+        if self.elapsed_frames.is_multiple_of(HASH_INTERVAL) {
+            let hash = self.world_hash().short();
+            self.replay.record_hash(self.elapsed_frames, hash);
+            if let Some(broadcast) = &mut self.broadcast {
+                broadcast.checkpoint(self.elapsed_frames, hash);
+            }
+        }
+    }
+
+    fn count_entities(&mut self) {
+        let despawns = self.despawn_events.drain();
+        self.entity_counts.record_despawns(&despawns);
+        [
+            (EntityKind::Obstacle, self.obstacles.len()),
+            (EntityKind::Coin, self.coins.len()),
+            (EntityKind::Trigger, self.triggers.len()),
+        ]
+        .into_iter()
+        .filter(|(kind, live)| self.entity_counts.record(*kind, *live))
+        .for_each(|(kind, live)| {
+            log!(
+                "{} {} alive, over the cap of {}",
+                live,
+                kind.name(),
+                kind.cap()
+            );
+        });
+    }
+
+    fn snapshot(&self) -> WalkSnapshot {
+        WalkSnapshot {
+            boy: self.boy.snapshot(),
+            background: self.background.clone(),
+            obstacles: self.obstacles.clone(),
+            timeline: self.timeline,
+            distance: self.distance,
+            elapsed_frames: self.elapsed_frames,
+            boss: self.boss.clone(),
+            rng: self.rng.clone(),
+            triggers: self.triggers.clone(),
+            force_fields: self.force_fields.clone(),
+            environments: self.environments.clone(),
+            swing_points: self.swing_points.clone(),
+            swing: self.swing.clone(),
+            controller: self.controller.clone(),
+            zone_effects: self.zone_effects.clone(),
+            camera: self.camera,
+            coins: self.coins.clone(),
+            coins_collected: self.coins_collected,
+            gem_chains: self.gem_chains.clone(),
+            treats: self.treats.clone(),
+            obstacles_cleared: self.obstacles_cleared,
+            lane: self.lane,
+        }
+    }
+
+    fn restore(&mut self, snapshot: WalkSnapshot) {
+        self.boy.restore(snapshot.boy);
+        self.background = snapshot.background;
+        self.obstacles = snapshot.obstacles;
+        self.timeline = snapshot.timeline;
+        self.distance = snapshot.distance;
+        self.elapsed_frames = snapshot.elapsed_frames;
+        self.replay.truncate(self.elapsed_frames);
+        self.boss = snapshot.boss;
+        self.rng = snapshot.rng;
+        self.triggers = snapshot.triggers;
+        self.force_fields = snapshot.force_fields;
+        self.environments = snapshot.environments;
+        self.swing_points = snapshot.swing_points;
+        self.swing = snapshot.swing;
+        self.controller = snapshot.controller;
+        self.boy.ride(self.controller.sprint());
+        self.zone_effects = snapshot.zone_effects;
+        self.camera = snapshot.camera;
+        self.coins = snapshot.coins;
+        self.coins_collected = snapshot.coins_collected;
+        self.gem_chains = snapshot.gem_chains;
+        self.treats = snapshot.treats;
+        self.flash = Flash::default();
+        self.obstacles_cleared = snapshot.obstacles_cleared;
+        self.combo.break_off();
+        self.time_scale = TimeScale::default();
+        self.lane = snapshot.lane;
+        if let Some(audit) = &mut self.audit {
+            audit.forget();
+        }
+    }
+
+    // A muted copy of everything the inputs act on, to step alongside this
+    // walk in a determinism audit. Scenery that never feeds back into play,
+    // and links to other players, are left out.
+    fn shadow(&self) -> Walk {
+        let mut boy = self.boy.clone();
+        boy.mute();
+        Walk {
+            boy,
+            background: self.background.clone(),
+            critters: Critters::new(&[], 0),
+            day_cycle: DayCycle::new(),
+            obstacles: self.obstacles.clone(),
+            obstacle_sheet: self.obstacle_sheet.clone(),
+            stone: self.stone.clone(),
+            timeline: self.timeline,
+            target: self.target.clone(),
+            story: None,
+            pack_segments: self.pack_segments.clone(),
+            distance: self.distance,
+            boss: self.boss.clone(),
+            config: self.config.clone(),
+            mode: self.mode,
+            elapsed_frames: self.elapsed_frames,
+            rng: self.rng.clone(),
+            seed: self.seed,
+            replay: self.replay.clone(),
+            dash_input: self.dash_input.clone(),
+            controller: self.controller.clone(),
+            race: None,
+            broadcast: None,
+            rewind: Rewind::new(),
+            cheats: self.cheats,
+            triggers: self.triggers.clone(),
+            force_fields: self.force_fields.clone(),
+            environments: self.environments.clone(),
+            swing_points: self.swing_points.clone(),
+            swing: self.swing.clone(),
+            zone_events: EventBus::new(),
+            despawn_events: EventBus::new(),
+            entity_counts: self.entity_counts,
+            zone_effects: self.zone_effects.clone(),
+            camera: self.camera,
+            layers: self.layers,
+            coins: self.coins.clone(),
+            coins_collected: self.coins_collected,
+            gem_chains: self.gem_chains.clone(),
+            flash: self.flash,
+            treats: self.treats.clone(),
+            snowfall: None,
+            combo: self.combo,
+            announcer: Announcer::default(),
+            time_scale: self.time_scale.clone(),
+            tick: self.tick,
+            obstacles_cleared: self.obstacles_cleared,
+            telegraph: self.telegraph,
+            audit: None,
+            narrating: false,
+            lane: self.lane,
+            #[cfg(debug_assertions)]
+            save_state: None,
+            #[cfg(debug_assertions)]
+            inspector: Inspector::new(),
+            #[cfg(debug_assertions)]
+            segment_spans: vec![],
+            #[cfg(debug_assertions)]
+            heatmap: None,
+        }
+    }
+
+    // Everything that decides how the run goes from here, for checking one
+    // run against another. The rng is sampled from a copy so hashing leaves
+    // it alone.
+    fn world_hash(&self) -> WorldHash {
+        let context = self.boy.state_machine.context();
+        let boy = self.boy.bounding_box();
+        WorldHash {
+            positions: StateHasher::digest(|hasher| {
+                [boy.x(), boy.y(), self.timeline]
+                    .into_iter()
+                    .for_each(|value| hasher.write_i16(value));
+                hasher.write_i32(self.distance);
+                self.coins
+                    .iter()
+                    .for_each(|coin| hasher.write_i16(coin.right()));
+            }),
+            velocities: StateHasher::digest(|hasher| {
+                hasher.write_i16(context.velocity().x);
+                hasher.write_i16(context.velocity().y);
+                hasher.write_i16(self.boy.walking_speed());
+            }),
+            rng: StateHasher::digest(|hasher| hasher.write_u64(self.rng.clone().gen())),
+            obstacles: StateHasher::digest(|hasher| {
+                self.obstacles.iter().for_each(|obstacle| {
+                    hasher.write_i16(obstacle.right());
+                    obstacle.hitboxes().iter().for_each(|hitbox| {
+                        hasher.write_i16(hitbox.y());
+                        hasher.write_i16(hitbox.height);
+                    });
+                });
+            }),
+            progress: StateHasher::digest(|hasher| {
+                hasher.write(self.boy.state_machine.frame_name().as_bytes());
+                hasher.write_u64(context.elapsed().to_bits());
+                [
+                    self.boy.jumps,
+                    self.elapsed_frames,
+                    self.coins_collected,
+                    self.obstacles_cleared,
+                ]
+                .into_iter()
+                .for_each(|value| hasher.write_u32(value));
+            }),
+        }
+    }
+
+    // Runs after every step of a live run being audited. The shadow starts as
+    // a copy of how things stand, then gets the same keys from the next frame.
+    // Only plain endless runs are audited, as those come from the seed alone.
+    fn step_shadow(&mut self, keystate: &KeyState) {
+        let plain = self.mode == GameMode::Endless
+            && !self.story.as_ref().map_or(false, |story| story.playing());
+        if !plain {
+            return;
+        }
+        let Some(mut audit) = self.audit.take() else {
+            return;
+        };
+        let due = audit.due(self.elapsed_frames);
+        match audit.shadow_mut() {
+            Some(shadow) => {
+                shadow.set_tick(self.tick);
+                shadow.step(keystate);
+                if due {
+                    audit::check(
+                        self.elapsed_frames,
+                        &self.world_hash(),
+                        &shadow.world_hash(),
+                    );
+                }
+            }
+            None => audit.follow(self.shadow()),
+        }
+        self.audit = Some(audit);
+    }
+
+    // Development only: F5 keeps a snapshot of the walk and F9 jumps back to it,
+    // for retrying one jump without replaying the whole run.
+    #[cfg(debug_assertions)]
+    fn handle_save_state_keys(&mut self, keystate: &KeyState) {
+        if keystate.is_pressed("F5") {
+            self.save_state = Some(self.snapshot());
+        }
+        if keystate.is_pressed("F9") {
+            if let Some(snapshot) = self.save_state.clone() {
+                self.restore(snapshot);
+            }
+        }
+    }
+
+    // Development only: selects whatever was clicked and nudges the selection
+    // while the nudge keys are held.
+    #[cfg(debug_assertions)]
+    fn inspect(&mut self, keystate: &KeyState) {
+        let boy = self.boy.bounding_box();
+        let obstacles: Vec<Vec<Rect>> = self
+            .obstacles
+            .iter()
+            .map(|obstacle| obstacle.hitboxes())
+            .collect();
+        keystate.clicks().iter().for_each(|click| {
+            // Clicks land on the mirrored, panned picture, so they are flipped
+            // and shifted back into world coordinates.
+            let click = if self.config.mirrored {
+                Point {
+                    x: WIDTH - click.x,
+                    y: click.y,
+                }
+            } else {
+                *click
+            };
+            let click = Point {
+                x: click.x + self.zone_effects.camera_x(),
+                y: click.y + self.camera.y(),
+            };
+            self.inspector.select(&click, &boy, &obstacles);
+        });
+
+        match (self.inspector.selection(), self.inspector.nudge(keystate)) {
+            (Some(Selection::Boy), Some(nudge)) => self.boy.nudge(nudge),
+            (Some(Selection::Obstacle(index)), Some(Nudge::Position(offset))) => {
+                if let Some(obstacle) = self.obstacles.get_mut(index) {
+                    obstacle.move_horizontally(offset.x);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Development only: plays `runs` seeded runs on from the current point
+    // with a simple jumping bot, and records which segment each one died in
+    // and how far into it. The walk is put back as it was afterwards.
+    #[cfg(debug_assertions)]
+    fn simulate_deaths(&mut self, runs: u64) -> Heatmap {
+        const MAX_FRAMES: u32 = 60 * 60;
+        let start = self.snapshot();
+        let cheats = self.cheats;
+        let spans = self.segment_spans.len();
+        let rewind = std::mem::replace(&mut self.rewind, Rewind::new());
+        let race = self.race.take();
+        let broadcast = self.broadcast.take();
+        self.set_cheats(Cheats::default());
+
+        let running = KeyState::holding(&[]);
+        let jumping = KeyState::holding(&["Space"]);
+        let mut heatmap = Heatmap::default();
+        (0..runs).for_each(|seed| {
+            self.restore(start.clone());
+            self.segment_spans.truncate(spans);
+            self.rng = StdRng::seed_from_u64(seed);
+            self.boy.mute();
+            // Each run reacts at its own distance, so deaths spread out the
+            // way different players' would.
+            let lookahead = self.rng.gen_range(BOT_MIN_LOOKAHEAD..BOT_MAX_LOOKAHEAD);
+            let died = (0..MAX_FRAMES).any(|_| {
+                let keystate = if self.bot_should_jump(lookahead) {
+                    &jumping
+                } else {
+                    &running
+                };
+                self.step(keystate);
+                self.boy.death_cause.is_some()
+            });
+            let boy_x = self.distance + self.boy.bounding_box().x() as i32;
+            match heatmap::site(&self.segment_spans, boy_x) {
+                Some((label, offset)) if died => heatmap.record_death(label, offset),
+                None if died => heatmap.record_death("Start", 0),
+                _ => heatmap.record_survival(),
+            }
+        });
+
+        self.restore(start);
+        self.segment_spans.truncate(spans);
+        self.rewind = rewind;
+        self.race = race;
+        self.broadcast = broadcast;
+        self.set_cheats(cheats);
+        heatmap
+    }
+
+    // A simple player for simulations and smoke tests: jumps whenever
+    // something is within `lookahead` pixels in front of the boy.
+    #[cfg(any(test, debug_assertions))]
+    fn bot_should_jump(&self, lookahead: i16) -> bool {
+        let boy = self.boy.bounding_box();
+        self.obstacles
+            .iter()
+            .flat_map(|obstacle| obstacle.hitboxes())
+            .any(|hitbox| {
+                hitbox.x() >= boy.right()
+                    && hitbox.x() < boy.right() + lookahead
+                    && hitbox.y() < boy.bottom()
+                    && hitbox.bottom() > boy.y()
+            })
+    }
+
+    #[cfg(debug_assertions)]
+    fn selected_boxes(&self) -> Vec<Rect> {
+        match self.inspector.selection() {
+            Some(Selection::Boy) => vec![self.boy.destination_box(), self.boy.bounding_box()],
+            Some(Selection::Obstacle(index)) => self
+                .obstacles
+                .get(index)
+                .map_or(vec![], |obstacle| obstacle.hitboxes()),
+            None => vec![],
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn draw_inspector_panel(&self, renderer: &Renderer) {
+        let properties = match self.inspector.selection() {
+            Some(Selection::Boy) => self.boy.inspect(),
+            Some(Selection::Obstacle(index)) => match self.obstacles.get(index) {
+                Some(obstacle) => std::iter::once(format!("Obstacle {}", index))
+                    .chain(std::iter::once(format!("Right: {}", obstacle.right())))
+                    .chain(
+                        obstacle
+                            .hitboxes()
+                            .iter()
+                            .map(|hitbox| inspector::describe_box("Hitbox", hitbox)),
+                    )
+                    .collect(),
+                None => return,
+            },
+            None => return,
+        };
+        let world = format!(
+            "World: {} (frame {})",
+            self.world_hash(),
+            self.elapsed_frames
+        );
+        let properties: Vec<String> = std::iter::once(world).chain(properties).collect();
+        self.inspector.draw_panel(renderer, &properties);
+    }
+
+    // Story courses track their own progress, which snapshots leave out.
+    fn can_rewind(&self) -> bool {
+        self.rewind.available() && !self.story.as_ref().map_or(false, |story| story.playing())
+    }
+
+    fn time_up(&self) -> bool {
+        self.mode
+            .time_limit()
+            .map_or(false, |limit| self.elapsed_frames >= limit)
+    }
+
+    fn knocked_out(&self) -> bool {
+        self.boy.knocked_out()
+    }
+
+    fn goal_reached(&self) -> bool {
+        self.story
+            .as_ref()
+            .map_or(false, |story| story.goal_reached(&self.boy.bounding_box()))
+    }
+
+    fn reset(mut walk: Self) -> Self {
+        walk.restart();
+        walk
+    }
+
+    // How long the next updates last, so animations keep their pace
+    // whatever rate the game loop runs at.
+    fn set_tick(&mut self, delta: f64) {
+        if delta != self.tick {
+            audit::flag(Source::FrameTime);
+        }
+        self.tick = delta;
+        self.boy.set_tick(delta);
+    }
+
+    // Starts the run over in place. The boy, the images and the sounds are
+    // kept as they are, and the lists the world is built from keep their
+    // room, so a restart allocates little more than the first obstacles.
+    fn restart(&mut self) {
+        memory::restarted();
+        let starting_segment = self.story.as_mut().and_then(|story| {
+            story.restart();
+            story.next_segment()
+        });
+        self.triggers.clear();
+        self.force_fields.clear();
+        self.environments.clear();
+        self.swing_points.clear();
+        self.swing = None;
+        self.obstacles.clear();
+        match starting_segment {
+            Some(segment) => {
+                self.triggers.extend(segment.trigger_zones(0));
+                self.force_fields.extend(segment.force_fields(0));
+                self.environments.extend(segment.environment_zones(0));
+                self.swing_points.extend(segment.swing_points(0));
+                self.obstacles.extend(segment.build(
+                    self.stone.clone(),
+                    self.obstacle_sheet.clone(),
+                    0,
+                ));
+            }
+            None => self.obstacles.extend(match self.mode {
+                GameMode::Practice(kind) => {
+                    self.swing_points.extend(kind.swing_points(0));
+                    kind.build(self.stone.clone(), self.obstacle_sheet.clone(), 0)
+                }
+                GameMode::BossRush => vec![],
+                GameMode::Endless | GameMode::TimeAttack | GameMode::Race => {
+                    stone_and_platform(self.stone.clone(), self.obstacle_sheet.clone(), 0)
+                }
+            }),
+        }
+        self.timeline = rightmost(&self.obstacles);
+        let cheats = self.cheats;
+        self.seed = cheats.fixed_seed.unwrap_or_else(|| self.rng.gen());
+        self.rng = StdRng::seed_from_u64(self.seed);
+
+        self.boy.restart();
+        self.day_cycle = DayCycle::new();
+        self.distance = 0;
+        self.boss = self.mode.boss_encounter();
+        self.elapsed_frames = 0;
+        self.replay = Replay::default();
+        self.dash_input = DashInput::default();
+        self.controller = Controller::default();
+        self.race = None;
+        self.rewind = Rewind::new();
+        self.zone_events = EventBus::new();
+        self.despawn_events = EventBus::new();
+        self.entity_counts = EntityCounts::default();
+        self.zone_effects = ZoneEffects::default();
+        self.camera = Camera::new();
+        self.coins.clear();
+        self.coins_collected = 0;
+        self.gem_chains.clear();
+        self.flash = Flash::default();
+        if let Some(treats) = self.treats.as_mut() {
+            treats.clear();
+        }
+        self.combo = Combo::default();
+        self.time_scale = TimeScale::default();
+        self.obstacles_cleared = 0;
+        self.telegraph = Telegraph::new(&RunHistory::load());
+        self.lane = None;
+        #[cfg(debug_assertions)]
+        {
+            self.inspector = Inspector::new();
+            self.segment_spans.clear();
+        }
+        self.cheats = Cheats::default();
+        self.set_cheats(cheats);
+        if let Some(audit) = &mut self.audit {
+            audit.forget();
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+const STATE_GRAPH_FILE: &str = "state_machines.dot";
+
+// Playtesting commands for the developer console.
+#[cfg(debug_assertions)]
+fn console_commands() -> CommandRegistry<Walk> {
+    let mut registry = CommandRegistry::new();
+    registry.register("spawn", |walk: &mut Walk, args: &[&str]| {
+        let x: i16 = console::arg(args, 1, "x")?;
+        let obstacle = match args.first().copied() {
+            Some("barrier") => segments::barrier(walk.stone.clone(), x),
+            Some("wood") => segments::wooden_barrier(walk.stone.clone(), x),
+            Some("platform") => segments::floating_platform(walk.obstacle_sheet.clone(), x),
+            _ => return Err(anyhow!("Usage: spawn barrier|wood|platform <x>")),
+        };
+        walk.obstacles.push(obstacle);
+        Ok(format!("Spawned {} at {}", args[0], x))
+    });
+    registry.register("set", |walk: &mut Walk, args: &[&str]| {
+        let value: i16 = console::arg(args, 1, "value")?;
+        let physics = walk.boy.physics_mut();
+        match args.first().copied() {
+            Some("gravity") => physics.gravity = value,
+            Some("jump_speed") => physics.jump_speed = value,
+            Some("terminal_velocity") => physics.terminal_velocity = value,
+            _ => {
+                return Err(anyhow!(
+                    "Usage: set gravity|jump_speed|terminal_velocity <value>"
+                ))
+            }
+        }
+        Ok(format!("{} is {}", args[0], value))
+    });
+    registry.register("cheat", |walk: &mut Walk, args: &[&str]| {
+        let name = args
+            .first()
+            .ok_or_else(|| anyhow!("Usage: cheat <name> [on|off|value]"))?;
+        let mut cheats = walk.cheats;
+        cheats.set(name, args.get(1).copied())?;
+        walk.set_cheats(cheats);
+        Ok(format!("{:?}", cheats))
+    });
+    registry.register("heatmap", |walk: &mut Walk, args: &[&str]| {
+        if args.first() == Some(&"clear") {
+            walk.heatmap = None;
+            return Ok("Heatmap cleared".to_string());
+        }
+        if walk.story.as_ref().map_or(false, |story| story.playing()) {
+            return Err(anyhow!("Story courses cannot be simulated"));
+        }
+        let runs: u64 = console::arg(args, 0, "runs")?;
+        let heatmap = walk.simulate_deaths(runs);
+        let export = heatmap.export();
+        log!("Death heatmap\n{}", export);
+        walk.heatmap = Some(heatmap);
+        Ok(export)
+    });
+    registry.register("graph", |walk: &mut Walk, _args: &[&str]| {
+        let dot = state_graph::to_dot(&[
+            walk.boy.transition_graph(),
+            WalkTheDogStateMachine::transition_graph(),
+        ]);
+        browser::download(STATE_GRAPH_FILE, "text/vnd.graphviz", &dot)?;
+        Ok(format!("Saved {}", STATE_GRAPH_FILE))
+    });
+    registry.register("replay", |walk: &mut Walk, args: &[&str]| {
+        match args.first() {
+            Some(code) => {
+                let record = RunRecord::from_code(code)?;
+                Ok(format!(
+                    "Seed {}, {} frames, {} jumps",
+                    record.seed,
+                    record.frames,
+                    record.replay.jumps().len()
+                ))
+            }
+            None => {
+                let record = walk.run_record();
+                Ok(format!(
+                    "{} bytes: {}",
+                    record.encode().len(),
+                    record.to_code()?
+                ))
+            }
+        }
+    });
+    registry.register("layer", |walk: &mut Walk, args: &[&str]| {
+        let layer = args
+            .first()
+            .and_then(|name| Layer::from_name(name))
+            .ok_or_else(|| {
+                anyhow!("Usage: layer background|midground|entities|fx|hud on|off|<z>")
+            })?;
+        let value = args.get(1).ok_or_else(|| anyhow!("Missing on|off|z"))?;
+        walk.layers.set(layer, value)?;
+        Ok(format!(
+            "{} z {} {}",
+            layer.name(),
+            walk.layers.z(layer),
+            if walk.layers.visible(layer) {
+                "on"
+            } else {
+                "off"
+            }
+        ))
+    });
+    registry.register("wear", |walk: &mut Walk, args: &[&str]| {
+        if args.first() == Some(&"off") {
+            walk.boy.outfit_mut().take_off_all();
+            return Ok("Took everything off".to_string());
+        }
+        let (anchor, sprite) = match args {
+            [anchor, sprite, ..] => (anchor.to_string(), sprite.to_string()),
+            _ => return Err(anyhow!("Usage: wear <anchor> <sprite> [x y] | wear off")),
+        };
+        let offset = Offset {
+            x: console::arg(args, 2, "x").unwrap_or(0),
+            y: console::arg(args, 3, "y").unwrap_or(0),
+        };
+        let atlas = walk.obstacle_sheet.clone();
+        walk.boy.outfit_mut().wear(
+            Attachment {
+                anchor,
+                sprite,
+                offset,
+            },
+            &atlas,
+        )?;
+        Ok(format!("Wearing {} on {}", args[1], args[0]))
+    });
+    registry.register("goto", |walk: &mut Walk, args: &[&str]| {
+        let distance = args
+            .first()
+            .map_or("", |distance| distance.trim_end_matches('m'));
+        walk.distance = console::arg(&[distance], 0, "distance")?;
+        Ok(format!("Moved to {}m", walk.distance))
+    });
+    registry
+}
+
+// From `?tick_rate=30|60|120`. Run links always play at the default rate so
+// they stay the same run for everyone.
+fn tick_rate_from_browser() -> Result<TickRate> {
+    Ok(browser::query_param("tick_rate")?
+        .and_then(|name| {
+            let tick_rate = TickRate::from_name(&name);
+            if tick_rate.is_none() {
+                log!("Ignoring unknown tick rate {}", name);
+            }
+            tick_rate
+        })
+        .unwrap_or_default())
+}
+
+pub struct WalkTheDog {
+    machine: Option<WalkTheDogStateMachine>,
+    captions: Option<Captions>,
+    target: Target,
+    config: GameConfig,
+    #[cfg(debug_assertions)]
+    console: Console<Walk>,
+    #[cfg(debug_assertions)]
+    hitbox_editor: HitboxEditor,
+}
+
+impl WalkTheDog {
+    pub fn new(target: Target) -> Self {
+        WalkTheDog {
+            machine: None,
+            captions: None,
+            target,
+            config: GameConfig::default(),
+            #[cfg(debug_assertions)]
+            console: Console::new(console_commands()),
+            #[cfg(debug_assertions)]
+            hitbox_editor: HitboxEditor::new(),
+        }
+    }
+}
+
+const CAPTION_FRAMES: u8 = 90;
+const CAPTION_X: i16 = 20;
+const CAPTION_BOTTOM: i16 = 580;
+const CAPTION_LINE_HEIGHT: i16 = 24;
+
+struct Captions {
+    caption_events: UnboundedReceiver<String>,
+    texts: Vec<FloatingText>,
+}
+
+impl Captions {
+    fn new(caption_events: UnboundedReceiver<String>) -> Self {
+        Captions {
+            caption_events,
+            texts: vec![],
+        }
+    }
+
+    fn update(&mut self) {
+        while let Ok(Some(caption)) = self.caption_events.try_next() {
+            self.texts.push(FloatingText::new(
+                &caption,
+                Point {
+                    x: CAPTION_X,
+                    y: CAPTION_BOTTOM,
+                },
+                CAPTION_FRAMES,
+            ));
+        }
+
+        self.texts.iter_mut().for_each(|text| text.update());
+        self.texts.retain(|text| !text.expired());
+
+        let mut y = CAPTION_BOTTOM;
+        self.texts.iter_mut().rev().for_each(|text| {
+            text.set_y(y);
+            y -= CAPTION_LINE_HEIGHT;
+        });
+    }
+
+    // Captions are laid out from the bottom left corner and moved clear of
+    // the safe area as a whole.
+    fn draw(&self, renderer: &Renderer) {
+        let corner = renderer.hud().position(
+            Anchor::BottomLeft,
+            Point {
+                x: CAPTION_X,
+                y: HEIGHT - CAPTION_BOTTOM,
+            },
+        );
+        let offset = Point {
+            x: corner.x - CAPTION_X,
+            y: corner.y - CAPTION_BOTTOM,
+        };
+        renderer.draw_translated(&offset, || {
+            self.texts.iter().for_each(|text| text.draw(renderer))
+        });
+    }
+}
+
+// Declares the states a state can end up in after an update, along with
+// turning each back into the machine and, in debug builds, the edges they add
+// to the transition graph.
+macro_rules! end_state {
+    ($end_state:ident from $from:ident { $($variant:ident($to:ident)),+ $(,)? }) => {
+        enum $end_state {
+            $($variant(WalkTheDogState<$to>)),+
+        }
+
+        impl From<$end_state> for WalkTheDogStateMachine {
+            fn from(state: $end_state) -> Self {
+                match state {
+                    $($end_state::$variant(state) => state.into()),+
+                }
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        impl $end_state {
+            const EDGES: &'static [(&'static str, &'static str, &'static str)] =
+                &[$((stringify!($from), stringify!($variant), stringify!($to))),+];
+        }
+    };
+}
+
+enum WalkTheDogStateMachine {
+    Diagnostics(WalkTheDogState<Diagnostics>),
+    Intro(WalkTheDogState<Intro>),
+    ConsentPrompt(WalkTheDogState<ConsentPrompt>),
+    Title(WalkTheDogState<Title>),
+    ModeSelect(WalkTheDogState<ModeSelect>),
+    Stats(WalkTheDogState<Stats>),
+    Settings(WalkTheDogState<Settings>),
+    LevelSelect(WalkTheDogState<LevelSelect>),
+    Ready(WalkTheDogState<Ready>),
+    Walking(WalkTheDogState<Walking>),
+    Scripted(WalkTheDogState<Scripted>),
+    Practicing(WalkTheDogState<Practicing>),
+    Lobby(WalkTheDogState<Lobby>),
+    Spectating(WalkTheDogState<Spectating>),
+    RewindOffer(WalkTheDogState<RewindOffer>),
+    GameOver(WalkTheDogState<GameOver>),
+    Replaying(WalkTheDogState<Replaying>),
+    LevelComplete(WalkTheDogState<LevelComplete>),
+    Transitioning(Box<Transitioning>),
+}
+
+// The states worth animating the change between.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scene {
+    Title,
+    Ready,
+    Walking,
+    GameOver,
+    Other,
+}
+
+fn transition_effect(from: Scene, to: Scene) -> Option<TransitionEffect> {
+    match (from, to) {
+        (Scene::Title | Scene::GameOver, Scene::Ready) => Some(TransitionEffect::Fade),
+        (Scene::Ready, Scene::Walking) => Some(TransitionEffect::Wipe),
+        (Scene::Walking | Scene::Other, Scene::GameOver) => Some(TransitionEffect::Iris),
+        _ => None,
+    }
+}
+
+// The next state, held still while the screen is covered and uncovered.
+struct Transitioning {
+    transition: Transition,
+    next: WalkTheDogStateMachine,
+}
+
+impl Transitioning {
+    fn update(mut self: Box<Self>) -> WalkTheDogStateMachine {
+        self.transition.update();
+        if self.transition.finished() {
+            self.next
+        } else {
+            WalkTheDogStateMachine::Transitioning(self)
+        }
+    }
+}
+
+struct WalkTheDogState<T> {
+    _state: T,
+    walk: Walk,
+}
+
+impl<T> WalkTheDogState<T> {
+    fn draw(&self, renderer: &Renderer) {
+        self.walk.draw(renderer);
+    }
+}
+
+impl WalkTheDogStateMachine {
+    fn update(self, keystate: &KeyState) -> Self {
+        log!("KeyState is {:#?}", keystate);
+        let from = self.scene();
+        let next: Self = match self {
+            WalkTheDogStateMachine::Diagnostics(state) => state.update().into(),
+            WalkTheDogStateMachine::Intro(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::ConsentPrompt(state) => state.update().into(),
+            WalkTheDogStateMachine::Title(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::ModeSelect(state) => state.update().into(),
+            WalkTheDogStateMachine::Stats(state) => state.update().into(),
+            WalkTheDogStateMachine::Settings(state) => state.update().into(),
+            WalkTheDogStateMachine::LevelSelect(state) => state.update().into(),
+            WalkTheDogStateMachine::Ready(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::Walking(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::Scripted(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::Practicing(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::Lobby(state) => state.update().into(),
+            WalkTheDogStateMachine::Spectating(state) => state.update().into(),
+            WalkTheDogStateMachine::RewindOffer(state) => state.update().into(),
+            WalkTheDogStateMachine::GameOver(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::Replaying(state) => state.update(keystate).into(),
+            WalkTheDogStateMachine::LevelComplete(state) => state.update().into(),
+            WalkTheDogStateMachine::Transitioning(transitioning) => transitioning.update(),
+        };
+        match transition_effect(from, next.scene()) {
+            Some(effect) => {
+                let boy = next.walk().boy.bounding_box();
+                let focus = Point {
+                    x: boy.x() + boy.width / 2,
+                    y: boy.y() + boy.height / 2,
+                };
+                WalkTheDogStateMachine::Transitioning(Box::new(Transitioning {
+                    transition: Transition::new(effect, focus),
+                    next,
+                }))
+            }
+            None => next,
+        }
+    }
+
+    // Transitions between screens, as declared by each state's `end_state!`.
+    #[cfg(debug_assertions)]
+    fn transition_graph() -> StateGraph {
+        let mut graph = StateGraph::new("WalkTheDog");
+        // Spectators stay spectating until the page is reloaded.
+        graph.add_state("Spectating");
+        [
+            DiagnosticsEndState::EDGES,
+            IntroEndState::EDGES,
+            ConsentPromptEndState::EDGES,
+            TitleEndState::EDGES,
+            ModeSelectEndState::EDGES,
+            LobbyEndState::EDGES,
+            SettingsEndState::EDGES,
+            StatsEndState::EDGES,
+            LevelSelectEndState::EDGES,
+            ReadyEndState::EDGES,
+            WalkingEndState::EDGES,
+            ScriptedEndState::EDGES,
+            RewindOfferEndState::EDGES,
+            PracticingEndState::EDGES,
+            GameOverEndState::EDGES,
+            ReplayingEndState::EDGES,
+            LevelCompleteEndState::EDGES,
+        ]
+        .concat()
+        .iter()
+        .for_each(|(from, label, to)| graph.add_edge(from, label, to));
+        graph
+    }
+
+    fn scene(&self) -> Scene {
+        match self {
+            WalkTheDogStateMachine::Title(_) => Scene::Title,
+            WalkTheDogStateMachine::Ready(_) => Scene::Ready,
+            WalkTheDogStateMachine::Walking(_) => Scene::Walking,
+            WalkTheDogStateMachine::GameOver(_) => Scene::GameOver,
+            _ => Scene::Other,
+        }
+    }
+
+    fn draw(&self, renderer: &Renderer) {
+        match self {
+            WalkTheDogStateMachine::Intro(state) => {
+                state.draw(renderer);
+                state
+                    ._state
+                    .cutscene
+                    .draw(renderer, &state.walk.obstacle_sheet);
+            }
+            WalkTheDogStateMachine::Diagnostics(state) => state.draw(renderer),
+            WalkTheDogStateMachine::ConsentPrompt(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Title(state) => state.draw(renderer),
+            WalkTheDogStateMachine::ModeSelect(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Stats(state) => {
+                state.draw(renderer);
+                if let Err(err) = state._state.stats.draw(renderer) {
+                    log!("Could not draw stats {:#?}", err);
+                }
+            }
+            WalkTheDogStateMachine::Settings(state) => state.draw(renderer),
+            WalkTheDogStateMachine::LevelSelect(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Ready(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Walking(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Scripted(state) => {
+                state.draw(renderer);
+                state
+                    ._state
+                    .cutscene
+                    .draw(renderer, &state.walk.obstacle_sheet);
+            }
+            WalkTheDogStateMachine::Practicing(state) => {
+                state.draw(renderer);
+                state._state.practice.draw(renderer);
+            }
+            WalkTheDogStateMachine::Lobby(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Spectating(state) => {
+                state.draw(renderer);
+                state.draw_status(renderer);
+            }
+            WalkTheDogStateMachine::RewindOffer(state) => state.draw(renderer),
+            WalkTheDogStateMachine::GameOver(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Replaying(state) => {
+                state.draw(renderer);
+                state._state.viewer.draw(
+                    renderer,
+                    state.walk.elapsed_frames,
+                    state._state.menu.is_none(),
+                );
+            }
+            WalkTheDogStateMachine::LevelComplete(state) => state.draw(renderer),
+            WalkTheDogStateMachine::Transitioning(transitioning) => {
+                transitioning.next.draw(renderer);
+                transitioning.transition.draw(renderer);
+            }
+        }
+    }
+
+    fn walk(&self) -> &Walk {
+        match self {
+            WalkTheDogStateMachine::Intro(state) => &state.walk,
+            WalkTheDogStateMachine::Diagnostics(state) => &state.walk,
+            WalkTheDogStateMachine::ConsentPrompt(state) => &state.walk,
+            WalkTheDogStateMachine::Title(state) => &state.walk,
+            WalkTheDogStateMachine::ModeSelect(state) => &state.walk,
+            WalkTheDogStateMachine::Stats(state) => &state.walk,
+            WalkTheDogStateMachine::Settings(state) => &state.walk,
+            WalkTheDogStateMachine::LevelSelect(state) => &state.walk,
+            WalkTheDogStateMachine::Ready(state) => &state.walk,
+            WalkTheDogStateMachine::Walking(state) => &state.walk,
+            WalkTheDogStateMachine::Scripted(state) => &state.walk,
+            WalkTheDogStateMachine::Practicing(state) => &state.walk,
+            WalkTheDogStateMachine::Lobby(state) => &state.walk,
+            WalkTheDogStateMachine::Spectating(state) => &state.walk,
+            WalkTheDogStateMachine::RewindOffer(state) => &state.walk,
+            WalkTheDogStateMachine::GameOver(state) => &state.walk,
+            WalkTheDogStateMachine::Replaying(state) => &state.walk,
+            WalkTheDogStateMachine::LevelComplete(state) => &state.walk,
+            WalkTheDogStateMachine::Transitioning(transitioning) => transitioning.next.walk(),
+        }
+    }
+
+    fn walk_mut(&mut self) -> &mut Walk {
+        match self {
+            WalkTheDogStateMachine::Intro(state) => &mut state.walk,
+            WalkTheDogStateMachine::Diagnostics(state) => &mut state.walk,
+            WalkTheDogStateMachine::ConsentPrompt(state) => &mut state.walk,
+            WalkTheDogStateMachine::Title(state) => &mut state.walk,
+            WalkTheDogStateMachine::ModeSelect(state) => &mut state.walk,
+            WalkTheDogStateMachine::Stats(state) => &mut state.walk,
+            WalkTheDogStateMachine::Settings(state) => &mut state.walk,
+            WalkTheDogStateMachine::LevelSelect(state) => &mut state.walk,
+            WalkTheDogStateMachine::Ready(state) => &mut state.walk,
+            WalkTheDogStateMachine::Walking(state) => &mut state.walk,
+            WalkTheDogStateMachine::Scripted(state) => &mut state.walk,
+            WalkTheDogStateMachine::Practicing(state) => &mut state.walk,
+            WalkTheDogStateMachine::Lobby(state) => &mut state.walk,
+            WalkTheDogStateMachine::Spectating(state) => &mut state.walk,
+            WalkTheDogStateMachine::RewindOffer(state) => &mut state.walk,
+            WalkTheDogStateMachine::GameOver(state) => &mut state.walk,
+            WalkTheDogStateMachine::Replaying(state) => &mut state.walk,
+            WalkTheDogStateMachine::LevelComplete(state) => &mut state.walk,
+            WalkTheDogStateMachine::Transitioning(transitioning) => transitioning.next.walk_mut(),
+        }
+    }
+
+    // Problems found in the data files are shown before anything else, and
+    // the game carries on as it would have once they have been read.
+    fn new(walk: Walk, report: Report) -> Self {
+        if report.is_empty() {
+            WalkTheDogState::first_screen(walk).into()
+        } else {
+            WalkTheDogStateMachine::Diagnostics(WalkTheDogState::diagnostics(walk, &report))
+        }
+    }
+}
+
+impl WalkTheDogState<Diagnostics> {
+    fn diagnostics(walk: Walk, report: &Report) -> WalkTheDogState<Diagnostics> {
+        let target = &walk.target;
+        let play_anyway_event = browser::draw_ui(target, &report.html())
+            .and_then(|_unit| browser::find_ui_element(target, ".play_anyway"))
+            .map(|element| engine::add_click_handler(target, element))
+            .unwrap();
+        WalkTheDogState {
+            _state: Diagnostics { play_anyway_event },
+            walk,
+        }
+    }
+
+    fn update(mut self) -> DiagnosticsEndState {
+        if !pressed(&mut self._state.play_anyway_event) {
+            return DiagnosticsEndState::Continue(self);
+        }
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        WalkTheDogState::first_screen(self.walk)
+    }
+
+    // Where a game starts once there is nothing wrong to show.
+    fn first_screen(walk: Walk) -> DiagnosticsEndState {
+        if let Some(spectator) = spectate::spectator_from_browser() {
+            return DiagnosticsEndState::Spectate(WalkTheDogState {
+                _state: Spectating {
+                    spectator,
+                    running: false,
+                    desynced: false,
+                },
+                walk,
+            });
+        }
+        if walk.story.is_some() {
+            return DiagnosticsEndState::SelectLevel(WalkTheDogState::level_select(walk));
+        }
+        DiagnosticsEndState::Complete(WalkTheDogState {
+            _state: Intro {
+                cutscene: Cutscene::intro(),
+            },
+            walk,
+        })
+    }
+}
+
+end_state! {
+    DiagnosticsEndState from Diagnostics {
+        Complete(Intro),
+        SelectLevel(LevelSelect),
+        Spectate(Spectating),
+        Continue(Diagnostics),
+    }
+}
+
+impl WalkTheDogState<Intro> {
+    fn update(mut self, keystate: &KeyState) -> IntroEndState {
+        self.walk.boy.update();
+        let boy = self.walk.boy.bounding_box();
+        self._state.cutscene.update(&mut self.walk.background, &boy);
+        if keystate.any_pressed() || self._state.cutscene.finished() {
+            match Consent::stored() {
+                Some(_) => IntroEndState::Complete(WalkTheDogState::title(self.walk)),
+                None => IntroEndState::AskConsent(WalkTheDogState::consent_prompt(self.walk)),
+            }
+        } else {
+            IntroEndState::Continue(self)
+        }
+    }
+}
+
+end_state! {
+    IntroEndState from Intro {
+        Complete(Title),
+        AskConsent(ConsentPrompt),
+        Continue(Intro),
+    }
+}
+
+impl WalkTheDogState<ConsentPrompt> {
+    // Asked once, before anything could be sent. The answer can be changed
+    // later from the settings.
+    fn consent_prompt(walk: Walk) -> WalkTheDogState<ConsentPrompt> {
+        let target = &walk.target;
+        let (offline_event, online_event) = browser::draw_ui(
+            target,
+            "<div class='consent'><p>Walk the Dog can share your scores with an online leaderboard and back up your progress online. Nothing leaves this browser unless you allow it.</p><button class='offline'>Keep everything offline</button><button class='online'>Share scores</button></div>",
+        )
+        .and_then(|_unit| {
+            Ok((
+                engine::add_click_handler(target, browser::find_ui_element(target, ".offline")?),
+                engine::add_click_handler(target, browser::find_ui_element(target, ".online")?),
+            ))
+        })
+        .unwrap();
+
+        WalkTheDogState {
+            _state: ConsentPrompt {
+                offline_event,
+                online_event,
+            },
+            walk,
+        }
+    }
+
+    fn update(mut self) -> ConsentPromptEndState {
+        let answer = if pressed(&mut self._state.offline_event) {
+            Some(Consent::Offline)
+        } else if pressed(&mut self._state.online_event) {
+            Some(Consent::Online)
+        } else {
+            None
+        };
+
+        match answer {
+            Some(consent) => {
+                if let Err(err) = consent.save() {
+                    log!("Could not save consent {:#?}", err);
+                }
+                if consent.allows_online() {
+                    cloud_save::sync_in_background();
+                }
+                browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+                ConsentPromptEndState::Complete(WalkTheDogState::title(self.walk))
+            }
+            None => ConsentPromptEndState::Continue(self),
+        }
+    }
+}
+
+end_state! {
+    ConsentPromptEndState from ConsentPrompt {
+        Complete(Title),
+        Continue(ConsentPrompt),
+    }
+}
+
+impl WalkTheDogState<Title> {
+    fn title(walk: Walk) -> WalkTheDogState<Title> {
+        let target = &walk.target;
+        let (play_event, modes_event, stats_event, settings_event) = browser::draw_ui(
+            target,
+            &format!(
+                "<div class='title'><button class='play'>{}</button><button class='modes'>{}</button><button class='stats'>{}</button><button class='settings'>{}</button></div>",
+                text("Play"),
+                text("Modes"),
+                text("Stats"),
+                text("Settings")
+            ),
+        )
+        .and_then(|_unit| {
+            Ok((
+                engine::add_click_handler(target, browser::find_ui_element(target, ".play")?),
+                engine::add_click_handler(target, browser::find_ui_element(target, ".modes")?),
+                engine::add_click_handler(target, browser::find_ui_element(target, ".stats")?),
+                engine::add_click_handler(target, browser::find_ui_element(target, ".settings")?),
+            ))
+        })
+        .unwrap();
+        narration::show_menu(
+            target,
+            text("Walk the Dog"),
+            &[text("Play"), text("Modes"), text("Stats"), text("Settings")],
+        );
+
+        WalkTheDogState {
+            _state: Title {
+                play_event,
+                modes_event,
+                stats_event,
+                settings_event,
+            },
+            walk,
+        }
+    }
+
+    fn update(mut self, keystate: &KeyState) -> TitleEndState {
+        self.walk.boy.update();
+        if keystate.is_pressed("ArrowRight") || pressed(&mut self._state.play_event) {
+            TitleEndState::Play(self.play())
+        } else if pressed(&mut self._state.modes_event) {
+            TitleEndState::Modes(self.show_modes())
+        } else if pressed(&mut self._state.stats_event) {
+            TitleEndState::Stats(self.show_stats())
+        } else if pressed(&mut self._state.settings_event) {
+            TitleEndState::Settings(self.show_settings())
+        } else {
+            TitleEndState::Continue(self)
+        }
+    }
+
+    fn play(self) -> WalkTheDogState<Ready> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        WalkTheDogState::new(self.walk)
+    }
+
+    fn show_modes(self) -> WalkTheDogState<ModeSelect> {
+        let target = &self.walk.target;
+        browser::hide_ui(target).expect("Failed to hide UI!");
+        let mut modes = GameMode::selectable();
+        if matches!(race::lobby_from_browser(), Ok(Some(_))) {
+            modes.push(GameMode::Race);
+        }
+        let (mode_events, back_event) = browser::draw_ui(target, &GameMode::select_html(&modes))
+            .and_then(|_unit| {
+                let mode_events = (0..modes.len())
+                    .map(|index| {
+                        browser::find_ui_element(target, &format!(".mode_{}", index))
+                            .map(|element| engine::add_click_handler(target, element))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let back_event =
+                    engine::add_click_handler(target, browser::find_ui_element(target, ".back")?);
+                Ok((mode_events, back_event))
+            })
+            .unwrap();
+        let mut options: Vec<String> = modes.iter().map(GameMode::label).collect();
+        options.push(text("Back").to_string());
+        narration::show_menu(target, text("Modes"), &options);
+
+        WalkTheDogState {
+            _state: ModeSelect {
+                modes,
+                mode_events,
+                back_event,
+            },
+            walk: self.walk,
+        }
+    }
+
+    fn show_stats(self) -> WalkTheDogState<Stats> {
+        let target = &self.walk.target;
+        browser::hide_ui(target).expect("Failed to hide UI!");
+        let back_event = browser::draw_ui(
+            target,
+            &format!("<button class='back'>{}</button>", text("Back")),
+        )
+        .and_then(|_unit| browser::find_ui_element(target, ".back"))
+        .map(|element| engine::add_click_handler(target, element))
+        .unwrap();
+        narration::show_menu(target, text("Stats"), &[text("Back")]);
+
+        WalkTheDogState {
+            _state: Stats {
+                stats: LifetimeStats::load(),
+                back_event,
+            },
+            walk: self.walk,
+        }
+    }
+
+    fn show_settings(self) -> WalkTheDogState<Settings> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        WalkTheDogState::settings(self.walk)
+    }
+}
+
+end_state! {
+    TitleEndState from Title {
+        Play(Ready),
+        Modes(ModeSelect),
+        Stats(Stats),
+        Settings(Settings),
+        Continue(Title),
+    }
+}
+
+impl WalkTheDogState<ModeSelect> {
+    fn update(mut self) -> ModeSelectEndState {
+        self.walk.boy.update();
+        match self._state.selected_mode() {
+            Some(GameMode::Race) => ModeSelectEndState::Lobby(self.open_lobby()),
+            Some(mode) => ModeSelectEndState::Start(self.start_mode(mode)),
+            None if pressed(&mut self._state.back_event) => {
+                browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+                ModeSelectEndState::Back(WalkTheDogState::title(self.walk))
+            }
+            None => ModeSelectEndState::Continue(self),
+        }
+    }
+
+    fn open_lobby(self) -> WalkTheDogState<Lobby> {
+        let target = &self.walk.target;
+        browser::hide_ui(target).expect("Failed to hide UI!");
+        let race = match race::lobby_from_browser() {
+            Ok(Some((endpoint, room))) => Race::join(&endpoint, &room),
+            _ => Race::default(),
+        };
+        let back_event = browser::draw_ui(
+            target,
+            "<div class='lobby'><p class='lobby_status'>Waiting for a rival...</p><button class='back'>Back</button></div>",
+        )
+        .and_then(|_unit| browser::find_ui_element(target, ".back"))
+        .map(|element| engine::add_click_handler(target, element))
+        .unwrap();
+
+        WalkTheDogState {
+            _state: Lobby {
+                race,
+                back_event,
+                failure_shown: false,
+            },
+            walk: self.walk,
+        }
+    }
+
+    fn start_mode(mut self, mode: GameMode) -> WalkTheDogState<Ready> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        self.walk.mode = mode;
+        WalkTheDogState::new(Walk::reset(self.walk))
+    }
+}
+
+end_state! {
+    ModeSelectEndState from ModeSelect {
+        Start(Ready),
+        Lobby(Lobby),
+        Back(Title),
+        Continue(ModeSelect),
+    }
+}
+
+impl WalkTheDogState<Lobby> {
+    fn update(mut self) -> LobbyEndState {
+        self.walk.boy.update();
+        self._state.race.update();
+        if pressed(&mut self._state.back_event) {
+            browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+            return LobbyEndState::Back(WalkTheDogState::title(self.walk));
+        }
+        if self._state.race.failed() && !self._state.failure_shown {
+            self._state.failure_shown = true;
+            match browser::find_ui_element(&self.walk.target, ".lobby_status") {
+                Ok(element) => element.set_inner_text("Could not reach a rival"),
+                Err(err) => {
+                    log!("Could not show lobby status {:#?}", err);
+                }
+            }
+        }
+        match self._state.race.seed() {
+            Some(seed) => LobbyEndState::Start(self.start_race(seed)),
+            None => LobbyEndState::Continue(self),
+        }
+    }
+
+    fn start_race(mut self, seed: u64) -> WalkTheDogState<Ready> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        self.walk.mode = GameMode::Race;
+        let mut walk = Walk::reset(self.walk);
+        walk.join_race(self._state.race, seed);
+        WalkTheDogState::new(walk)
+    }
+}
+
+end_state! {
+    LobbyEndState from Lobby {
+        Start(Ready),
+        Back(Title),
+        Continue(Lobby),
+    }
+}
+
+// Plays a broadcast run one cue per frame, a little behind the player.
+impl WalkTheDogState<Spectating> {
+    fn update(mut self) -> WalkTheDogState<Spectating> {
+        match self._state.spectator.next() {
+            Some(Cue::Start { seed, config }) => {
+                self.walk = Walk::watch(self.walk, seed, config);
+                self._state.running = false;
+                self._state.desynced = false;
+            }
+            Some(Cue::Step(input)) => {
+                if !self._state.running {
+                    self.walk.boy.run_right();
+                    self._state.running = true;
+                }
+                self.walk.step(&recorded_input(input));
+            }
+            Some(Cue::Rewind) => {
+                if let Some(snapshot) = self.walk.rewind.rewind() {
+                    self.walk.restore(snapshot);
+                }
+            }
+            Some(Cue::Check { frame, hash }) => {
+                let matched =
+                    self.walk.elapsed_frames == frame && self.walk.world_hash().short() == hash;
+                if !matched && !self._state.desynced {
+                    log!(
+                        "Spectating desynced from the broadcast by frame {} (at frame {})",
+                        frame,
+                        self.walk.elapsed_frames
+                    );
+                    self._state.desynced = true;
+                }
+            }
+            // Only an idle boy can be animated without running ahead of the
+            // broadcast.
+            None if !self._state.running => self.walk.boy.update(),
+            None => {}
+        }
+        self
+    }
+
+    fn draw_status(&self, renderer: &Renderer) {
+        let status = if self._state.spectator.watching() {
+            "LIVE"
+        } else {
+            "Waiting for a run"
+        };
+        if let Err(err) = renderer.draw_styled_text(
+            status,
+            &renderer
+                .hud()
+                .position(Anchor::BottomLeft, Point { x: 10, y: 10 }),
+            &hud_style(),
+        ) {
+            log!("Could not draw spectator status {:#?}", err);
+        }
+    }
 }
 
-impl WalkTheDog {
-    pub fn new() -> Self {
-        WalkTheDog { machine: None }
+impl WalkTheDogState<Stats> {
+    fn update(mut self) -> StatsEndState {
+        if pressed(&mut self._state.back_event) {
+            browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+            StatsEndState::Complete(WalkTheDogState::title(self.walk))
+        } else {
+            StatsEndState::Continue(self)
+        }
     }
 }
 
-enum WalkTheDogStateMachine {
-    Ready(WalkTheDogState<Ready>),
-    Walking(WalkTheDogState<Walking>),
-    GameOver(WalkTheDogState<GameOver>),
+impl WalkTheDogState<Settings> {
+    fn settings(walk: Walk) -> WalkTheDogState<Settings> {
+        let target = &walk.target;
+        let mixer = MixerSetting::load();
+        let sliders: String = Fader::ALL
+            .iter()
+            .map(|fader| {
+                format!(
+                    "<label>{}<input type='range' class='{}' min='0' max='100' value='{}'></label>",
+                    text(fader.label()),
+                    fader.class(),
+                    mixer.level(*fader)
+                )
+            })
+            .collect();
+        let (button_events, volume_events) = browser::draw_ui(
+            target,
+            &format!(
+                "<div class='settings'>{}{}{}<textarea class='save_code' rows='4' cols='40'></textarea><p class='save_status'></p>{}</div>",
+                settings_buttons_html(&SETTINGS_BUTTONS),
+                sliders,
+                settings_buttons_html(&SOUND_BUTTONS),
+                settings_buttons_html(&SAVE_BUTTONS)
+            ),
+        )
+        .and_then(|_unit| {
+            let button_events = all_settings_buttons()
+                .map(|(class, _label, action)| {
+                    let button = browser::find_ui_element(target, &format!(".{}", class))?;
+                    Ok((*action, engine::add_click_handler(target, button)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let volume_events = Fader::ALL
+                .iter()
+                .map(|fader| {
+                    let slider =
+                        browser::find_ui_element(target, &format!(".{}", fader.class()))?;
+                    Ok((*fader, engine::add_input_handler(target, slider)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok((button_events, volume_events))
+        })
+        .unwrap();
+        let labels: Vec<String> = all_settings_buttons()
+            .map(|(_class, label, _action)| label())
+            .collect();
+        narration::show_menu(target, text("Settings"), &labels);
+
+        WalkTheDogState {
+            _state: Settings {
+                button_events,
+                volume_events,
+            },
+            walk,
+        }
+    }
+
+    fn update(mut self) -> SettingsEndState {
+        let moved: Vec<Fader> = self
+            ._state
+            .volume_events
+            .iter_mut()
+            .filter_map(|(fader, event)| pressed(event).then_some(*fader))
+            .collect();
+        if !moved.is_empty() {
+            self.set_volumes(&moved);
+        }
+        let actions: Vec<SettingsAction> = self
+            ._state
+            .button_events
+            .iter_mut()
+            .filter_map(|(action, event)| pressed(event).then_some(*action))
+            .collect();
+        for action in actions {
+            match action {
+                SettingsAction::Language => {
+                    return SettingsEndState::Continue(self.switch_language());
+                }
+                SettingsAction::Apply(apply) => apply(&self),
+                SettingsAction::Back => {
+                    browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+                    return SettingsEndState::Complete(WalkTheDogState::title(self.walk));
+                }
+            }
+        }
+        SettingsEndState::Continue(self)
+    }
+
+    // Everything on screen is shown again in the new language straight away.
+    fn switch_language(self) -> WalkTheDogState<Settings> {
+        let locale = i18n::current().next();
+        if let Err(err) = locale.save() {
+            log!("Could not save locale {:#?}", err);
+        }
+        i18n::set_current(locale);
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        WalkTheDogState::settings(self.walk)
+    }
+
+    fn cycle_graphics(&self) {
+        let setting = GraphicsSetting::load().next();
+        if let Err(err) = setting.save() {
+            log!("Could not save graphics setting {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".graphics") {
+            Ok(element) => element.set_inner_text(&setting_label("Graphics", setting.label())),
+            Err(err) => {
+                log!("Could not show graphics setting {:#?}", err);
+            }
+        }
+    }
+
+    fn cycle_frame_rate(&self) {
+        let setting = FrameRateSetting::load().next();
+        if let Err(err) = setting.save() {
+            log!("Could not save frame rate setting {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".frame_rate") {
+            Ok(element) => element.set_inner_text(&setting_label("Frame rate", setting.label())),
+            Err(err) => {
+                log!("Could not show frame rate setting {:#?}", err);
+            }
+        }
+    }
+
+    fn cycle_consent(&self) {
+        let consent = Consent::load().next();
+        if let Err(err) = consent.save() {
+            log!("Could not save consent {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".privacy") {
+            Ok(element) => element.set_inner_text(&setting_label("Sharing", consent.label())),
+            Err(err) => {
+                log!("Could not show consent {:#?}", err);
+            }
+        }
+    }
+
+    fn cycle_announcer(&self) {
+        let setting = AnnouncerSetting::load().next();
+        if let Err(err) = setting.save() {
+            log!("Could not save announcer setting {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".announcer") {
+            Ok(element) => element.set_inner_text(&setting_label("Announcer", setting.label())),
+            Err(err) => {
+                log!("Could not show announcer setting {:#?}", err);
+            }
+        }
+    }
+
+    fn cycle_rumble(&self) {
+        let setting = RumbleSetting::load().next();
+        if let Err(err) = setting.save() {
+            log!("Could not save rumble setting {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".rumble") {
+            Ok(element) => element.set_inner_text(&setting_label("Rumble", setting.label())),
+            Err(err) => {
+                log!("Could not show rumble setting {:#?}", err);
+            }
+        }
+    }
+
+    fn cycle_menu_style(&self) {
+        let style = MenuStyle::load().next();
+        if let Err(err) = style.save() {
+            log!("Could not save menu style {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".menus") {
+            Ok(element) => element.set_inner_text(&setting_label("Replay controls", style.label())),
+            Err(err) => {
+                log!("Could not show menu style {:#?}", err);
+            }
+        }
+    }
+
+    fn cycle_pause_music(&self) {
+        let setting = PauseMusicSetting::load().next();
+        if let Err(err) = setting.save() {
+            log!("Could not save pause music setting {:#?}", err);
+        }
+        match browser::find_ui_element(&self.walk.target, ".pause_music") {
+            Ok(element) => {
+                element.set_inner_text(&setting_label("Music when paused", setting.label()))
+            }
+            Err(err) => {
+                log!("Could not show pause music setting {:#?}", err);
+            }
+        }
+    }
+
+    // Levels are heard straight away, and kept for next time.
+    fn set_volumes(&self, faders: &[Fader]) {
+        let mut mixer = MixerSetting::load();
+        faders.iter().for_each(|fader| {
+            match browser::find_ui_input(&self.walk.target, &format!(".{}", fader.class())) {
+                Ok(slider) => mixer.set_level(*fader, slider.value().parse().unwrap_or(100)),
+                Err(err) => {
+                    log!("Could not read volume slider {:#?}", err);
+                }
+            }
+        });
+        if let Err(err) = mixer.save() {
+            log!("Could not save mixer setting {:#?}", err);
+        }
+        self.walk.boy.audio().set_levels(&mixer);
+    }
+
+    fn export_save(&self) {
+        let target = &self.walk.target;
+        let status = match save::export().and_then(|code| {
+            browser::find_ui_text_area(target, ".save_code").map(|area| area.set_value(&code))
+        }) {
+            Ok(()) => "Copy this code into another browser",
+            Err(err) => {
+                log!("Could not export save {:#?}", err);
+                "Export failed"
+            }
+        };
+        self.show_save_status(status);
+    }
+
+    fn import_save(&self) {
+        let target = &self.walk.target;
+        let status = match browser::find_ui_text_area(target, ".save_code")
+            .and_then(|area| save::import(&area.value()))
+        {
+            Ok(()) => "Save imported",
+            Err(err) => {
+                log!("Could not import save {:#?}", err);
+                "Import failed"
+            }
+        };
+        self.show_save_status(status);
+    }
+
+    fn show_save_status(&self, status: &'static str) {
+        match browser::find_ui_element(&self.walk.target, ".save_status") {
+            Ok(element) => element.set_inner_text(text(status)),
+            Err(err) => {
+                log!("Could not show save status {:#?}", err);
+            }
+        }
+    }
 }
 
-struct WalkTheDogState<T> {
-    _state: T,
-    walk: Walk,
+end_state! {
+    SettingsEndState from Settings {
+        Complete(Title),
+        Continue(Settings),
+    }
 }
 
-impl<T> WalkTheDogState<T> {
-    fn draw(&self, renderer: &Renderer) {
-        self.walk.draw(renderer);
+end_state! {
+    StatsEndState from Stats {
+        Complete(Title),
+        Continue(Stats),
     }
 }
 
-impl WalkTheDogStateMachine {
-    fn update(self, keystate: &KeyState) -> Self {
-        log!("KeyState is {:#?}", keystate);
-        match self {
-            WalkTheDogStateMachine::Ready(state) => state.update(keystate).into(),
-            WalkTheDogStateMachine::Walking(state) => state.update(keystate).into(),
-            WalkTheDogStateMachine::GameOver(state) => state.update().into(),
+impl WalkTheDogState<LevelSelect> {
+    fn level_select(walk: Walk) -> WalkTheDogState<LevelSelect> {
+        let target = &walk.target;
+        let (html, level_count) = walk
+            .story
+            .as_ref()
+            .map(|story| (story.level_select_html(), story.level_count()))
+            .unwrap_or_default();
+        let level_events = browser::draw_ui(target, &html)
+            .and_then(|_unit| {
+                (0..level_count)
+                    .map(|index| {
+                        browser::find_ui_element(target, &format!(".level_{}", index))
+                            .map(|element| engine::add_click_handler(target, element))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .unwrap();
+
+        WalkTheDogState {
+            _state: LevelSelect { level_events },
+            walk,
         }
     }
 
-    fn draw(&self, renderer: &Renderer) {
-        match self {
-            WalkTheDogStateMachine::Ready(state) => state.draw(renderer),
-            WalkTheDogStateMachine::Walking(state) => state.draw(renderer),
-            WalkTheDogStateMachine::GameOver(state) => state.draw(renderer),
+    fn update(mut self) -> LevelSelectEndState {
+        self.walk.boy.update();
+        match self._state.selected_level() {
+            Some(level) => LevelSelectEndState::Complete(self.start_level(level)),
+            None => LevelSelectEndState::Continue(self),
         }
     }
 
-    fn new(walk: Walk) -> Self {
-        WalkTheDogStateMachine::Ready(WalkTheDogState::new(walk))
+    fn start_level(mut self, level: usize) -> WalkTheDogState<Ready> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        if let Some(story) = &mut self.walk.story {
+            story.start(level);
+        }
+        WalkTheDogState::new(Walk::reset(self.walk))
+    }
+}
+
+end_state! {
+    LevelSelectEndState from LevelSelect {
+        Complete(Ready),
+        Continue(LevelSelect),
     }
 }
 
@@ -903,7 +4815,10 @@ impl WalkTheDogState<Ready> {
     fn update(mut self, keystate: &KeyState) -> ReadyEndState {
         self.walk.boy.update();
         if keystate.is_pressed("ArrowRight") {
-            ReadyEndState::Complete(self.start_running())
+            match self.walk.mode {
+                GameMode::Practice(_) => ReadyEndState::Practice(self.start_practice()),
+                _ => ReadyEndState::Complete(self.start_running()),
+            }
         } else {
             ReadyEndState::Continue(self)
         }
@@ -915,136 +4830,557 @@ impl WalkTheDogState<Ready> {
 
     fn start_running(mut self) -> WalkTheDogState<Walking> {
         self.run_right();
+        self.walk.start_broadcast();
+        WalkTheDogState {
+            _state: Walking,
+            walk: self.walk,
+        }
+    }
+
+    fn start_practice(mut self) -> WalkTheDogState<Practicing> {
+        self.run_right();
+        WalkTheDogState {
+            _state: Practicing {
+                practice: Practice::new(),
+            },
+            walk: self.walk,
+        }
+    }
+
+    fn new(walk: Walk) -> WalkTheDogState<Ready> {
+        WalkTheDogState {
+            _state: Ready,
+            walk,
+        }
+    }
+}
+
+end_state! {
+    ReadyEndState from Ready {
+        Complete(Walking),
+        Practice(Practicing),
+        Continue(Ready),
+    }
+}
+
+impl WalkTheDogState<Walking> {
+    fn update(mut self, keystate: &KeyState) -> WalkingEndState {
+        #[cfg(debug_assertions)]
+        self.walk.handle_save_state_keys(keystate);
+        self.walk.step(keystate);
+        self.walk.step_shadow(keystate);
+        if self.walk.knocked_out() && self.walk.can_rewind() {
+            WalkingEndState::RewindOffer(self.offer_rewind())
+        } else if self.walk.knocked_out() || self.walk.time_up() {
+            WalkingEndState::Complete(self.end_game())
+        } else if self.walk.goal_reached() {
+            WalkingEndState::LevelComplete(self.complete_level())
+        } else if let Some(script) = self.walk.zone_effects.take_cutscene() {
+            match Cutscene::from_script(&script) {
+                Ok(cutscene) => WalkingEndState::Scripted(WalkTheDogState {
+                    _state: Scripted { cutscene },
+                    walk: self.walk,
+                }),
+                Err(err) => {
+                    log!("Could not play the cutscene {:#?}", err);
+                    WalkingEndState::Continue(self)
+                }
+            }
+        } else {
+            WalkingEndState::Continue(self)
+        }
+    }
+
+    fn offer_rewind(self) -> WalkTheDogState<RewindOffer> {
+        let target = &self.walk.target;
+        let (rewind_event, give_up_event) = browser::draw_ui(
+            target,
+            &format!(
+                "<div class='rewind_offer'><button class='rewind'>Rewind {} seconds ({} left)</button><button class='give_up'>Give Up</button></div>",
+                REWIND_SECONDS,
+                self.walk.rewind.uses_left()
+            ),
+        )
+        .and_then(|_unit| {
+            Ok((
+                engine::add_click_handler(target, browser::find_ui_element(target, ".rewind")?),
+                engine::add_click_handler(target, browser::find_ui_element(target, ".give_up")?),
+            ))
+        })
+        .unwrap();
+
+        WalkTheDogState {
+            _state: RewindOffer {
+                rewind_event,
+                give_up_event,
+            },
+            walk: self.walk,
+        }
+    }
+
+    fn end_game(mut self) -> WalkTheDogState<GameOver> {
+        if let Some(broadcast) = &mut self.walk.broadcast {
+            broadcast.finish();
+        }
+        let mut stats = LifetimeStats::load();
+        stats.record_run(
+            self.walk.distance,
+            self.walk.boy.jumps,
+            self.walk
+                .boy
+                .death_cause
+                .map_or("unknown", |cause| cause.name()),
+        );
+        if let Err(err) = stats.save() {
+            log!("Could not save stats {:#?}", err);
+        }
+        let mut history = RunHistory::load();
+        history.record_run(
+            self.walk.obstacles_cleared,
+            self.walk.boy.death_cause == Some(DeathCause::Stone),
+        );
+        if let Err(err) = history.save() {
+            log!("Could not save run history {:#?}", err);
+        }
+
+        let target = &self.walk.target;
+        let playing_story = self
+            .walk
+            .story
+            .as_ref()
+            .map_or(false, |story| story.playing());
+        let modded = !self.walk.pack_segments.is_empty();
+        let bucket = if playing_story || self.walk.cheats.any() || modded {
+            None
+        } else {
+            self.walk.mode.leaderboard_bucket(&self.walk.config)
+        };
+        if let Some(race) = &self.walk.race {
+            race.finish(self.walk.distance);
+        }
+        let rival = self
+            .walk
+            .race
+            .as_ref()
+            .and_then(|race| race.rival().distance());
+        let html = match (bucket, rival) {
+            (None, Some(rival)) => format!(
+                "<div class='game_over'><p>You: {} Rival: {}</p><button class='new_game'>New Game</button></div>",
+                self.walk.distance, rival
+            ),
+            (None, None) => "<button class='new_game'>New Game</button>".to_string(),
+            (Some(bucket), _) => {
+                let mut leaderboard = Leaderboard::load();
+                leaderboard.record(&bucket, self.walk.distance);
+                if let Err(err) = leaderboard.save() {
+                    log!("Could not save leaderboard {:#?}", err);
+                }
+                let record = self.walk.run_record();
+                browser::spawn_local(async move {
+                    if let Err(err) = record.save(LAST_RUN_KEY).await {
+                        log!("Could not save the last run {:#?}", err);
+                    }
+                });
+                leaderboard::submit_in_background(Submission::new(
+                    &bucket,
+                    self.walk.distance,
+                    self.walk.seed,
+                    self.walk.elapsed_frames,
+                    &self.walk.replay,
+                ));
+                // Other modes do not come from the seed alone, so only endless
+                // runs can be shared.
+                let challenge = match self.walk.mode {
+                    GameMode::Endless => self
+                        .walk
+                        .run_link()
+                        .url()
+                        .map(|url| {
+                            format!("<p><a class='challenge' href='{}'>Challenge link</a></p>", url)
+                        })
+                        .unwrap_or_else(|err| {
+                            log!("Could not make a challenge link {:#?}", err);
+                            String::new()
+                        }),
+                    _ => String::new(),
+                };
+                format!(
+                    "<div class='game_over'><p>{}: {} (best {})</p>{}<button class='watch_replay'>Watch replay</button><button class='new_game'>New Game</button></div>",
+                    bucket,
+                    self.walk.distance,
+                    leaderboard.best(&bucket).unwrap_or(self.walk.distance),
+                    challenge
+                )
+            }
+        };
+        cloud_save::sync_in_background();
+        let game_over = GameOver::show(target, html);
+        narration::announce(target, &narration::game_over(self.walk.distance));
+
+        WalkTheDogState {
+            _state: game_over,
+            walk: self.walk,
+        }
+    }
+
+    fn complete_level(mut self) -> WalkTheDogState<LevelComplete> {
+        let (coins, hits) = (self.walk.coins_collected, self.walk.boy.hits());
+        let html = self
+            .walk
+            .story
+            .as_mut()
+            .and_then(|story| {
+                story
+                    .complete(coins, hits)
+                    .map(|stars| story.level_complete_html(stars))
+                    .ok()
+            })
+            .unwrap_or_default();
+        let target = &self.walk.target;
+        let receiver = browser::draw_ui(target, &html)
+            .and_then(|_unit| browser::find_ui_element(target, ".level_select"))
+            .map(|element| engine::add_click_handler(target, element))
+            .unwrap();
+
+        WalkTheDogState {
+            _state: LevelComplete {
+                level_select_event: receiver,
+            },
+            walk: self.walk,
+        }
+    }
+}
+
+end_state! {
+    WalkingEndState from Walking {
+        Complete(GameOver),
+        LevelComplete(LevelComplete),
+        RewindOffer(RewindOffer),
+        Scripted(Scripted),
+        Continue(Walking),
+    }
+}
+
+// The run holds still while a scene placed on the course plays over it.
+impl WalkTheDogState<Scripted> {
+    fn update(mut self, keystate: &KeyState) -> ScriptedEndState {
+        let boy = self.walk.boy.bounding_box();
+        self._state.cutscene.update(&mut self.walk.background, &boy);
+        if keystate.is_pressed(SKIP_SCENE_KEY) || self._state.cutscene.finished() {
+            ScriptedEndState::Complete(WalkTheDogState {
+                _state: Walking,
+                walk: self.walk,
+            })
+        } else {
+            ScriptedEndState::Continue(self)
+        }
+    }
+}
+
+end_state! {
+    ScriptedEndState from Scripted {
+        Complete(Walking),
+        Continue(Scripted),
+    }
+}
+
+impl WalkTheDogState<RewindOffer> {
+    fn update(mut self) -> RewindOfferEndState {
+        if pressed(&mut self._state.rewind_event) {
+            RewindOfferEndState::Rewind(self.rewind())
+        } else if pressed(&mut self._state.give_up_event) {
+            RewindOfferEndState::GiveUp(self.give_up())
+        } else {
+            RewindOfferEndState::Continue(self)
+        }
+    }
+
+    fn rewind(mut self) -> WalkTheDogState<Walking> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        if let Some(snapshot) = self.walk.rewind.rewind() {
+            self.walk.restore(snapshot);
+        }
+        if let Some(broadcast) = &mut self.walk.broadcast {
+            broadcast.rewound();
+        }
+        WalkTheDogState {
+            _state: Walking,
+            walk: self.walk,
+        }
+    }
+
+    fn give_up(self) -> WalkTheDogState<GameOver> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
         WalkTheDogState {
             _state: Walking,
             walk: self.walk,
         }
+        .end_game()
+    }
+}
+
+end_state! {
+    RewindOfferEndState from RewindOffer {
+        Rewind(Walking),
+        GiveUp(GameOver),
+        Continue(RewindOffer),
+    }
+}
+
+impl WalkTheDogState<Practicing> {
+    fn update(mut self, keystate: &KeyState) -> PracticingEndState {
+        if keystate.is_pressed("Escape") {
+            return PracticingEndState::Quit(self.quit());
+        }
+        let practice = &mut self._state.practice;
+        practice.toggle_slow_motion(keystate.is_pressed("KeyS"));
+        if practice.restart_requested(keystate.is_pressed("KeyR")) || self.walk.knocked_out() {
+            return PracticingEndState::Continue(self.retry());
+        }
+        if practice.should_step() {
+            self.walk.step(keystate);
+        }
+        PracticingEndState::Continue(self)
+    }
+
+    // Restarts straight away, without going through the game over screen.
+    fn retry(mut self) -> Self {
+        self._state.practice.retry();
+        let mut walk = Walk::reset(self.walk);
+        walk.boy.run_right();
+        WalkTheDogState {
+            _state: self._state,
+            walk,
+        }
+    }
+
+    fn quit(mut self) -> WalkTheDogState<Title> {
+        self.walk.mode = GameMode::Endless;
+        WalkTheDogState::title(Walk::reset(self.walk))
+    }
+}
+
+end_state! {
+    PracticingEndState from Practicing {
+        Quit(Title),
+        Continue(Practicing),
+    }
+}
+
+impl WalkTheDogState<GameOver> {
+    fn update(mut self, keystate: &KeyState) -> GameOverEndState {
+        let key = self._state.key_pressed(keystate);
+        if key == Some(RESTART_KEY) {
+            GameOverEndState::Restart(self.restart())
+        } else if key == Some(NEW_GAME_KEY) || self._state.new_game_pressed() {
+            GameOverEndState::Complete(self.new_game())
+        } else if self._state.watch_replay_pressed() {
+            GameOverEndState::WatchReplay(self.watch_replay())
+        } else {
+            GameOverEndState::Continue(self)
+        }
+    }
+
+    // A race is a single run, so another game afterwards is an endless one.
+    fn new_game(mut self) -> WalkTheDogState<Ready> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        if self.walk.mode == GameMode::Race {
+            self.walk.mode = GameMode::Endless;
+        }
+        WalkTheDogState {
+            _state: Ready,
+            walk: Walk::reset(self.walk),
+        }
     }
 
-    fn new(walk: Walk) -> WalkTheDogState<Ready> {
-        WalkTheDogState {
-            _state: Ready,
-            walk,
+    // Straight back into a run, skipping the wait on the Ready screen.
+    fn restart(mut self) -> WalkTheDogState<Walking> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        if self.walk.mode == GameMode::Race {
+            self.walk.mode = GameMode::Endless;
         }
+        self.walk.restart();
+        WalkTheDogState::new(self.walk).start_running()
     }
-}
 
-enum ReadyEndState {
-    Complete(WalkTheDogState<Walking>),
-    Continue(WalkTheDogState<Ready>),
+    // The run just played, from its first frame, silently and on its own.
+    fn watch_replay(mut self) -> WalkTheDogState<Replaying> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        let record = self.walk.run_record();
+        let broadcast = self.walk.broadcast.take();
+        let sound = self.walk.boy.mute();
+        self.walk.restart();
+        self.walk.reseed(record.seed);
+        let viewer = ReplayViewer::new(record.replay, record.frames, self.walk.snapshot());
+        let menu = match MenuStyle::load() {
+            MenuStyle::Canvas => None,
+            MenuStyle::Page => Some(Box::new(PageMenu::new("Replay"))),
+        };
+        WalkTheDogState {
+            _state: Replaying {
+                viewer,
+                menu,
+                game_over: self._state.html,
+                broadcast,
+                sound,
+            },
+            walk: self.walk,
+        }
+    }
 }
 
-impl From<ReadyEndState> for WalkTheDogStateMachine {
-    fn from(state: ReadyEndState) -> Self {
-        match state {
-            ReadyEndState::Complete(walking) => walking.into(),
-            ReadyEndState::Continue(ready) => ready.into(),
-        }
+end_state! {
+    GameOverEndState from GameOver {
+        Complete(Ready),
+        Restart(Walking),
+        WatchReplay(Replaying),
+        Continue(GameOver),
     }
 }
 
-impl WalkTheDogState<Walking> {
-    fn update(mut self, keystate: &KeyState) -> WalkingEndState {
-        if keystate.is_pressed("Space") {
-            self.walk.boy.jump();
+impl WalkTheDogState<Replaying> {
+    fn update(mut self, keystate: &KeyState) -> ReplayingEndState {
+        let chosen = self._state.menu.as_mut().and_then(|menu| {
+            menu.sync(&self.walk.target, self._state.viewer.menu());
+            menu.clicked()
+        });
+        let control = match chosen {
+            Some(action) => self._state.viewer.activate(action),
+            None => self._state.viewer.handle(keystate),
+        };
+        match control {
+            Some(Control::Done) => return ReplayingEndState::Complete(self.leave()),
+            Some(Control::Seek(frame)) => self.seek(frame),
+            None => {}
         }
+        let frames = self._state.viewer.frames_to_play(self.walk.elapsed_frames);
+        (0..frames).for_each(|_frame| self.play_frame());
+        ReplayingEndState::Continue(self)
+    }
 
-        self.walk.boy.update();
-
-        let walking_speed = self.walk.velocity();
-        let [first_background, second_background] = &mut self.walk.backgrounds;
-        first_background.move_horizontally(walking_speed);
-        second_background.move_horizontally(walking_speed);
+    // Steps one frame with the keys as they were on it in the run.
+    fn play_frame(&mut self) {
+        let frame = self.walk.elapsed_frames;
+        let viewer = &self._state.viewer;
+        self.walk.step(&recorded_input(viewer.input(frame)));
+        let walk = &self.walk;
+        self._state
+            .viewer
+            .verify(walk.elapsed_frames, || walk.world_hash().short());
+        self._state
+            .viewer
+            .keyframe(walk.elapsed_frames, || walk.snapshot());
+    }
 
-        if first_background.right() < 0 {
-            first_background.set_x(second_background.right());
+    // Jumps to the nearest keyframe when it saves stepping, then plays on.
+    fn seek(&mut self, frame: u32) {
+        let frame = frame.min(self._state.viewer.frames());
+        let (keyframe, snapshot) = self._state.viewer.keyframe_before(frame);
+        if frame < self.walk.elapsed_frames || keyframe > self.walk.elapsed_frames {
+            self.walk.restore(snapshot.clone());
         }
-
-        if second_background.right() < 0 {
-            second_background.set_x(first_background.right());
+        while self.walk.elapsed_frames < frame {
+            self.play_frame();
         }
+    }
 
-        self.walk.backgrounds.iter_mut().for_each(|background| {
-            background.move_horizontally(walking_speed);
-        });
-
-        self.walk.obstacles.retain(|obstacle| obstacle.right() > 0);
+    // Back to the end of the run, where the game over screen was.
+    fn leave(mut self) -> WalkTheDogState<GameOver> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        self.seek(self._state.viewer.frames());
+        self.walk.boy.unmute(self._state.sound);
+        self.walk.broadcast = self._state.broadcast;
+        let game_over = GameOver::show(&self.walk.target, self._state.game_over);
+        WalkTheDogState {
+            _state: game_over,
+            walk: self.walk,
+        }
+    }
+}
 
-        self.walk.obstacles.iter_mut().for_each(|obstacle| {
-            obstacle.move_horizontally(walking_speed);
-            obstacle.check_intersection(&mut self.walk.boy);
-        });
+end_state! {
+    ReplayingEndState from Replaying {
+        Complete(GameOver),
+        Continue(Replaying),
+    }
+}
 
-        if self.walk.timeline < TIMELINE_MINIMUM {
-            self.walk.generate_next_segment();
+impl WalkTheDogState<LevelComplete> {
+    fn update(mut self) -> LevelCompleteEndState {
+        if self._state.level_select_pressed() {
+            LevelCompleteEndState::Complete(self.back_to_levels())
         } else {
-            self.walk.timeline += walking_speed;
+            LevelCompleteEndState::Continue(self)
         }
-        if self.walk.knocked_out() {
-            WalkingEndState::Complete(self.end_game())
-        } else {
-            WalkingEndState::Continue(self)
+    }
+
+    fn back_to_levels(mut self) -> WalkTheDogState<LevelSelect> {
+        browser::hide_ui(&self.walk.target).expect("Failed to hide UI!");
+        if let Some(story) = &mut self.walk.story {
+            story.quit();
         }
+        WalkTheDogState::level_select(Walk::reset(self.walk))
     }
+}
 
-    fn end_game(self) -> WalkTheDogState<GameOver> {
-        let receiver = browser::draw_ui("<button id='new_game'>New Game</button>")
-            .and_then(|_unit| browser::find_html_element_by_id("new_game"))
-            .map(|element| engine::add_click_handler(element))
-            .unwrap();
+end_state! {
+    LevelCompleteEndState from LevelComplete {
+        Complete(LevelSelect),
+        Continue(LevelComplete),
+    }
+}
 
-        WalkTheDogState {
-            _state: GameOver {
-                new_game_event: receiver,
-            },
-            walk: self.walk,
-        }
+impl From<WalkTheDogState<Intro>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Intro>) -> Self {
+        WalkTheDogStateMachine::Intro(state)
+    }
+}
+
+impl From<WalkTheDogState<Diagnostics>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Diagnostics>) -> Self {
+        WalkTheDogStateMachine::Diagnostics(state)
     }
 }
 
-enum WalkingEndState {
-    Complete(WalkTheDogState<GameOver>),
-    Continue(WalkTheDogState<Walking>),
+impl From<WalkTheDogState<ConsentPrompt>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<ConsentPrompt>) -> Self {
+        WalkTheDogStateMachine::ConsentPrompt(state)
+    }
 }
 
-impl From<WalkingEndState> for WalkTheDogStateMachine {
-    fn from(state: WalkingEndState) -> Self {
-        match state {
-            WalkingEndState::Complete(gameover) => gameover.into(),
-            WalkingEndState::Continue(walking) => walking.into(),
-        }
+impl From<WalkTheDogState<Title>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Title>) -> Self {
+        WalkTheDogStateMachine::Title(state)
     }
 }
 
-impl WalkTheDogState<GameOver> {
-    fn update(mut self) -> GameOverEndState {
-        if self._state.new_game_pressed() {
-            GameOverEndState::Complete(self.new_game())
-        } else {
-            GameOverEndState::Continue(self)
-        }
+impl From<WalkTheDogState<ModeSelect>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<ModeSelect>) -> Self {
+        WalkTheDogStateMachine::ModeSelect(state)
     }
+}
 
-    fn new_game(self) -> WalkTheDogState<Ready> {
-        browser::hide_ui().expect("Failed to hide UI!");
-        WalkTheDogState {
-            _state: Ready,
-            walk: Walk::reset(self.walk),
-        }
+impl From<WalkTheDogState<Stats>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Stats>) -> Self {
+        WalkTheDogStateMachine::Stats(state)
     }
 }
 
-enum GameOverEndState {
-    Complete(WalkTheDogState<Ready>),
-    Continue(WalkTheDogState<GameOver>),
+impl From<WalkTheDogState<Settings>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Settings>) -> Self {
+        WalkTheDogStateMachine::Settings(state)
+    }
 }
 
-impl From<GameOverEndState> for WalkTheDogStateMachine {
-    fn from(state: GameOverEndState) -> Self {
-        match state {
-            GameOverEndState::Complete(ready) => ready.into(),
-            GameOverEndState::Continue(gameover) => gameover.into(),
-        }
+impl From<WalkTheDogState<LevelSelect>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<LevelSelect>) -> Self {
+        WalkTheDogStateMachine::LevelSelect(state)
+    }
+}
+
+impl From<WalkTheDogState<LevelComplete>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<LevelComplete>) -> Self {
+        WalkTheDogStateMachine::LevelComplete(state)
     }
 }
 
@@ -1058,90 +5394,610 @@ impl From<WalkTheDogState<Walking>> for WalkTheDogStateMachine {
         WalkTheDogStateMachine::Walking(state)
     }
 }
+impl From<WalkTheDogState<Scripted>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Scripted>) -> Self {
+        WalkTheDogStateMachine::Scripted(state)
+    }
+}
+impl From<WalkTheDogState<Practicing>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Practicing>) -> Self {
+        WalkTheDogStateMachine::Practicing(state)
+    }
+}
+impl From<WalkTheDogState<Lobby>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Lobby>) -> Self {
+        WalkTheDogStateMachine::Lobby(state)
+    }
+}
+impl From<WalkTheDogState<Spectating>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Spectating>) -> Self {
+        WalkTheDogStateMachine::Spectating(state)
+    }
+}
+impl From<WalkTheDogState<RewindOffer>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<RewindOffer>) -> Self {
+        WalkTheDogStateMachine::RewindOffer(state)
+    }
+}
 impl From<WalkTheDogState<GameOver>> for WalkTheDogStateMachine {
     fn from(state: WalkTheDogState<GameOver>) -> Self {
         WalkTheDogStateMachine::GameOver(state)
     }
 }
 
+impl From<WalkTheDogState<Replaying>> for WalkTheDogStateMachine {
+    fn from(state: WalkTheDogState<Replaying>) -> Self {
+        WalkTheDogStateMachine::Replaying(state)
+    }
+}
+
+struct Diagnostics {
+    play_anyway_event: UnboundedReceiver<()>,
+}
+struct Intro {
+    cutscene: Cutscene,
+}
+struct ConsentPrompt {
+    offline_event: UnboundedReceiver<()>,
+    online_event: UnboundedReceiver<()>,
+}
+struct Title {
+    play_event: UnboundedReceiver<()>,
+    modes_event: UnboundedReceiver<()>,
+    stats_event: UnboundedReceiver<()>,
+    settings_event: UnboundedReceiver<()>,
+}
+
+struct Settings {
+    button_events: Vec<(SettingsAction, UnboundedReceiver<()>)>,
+    volume_events: Vec<(Fader, UnboundedReceiver<()>)>,
+}
+
+// What a button on the settings screen does when pressed.
+#[derive(Clone, Copy)]
+enum SettingsAction {
+    // Shows the whole screen again in the new language.
+    Language,
+    Apply(fn(&WalkTheDogState<Settings>)),
+    Back,
+}
+
+// A button on the settings screen: its class, its label and what it does.
+type SettingsButton = (&'static str, fn() -> String, SettingsAction);
+
+// Shown above the volume sliders.
+const SETTINGS_BUTTONS: [SettingsButton; 7] = [
+    (
+        "language",
+        || language_label(i18n::current()),
+        SettingsAction::Language,
+    ),
+    (
+        "graphics",
+        || setting_label("Graphics", GraphicsSetting::load().label()),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_graphics),
+    ),
+    (
+        "frame_rate",
+        || setting_label("Frame rate", FrameRateSetting::load().label()),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_frame_rate),
+    ),
+    (
+        "privacy",
+        || setting_label("Sharing", Consent::load().label()),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_consent),
+    ),
+    (
+        "announcer",
+        || setting_label("Announcer", AnnouncerSetting::load().label()),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_announcer),
+    ),
+    (
+        "rumble",
+        || setting_label("Rumble", RumbleSetting::load().label()),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_rumble),
+    ),
+    (
+        "menus",
+        || setting_label("Replay controls", MenuStyle::load().label()),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_menu_style),
+    ),
+];
+
+// Shown under the volume sliders.
+const SOUND_BUTTONS: [SettingsButton; 1] = [(
+    "pause_music",
+    || setting_label("Music when paused", PauseMusicSetting::load().label()),
+    SettingsAction::Apply(WalkTheDogState::<Settings>::cycle_pause_music),
+)];
+
+// Shown under the save code they read and write.
+const SAVE_BUTTONS: [SettingsButton; 3] = [
+    (
+        "export",
+        || text("Export").to_string(),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::export_save),
+    ),
+    (
+        "import",
+        || text("Import").to_string(),
+        SettingsAction::Apply(WalkTheDogState::<Settings>::import_save),
+    ),
+    ("back", || text("Back").to_string(), SettingsAction::Back),
+];
+
+// In the order they are shown.
+fn all_settings_buttons() -> impl Iterator<Item = &'static SettingsButton> {
+    SETTINGS_BUTTONS
+        .iter()
+        .chain(SOUND_BUTTONS.iter())
+        .chain(SAVE_BUTTONS.iter())
+}
+
+fn settings_buttons_html(buttons: &[SettingsButton]) -> String {
+    buttons
+        .iter()
+        .map(|(class, label, _action)| format!("<button class='{}'>{}</button>", class, label()))
+        .collect()
+}
+
+struct Stats {
+    stats: LifetimeStats,
+    back_event: UnboundedReceiver<()>,
+}
+
+// Setting names and values are both keys, resolved when the label is shown.
+fn setting_label(name: &'static str, value: &'static str) -> String {
+    format!("{}: {}", text(name), text(value))
+}
+
+fn language_label(locale: Locale) -> String {
+    format!("{}: {}", text("Language"), locale.label())
+}
+
+fn pressed(event: &mut UnboundedReceiver<()>) -> bool {
+    matches!(event.try_next(), Ok(Some(())))
+}
+
+// The keys for a frame of a recorded or broadcast run.
+fn recorded_input(input: Input) -> KeyState {
+    let keys = [
+        (input.jumped, "Space"),
+        (input.dashed, RECORDED_DASH),
+        (input.grabbing, GRAB_KEY),
+    ];
+    let held: Vec<&str> = keys
+        .iter()
+        .filter(|(held, _key)| *held)
+        .map(|(_held, key)| *key)
+        .collect();
+    KeyState::holding(&held)
+}
+
+struct LevelSelect {
+    level_events: Vec<UnboundedReceiver<()>>,
+}
+
+impl LevelSelect {
+    fn selected_level(&mut self) -> Option<usize> {
+        self.level_events
+            .iter_mut()
+            .position(|event| matches!(event.try_next(), Ok(Some(()))))
+    }
+}
+
+struct ModeSelect {
+    modes: Vec<GameMode>,
+    mode_events: Vec<UnboundedReceiver<()>>,
+    back_event: UnboundedReceiver<()>,
+}
+
+impl ModeSelect {
+    fn selected_mode(&mut self) -> Option<GameMode> {
+        self.mode_events
+            .iter_mut()
+            .position(|event| matches!(event.try_next(), Ok(Some(()))))
+            .and_then(|index| self.modes.get(index).copied())
+    }
+}
+
 struct Ready;
+struct Lobby {
+    race: Race,
+    back_event: UnboundedReceiver<()>,
+    failure_shown: bool,
+}
+struct Spectating {
+    spectator: Spectator,
+    running: bool,
+    // Logged once a run, at the first check that failed.
+    desynced: bool,
+}
 struct Walking;
+struct Scripted {
+    cutscene: Cutscene,
+}
+struct Practicing {
+    practice: Practice,
+}
+struct RewindOffer {
+    rewind_event: UnboundedReceiver<()>,
+    give_up_event: UnboundedReceiver<()>,
+}
+
+const RESTART_KEY: &str = "KeyR";
+const NEW_GAME_KEY: &str = "Enter";
+const SKIP_SCENE_KEY: &str = "Enter";
+
 struct GameOver {
     new_game_event: UnboundedReceiver<()>,
+    watch_replay_event: Option<UnboundedReceiver<()>>,
+    // Kept to show again after watching the replay.
+    html: String,
+    // The restart and new game keys only count once they have been let go
+    // since the run ended, so a key still held from the run does not restart
+    // it.
+    keys_armed: bool,
 }
 
 impl GameOver {
+    fn new(new_game_event: UnboundedReceiver<()>) -> Self {
+        GameOver {
+            new_game_event,
+            watch_replay_event: None,
+            html: String::new(),
+            keys_armed: false,
+        }
+    }
+
+    // Draws the screen, which only has a replay button for runs that can be
+    // played again from their seed.
+    fn show(target: &Target, html: String) -> Self {
+        let receiver = browser::draw_ui(target, &html)
+            .and_then(|_unit| browser::find_ui_element(target, ".new_game"))
+            .map(|element| engine::add_click_handler(target, element))
+            .unwrap();
+        let watch_replay_event = browser::find_ui_element(target, ".watch_replay")
+            .ok()
+            .map(|element| engine::add_click_handler(target, element));
+        GameOver {
+            watch_replay_event,
+            html,
+            ..GameOver::new(receiver)
+        }
+    }
+
     fn new_game_pressed(&mut self) -> bool {
         matches!(self.new_game_event.try_next(), Ok(Some(())))
     }
+
+    fn watch_replay_pressed(&mut self) -> bool {
+        matches!(
+            self.watch_replay_event
+                .as_mut()
+                .map(|event| event.try_next()),
+            Some(Ok(Some(())))
+        )
+    }
+
+    fn key_pressed(&mut self, keystate: &KeyState) -> Option<&'static str> {
+        let pressed = [RESTART_KEY, NEW_GAME_KEY]
+            .into_iter()
+            .find(|key| keystate.is_pressed(key));
+        let armed = self.keys_armed;
+        self.keys_armed = pressed.is_none();
+        pressed.filter(|_key| armed)
+    }
+}
+
+// Watching the run that just ended, with the game over screen to go back to.
+struct Replaying {
+    viewer: ReplayViewer<WalkSnapshot>,
+    // The viewer's buttons, when they are on the page rather than the canvas.
+    menu: Option<Box<PageMenu<Action>>>,
+    game_over: String,
+    broadcast: Option<Broadcast>,
+    sound: (Audio, Haptics),
+}
+
+struct LevelComplete {
+    level_select_event: UnboundedReceiver<()>,
+}
+
+impl LevelComplete {
+    fn level_select_pressed(&mut self) -> bool {
+        matches!(self.level_select_event.try_next(), Ok(Some(())))
+    }
 }
 const TIMELINE_MINIMUM: i16 = 1000;
+const HITBOX_COLOR: Color = Color::rgb(255, 0, 0);
+const HUD_MARGIN: i16 = 20;
+const TIMER_Y: i16 = 40;
+const COINS_Y: i16 = 70;
+const TREATS_Y: i16 = 100;
+const DASH_METER_Y: i16 = 120;
+const HUD_SHADOW: Color = Color::rgba(0, 0, 0, 0.5);
+const HUD_TEXT: Color = Color::rgb(255, 255, 255);
+
+// Light text with a drop shadow reads over both the sky and the trees.
+fn hud_style() -> TextStyle {
+    TextStyle::new()
+        .with_color(HUD_TEXT)
+        .with_shadow(HUD_SHADOW, Point { x: 2, y: 2 })
+}
 const OBSTACLE_BUFFER: i16 = 20;
+// One in this many jumpable obstacles gets a gem chain over it.
+const GEM_CHAIN_ODDS: u32 = 3;
+const GOAL_BUFFER: i16 = 300;
+#[cfg(debug_assertions)]
+const BOT_MIN_LOOKAHEAD: i16 = 10;
+#[cfg(debug_assertions)]
+const BOT_MAX_LOOKAHEAD: i16 = 120;
 
 #[async_trait(?Send)]
 impl Game for WalkTheDog {
     async fn initialize(&self) -> Result<Box<dyn Game>> {
         match self.machine {
             None => {
-                let json = browser::fetch_json("rhb.json").await?;
-                let audio = Audio::new()?;
-                let sound = audio.load_sound("SFX_Jump_23.mp3").await?;
-                let background_music = audio.load_sound("background_song.mp3").await?;
-                audio.play_looping_sound(&background_music)?;
+                cloud_save::sync_in_background();
+                let packs = packs::load_from_browser().await;
+                let pack_seasons = packs
+                    .iter()
+                    .flat_map(|pack| pack.seasons.iter().cloned())
+                    .collect();
+                let pack_segments = packs.into_iter().flat_map(|pack| pack.segments).collect();
+                let assets = Assets::load("assets.json", Quality::detect(), pack_seasons)
+                    .await
+                    .unwrap_or_else(|err| {
+                        log!("Loading original assets {:#?}", err);
+                        Assets::default()
+                    });
+                // A run link decides the seed and mutators for everyone who opens it.
+                let run_link = RunLink::from_browser()?;
+                let config = match &run_link {
+                    Some(run_link) => run_link.config.clone(),
+                    None => browser::query_param("mutators")?
+                        .map(|names| GameConfig::from_names(&names))
+                        .unwrap_or_default()
+                        .with_tick_rate(tick_rate_from_browser()?),
+                };
+                let cheats = browser::query_param("cheats")?
+                    .map(|names| Cheats::from_names(&names))
+                    .unwrap_or_default();
+                let seed = run_link
+                    .map(|run_link| run_link.seed)
+                    .or(cheats.fixed_seed)
+                    .unwrap_or_else(|| thread_rng().gen());
+                let mut audio = Audio::new()?;
+                let captions = browser::query_param("captions")?
+                    .map(|_| Captions::new(audio.enable_captions()));
+                let sound = audio
+                    .load_sound(&[assets.path("SFX_Jump_23.mp3")])
+                    .await?
+                    .with_caption("[jump]");
+                audio.stream_music(assets.path("background_song.mp3"), Some("[music playing]"))?;
+                if let Err(err) = audio.pause_when_hidden(&self.target) {
+                    log!("Could not pause audio with the page {:#?}", err);
+                }
+                let hitboxes = hitboxes::load_hitboxes("hitboxes.json")
+                    .await
+                    .unwrap_or_else(|err| {
+                        log!("Using the default hitboxes {:#?}", err);
+                        Hitboxes::default()
+                    });
+                let mut attachments = attachments::load_attachments("attachments.json")
+                    .await
+                    .unwrap_or_else(|err| {
+                        log!("Wearing nothing {:#?}", err);
+                        AttachmentData::default()
+                    });
+                if let Some(season) = assets.season() {
+                    attachments
+                        .attachments
+                        .extend(season.attachments.iter().cloned());
+                }
+                let mut report = Report::default();
+                let atlas = Rc::new(assets.load_atlas(&mut report).await?);
+                let boy_sheet = atlas.namespace("rhb");
+                report.check_animations("rhb.json", &boy_sheet, &REQUIRED_ANIMATIONS);
+                report.check_hitboxes("hitboxes.json", &hitboxes, &boy_sheet);
+                let worn: Vec<&str> = attachments
+                    .attachments
+                    .iter()
+                    .map(|attachment| attachment.sprite.as_str())
+                    .collect();
+                report.check_cells(
+                    "attachments.json",
+                    &atlas.sheet().frames,
+                    &worn,
+                    "The boy's outfit",
+                );
+                segments::check_platforms(&atlas, &mut report);
+                let treats = assets
+                    .season()
+                    .and_then(|season| season.collectible.as_ref())
+                    .and_then(|collectible| match Treats::new(collectible, &atlas) {
+                        Ok(treats) => Some(treats),
+                        Err(err) => {
+                            log!("Leaving out the seasonal collectible {:#?}", err);
+                            None
+                        }
+                    });
+                let snowfall = assets
+                    .season()
+                    .filter(|season| season.snow)
+                    .map(|_season| Snowfall::new(thread_rng().gen(), WIDTH, HEIGHT));
+                let footsteps = Rc::new(FootstepCues::synthesize(&audio));
+                let announcer = Announcer::synthesize(&audio);
                 let rhb = RedHatBoy::new(
-                    json.into_serde::<Sheet>()?,
-                    engine::load_image("rhb.png").await?,
+                    boy_sheet,
+                    atlas.image().clone(),
                     audio,
                     sound,
-                );
-                let background = engine::load_image("BG.png").await?;
-                let stone = engine::load_image("Stone.png").await?;
-                let platform_sheet = browser::fetch_json("tiles.json").await?;
-                let sprite_sheet = Rc::new(SpriteSheet::new(
-                    platform_sheet.into_serde::<Sheet>()?,
-                    engine::load_image("tiles.png").await?,
-                ));
-                let background_width = background.width() as i16;
+                    config.physics,
+                    f64::from(config.tick_rate.frame_size()),
+                )
+                .with_hitboxes(hitboxes)
+                .with_outfit(Outfit::new(attachments, &atlas))
+                .with_riding_sheet(atlas.namespace("dog"))
+                .with_footsteps(footsteps);
+                let background = match browser::query_param("background")?.as_deref() {
+                    Some("procedural") => {
+                        Background::Procedural(ProceduralBackground::new(thread_rng().gen()))
+                    }
+                    _ => {
+                        let background = assets.load_image("BG.png").await?;
+                        let background_width = background.width() as i16;
+                        Background::Image([
+                            Image::new(background.clone(), Point { x: 0, y: 0 }),
+                            Image::new(
+                                background,
+                                Point {
+                                    x: background_width,
+                                    y: 0,
+                                },
+                            ),
+                        ])
+                    }
+                };
+                let critters = Critters::new(background.critter_kinds(), thread_rng().gen());
+                let stone = MaskedImage::with_mask(assets.load_image("Stone.png").await?);
+                let sprite_sheet = atlas;
+                let story = match browser::query_param("mode")?.as_deref() {
+                    Some("story") => Some(Story::new(levels::load_levels("levels.json").await?)),
+                    _ => None,
+                };
                 let starting_obstacles = stone_and_platform(stone.clone(), sprite_sheet.clone(), 0);
                 let timeline = rightmost(&starting_obstacles);
 
-                let machine = WalkTheDogStateMachine::new(Walk {
+                let mut walk = Walk {
                     boy: rhb,
-                    backgrounds: [
-                        Image::new(background.clone(), Point { x: 0, y: 0 }),
-                        Image::new(
-                            background,
-                            Point {
-                                x: background_width,
-                                y: 0,
-                            },
-                        ),
-                    ],
+                    background,
+                    critters,
+                    day_cycle: DayCycle::new(),
                     obstacles: starting_obstacles,
                     obstacle_sheet: sprite_sheet,
                     stone,
                     timeline,
-                });
+                    target: self.target.clone(),
+                    story,
+                    pack_segments: Rc::new(pack_segments),
+                    distance: 0,
+                    boss: BossEncounter::new(),
+                    config: config.clone(),
+                    mode: GameMode::Endless,
+                    elapsed_frames: 0,
+                    rng: StdRng::seed_from_u64(seed),
+                    seed,
+                    replay: Replay::default(),
+                    dash_input: DashInput::default(),
+                    controller: Controller::default(),
+                    race: None,
+                    broadcast: spectate::broadcast_from_browser(),
+                    rewind: Rewind::new(),
+                    cheats: Cheats::default(),
+                    triggers: vec![],
+                    force_fields: vec![],
+                    environments: vec![],
+                    swing_points: vec![],
+                    swing: None,
+                    zone_events: EventBus::new(),
+                    despawn_events: EventBus::new(),
+                    entity_counts: EntityCounts::default(),
+                    zone_effects: ZoneEffects::default(),
+                    camera: Camera::new(),
+                    layers: Layers::default(),
+                    coins: vec![],
+                    coins_collected: 0,
+                    gem_chains: vec![],
+                    flash: Flash::default(),
+                    treats,
+                    snowfall,
+                    combo: Combo::default(),
+                    announcer,
+                    time_scale: TimeScale::default(),
+                    tick: f64::from(config.tick_rate.frame_size()),
+                    obstacles_cleared: 0,
+                    telegraph: Telegraph::new(&RunHistory::load()),
+                    audit: browser::query_param("audit")?.map(|param| Audit::from_param(&param)),
+                    narrating: true,
+                    lane: None,
+                    #[cfg(debug_assertions)]
+                    save_state: None,
+                    #[cfg(debug_assertions)]
+                    inspector: Inspector::new(),
+                    #[cfg(debug_assertions)]
+                    segment_spans: vec![],
+                    #[cfg(debug_assertions)]
+                    heatmap: None,
+                };
+                walk.set_cheats(cheats);
+                let machine = WalkTheDogStateMachine::new(walk, report);
                 Ok(Box::new(WalkTheDog {
                     machine: Some(machine),
+                    captions,
+                    target: self.target.clone(),
+                    config,
+                    #[cfg(debug_assertions)]
+                    console: Console::new(console_commands()),
+                    #[cfg(debug_assertions)]
+                    hitbox_editor: HitboxEditor::new(),
                 }))
             }
             Some(_) => Err(anyhow!("Error: Game is already initialized!")),
         }
     }
 
-    fn update(&mut self, keystate: &KeyState) {
-        if let Some(machine) = self.machine.take() {
+    fn update(&mut self, keystate: &KeyState, delta: f64) {
+        #[cfg(debug_assertions)]
+        if let Some(machine) = &mut self.machine {
+            let paused = self
+                .console
+                .update(keystate, &self.target, machine.walk_mut())
+                || self
+                    .hitbox_editor
+                    .update(keystate, &self.target, &mut machine.walk_mut().boy);
+            if paused {
+                machine.walk().boy.audio().pause();
+                return;
+            }
+            machine.walk().boy.audio().resume();
+            machine.walk_mut().inspect(keystate);
+        }
+
+        if let Some(mut machine) = self.machine.take() {
+            machine.walk_mut().set_tick(delta);
             self.machine.replace(machine.update(keystate));
         }
         assert!(self.machine.is_some());
+
+        if let Some(captions) = &mut self.captions {
+            captions.update();
+        }
+    }
+
+    fn frame_size(&self) -> f32 {
+        self.config.tick_rate.frame_size()
     }
 
     fn draw(&self, renderer: &Renderer) {
         renderer.clear(&Rect::new(Point { x: 0, y: 0 }, 600, 600));
 
         if let Some(machine) = &self.machine {
-            machine.draw(renderer);
+            if self.config.mirrored {
+                renderer.draw_mirrored(WIDTH, || machine.draw(renderer));
+            } else {
+                machine.draw(renderer);
+            }
+            #[cfg(debug_assertions)]
+            {
+                machine.walk().draw_inspector_panel(renderer);
+                self.hitbox_editor.draw(renderer, &machine.walk().boy);
+            }
+        }
+
+        if let Some(captions) = &self.captions {
+            captions.draw(renderer);
         }
     }
 }
@@ -1149,6 +6005,7 @@ impl Game for WalkTheDog {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{dash::DASH_KEY, swings::SwingPlacement};
     use futures::channel::mpsc::unbounded;
     use std::collections::HashMap;
     use web_sys::{AudioBuffer, AudioBufferOptions};
@@ -1156,55 +6013,534 @@ mod tests {
     use wasm_bindgen_test::wasm_bindgen_test;
     wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
 
-    // #[wasm_bindgen_test]
-    fn test_transition_from_game_over_to_new_game() {
+    #[test]
+    fn rotated_trimmed_cells_map_back_to_their_logical_size() {
+        let cell: Cell = serde_json::from_str(
+            r#"{
+                "frame": {"x":10,"y":20,"w":71,"h":115},
+                "rotated": true,
+                "trimmed": true,
+                "spriteSourceSize": {"x":58,"y":8,"w":71,"h":115}
+            }"#,
+        )
+        .unwrap();
+
+        let atlas = cell.atlas_rect();
+        assert_eq!(
+            (atlas.x(), atlas.y(), atlas.width, atlas.height),
+            (10, 20, 115, 71)
+        );
+
+        let destination = cell.destination(&Point { x: 100, y: 200 });
+        assert_eq!(
+            (
+                destination.x(),
+                destination.y(),
+                destination.width,
+                destination.height
+            ),
+            (158, 208, 71, 115)
+        );
+    }
+
+    #[test]
+    fn only_changes_between_main_scenes_are_animated() {
+        assert_eq!(
+            transition_effect(Scene::Title, Scene::Ready),
+            Some(TransitionEffect::Fade)
+        );
+        assert_eq!(
+            transition_effect(Scene::Walking, Scene::GameOver),
+            Some(TransitionEffect::Iris)
+        );
+        assert_eq!(transition_effect(Scene::Walking, Scene::Other), None);
+        assert_eq!(transition_effect(Scene::Ready, Scene::Ready), None);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn end_states_make_up_the_screen_graph() {
+        let dot = state_graph::to_dot(&[WalkTheDogStateMachine::transition_graph()]);
+
+        assert!(
+            dot.contains("\"WalkTheDog.Walking\" -> \"WalkTheDog.GameOver\" [label=\"Complete\"];")
+        );
+        assert!(dot.contains("\"WalkTheDog.Spectating\" [label=\"Spectating\"];"));
+        assert!(!dot.contains("label=\"Continue\""));
+    }
+
+    #[test]
+    fn keys_held_from_the_run_are_ignored() {
         let (_, receiver) = unbounded();
-        let image = HtmlImageElement::new().unwrap();
+        let mut game_over = GameOver::new(receiver);
+        let held = KeyState::holding(&[RESTART_KEY]);
+
+        assert_eq!(game_over.key_pressed(&held), None);
+        assert_eq!(game_over.key_pressed(&KeyState::holding(&[])), None);
+        assert_eq!(game_over.key_pressed(&held), Some(RESTART_KEY));
+        assert_eq!(
+            game_over.key_pressed(&KeyState::holding(&[NEW_GAME_KEY])),
+            None
+        );
+        assert_eq!(game_over.key_pressed(&KeyState::holding(&[])), None);
+        assert_eq!(
+            game_over.key_pressed(&KeyState::holding(&[NEW_GAME_KEY])),
+            Some(NEW_GAME_KEY)
+        );
+    }
+
+    const BOY_ANIMATIONS: [&str; 5] = ["Idle", "Run", "Slide", "Jump", "Dead"];
+
+    // A boy with a blank image and `frame_count` sprites per animation.
+    fn test_boy(frame_count: u16) -> RedHatBoy {
         let audio = Audio::new().unwrap();
         let options = AudioBufferOptions::new(1, 3000.0);
         let sound = Sound {
             buffer: AudioBuffer::new(&options).unwrap(),
+            caption: None,
         };
-        let rhb = RedHatBoy::new(
+        let frames = BOY_ANIMATIONS
+            .iter()
+            .flat_map(|animation| {
+                (1..=frame_count).map(move |frame| {
+                    let cell: Cell =
+                        serde_json::from_str(r#"{"frame": {"x":0,"y":0,"w":70,"h":115}}"#).unwrap();
+                    (format!("{} ({}).png", animation, frame), cell)
+                })
+            })
+            .collect();
+        RedHatBoy::new(
             Sheet {
-                frames: HashMap::new(),
+                frames,
+                tags: vec![],
             },
-            image.clone(),
+            HtmlImageElement::new().unwrap(),
             audio,
             sound,
-        );
+            Physics::default(),
+            f64::from(GameConfig::default().tick_rate.frame_size()),
+        )
+    }
+
+    // A walk with blank images, one sprite per animation for the boy and
+    // stones the size of the real one.
+    fn test_walk(seed: u64) -> Walk {
+        let image = HtmlImageElement::new().unwrap();
+        let stone = HtmlImageElement::new().unwrap();
+        stone.set_width(90);
+        stone.set_height(54);
+        let rhb = test_boy(1);
         let sprite_sheet = SpriteSheet::new(
             Sheet {
                 frames: HashMap::new(),
+                tags: vec![],
             },
             image.clone(),
         );
-        let walk = Walk {
+        Walk {
             boy: rhb,
-            backgrounds: [
+            background: Background::Image([
                 Image::new(image.clone(), Point { x: 0, y: 0 }),
                 Image::new(image.clone(), Point { x: 0, y: 0 }),
-            ],
+            ]),
+            critters: Critters::new(&[], 0),
+            day_cycle: DayCycle::new(),
             obstacles: vec![],
             obstacle_sheet: Rc::new(sprite_sheet),
-            stone: image.clone(),
+            stone: MaskedImage::new(stone),
             timeline: 0,
-        };
-        let document = browser::document().unwrap();
-        document
+            target: Target::default(),
+            story: None,
+            pack_segments: Rc::new(vec![]),
+            distance: 0,
+            boss: BossEncounter::new(),
+            config: GameConfig::default(),
+            mode: GameMode::Endless,
+            elapsed_frames: 0,
+            rng: StdRng::seed_from_u64(seed),
+            seed,
+            replay: Replay::default(),
+            dash_input: DashInput::default(),
+            controller: Controller::default(),
+            race: None,
+            broadcast: None,
+            rewind: Rewind::new(),
+            cheats: Cheats::default(),
+            triggers: vec![],
+            force_fields: vec![],
+            environments: vec![],
+            swing_points: vec![],
+            swing: None,
+            zone_events: EventBus::new(),
+            despawn_events: EventBus::new(),
+            entity_counts: EntityCounts::default(),
+            zone_effects: ZoneEffects::default(),
+            camera: Camera::new(),
+            layers: Layers::default(),
+            coins: vec![],
+            coins_collected: 0,
+            gem_chains: vec![],
+            flash: Flash::default(),
+            treats: None,
+            snowfall: None,
+            combo: Combo::default(),
+            announcer: Announcer::default(),
+            time_scale: TimeScale::default(),
+            tick: f64::from(GameConfig::default().tick_rate.frame_size()),
+            obstacles_cleared: 0,
+            telegraph: Telegraph::default(),
+            audit: None,
+            narrating: true,
+            lane: None,
+            #[cfg(debug_assertions)]
+            save_state: None,
+            #[cfg(debug_assertions)]
+            inspector: Inspector::new(),
+            #[cfg(debug_assertions)]
+            segment_spans: vec![],
+            #[cfg(debug_assertions)]
+            heatmap: None,
+        }
+    }
+
+    // Plays seeded endless runs with the jumping bot, checking that nothing
+    // piles up as the world scrolls past and the score never goes backwards.
+    #[wasm_bindgen_test]
+    fn smoke_test_bot_plays_seeded_runs() {
+        const RUNS: u64 = 8;
+        const MAX_FRAMES: u32 = 60 * 60;
+        const LOOKAHEAD: i16 = 60;
+        const MAX_OBSTACLES: usize = 64;
+        const MAX_COINS: usize = 64;
+        let running = KeyState::holding(&[]);
+        let jumping = KeyState::holding(&["Space"]);
+
+        (0..RUNS).for_each(|seed| {
+            let mut walk = test_walk(seed);
+            walk.boy.run_right();
+            let (mut distance, mut coins) = (walk.distance, walk.coins_collected);
+            for _ in 0..MAX_FRAMES {
+                if walk.knocked_out() {
+                    break;
+                }
+                let keystate = if walk.bot_should_jump(LOOKAHEAD) {
+                    &jumping
+                } else {
+                    &running
+                };
+                walk.step(keystate);
+
+                assert!(walk.distance >= distance, "seed {} went backwards", seed);
+                assert!(walk.coins_collected >= coins, "seed {} lost coins", seed);
+                assert!(
+                    walk.obstacles.len() <= MAX_OBSTACLES,
+                    "seed {} kept {} obstacles",
+                    seed,
+                    walk.obstacles.len()
+                );
+                assert!(walk.coins.len() <= MAX_COINS, "seed {} kept coins", seed);
+                (distance, coins) = (walk.distance, walk.coins_collected);
+            }
+        });
+    }
+
+    // Two walks from one seed, fed the same keys, hash the same on every
+    // frame, and so do a walk and the shadow an audit would copy from it.
+    #[wasm_bindgen_test]
+    fn seeded_walks_stay_in_lockstep() {
+        const FRAMES: u32 = 60 * 20;
+        const LOOKAHEAD: i16 = 60;
+        let running = KeyState::holding(&[]);
+        let jumping = KeyState::holding(&["Space"]);
+        let mut live = test_walk(7);
+        let mut twin = test_walk(7);
+        live.boy.run_right();
+        twin.boy.run_right();
+        let mut shadow = live.shadow();
+
+        for _ in 0..FRAMES {
+            if live.knocked_out() {
+                break;
+            }
+            let keystate = if live.bot_should_jump(LOOKAHEAD) {
+                &jumping
+            } else {
+                &running
+            };
+            live.step(keystate);
+            twin.step(keystate);
+            shadow.step(keystate);
+
+            assert_eq!(
+                live.world_hash(),
+                twin.world_hash(),
+                "frame {}",
+                live.elapsed_frames
+            );
+            assert_eq!(
+                live.world_hash(),
+                shadow.world_hash(),
+                "frame {}",
+                live.elapsed_frames
+            );
+        }
+    }
+
+    // Random events from every state, including ones a state ignores. Landings
+    // are on surfaces anywhere between the top of the screen and the floor.
+    fn random_event(rng: &mut StdRng) -> Event {
+        const SURFACES: [Surface; 5] = [
+            Surface::Normal,
+            Surface::ConveyorLeft,
+            Surface::ConveyorRight,
+            Surface::Icy,
+            Surface::Sticky,
+        ];
+        match rng.gen_range(0..11) {
+            0 => Event::Run,
+            1 => Event::Slide,
+            2 => Event::KnockOut,
+            3 => Event::Jump,
+            4 => Event::Land(
+                rng.gen_range(0..=HEIGHT),
+                SURFACES[rng.gen_range(0..SURFACES.len())],
+            ),
+            5 => Event::FallOff,
+            6 => Event::Dash,
+            7 => Event::Stumble,
+            8 => Event::Grab,
+            9 => Event::Release(Point {
+                x: rng.gen_range(-10..=10),
+                y: rng.gen_range(-10..=10),
+            }),
+            _ => Event::Update,
+        }
+    }
+
+    // He never sinks through the floor and never shows a frame past the end
+    // of his current animation.
+    fn assert_boy_invariants(boy: &RedHatBoy, floor: i16, seed: u64, step: usize) {
+        let state_machine = &boy.state_machine;
+        let context = state_machine.context();
+        assert!(
+            context.position().y <= floor,
+            "seed {} sank to {} at step {} while {}",
+            seed,
+            context.position().y,
+            step,
+            state_machine.name()
+        );
+        let length = context.animations.length(state_machine.frame_name());
+        assert!(
+            context.elapsed() <= length,
+            "seed {} was {}ms into {}ms at step {} while {}",
+            seed,
+            context.elapsed(),
+            length,
+            step,
+            state_machine.name()
+        );
+    }
+
+    fn add_ui_container() {
+        browser::document()
+            .unwrap()
             .body()
             .unwrap()
             .insert_adjacent_html("afterbegin", "<div id='ui'></div>")
             .unwrap();
-        browser::draw_ui("<p>This is the UI</p>").unwrap();
+    }
+
+    #[wasm_bindgen_test]
+    fn fuzz_boy_state_machine_with_random_events() {
+        const SEQUENCES: u64 = 1000;
+        const EVENTS: usize = 100;
+
+        (0..SEQUENCES).for_each(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut boy = test_boy(4);
+            // He starts out standing on the floor.
+            let floor = boy.pos_y();
+            for step in 0..EVENTS {
+                let event = random_event(&mut rng);
+                boy.state_machine = boy.state_machine.clone().transition(event);
+                assert_boy_invariants(&boy, floor, seed, step);
+            }
+        });
+    }
+
+    #[wasm_bindgen_test]
+    fn fuzz_walk_the_dog_with_random_input() {
+        const SEQUENCES: u64 = 20;
+        const UPDATES: usize = 600;
+        const INPUTS: [&[&str]; 7] = [
+            &[],
+            &["Space"],
+            &["ArrowRight"],
+            &["ArrowRight", "Space"],
+            &["ArrowUp", "Space"],
+            &["Escape"],
+            &["KeyS", "KeyR"],
+        ];
+        add_ui_container();
+
+        (0..SEQUENCES).for_each(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let walk = test_walk(seed);
+            let floor = walk.boy.pos_y();
+            let mut machine = WalkTheDogStateMachine::Ready(WalkTheDogState::new(walk));
+            for step in 0..UPDATES {
+                let keystate = KeyState::holding(INPUTS[rng.gen_range(0..INPUTS.len())]);
+                machine = machine.update(&keystate);
+                assert_boy_invariants(&machine.walk().boy, floor, seed, step);
+            }
+        });
+    }
+
+    #[wasm_bindgen_test]
+    fn restart_reuses_the_boy_and_runs_straight_away() {
+        let (_, receiver) = unbounded();
+        add_ui_container();
+        let mut walk = test_walk(0);
+        walk.boy.run_right();
+        (0..120).for_each(|_| walk.step(&KeyState::holding(&[])));
+        let animations = walk.boy.state_machine.context().animations.clone();
+
+        let machine = WalkTheDogStateMachine::GameOver(WalkTheDogState {
+            _state: GameOver::new(receiver),
+            walk,
+        })
+        .update(&KeyState::holding(&[]))
+        .update(&KeyState::holding(&[RESTART_KEY]));
+
+        assert!(matches!(machine, WalkTheDogStateMachine::Walking(_)));
+        let walk = machine.walk();
+        assert_eq!(walk.distance, 0);
+        assert!(Rc::ptr_eq(
+            &walk.boy.state_machine.context().animations,
+            &animations
+        ));
+    }
+
+    // Wood just ahead of him, or stone, with a dash started into it.
+    fn dash_into(barrier: fn(MaskedImage, i16) -> Box<dyn Obstacle>) -> Walk {
+        let mut walk = test_walk(0);
+        walk.boy.run_right();
+        (0..10).for_each(|_| walk.step(&KeyState::holding(&[])));
+        walk.obstacles = vec![barrier(
+            walk.stone.clone(),
+            walk.boy.bounding_box().right() + 10,
+        )];
+        walk.step(&KeyState::holding(&[RECORDED_DASH]));
+        (0..40).for_each(|_| walk.step(&KeyState::holding(&[])));
+        walk
+    }
+
+    #[wasm_bindgen_test]
+    fn dashes_smash_wood_but_not_stone() {
+        let walk = dash_into(segments::wooden_barrier);
+        assert!(!walk.knocked_out());
+        assert!(walk.obstacles[0].hitboxes().is_empty());
+        assert_eq!(walk.replay.dashes(), &[10]);
+
+        let walk = dash_into(segments::barrier);
+        assert!(walk.knocked_out());
+        assert_eq!(walk.boy.death_cause, Some(DeathCause::Stone));
+    }
+
+    #[wasm_bindgen_test]
+    fn dashes_shrug_off_hits_and_wait_for_the_meter() {
+        let mut boy = test_boy(4);
+        boy.run_right();
+        boy.dash();
+        assert!(boy.dashing());
+        boy.knock_out(DeathCause::Platform);
+        assert!(!boy.knocked_out());
+
+        (0..20).for_each(|_| boy.update());
+        assert!(!boy.dashing());
+        boy.dash();
+        assert!(!boy.dashing());
+        assert!(!boy.dash_meter().ready());
+    }
+
+    #[wasm_bindgen_test]
+    fn wood_knocks_him_out_without_a_dash() {
+        let mut walk = test_walk(0);
+        walk.boy.run_right();
+        walk.obstacles = vec![segments::wooden_barrier(
+            walk.stone.clone(),
+            walk.boy.bounding_box().right() + 10,
+        )];
+        (0..40).for_each(|_| walk.step(&KeyState::holding(&[])));
+
+        assert!(walk.knocked_out());
+        assert_eq!(walk.boy.death_cause, Some(DeathCause::Wood));
+    }
+
+    #[wasm_bindgen_test]
+    fn swings_carry_him_along_until_he_lets_go() {
+        let mut walk = test_walk(0);
+        walk.boy.run_right();
+        walk.obstacles.clear();
+        walk.step(&KeyState::holding(&["Space"]));
+        let hands = walk.boy.hands();
+        let placement = SwingPlacement {
+            x: hands.x + 40,
+            y: hands.y - 120,
+            reach: 200,
+        };
+        walk.swing_points = vec![SwingPoint::new(&placement, 0)];
+
+        let grabbing = KeyState::holding(&[GRAB_KEY]);
+        walk.step(&grabbing);
+        assert!(walk.boy.swinging());
+        let distance = walk.distance;
+        (0..10).for_each(|_| walk.step(&grabbing));
+        assert!(walk.distance > distance);
+        assert!(walk.replay.input(5).grabbing);
+
+        walk.step(&KeyState::holding(&[]));
+        assert!(!walk.boy.swinging());
+        assert!(walk.swing.is_none());
+        assert_eq!(walk.boy.state_machine.name(), "Jumping");
+    }
+
+    #[wasm_bindgen_test]
+    fn dog_sprints_run_twice_as_fast_and_only_jump() {
+        let mut walk = test_walk(0);
+        walk.boy.run_right();
+        walk.obstacles.clear();
+        let running = KeyState::holding(&[]);
+        walk.step(&running);
+        let before = walk.distance;
+        walk.step(&running);
+        let stride = walk.distance - before;
+
+        walk.controller.mount(200);
+        let before = walk.distance;
+        walk.step(&KeyState::holding(&[DASH_KEY]));
+        assert_eq!(walk.distance - before, stride * 2);
+        assert!(!walk.boy.dashing());
+
+        (0..40).for_each(|_| walk.step(&running));
+        assert_eq!(walk.controller, Controller::OnFoot);
+        assert!(walk.boy.riding.is_none());
+    }
+
+    // #[wasm_bindgen_test]
+    fn test_transition_from_game_over_to_new_game() {
+        let (_, receiver) = unbounded();
+        let walk = test_walk(0);
+        let document = browser::document().unwrap();
+        add_ui_container();
+        browser::draw_ui(&Target::default(), "<p>This is the UI</p>").unwrap();
         let state = WalkTheDogState {
-            _state: GameOver {
-                new_game_event: receiver,
-            },
+            _state: GameOver::new(receiver),
             walk: walk,
         };
         state.new_game();
-        let ui = browser::find_html_element_by_id("ui").unwrap();
+        let ui = document.get_element_by_id("ui").unwrap();
         assert_eq!(ui.child_element_count(), 0);
     }
 }
@@ -0,0 +1,197 @@
+use crate::{
+    engine::{Color, Point, Rect, Renderer},
+    fairness::JumpEnvelope,
+};
+
+const GEM_RADIUS: i16 = 8;
+const GEM_COLOR: Color = Color::rgb(90, 210, 255);
+const GEMS_PER_CHAIN: usize = 5;
+// A whole chain in one jump is worth this many coins per gem on top.
+const CHAIN_MULTIPLIER: u32 = 3;
+const FLASH_FRAMES: u8 = 12;
+const FLASH_ALPHA: f64 = 0.6;
+const FLASH_COLOR: Color = Color::rgb(255, 255, 255);
+
+// Gems laid along the path of one jump, worth a bonus only when every one
+// is taken before he lands.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GemChain {
+    gems: Vec<Point>,
+    taken: usize,
+    broken: bool,
+}
+
+// What a frame of collecting did to a chain.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChainProgress {
+    pub collected: u32,
+    pub bonus: u32,
+}
+
+// `count` points along the jump over `obstacle` that starts from the middle
+// of its takeoff window, each where the middle of `boy` will be. `None` if
+// the obstacle cannot be jumped at this speed.
+pub fn jump_path(
+    obstacle: &Rect,
+    boy: &Rect,
+    envelope: &JumpEnvelope,
+    count: usize,
+) -> Option<Vec<Point>> {
+    let (nearest, farthest) =
+        envelope.takeoff_window(obstacle.width, obstacle.height, boy.width)?;
+    let takeoff = obstacle.x() - (nearest + farthest) / 2;
+    Some(
+        envelope
+            .arc(count)
+            .into_iter()
+            .map(|(scrolled, lift)| Point {
+                x: takeoff - boy.width / 2 + scrolled,
+                y: obstacle.bottom() - boy.height / 2 - lift,
+            })
+            .collect(),
+    )
+}
+
+impl GemChain {
+    pub fn over(obstacle: &Rect, boy: &Rect, envelope: &JumpEnvelope) -> Option<Self> {
+        Some(GemChain {
+            gems: jump_path(obstacle, boy, envelope, GEMS_PER_CHAIN)?,
+            taken: 0,
+            broken: false,
+        })
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.gems.iter_mut().for_each(|gem| gem.x += x);
+    }
+
+    // Of the gems still there, or nothing once they are all taken.
+    pub fn right(&self) -> i16 {
+        self.gems
+            .iter()
+            .map(|gem| gem.x + GEM_RADIUS)
+            .max()
+            .unwrap_or(i16::MIN)
+    }
+
+    // Takes the gems the boy is touching. Landing with some taken and some
+    // left breaks the chain, and taking the last of an unbroken one pays out.
+    pub fn collect(&mut self, boy: &Rect, airborne: bool) -> ChainProgress {
+        let before = self.gems.len();
+        self.gems.retain(|gem| !hitbox(gem).intersects(boy));
+        let collected = before - self.gems.len();
+        self.taken += collected;
+        if !airborne && self.taken > 0 && !self.gems.is_empty() {
+            self.broken = true;
+        }
+        let bonus = if collected > 0 && self.gems.is_empty() && !self.broken {
+            self.taken as u32 * CHAIN_MULTIPLIER
+        } else {
+            0
+        };
+        ChainProgress {
+            collected: collected as u32,
+            bonus,
+        }
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.gems.iter().for_each(|gem| {
+            if let Err(err) = renderer.fill_circle(gem, GEM_RADIUS, &GEM_COLOR) {
+                log!("Could not draw gem {:#?}", err);
+            }
+        });
+    }
+}
+
+fn hitbox(gem: &Point) -> Rect {
+    Rect::new_from_x_y(
+        gem.x - GEM_RADIUS,
+        gem.y - GEM_RADIUS,
+        GEM_RADIUS * 2,
+        GEM_RADIUS * 2,
+    )
+}
+
+// The whole screen lights up for a moment when a chain pays out.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Flash {
+    frames: u8,
+}
+
+impl Flash {
+    pub fn start(&mut self) {
+        self.frames = FLASH_FRAMES;
+    }
+
+    pub fn update(&mut self) {
+        self.frames = self.frames.saturating_sub(1);
+    }
+
+    pub fn draw(&self, renderer: &Renderer, screen: &Rect) {
+        if self.frames == 0 {
+            return;
+        }
+        let alpha = FLASH_ALPHA * f64::from(self.frames) / f64::from(FLASH_FRAMES);
+        renderer.draw_with_alpha(alpha, || renderer.fill_rect(screen, &FLASH_COLOR));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Physics;
+
+    fn chain() -> GemChain {
+        let stone = Rect::new_from_x_y(600, 546, 90, 54);
+        let boy = Rect::new_from_x_y(0, 480, 50, 120);
+        GemChain::over(&stone, &boy, &JumpEnvelope::new(&Physics::default(), 4)).unwrap()
+    }
+
+    #[test]
+    fn chains_arc_over_the_obstacle() {
+        let chain = chain();
+
+        assert_eq!(chain.gems.len(), GEMS_PER_CHAIN);
+        let apex = chain.gems.iter().map(|gem| gem.y).min().unwrap();
+        assert_eq!(chain.gems[GEMS_PER_CHAIN / 2].y, apex);
+        assert!(chain.gems.iter().all(|gem| gem.y < 546));
+        assert!(chain.gems.first().unwrap().x < 600 && chain.right() > 600);
+    }
+
+    #[test]
+    fn a_whole_chain_in_one_jump_pays_a_bonus() {
+        let mut chain = chain();
+        let everywhere = Rect::new_from_x_y(0, 0, 1000, 600);
+
+        assert_eq!(
+            chain.collect(&everywhere, true),
+            ChainProgress {
+                collected: GEMS_PER_CHAIN as u32,
+                bonus: GEMS_PER_CHAIN as u32 * CHAIN_MULTIPLIER,
+            }
+        );
+        assert_eq!(chain.right(), i16::MIN);
+    }
+
+    #[test]
+    fn landing_part_way_breaks_the_chain() {
+        let mut chain = chain();
+        let first = chain.gems[0];
+        let touching_first = Rect::new_from_x_y(first.x - 1, first.y - 1, 2, 2);
+        let everywhere = Rect::new_from_x_y(0, 0, 1000, 600);
+
+        assert_eq!(chain.collect(&touching_first, true).collected, 1);
+        assert_eq!(chain.collect(&Rect::default(), false).collected, 0);
+        assert_eq!(chain.collect(&everywhere, true).bonus, 0);
+    }
+
+    #[test]
+    fn running_under_a_chain_does_not_break_it() {
+        let mut chain = chain();
+        let everywhere = Rect::new_from_x_y(0, 0, 1000, 600);
+
+        chain.collect(&Rect::default(), false);
+        assert!(chain.collect(&everywhere, true).bonus > 0);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use crate::engine::{Color, Rect, Renderer};
+
+const BUCKET_WIDTH: i32 = 50;
+const HEIGHT: i16 = 600;
+const HOTTEST_ALPHA: f32 = 0.6;
+
+// Where a generated segment lies, in distance walked rather than screen
+// position, so spans never need scrolling.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentSpan {
+    pub label: &'static str,
+    pub start: i32,
+    pub end: i32,
+}
+
+// The segment a point lies in and how far into it. Later spans win, as
+// segments laid again after a rewind cover the old ones.
+pub fn site(spans: &[SegmentSpan], x: i32) -> Option<(&'static str, i32)> {
+    spans
+        .iter()
+        .rev()
+        .find(|span| span.start <= x && x < span.end)
+        .map(|span| (span.label, x - span.start))
+}
+
+// Death counts per segment type, bucketed by how far into the segment the
+// boy was.
+#[derive(Debug, Default)]
+pub struct Heatmap {
+    deaths: BTreeMap<&'static str, Vec<u32>>,
+    runs: u32,
+    survived: u32,
+}
+
+impl Heatmap {
+    pub fn record_death(&mut self, label: &'static str, offset: i32) {
+        let bucket = (offset / BUCKET_WIDTH) as usize;
+        let counts = self.deaths.entry(label).or_default();
+        if counts.len() <= bucket {
+            counts.resize(bucket + 1, 0);
+        }
+        counts[bucket] += 1;
+        self.runs += 1;
+    }
+
+    pub fn record_survival(&mut self) {
+        self.runs += 1;
+        self.survived += 1;
+    }
+
+    // One line per segment type, with a death count per bucket.
+    pub fn export(&self) -> String {
+        let mut lines = vec![format!(
+            "{} runs, {} survived, buckets of {}px",
+            self.runs, self.survived, BUCKET_WIDTH
+        )];
+        lines.extend(self.deaths.iter().map(|(label, counts)| {
+            let counts: Vec<String> = counts.iter().map(u32::to_string).collect();
+            format!("{}: {}", label, counts.join(" "))
+        }));
+        lines.join("\n")
+    }
+
+    fn hottest(&self) -> u32 {
+        self.deaths.values().flatten().copied().max().unwrap_or(0)
+    }
+
+    // Shades each bucket of the visible segments by how often runs died there.
+    pub fn draw(&self, renderer: &Renderer, spans: &[SegmentSpan], distance: i32) {
+        let hottest = self.hottest();
+        if hottest == 0 {
+            return;
+        }
+        spans.iter().for_each(|span| {
+            let counts = match self.deaths.get(span.label) {
+                Some(counts) => counts,
+                None => return,
+            };
+            counts.iter().enumerate().for_each(|(bucket, count)| {
+                let x = span.start - distance + bucket as i32 * BUCKET_WIDTH;
+                if *count == 0 || x + BUCKET_WIDTH < 0 || x > i16::MAX as i32 {
+                    return;
+                }
+                renderer.fill_rect(
+                    &Rect::new_from_x_y(x as i16, 0, BUCKET_WIDTH as i16, HEIGHT),
+                    &Color::rgba(255, 0, 0, HOTTEST_ALPHA * *count as f32 / hottest as f32),
+                );
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_latest_span_under_a_point() {
+        let spans = vec![
+            SegmentSpan {
+                label: "Stones",
+                start: 0,
+                end: 800,
+            },
+            SegmentSpan {
+                label: "Climb",
+                start: 500,
+                end: 1500,
+            },
+        ];
+
+        assert_eq!(site(&spans, 100), Some(("Stones", 100)));
+        assert_eq!(site(&spans, 600), Some(("Climb", 100)));
+        assert_eq!(site(&spans, 1500), None);
+    }
+
+    #[test]
+    fn exports_deaths_per_bucket() {
+        let mut heatmap = Heatmap::default();
+        heatmap.record_death("Stones", 120);
+        heatmap.record_death("Stones", 149);
+        heatmap.record_death("Climb", 10);
+        heatmap.record_survival();
+
+        assert_eq!(
+            heatmap.export(),
+            "4 runs, 1 survived, buckets of 50px\nClimb: 1\nStones: 0 0 2"
+        );
+        assert_eq!(heatmap.hottest(), 2);
+    }
+}
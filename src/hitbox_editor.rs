@@ -0,0 +1,397 @@
+use anyhow::Result;
+
+use crate::{
+    browser::{self, Target},
+    engine::{Color, KeyState, Point, Rect, Renderer},
+    game::{Cell, RedHatBoy},
+    hitboxes::{Hitboxes, Insets},
+    versioned,
+};
+
+const TOGGLE_KEY: &str = "KeyH";
+const NEXT_STATE_KEY: &str = "KeyN";
+const NEXT_FRAME_KEY: &str = "KeyM";
+const FRAME_BOX_KEY: &str = "KeyF";
+const EXPORT_KEY: &str = "KeyE";
+const ZOOM: i16 = 4;
+const ORIGIN: Point = Point { x: 66, y: 104 };
+const GRAB_DISTANCE: i16 = 8;
+const TEXT_X: i16 = 20;
+const TEXT_Y: i16 = 30;
+const LINE_HEIGHT: i16 = 24;
+const HINTS: [&str; 2] = [
+    "Drag an edge  N: next state  M: next frame",
+    "F: frame's own box  E: export  H: close",
+];
+const SHADE_COLOR: Color = Color::rgba(0, 0, 0, 0.7);
+const SPRITE_COLOR: Color = Color::rgb(160, 160, 160);
+const HITBOX_COLOR: Color = Color::rgb(255, 0, 0);
+// `None` edits the default insets.
+const TARGETS: [Option<&str>; 7] = [
+    None,
+    Some("Idle"),
+    Some("Running"),
+    Some("Sliding"),
+    Some("Jumping"),
+    Some("Falling"),
+    Some("KnockOut"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edge {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
+
+// Which entry of the hitboxes a drag changes. A frame with its own box is
+// edited on its own, anything else edits its state.
+#[derive(Debug, PartialEq)]
+enum Editing {
+    Default,
+    State(&'static str),
+    Frame(String),
+}
+
+impl Editing {
+    fn new(target: Option<&'static str>, frame_name: &str, hitboxes: &Hitboxes) -> Self {
+        match target {
+            None => Editing::Default,
+            Some(_state) if hitboxes.frames.contains_key(frame_name) => {
+                Editing::Frame(frame_name.to_string())
+            }
+            Some(state) => Editing::State(state),
+        }
+    }
+
+    fn insets(&self, hitboxes: &Hitboxes) -> Insets {
+        match self {
+            Editing::Default => hitboxes.default,
+            Editing::State(state) => hitboxes.for_state(state),
+            Editing::Frame(frame_name) => hitboxes.frames[frame_name],
+        }
+    }
+
+    fn set(&self, hitboxes: &mut Hitboxes, insets: Insets) {
+        match self {
+            Editing::Default => hitboxes.default = insets,
+            Editing::State(state) => {
+                hitboxes.states.insert(state.to_string(), insets);
+            }
+            Editing::Frame(frame_name) => {
+                hitboxes.frames.insert(frame_name.clone(), insets);
+            }
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Editing::Default => "Default (all states)".to_string(),
+            Editing::State(state) => state.to_string(),
+            Editing::Frame(frame_name) => format!("Frame {}", frame_name),
+        }
+    }
+}
+
+// Development only: shows the boy's sprites zoomed in with their collision
+// boxes, whose edges can be dragged with the mouse. The game is paused while
+// it is open, and the result can be exported for `hitboxes.json`.
+pub struct HitboxEditor {
+    open: bool,
+    target: usize,
+    frame: usize,
+    dragging: Option<Edge>,
+    toggle_held: bool,
+    next_state_held: bool,
+    next_frame_held: bool,
+    frame_box_held: bool,
+    export_held: bool,
+}
+
+impl HitboxEditor {
+    pub fn new() -> Self {
+        HitboxEditor {
+            open: false,
+            target: 0,
+            frame: 0,
+            dragging: None,
+            toggle_held: false,
+            next_state_held: false,
+            next_frame_held: false,
+            frame_box_held: false,
+            export_held: false,
+        }
+    }
+
+    // Returns true while the editor is open.
+    pub fn update(&mut self, keystate: &KeyState, target: &Target, boy: &mut RedHatBoy) -> bool {
+        if once(keystate, TOGGLE_KEY, &mut self.toggle_held) {
+            self.open = !self.open;
+            if !self.open {
+                if let Err(err) = browser::hide_ui(target) {
+                    log!("Could not hide the hitbox export {:#?}", err);
+                }
+            }
+        }
+        if !self.open {
+            return false;
+        }
+
+        if once(keystate, NEXT_STATE_KEY, &mut self.next_state_held) {
+            self.target = (self.target + 1) % TARGETS.len();
+            self.frame = 0;
+        }
+        if once(keystate, NEXT_FRAME_KEY, &mut self.next_frame_held) {
+            self.frame += 1;
+        }
+        if once(keystate, EXPORT_KEY, &mut self.export_held) {
+            if let Err(err) = self.export(target, boy.hitboxes()) {
+                log!("Could not export hitboxes {:#?}", err);
+            }
+        }
+
+        let (frame_name, size) = match self.current_frame(boy) {
+            Some((frame_name, cell)) => (frame_name.to_string(), (cell.frame.w, cell.frame.h)),
+            None => return true,
+        };
+        if once(keystate, FRAME_BOX_KEY, &mut self.frame_box_held) {
+            if let Some(state) = TARGETS[self.target] {
+                toggle_frame_box(boy.hitboxes_mut(), state, &frame_name);
+            }
+        }
+
+        let editing = Editing::new(TARGETS[self.target], &frame_name, boy.hitboxes());
+        let insets = editing.insets(boy.hitboxes());
+        if let Some(click) = keystate.clicks().last() {
+            self.dragging = grab(click, &zoomed_box(&insets, size));
+        }
+        if !keystate.pointer_held() {
+            self.dragging = None;
+        }
+        if let (Some(edge), Some(pointer)) = (self.dragging, keystate.pointer()) {
+            editing.set(boy.hitboxes_mut(), drag(insets, edge, &pointer, size));
+        }
+        true
+    }
+
+    pub fn draw(&self, renderer: &Renderer, boy: &RedHatBoy) {
+        if !self.open {
+            return;
+        }
+        renderer.fill_rect(&Rect::new_from_x_y(0, 0, 600, 600), &SHADE_COLOR);
+        let (frame_name, cell) = match self.current_frame(boy) {
+            Some(frame) => frame,
+            None => return,
+        };
+        let editing = Editing::new(TARGETS[self.target], frame_name, boy.hitboxes());
+        let insets = editing.insets(boy.hitboxes());
+        let size = (cell.frame.w, cell.frame.h);
+        let sprite = zoomed_box(&Insets::default(), size);
+        if let Err(err) = renderer.draw_cell_into(boy.image(), cell, &sprite) {
+            log!("Could not draw the zoomed sprite {:#?}", err);
+        }
+        renderer.stroke_rect(&sprite, &SPRITE_COLOR);
+        renderer.stroke_rect(&zoomed_box(&insets, size), &HITBOX_COLOR);
+
+        let mut lines = vec![
+            format!("Hitbox: {}  Sprite: {}", editing.label(), frame_name),
+            format!(
+                "Left {}  Top {}  Right {}  Bottom {}",
+                insets.left, insets.top, insets.right, insets.bottom
+            ),
+        ];
+        lines.extend(HINTS.iter().map(|hint| hint.to_string()));
+        lines.iter().enumerate().for_each(|(index, line)| {
+            let position = Point {
+                x: TEXT_X,
+                y: TEXT_Y + index as i16 * LINE_HEIGHT,
+            };
+            if let Err(err) = renderer.draw_text(line, &position) {
+                log!("Could not draw hitbox editor text {:#?}", err);
+            }
+        });
+    }
+
+    // The default box is shown on the idle sprites.
+    fn current_frame<'a>(&self, boy: &'a RedHatBoy) -> Option<(&'a str, &'a Cell)> {
+        let frames = boy.state_frames(TARGETS[self.target].unwrap_or("Idle"));
+        frames.get(self.frame % frames.len().max(1)).copied()
+    }
+
+    fn export(&self, target: &Target, hitboxes: &Hitboxes) -> Result<()> {
+        let json = versioned::write(hitboxes)?;
+        log!("hitboxes.json {}", json);
+        browser::draw_ui(
+            target,
+            &format!(
+                "<div class='hitbox_export'><textarea rows='6' cols='60' readonly>{}</textarea></div>",
+                json
+            ),
+        )
+    }
+}
+
+// Keys act once per press rather than on every frame they are held.
+fn once(keystate: &KeyState, code: &str, held: &mut bool) -> bool {
+    let pressed = keystate.is_pressed(code) && !*held;
+    *held = keystate.is_pressed(code);
+    pressed
+}
+
+// Gives the frame a box of its own, starting from its state's, or hands it
+// back to its state.
+fn toggle_frame_box(hitboxes: &mut Hitboxes, state: &str, frame_name: &str) {
+    if hitboxes.frames.remove(frame_name).is_none() {
+        let insets = hitboxes.for_state(state);
+        hitboxes.frames.insert(frame_name.to_string(), insets);
+    }
+}
+
+fn zoomed_box(insets: &Insets, (width, height): (i16, i16)) -> Rect {
+    let sprite = Rect::new_from_x_y(ORIGIN.x, ORIGIN.y, width * ZOOM, height * ZOOM);
+    Rect::new_from_x_y(
+        sprite.x() + insets.left * ZOOM,
+        sprite.y() + insets.top * ZOOM,
+        sprite.width - (insets.left + insets.right) * ZOOM,
+        sprite.height - (insets.top + insets.bottom) * ZOOM,
+    )
+}
+
+// The edge closest to the pointer, if it is close enough to grab.
+fn grab(pointer: &Point, hitbox: &Rect) -> Option<Edge> {
+    let along_x = (hitbox.x() - GRAB_DISTANCE..hitbox.right() + GRAB_DISTANCE).contains(&pointer.x);
+    let along_y =
+        (hitbox.y() - GRAB_DISTANCE..hitbox.bottom() + GRAB_DISTANCE).contains(&pointer.y);
+    [
+        (Edge::Left, (pointer.x - hitbox.x()).abs(), along_y),
+        (Edge::Right, (pointer.x - hitbox.right()).abs(), along_y),
+        (Edge::Top, (pointer.y - hitbox.y()).abs(), along_x),
+        (Edge::Bottom, (pointer.y - hitbox.bottom()).abs(), along_x),
+    ]
+    .iter()
+    .filter(|(_edge, distance, along)| *along && *distance <= GRAB_DISTANCE)
+    .min_by_key(|(_edge, distance, _along)| *distance)
+    .map(|(edge, _distance, _along)| *edge)
+}
+
+// Moves one edge to the pointer, in sprite pixels, keeping the box at least a
+// pixel wide and inside the sprite.
+fn drag(insets: Insets, edge: Edge, pointer: &Point, (width, height): (i16, i16)) -> Insets {
+    let x = (pointer.x - ORIGIN.x) / ZOOM;
+    let y = (pointer.y - ORIGIN.y) / ZOOM;
+    match edge {
+        Edge::Left => Insets {
+            left: x.clamp(0, width - insets.right - 1),
+            ..insets
+        },
+        Edge::Right => Insets {
+            right: (width - x).clamp(0, width - insets.left - 1),
+            ..insets
+        },
+        Edge::Top => Insets {
+            top: y.clamp(0, height - insets.bottom - 1),
+            ..insets
+        },
+        Edge::Bottom => Insets {
+            bottom: (height - y).clamp(0, height - insets.top - 1),
+            ..insets
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: (i16, i16) = (80, 120);
+
+    fn insets() -> Insets {
+        Insets {
+            left: 18,
+            top: 14,
+            right: 10,
+            bottom: 0,
+        }
+    }
+
+    #[test]
+    fn grabs_the_nearest_edge() {
+        let hitbox = zoomed_box(&insets(), SIZE);
+
+        assert_eq!(hitbox.x(), ORIGIN.x + 72);
+        assert_eq!(
+            grab(
+                &Point {
+                    x: hitbox.x() + 3,
+                    y: hitbox.y() + 100
+                },
+                &hitbox
+            ),
+            Some(Edge::Left)
+        );
+        assert_eq!(
+            grab(
+                &Point {
+                    x: hitbox.x() + 100,
+                    y: hitbox.bottom() - 2
+                },
+                &hitbox
+            ),
+            Some(Edge::Bottom)
+        );
+        assert_eq!(
+            grab(
+                &Point {
+                    x: hitbox.x() + 100,
+                    y: hitbox.y() + 100
+                },
+                &hitbox
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn dragging_moves_one_edge_in_sprite_pixels() {
+        let dragged = drag(
+            insets(),
+            Edge::Top,
+            &Point {
+                x: 0,
+                y: ORIGIN.y + 40 * ZOOM + 1,
+            },
+            SIZE,
+        );
+        assert_eq!(
+            dragged,
+            Insets {
+                top: 40,
+                ..insets()
+            }
+        );
+
+        let dragged = drag(insets(), Edge::Right, &Point { x: 0, y: 0 }, SIZE);
+        assert_eq!(dragged.right, SIZE.0 - insets().left - 1);
+    }
+
+    #[test]
+    fn frames_with_their_own_box_are_edited_alone() {
+        let mut hitboxes = Hitboxes::default();
+        let frame = "Jump (1).png";
+        assert_eq!(
+            Editing::new(Some("Jumping"), frame, &hitboxes),
+            Editing::State("Jumping")
+        );
+
+        toggle_frame_box(&mut hitboxes, "Jumping", frame);
+        let editing = Editing::new(Some("Jumping"), frame, &hitboxes);
+        editing.set(&mut hitboxes, insets());
+        assert_eq!(editing, Editing::Frame(frame.to_string()));
+        assert_eq!(hitboxes.insets("Jumping", Some(frame)), insets());
+        assert!(hitboxes.states.is_empty());
+
+        toggle_frame_box(&mut hitboxes, "Jumping", frame);
+        assert!(hitboxes.frames.is_empty());
+    }
+}
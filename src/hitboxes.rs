@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::Rect,
+    versioned::{self, Versioned},
+};
+
+// How far each edge of a collision box sits inside the edge of the sprite.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Insets {
+    pub left: i16,
+    pub top: i16,
+    pub right: i16,
+    pub bottom: i16,
+}
+
+// The box the boy used before hitboxes were loaded from data.
+const DEFAULT_INSETS: Insets = Insets {
+    left: 18,
+    top: 14,
+    right: 10,
+    bottom: 0,
+};
+
+impl Insets {
+    pub fn apply(&self, sprite: &Rect) -> Rect {
+        Rect::new_from_x_y(
+            sprite.x() + self.left,
+            sprite.y() + self.top,
+            sprite.width - self.left - self.right,
+            sprite.height - self.top - self.bottom,
+        )
+    }
+}
+
+// Collision insets for the boy. A sprite frame's own entry wins over its
+// state's, and states without an entry use the default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hitboxes {
+    pub default: Insets,
+    #[serde(default)]
+    pub states: BTreeMap<String, Insets>,
+    #[serde(default)]
+    pub frames: BTreeMap<String, Insets>,
+}
+
+impl Default for Hitboxes {
+    fn default() -> Self {
+        Hitboxes {
+            default: DEFAULT_INSETS,
+            states: BTreeMap::new(),
+            frames: BTreeMap::new(),
+        }
+    }
+}
+
+impl Hitboxes {
+    pub fn for_state(&self, state: &str) -> Insets {
+        self.states.get(state).copied().unwrap_or(self.default)
+    }
+
+    pub fn insets(&self, state: &str, frame_name: Option<&str>) -> Insets {
+        frame_name
+            .and_then(|frame_name| self.frames.get(frame_name))
+            .copied()
+            .unwrap_or_else(|| self.for_state(state))
+    }
+}
+
+impl Versioned for Hitboxes {
+    const VERSION: u32 = 1;
+}
+
+pub async fn load_hitboxes(path: &str) -> Result<Hitboxes> {
+    versioned::fetch(path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_insets_match_the_old_offsets() {
+        let sprite = Rect::new_from_x_y(100, 200, 80, 120);
+
+        assert_eq!(
+            Hitboxes::default().for_state("Running").apply(&sprite),
+            Rect::new_from_x_y(118, 214, 52, 106)
+        );
+    }
+
+    #[test]
+    fn states_fall_back_to_the_default() {
+        let hitboxes: Hitboxes = serde_json::from_str(
+            r#"{
+                "default": {"left": 1, "top": 2, "right": 3, "bottom": 4},
+                "states": {"Sliding": {"left": 5, "top": 40, "right": 5, "bottom": 0}}
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(hitboxes.for_state("Sliding").top, 40);
+        assert_eq!(hitboxes.for_state("Jumping"), hitboxes.default);
+    }
+
+    #[test]
+    fn shipped_boxes_are_shorter_when_sliding_and_tucked_when_jumping() {
+        let hitboxes: Hitboxes = versioned::read(include_str!("../static/hitboxes.json")).unwrap();
+        let sprite = Rect::new_from_x_y(0, 0, 70, 115);
+        let height = |state: &str, frame_name: Option<&str>| {
+            hitboxes.insets(state, frame_name).apply(&sprite).height
+        };
+
+        assert!(height("Sliding", None) < height("Running", None));
+        assert!(height("Jumping", Some("Jump (6).png")) < height("Running", None));
+        assert_eq!(
+            height("Jumping", Some("Jump (1).png")),
+            height("Running", None)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::cell::Cell;
+
+use serde::{Deserialize, Serialize};
+
+use crate::versioned::{self, Versioned};
+
+pub const LOCALE_KEY: &str = "walk_the_dog.locale";
+
+// UI text is keyed by its English wording, so anything without a translation
+// still reads sensibly.
+const SPANISH: [(&str, &str); 52] = [
+    ("Play", "Jugar"),
+    ("Modes", "Modos"),
+    ("Stats", "Estadísticas"),
+    ("Settings", "Ajustes"),
+    ("Back", "Volver"),
+    ("Graphics", "Gráficos"),
+    ("Frame rate", "Fotogramas"),
+    ("Sharing", "Compartir"),
+    ("Announcer", "Locutor"),
+    ("Rumble", "Vibración"),
+    ("Language", "Idioma"),
+    ("Export", "Exportar"),
+    ("Import", "Importar"),
+    ("Auto", "Automático"),
+    ("High", "Alta"),
+    ("Low", "Baja"),
+    ("60 FPS", "60 FPS"),
+    ("30 FPS", "30 FPS"),
+    ("On", "Sí"),
+    ("Off", "No"),
+    ("Offline", "Sin conexión"),
+    ("Leaderboards", "Clasificaciones"),
+    (
+        "Copy this code into another browser",
+        "Copia este código en otro navegador",
+    ),
+    ("Export failed", "No se pudo exportar"),
+    ("Save imported", "Partida importada"),
+    ("Import failed", "No se pudo importar"),
+    ("Endless", "Sin fin"),
+    ("Time Attack", "Contrarreloj"),
+    ("Boss Rush", "Jefes"),
+    ("Race", "Carrera"),
+    ("Practice", "Práctica"),
+    ("Pause", "Pausa"),
+    ("Done", "Salir"),
+    ("Replay", "Repetición"),
+    ("Dash", "Impulso"),
+    ("Checkpoint", "Punto de control"),
+    ("Walk the Dog", "Pasea al perro"),
+    (
+        "Tab between options, Enter to choose",
+        "Tabulador para moverse, Intro para elegir",
+    ),
+    ("Game over", "Fin de la partida"),
+    ("score", "puntuación"),
+    (
+        "press Enter for new game",
+        "pulsa Intro para jugar otra vez",
+    ),
+    ("Replay controls", "Controles de repetición"),
+    ("Drawn", "Dibujados"),
+    ("Buttons", "Botones"),
+    ("Master volume", "Volumen general"),
+    ("Music", "Música"),
+    ("Sound effects", "Efectos"),
+    ("Interface", "Interfaz"),
+    ("Voice", "Voz"),
+    ("Music when paused", "Música en pausa"),
+    ("Lower", "Más baja"),
+    ("Keep", "Igual"),
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Locale {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Versioned for Locale {
+    const VERSION: u32 = 1;
+}
+
+thread_local! {
+    // Loaded on first use, then kept in step with the setting.
+    static CURRENT: Cell<Option<Locale>> = const { Cell::new(None) };
+}
+
+impl Locale {
+    pub fn load() -> Self {
+        versioned::load(LOCALE_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the default locale {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(LOCALE_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Locale::English => Locale::Spanish,
+            Locale::Spanish => Locale::English,
+        }
+    }
+
+    // Each language is named in itself, so it can be found when the UI is
+    // in one the player can't read.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Locale::English => "English",
+            Locale::Spanish => "Español",
+        }
+    }
+
+    pub fn text(&self, key: &'static str) -> &'static str {
+        let table: &[(&str, &str)] = match self {
+            Locale::English => &[],
+            Locale::Spanish => &SPANISH,
+        };
+        table
+            .iter()
+            .find(|(english, _translated)| *english == key)
+            .map_or(key, |(_english, translated)| translated)
+    }
+}
+
+pub fn current() -> Locale {
+    CURRENT.with(|current| {
+        current.get().unwrap_or_else(|| {
+            let locale = Locale::load();
+            current.set(Some(locale));
+            locale
+        })
+    })
+}
+
+// Switches every piece of text resolved from here on, without a reload.
+pub fn set_current(locale: Locale) {
+    CURRENT.with(|current| current.set(Some(locale)));
+}
+
+// Widgets hold on to keys and resolve them each time they are shown, so
+// they follow the locale as soon as it changes.
+pub fn text(key: &'static str) -> &'static str {
+    current().text(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_falls_back_to_its_key() {
+        assert_eq!(Locale::English.text("Play"), "Play");
+        assert_eq!(Locale::Spanish.text("Play"), "Jugar");
+        assert_eq!(Locale::Spanish.text("Not translated"), "Not translated");
+    }
+
+    #[test]
+    fn switching_takes_effect_immediately() {
+        set_current(Locale::Spanish);
+        assert_eq!(text("Back"), "Volver");
+        set_current(current().next());
+        assert_eq!(text("Back"), "Back");
+    }
+
+    #[test]
+    fn keys_are_translated_once() {
+        SPANISH
+            .iter()
+            .enumerate()
+            .for_each(|(index, (key, _text))| {
+                assert!(SPANISH[index + 1..]
+                    .iter()
+                    .all(|(other, _text)| other != key));
+            });
+    }
+}
@@ -0,0 +1,162 @@
+use crate::engine::{Color, KeyState, Point, Rect, Renderer};
+
+const SELECTED_COLOR: Color = Color::rgb(255, 220, 0);
+const PANEL_X: i16 = 20;
+const PANEL_Y: i16 = 140;
+const LINE_HEIGHT: i16 = 24;
+const HINT: &str = "IJKL: nudge  Shift+IJKL: nudge velocity";
+const VELOCITY_KEY: &str = "ShiftLeft";
+const NUDGE_KEYS: [(&str, Point); 4] = [
+    ("KeyJ", Point { x: -1, y: 0 }),
+    ("KeyL", Point { x: 1, y: 0 }),
+    ("KeyI", Point { x: 0, y: -1 }),
+    ("KeyK", Point { x: 0, y: 1 }),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    Boy,
+    Obstacle(usize),
+}
+
+pub enum Nudge {
+    Position(Point),
+    Velocity(Point),
+}
+
+// Development only: clicking the boy or an obstacle selects it and shows its
+// properties in a panel over the game.
+pub struct Inspector {
+    selection: Option<Selection>,
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        Inspector { selection: None }
+    }
+
+    pub fn selection(&self) -> Option<Selection> {
+        self.selection
+    }
+
+    // The boy is drawn over the obstacles, so he wins where they overlap.
+    // Clicking empty space clears the selection.
+    pub fn select(&mut self, click: &Point, boy: &Rect, obstacles: &[Vec<Rect>]) {
+        self.selection = if boy.contains(click) {
+            Some(Selection::Boy)
+        } else {
+            obstacles
+                .iter()
+                .position(|hitboxes| hitboxes.iter().any(|hitbox| hitbox.contains(click)))
+                .map(Selection::Obstacle)
+        };
+    }
+
+    // Keeps an obstacle selection on the same obstacle when the ones flagged
+    // in `removed` are dropped from the list.
+    pub fn obstacles_removed(&mut self, removed: &[bool]) {
+        if let Some(Selection::Obstacle(index)) = self.selection {
+            self.selection = match removed.get(index) {
+                Some(false) => Some(Selection::Obstacle(
+                    index - removed[..index].iter().filter(|gone| **gone).count(),
+                )),
+                _ => None,
+            };
+        }
+    }
+
+    // Nudges repeat every frame the keys are held.
+    pub fn nudge(&self, keystate: &KeyState) -> Option<Nudge> {
+        self.selection?;
+        let offset = NUDGE_KEYS
+            .iter()
+            .filter(|(code, _offset)| keystate.is_pressed(code))
+            .fold(Point::default(), |total, (_code, offset)| Point {
+                x: total.x + offset.x,
+                y: total.y + offset.y,
+            });
+        if offset == Point::default() {
+            None
+        } else if keystate.is_pressed(VELOCITY_KEY) {
+            Some(Nudge::Velocity(offset))
+        } else {
+            Some(Nudge::Position(offset))
+        }
+    }
+
+    pub fn draw_selection(&self, renderer: &Renderer, boxes: &[Rect]) {
+        boxes
+            .iter()
+            .for_each(|bounding_box| renderer.stroke_rect(bounding_box, &SELECTED_COLOR));
+    }
+
+    pub fn draw_panel(&self, renderer: &Renderer, properties: &[String]) {
+        properties
+            .iter()
+            .chain(std::iter::once(&HINT.to_string()))
+            .enumerate()
+            .for_each(|(index, line)| {
+                let position = Point {
+                    x: PANEL_X,
+                    y: PANEL_Y + index as i16 * LINE_HEIGHT,
+                };
+                if let Err(err) = renderer.draw_text(line, &position) {
+                    log!("Could not draw inspector panel {:#?}", err);
+                }
+            });
+    }
+}
+
+pub fn describe_box(name: &str, bounding_box: &Rect) -> String {
+    format!(
+        "{}: {}, {} {}x{}",
+        name,
+        bounding_box.x(),
+        bounding_box.y(),
+        bounding_box.width,
+        bounding_box.height
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obstacles() -> Vec<Vec<Rect>> {
+        vec![
+            vec![Rect::new_from_x_y(100, 400, 50, 50)],
+            vec![
+                Rect::new_from_x_y(300, 200, 60, 20),
+                Rect::new_from_x_y(360, 200, 60, 20),
+            ],
+        ]
+    }
+
+    #[test]
+    fn clicks_pick_the_entity_under_them() {
+        let mut inspector = Inspector::new();
+        let boy = Rect::new_from_x_y(120, 380, 40, 100);
+
+        inspector.select(&Point { x: 370, y: 210 }, &boy, &obstacles());
+        assert_eq!(inspector.selection(), Some(Selection::Obstacle(1)));
+
+        inspector.select(&Point { x: 130, y: 420 }, &boy, &obstacles());
+        assert_eq!(inspector.selection(), Some(Selection::Boy));
+
+        inspector.select(&Point { x: 500, y: 50 }, &boy, &obstacles());
+        assert_eq!(inspector.selection(), None);
+    }
+
+    #[test]
+    fn obstacle_selection_follows_removals() {
+        let mut inspector = Inspector {
+            selection: Some(Selection::Obstacle(2)),
+        };
+
+        inspector.obstacles_removed(&[true, false, false]);
+        assert_eq!(inspector.selection(), Some(Selection::Obstacle(1)));
+
+        inspector.obstacles_removed(&[false, true]);
+        assert_eq!(inspector.selection(), None);
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsValue;
+use web_sys::{Request, RequestInit};
+
+use crate::{
+    browser,
+    consent::Consent,
+    save,
+    signing::{hmac_sha256, sha256, to_hex},
+    versioned::{self, Versioned},
+};
+
+pub const LEADERBOARD_KEY: &str = "walk_the_dog.leaderboard";
+const ENTRIES_PER_BUCKET: usize = 5;
+// A world hash is kept every second of play at the default tick rate.
+pub const HASH_INTERVAL: u32 = 60;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Leaderboard {
+    buckets: HashMap<String, Vec<i32>>,
+}
+
+impl Versioned for Leaderboard {
+    const VERSION: u32 = 1;
+}
+
+impl Leaderboard {
+    pub fn load() -> Self {
+        versioned::load(LEADERBOARD_KEY)
+            .unwrap_or_else(|err| {
+                log!("Starting with an empty leaderboard {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        save::save_entry(LEADERBOARD_KEY, self)
+    }
+
+    pub fn record(&mut self, bucket: &str, distance: i32) {
+        let entries = self.buckets.entry(bucket.to_string()).or_default();
+        entries.push(distance);
+        entries.sort_unstable_by(|a, b| b.cmp(a));
+        entries.truncate(ENTRIES_PER_BUCKET);
+    }
+
+    pub fn best(&self, bucket: &str) -> Option<i32> {
+        self.buckets
+            .get(bucket)
+            .and_then(|entries| entries.first())
+            .copied()
+    }
+}
+
+// What the player did on one frame, as far as playing it again goes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Input {
+    pub jumped: bool,
+    pub dashed: bool,
+    pub grabbing: bool,
+}
+
+// The frames the jump and grab keys were held on, as runs of [first frame,
+// frame count], and the frames dashes started on. With the seed, this is
+// everything needed to play a run again. The world hashes, one every
+// `HASH_INTERVAL` frames from the first interval on, show where a replay
+// stops matching the run it came from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Replay {
+    jumps: Vec<[u32; 2]>,
+    dashes: Vec<u32>,
+    grabs: Vec<[u32; 2]>,
+    hashes: Vec<u32>,
+}
+
+impl Replay {
+    pub fn from_jumps(jumps: Vec<[u32; 2]>) -> Self {
+        Replay {
+            jumps,
+            ..Replay::default()
+        }
+    }
+
+    pub fn with_dashes(mut self, dashes: Vec<u32>) -> Self {
+        self.dashes = dashes;
+        self
+    }
+
+    pub fn with_grabs(mut self, grabs: Vec<[u32; 2]>) -> Self {
+        self.grabs = grabs;
+        self
+    }
+
+    pub fn with_hashes(mut self, hashes: Vec<u32>) -> Self {
+        self.hashes = hashes;
+        self
+    }
+
+    pub fn jumps(&self) -> &[[u32; 2]] {
+        &self.jumps
+    }
+
+    pub fn dashes(&self) -> &[u32] {
+        &self.dashes
+    }
+
+    pub fn grabs(&self) -> &[[u32; 2]] {
+        &self.grabs
+    }
+
+    pub fn hashes(&self) -> &[u32] {
+        &self.hashes
+    }
+
+    pub fn record(&mut self, frame: u32, input: Input) {
+        if input.jumped {
+            self.record_jump(frame);
+        }
+        if input.dashed {
+            self.record_dash(frame);
+        }
+        if input.grabbing {
+            extend_runs(&mut self.grabs, frame);
+        }
+    }
+
+    pub fn record_jump(&mut self, frame: u32) {
+        extend_runs(&mut self.jumps, frame);
+    }
+
+    pub fn record_dash(&mut self, frame: u32) {
+        self.dashes.push(frame);
+    }
+
+    // Only kept when `frame` is the next one due.
+    pub fn record_hash(&mut self, frame: u32, hash: u32) {
+        if frame == (self.hashes.len() as u32 + 1) * HASH_INTERVAL {
+            self.hashes.push(hash);
+        }
+    }
+
+    // The hash the world had after `frame` frames, when one was kept.
+    pub fn hash_at(&self, frame: u32) -> Option<u32> {
+        if frame == 0 || !frame.is_multiple_of(HASH_INTERVAL) {
+            return None;
+        }
+        self.hashes
+            .get((frame / HASH_INTERVAL - 1) as usize)
+            .copied()
+    }
+
+    pub fn input(&self, frame: u32) -> Input {
+        Input {
+            jumped: in_runs(&self.jumps, frame),
+            dashed: self.dashes.contains(&frame),
+            grabbing: in_runs(&self.grabs, frame),
+        }
+    }
+
+    // Forgets everything from `frame` on, for when a rewind takes the run back.
+    // The world is back as it was after `frame` frames, so the hash kept then
+    // still holds.
+    pub fn truncate(&mut self, frame: u32) {
+        truncate_runs(&mut self.jumps, frame);
+        truncate_runs(&mut self.grabs, frame);
+        self.dashes.retain(|dash| *dash < frame);
+        self.hashes.truncate((frame / HASH_INTERVAL) as usize);
+    }
+
+    // Written out by hand so the text that gets hashed never depends on how
+    // the browser stringifies JSON. Runs without dashes or grabs hash as they
+    // always did.
+    fn to_json(&self) -> String {
+        let mut json = format!(r#"{{"jumps":[{}]"#, runs_json(&self.jumps));
+        if !self.dashes.is_empty() {
+            let dashes: Vec<String> = self.dashes.iter().map(u32::to_string).collect();
+            json.push_str(&format!(r#","dashes":[{}]"#, dashes.join(",")));
+        }
+        if !self.grabs.is_empty() {
+            json.push_str(&format!(r#","grabs":[{}]"#, runs_json(&self.grabs)));
+        }
+        json.push('}');
+        json
+    }
+}
+
+fn extend_runs(runs: &mut Vec<[u32; 2]>, frame: u32) {
+    match runs.last_mut() {
+        Some([first, count]) if *first + *count == frame => *count += 1,
+        _ => runs.push([frame, 1]),
+    }
+}
+
+fn in_runs(runs: &[[u32; 2]], frame: u32) -> bool {
+    runs.iter()
+        .any(|[first, count]| (*first..*first + *count).contains(&frame))
+}
+
+fn truncate_runs(runs: &mut Vec<[u32; 2]>, frame: u32) {
+    runs.retain(|[first, _count]| *first < frame);
+    if let Some([first, count]) = runs.last_mut() {
+        *count = (*count).min(frame - *first);
+    }
+}
+
+fn runs_json(runs: &[[u32; 2]]) -> String {
+    let runs: Vec<String> = runs
+        .iter()
+        .map(|[first, count]| format!("[{},{}]", first, count))
+        .collect();
+    runs.join(",")
+}
+
+// A score for the online leaderboard. The seed is a string because JSON
+// numbers lose precision past 53 bits.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Submission {
+    bucket: String,
+    distance: i32,
+    seed: String,
+    frames: u32,
+    replay_hash: String,
+    #[serde(skip)]
+    replay: String,
+}
+
+impl Submission {
+    pub fn new(bucket: &str, distance: i32, seed: u64, frames: u32, replay: &Replay) -> Self {
+        let replay = replay.to_json();
+        Submission {
+            bucket: bucket.to_string(),
+            distance,
+            seed: seed.to_string(),
+            frames,
+            replay_hash: to_hex(&sha256(replay.as_bytes())),
+            replay,
+        }
+    }
+
+    // The fields in a fixed order, so the server can rebuild exactly what was signed.
+    fn signed_message(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.bucket, self.distance, self.seed, self.frames, self.replay_hash
+        )
+    }
+
+    pub fn signature(&self, token: &str) -> String {
+        to_hex(&hmac_sha256(
+            token.as_bytes(),
+            self.signed_message().as_bytes(),
+        ))
+    }
+}
+
+#[derive(Serialize)]
+struct SignedSubmission<'a> {
+    #[serde(flatten)]
+    submission: &'a Submission,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    token: String,
+}
+
+// The server asks for the replay when a score looks too good, and checks it
+// by playing the seed with the recorded jumps.
+#[derive(Deserialize)]
+struct SubmitResponse {
+    #[serde(default)]
+    replay_requested: bool,
+}
+
+// Submits scores to `<endpoint>/scores`, signed with a token handed out by
+// `<endpoint>/session` that never leaves the page.
+pub struct LeaderboardClient {
+    endpoint: String,
+    token: String,
+}
+
+impl LeaderboardClient {
+    // Enabled with `?leaderboard=<endpoint>`, once the player has agreed to it.
+    pub async fn from_browser() -> Result<Option<Self>> {
+        if !Consent::load().allows_online() {
+            return Ok(None);
+        }
+        let endpoint = match browser::query_param("leaderboard")? {
+            Some(endpoint) => endpoint,
+            None => return Ok(None),
+        };
+        let session: Session = post(&format!("{}/session", endpoint), "{}").await?;
+        Ok(Some(LeaderboardClient {
+            endpoint,
+            token: session.token,
+        }))
+    }
+
+    pub async fn submit(&self, submission: &Submission) -> Result<()> {
+        let signed = SignedSubmission {
+            submission,
+            signature: submission.signature(&self.token),
+        };
+        let body = browser::to_json(&signed)?;
+        let response: SubmitResponse = post(&format!("{}/scores", self.endpoint), &body).await?;
+        if response.replay_requested {
+            self.upload_replay(submission).await?;
+        }
+        Ok(())
+    }
+
+    // The replay goes up as the exact text that was hashed, so the server can
+    // check it against the signed submission.
+    async fn upload_replay(&self, submission: &Submission) -> Result<()> {
+        let url = format!("{}/replays/{}", self.endpoint, submission.replay_hash);
+        let request = request(&url, &submission.replay)?;
+        request
+            .headers()
+            .set("X-Signature", &submission.signature(&self.token))
+            .map_err(|err| anyhow!("Could not set replay signature {:#?}", err))?;
+        let response = browser::fetch_request(&request).await?;
+        if response.ok() {
+            Ok(())
+        } else {
+            Err(anyhow!("Replay upload failed {}", response.status()))
+        }
+    }
+}
+
+fn request(url: &str, body: &str) -> Result<Request> {
+    let mut init = RequestInit::new();
+    init.method("POST");
+    init.body(Some(&JsValue::from_str(body)));
+    let request = Request::new_with_str_and_init(url, &init)
+        .map_err(|err| anyhow!("Could not create leaderboard request {:#?}", err))?;
+    request
+        .headers()
+        .set("Content-Type", "application/json")
+        .map_err(|err| anyhow!("Could not set leaderboard headers {:#?}", err))?;
+    Ok(request)
+}
+
+async fn post<T: serde::de::DeserializeOwned>(url: &str, body: &str) -> Result<T> {
+    let response = browser::fetch_request(&request(url, body)?).await?;
+    if !response.ok() {
+        return Err(anyhow!("Leaderboard request failed {}", response.status()));
+    }
+    browser::from_json(&browser::response_text(&response).await?)
+}
+
+// Online scores are optional, so this does nothing unless an endpoint is set.
+pub fn submit_in_background(submission: Submission) {
+    browser::spawn_local(async move {
+        let result = match LeaderboardClient::from_browser().await {
+            Ok(Some(client)) => client.submit(&submission).await,
+            Ok(None) => Ok(()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            log!("Could not submit score {:#?}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_top_runs_per_bucket() {
+        let mut leaderboard = Leaderboard::default();
+        (1..=7).for_each(|distance| leaderboard.record("standard", distance * 100));
+        leaderboard.record("mirror", 50);
+
+        assert_eq!(leaderboard.best("standard"), Some(700));
+        assert_eq!(leaderboard.buckets["standard"].len(), ENTRIES_PER_BUCKET);
+        assert_eq!(leaderboard.best("mirror"), Some(50));
+        assert_eq!(leaderboard.best("double_speed"), None);
+    }
+
+    #[test]
+    fn replay_keeps_held_jumps_as_runs() {
+        let mut replay = Replay::default();
+        [10, 11, 12, 40, 41]
+            .iter()
+            .for_each(|frame| replay.record_jump(*frame));
+        assert_eq!(replay.jumps, vec![[10, 3], [40, 2]]);
+
+        replay.truncate(11);
+        assert_eq!(replay.jumps, vec![[10, 1]]);
+        assert_eq!(replay.to_json(), r#"{"jumps":[[10,1]]}"#);
+    }
+
+    #[test]
+    fn replay_keeps_dashes_apart_from_jumps() {
+        let mut replay = Replay::default();
+        replay.record_jump(10);
+        replay.record_dash(20);
+        replay.record_dash(90);
+        assert!(replay.input(20).dashed && !replay.input(21).dashed);
+
+        replay.truncate(50);
+        assert_eq!(replay.dashes(), &[20]);
+        assert_eq!(replay.to_json(), r#"{"jumps":[[10,1]],"dashes":[20]}"#);
+    }
+
+    #[test]
+    fn replay_keeps_every_input_of_a_frame() {
+        let mut replay = Replay::default();
+        let everything = Input {
+            jumped: true,
+            dashed: true,
+            grabbing: true,
+        };
+        replay.record(5, everything);
+        replay.record(
+            6,
+            Input {
+                grabbing: true,
+                ..Input::default()
+            },
+        );
+
+        assert_eq!(replay.input(5), everything);
+        assert!(!replay.input(6).jumped && replay.input(6).grabbing);
+        assert_eq!(
+            replay.to_json(),
+            r#"{"jumps":[[5,1]],"dashes":[5],"grabs":[[5,2]]}"#
+        );
+    }
+
+    #[test]
+    fn replay_keeps_world_hashes_on_their_frames() {
+        let mut replay = Replay::default();
+        replay.record_hash(HASH_INTERVAL, 1);
+        replay.record_hash(HASH_INTERVAL + 1, 9);
+        replay.record_hash(HASH_INTERVAL * 2, 2);
+        replay.record_hash(HASH_INTERVAL * 3, 3);
+
+        assert_eq!(replay.hash_at(HASH_INTERVAL * 2), Some(2));
+        assert_eq!(replay.hash_at(HASH_INTERVAL + 1), None);
+        assert_eq!(replay.hash_at(0), None);
+
+        replay.truncate(HASH_INTERVAL * 3);
+        assert_eq!(replay.hashes(), &[1, 2, 3]);
+        replay.truncate(HASH_INTERVAL * 2 - 1);
+        assert_eq!(replay.hashes(), &[1]);
+        assert_eq!(replay.to_json(), r#"{"jumps":[]}"#);
+    }
+
+    #[test]
+    fn signature_covers_the_seed_and_replay() {
+        let mut replay = Replay::default();
+        replay.record_jump(5);
+        let submission = Submission::new("standard", 1200, 42, 900, &replay);
+        let other_seed = Submission::new("standard", 1200, 43, 900, &replay);
+        replay.record_jump(6);
+        let other_replay = Submission::new("standard", 1200, 42, 900, &replay);
+
+        assert_eq!(submission.signed_message().split('|').nth(2), Some("42"));
+        assert_ne!(submission.signature("token"), other_seed.signature("token"));
+        assert_ne!(
+            submission.signature("token"),
+            other_replay.signature("token")
+        );
+        assert_ne!(submission.signature("token"), submission.signature("other"));
+    }
+}
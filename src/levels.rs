@@ -0,0 +1,637 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    diagnostics,
+    engine::{physics::ForceField, Color, MaskedImage, Point, Rect, Renderer, SpriteSheet},
+    environments::{EnvironmentPlacement, EnvironmentZone},
+    game::Obstacle,
+    save,
+    segments::{chasm, chasm_swings, climb, platform_and_stone, stone_and_platform, woodpile},
+    surfaces::Surface,
+    swings::{SwingPlacement, SwingPoint},
+    tiled::{self, Map},
+    triggers::{TriggerPlacement, TriggerZone},
+    versioned::{self, Versioned},
+    wind::ForcePlacement,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+pub const PROGRESS_KEY: &str = "walk_the_dog.story_progress";
+const FRAMES_PER_SECOND: u32 = 60;
+const MAX_STARS: u8 = 3;
+
+const GOAL_TOP: i16 = 400;
+const GOAL_HEIGHT: i16 = 200;
+const POLE_WIDTH: i16 = 6;
+const FLAG_WIDTH: i16 = 50;
+const FLAG_HEIGHT: i16 = 40;
+const POLE_COLOR: Color = Color::rgb(90, 90, 90);
+const FLAG_COLOR: Color = Color::rgb(220, 40, 40);
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum SegmentKind {
+    StoneAndPlatform,
+    PlatformAndStone,
+    Climb,
+    Woodpile,
+    Chasm,
+}
+
+impl SegmentKind {
+    // Every segment that can be built, for modes that let the player pick one.
+    pub const ALL: [SegmentKind; 5] = [
+        SegmentKind::StoneAndPlatform,
+        SegmentKind::PlatformAndStone,
+        SegmentKind::Climb,
+        SegmentKind::Woodpile,
+        SegmentKind::Chasm,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SegmentKind::StoneAndPlatform => "Stone and platform",
+            SegmentKind::PlatformAndStone => "Platform and stone",
+            SegmentKind::Climb => "Climb",
+            SegmentKind::Woodpile => "Woodpile",
+            SegmentKind::Chasm => "Chasm",
+        }
+    }
+
+    pub fn build(
+        &self,
+        stone: MaskedImage,
+        sprite_sheet: Rc<SpriteSheet>,
+        offset_x: i16,
+    ) -> Vec<Box<dyn Obstacle>> {
+        match self {
+            SegmentKind::StoneAndPlatform => stone_and_platform(stone, sprite_sheet, offset_x),
+            SegmentKind::PlatformAndStone => platform_and_stone(stone, sprite_sheet, offset_x),
+            SegmentKind::Climb => climb(stone, sprite_sheet, offset_x),
+            SegmentKind::Woodpile => woodpile(stone, offset_x),
+            SegmentKind::Chasm => chasm(stone, sprite_sheet, offset_x),
+        }
+    }
+
+    // The swing points some segments can't be crossed without.
+    pub fn swing_points(&self, offset_x: i16) -> Vec<SwingPoint> {
+        let placements = match self {
+            SegmentKind::Chasm => chasm_swings(),
+            _ => vec![],
+        };
+        placements
+            .iter()
+            .map(|placement| SwingPoint::new(placement, offset_x))
+            .collect()
+    }
+}
+
+// A level segment with the trigger zones, force fields, environments and swing
+// points placed on it, relative to its start, and what its platforms are made
+// of. Plain segments are written as just their kind.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "SegmentData")]
+pub struct Segment {
+    pub kind: SegmentKind,
+    pub triggers: Vec<TriggerPlacement>,
+    pub forces: Vec<ForcePlacement>,
+    pub environments: Vec<EnvironmentPlacement>,
+    pub swings: Vec<SwingPlacement>,
+    pub surface: Surface,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SegmentData {
+    Kind(SegmentKind),
+    Placed {
+        kind: SegmentKind,
+        #[serde(default)]
+        triggers: Vec<TriggerPlacement>,
+        #[serde(default)]
+        forces: Vec<ForcePlacement>,
+        #[serde(default)]
+        environments: Vec<EnvironmentPlacement>,
+        #[serde(default)]
+        swings: Vec<SwingPlacement>,
+        #[serde(default)]
+        surface: Surface,
+    },
+}
+
+impl From<SegmentData> for Segment {
+    fn from(data: SegmentData) -> Self {
+        match data {
+            SegmentData::Kind(kind) => Segment {
+                kind,
+                triggers: vec![],
+                forces: vec![],
+                environments: vec![],
+                swings: vec![],
+                surface: Surface::Normal,
+            },
+            SegmentData::Placed {
+                kind,
+                triggers,
+                forces,
+                environments,
+                swings,
+                surface,
+            } => Segment {
+                kind,
+                triggers,
+                forces,
+                environments,
+                swings,
+                surface,
+            },
+        }
+    }
+}
+
+impl Segment {
+    pub fn build(
+        &self,
+        stone: MaskedImage,
+        sprite_sheet: Rc<SpriteSheet>,
+        offset_x: i16,
+    ) -> Vec<Box<dyn Obstacle>> {
+        let mut obstacles = self.kind.build(stone, sprite_sheet, offset_x);
+        obstacles
+            .iter_mut()
+            .for_each(|obstacle| obstacle.set_surface(self.surface));
+        obstacles
+    }
+
+    pub fn trigger_zones(&self, offset_x: i16) -> Vec<TriggerZone> {
+        self.triggers
+            .iter()
+            .map(|placement| TriggerZone::new(placement, offset_x))
+            .collect()
+    }
+
+    pub fn force_fields(&self, offset_x: i16) -> Vec<ForceField> {
+        self.forces
+            .iter()
+            .map(|placement| placement.field(offset_x))
+            .collect()
+    }
+
+    pub fn environment_zones(&self, offset_x: i16) -> Vec<EnvironmentZone> {
+        self.environments
+            .iter()
+            .map(|placement| EnvironmentZone::new(placement, offset_x))
+            .collect()
+    }
+
+    // Its kind's own swing points as well as any placed on it.
+    pub fn swing_points(&self, offset_x: i16) -> Vec<SwingPoint> {
+        let mut points = self.kind.swing_points(offset_x);
+        points.extend(
+            self.swings
+                .iter()
+                .map(|placement| SwingPoint::new(placement, offset_x)),
+        );
+        points
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Level {
+    pub name: String,
+    pub segments: Vec<Segment>,
+    pub par_seconds: u32,
+    #[serde(default)]
+    pub coins: u32,
+    // A map exported from Tiled whose objects add trigger zones to the segments.
+    #[serde(default)]
+    pub map: Option<String>,
+}
+
+pub struct LevelResult {
+    pub frames: u32,
+    pub coins: u32,
+    pub hits: u32,
+}
+
+impl Level {
+    // Adds the triggers placed in `map` to their segments, returning what
+    // could not be placed.
+    pub fn add_map(&mut self, map: &Map) -> Vec<anyhow::Error> {
+        let (placements, mut errors) = map.placements();
+        placements
+            .into_iter()
+            .for_each(|(index, placement)| match self.segments.get_mut(index) {
+                Some(segment) => segment.triggers.push(placement),
+                None => errors.push(anyhow!("{} has no segment {}", self.name, index)),
+            });
+        errors
+    }
+
+    // One star each for beating par time, collecting the coins and taking no hits.
+    pub fn stars(&self, result: &LevelResult) -> u8 {
+        (result.frames <= self.par_seconds * FRAMES_PER_SECOND) as u8
+            + (result.coins >= self.coins) as u8
+            + (result.hits == 0) as u8
+    }
+}
+
+impl Versioned for Vec<Level> {
+    const VERSION: u32 = 1;
+}
+
+pub async fn load_levels(path: &str) -> Result<Vec<Level>> {
+    let mut levels: Vec<Level> = versioned::fetch(path).await?;
+    for level in levels.iter_mut() {
+        let map = match &level.map {
+            Some(map) => tiled::load_map(map).await,
+            None => continue,
+        };
+        match map {
+            Ok(map) => level.add_map(&map).iter().for_each(|err| {
+                log!("Skipping a map object in {} {:#?}", level.name, err);
+            }),
+            Err(err) => {
+                log!("Could not load the map for {} {:#?}", level.name, err);
+            }
+        }
+    }
+    Ok(levels)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Progress {
+    best_stars: HashMap<String, u8>,
+}
+
+impl Versioned for Progress {
+    const VERSION: u32 = 1;
+}
+
+impl Progress {
+    fn load() -> Self {
+        versioned::load(PROGRESS_KEY)
+            .unwrap_or_else(|err| {
+                log!("Starting with empty story progress {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<()> {
+        save::save_entry(PROGRESS_KEY, self)
+    }
+
+    fn record(&mut self, level: &str, stars: u8) {
+        let best = self.best_stars.entry(level.to_string()).or_insert(stars);
+        *best = (*best).max(stars);
+    }
+
+    fn stars(&self, level: &str) -> Option<u8> {
+        self.best_stars.get(level).copied()
+    }
+}
+
+struct Goal {
+    x: i16,
+}
+
+impl Goal {
+    fn draw(&self, renderer: &Renderer) {
+        renderer.fill_rect(
+            &Rect::new_from_x_y(self.x, GOAL_TOP, POLE_WIDTH, GOAL_HEIGHT),
+            &POLE_COLOR,
+        );
+        renderer.fill_polygon(
+            &[
+                Point {
+                    x: self.x + POLE_WIDTH,
+                    y: GOAL_TOP,
+                },
+                Point {
+                    x: self.x + POLE_WIDTH + FLAG_WIDTH,
+                    y: GOAL_TOP + FLAG_HEIGHT / 2,
+                },
+                Point {
+                    x: self.x + POLE_WIDTH,
+                    y: GOAL_TOP + FLAG_HEIGHT,
+                },
+            ],
+            &FLAG_COLOR,
+        );
+    }
+}
+
+struct Course {
+    level: usize,
+    next_segment: usize,
+    frames: u32,
+    goal: Option<Goal>,
+}
+
+impl Course {
+    fn new(level: usize) -> Self {
+        Course {
+            level,
+            next_segment: 0,
+            frames: 0,
+            goal: None,
+        }
+    }
+}
+
+pub struct Story {
+    levels: Vec<Level>,
+    progress: Progress,
+    course: Option<Course>,
+}
+
+impl Story {
+    pub fn new(levels: Vec<Level>) -> Self {
+        Story {
+            levels,
+            progress: Progress::load(),
+            course: None,
+        }
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn playing(&self) -> bool {
+        self.course.is_some()
+    }
+
+    pub fn start(&mut self, level: usize) {
+        self.course = Some(Course::new(level));
+    }
+
+    pub fn restart(&mut self) {
+        if let Some(course) = &mut self.course {
+            *course = Course::new(course.level);
+        }
+    }
+
+    pub fn quit(&mut self) {
+        self.course = None;
+    }
+
+    pub fn next_segment(&mut self) -> Option<Segment> {
+        let course = self.course.as_mut()?;
+        let segment = self
+            .levels
+            .get(course.level)?
+            .segments
+            .get(course.next_segment)
+            .cloned()?;
+        course.next_segment += 1;
+        Some(segment)
+    }
+
+    pub fn place_goal(&mut self, x: i16) {
+        if let Some(course) = &mut self.course {
+            course.goal.get_or_insert(Goal { x });
+        }
+    }
+
+    pub fn update(&mut self, walking_speed: i16) {
+        if let Some(course) = &mut self.course {
+            course.frames += 1;
+            if let Some(goal) = &mut course.goal {
+                goal.x += walking_speed;
+            }
+        }
+    }
+
+    pub fn goal_reached(&self, boy: &Rect) -> bool {
+        self.course
+            .as_ref()
+            .and_then(|course| course.goal.as_ref())
+            .map_or(false, |goal| boy.right() >= goal.x)
+    }
+
+    // Rates the finished course, on which the boy collected `coins` and was
+    // hit `hits` times, and keeps the best rating for the level.
+    pub fn complete(&mut self, coins: u32, hits: u32) -> Result<u8> {
+        let course = self
+            .course
+            .as_ref()
+            .ok_or_else(|| anyhow!("No level is being played"))?;
+        let level = self
+            .levels
+            .get(course.level)
+            .ok_or_else(|| anyhow!("Level {} not found", course.level))?;
+        let stars = level.stars(&LevelResult {
+            frames: course.frames,
+            coins,
+            hits,
+        });
+        self.progress.record(&level.name, stars);
+        if let Err(err) = self.progress.save() {
+            log!("Could not save story progress {:#?}", err);
+        }
+        Ok(stars)
+    }
+
+    pub fn level_complete_html(&self, stars: u8) -> String {
+        let name = self
+            .course
+            .as_ref()
+            .and_then(|course| self.levels.get(course.level))
+            .map_or("", |level| level.name.as_str());
+        format!(
+            "<div class='level_complete'><p>{} {}</p><button class='level_select'>Levels</button></div>",
+            diagnostics::escape(name),
+            star_string(stars)
+        )
+    }
+
+    pub fn level_select_html(&self) -> String {
+        let entries: String = self
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(index, level)| {
+                format!(
+                    "<div class='level_entry'><button class='level_{}'>{}</button><span class='stars'>{}</span></div>",
+                    index,
+                    index + 1,
+                    star_string(self.progress.stars(&level.name).unwrap_or(0))
+                )
+            })
+            .collect();
+        format!("<div class='level_select'>{}</div>", entries)
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        if let Some(goal) = self.course.as_ref().and_then(|course| course.goal.as_ref()) {
+            goal.draw(renderer);
+        }
+    }
+}
+
+fn star_string(stars: u8) -> String {
+    (0..MAX_STARS)
+        .map(|star| if star < stars { '★' } else { '☆' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels() -> Vec<Level> {
+        serde_json::from_str(
+            r#"[
+                {"name": "Meadow", "segments": ["StoneAndPlatform", "PlatformAndStone"], "parSeconds": 10},
+                {"name": "Hills", "segments": ["PlatformAndStone"], "parSeconds": 5, "coins": 3}
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn story() -> Story {
+        Story {
+            levels: levels(),
+            progress: Progress::default(),
+            course: None,
+        }
+    }
+
+    #[test]
+    fn rates_time_coins_and_hits() {
+        let levels = levels();
+        let fast = LevelResult {
+            frames: 10 * FRAMES_PER_SECOND,
+            coins: 0,
+            hits: 0,
+        };
+        let slow = LevelResult {
+            frames: 10 * FRAMES_PER_SECOND + 1,
+            coins: 0,
+            hits: 1,
+        };
+
+        assert_eq!(levels[0].stars(&fast), 3);
+        assert_eq!(levels[0].stars(&slow), 1);
+        assert_eq!(levels[1].stars(&fast), 1);
+    }
+
+    #[test]
+    fn progress_keeps_the_best_rating() {
+        let mut progress = Progress::default();
+        progress.record("Meadow", 2);
+        progress.record("Meadow", 1);
+
+        assert_eq!(progress.stars("Meadow"), Some(2));
+        assert_eq!(progress.stars("Hills"), None);
+    }
+
+    #[test]
+    fn courses_follow_the_level_then_place_one_goal() {
+        let mut story = story();
+        assert_eq!(story.next_segment(), None);
+
+        story.start(0);
+        let kind = |segment: Option<Segment>| segment.map(|segment| segment.kind);
+        assert_eq!(
+            kind(story.next_segment()),
+            Some(SegmentKind::StoneAndPlatform)
+        );
+        assert_eq!(
+            kind(story.next_segment()),
+            Some(SegmentKind::PlatformAndStone)
+        );
+        assert_eq!(story.next_segment(), None);
+
+        story.place_goal(500);
+        story.place_goal(900);
+        story.update(-100);
+
+        let boy = Rect::new_from_x_y(300, 0, 100, 100);
+        assert!(story.goal_reached(&boy));
+
+        story.restart();
+        assert!(!story.goal_reached(&boy));
+        assert_eq!(
+            kind(story.next_segment()),
+            Some(SegmentKind::StoneAndPlatform)
+        );
+    }
+
+    #[test]
+    fn segments_can_place_trigger_zones() {
+        let segments: Vec<Segment> = serde_json::from_str(
+            r#"[
+                "StoneAndPlatform",
+                {"kind": "PlatformAndStone", "surface": "Icy", "triggers": [{"kind": "SpeedBoost", "x": 100, "width": 200}]}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(segments[0].triggers.is_empty());
+        assert_eq!(segments[0].surface, Surface::Normal);
+        assert_eq!(segments[1].kind, SegmentKind::PlatformAndStone);
+        assert_eq!(segments[1].surface, Surface::Icy);
+        assert_eq!(segments[1].trigger_zones(1000)[0].right(), 1300);
+    }
+
+    #[test]
+    fn maps_add_triggers_to_their_segments() {
+        let mut level = levels().remove(0);
+        let map: Map = serde_json::from_str(
+            r#"{"layers": [{"type": "objectgroup", "objects": [
+                {"class": "Checkpoint", "x": 40, "y": 0,
+                 "properties": [{"name": "segment", "type": "int", "value": 1}]},
+                {"class": "SpeedBoost", "x": 0, "y": 0, "width": 100,
+                 "properties": [{"name": "segment", "type": "int", "value": 5}]}
+            ]}]}"#,
+        )
+        .unwrap();
+
+        let errors = level.add_map(&map);
+        assert!(level.segments[0].triggers.is_empty());
+        assert_eq!(level.segments[1].trigger_zones(1000)[0].right(), 1060);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn chasms_bring_their_own_swing_points() {
+        let segments: Vec<Segment> = serde_json::from_str(
+            r#"[
+                "Chasm",
+                {"kind": "StoneAndPlatform", "swings": [{"x": 300, "y": 200, "reach": 120}]}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(segments[0].swing_points(0).len(), 2);
+        let placed = segments[1].swing_points(1000);
+        assert_eq!(placed.len(), 1);
+        assert_eq!(placed[0].anchor(), Point { x: 1300, y: 200 });
+        assert_eq!(placed[0].right(), 1420);
+    }
+
+    #[test]
+    fn level_select_lists_best_ratings() {
+        let mut story = story();
+        story.progress.record("Hills", 2);
+
+        let html = story.level_select_html();
+        assert!(html.contains("<button class='level_0'>1</button><span class='stars'>☆☆☆</span>"));
+        assert!(html.contains("<button class='level_1'>2</button><span class='stars'>★★☆</span>"));
+    }
+
+    #[test]
+    fn level_names_are_escaped_when_complete() {
+        let mut story = story();
+        story.levels[0].name = "<b>Meadow</b>".to_string();
+        story.start(0);
+
+        assert!(story
+            .level_complete_html(2)
+            .contains("<p>&lt;b&gt;Meadow&lt;/b&gt; ★★☆</p>"));
+    }
+}
@@ -2,6 +2,8 @@ use engine::Audio;
 use engine::GameLoop;
 use engine::Image;
 use engine::KeyState;
+use engine::LoopDriver;
+use engine::LoopHandle;
 use engine::Point;
 use engine::Rect;
 use engine::SpriteSheet;
@@ -19,16 +21,81 @@ use serde::Deserialize;
 use wasm_bindgen::prelude::*;
 use web_sys::HtmlImageElement;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
 #[macro_use]
 mod browser;
+mod announcer;
+mod attachments;
+mod audit;
+mod background;
+mod boss;
+mod camera;
+mod cheats;
+mod cloud_save;
+mod coins;
+mod config;
+mod consent;
+#[cfg(debug_assertions)]
+mod console;
+mod critters;
+mod cutscene;
+mod dash;
+mod day_cycle;
+mod diagnostics;
 mod engine;
+mod entities;
+mod environments;
+mod fairness;
+mod footsteps;
 mod game;
+mod gems;
+#[cfg(debug_assertions)]
+mod heatmap;
+#[cfg(debug_assertions)]
+mod hitbox_editor;
+mod hitboxes;
+mod i18n;
+#[cfg(debug_assertions)]
+mod inspector;
+mod leaderboard;
+mod levels;
+mod lifecycle;
+mod menus;
+mod modes;
+mod narration;
+mod packs;
+mod permalink;
+mod practice;
+mod race;
+mod replay_file;
+mod replay_viewer;
+mod rewind;
+mod riding;
+mod routes;
+mod save;
+mod seasons;
 mod segments;
+mod sheets;
+mod signing;
 mod sound;
+mod spectate;
+#[cfg(debug_assertions)]
+mod state_graph;
+mod stats;
+mod surfaces;
+mod swings;
+mod telegraph;
+mod tiled;
+mod time_scale;
+mod triggers;
+mod versioned;
+mod warnings;
+mod wind;
 
+use crate::browser::Target;
 use crate::engine::{Game, Renderer};
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
@@ -50,11 +117,81 @@ pub fn main_js() -> Result<(), JsValue> {
     #[cfg(debug_assertions)]
     console_error_panic_hook::set_once();
 
+    let has_default_canvas = browser::document()
+        .map_err(|err| JsValue::from_str(&err.to_string()))?
+        .get_element_by_id(browser::DEFAULT_CANVAS_ID)
+        .is_some();
+    if has_default_canvas {
+        browser::query_param("driver")
+            .and_then(|driver| start_game(Target::default(), driver.as_deref()))
+            .map_err(|err| JsValue::from_str(&err.to_string()))?;
+    }
+    Ok(())
+}
+
+// Lets the embedding page run additional instances on its own canvas and UI
+// elements. `driver` is "animation_frame" or "timer", and is picked to suit
+// the page when left out.
+#[wasm_bindgen]
+pub fn start(canvas_id: &str, ui_id: &str, driver: Option<String>) -> Result<(), JsValue> {
+    start_game(Target::new(canvas_id, ui_id), driver.as_deref())
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+// Lets the embedding page load assets from a subpath or CDN, with an optional
+// version to bypass stale caches. Without it, `?asset_base=` and
+// `?cache_bust=` are used. Call it before `start`.
+#[wasm_bindgen]
+pub fn set_asset_base(base_url: &str, cache_bust: Option<String>) {
+    browser::asset_config::set(browser::asset_config::AssetConfig {
+        base_url: base_url.to_string(),
+        cache_bust,
+    });
+}
+
+thread_local! {
+    // The instance on each canvas, so starting one there again stops the old
+    // one rather than leaving both listening.
+    static RUNNING: RefCell<HashMap<String, (Target, LoopHandle)>> = RefCell::new(HashMap::new());
+}
+
+// Lets the embedding page stop the instance on a canvas, which drops its
+// listeners along with the game.
+#[wasm_bindgen]
+pub fn stop(canvas_id: &str) {
+    if let Some(running) = RUNNING.with(|running| running.borrow_mut().remove(canvas_id)) {
+        stop_instance(running);
+    }
+}
+
+fn stop_instance((target, handle): (Target, LoopHandle)) {
+    handle.stop();
+    browser::release(&target);
+}
+
+// Missing elements are reported to the caller here, rather than as a panic
+// once the game is already loading.
+fn start_game(target: Target, driver: Option<&str>) -> Result<()> {
+    let canvas = browser::canvas(&target.canvas_id)?;
+    browser::find_ui(&target)?;
+    let driver = match driver {
+        Some(name) => LoopDriver::from_name(name)?,
+        None => LoopDriver::detect()?,
+    };
+    let handle = LoopHandle::default();
+    let replaced = RUNNING.with(|running| {
+        running
+            .borrow_mut()
+            .insert(target.canvas_id.clone(), (target.clone(), handle.clone()))
+    });
+    if let Some(replaced) = replaced {
+        stop_instance(replaced);
+    }
     browser::spawn_local(async move {
-        let game = WalkTheDog::new();
-        GameLoop::start(game)
-            .await
-            .expect("Could not start game loop");
+        let game = WalkTheDog::new(target);
+        if let Err(err) = GameLoop::start(game, canvas, driver, handle).await {
+            log!("Could not start game loop {:#?}", err);
+        }
     });
     Ok(())
 }
@@ -0,0 +1,200 @@
+use crate::engine::{Color, Point, Rect, Renderer};
+
+const ENTRANCE_FRAMES: u8 = 24;
+const DUST_FRAMES: u8 = 16;
+const DROP_HEIGHT: i16 = 240;
+const DUST_PUFFS: i16 = 4;
+const DUST_RADIUS: i16 = 12;
+const DUST_ALPHA: f64 = 0.7;
+const DUST_COLOR: Color = Color::rgb(190, 175, 150);
+
+// Where an obstacle is in its life. Only the drawing changes with the phase,
+// so runs play out the same with or without entrances.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Spawning,
+    Active,
+    Despawning,
+}
+
+// How an obstacle makes its way in once it scrolls into view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Entrance {
+    // Up out of the ground.
+    Rise,
+    // Down from above, kicking up dust where it lands.
+    Drop,
+}
+
+impl Entrance {
+    fn frames(&self) -> u8 {
+        match self {
+            Entrance::Rise => ENTRANCE_FRAMES,
+            Entrance::Drop => ENTRANCE_FRAMES + DUST_FRAMES,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lifecycle {
+    entrance: Option<Entrance>,
+    phase: Phase,
+    // Frames since it came into view, while spawning.
+    frame: Option<u8>,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Lifecycle::new(None)
+    }
+}
+
+impl Lifecycle {
+    pub fn new(entrance: Option<Entrance>) -> Self {
+        Lifecycle {
+            entrance,
+            phase: if entrance.is_some() {
+                Phase::Spawning
+            } else {
+                Phase::Active
+            },
+            frame: None,
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    // Plays the entrance once the left of `bounds` is within `view_width`,
+    // and starts despawning once all of it has gone off the left.
+    pub fn update(&mut self, bounds: &Rect, view_width: i16) {
+        if let (Phase::Spawning, Some(entrance)) = (self.phase, self.entrance) {
+            self.frame = match self.frame {
+                Some(frame) => Some(frame + 1),
+                None if bounds.x() < view_width => Some(0),
+                None => None,
+            };
+            if matches!(self.frame, Some(frame) if frame >= entrance.frames()) {
+                self.phase = Phase::Active;
+            }
+        }
+        if bounds.right() <= 0 {
+            self.phase = Phase::Despawning;
+        }
+    }
+
+    // How far into the movement, from 0 to 1.
+    fn progress(&self) -> f64 {
+        match self.phase {
+            Phase::Spawning => {
+                f64::from(self.frame.unwrap_or(0).min(ENTRANCE_FRAMES)) / f64::from(ENTRANCE_FRAMES)
+            }
+            Phase::Active | Phase::Despawning => 1.0,
+        }
+    }
+
+    // How far from its place to draw it, for an obstacle whose top is at
+    // `top` over ground at `ground`.
+    fn offset(&self, top: i16, ground: i16) -> i16 {
+        let progress = self.progress();
+        let left = match self.entrance {
+            Some(Entrance::Rise) if self.phase == Phase::Spawning => {
+                f64::from(ground - top) * (1.0 - progress).powi(2)
+            }
+            Some(Entrance::Drop) if self.phase == Phase::Spawning => {
+                -f64::from(DROP_HEIGHT) * (1.0 - progress * progress)
+            }
+            _ => 0.0,
+        };
+        left.round() as i16
+    }
+
+    // Draws the obstacle shifted along its entrance, plus any dust.
+    pub fn draw(&self, renderer: &Renderer, bounds: &Rect, ground: i16, draw: impl FnOnce()) {
+        let offset = self.offset(bounds.y(), ground);
+        if offset == 0 {
+            draw();
+        } else {
+            renderer.draw_translated(&Point { x: 0, y: offset }, draw);
+        }
+        if let Some(dust) = self.dust() {
+            draw_dust(renderer, bounds, dust);
+        }
+    }
+
+    // How far through the dust settling, from 0 to 1, while there is any.
+    fn dust(&self) -> Option<f64> {
+        match (self.phase, self.entrance, self.frame) {
+            (Phase::Spawning, Some(Entrance::Drop), Some(frame)) if frame >= ENTRANCE_FRAMES => {
+                Some(f64::from(frame - ENTRANCE_FRAMES) / f64::from(DUST_FRAMES))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Puffs spread along the base, growing and fading as they settle.
+fn draw_dust(renderer: &Renderer, bounds: &Rect, settled: f64) {
+    let radius = DUST_RADIUS + (f64::from(DUST_RADIUS) * settled) as i16;
+    renderer.draw_with_alpha(DUST_ALPHA * (1.0 - settled), || {
+        (0..DUST_PUFFS).for_each(|puff| {
+            let center = Point {
+                x: bounds.x() + bounds.width * puff / (DUST_PUFFS - 1),
+                y: bounds.bottom() - radius / 2,
+            };
+            if let Err(err) = renderer.fill_circle(&center, radius, &DUST_COLOR) {
+                log!("Could not draw dust {:#?}", err);
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: i16) -> Rect {
+        Rect::new_from_x_y(x, 400, 100, 50)
+    }
+
+    #[test]
+    fn entrances_wait_until_in_view() {
+        let mut lifecycle = Lifecycle::new(Some(Entrance::Rise));
+        lifecycle.update(&at(650), 600);
+        assert_eq!(lifecycle.offset(400, 600), 200);
+
+        lifecycle.update(&at(590), 600);
+        (0..ENTRANCE_FRAMES / 2).for_each(|_frame| lifecycle.update(&at(550), 600));
+        assert_eq!(lifecycle.offset(400, 600), 50);
+
+        (0..ENTRANCE_FRAMES).for_each(|_frame| lifecycle.update(&at(500), 600));
+        assert_eq!(lifecycle.phase(), Phase::Active);
+        assert_eq!(lifecycle.offset(400, 600), 0);
+    }
+
+    #[test]
+    fn drops_fall_then_settle_their_dust() {
+        let mut lifecycle = Lifecycle::new(Some(Entrance::Drop));
+        lifecycle.update(&at(500), 600);
+        assert_eq!(lifecycle.offset(400, 600), -DROP_HEIGHT);
+        assert_eq!(lifecycle.dust(), None);
+
+        (0..ENTRANCE_FRAMES).for_each(|_frame| lifecycle.update(&at(500), 600));
+        assert_eq!(lifecycle.offset(400, 600), 0);
+        assert_eq!(lifecycle.dust(), Some(0.0));
+
+        (0..DUST_FRAMES).for_each(|_frame| lifecycle.update(&at(500), 600));
+        assert_eq!(lifecycle.phase(), Phase::Active);
+        assert_eq!(lifecycle.dust(), None);
+    }
+
+    #[test]
+    fn obstacles_despawn_once_off_the_left() {
+        let mut lifecycle = Lifecycle::default();
+        assert_eq!(lifecycle.phase(), Phase::Active);
+
+        lifecycle.update(&at(-100), 600);
+        assert_eq!(lifecycle.phase(), Phase::Despawning);
+    }
+}
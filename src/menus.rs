@@ -0,0 +1,180 @@
+use futures::channel::mpsc::UnboundedReceiver;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    browser::{self, Target},
+    engine,
+    i18n::text,
+    narration,
+    versioned::{self, Versioned},
+};
+
+pub const MENU_STYLE_KEY: &str = "walk_the_dog.menu_style";
+
+// Whether the replay viewer's controls are drawn on the canvas or made of
+// buttons on the page, which keyboards and screen readers can move through.
+// Every other menu is already made of page buttons.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum MenuStyle {
+    #[default]
+    Canvas,
+    Page,
+}
+
+impl Versioned for MenuStyle {
+    const VERSION: u32 = 1;
+}
+
+impl MenuStyle {
+    pub fn load() -> Self {
+        versioned::load(MENU_STYLE_KEY)
+            .unwrap_or_else(|err| {
+                log!("Using the default menu style {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        versioned::save(MENU_STYLE_KEY, self)
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            MenuStyle::Canvas => MenuStyle::Page,
+            MenuStyle::Page => MenuStyle::Canvas,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            MenuStyle::Canvas => "Drawn",
+            MenuStyle::Page => "Buttons",
+        }
+    }
+}
+
+// One entry of a menu, however it is shown. `label` is a text key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuItem<A> {
+    pub action: A,
+    pub label: &'static str,
+    pub pressed: bool,
+}
+
+impl<A> MenuItem<A> {
+    pub fn new(action: A, label: &'static str) -> Self {
+        MenuItem {
+            action,
+            label,
+            pressed: false,
+        }
+    }
+
+    pub fn pressed(self, pressed: bool) -> Self {
+        MenuItem { pressed, ..self }
+    }
+}
+
+// A menu shown as buttons on the page in place of the canvas. It follows the
+// same items the canvas would draw, remaking the buttons when they change.
+pub struct PageMenu<A> {
+    name: &'static str,
+    items: Vec<MenuItem<A>>,
+    events: Vec<UnboundedReceiver<()>>,
+    // The item last chosen, which keeps focus when the buttons are remade.
+    focused: usize,
+}
+
+impl<A: Copy + PartialEq> PageMenu<A> {
+    pub fn new(name: &'static str) -> Self {
+        PageMenu {
+            name,
+            items: vec![],
+            events: vec![],
+            focused: 0,
+        }
+    }
+
+    pub fn sync(&mut self, target: &Target, items: Vec<MenuItem<A>>) {
+        if items == self.items {
+            return;
+        }
+        let first = self.items.is_empty();
+        if let Err(err) = self.show(target, &items) {
+            log!("Could not show page menu {:#?}", err);
+        }
+        self.items = items;
+        if first {
+            let labels: Vec<&str> = self.items.iter().map(|item| text(item.label)).collect();
+            narration::show_menu(target, text(self.name), &labels);
+        }
+    }
+
+    fn show(&mut self, target: &Target, items: &[MenuItem<A>]) -> anyhow::Result<()> {
+        browser::hide_ui(target)?;
+        browser::draw_ui(target, &html(self.name, items))?;
+        self.events = (0..items.len())
+            .map(|index| {
+                browser::find_ui_element(target, &format!(".menu_item_{}", index))
+                    .map(|element| engine::add_click_handler(target, element))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let focused = self.focused.min(items.len().saturating_sub(1));
+        browser::find_ui_element(target, &format!(".menu_item_{}", focused))?
+            .focus()
+            .map_err(|err| anyhow::anyhow!("Could not focus menu item {:#?}", err))
+    }
+
+    pub fn clicked(&mut self) -> Option<A> {
+        let index = self
+            .events
+            .iter_mut()
+            .position(|event| matches!(event.try_next(), Ok(Some(()))))?;
+        self.focused = index;
+        self.items.get(index).map(|item| item.action)
+    }
+}
+
+// Buttons in the order they are drawn, so tabbing goes the same way the eye
+// does. Toggles say whether they are on.
+fn html<A>(name: &'static str, items: &[MenuItem<A>]) -> String {
+    let buttons: String = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            format!(
+                "<button class='menu_item_{}' aria-pressed='{}'>{}</button>",
+                index,
+                item.pressed,
+                text(item.label)
+            )
+        })
+        .collect();
+    format!(
+        "<div class='page_menu' role='toolbar' aria-label='{}'>{}</div>",
+        text(name),
+        buttons
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{self, Locale};
+
+    #[test]
+    fn page_menus_are_buttons_in_order() {
+        i18n::set_current(Locale::Spanish);
+        let items = vec![
+            MenuItem::new(1, "Pause"),
+            MenuItem::new(2, "1x").pressed(true),
+            MenuItem::new(3, "Done"),
+        ];
+
+        assert_eq!(
+            html("Replay", &items),
+            "<div class='page_menu' role='toolbar' aria-label='Repetición'><button class='menu_item_0' aria-pressed='false'>Pausa</button><button class='menu_item_1' aria-pressed='true'>1x</button><button class='menu_item_2' aria-pressed='false'>Salir</button></div>"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{boss::BossEncounter, config::GameConfig, i18n::text, levels::SegmentKind};
+
+const FRAMES_PER_SECOND: u32 = 60;
+const TIME_ATTACK_SECONDS: u32 = 60;
+const BOSS_RUSH_INTERVAL: i32 = 600;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameMode {
+    Endless,
+    // A fixed length run, ranked by distance.
+    TimeAttack,
+    // Bosses back to back with no obstacles in between.
+    BossRush,
+    // The same segment over and over.
+    Practice(SegmentKind),
+    // Endless against another player on the same seed, over a peer connection.
+    Race,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Endless
+    }
+}
+
+impl GameMode {
+    // The modes offered on the mode select screen, in order.
+    pub fn selectable() -> Vec<GameMode> {
+        let mut modes = vec![GameMode::TimeAttack, GameMode::BossRush];
+        modes.extend(
+            SegmentKind::ALL
+                .iter()
+                .map(|kind| GameMode::Practice(*kind)),
+        );
+        modes
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            GameMode::Endless => text("Endless").to_string(),
+            GameMode::TimeAttack => text("Time Attack").to_string(),
+            GameMode::BossRush => text("Boss Rush").to_string(),
+            GameMode::Practice(kind) => format!("{}: {}", text("Practice"), kind.label()),
+            GameMode::Race => text("Race").to_string(),
+        }
+    }
+
+    pub fn time_limit(&self) -> Option<u32> {
+        match self {
+            GameMode::TimeAttack => Some(TIME_ATTACK_SECONDS * FRAMES_PER_SECOND),
+            _ => None,
+        }
+    }
+
+    pub fn boss_encounter(&self) -> BossEncounter {
+        match self {
+            GameMode::BossRush => BossEncounter::with_interval(BOSS_RUSH_INTERVAL),
+            _ => BossEncounter::new(),
+        }
+    }
+
+    // Practice runs are for learning and never reach the leaderboard, and
+    // races are only between the two players.
+    pub fn leaderboard_bucket(&self, config: &GameConfig) -> Option<String> {
+        match self {
+            GameMode::Endless => Some(config.leaderboard_bucket()),
+            GameMode::TimeAttack => Some(format!("time_attack {}", config.leaderboard_bucket())),
+            GameMode::BossRush => Some(format!("boss_rush {}", config.leaderboard_bucket())),
+            GameMode::Practice(_) | GameMode::Race => None,
+        }
+    }
+
+    pub fn select_html(modes: &[GameMode]) -> String {
+        let entries: String = modes
+            .iter()
+            .enumerate()
+            .map(|(index, mode)| {
+                format!("<button class='mode_{}'>{}</button>", index, mode.label())
+            })
+            .collect();
+        format!(
+            "<div class='mode_select'>{}<button class='back'>{}</button></div>",
+            entries,
+            text("Back")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{self, Locale};
+
+    #[test]
+    fn only_time_attack_is_timed() {
+        assert_eq!(GameMode::TimeAttack.time_limit(), Some(3600));
+        assert_eq!(GameMode::Endless.time_limit(), None);
+        assert_eq!(
+            GameMode::Practice(SegmentKind::StoneAndPlatform).time_limit(),
+            None
+        );
+    }
+
+    #[test]
+    fn modes_rank_separately() {
+        let config = GameConfig::default();
+
+        assert_eq!(
+            GameMode::Endless.leaderboard_bucket(&config).as_deref(),
+            Some("standard")
+        );
+        assert_eq!(
+            GameMode::TimeAttack.leaderboard_bucket(&config).as_deref(),
+            Some("time_attack standard")
+        );
+        assert_eq!(
+            GameMode::Practice(SegmentKind::PlatformAndStone).leaderboard_bucket(&config),
+            None
+        );
+    }
+
+    #[test]
+    fn every_segment_can_be_practised() {
+        let modes = GameMode::selectable();
+
+        assert!(SegmentKind::ALL
+            .iter()
+            .all(|kind| modes.contains(&GameMode::Practice(*kind))));
+        i18n::set_current(Locale::Spanish);
+        let html = GameMode::select_html(&modes);
+        assert!(html.contains("mode_3") && html.contains("Práctica"));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{
+    browser::{self, Target},
+    i18n::text,
+};
+
+// What screen reader users hear as a menu comes up, before moving through it.
+pub fn menu(name: &str, options: &[impl AsRef<str>]) -> String {
+    let options: Vec<&str> = options.iter().map(AsRef::as_ref).collect();
+    format!(
+        "{}: {}. {}",
+        name,
+        options.join(", "),
+        text("Tab between options, Enter to choose")
+    )
+}
+
+pub fn game_over(distance: i32) -> String {
+    format!(
+        "{}, {} {}, {}",
+        text("Game over"),
+        text("score"),
+        distance,
+        text("press Enter for new game")
+    )
+}
+
+pub fn checkpoint(distance: i32) -> String {
+    format!("{} {}m", text("Checkpoint"), distance)
+}
+
+pub fn announce(target: &Target, message: &str) {
+    if let Err(err) = browser::announce(target, message) {
+        log!("Could not announce {:#?}", err);
+    }
+}
+
+// Focuses the menu first, so the whole of it is read rather than its
+// first button.
+pub fn show_menu(target: &Target, name: &str, options: &[impl AsRef<str>]) {
+    if let Err(err) = browser::focus_ui(target) {
+        log!("Could not focus menu {:#?}", err);
+    }
+    announce(target, &menu(name, options));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{self, Locale};
+
+    #[test]
+    fn messages_read_out_the_screen() {
+        i18n::set_current(Locale::English);
+
+        assert_eq!(
+            game_over(1200),
+            "Game over, score 1200, press Enter for new game"
+        );
+        assert_eq!(
+            menu("Settings", &["Language: English", "Back"]),
+            "Settings: Language: English, Back. Tab between options, Enter to choose"
+        );
+
+        i18n::set_current(Locale::Spanish);
+        assert_eq!(checkpoint(300), "Punto de control 300m");
+    }
+}
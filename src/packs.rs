@@ -0,0 +1,268 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{
+    browser,
+    cutscene::Cutscene,
+    levels::Segment,
+    seasons::Season,
+    triggers::TriggerKind,
+    versioned::{self, Versioned},
+};
+
+const MAX_PACKS: usize = 4;
+const MAX_NAME: usize = 40;
+const MAX_SEGMENTS: usize = 32;
+const MAX_SEASONS: usize = 8;
+const MAX_PLACEMENTS: usize = 16;
+const MAX_TEXT: usize = 280;
+// How far from the start of its segment anything in a pack may be placed,
+// and the longest sprint it may give the dog.
+const MAX_REACH: i16 = 2000;
+const MAX_SPRINT: i32 = 5000;
+
+// Community content loaded from `?pack=<url>`: segments added to the ones
+// endless runs pick from, and seasons to theme them. Packs are only data,
+// built from the segment kinds and assets the game already has, and are
+// checked in full before anything in them is used.
+#[derive(Deserialize, Debug, Default)]
+pub struct Pack {
+    pub name: String,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    #[serde(default)]
+    pub seasons: Vec<Season>,
+}
+
+impl Versioned for Pack {
+    const VERSION: u32 = 1;
+}
+
+fn within_reach(x: i16, width: i16, height: i16) -> bool {
+    (0..=MAX_REACH).contains(&x) && width > 0 && width <= MAX_REACH && height > 0
+}
+
+fn check_text(text: &str) -> Result<()> {
+    if text.chars().count() > MAX_TEXT {
+        return Err(anyhow!("Text longer than {} characters", MAX_TEXT));
+    }
+    Ok(())
+}
+
+fn check_segment(segment: &Segment) -> Result<()> {
+    let placements = segment.triggers.len()
+        + segment.forces.len()
+        + segment.environments.len()
+        + segment.swings.len();
+    if placements > MAX_PLACEMENTS {
+        return Err(anyhow!("More than {} placements", MAX_PLACEMENTS));
+    }
+    let areas = segment
+        .triggers
+        .iter()
+        .map(|trigger| (trigger.x, trigger.width, trigger.height))
+        .chain(
+            segment
+                .forces
+                .iter()
+                .map(|force| (force.x, force.width, force.height)),
+        )
+        .chain(
+            segment
+                .environments
+                .iter()
+                .map(|environment| (environment.x, environment.width, environment.height)),
+        )
+        .chain(segment.swings.iter().map(|swing| (swing.x, 1, swing.reach)));
+    for (x, width, height) in areas {
+        if !within_reach(x, width, height) {
+            return Err(anyhow!("A placement at {} is out of reach", x));
+        }
+    }
+    for trigger in segment.triggers.iter() {
+        match &trigger.kind {
+            TriggerKind::Tutorial { text } => check_text(text)?,
+            TriggerKind::Cutscene { script } => {
+                check_text(script)?;
+                Cutscene::from_script(script)?;
+            }
+            TriggerKind::DogSprint { distance } if !(1..=MAX_SPRINT).contains(distance) => {
+                return Err(anyhow!("A sprint of {} is out of range", distance));
+            }
+            TriggerKind::CameraPan { x } if x.abs() > MAX_REACH => {
+                return Err(anyhow!("A camera pan of {} is out of range", x));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Season files must stay beside the pack: a relative path with no scheme
+// and no way up out of its folder.
+fn check_path(path: &str) -> Result<()> {
+    let escapes = path.starts_with('/')
+        || path.contains(':')
+        || path.contains('\\')
+        || path.split('/').any(|part| part == "..");
+    if path.is_empty() || escapes {
+        return Err(anyhow!("{:?} is not a path inside the pack", path));
+    }
+    Ok(())
+}
+
+fn check_season(season: &Season) -> Result<()> {
+    if season.name.is_empty() || season.name.chars().count() > MAX_NAME {
+        return Err(anyhow!("Season names need 1 to {} characters", MAX_NAME));
+    }
+    season.files.values().try_for_each(|path| check_path(path))
+}
+
+// Everything up to and including the last slash of `url`.
+fn folder(url: &str) -> &str {
+    url.rfind('/').map_or("", |slash| &url[..=slash])
+}
+
+impl Pack {
+    // The first thing wrong with the pack, if anything is.
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() || self.name.chars().count() > MAX_NAME {
+            return Err(anyhow!("Pack names need 1 to {} characters", MAX_NAME));
+        }
+        if self.segments.len() > MAX_SEGMENTS {
+            return Err(anyhow!("More than {} segments", MAX_SEGMENTS));
+        }
+        if self.seasons.len() > MAX_SEASONS {
+            return Err(anyhow!("More than {} seasons", MAX_SEASONS));
+        }
+        self.segments
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, segment)| {
+                check_segment(segment).map_err(|err| anyhow!("Segment {}: {}", index, err))
+            })?;
+        self.seasons.iter().try_for_each(|season| {
+            check_season(season).map_err(|err| anyhow!("Season {}: {}", season.name, err))
+        })
+    }
+
+    // Season files are given relative to the pack, and fetched from beside it.
+    fn rebase(mut self, url: &str) -> Self {
+        let folder = folder(url);
+        self.seasons.iter_mut().for_each(|season| {
+            season
+                .files
+                .values_mut()
+                .for_each(|path| *path = format!("{}{}", folder, path));
+        });
+        self
+    }
+
+    pub async fn load(url: &str) -> Result<Pack> {
+        let pack: Pack = versioned::fetch(url).await?;
+        pack.validate()
+            .map_err(|err| anyhow!("Rejected the pack {} {}", url, err))?;
+        Ok(pack.rebase(url))
+    }
+}
+
+// Comma separated URLs, as given in the `pack` query parameter.
+fn urls(param: &str) -> Vec<&str> {
+    param
+        .split(',')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .take(MAX_PACKS)
+        .collect()
+}
+
+// Every pack named in the query string that loads and passes its checks.
+pub async fn load_from_browser() -> Vec<Pack> {
+    let param = match browser::query_param("pack") {
+        Ok(Some(param)) => param,
+        Ok(None) => return vec![],
+        Err(err) => {
+            log!("Could not read the pack parameter {:#?}", err);
+            return vec![];
+        }
+    };
+    let mut packs = vec![];
+    for url in urls(&param) {
+        match Pack::load(url).await {
+            Ok(pack) => packs.push(pack),
+            Err(err) => {
+                log!("Leaving out a pack {:#?}", err);
+            }
+        }
+    }
+    packs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACK: &str = r#"{
+        "name": "Creek extras",
+        "segments": [
+            "Climb",
+            {"kind": "Chasm", "surface": "Icy",
+             "triggers": [{"kind": {"Tutorial": {"text": "Mind the gap"}}, "x": 0, "width": 100}]}
+        ],
+        "seasons": [
+            {"name": "autumn", "months": [9, 10, 11], "files": {"BG.png": "art/autumn.png"}}
+        ]
+    }"#;
+
+    fn pack() -> Pack {
+        serde_json::from_str(PACK).unwrap()
+    }
+
+    #[test]
+    fn packs_within_the_limits_pass() {
+        let pack = pack();
+
+        assert!(pack.validate().is_ok());
+        assert_eq!(pack.segments.len(), 2);
+    }
+
+    #[test]
+    fn out_of_reach_or_oversized_content_is_rejected() {
+        let mut far = pack();
+        far.segments[1].triggers[0].x = MAX_REACH + 1;
+        let mut chatty = pack();
+        if let TriggerKind::Tutorial { text } = &mut chatty.segments[1].triggers[0].kind {
+            *text = "a".repeat(MAX_TEXT + 1);
+        }
+        let mut crowded = pack();
+        crowded.segments = vec![pack().segments.remove(0); MAX_SEGMENTS + 1];
+        let mut unnamed = pack();
+        unnamed.name.clear();
+
+        assert!(far.validate().is_err());
+        assert!(chatty.validate().is_err());
+        assert!(crowded.validate().is_err());
+        assert!(unnamed.validate().is_err());
+    }
+
+    #[test]
+    fn season_files_stay_beside_the_pack() {
+        assert!(check_path("art/autumn.png").is_ok());
+        assert!(check_path("../assets.json").is_err());
+        assert!(check_path("/BG.png").is_err());
+        assert!(check_path("https://example.com/BG.png").is_err());
+        assert!(check_path("javascript:alert(1)").is_err());
+
+        let pack = pack().rebase("https://example.com/packs/creek.json");
+        assert_eq!(
+            pack.seasons[0].files.get("BG.png").map(String::as_str),
+            Some("https://example.com/packs/art/autumn.png")
+        );
+    }
+
+    #[test]
+    fn pack_urls_are_comma_separated_and_capped() {
+        assert_eq!(urls(" a.json, ,b.json"), vec!["a.json", "b.json"]);
+        assert_eq!(urls("a,b,c,d,e,f").len(), MAX_PACKS);
+    }
+}
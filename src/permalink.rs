@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    browser,
+    config::{GameConfig, Mutator},
+};
+
+// Packed low bits first: the format version, one bit per mutator, the
+// character, then the seed. Written in base62 so it fits a URL untouched.
+const FORMAT_VERSION: u128 = 1;
+const VERSION_BITS: u32 = 4;
+const MUTATOR_BITS: u32 = 4;
+const CHARACTER_BITS: u32 = 4;
+const DIGITS: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+// Bit positions in the packed mutator field. New mutators go on the end so
+// old links keep their meaning.
+const MUTATORS: [Mutator; 4] = [
+    Mutator::LowGravity,
+    Mutator::DoubleSpeed,
+    Mutator::HazardsOnly,
+    Mutator::Mirror,
+];
+
+// Only the boy so far. Links carry the character anyway so they stay valid
+// once there are others to pick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Character {
+    #[default]
+    RedHatBoy,
+}
+
+impl Character {
+    fn id(&self) -> u128 {
+        match self {
+            Character::RedHatBoy => 0,
+        }
+    }
+
+    fn from_id(id: u128) -> Option<Self> {
+        match id {
+            0 => Some(Character::RedHatBoy),
+            _ => None,
+        }
+    }
+}
+
+// A run someone else can play exactly, shared as `?run=<code>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunLink {
+    pub seed: u64,
+    pub config: GameConfig,
+    pub character: Character,
+}
+
+impl RunLink {
+    pub fn encode(&self) -> String {
+        let mutators = MUTATORS
+            .iter()
+            .zip(0..)
+            .filter(|(mutator, _bit)| self.config.mutators().contains(mutator))
+            .fold(0, |bits, (_mutator, bit)| bits | 1 << bit);
+        let packed = FORMAT_VERSION
+            | mutators << VERSION_BITS
+            | self.character.id() << (VERSION_BITS + MUTATOR_BITS)
+            | u128::from(self.seed) << (VERSION_BITS + MUTATOR_BITS + CHARACTER_BITS);
+        to_base62(packed)
+    }
+
+    pub fn decode(code: &str) -> Result<Self> {
+        let packed = from_base62(code)?;
+        let field = |shift: u32, bits: u32| (packed >> shift) & ((1 << bits) - 1);
+        let version = field(0, VERSION_BITS);
+        if version != FORMAT_VERSION {
+            return Err(anyhow!(
+                "Run link version {} is not one this game reads",
+                version
+            ));
+        }
+        let mutator_bits = field(VERSION_BITS, MUTATOR_BITS);
+        let mutators: Vec<Mutator> = MUTATORS
+            .iter()
+            .zip(0..)
+            .filter(|(_mutator, bit)| mutator_bits & 1 << bit != 0)
+            .map(|(mutator, _bit)| *mutator)
+            .collect();
+        let character_id = field(VERSION_BITS + MUTATOR_BITS, CHARACTER_BITS);
+        let character = Character::from_id(character_id)
+            .ok_or_else(|| anyhow!("Unknown character {} in run link", character_id))?;
+        let seed = u64::try_from(packed >> (VERSION_BITS + MUTATOR_BITS + CHARACTER_BITS))
+            .map_err(|err| anyhow!("Run link seed is too large {:#?}", err))?;
+        Ok(RunLink {
+            seed,
+            config: GameConfig::with_mutators(&mutators),
+            character,
+        })
+    }
+
+    // From `?run=<code>`. A link that does not decode starts a normal run.
+    pub fn from_browser() -> Result<Option<Self>> {
+        Ok(
+            browser::query_param("run")?.and_then(|code| match RunLink::decode(&code) {
+                Ok(run_link) => Some(run_link),
+                Err(err) => {
+                    log!("Ignoring run link {:#?}", err);
+                    None
+                }
+            }),
+        )
+    }
+
+    // This page with the code as its only query parameter.
+    pub fn url(&self) -> Result<String> {
+        let location = browser::window()?.location();
+        let origin = location
+            .origin()
+            .map_err(|err| anyhow!("Could not read location origin {:#?}", err))?;
+        let path = location
+            .pathname()
+            .map_err(|err| anyhow!("Could not read location path {:#?}", err))?;
+        Ok(format!("{}{}?run={}", origin, path, self.encode()))
+    }
+}
+
+fn to_base62(mut value: u128) -> String {
+    let mut digits = vec![];
+    loop {
+        digits.push(DIGITS[(value % 62) as usize] as char);
+        value /= 62;
+        if value == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+fn from_base62(code: &str) -> Result<u128> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err(anyhow!("Run link is empty"));
+    }
+    code.bytes().try_fold(0u128, |value, byte| {
+        let digit = DIGITS
+            .iter()
+            .position(|digit| *digit == byte)
+            .ok_or_else(|| anyhow!("{} is not a run link character", byte as char))?;
+        value
+            .checked_mul(62)
+            .and_then(|value| value.checked_add(digit as u128))
+            .ok_or_else(|| anyhow!("Run link is too long"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base62_round_trips() {
+        assert_eq!(to_base62(0), "0");
+        assert_eq!(to_base62(61), "z");
+        assert_eq!(to_base62(62), "10");
+        assert_eq!(from_base62(&to_base62(u128::MAX)).unwrap(), u128::MAX);
+        assert!(from_base62("a-b").is_err());
+        assert!(from_base62("").is_err());
+    }
+
+    #[test]
+    fn links_carry_seed_mutators_and_character() {
+        let link = RunLink {
+            seed: u64::MAX,
+            config: GameConfig::with_mutators(&[Mutator::Mirror, Mutator::LowGravity]),
+            character: Character::RedHatBoy,
+        };
+        let code = link.encode();
+
+        assert!(code.len() <= 14);
+        assert!(code.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(RunLink::decode(&code).unwrap(), link);
+    }
+
+    #[test]
+    fn small_seeds_make_short_links() {
+        let link = RunLink {
+            seed: 42,
+            config: GameConfig::default(),
+            character: Character::RedHatBoy,
+        };
+
+        assert_eq!(link.encode().len(), 3);
+        assert_eq!(RunLink::decode(&link.encode()).unwrap(), link);
+    }
+
+    #[test]
+    fn other_versions_are_refused() {
+        // Version 2 with nothing else set.
+        assert!(RunLink::decode("2").is_err());
+        assert!(RunLink::decode("1").is_ok());
+    }
+}
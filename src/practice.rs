@@ -0,0 +1,121 @@
+use crate::engine::{Anchor, Color, Point, Renderer};
+
+const SLOW_MOTION_FACTOR: u32 = 2;
+const TEXT_X: i16 = 20;
+const TEXT_Y: i16 = 40;
+const LINE_HEIGHT: i16 = 24;
+const HINT: &str = "R: restart  S: slow motion  Esc: quit";
+const DOT_SPACING: usize = 3;
+const DOT_RADIUS: i16 = 3;
+const DOT_COLOR: Color = Color::rgba(255, 255, 255, 0.8);
+
+pub struct Practice {
+    attempts: u32,
+    slow_motion: bool,
+    frame: u32,
+    restart_held: bool,
+    slow_motion_held: bool,
+}
+
+impl Practice {
+    pub fn new() -> Self {
+        Practice {
+            attempts: 1,
+            slow_motion: false,
+            frame: 0,
+            restart_held: false,
+            slow_motion_held: false,
+        }
+    }
+
+    // Keys act once per press rather than on every frame they are held.
+    pub fn restart_requested(&mut self, held: bool) -> bool {
+        let pressed = held && !self.restart_held;
+        self.restart_held = held;
+        pressed
+    }
+
+    pub fn toggle_slow_motion(&mut self, held: bool) {
+        if held && !self.slow_motion_held {
+            self.slow_motion = !self.slow_motion;
+        }
+        self.slow_motion_held = held;
+    }
+
+    pub fn retry(&mut self) {
+        self.attempts += 1;
+    }
+
+    // In slow motion the world only moves on every other frame.
+    pub fn should_step(&mut self) -> bool {
+        self.frame = self.frame.wrapping_add(1);
+        !self.slow_motion || self.frame % SLOW_MOTION_FACTOR == 0
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let mut lines = vec![format!("Attempt {}", self.attempts), HINT.to_string()];
+        if self.slow_motion {
+            lines.push("Slow motion".to_string());
+        }
+        lines.iter().enumerate().for_each(|(index, line)| {
+            let position = renderer.hud().position(
+                Anchor::TopLeft,
+                Point {
+                    x: TEXT_X,
+                    y: TEXT_Y + index as i16 * LINE_HEIGHT,
+                },
+            );
+            if let Err(err) = renderer.draw_text(line, &position) {
+                log!("Could not draw practice text {:#?}", err);
+            }
+        });
+    }
+}
+
+// A predicted jump, dotted every few frames.
+pub fn draw_trajectory(renderer: &Renderer, points: &[Point]) {
+    points.iter().step_by(DOT_SPACING).for_each(|point| {
+        if let Err(err) = renderer.fill_circle(point, DOT_RADIUS, &DOT_COLOR) {
+            log!("Could not draw jump trajectory {:#?}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holding_restart_only_restarts_once() {
+        let mut practice = Practice::new();
+
+        assert!(practice.restart_requested(true));
+        assert!(!practice.restart_requested(true));
+        assert!(!practice.restart_requested(false));
+        assert!(practice.restart_requested(true));
+    }
+
+    #[test]
+    fn slow_motion_skips_every_other_frame() {
+        let mut practice = Practice::new();
+        assert!((0..4).all(|_| practice.should_step()));
+
+        practice.toggle_slow_motion(true);
+        practice.toggle_slow_motion(true);
+        let steps = (0..4).filter(|_| practice.should_step()).count();
+        assert_eq!(steps, 2);
+
+        practice.toggle_slow_motion(false);
+        practice.toggle_slow_motion(true);
+        assert!((0..4).all(|_| practice.should_step()));
+    }
+
+    #[test]
+    fn retries_count_attempts() {
+        let mut practice = Practice::new();
+        practice.retry();
+        practice.retry();
+
+        assert_eq!(practice.attempts, 3);
+    }
+}
@@ -0,0 +1,521 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    StreamExt,
+};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    MessageEvent, RtcDataChannel, RtcDataChannelEvent, RtcDataChannelState, RtcIceCandidateInit,
+    RtcPeerConnection, RtcPeerConnectionIceEvent, RtcSdpType, RtcSessionDescriptionInit,
+};
+
+use crate::{
+    browser::{self, socket::Socket},
+    consent::Consent,
+    engine::{Color, Rect, Renderer},
+};
+
+const CHANNEL_LABEL: &str = "race";
+const DEFAULT_ROOM: &str = "lobby";
+// Positions go out every few frames; the rival is drawn where it last was.
+const POSITION_INTERVAL: u32 = 3;
+const RIVAL_WIDTH: i16 = 60;
+const RIVAL_HEIGHT: i16 = 110;
+const RIVAL_COLOR: Color = Color::rgba(40, 120, 220, 0.45);
+
+// What racers send each other once the data channel is open. The host picks
+// the seed so both runs lay out the same course. The seed is a string
+// because JSON numbers lose precision past 53 bits.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum RaceMessage {
+    Seed { seed: String },
+    Position { distance: i32, y: i16 },
+    Finished { distance: i32 },
+}
+
+// The handshake, relayed by the signaling server to the other player in the
+// room. The server only answers `Join` with `Matched` once two players are
+// waiting, telling the first of them to host.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+enum Signal {
+    Join {
+        room: String,
+    },
+    Matched {
+        host: bool,
+    },
+    Offer {
+        sdp: String,
+    },
+    Answer {
+        sdp: String,
+    },
+    Candidate {
+        candidate: String,
+        sdp_mid: Option<String>,
+        sdp_m_line_index: Option<u16>,
+    },
+}
+
+// The other player, as last heard from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rival {
+    distance: Option<i32>,
+    y: i16,
+    finished: bool,
+}
+
+impl Rival {
+    fn receive(&mut self, message: &RaceMessage) {
+        match message {
+            RaceMessage::Position { distance, y } => {
+                self.distance = Some(*distance);
+                self.y = *y;
+            }
+            RaceMessage::Finished { distance } => {
+                self.distance = Some(*distance);
+                self.finished = true;
+            }
+            RaceMessage::Seed { .. } => {}
+        }
+    }
+
+    pub fn distance(&self) -> Option<i32> {
+        self.distance
+    }
+
+    // Where the rival is on screen, relative to the boy standing at `boy_x`
+    // having walked `distance`.
+    fn screen_x(&self, boy_x: i16, distance: i32) -> Option<i16> {
+        self.distance.map(|rival| {
+            (boy_x as i32 + rival - distance).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+        })
+    }
+
+    pub fn draw(&self, renderer: &Renderer, boy_x: i16, distance: i32) {
+        if let Some(x) = self.screen_x(boy_x, distance) {
+            renderer.fill_rect(
+                &Rect::new_from_x_y(x, self.y, RIVAL_WIDTH, RIVAL_HEIGHT),
+                &RIVAL_COLOR,
+            );
+        }
+    }
+}
+
+struct RaceLink {
+    channel: RtcDataChannel,
+    messages: UnboundedReceiver<RaceMessage>,
+    host: bool,
+    _peer: RtcPeerConnection,
+}
+
+// A two player race over a direct peer connection. The connection is made in
+// the background; `update` picks it up once it is ready.
+#[derive(Default)]
+pub struct Race {
+    connecting: Option<oneshot::Receiver<Result<RaceLink>>>,
+    link: Option<RaceLink>,
+    failed: bool,
+    seed: Option<u64>,
+    rival: Rival,
+    frames: u32,
+}
+
+// Races are offered with `?race=<signaling websocket url>`, optionally with
+// `&room=<name>` to meet a particular player, once the player has agreed to
+// go online.
+pub fn lobby_from_browser() -> Result<Option<(String, String)>> {
+    if !Consent::load().allows_online() {
+        return Ok(None);
+    }
+    let endpoint = match browser::query_param("race")? {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+    let room = browser::query_param("room")?.unwrap_or_else(|| DEFAULT_ROOM.to_string());
+    Ok(Some((endpoint, room)))
+}
+
+impl Race {
+    pub fn join(endpoint: &str, room: &str) -> Self {
+        let (link_sender, link_receiver) = oneshot::channel();
+        let endpoint = endpoint.to_string();
+        let room = room.to_string();
+        browser::spawn_local(async move {
+            if link_sender.send(connect(&endpoint, &room).await).is_err() {
+                log!("Race lobby closed before connecting");
+            }
+        });
+        Race {
+            connecting: Some(link_receiver),
+            ..Race::default()
+        }
+    }
+
+    pub fn update(&mut self) {
+        let connection = match &mut self.connecting {
+            Some(connecting) => connecting
+                .try_recv()
+                .map_err(|err| anyhow!("Race connection was dropped {:#?}", err)),
+            None => Ok(None),
+        };
+        match connection {
+            Ok(None) => {}
+            Ok(Some(Ok(link))) => {
+                self.connecting = None;
+                self.connected(link);
+            }
+            Ok(Some(Err(err))) | Err(err) => {
+                self.connecting = None;
+                self.failed = true;
+                log!("Could not join race {:#?}", err);
+            }
+        }
+
+        let mut received = vec![];
+        if let Some(link) = &mut self.link {
+            while let Ok(Some(message)) = link.messages.try_next() {
+                received.push(message);
+            }
+        }
+        received.iter().for_each(|message| self.receive(message));
+    }
+
+    fn connected(&mut self, link: RaceLink) {
+        let host = link.host;
+        self.link = Some(link);
+        if host {
+            let seed = thread_rng().gen();
+            self.seed = Some(seed);
+            self.send(&RaceMessage::Seed {
+                seed: seed.to_string(),
+            });
+        }
+    }
+
+    fn receive(&mut self, message: &RaceMessage) {
+        match message {
+            RaceMessage::Seed { seed } if self.seed.is_none() => match seed.parse() {
+                Ok(seed) => self.seed = Some(seed),
+                Err(err) => {
+                    log!("Could not read race seed {:#?}", err);
+                }
+            },
+            _ => self.rival.receive(message),
+        }
+    }
+
+    fn send(&self, message: &RaceMessage) {
+        if let Some(link) = &self.link {
+            if let Err(err) = browser::to_json(message).and_then(|json| {
+                link.channel
+                    .send_with_str(&json)
+                    .map_err(|err| anyhow!("Could not send race message {:#?}", err))
+            }) {
+                log!("Could not send race message {:#?}", err);
+            }
+        }
+    }
+
+    // The seed both players run with, once they have agreed on one.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn rival(&self) -> &Rival {
+        &self.rival
+    }
+
+    pub fn send_position(&mut self, distance: i32, y: i16) {
+        self.frames += 1;
+        if self.frames % POSITION_INTERVAL == 0 {
+            self.send(&RaceMessage::Position { distance, y });
+        }
+    }
+
+    pub fn finish(&self, distance: i32) {
+        self.send(&RaceMessage::Finished { distance });
+    }
+}
+
+fn send_signal(socket: &Socket, signal: &Signal) -> Result<()> {
+    socket.send(&browser::to_json(signal)?);
+    Ok(())
+}
+
+// The next message from the signaling server that parses as a signal.
+async fn next_signal(messages: &mut UnboundedReceiver<String>) -> Option<Signal> {
+    while let Some(text) = messages.next().await {
+        match browser::from_json(&text) {
+            Ok(signal) => return Some(signal),
+            Err(err) => {
+                log!("Could not read signal {:#?}", err);
+            }
+        }
+    }
+    None
+}
+
+// Forwards every message from the data channel that parses as a `T`.
+fn listen<T: serde::de::DeserializeOwned + 'static>(
+    set_onmessage: impl FnOnce(&js_sys::Function),
+) -> UnboundedReceiver<T> {
+    let (mut sender, receiver): (UnboundedSender<T>, _) = unbounded();
+    let on_message = browser::closure_wrap(Box::new(move |event: MessageEvent| {
+        match event
+            .data()
+            .as_string()
+            .ok_or_else(|| anyhow!("Race message was not text"))
+            .and_then(|text| browser::from_json(&text))
+        {
+            Ok(message) => {
+                if let Err(err) = sender.start_send(message) {
+                    log!("Could not pass on race message {:#?}", err);
+                }
+            }
+            Err(err) => {
+                log!("Could not read race message {:#?}", err);
+            }
+        }
+    }) as Box<dyn FnMut(MessageEvent)>);
+    set_onmessage(on_message.as_ref().unchecked_ref());
+    on_message.forget();
+    receiver
+}
+
+// Resolves once `set_onopen` fires, unless `is_open` says it already has.
+async fn opened(set_onopen: impl FnOnce(Option<&js_sys::Function>), is_open: impl Fn() -> bool) {
+    let (open_sender, open_receiver) = oneshot::channel::<()>();
+    let open_sender = Rc::new(RefCell::new(Some(open_sender)));
+    let on_open = browser::closure_wrap(Box::new(move || {
+        if let Some(sender) = open_sender.borrow_mut().take() {
+            let _ = sender.send(());
+        }
+    }) as Box<dyn FnMut()>);
+    set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    if !is_open() {
+        let _ = open_receiver.await;
+    }
+}
+
+fn sdp_of(description: &JsValue) -> Result<String> {
+    js_sys::Reflect::get(description, &JsValue::from_str("sdp"))
+        .ok()
+        .and_then(|sdp| sdp.as_string())
+        .ok_or_else(|| anyhow!("Session description has no sdp"))
+}
+
+async fn set_description(
+    peer: &RtcPeerConnection,
+    kind: RtcSdpType,
+    sdp: &str,
+    local: bool,
+) -> Result<()> {
+    let mut description = RtcSessionDescriptionInit::new(kind);
+    description.sdp(sdp);
+    let promise = if local {
+        peer.set_local_description(&description)
+    } else {
+        peer.set_remote_description(&description)
+    };
+    JsFuture::from(promise)
+        .await
+        .map(|_| ())
+        .map_err(|err| anyhow!("Could not set session description {:#?}", err))
+}
+
+async fn add_candidate(peer: &RtcPeerConnection, signal: &Signal) {
+    if let Signal::Candidate {
+        candidate,
+        sdp_mid,
+        sdp_m_line_index,
+    } = signal
+    {
+        let mut init = RtcIceCandidateInit::new(candidate);
+        init.sdp_mid(sdp_mid.as_deref())
+            .sdp_m_line_index(*sdp_m_line_index);
+        if let Err(err) =
+            JsFuture::from(peer.add_ice_candidate_with_opt_rtc_ice_candidate_init(Some(&init)))
+                .await
+        {
+            log!("Could not add race candidate {:#?}", err);
+        }
+    }
+}
+
+// Meets the other player through the signaling server, then swaps offer,
+// answer and candidates with them until the data channel opens. After that
+// the server is no longer needed.
+async fn connect(endpoint: &str, room: &str) -> Result<RaceLink> {
+    let (socket, mut signals) = Socket::open(endpoint)?;
+    send_signal(
+        &socket,
+        &Signal::Join {
+            room: room.to_string(),
+        },
+    )?;
+
+    let host = loop {
+        match next_signal(&mut signals).await {
+            Some(Signal::Matched { host }) => break host,
+            Some(_) => {}
+            None => return Err(anyhow!("Signaling closed before a rival joined")),
+        }
+    };
+
+    let peer = RtcPeerConnection::new()
+        .map_err(|err| anyhow!("Could not create peer connection {:#?}", err))?;
+    let candidate_socket = socket.clone();
+    let on_candidate = browser::closure_wrap(Box::new(move |event: RtcPeerConnectionIceEvent| {
+        if let Some(candidate) = event.candidate() {
+            let signal = Signal::Candidate {
+                candidate: candidate.candidate(),
+                sdp_mid: candidate.sdp_mid(),
+                sdp_m_line_index: candidate.sdp_m_line_index(),
+            };
+            if let Err(err) = send_signal(&candidate_socket, &signal) {
+                log!("Could not send race candidate {:#?}", err);
+            }
+        }
+    }) as Box<dyn FnMut(RtcPeerConnectionIceEvent)>);
+    peer.set_onicecandidate(Some(on_candidate.as_ref().unchecked_ref()));
+    on_candidate.forget();
+
+    let (channel_sender, channel_receiver) = oneshot::channel();
+    let channel_sender = Rc::new(RefCell::new(Some(channel_sender)));
+    let on_channel = browser::closure_wrap(Box::new(move |event: RtcDataChannelEvent| {
+        if let Some(sender) = channel_sender.borrow_mut().take() {
+            let _ = sender.send(event.channel());
+        }
+    }) as Box<dyn FnMut(RtcDataChannelEvent)>);
+    peer.set_ondatachannel(Some(on_channel.as_ref().unchecked_ref()));
+    on_channel.forget();
+
+    let host_channel = if host {
+        let channel = peer.create_data_channel(CHANNEL_LABEL);
+        let offer = JsFuture::from(peer.create_offer())
+            .await
+            .map_err(|err| anyhow!("Could not create race offer {:#?}", err))?;
+        let sdp = sdp_of(&offer)?;
+        set_description(&peer, RtcSdpType::Offer, &sdp, true).await?;
+        send_signal(&socket, &Signal::Offer { sdp })?;
+        Some(channel)
+    } else {
+        None
+    };
+
+    // Candidates can arrive before the description they belong to, so they
+    // wait until it has been set.
+    let mut early_candidates = vec![];
+    let remote_sdp = loop {
+        match next_signal(&mut signals).await {
+            Some(Signal::Offer { sdp }) if !host => break sdp,
+            Some(Signal::Answer { sdp }) if host => break sdp,
+            Some(candidate @ Signal::Candidate { .. }) => early_candidates.push(candidate),
+            Some(_) => {}
+            None => return Err(anyhow!("Signaling closed during the race handshake")),
+        }
+    };
+    if host {
+        set_description(&peer, RtcSdpType::Answer, &remote_sdp, false).await?;
+    } else {
+        set_description(&peer, RtcSdpType::Offer, &remote_sdp, false).await?;
+        let answer = JsFuture::from(peer.create_answer())
+            .await
+            .map_err(|err| anyhow!("Could not create race answer {:#?}", err))?;
+        let sdp = sdp_of(&answer)?;
+        set_description(&peer, RtcSdpType::Answer, &sdp, true).await?;
+        send_signal(&socket, &Signal::Answer { sdp })?;
+    }
+    for candidate in early_candidates.iter() {
+        add_candidate(&peer, candidate).await;
+    }
+
+    let candidate_peer = peer.clone();
+    browser::spawn_local(async move {
+        while let Some(signal) = next_signal(&mut signals).await {
+            add_candidate(&candidate_peer, &signal).await;
+        }
+    });
+
+    let channel = match host_channel {
+        Some(channel) => channel,
+        None => channel_receiver
+            .await
+            .map_err(|err| anyhow!("Rival left before opening a channel {:#?}", err))?,
+    };
+    let messages = listen::<RaceMessage>(|on_message| channel.set_onmessage(Some(on_message)));
+    opened(
+        |on_open| channel.set_onopen(on_open),
+        || channel.ready_state() == RtcDataChannelState::Open,
+    )
+    .await;
+    socket.close();
+
+    Ok(RaceLink {
+        channel,
+        messages,
+        host,
+        _peer: peer,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_seed_heard_is_kept() {
+        let mut race = Race::default();
+        race.receive(&RaceMessage::Seed {
+            seed: u64::MAX.to_string(),
+        });
+        race.receive(&RaceMessage::Seed {
+            seed: "7".to_string(),
+        });
+
+        assert_eq!(race.seed(), Some(u64::MAX));
+    }
+
+    #[test]
+    fn rival_is_drawn_relative_to_the_boy() {
+        let mut rival = Rival::default();
+        assert_eq!(rival.screen_x(100, 500), None);
+
+        rival.receive(&RaceMessage::Position {
+            distance: 650,
+            y: 300,
+        });
+        assert_eq!(rival.screen_x(100, 500), Some(250));
+
+        rival.receive(&RaceMessage::Finished { distance: 400 });
+        assert_eq!(rival.screen_x(100, 500), Some(0));
+        assert!(rival.finished);
+    }
+
+    #[test]
+    fn messages_are_tagged_by_type() {
+        let message = RaceMessage::Position {
+            distance: 12,
+            y: 300,
+        };
+        let json = serde_json::to_string(&message).unwrap();
+
+        assert_eq!(json, r#"{"type":"Position","distance":12,"y":300}"#);
+        assert_eq!(serde_json::from_str::<RaceMessage>(&json).unwrap(), message);
+    }
+}
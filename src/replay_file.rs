@@ -0,0 +1,400 @@
+use anyhow::{anyhow, Result};
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+
+#[cfg(debug_assertions)]
+use crate::browser;
+use crate::{
+    browser::idb::{self, Database},
+    leaderboard::Replay,
+};
+
+pub const LAST_RUN_KEY: &str = "walk_the_dog.last_run";
+
+// "WTDR", the format version and a flags byte, then the run: varint seed and
+// frame count followed by the jumps, either as varint gaps and lengths or as
+// one bit per frame, whichever is smaller, then the dashes as varint gaps,
+// the grabs as runs and the world hashes as a varint count of four byte
+// little endian words. The body is deflated when that makes it smaller still.
+const MAGIC: &[u8; 4] = b"WTDR";
+const FORMAT_VERSION: u8 = 4;
+// The first versions with dashes, grabs and hashes. Older files have none.
+const DASHES_VERSION: u8 = 2;
+const GRABS_VERSION: u8 = 3;
+const HASHES_VERSION: u8 = 4;
+const HEADER_LEN: usize = 6;
+const DEFLATED: u8 = 0b01;
+const BITMAP: u8 = 0b10;
+const COMPRESSION_LEVEL: u8 = 9;
+// Far more than any real run inflates to, so a bad file cannot eat memory.
+const MAX_BODY_LEN: usize = 1 << 20;
+
+// Everything needed to play a run again, packed small enough for share
+// links.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunRecord {
+    pub seed: u64,
+    pub frames: u32,
+    pub replay: Replay,
+}
+
+impl RunRecord {
+    pub fn encode(&self) -> Vec<u8> {
+        let jumps = self.replay.jumps();
+        let runs = encode_runs(jumps);
+        let bitmap = encode_bitmap(jumps);
+        let (mut flags, inputs) = if bitmap.len() < runs.len() {
+            (BITMAP, bitmap)
+        } else {
+            (0, runs)
+        };
+        let mut body = vec![];
+        write_varint(&mut body, self.seed);
+        write_varint(&mut body, u64::from(self.frames));
+        body.extend(inputs);
+        body.extend(encode_dashes(self.replay.dashes()));
+        body.extend(encode_runs(self.replay.grabs()));
+        body.extend(encode_hashes(self.replay.hashes()));
+        let deflated = compress_to_vec(&body, COMPRESSION_LEVEL);
+        if deflated.len() < body.len() {
+            flags |= DEFLATED;
+            body = deflated;
+        }
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([FORMAT_VERSION, flags]);
+        bytes.extend(body);
+        bytes
+    }
+
+    // Only the console reads replays back so far.
+    #[allow(dead_code)]
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(anyhow!("Not a replay"));
+        }
+        let (version, flags) = (bytes[4], bytes[5]);
+        if version > FORMAT_VERSION {
+            return Err(anyhow!(
+                "Replay version {} is newer than this game reads ({})",
+                version,
+                FORMAT_VERSION
+            ));
+        }
+        let body = &bytes[HEADER_LEN..];
+        let body = if flags & DEFLATED != 0 {
+            decompress_to_vec_with_limit(body, MAX_BODY_LEN)
+                .map_err(|err| anyhow!("Could not inflate replay {:#?}", err))?
+        } else {
+            body.to_vec()
+        };
+
+        let mut reader = Reader::new(&body);
+        let seed = reader.varint()?;
+        let frames = u32::try_from(reader.varint()?)
+            .map_err(|err| anyhow!("Replay frame count is too large {:#?}", err))?;
+        let replay = if flags & BITMAP != 0 {
+            decode_bitmap(&mut reader)?
+        } else {
+            Replay::from_jumps(decode_runs(&mut reader)?)
+        };
+        let replay = if version >= DASHES_VERSION {
+            replay.with_dashes(decode_dashes(&mut reader)?)
+        } else {
+            replay
+        };
+        let replay = if version >= GRABS_VERSION {
+            replay.with_grabs(decode_runs(&mut reader)?)
+        } else {
+            replay
+        };
+        let replay = if version >= HASHES_VERSION {
+            replay.with_hashes(decode_hashes(&mut reader)?)
+        } else {
+            replay
+        };
+        Ok(RunRecord {
+            seed,
+            frames,
+            replay,
+        })
+    }
+
+    // Base64, for share links.
+    #[cfg(debug_assertions)]
+    pub fn to_code(&self) -> Result<String> {
+        let binary: String = self.encode().into_iter().map(char::from).collect();
+        browser::window()?
+            .btoa(&binary)
+            .map_err(|err| anyhow!("Could not encode replay {:#?}", err))
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn from_code(code: &str) -> Result<Self> {
+        let binary = browser::window()?
+            .atob(code.trim())
+            .map_err(|err| anyhow!("Replay code is not valid base64 {:#?}", err))?;
+        let bytes: Vec<u8> = binary.chars().map(|c| c as u8).collect();
+        RunRecord::decode(&bytes)
+    }
+
+    pub async fn save(&self, key: &str) -> Result<()> {
+        Database::open()
+            .await?
+            .put_bytes(idb::REPLAYS, key, &self.encode())
+            .await
+    }
+}
+
+// Each run is the gap since the last one ended, then its length.
+fn encode_runs(runs: &[[u32; 2]]) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, runs.len() as u64);
+    let mut end = 0;
+    runs.iter().for_each(|[first, count]| {
+        write_varint(&mut bytes, u64::from(first - end));
+        write_varint(&mut bytes, u64::from(*count));
+        end = first + count;
+    });
+    bytes
+}
+
+fn decode_runs(reader: &mut Reader) -> Result<Vec<[u32; 2]>> {
+    let len = reader.varint()?;
+    let mut end: u32 = 0;
+    let mut runs = vec![];
+    for _run in 0..len {
+        let first = reader.frame()?.checked_add(end);
+        let count = reader.frame()?;
+        match first.and_then(|first| first.checked_add(count)) {
+            Some(run_end) => {
+                runs.push([run_end - count, count]);
+                end = run_end;
+            }
+            None => return Err(anyhow!("Replay runs past the last frame")),
+        }
+    }
+    Ok(runs)
+}
+
+// Each dash is the gap since the one before.
+fn encode_dashes(dashes: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, dashes.len() as u64);
+    let mut last = 0;
+    dashes.iter().for_each(|dash| {
+        write_varint(&mut bytes, u64::from(dash - last));
+        last = *dash;
+    });
+    bytes
+}
+
+fn decode_dashes(reader: &mut Reader) -> Result<Vec<u32>> {
+    let len = reader.varint()?;
+    let mut last: u32 = 0;
+    let mut dashes = vec![];
+    for _dash in 0..len {
+        last = reader
+            .frame()?
+            .checked_add(last)
+            .ok_or_else(|| anyhow!("Replay dashes past the last frame"))?;
+        dashes.push(last);
+    }
+    Ok(dashes)
+}
+
+// Hashes look random, so they are written whole rather than as varints.
+fn encode_hashes(hashes: &[u32]) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_varint(&mut bytes, hashes.len() as u64);
+    hashes
+        .iter()
+        .for_each(|hash| bytes.extend(hash.to_le_bytes()));
+    bytes
+}
+
+fn decode_hashes(reader: &mut Reader) -> Result<Vec<u32>> {
+    let len = reader.varint()?;
+    let mut hashes = vec![];
+    for _hash in 0..len {
+        let bytes = reader.take(4)?;
+        hashes.push(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    }
+    Ok(hashes)
+}
+
+// Bit `n` is set when the jump key was held on frame `n`.
+fn encode_bitmap(jumps: &[[u32; 2]]) -> Vec<u8> {
+    let len = jumps.last().map_or(0, |[first, count]| first + count);
+    let mut bits = vec![0u8; (len as usize).div_ceil(8)];
+    jumps
+        .iter()
+        .flat_map(|[first, count]| *first..first + count)
+        .for_each(|frame| bits[frame as usize / 8] |= 1 << (frame % 8));
+    let mut bytes = vec![];
+    write_varint(&mut bytes, u64::from(len));
+    bytes.extend(bits);
+    bytes
+}
+
+fn decode_bitmap(reader: &mut Reader) -> Result<Replay> {
+    let len = reader.frame()?;
+    let bits = reader.take((len as usize).div_ceil(8))?;
+    let mut replay = Replay::default();
+    (0..len)
+        .filter(|frame| bits[*frame as usize / 8] & (1 << (frame % 8)) != 0)
+        .for_each(|frame| replay.record_jump(frame));
+    Ok(replay)
+}
+
+// LEB128: seven bits at a time, low bits first, with the top bit set on all
+// but the last byte.
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("Replay ends early"))?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Replay varint is too long"))
+    }
+
+    fn frame(&mut self) -> Result<u32> {
+        u32::try_from(self.varint()?).map_err(|err| anyhow!("Replay frame is too large {:#?}", err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leaderboard::Input;
+
+    fn record(frames: impl Iterator<Item = u32>) -> RunRecord {
+        let mut replay = Replay::default();
+        frames.for_each(|frame| replay.record_jump(frame));
+        RunRecord {
+            seed: u64::MAX - 7,
+            frames: 5000,
+            replay,
+        }
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        let values = [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX];
+        let mut bytes = vec![];
+        values
+            .iter()
+            .for_each(|value| write_varint(&mut bytes, *value));
+        assert_eq!(&bytes[..5], &[0, 1, 127, 0x80, 1]);
+
+        let mut reader = Reader::new(&bytes);
+        values
+            .iter()
+            .for_each(|value| assert_eq!(reader.varint().unwrap(), *value));
+        assert!(reader.varint().is_err());
+    }
+
+    #[test]
+    fn sparse_jumps_are_stored_as_runs() {
+        let record = record([10, 11, 12, 900, 4000].into_iter());
+        let bytes = record.encode();
+
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(bytes[5] & BITMAP, 0);
+        assert!(bytes.len() < 30);
+        assert_eq!(RunRecord::decode(&bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn dense_jumps_are_bitpacked() {
+        let record = record((0..2000).filter(|frame| frame % 3 == 0));
+        let bytes = record.encode();
+
+        assert_ne!(bytes[5] & BITMAP, 0);
+        assert!(bytes.len() < 2000 / 8);
+        assert_eq!(RunRecord::decode(&bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn repetitive_runs_are_deflated() {
+        let record = record((0..20_000).filter(|frame| frame % 40 < 5));
+        let bytes = record.encode();
+
+        assert_ne!(bytes[5] & DEFLATED, 0);
+        assert_eq!(RunRecord::decode(&bytes).unwrap(), record);
+    }
+
+    #[test]
+    fn inputs_round_trip_and_old_files_have_none() {
+        let mut record = record([10, 11].into_iter());
+        record.replay.record_dash(30);
+        record.replay.record_dash(700);
+        record.replay.record(
+            31,
+            Input {
+                grabbing: true,
+                ..Input::default()
+            },
+        );
+        assert_eq!(RunRecord::decode(&record.encode()).unwrap(), record);
+
+        let mut old = MAGIC.to_vec();
+        old.extend([1, 0]);
+        old.extend([7, 100]);
+        old.extend(encode_runs(&[[10, 2]]));
+        let old = RunRecord::decode(&old).unwrap();
+        assert_eq!(old.replay.jumps(), &[[10, 2]]);
+        assert!(old.replay.dashes().is_empty());
+        assert!(old.replay.grabs().is_empty());
+        assert!(old.replay.hashes().is_empty());
+    }
+
+    #[test]
+    fn hashes_round_trip() {
+        let mut record = record([10].into_iter());
+        record.replay = record.replay.with_hashes(vec![0, 1, u32::MAX, 0xdead_beef]);
+
+        assert_eq!(RunRecord::decode(&record.encode()).unwrap(), record);
+    }
+
+    #[test]
+    fn bad_files_are_refused() {
+        let mut bytes = record([5].into_iter()).encode();
+        assert!(RunRecord::decode(b"JSON{}").is_err());
+        assert!(RunRecord::decode(&bytes[..bytes.len() - 1]).is_err());
+
+        bytes[4] = FORMAT_VERSION + 1;
+        assert!(RunRecord::decode(&bytes).is_err());
+    }
+}
@@ -0,0 +1,387 @@
+use crate::{
+    engine::{Align, Color, KeyState, Point, Rect, Renderer, TextStyle},
+    i18n::text,
+    leaderboard::{Input, Replay, HASH_INTERVAL},
+    menus::MenuItem,
+};
+
+// Seeking back restores the nearest keyframe and plays on from there.
+const KEYFRAME_INTERVAL: u32 = 120;
+const FRAMES_PER_SECOND: u32 = 60;
+const LEAVE_KEY: &str = "Escape";
+
+const BAR: Rect = Rect::new_from_x_y(0, 552, 600, 48);
+const PLAY_BUTTON: Rect = Rect::new_from_x_y(8, 558, 52, 36);
+const SPEEDS: [Speed; 3] = [Speed::Half, Speed::Normal, Speed::Double];
+const SPEED_BUTTONS: [Rect; 3] = [
+    Rect::new_from_x_y(66, 558, 44, 36),
+    Rect::new_from_x_y(114, 558, 44, 36),
+    Rect::new_from_x_y(162, 558, 44, 36),
+];
+// The track is drawn thin but takes clicks across the whole height of the bar.
+const TRACK: Rect = Rect::new_from_x_y(218, 572, 300, 8);
+const TRACK_HIT: Rect = Rect::new_from_x_y(218, 552, 300, 48);
+const DONE_BUTTON: Rect = Rect::new_from_x_y(530, 558, 62, 36);
+const LABEL: Point = Point { x: 8, y: 520 };
+
+const BAR_COLOR: Color = Color::rgba(0, 0, 0, 0.6);
+const BUTTON_COLOR: Color = Color::rgb(70, 70, 80);
+const SELECTED_COLOR: Color = Color::rgb(200, 60, 50);
+const TRACK_COLOR: Color = Color::rgb(120, 120, 130);
+const PLAYED_COLOR: Color = Color::rgb(245, 200, 40);
+const TEXT_COLOR: Color = Color::rgb(255, 255, 255);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Speed {
+    Half,
+    Normal,
+    Double,
+}
+
+impl Speed {
+    fn label(&self) -> &'static str {
+        match self {
+            Speed::Half => "0.5x",
+            Speed::Normal => "1x",
+            Speed::Double => "2x",
+        }
+    }
+
+    // Frames played per two drawn.
+    fn half_steps(&self) -> u32 {
+        match self {
+            Speed::Half => 1,
+            Speed::Normal => 2,
+            Speed::Double => 4,
+        }
+    }
+}
+
+// The viewer's buttons, drawn on the canvas or shown on the page.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    TogglePause,
+    Speed(Speed),
+    Done,
+}
+
+impl Action {
+    fn button(&self) -> Rect {
+        match self {
+            Action::TogglePause => PLAY_BUTTON,
+            Action::Speed(speed) => {
+                SPEED_BUTTONS[SPEEDS
+                    .iter()
+                    .position(|listed| listed == speed)
+                    .unwrap_or(0)]
+            }
+            Action::Done => DONE_BUTTON,
+        }
+    }
+}
+
+// What the player asked the viewer for, beyond pausing and changing speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Control {
+    Seek(u32),
+    Done,
+}
+
+// Plays a recorded run back with a scrubber, pause and speed buttons drawn
+// over the canvas. It only decides which frame to show; the game steps the
+// walk there with `input`, snapshotting it as `T`.
+pub struct ReplayViewer<T> {
+    replay: Replay,
+    frames: u32,
+    keyframes: Vec<(u32, T)>,
+    paused: bool,
+    speed: Speed,
+    half_steps: u32,
+    scrubbing: bool,
+    // The first frame whose world hash did not match the recorded one.
+    diverged: Option<u32>,
+}
+
+impl<T> ReplayViewer<T> {
+    // `start` is the walk on the run's first frame.
+    pub fn new(replay: Replay, frames: u32, start: T) -> Self {
+        ReplayViewer {
+            replay,
+            frames,
+            keyframes: vec![(0, start)],
+            paused: false,
+            speed: Speed::Normal,
+            half_steps: 0,
+            scrubbing: false,
+            diverged: None,
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn input(&self, frame: u32) -> Input {
+        self.replay.input(frame)
+    }
+
+    // Checks the world against the hash recorded for `frame`, if there is one,
+    // and reports the first that does not match. The run came apart somewhere
+    // in the interval before it.
+    pub fn verify(&mut self, frame: u32, hash: impl FnOnce() -> u32) {
+        if self.diverged.is_some() {
+            return;
+        }
+        let expected = self.replay.hash_at(frame);
+        if expected.map_or(false, |expected| expected != hash()) {
+            log!(
+                "Replay diverged from the recorded run after frame {} and by frame {}",
+                frame.saturating_sub(HASH_INTERVAL),
+                frame
+            );
+            self.diverged = Some(frame);
+        }
+    }
+
+    // Keeps a snapshot every so often on the way through.
+    pub fn keyframe(&mut self, frame: u32, snapshot: impl FnOnce() -> T) {
+        let seen = matches!(self.keyframes.last(), Some((last, _snapshot)) if *last >= frame);
+        if !seen && frame.is_multiple_of(KEYFRAME_INTERVAL) {
+            self.keyframes.push((frame, snapshot()));
+        }
+    }
+
+    // The latest keyframe at or before `frame`.
+    pub fn keyframe_before(&self, frame: u32) -> (u32, &T) {
+        let (keyframe, snapshot) = self
+            .keyframes
+            .iter()
+            .rev()
+            .find(|(keyframe, _snapshot)| *keyframe <= frame)
+            .unwrap_or(&self.keyframes[0]);
+        (*keyframe, snapshot)
+    }
+
+    // How many frames to play this update to keep up the speed, from `frame`.
+    pub fn frames_to_play(&mut self, frame: u32) -> u32 {
+        if self.paused || self.scrubbing || frame >= self.frames {
+            return 0;
+        }
+        self.half_steps += self.speed.half_steps();
+        let frames = self.half_steps / 2;
+        self.half_steps %= 2;
+        frames.min(self.frames - frame)
+    }
+
+    pub fn handle(&mut self, keystate: &KeyState) -> Option<Control> {
+        if keystate.is_pressed(LEAVE_KEY) {
+            return Some(Control::Done);
+        }
+        let clicked = keystate.clicks().iter().find_map(|click| self.click(click));
+        if !keystate.pointer_held() {
+            self.scrubbing = false;
+        }
+        match (clicked, keystate.pointer()) {
+            (Some(control), _) => Some(control),
+            (None, Some(pointer)) if self.scrubbing => {
+                Some(Control::Seek(self.frame_at(pointer.x)))
+            }
+            _ => None,
+        }
+    }
+
+    // The buttons as they stand, for whichever way they are shown.
+    pub fn menu(&self) -> Vec<MenuItem<Action>> {
+        let play_label = if self.paused { "Play" } else { "Pause" };
+        let mut items = vec![MenuItem::new(Action::TogglePause, play_label)];
+        items.extend(SPEEDS.iter().map(|speed| {
+            MenuItem::new(Action::Speed(*speed), speed.label()).pressed(*speed == self.speed)
+        }));
+        items.push(MenuItem::new(Action::Done, "Done"));
+        items
+    }
+
+    pub fn activate(&mut self, action: Action) -> Option<Control> {
+        match action {
+            Action::TogglePause => self.paused = !self.paused,
+            Action::Speed(speed) => self.speed = speed,
+            Action::Done => return Some(Control::Done),
+        }
+        None
+    }
+
+    fn click(&mut self, click: &Point) -> Option<Control> {
+        let pressed = self
+            .menu()
+            .iter()
+            .map(|item| item.action)
+            .find(|action| action.button().contains(click));
+        if let Some(action) = pressed {
+            self.activate(action)
+        } else if TRACK_HIT.contains(click) {
+            self.scrubbing = true;
+            Some(Control::Seek(self.frame_at(click.x)))
+        } else {
+            None
+        }
+    }
+
+    fn frame_at(&self, x: i16) -> u32 {
+        let along = (x - TRACK.x()).clamp(0, TRACK.width);
+        (u64::from(self.frames) * along as u64 / TRACK.width as u64) as u32
+    }
+
+    // `buttons` is false when they are on the page instead.
+    pub fn draw(&self, renderer: &Renderer, frame: u32, buttons: bool) {
+        renderer.fill_rect(&BAR, &BAR_COLOR);
+        if buttons {
+            self.menu().iter().for_each(|item| {
+                draw_button(
+                    renderer,
+                    &item.action.button(),
+                    text(item.label),
+                    item.pressed,
+                )
+            });
+        }
+
+        renderer.fill_rect(&TRACK, &TRACK_COLOR);
+        let played = (TRACK.width as u64 * u64::from(frame.min(self.frames))
+            / u64::from(self.frames.max(1))) as i16;
+        renderer.fill_rect(
+            &Rect::new(TRACK.position, played, TRACK.height),
+            &PLAYED_COLOR,
+        );
+
+        let label = format!(
+            "{} {} / {}",
+            text("Replay"),
+            clock(frame.min(self.frames)),
+            clock(self.frames)
+        );
+        if let Err(err) =
+            renderer.draw_styled_text(&label, &LABEL, &TextStyle::new().with_color(TEXT_COLOR))
+        {
+            log!("Could not draw replay time {:#?}", err);
+        }
+    }
+}
+
+fn draw_button(renderer: &Renderer, button: &Rect, label: &str, selected: bool) {
+    let color = if selected {
+        SELECTED_COLOR
+    } else {
+        BUTTON_COLOR
+    };
+    renderer.fill_rect(button, &color);
+    // On the baseline, so this sits the text in the middle.
+    let center = Point {
+        x: button.x() + button.width / 2,
+        y: button.y() + 25,
+    };
+    let style = TextStyle::new()
+        .with_color(TEXT_COLOR)
+        .with_align(Align::Center);
+    if let Err(err) = renderer.draw_styled_text(label, &center, &style) {
+        log!("Could not draw replay button {:#?}", err);
+    }
+}
+
+// Minutes and seconds, as "1:05".
+fn clock(frames: u32) -> String {
+    let seconds = frames / FRAMES_PER_SECOND;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewer() -> ReplayViewer<u32> {
+        ReplayViewer::new(Replay::from_jumps(vec![[10, 3]]), 600, 0)
+    }
+
+    #[test]
+    fn speeds_play_half_one_or_two_frames_an_update() {
+        let mut viewer = viewer();
+        let played = |viewer: &mut ReplayViewer<u32>| -> u32 {
+            (0..4).map(|_update| viewer.frames_to_play(0)).sum()
+        };
+        assert_eq!(played(&mut viewer), 4);
+
+        viewer.speed = Speed::Half;
+        assert_eq!(played(&mut viewer), 2);
+
+        viewer.speed = Speed::Double;
+        assert_eq!(played(&mut viewer), 8);
+        assert_eq!(viewer.frames_to_play(599), 1);
+
+        viewer.paused = true;
+        assert_eq!(played(&mut viewer), 0);
+    }
+
+    #[test]
+    fn seeking_starts_from_the_nearest_keyframe() {
+        let mut viewer = viewer();
+        (1..=300).for_each(|frame| viewer.keyframe(frame, || frame * 10));
+
+        assert_eq!(viewer.keyframe_before(250), (240, &2400));
+        assert_eq!(viewer.keyframe_before(119), (0, &0));
+
+        viewer.keyframe(120, || 0);
+        assert_eq!(viewer.keyframe_before(130), (120, &1200));
+    }
+
+    #[test]
+    fn widgets_answer_clicks() {
+        let mut viewer = viewer();
+        let inside = |rect: &Rect| Point {
+            x: rect.x() + 2,
+            y: rect.y() + 2,
+        };
+
+        assert_eq!(viewer.click(&inside(&PLAY_BUTTON)), None);
+        assert!(viewer.paused);
+        viewer.click(&inside(&SPEED_BUTTONS[2]));
+        assert_eq!(viewer.speed, Speed::Double);
+        assert_eq!(
+            viewer.click(&Point {
+                x: TRACK.x() + TRACK.width / 2,
+                y: 590
+            }),
+            Some(Control::Seek(300))
+        );
+        assert!(viewer.scrubbing);
+        assert_eq!(viewer.click(&inside(&DONE_BUTTON)), Some(Control::Done));
+        assert_eq!(viewer.click(&Point { x: 300, y: 100 }), None);
+    }
+
+    #[test]
+    fn the_menu_follows_the_viewer() {
+        let mut viewer = viewer();
+
+        viewer.activate(Action::TogglePause);
+        viewer.activate(Action::Speed(Speed::Half));
+        let menu = viewer.menu();
+        assert_eq!(menu[0].label, "Play");
+        assert_eq!(
+            menu.iter()
+                .filter(|item| item.pressed)
+                .map(|item| item.action)
+                .collect::<Vec<_>>(),
+            vec![Action::Speed(Speed::Half)]
+        );
+        assert_eq!(viewer.activate(Action::Done), Some(Control::Done));
+    }
+
+    #[test]
+    fn jumps_come_from_the_replay() {
+        let viewer = viewer();
+
+        let jumping = |frame| viewer.input(frame).jumped;
+        assert!(!jumping(9));
+        assert!(jumping(10) && jumping(12));
+        assert!(!jumping(13));
+        assert_eq!(clock(3725), "1:02");
+    }
+}
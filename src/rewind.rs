@@ -0,0 +1,99 @@
+use std::collections::VecDeque;
+
+const FRAMES_PER_SECOND: u32 = 60;
+pub const REWIND_SECONDS: u32 = 3;
+// Snapshots are taken a few times a second rather than every frame to keep copying cheap.
+const SNAPSHOT_INTERVAL: u32 = 10;
+const CAPACITY: usize = (REWIND_SECONDS * FRAMES_PER_SECOND / SNAPSHOT_INTERVAL) as usize + 1;
+const REWINDS_PER_RUN: u8 = 2;
+
+// A ring buffer of recent snapshots plus the number of rewinds left this run.
+pub struct Rewind<T> {
+    snapshots: VecDeque<T>,
+    frame: u32,
+    uses_left: u8,
+}
+
+impl<T> Rewind<T> {
+    pub fn new() -> Self {
+        Rewind {
+            snapshots: VecDeque::with_capacity(CAPACITY),
+            frame: 0,
+            uses_left: REWINDS_PER_RUN,
+        }
+    }
+
+    pub fn snapshot_due(&mut self) -> bool {
+        let due = self.frame % SNAPSHOT_INTERVAL == 0;
+        self.frame = self.frame.wrapping_add(1);
+        due
+    }
+
+    pub fn record(&mut self, snapshot: T) {
+        if self.snapshots.len() == CAPACITY {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn available(&self) -> bool {
+        self.uses_left > 0 && !self.snapshots.is_empty()
+    }
+
+    pub fn uses_left(&self) -> u8 {
+        self.uses_left
+    }
+
+    // Hands back the oldest snapshot, about `REWIND_SECONDS` ago, and forgets
+    // the rest so the next rewind starts from fresh history.
+    pub fn rewind(&mut self) -> Option<T> {
+        if self.uses_left == 0 {
+            return None;
+        }
+        let snapshot = self.snapshots.pop_front()?;
+        self.snapshots.clear();
+        self.frame = 0;
+        self.uses_left -= 1;
+        Some(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(rewind: &mut Rewind<u32>, frames: u32) {
+        (0..frames).for_each(|frame| {
+            if rewind.snapshot_due() {
+                rewind.record(frame);
+            }
+        });
+    }
+
+    #[test]
+    fn keeps_only_the_last_few_seconds() {
+        let mut rewind = Rewind::new();
+        run(&mut rewind, 600);
+
+        assert_eq!(rewind.snapshots.len(), CAPACITY);
+        assert_eq!(
+            rewind.rewind(),
+            Some(600 - REWIND_SECONDS * FRAMES_PER_SECOND - 10)
+        );
+    }
+
+    #[test]
+    fn rewinds_are_limited() {
+        let mut rewind = Rewind::new();
+        assert!(!rewind.available());
+
+        (0..REWINDS_PER_RUN).for_each(|_| {
+            run(&mut rewind, 30);
+            assert!(rewind.rewind().is_some());
+        });
+        run(&mut rewind, 30);
+
+        assert!(!rewind.available());
+        assert_eq!(rewind.rewind(), None);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    config::Physics,
+    dash::DashInput,
+    engine::{physics::Overrides, Color, KeyState, Point, Rect, Renderer},
+    leaderboard::Input,
+    swings::GRAB_KEY,
+};
+
+// The dog runs twice as fast as the boy and jumps a fifth harder.
+const SPEED_FACTOR: i16 = 2;
+const JUMP_BOOST_DIVISOR: i16 = 5;
+
+const DOG_COLOR: Color = Color::rgb(150, 100, 60);
+const DOG_LENGTH: i16 = 90;
+const DOG_BODY_HEIGHT: i16 = 26;
+const DOG_LEG_HEIGHT: i16 = 16;
+const DOG_HEAD_RADIUS: i16 = 15;
+
+// Who turns keys into moves. The boy on foot has every move he knows; the dog
+// on a sprint only jumps.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Controller {
+    #[default]
+    OnFoot,
+    Riding(DogSprint),
+}
+
+impl Controller {
+    // Dashes are read either way, so a double tap doesn't start halfway
+    // through a sprint and finish after it.
+    pub fn read(&self, keystate: &KeyState, dash_input: &mut DashInput) -> Input {
+        let jumped = keystate.is_pressed("Space");
+        let dashed = dash_input.update(keystate);
+        match self {
+            Controller::OnFoot => Input {
+                jumped,
+                dashed,
+                grabbing: keystate.is_pressed(GRAB_KEY),
+            },
+            Controller::Riding(_) => Input {
+                jumped,
+                ..Input::default()
+            },
+        }
+    }
+
+    pub fn sprint(&self) -> Option<&DogSprint> {
+        match self {
+            Controller::OnFoot => None,
+            Controller::Riding(sprint) => Some(sprint),
+        }
+    }
+
+    // Hops on the dog for `distance` pixels.
+    pub fn mount(&mut self, distance: i32) {
+        *self = Controller::Riding(DogSprint::new(distance));
+    }
+
+    pub fn dismount(&mut self) {
+        *self = Controller::OnFoot;
+    }
+
+    // Counts off the ground covered at `walking_speed`, handing back to the
+    // boy once the sprint is run.
+    pub fn update(&mut self, walking_speed: i16) {
+        if let Controller::Riding(sprint) = self {
+            sprint.remaining += i32::from(walking_speed);
+            sprint.frame = sprint.frame.wrapping_add(1);
+            if sprint.remaining <= 0 {
+                self.dismount();
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DogSprint {
+    remaining: i32,
+    frame: u16,
+}
+
+impl DogSprint {
+    fn new(distance: i32) -> Self {
+        DogSprint {
+            remaining: distance,
+            frame: 0,
+        }
+    }
+
+    // Walking speeds are negative, as the world moves left.
+    pub fn walking_speed(&self, walking_speed: i16) -> i16 {
+        walking_speed * SPEED_FACTOR
+    }
+
+    // Jumps go higher on top of whatever the zone he is in does to them.
+    pub fn overrides(&self, environment: Overrides, physics: Physics) -> Overrides {
+        let jump_speed = environment.apply(physics).jump_speed;
+        Overrides {
+            jump_speed: Some(jump_speed + jump_speed / JUMP_BOOST_DIVISOR),
+            ..environment
+        }
+    }
+
+    // For when the riding sheet has no art for a frame: a dog drawn across
+    // his legs, with `feet` the box he stands in.
+    pub fn draw_dog(&self, renderer: &Renderer, feet: &Rect) {
+        let x = feet.x() + feet.width / 2 - DOG_LENGTH / 2;
+        let back = feet.bottom() - DOG_LEG_HEIGHT - DOG_BODY_HEIGHT;
+        let step = if (self.frame / 4).is_multiple_of(2) {
+            0
+        } else {
+            6
+        };
+        renderer.fill_rect(
+            &Rect::new_from_x_y(x, back, DOG_LENGTH, DOG_BODY_HEIGHT),
+            &DOG_COLOR,
+        );
+        [x + 6 + step, x + DOG_LENGTH - 14 - step]
+            .iter()
+            .for_each(|leg| {
+                renderer.fill_rect(
+                    &Rect::new_from_x_y(*leg, back + DOG_BODY_HEIGHT, 8, DOG_LEG_HEIGHT),
+                    &DOG_COLOR,
+                )
+            });
+        let head = Point {
+            x: x + DOG_LENGTH + DOG_HEAD_RADIUS / 2,
+            y: back - DOG_HEAD_RADIUS / 2,
+        };
+        if let Err(err) = renderer.fill_circle(&head, DOG_HEAD_RADIUS, &DOG_COLOR) {
+            log!("Could not draw dog {:#?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dash::DASH_KEY;
+
+    #[test]
+    fn the_dog_only_jumps() {
+        let mut controller = Controller::default();
+        let mut dash_input = DashInput::default();
+        let keys = KeyState::holding(&["Space", DASH_KEY, GRAB_KEY]);
+
+        controller.mount(500);
+        assert_eq!(
+            controller.read(&keys, &mut dash_input),
+            Input {
+                jumped: true,
+                ..Input::default()
+            }
+        );
+        controller.dismount();
+        assert_eq!(
+            controller.read(&keys, &mut DashInput::default()),
+            Input {
+                jumped: true,
+                dashed: true,
+                grabbing: true,
+            }
+        );
+    }
+
+    #[test]
+    fn sprints_end_after_their_distance() {
+        let mut controller = Controller::default();
+        controller.mount(100);
+
+        (0..16).for_each(|_| controller.update(-6));
+        assert!(controller.sprint().is_some());
+        controller.update(-6);
+        assert_eq!(controller, Controller::OnFoot);
+    }
+
+    #[test]
+    fn riding_is_faster_and_jumps_higher() {
+        let sprint = DogSprint::new(100);
+        let underwater = Overrides {
+            gravity_period: Some(3),
+            jump_speed: Some(-10),
+            ..Overrides::default()
+        };
+
+        assert_eq!(sprint.walking_speed(-3), -6);
+        assert_eq!(
+            sprint.overrides(Overrides::default(), Physics::default()),
+            Overrides {
+                jump_speed: Some(-30),
+                ..Overrides::default()
+            }
+        );
+        assert_eq!(
+            sprint.overrides(underwater, Physics::default()),
+            Overrides {
+                jump_speed: Some(-12),
+                ..underwater
+            }
+        );
+    }
+}
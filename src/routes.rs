@@ -0,0 +1,142 @@
+use std::rc::Rc;
+
+use rand::Rng;
+
+use crate::{
+    coins::{self, Coin},
+    engine::{MaskedImage, Point, SpriteSheet},
+    game::Obstacle,
+    segments::{self, PLATFORM_WIDTH},
+};
+
+// Heights of the tops of the high road's platforms. The low road is the ground.
+pub const LOW_LANE: i16 = segments::HIGH_PLATFORM;
+pub const HIGH_LANE: i16 = LOW_LANE - 160;
+
+const LANE_PLATFORMS: i16 = 3;
+const LANE_GAP: i16 = 120;
+const FIRST_PLATFORM: i16 = 300;
+const GROUND_STONE: i16 = 120;
+const COINS_PER_PLATFORM: i16 = 3;
+const COIN_SPACING: i16 = 100;
+const COIN_LIFT: i16 = 60;
+// Chance, out of ROUTE_ODDS, that a road keeps going past each section.
+const ROUTE_CONTINUES: u32 = 3;
+const ROUTE_ODDS: u32 = 4;
+
+// A stretch with two roads: the ground, with a stone to jump, and a line of
+// platforms with gaps to cross and coins along it. The high road starts at
+// `entry` and ends at `exit`, so sections can be chained without a step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Branch {
+    pub entry: i16,
+    pub exit: i16,
+}
+
+pub const BRANCHES: [Branch; 4] = [
+    Branch {
+        entry: LOW_LANE,
+        exit: LOW_LANE,
+    },
+    Branch {
+        entry: LOW_LANE,
+        exit: HIGH_LANE,
+    },
+    Branch {
+        entry: HIGH_LANE,
+        exit: HIGH_LANE,
+    },
+    Branch {
+        entry: HIGH_LANE,
+        exit: LOW_LANE,
+    },
+];
+
+pub struct Lanes {
+    pub obstacles: Vec<Box<dyn Obstacle>>,
+    pub coins: Vec<Coin>,
+}
+
+impl Branch {
+    // The high road's platforms step evenly from the entry to the exit height.
+    fn platform_top(&self, index: i16) -> i16 {
+        self.entry + (self.exit - self.entry) * index / (LANE_PLATFORMS - 1)
+    }
+
+    pub fn build(&self, stone: MaskedImage, sprite_sheet: Rc<SpriteSheet>, offset_x: i16) -> Lanes {
+        let mut obstacles = vec![segments::barrier(stone, offset_x + GROUND_STONE)];
+        let mut coins = vec![];
+        (0..LANE_PLATFORMS).for_each(|index| {
+            let position = Point {
+                x: offset_x + FIRST_PLATFORM + index * (PLATFORM_WIDTH + LANE_GAP),
+                y: self.platform_top(index),
+            };
+            obstacles.push(segments::floating_platform_at(
+                sprite_sheet.clone(),
+                position,
+            ));
+            coins.extend(coins::trail(
+                Point {
+                    x: position.x + COIN_SPACING / 2,
+                    y: position.y - COIN_LIFT,
+                },
+                COINS_PER_PLATFORM,
+                COIN_SPACING,
+            ));
+        });
+        Lanes { obstacles, coins }
+    }
+}
+
+// Picks what follows a road ending at `lane`, or nothing to go back to plain
+// segments. Only sections starting where the last one ended are considered.
+pub fn next_branch(lane: i16, rng: &mut impl Rng) -> Option<Branch> {
+    if !rng.gen_ratio(ROUTE_CONTINUES, ROUTE_ODDS) {
+        return None;
+    }
+    let following: Vec<Branch> = BRANCHES
+        .iter()
+        .filter(|branch| branch.entry == lane)
+        .copied()
+        .collect();
+    following.get(rng.gen_range(0..following.len())).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn platforms_step_from_entry_to_exit() {
+        let rising = Branch {
+            entry: LOW_LANE,
+            exit: HIGH_LANE,
+        };
+
+        assert_eq!(rising.platform_top(0), LOW_LANE);
+        assert_eq!(rising.platform_top(1), (LOW_LANE + HIGH_LANE) / 2);
+        assert_eq!(rising.platform_top(2), HIGH_LANE);
+    }
+
+    #[test]
+    fn consecutive_branches_meet_at_the_same_height() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut lane = LOW_LANE;
+
+        (0..200).for_each(|_| {
+            if let Some(branch) = next_branch(lane, &mut rng) {
+                assert_eq!(branch.entry, lane);
+                lane = branch.exit;
+            }
+        });
+    }
+
+    #[test]
+    fn every_lane_height_can_be_continued() {
+        assert!([LOW_LANE, HIGH_LANE]
+            .iter()
+            .all(|lane| BRANCHES.iter().any(|branch| branch.entry == *lane)));
+    }
+}
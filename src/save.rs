@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    browser,
+    leaderboard::LEADERBOARD_KEY,
+    levels::PROGRESS_KEY,
+    stats::STATS_KEY,
+    versioned::{self, Versioned},
+};
+
+const SAVE_VERSION: u32 = 1;
+const SAVE_KEYS: [&str; 3] = [PROGRESS_KEY, LEADERBOARD_KEY, STATS_KEY];
+const SAVED_AT_KEY: &str = "walk_the_dog.saved_at";
+
+// Everything the game keeps in local storage, as raw JSON strings per key.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SaveData {
+    version: u32,
+    #[serde(default)]
+    saved_at: u64,
+    items: BTreeMap<String, String>,
+}
+
+impl SaveData {
+    pub fn newer_than(&self, other: &SaveData) -> bool {
+        self.saved_at > other.saved_at
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.version != SAVE_VERSION {
+            return Err(anyhow!("Unsupported save version {}", self.version));
+        }
+        match self
+            .items
+            .keys()
+            .find(|key| !SAVE_KEYS.contains(&key.as_str()))
+        {
+            Some(key) => Err(anyhow!("Unknown save entry {}", key)),
+            None => Ok(()),
+        }
+    }
+}
+
+// Persisted entries go through here so the save remembers when it last changed.
+pub fn save_entry<T: Versioned + Serialize>(key: &str, value: &T) -> Result<()> {
+    versioned::save(key, value)?;
+    browser::save_json(SAVED_AT_KEY, &(js_sys::Date::now() as u64))
+}
+
+pub fn snapshot() -> Result<SaveData> {
+    let mut items = BTreeMap::new();
+    for key in SAVE_KEYS.iter() {
+        if let Some(item) = browser::load_item(key)? {
+            items.insert(key.to_string(), item);
+        }
+    }
+    Ok(SaveData {
+        version: SAVE_VERSION,
+        saved_at: browser::load_json(SAVED_AT_KEY)?.unwrap_or(0),
+        items,
+    })
+}
+
+// Keeps the restored save's timestamp so the next sync sees both sides as equal.
+pub fn restore(save: &SaveData) -> Result<()> {
+    save.validate()?;
+    save.items
+        .iter()
+        .try_for_each(|(key, item)| browser::save_item(key, item))?;
+    browser::save_json(SAVED_AT_KEY, &save.saved_at)
+}
+
+pub fn export() -> Result<String> {
+    browser::window()?
+        .btoa(&browser::to_json(&snapshot()?)?)
+        .map_err(|err| anyhow!("Could not encode save {:#?}", err))
+}
+
+// An imported save counts as a fresh change, so it wins the next cloud sync.
+pub fn import(code: &str) -> Result<()> {
+    let json = browser::window()?
+        .atob(code.trim())
+        .map_err(|err| anyhow!("Save code is not valid base64 {:#?}", err))?;
+    let mut save: SaveData = browser::from_json(&json)?;
+    save.saved_at = js_sys::Date::now() as u64;
+    restore(&save)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn save(json: &str) -> SaveData {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn accepts_known_entries() {
+        let save = save(
+            r#"{"version": 1, "items": {"walk_the_dog.stats": "{\"runs\":1}", "walk_the_dog.leaderboard": "{}"}}"#,
+        );
+        assert!(save.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_other_versions_and_unknown_entries() {
+        assert!(save(r#"{"version": 2, "items": {}}"#).validate().is_err());
+        assert!(save(r#"{"version": 1, "items": {"token": "secret"}}"#)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn older_saves_without_a_timestamp_are_oldest() {
+        let old = save(r#"{"version": 1, "items": {}}"#);
+        let new = save(r#"{"version": 1, "saved_at": 1700000000000, "items": {}}"#);
+
+        assert!(new.newer_than(&old));
+        assert!(!old.newer_than(&new));
+        assert!(!new.newer_than(&new));
+    }
+}
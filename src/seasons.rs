@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::Deserialize;
+use web_sys::HtmlImageElement;
+
+use crate::{
+    attachments::Attachment,
+    browser,
+    engine::{Color, Point, Rect, Renderer, SpriteSheet},
+    game::Cell,
+};
+
+// One treat for every this many jumps offered one.
+const TREAT_SPACING: u32 = 4;
+const FLAKES: usize = 60;
+const FLAKE_SIZE: i16 = 3;
+const FLAKE_COLOR: Color = Color::rgba(255, 255, 255, 0.8);
+
+// Themed content switched on by the calendar, listed in the asset manifest:
+// files to swap, things for the boy to wear and something extra to collect.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Season {
+    pub name: String,
+    // 1 for January.
+    months: Vec<u32>,
+    #[serde(default)]
+    pub files: HashMap<String, String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    pub collectible: Option<Collectible>,
+    #[serde(default)]
+    pub snow: bool,
+}
+
+// A sprite from the atlas scattered through the run and counted on the HUD
+// under `name`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Collectible {
+    pub name: String,
+    pub sprite: String,
+}
+
+impl Season {
+    // `?season=<name>` picks one whatever the date and `?season=none` turns
+    // them all off.
+    pub fn detect(seasons: Vec<Season>) -> Option<Season> {
+        match browser::query_param("season") {
+            Ok(Some(name)) => return seasons.into_iter().find(|season| season.name == name),
+            Ok(None) => {}
+            Err(err) => {
+                log!("Could not read season override {:#?}", err);
+            }
+        }
+        Season::for_month(seasons, browser::month())
+    }
+
+    fn for_month(seasons: Vec<Season>, month: u32) -> Option<Season> {
+        seasons
+            .into_iter()
+            .find(|season| season.months.contains(&month))
+    }
+}
+
+// The season's collectible and the ones still out in the world.
+#[derive(Clone)]
+pub struct Treats {
+    name: String,
+    cell: Cell,
+    image: HtmlImageElement,
+    items: Vec<Point>,
+    offers: u32,
+    collected: u32,
+}
+
+impl Treats {
+    pub fn new(collectible: &Collectible, atlas: &SpriteSheet) -> Result<Self> {
+        let cell = atlas
+            .cell(&collectible.sprite)
+            .cloned()
+            .ok_or_else(|| anyhow!("No sprite {} in the atlas", collectible.sprite))?;
+        Ok(Treats {
+            name: collectible.name.clone(),
+            cell,
+            image: atlas.image().clone(),
+            items: vec![],
+            offers: 0,
+            collected: 0,
+        })
+    }
+
+    // Every few jumps gets one, centered on `at`.
+    pub fn offer(&mut self, at: Point) {
+        self.offers += 1;
+        if self.offers.is_multiple_of(TREAT_SPACING) {
+            self.items.push(Point {
+                x: at.x - self.cell.frame.w / 2,
+                y: at.y - self.cell.frame.h / 2,
+            });
+        }
+    }
+
+    pub fn update(&mut self, walking_speed: i16, boy: &Rect) {
+        let cell = &self.cell;
+        self.items
+            .iter_mut()
+            .for_each(|item| item.x += walking_speed);
+        let touched = self
+            .items
+            .iter()
+            .filter(|item| cell.destination(item).intersects(boy))
+            .count();
+        self.items.retain(|item| {
+            let hitbox = cell.destination(item);
+            hitbox.right() > 0 && !hitbox.intersects(boy)
+        });
+        self.collected += touched as u32;
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.offers = 0;
+        self.collected = 0;
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.items.iter().for_each(|item| {
+            if let Err(err) = renderer.draw_cell(&self.image, &self.cell, item) {
+                log!("Could not draw {} {:#?}", self.name, err);
+            }
+        });
+    }
+
+    // For the HUD, once there is something to count.
+    pub fn label(&self) -> Option<String> {
+        (self.collected > 0).then(|| format!("{}: {}", self.name, self.collected))
+    }
+}
+
+// Flakes drifting down over everything, blown back as the world scrolls.
+#[derive(Clone, Debug)]
+pub struct Snowfall {
+    flakes: Vec<Point>,
+    width: i16,
+    height: i16,
+}
+
+impl Snowfall {
+    pub fn new(seed: u64, width: i16, height: i16) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let flakes = (0..FLAKES)
+            .map(|_flake| Point {
+                x: rng.gen_range(0..width),
+                y: rng.gen_range(0..height),
+            })
+            .collect();
+        Snowfall {
+            flakes,
+            width,
+            height,
+        }
+    }
+
+    // Alternate flakes fall at different speeds, so they do not move as one.
+    pub fn update(&mut self, walking_speed: i16) {
+        let (width, height) = (self.width, self.height);
+        self.flakes.iter_mut().zip(1..).for_each(|(flake, index)| {
+            flake.x = (flake.x + walking_speed / 2).rem_euclid(width);
+            flake.y += 1 + index % 2;
+            if flake.y > height {
+                flake.y -= height + FLAKE_SIZE;
+            }
+        });
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        self.flakes.iter().for_each(|flake| {
+            renderer.fill_rect(&Rect::new(*flake, FLAKE_SIZE, FLAKE_SIZE), &FLAKE_COLOR)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seasons() -> Vec<Season> {
+        serde_json::from_str(
+            r#"[
+                { "name": "halloween", "months": [10],
+                  "collectible": { "name": "Pumpkins", "sprite": "seasons/pumpkin.png" } },
+                { "name": "winter", "months": [12, 1], "snow": true,
+                  "files": { "BG.png": "seasons/winter/BG.png" } }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn seasons_follow_the_calendar() {
+        let season = |month| Season::for_month(seasons(), month).map(|season| season.name);
+
+        assert_eq!(season(10).as_deref(), Some("halloween"));
+        assert_eq!(season(1).as_deref(), Some("winter"));
+        assert_eq!(season(6), None);
+    }
+
+    #[test]
+    fn snow_blows_back_and_wraps_around() {
+        let mut snowfall = Snowfall::new(7, 600, 600);
+        snowfall.flakes = vec![Point { x: 1, y: 599 }, Point { x: 300, y: 10 }];
+        snowfall.update(-4);
+
+        assert_eq!(
+            snowfall.flakes,
+            vec![Point { x: 599, y: -2 }, Point { x: 298, y: 11 }]
+        );
+    }
+}
@@ -1,52 +1,50 @@
 use std::{rc::Rc, vec};
 
-use web_sys::HtmlImageElement;
-
-use crate::engine::{Image, Point, Rect, SpriteSheet};
+use crate::diagnostics::Report;
+use crate::engine::{Image, MaskedImage, Point, Rect, SpriteSheet};
 use crate::game::{Barrier, Obstacle, Platform};
+use crate::lifecycle::Entrance;
+use crate::swings::SwingPlacement;
 
 const LOW_PLATFORM: i16 = 420;
-const HIGH_PLATFORM: i16 = 375;
+pub const HIGH_PLATFORM: i16 = 375;
 const FIRST_PLATFORM: i16 = 370;
 
 const STONE_ON_GROUND: i16 = 546;
 
-const FLOATING_PLATFORM_SPRITES: [&str; 3] = ["13.png", "14.png", "15.png"];
-const PLATFORM_WIDTH: i16 = 384;
+const FLOATING_PLATFORM_SPRITES: [&str; 3] = ["tiles/13.png", "tiles/14.png", "tiles/15.png"];
+pub const PLATFORM_WIDTH: i16 = 384;
 const PLATFORM_HEIGHT: i16 = 93;
 const PLATFORM_EDGE_WIDTH: i16 = 60;
 const PLATFORM_EDGE_HEIGHT: i16 = 54;
-const FLOATING_PLATFORM_BOUNDING_BOXES: [Rect; 3] = [
-    Rect::new_from_x_y(0, 0, PLATFORM_EDGE_WIDTH, PLATFORM_EDGE_HEIGHT),
-    Rect::new_from_x_y(
-        PLATFORM_EDGE_WIDTH,
-        0,
-        PLATFORM_WIDTH - (PLATFORM_EDGE_WIDTH * 2),
-        PLATFORM_HEIGHT,
-    ),
-    Rect::new_from_x_y(
-        PLATFORM_WIDTH - PLATFORM_EDGE_WIDTH,
-        0,
-        PLATFORM_EDGE_WIDTH,
-        PLATFORM_EDGE_HEIGHT,
-    ),
-];
+
+const fn floating_platform_bounding_boxes(width: i16) -> [Rect; 3] {
+    [
+        Rect::new_from_x_y(0, 0, PLATFORM_EDGE_WIDTH, PLATFORM_EDGE_HEIGHT),
+        Rect::new_from_x_y(
+            PLATFORM_EDGE_WIDTH,
+            0,
+            width - (PLATFORM_EDGE_WIDTH * 2),
+            PLATFORM_HEIGHT,
+        ),
+        Rect::new_from_x_y(
+            width - PLATFORM_EDGE_WIDTH,
+            0,
+            PLATFORM_EDGE_WIDTH,
+            PLATFORM_EDGE_HEIGHT,
+        ),
+    ]
+}
 
 pub fn stone_and_platform(
-    stone: HtmlImageElement,
+    stone: MaskedImage,
     sprite_sheet: Rc<SpriteSheet>,
     offset_x: i16,
 ) -> Vec<Box<dyn Obstacle>> {
     const INITIAL_STONE_OFFSET: i16 = 250;
 
     vec![
-        Box::new(Barrier::new(Image::new(
-            stone,
-            Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
-                y: STONE_ON_GROUND,
-            },
-        ))),
+        barrier(stone, offset_x + INITIAL_STONE_OFFSET),
         Box::new(create_floating_platform(
             sprite_sheet,
             Point {
@@ -58,7 +56,7 @@ pub fn stone_and_platform(
 }
 
 pub fn platform_and_stone(
-    stone: HtmlImageElement,
+    stone: MaskedImage,
     sprite_sheet: Rc<SpriteSheet>,
     offset_x: i16,
 ) -> Vec<Box<dyn Obstacle>> {
@@ -72,21 +70,164 @@ pub fn platform_and_stone(
                 y: LOW_PLATFORM,
             },
         )),
-        Box::new(Barrier::new(Image::new(
+        barrier(stone, offset_x + INITIAL_STONE_OFFSET),
+    ]
+}
+
+// A staircase of platforms climbing well above the view, with a stone on the
+// ground for anyone who stays below.
+pub fn climb(
+    stone: MaskedImage,
+    sprite_sheet: Rc<SpriteSheet>,
+    offset_x: i16,
+) -> Vec<Box<dyn Obstacle>> {
+    const STEPS: i16 = 4;
+    const STEP_GAP: i16 = 60;
+    const STEP_RISE: i16 = 160;
+    const GROUND_STONE_OFFSET: i16 = 950;
+
+    (0..STEPS)
+        .map(|step| -> Box<dyn Obstacle> {
+            Box::new(create_floating_platform(
+                sprite_sheet.clone(),
+                Point {
+                    x: offset_x + FIRST_PLATFORM + step * (PLATFORM_WIDTH + STEP_GAP),
+                    y: LOW_PLATFORM - step * STEP_RISE,
+                },
+            ))
+        })
+        .chain(std::iter::once(barrier(
+            stone,
+            offset_x + GROUND_STONE_OFFSET,
+        )))
+        .collect()
+}
+
+// A wooden barrier to dash through, with a stone not far behind for anyone
+// still slowed down from smashing it.
+pub fn woodpile(stone: MaskedImage, offset_x: i16) -> Vec<Box<dyn Obstacle>> {
+    const WOOD_OFFSET: i16 = 250;
+    const STONE_OFFSET: i16 = 850;
+
+    vec![
+        wooden_barrier(stone.clone(), offset_x + WOOD_OFFSET),
+        barrier(stone, offset_x + STONE_OFFSET),
+    ]
+}
+
+// A platform to leap from over a row of stones too long to clear with a
+// jump, so the way across is by the swing points above them.
+pub fn chasm(
+    stone: MaskedImage,
+    sprite_sheet: Rc<SpriteSheet>,
+    offset_x: i16,
+) -> Vec<Box<dyn Obstacle>> {
+    const STONE_OFFSETS: [i16; 5] = [800, 900, 1000, 1100, 1200];
+
+    std::iter::once(floating_platform_at(
+        sprite_sheet,
+        Point {
+            x: offset_x + FIRST_PLATFORM,
+            y: LOW_PLATFORM,
+        },
+    ))
+    .chain(
+        STONE_OFFSETS
+            .iter()
+            .map(|stone_offset| barrier(stone.clone(), offset_x + stone_offset)),
+    )
+    .collect()
+}
+
+pub fn chasm_swings() -> Vec<SwingPlacement> {
+    const SWING_HEIGHT: i16 = 150;
+    const SWING_REACH: i16 = 190;
+
+    [900, 1150]
+        .iter()
+        .map(|x| SwingPlacement {
+            x: *x,
+            y: SWING_HEIGHT,
+            reach: SWING_REACH,
+        })
+        .collect()
+}
+
+pub fn stones(stone: MaskedImage, offset_x: i16) -> Vec<Box<dyn Obstacle>> {
+    const STONE_OFFSETS: [i16; 2] = [250, 650];
+
+    STONE_OFFSETS
+        .iter()
+        .map(|stone_offset| barrier(stone.clone(), offset_x + stone_offset))
+        .collect()
+}
+
+// Single obstacles, for placing by hand.
+pub fn barrier(stone: MaskedImage, x: i16) -> Box<dyn Obstacle> {
+    Box::new(
+        Barrier::new(Image::masked(
             stone,
             Point {
-                x: offset_x + INITIAL_STONE_OFFSET,
+                x,
                 y: STONE_ON_GROUND,
             },
-        ))),
-    ]
+        ))
+        .with_entrance(Entrance::Drop),
+    )
+}
+
+pub fn wooden_barrier(stone: MaskedImage, x: i16) -> Box<dyn Obstacle> {
+    Box::new(
+        Barrier::wooden(Image::masked(
+            stone,
+            Point {
+                x,
+                y: STONE_ON_GROUND,
+            },
+        ))
+        .with_entrance(Entrance::Drop),
+    )
+}
+
+#[allow(dead_code)]
+pub fn floating_platform(sprite_sheet: Rc<SpriteSheet>, x: i16) -> Box<dyn Obstacle> {
+    floating_platform_at(sprite_sheet, Point { x, y: LOW_PLATFORM })
+}
+
+pub fn floating_platform_at(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Box<dyn Obstacle> {
+    Box::new(create_floating_platform(sprite_sheet, position))
+}
+
+// The floating platform's sprites must be in the atlas, and its bounding
+// boxes must lie on them.
+pub fn check_platforms(atlas: &SpriteSheet, report: &mut Report) {
+    report.check_cells(
+        "assets.json atlas",
+        &atlas.sheet().frames,
+        &FLOATING_PLATFORM_SPRITES,
+        "The floating platform",
+    );
+    let height = FLOATING_PLATFORM_SPRITES
+        .iter()
+        .filter_map(|name| atlas.cell(name))
+        .map(|cell| cell.frame.h)
+        .max()
+        .unwrap_or(0);
+    report.check_bounding_boxes(
+        "segments",
+        "The floating platform",
+        &Rect::new_from_x_y(0, 0, PLATFORM_WIDTH, height),
+        &floating_platform_bounding_boxes(PLATFORM_WIDTH),
+    );
 }
 
 fn create_floating_platform(sprite_sheet: Rc<SpriteSheet>, position: Point) -> Platform {
-    Platform::new(
+    Platform::with_width(
         sprite_sheet,
         position,
-        &FLOATING_PLATFORM_SPRITES,
-        &FLOATING_PLATFORM_BOUNDING_BOXES,
+        FLOATING_PLATFORM_SPRITES,
+        PLATFORM_WIDTH,
+        &floating_platform_bounding_boxes(PLATFORM_WIDTH),
     )
+    .with_entrance(Entrance::Rise)
 }
@@ -0,0 +1,355 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{anyhow, Result};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use wasm_bindgen::JsValue;
+
+use crate::{
+    browser::{
+        self,
+        idb::{self, Database},
+    },
+    game::{Cell, FrameTag, Sheet},
+};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SheetFormat {
+    TexturePackerHash,
+    TexturePackerArray,
+    Aseprite,
+    // Free Texture Packer's Phaser 3 export, which lists frames per texture.
+    Phaser3,
+}
+
+#[derive(Deserialize)]
+struct RawSheet {
+    #[serde(default)]
+    frames: Option<RawFrames>,
+    #[serde(default)]
+    textures: Vec<RawTexture>,
+    #[serde(default)]
+    meta: RawMeta,
+}
+
+#[derive(Deserialize)]
+struct RawTexture {
+    frames: Vec<NamedCell>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawFrames {
+    Array(Vec<NamedCell>),
+    Hash(OrderedCells),
+}
+
+#[derive(Deserialize)]
+struct NamedCell {
+    filename: String,
+    #[serde(flatten)]
+    cell: Cell,
+}
+
+// Keeps frames in file order, which Aseprite frame tags index into.
+struct OrderedCells(Vec<(String, Cell)>);
+
+impl<'de> Deserialize<'de> for OrderedCells {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedCellsVisitor;
+
+        impl<'de> Visitor<'de> for OrderedCellsVisitor {
+            type Value = OrderedCells;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of frame names to frames")
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, M::Error> {
+                let mut cells = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    cells.push(entry);
+                }
+                Ok(OrderedCells(cells))
+            }
+        }
+
+        deserializer.deserialize_map(OrderedCellsVisitor)
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct RawMeta {
+    #[serde(default)]
+    app: String,
+    #[serde(default)]
+    frame_tags: Vec<RawFrameTag>,
+}
+
+#[derive(Deserialize)]
+struct RawFrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+impl RawSheet {
+    fn format(&self) -> SheetFormat {
+        if self.meta.app.contains("aseprite") || !self.meta.frame_tags.is_empty() {
+            SheetFormat::Aseprite
+        } else {
+            match self.frames {
+                Some(RawFrames::Array(_)) => SheetFormat::TexturePackerArray,
+                Some(RawFrames::Hash(_)) => SheetFormat::TexturePackerHash,
+                None => SheetFormat::Phaser3,
+            }
+        }
+    }
+}
+
+impl RawFrames {
+    fn into_ordered_cells(self) -> Vec<(String, Cell)> {
+        match self {
+            RawFrames::Array(cells) => named_cells(cells),
+            RawFrames::Hash(OrderedCells(cells)) => cells,
+        }
+    }
+}
+
+fn named_cells(cells: Vec<NamedCell>) -> Vec<(String, Cell)> {
+    cells
+        .into_iter()
+        .map(|named| (named.filename, named.cell))
+        .collect()
+}
+
+// Every sheet is drawn from one image, so only a single texture can be used.
+fn single_texture_cells(textures: Vec<RawTexture>) -> Result<Vec<(String, Cell)>> {
+    let mut textures = textures.into_iter();
+    match (textures.next(), textures.next()) {
+        (Some(texture), None) => Ok(named_cells(texture.frames)),
+        (None, _) => Err(anyhow!("Sprite sheet has no frames")),
+        (Some(_), Some(_)) => Err(anyhow!("Sprite sheet is split across several textures")),
+    }
+}
+
+impl RawFrameTag {
+    fn frame_names(&self, names: &[String]) -> Result<Vec<String>> {
+        let forward = names
+            .get(self.from..=self.to)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Frame tag {} spans frames {}..={} but the sheet only has {}",
+                    self.name,
+                    self.from,
+                    self.to,
+                    names.len()
+                )
+            })?
+            .to_vec();
+
+        Ok(match self.direction.as_str() {
+            "reverse" => forward.into_iter().rev().collect(),
+            "pingpong" => {
+                let back = forward
+                    .iter()
+                    .rev()
+                    .skip(1)
+                    .take(forward.len().saturating_sub(2));
+                forward.iter().chain(back).cloned().collect()
+            }
+            _ => forward,
+        })
+    }
+}
+
+fn build_sheet(raw: RawSheet) -> Result<Sheet> {
+    let format = raw.format();
+    let RawSheet {
+        frames,
+        textures,
+        meta,
+    } = raw;
+    let cells = match frames {
+        Some(frames) => frames.into_ordered_cells(),
+        None => single_texture_cells(textures)?,
+    };
+    let names: Vec<String> = cells.iter().map(|(name, _)| name.clone()).collect();
+
+    let tags = match format {
+        SheetFormat::Aseprite => meta
+            .frame_tags
+            .iter()
+            .map(|tag| {
+                Ok(FrameTag {
+                    name: tag.name.clone(),
+                    frames: tag.frame_names(&names)?,
+                })
+            })
+            .collect::<Result<Vec<FrameTag>>>()?,
+        SheetFormat::TexturePackerHash | SheetFormat::TexturePackerArray | SheetFormat::Phaser3 => {
+            vec![]
+        }
+    };
+
+    Ok(Sheet {
+        frames: cells.into_iter().collect::<HashMap<String, Cell>>(),
+        tags,
+    })
+}
+
+pub fn load_sheet(json: &JsValue) -> Result<Sheet> {
+    build_sheet(
+        json.into_serde::<RawSheet>()
+            .map_err(|err| anyhow!("Unrecognized sprite sheet format {:#?}", err))?,
+    )
+}
+
+// Decoded sheets are cached per release, so a new build never reads a stale layout.
+pub async fn load_cached_sheet(path: &str) -> Result<Sheet> {
+    let key = format!("{}@{}", path, env!("CARGO_PKG_VERSION"));
+    let db = Database::open()
+        .await
+        .map_err(|err| {
+            log!("Loading {} without a cache {:#?}", path, err);
+        })
+        .ok();
+
+    if let Some(db) = &db {
+        match db.get_json(idb::SHEETS, &key).await {
+            Ok(Some(sheet)) => return Ok(sheet),
+            Ok(None) => {}
+            Err(err) => {
+                log!("Could not read cached sheet {:#?}", err);
+            }
+        }
+    }
+
+    let sheet = load_sheet(&browser::fetch_json(path).await?)?;
+    if let Some(db) = &db {
+        if let Err(err) = db.put_json(idb::SHEETS, &key, &sheet).await {
+            log!("Could not cache sheet {:#?}", err);
+        }
+    }
+    Ok(sheet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: &str = r#""frame": {"x":0,"y":0,"w":10,"h":20},
+        "spriteSourceSize": {"x":1,"y":2,"w":10,"h":20}"#;
+
+    fn parse(json: &str) -> RawSheet {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn detects_texture_packer_hash() {
+        let raw = parse(&format!(
+            r#"{{"frames": {{"a.png": {{{}}}}}, "meta": {{"app": "https://www.codeandweb.com/texturepacker"}}}}"#,
+            FRAME
+        ));
+        assert_eq!(raw.format(), SheetFormat::TexturePackerHash);
+        let sheet = build_sheet(raw).unwrap();
+        assert!(sheet.frames.contains_key("a.png"));
+    }
+
+    #[test]
+    fn detects_texture_packer_array() {
+        let raw = parse(&format!(
+            r#"{{"frames": [{{"filename": "a.png", {}}}, {{"filename": "b.png", {}}}]}}"#,
+            FRAME, FRAME
+        ));
+        assert_eq!(raw.format(), SheetFormat::TexturePackerArray);
+        let sheet = build_sheet(raw).unwrap();
+        assert_eq!(sheet.frames.len(), 2);
+    }
+
+    #[test]
+    fn reads_aseprite_tags_and_durations() {
+        let raw = parse(&format!(
+            r#"{{
+                "frames": {{
+                    "rhb 0.aseprite": {{{}, "duration": 100}},
+                    "rhb 1.aseprite": {{{}, "duration": 50}},
+                    "rhb 2.aseprite": {{{}, "duration": 50}}
+                }},
+                "meta": {{
+                    "app": "https://www.aseprite.org/",
+                    "frameTags": [
+                        {{"name": "Idle", "from": 0, "to": 0, "direction": "forward"}},
+                        {{"name": "Run", "from": 0, "to": 2, "direction": "pingpong"}}
+                    ]
+                }}
+            }}"#,
+            FRAME, FRAME, FRAME
+        ));
+        assert_eq!(raw.format(), SheetFormat::Aseprite);
+        let sheet = build_sheet(raw).unwrap();
+
+        assert_eq!(sheet.frames["rhb 0.aseprite"].duration, Some(100));
+        assert_eq!(sheet.tags[0].frames, vec!["rhb 0.aseprite"]);
+        assert_eq!(
+            sheet.tags[1].frames,
+            vec![
+                "rhb 0.aseprite",
+                "rhb 1.aseprite",
+                "rhb 2.aseprite",
+                "rhb 1.aseprite"
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_free_texture_packer_phaser_export() {
+        let raw = parse(&format!(
+            r#"{{
+                "textures": [{{
+                    "image": "tiles.png",
+                    "size": {{"w": 64, "h": 64}},
+                    "frames": [{{"filename": "a.png", {}}}, {{"filename": "b.png", {}}}]
+                }}],
+                "meta": {{"app": "http://free-tex-packer.com"}}
+            }}"#,
+            FRAME, FRAME
+        ));
+        assert_eq!(raw.format(), SheetFormat::Phaser3);
+        let sheet = build_sheet(raw).unwrap();
+        assert_eq!(sheet.frames.len(), 2);
+        assert_eq!(sheet.frames["b.png"].sprite_source_size.y, 2);
+    }
+
+    #[test]
+    fn rejects_sheets_split_across_textures() {
+        let texture = format!(r#"{{"frames": [{{"filename": "a.png", {}}}]}}"#, FRAME);
+        let raw = parse(&format!(r#"{{"textures": [{}, {}]}}"#, texture, texture));
+        assert!(build_sheet(raw).is_err());
+        assert!(build_sheet(parse(r#"{"textures": []}"#)).is_err());
+    }
+
+    #[test]
+    fn rejects_tags_outside_the_sheet() {
+        let raw = parse(&format!(
+            r#"{{"frames": {{"a": {{{}}}}}, "meta": {{"frameTags": [{{"name": "Run", "from": 0, "to": 4}}]}}}}"#,
+            FRAME
+        ));
+        assert!(build_sheet(raw).is_err());
+    }
+
+    #[test]
+    fn cached_sheets_round_trip() {
+        let raw = parse(&format!(r#"{{"frames": {{"a.png": {{{}}}}}}}"#, FRAME));
+        let sheet = build_sheet(raw).unwrap();
+        let cached: Sheet = serde_json::from_str(&serde_json::to_string(&sheet).unwrap()).unwrap();
+
+        assert_eq!(cached.frames["a.png"].sprite_source_size.x, 1);
+        assert_eq!(cached.frames["a.png"].frame.h, 20);
+    }
+}
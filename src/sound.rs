@@ -2,7 +2,14 @@ use anyhow::{anyhow, Result};
 use js_sys::ArrayBuffer;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{AudioBuffer, AudioBufferSourceNode, AudioContext, AudioDestinationNode, AudioNode};
+use web_sys::{
+    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioNode, GainNode, HtmlAudioElement,
+};
+
+// How far the music drops under an important cue, and how many seconds it
+// takes to come back once the cue is over.
+const DUCKED_GAIN: f32 = 0.25;
+const DUCK_RELEASE_SECONDS: f64 = 0.5;
 
 pub fn create_audio_context() -> Result<AudioContext> {
     AudioContext::new().map_err(|err| anyhow!("Could not create audio context: {:#?}", err))
@@ -15,34 +22,153 @@ pub fn create_buffer_source(ctx: &AudioContext) -> Result<AudioBufferSourceNode>
 
 pub fn connect_with_audio_node(
     buffer_source: &AudioBufferSourceNode,
-    destination: &AudioDestinationNode,
+    output: &AudioNode,
 ) -> Result<AudioNode> {
     buffer_source
-        .connect_with_audio_node(destination)
-        .map_err(|err| anyhow!("Error connecting audio source to destination {:#?}", err))
+        .connect_with_audio_node(output)
+        .map_err(|err| anyhow!("Error connecting audio source to its bus {:#?}", err))
 }
 
-fn create_track_source(ctx: &AudioContext, buffer: &AudioBuffer) -> Result<AudioBufferSourceNode> {
+fn create_track_source(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    output: &AudioNode,
+) -> Result<AudioBufferSourceNode> {
     let track_source = create_buffer_source(ctx)?;
     track_source.set_buffer(Some(&buffer));
-    connect_with_audio_node(&track_source, &ctx.destination())?;
+    connect_with_audio_node(&track_source, output)?;
     Ok(track_source)
 }
 
+// A volume control feeding `output`, which may be another one of these.
+pub fn create_gain(ctx: &AudioContext, output: &AudioNode) -> Result<GainNode> {
+    let gain = ctx
+        .create_gain()
+        .map_err(|err| anyhow!("Could not create a gain node {:#?}", err))?;
+    gain.connect_with_audio_node(output)
+        .map_err(|err| anyhow!("Error connecting gain node {:#?}", err))?;
+    Ok(gain)
+}
+
+pub fn set_gain(gain: &GainNode, value: f32) {
+    gain.gain().set_value(value);
+}
+
+// Like `set_gain`, but first drops anything scheduled, such as a duck.
+pub fn hold_gain(ctx: &AudioContext, gain: &GainNode, value: f32) -> Result<()> {
+    let now = ctx.current_time();
+    gain.gain()
+        .cancel_scheduled_values(now)
+        .and_then(|param| param.set_value_at_time(value, now))
+        .map(|_param| ())
+        .map_err(|err| anyhow!("Could not hold the gain {:#?}", err))
+}
+
+// Drops `gain` at once, holds it down for `hold` seconds and then ramps it
+// back up. Ducking again while it is still down starts the hold over.
+pub fn duck(ctx: &AudioContext, gain: &GainNode, hold: f64) -> Result<()> {
+    let now = ctx.current_time();
+    let param = gain.gain();
+    param
+        .cancel_scheduled_values(now)
+        .and_then(|param| param.set_value_at_time(DUCKED_GAIN, now))
+        .and_then(|param| param.set_value_at_time(DUCKED_GAIN, now + hold))
+        .and_then(|param| {
+            param.linear_ramp_to_value_at_time(1.0, now + hold + DUCK_RELEASE_SECONDS)
+        })
+        .map(|_param| ())
+        .map_err(|err| anyhow!("Could not duck the music {:#?}", err))
+}
+
+// Music plays through a media element so it can start before the whole file
+// has downloaded, while still going through the audio graph.
+pub fn stream_looping(
+    ctx: &AudioContext,
+    url: &str,
+    output: &AudioNode,
+) -> Result<HtmlAudioElement> {
+    let element = HtmlAudioElement::new_with_src(url)
+        .map_err(|err| anyhow!("Could not create audio element for {} {:#?}", url, err))?;
+    element.set_loop(true);
+    element.set_preload("auto");
+    ctx.create_media_element_source(&element)
+        .map_err(|err| anyhow!("Could not create media source {:#?}", err))?
+        .connect_with_audio_node(output)
+        .map_err(|err| anyhow!("Error connecting media source to the music gain {:#?}", err))?;
+    let playing = element
+        .play()
+        .map_err(|err| anyhow!("Could not start streaming {} {:#?}", url, err))?;
+    crate::browser::spawn_local(async move {
+        if let Err(err) = JsFuture::from(playing).await {
+            log!("Streaming music was blocked {:#?}", err);
+        }
+    });
+    Ok(element)
+}
+
+// Lets `canPlayType` rule out formats before they are downloaded.
+pub fn mime_type(path: &str) -> Option<&'static str> {
+    match path.rsplit_once('.')?.1.to_ascii_lowercase().as_str() {
+        "ogg" | "oga" => Some("audio/ogg"),
+        "mp3" => Some("audio/mpeg"),
+        "m4a" | "mp4" => Some("audio/mp4"),
+        "aac" => Some("audio/aac"),
+        "wav" => Some("audio/wav"),
+        _ => None,
+    }
+}
+
 pub enum LOOPING {
     NO,
     YES,
 }
 
-pub fn play_sound(ctx: &AudioContext, buffer: &AudioBuffer, looping: LOOPING) -> Result<()> {
-    let track_source = create_track_source(ctx, buffer)?;
+pub fn play_sound(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    looping: LOOPING,
+    output: &AudioNode,
+) -> Result<AudioBufferSourceNode> {
+    let track_source = create_track_source(ctx, buffer, output)?;
     if matches!(looping, LOOPING::YES) {
         track_source.set_loop(true);
     }
-    connect_with_audio_node(&track_source, &ctx.destination())?;
     track_source
         .start()
-        .map_err(|err| anyhow!("Could not start the sound! {:#?}", err))
+        .map_err(|err| anyhow!("Could not start the sound! {:#?}", err))?;
+    Ok(track_source)
+}
+
+// Plays `duration` seconds of `buffer` starting `offset` seconds in.
+pub fn play_clip(
+    ctx: &AudioContext,
+    buffer: &AudioBuffer,
+    offset: f64,
+    duration: f64,
+    output: &AudioNode,
+) -> Result<AudioBufferSourceNode> {
+    let track_source = create_track_source(ctx, buffer, output)?;
+    track_source
+        .start_with_when_and_grain_offset_and_grain_duration(0.0, offset, duration)
+        .map_err(|err| anyhow!("Could not start the clip! {:#?}", err))?;
+    Ok(track_source)
+}
+
+pub fn stop(source: &AudioBufferSourceNode) -> Result<()> {
+    source
+        .stop()
+        .map_err(|err| anyhow!("Could not stop the sound {:#?}", err))
+}
+
+// A mono buffer holding `samples`, at the context's own sample rate.
+pub fn create_buffer(ctx: &AudioContext, samples: &[f32]) -> Result<AudioBuffer> {
+    let buffer = ctx
+        .create_buffer(1, samples.len() as u32, ctx.sample_rate())
+        .map_err(|err| anyhow!("Could not create audio buffer {:#?}", err))?;
+    buffer
+        .copy_to_channel(samples, 0)
+        .map_err(|err| anyhow!("Could not fill audio buffer {:#?}", err))?;
+    Ok(buffer)
 }
 
 pub async fn decode_audio_data(
@@ -58,3 +184,16 @@ pub async fn decode_audio_data(
     .dyn_into()
     .map_err(|err| anyhow!("Could not cast into AudioBuffer {:#?}", err))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mime_types_follow_the_extension() {
+        assert_eq!(mime_type("SFX_Jump_23.mp3"), Some("audio/mpeg"));
+        assert_eq!(mime_type("low/song.OGG"), Some("audio/ogg"));
+        assert_eq!(mime_type("song.m4a"), Some("audio/mp4"));
+        assert_eq!(mime_type("song"), None);
+    }
+}
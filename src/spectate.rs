@@ -0,0 +1,351 @@
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use futures::channel::mpsc::UnboundedReceiver;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    browser::{self, socket::Socket},
+    config::GameConfig,
+    consent::Consent,
+    leaderboard::Input,
+};
+
+// Inputs are sent in small batches rather than every frame.
+const FLUSH_INTERVAL: usize = 6;
+// Spectators stay this many frames behind the player, so a slow batch does
+// not stall the run they are watching.
+const DELAY_FRAMES: usize = 30;
+
+// One character per frame, found by adding up 1 for a jump, 2 for a dash and
+// 4 for holding on to a swing.
+const STEPS: [char; 8] = ['.', 'j', 'd', 'b', 'g', 'G', 'h', 'H'];
+const REWIND: char = 'r';
+
+// What a broadcasting player sends through the relay server. A run is its
+// seed and mutators followed by one input per frame, which is enough for a
+// spectator to play it again exactly, with the world hash every so often to
+// check that it does. The seed is a string because JSON numbers lose
+// precision past 53 bits.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum BroadcastMessage {
+    Start { seed: String, mutators: String },
+    Inputs { inputs: String },
+    Hash { frame: u32, hash: u32 },
+}
+
+// One thing for a spectator to do, in the order the player did it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cue {
+    Start { seed: u64, config: GameConfig },
+    Step(Input),
+    Rewind,
+    // The world hash the player had after `frame` frames.
+    Check { frame: u32, hash: u32 },
+}
+
+fn decode(inputs: &str) -> impl Iterator<Item = Cue> + '_ {
+    inputs.chars().filter_map(|input| match input {
+        REWIND => Some(Cue::Rewind),
+        _ => match STEPS.iter().position(|step| *step == input) {
+            Some(bits) => Some(Cue::Step(Input {
+                jumped: bits & 1 != 0,
+                dashed: bits & 2 != 0,
+                grabbing: bits & 4 != 0,
+            })),
+            None => {
+                log!("Ignoring unknown broadcast input {}", input);
+                None
+            }
+        },
+    })
+}
+
+fn encode(input: Input) -> char {
+    let bits =
+        usize::from(input.jumped) + 2 * usize::from(input.dashed) + 4 * usize::from(input.grabbing);
+    STEPS[bits]
+}
+
+// Broadcasts are sent to `?broadcast=<relay websocket url>` and watched from
+// `?spectate=<relay websocket url>`, once the player has agreed to go online.
+fn relay_from_browser(param: &str) -> Result<Option<String>> {
+    if !Consent::load().allows_online() {
+        return Ok(None);
+    }
+    browser::query_param(param)
+}
+
+pub fn broadcast_from_browser() -> Option<Broadcast> {
+    match relay_from_browser("broadcast").and_then(|url| url.map(Broadcast::open).transpose()) {
+        Ok(broadcast) => broadcast,
+        Err(err) => {
+            log!("Could not start broadcasting {:#?}", err);
+            None
+        }
+    }
+}
+
+pub fn spectator_from_browser() -> Option<Spectator> {
+    match relay_from_browser("spectate").and_then(|url| url.map(Spectator::open).transpose()) {
+        Ok(spectator) => spectator,
+        Err(err) => {
+            log!("Could not start spectating {:#?}", err);
+            None
+        }
+    }
+}
+
+pub struct Broadcast {
+    socket: Socket,
+    live: bool,
+    pending: String,
+}
+
+impl Broadcast {
+    fn open(url: String) -> Result<Self> {
+        let (socket, _messages) = Socket::open(&url)?;
+        Ok(Broadcast {
+            socket,
+            live: false,
+            pending: String::new(),
+        })
+    }
+
+    pub fn start(&mut self, seed: u64, config: &GameConfig) {
+        self.pending.clear();
+        self.live = true;
+        self.send(&BroadcastMessage::Start {
+            seed: seed.to_string(),
+            mutators: config.names(),
+        });
+    }
+
+    pub fn step(&mut self, input: Input) {
+        self.record(encode(input));
+    }
+
+    pub fn rewound(&mut self) {
+        self.record(REWIND);
+        self.flush();
+    }
+
+    // The inputs before it go first, so spectators check on the same frame.
+    pub fn checkpoint(&mut self, frame: u32, hash: u32) {
+        if self.live {
+            self.flush();
+            self.send(&BroadcastMessage::Hash { frame, hash });
+        }
+    }
+
+    // Sends what is left of the run; nothing more goes out until the next start.
+    pub fn finish(&mut self) {
+        self.flush();
+        self.live = false;
+    }
+
+    fn record(&mut self, input: char) {
+        if self.live {
+            self.pending.push(input);
+            if self.pending.len() >= FLUSH_INTERVAL {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.pending.is_empty() {
+            let inputs = std::mem::take(&mut self.pending);
+            self.send(&BroadcastMessage::Inputs { inputs });
+        }
+    }
+
+    fn send(&self, message: &BroadcastMessage) {
+        match browser::to_json(message) {
+            Ok(json) => self.socket.send(&json),
+            Err(err) => {
+                log!("Could not send broadcast {:#?}", err);
+            }
+        }
+    }
+}
+
+// The cues heard so far, held back until enough have arrived to play
+// smoothly. Running dry starts the wait over.
+#[derive(Debug, Default)]
+struct Feed {
+    queue: VecDeque<Cue>,
+    playing: bool,
+}
+
+impl Feed {
+    fn receive(&mut self, message: &BroadcastMessage) {
+        match message {
+            BroadcastMessage::Start { seed, mutators } => match seed.parse() {
+                Ok(seed) => self.queue.push_back(Cue::Start {
+                    seed,
+                    config: GameConfig::from_names(mutators),
+                }),
+                Err(err) => {
+                    log!("Could not read broadcast seed {:#?}", err);
+                }
+            },
+            BroadcastMessage::Inputs { inputs } => self.queue.extend(decode(inputs)),
+            BroadcastMessage::Hash { frame, hash } => self.queue.push_back(Cue::Check {
+                frame: *frame,
+                hash: *hash,
+            }),
+        }
+    }
+
+    fn next(&mut self) -> Option<Cue> {
+        if !self.playing && self.queue.len() < DELAY_FRAMES {
+            return None;
+        }
+        self.playing = true;
+        let cue = self.queue.pop_front();
+        if self.queue.is_empty() {
+            self.playing = false;
+        }
+        cue
+    }
+}
+
+pub struct Spectator {
+    _socket: Socket,
+    messages: UnboundedReceiver<String>,
+    feed: Feed,
+    watching: bool,
+}
+
+impl Spectator {
+    fn open(url: String) -> Result<Self> {
+        let (socket, messages) = Socket::open(&url)?;
+        Ok(Spectator {
+            _socket: socket,
+            messages,
+            feed: Feed::default(),
+            watching: false,
+        })
+    }
+
+    // The next thing to play, if the feed is far enough ahead.
+    pub fn next(&mut self) -> Option<Cue> {
+        while let Ok(Some(text)) = self.messages.try_next() {
+            match browser::from_json(&text) {
+                Ok(message) => self.feed.receive(&message),
+                Err(err) => {
+                    log!("Could not read broadcast {:#?}", err);
+                }
+            }
+        }
+        let cue = self.feed.next();
+        if matches!(cue, Some(Cue::Start { .. })) {
+            self.watching = true;
+        }
+        cue
+    }
+
+    // Whether a run has started since the spectator joined.
+    pub fn watching(&self) -> bool {
+        self.watching
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(inputs: &str) -> BroadcastMessage {
+        BroadcastMessage::Inputs {
+            inputs: inputs.to_string(),
+        }
+    }
+
+    #[test]
+    fn inputs_decode_in_order() {
+        let cues: Vec<Cue> = decode(".jrb").collect();
+        let jump = Input {
+            jumped: true,
+            ..Input::default()
+        };
+
+        assert_eq!(
+            cues,
+            vec![
+                Cue::Step(Input::default()),
+                Cue::Step(jump),
+                Cue::Rewind,
+                Cue::Step(Input {
+                    dashed: true,
+                    ..jump
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_input_has_its_own_step() {
+        (0..8).for_each(|bits| {
+            let input = Input {
+                jumped: bits & 1 != 0,
+                dashed: bits & 2 != 0,
+                grabbing: bits & 4 != 0,
+            };
+            let encoded = encode(input).to_string();
+            assert_eq!(
+                decode(&encoded).collect::<Vec<Cue>>(),
+                vec![Cue::Step(input)]
+            );
+        });
+    }
+
+    #[test]
+    fn feed_waits_for_the_delay_before_playing() {
+        let mut feed = Feed::default();
+        feed.receive(&BroadcastMessage::Start {
+            seed: u64::MAX.to_string(),
+            mutators: "mirror".to_string(),
+        });
+        feed.receive(&inputs(&".".repeat(DELAY_FRAMES - 2)));
+        assert_eq!(feed.next(), None);
+
+        feed.receive(&inputs("j"));
+        assert_eq!(
+            feed.next(),
+            Some(Cue::Start {
+                seed: u64::MAX,
+                config: GameConfig::from_names("mirror")
+            })
+        );
+        assert_eq!(feed.next(), Some(Cue::Step(Input::default())));
+    }
+
+    #[test]
+    fn feed_buffers_again_after_running_dry() {
+        let mut feed = Feed::default();
+        feed.receive(&inputs(&".".repeat(DELAY_FRAMES)));
+        (0..DELAY_FRAMES).for_each(|_| assert!(feed.next().is_some()));
+
+        feed.receive(&inputs("j"));
+        assert_eq!(feed.next(), None);
+    }
+
+    #[test]
+    fn hashes_are_checked_after_the_inputs_before_them() {
+        let mut feed = Feed::default();
+        feed.receive(&inputs(&".".repeat(DELAY_FRAMES)));
+        feed.receive(&BroadcastMessage::Hash { frame: 30, hash: 7 });
+        (0..DELAY_FRAMES).for_each(|_| assert!(matches!(feed.next(), Some(Cue::Step(_)))));
+
+        assert_eq!(feed.next(), Some(Cue::Check { frame: 30, hash: 7 }));
+    }
+
+    #[test]
+    fn messages_are_tagged_by_type() {
+        let json = serde_json::to_string(&inputs("..j")).unwrap();
+
+        assert_eq!(json, r#"{"type":"Inputs","inputs":"..j"}"#);
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeSet;
+
+// A state machine's states and the labelled transitions between them, for
+// drawing with Graphviz.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateGraph {
+    name: String,
+    states: BTreeSet<String>,
+    edges: BTreeSet<(String, String, String)>,
+}
+
+impl StateGraph {
+    pub fn new(name: &str) -> Self {
+        StateGraph {
+            name: name.to_string(),
+            ..StateGraph::default()
+        }
+    }
+
+    pub fn add_state(&mut self, state: &str) {
+        self.states.insert(state.to_string());
+    }
+
+    // Staying in the same state is not drawn, it would only loop every
+    // state back on itself.
+    pub fn add_edge(&mut self, from: &str, label: &str, to: &str) {
+        self.add_state(from);
+        self.add_state(to);
+        if from != to {
+            self.edges
+                .insert((from.to_string(), label.to_string(), to.to_string()));
+        }
+    }
+
+    // A cluster of its own, with node names qualified by the machine's so
+    // machines sharing a state name stay apart.
+    fn cluster(&self) -> String {
+        let node = |state: &str| format!("\"{}.{}\"", self.name, state);
+        let mut lines = vec![
+            format!("  subgraph \"cluster_{}\" {{", self.name),
+            format!("    label=\"{}\";", self.name),
+        ];
+        lines.extend(
+            self.states
+                .iter()
+                .map(|state| format!("    {} [label=\"{}\"];", node(state), state)),
+        );
+        lines.extend(self.edges.iter().map(|(from, label, to)| {
+            format!("    {} -> {} [label=\"{}\"];", node(from), node(to), label)
+        }));
+        lines.push("  }".to_string());
+        lines.join("\n")
+    }
+}
+
+pub fn to_dot(graphs: &[StateGraph]) -> String {
+    let clusters: Vec<String> = graphs.iter().map(StateGraph::cluster).collect();
+    format!("digraph state_machines {{\n{}\n}}\n", clusters.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_are_drawn_once_and_loops_are_left_out() {
+        let mut graph = StateGraph::new("Boy");
+        graph.add_edge("Idle", "Run", "Running");
+        graph.add_edge("Idle", "Run", "Running");
+        graph.add_edge("Running", "Update", "Running");
+
+        assert_eq!(
+            to_dot(&[graph]),
+            "digraph state_machines {\n  subgraph \"cluster_Boy\" {\n    label=\"Boy\";\n    \"Boy.Idle\" [label=\"Idle\"];\n    \"Boy.Running\" [label=\"Running\"];\n    \"Boy.Idle\" -> \"Boy.Running\" [label=\"Run\"];\n  }\n}\n"
+        );
+    }
+}
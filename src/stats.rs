@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{Align, Color, Point, Rect, Renderer, TextStyle},
+    save,
+    versioned::{self, Versioned},
+};
+
+pub const STATS_KEY: &str = "walk_the_dog.stats";
+
+const PANEL: Rect = Rect::new_from_x_y(60, 60, 480, 380);
+const PANEL_COLOR: Color = Color::rgba(255, 255, 255, 0.85);
+const BAR_COLOR: Color = Color::rgb(200, 70, 60);
+const TEXT_X: i16 = 80;
+const LINE_HEIGHT: i16 = 32;
+const BAR_X: i16 = 200;
+// Cause names are right aligned against the bars, however long they are.
+const LABEL_GAP: i16 = 8;
+const BAR_HEIGHT: i16 = 20;
+const BAR_MAX_WIDTH: i16 = 300;
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct LifetimeStats {
+    runs: u32,
+    total_distance: i64,
+    total_jumps: u32,
+    deaths: BTreeMap<String, u32>,
+}
+
+impl Versioned for LifetimeStats {
+    const VERSION: u32 = 1;
+}
+
+impl LifetimeStats {
+    pub fn load() -> Self {
+        versioned::load(STATS_KEY)
+            .unwrap_or_else(|err| {
+                log!("Starting with empty stats {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        save::save_entry(STATS_KEY, self)
+    }
+
+    pub fn record_run(&mut self, distance: i32, jumps: u32, cause: &str) {
+        self.runs += 1;
+        self.total_distance += distance as i64;
+        self.total_jumps += jumps;
+        *self.deaths.entry(cause.to_string()).or_insert(0) += 1;
+    }
+
+    fn average_run_length(&self) -> i64 {
+        if self.runs == 0 {
+            0
+        } else {
+            self.total_distance / self.runs as i64
+        }
+    }
+
+    // Bar widths are scaled so the most common cause of death fills the chart.
+    fn bars(&self) -> Vec<(&str, u32, i16)> {
+        let most = self.deaths.values().copied().max().unwrap_or(0).max(1);
+        self.deaths
+            .iter()
+            .map(|(cause, count)| {
+                let width = (*count as i32 * BAR_MAX_WIDTH as i32 / most as i32) as i16;
+                (cause.as_str(), *count, width)
+            })
+            .collect()
+    }
+
+    pub fn draw(&self, renderer: &Renderer) -> Result<()> {
+        renderer.fill_rect(&PANEL, &PANEL_COLOR);
+
+        let lines = [
+            format!("Runs: {}", self.runs),
+            format!("Total distance: {}", self.total_distance),
+            format!("Total jumps: {}", self.total_jumps),
+            format!("Average run: {}", self.average_run_length()),
+        ];
+        let mut y = PANEL.y() + LINE_HEIGHT;
+        for line in lines.iter() {
+            renderer.draw_text(line, &Point { x: TEXT_X, y })?;
+            y += LINE_HEIGHT;
+        }
+
+        y += LINE_HEIGHT / 2;
+        for (cause, count, width) in self.bars() {
+            renderer.draw_styled_text(
+                cause,
+                &Point {
+                    x: BAR_X - LABEL_GAP,
+                    y,
+                },
+                &TextStyle::new().with_align(Align::Right),
+            )?;
+            renderer.fill_rect(
+                &Rect::new_from_x_y(BAR_X, y - BAR_HEIGHT + 4, width, BAR_HEIGHT),
+                &BAR_COLOR,
+            );
+            renderer.draw_text(
+                &count.to_string(),
+                &Point {
+                    x: BAR_X + width + 8,
+                    y,
+                },
+            )?;
+            y += LINE_HEIGHT;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregates_runs() {
+        let mut stats = LifetimeStats::default();
+        stats.record_run(1000, 4, "stone");
+        stats.record_run(3000, 10, "platform");
+        stats.record_run(2000, 6, "stone");
+
+        assert_eq!(stats.runs, 3);
+        assert_eq!(stats.total_distance, 6000);
+        assert_eq!(stats.total_jumps, 20);
+        assert_eq!(stats.average_run_length(), 2000);
+    }
+
+    #[test]
+    fn bars_scale_to_the_most_common_death() {
+        let mut stats = LifetimeStats::default();
+        assert!(stats.bars().is_empty());
+        assert_eq!(stats.average_run_length(), 0);
+
+        stats.record_run(0, 0, "stone");
+        stats.record_run(0, 0, "stone");
+        stats.record_run(0, 0, "boss");
+
+        assert_eq!(
+            stats.bars(),
+            vec![("boss", 1, BAR_MAX_WIDTH / 2), ("stone", 2, BAR_MAX_WIDTH)]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use serde::Deserialize;
+
+use crate::engine::{Color, Rect, Renderer};
+
+const CONVEYOR_SPEED: i16 = 2;
+const ICE_SLIDE: i16 = 2;
+// How much the speed can change per frame on ice, so it takes a while to
+// pick up or lose the slide.
+const ICE_GRIP: i16 = 1;
+const STRIP_HEIGHT: i16 = 6;
+
+// What a platform's top is made of. Speeds are in the boy's direction of
+// running, so a rightward conveyor carries him along faster.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub enum Surface {
+    #[default]
+    Normal,
+    ConveyorLeft,
+    ConveyorRight,
+    Icy,
+    Sticky,
+}
+
+impl Surface {
+    // The speed the boy settles at while standing here.
+    pub fn target_speed(&self, running_speed: i16) -> i16 {
+        match self {
+            Surface::Normal => running_speed,
+            Surface::ConveyorLeft => running_speed - CONVEYOR_SPEED,
+            Surface::ConveyorRight => running_speed + CONVEYOR_SPEED,
+            Surface::Icy => running_speed + ICE_SLIDE,
+            Surface::Sticky => running_speed / 2,
+        }
+    }
+
+    // Moves `speed` towards the target speed, all at once unless it is icy.
+    pub fn settle(&self, speed: i16, running_speed: i16) -> i16 {
+        let target = self.target_speed(running_speed);
+        match self {
+            Surface::Icy => speed + (target - speed).clamp(-ICE_GRIP, ICE_GRIP),
+            _ => target,
+        }
+    }
+
+    fn color(&self) -> Option<Color> {
+        match self {
+            Surface::Normal => None,
+            Surface::ConveyorLeft | Surface::ConveyorRight => Some(Color::rgb(90, 90, 90)),
+            Surface::Icy => Some(Color::rgb(180, 230, 255)),
+            Surface::Sticky => Some(Color::rgb(140, 90, 30)),
+        }
+    }
+
+    // Marks the top of a platform so special surfaces can be told apart.
+    pub fn draw_strip(&self, renderer: &Renderer, top: &Rect) {
+        if let Some(color) = self.color() {
+            renderer.fill_rect(
+                &Rect::new_from_x_y(top.x(), top.y(), top.width, STRIP_HEIGHT),
+                &color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surfaces_change_the_running_speed() {
+        assert_eq!(Surface::Normal.settle(0, 3), 3);
+        assert_eq!(Surface::ConveyorRight.settle(3, 3), 5);
+        assert_eq!(Surface::ConveyorLeft.settle(3, 3), 1);
+        assert_eq!(Surface::Sticky.settle(3, 3), 1);
+    }
+
+    #[test]
+    fn ice_changes_speed_gradually() {
+        assert_eq!(Surface::Icy.settle(3, 3), 4);
+        assert_eq!(Surface::Icy.settle(4, 3), 5);
+        assert_eq!(Surface::Icy.settle(5, 3), 5);
+        assert_eq!(Surface::Normal.settle(5, 3), 3);
+    }
+}
@@ -0,0 +1,155 @@
+use serde::Deserialize;
+
+use crate::engine::{physics::Pendulum, Color, Point, Renderer};
+
+// Held to hang on to a swing point, and let go to fly off it.
+pub const GRAB_KEY: &str = "ArrowUp";
+const DEFAULT_REACH: i16 = 170;
+const HOOK_RADIUS: i16 = 8;
+const LEASH_WIDTH: f64 = 3.0;
+const HOOK_COLOR: Color = Color::rgb(120, 80, 40);
+const IN_REACH_COLOR: Color = Color::rgb(250, 210, 60);
+const LEASH_COLOR: Color = Color::rgb(200, 40, 40);
+
+// A swing point as placed in segment data, relative to the start of its
+// segment. The boy can catch hold of it from anywhere below within `reach`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SwingPlacement {
+    pub x: i16,
+    pub y: i16,
+    #[serde(default = "default_reach")]
+    pub reach: i16,
+}
+
+fn default_reach() -> i16 {
+    DEFAULT_REACH
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwingPoint {
+    anchor: Point,
+    reach: i16,
+}
+
+impl SwingPoint {
+    pub fn new(placement: &SwingPlacement, offset_x: i16) -> Self {
+        SwingPoint {
+            anchor: Point {
+                x: offset_x + placement.x,
+                y: placement.y,
+            },
+            reach: placement.reach,
+        }
+    }
+
+    pub fn anchor(&self) -> Point {
+        self.anchor
+    }
+
+    pub fn in_reach(&self, hands: &Point) -> bool {
+        let x = i32::from(hands.x - self.anchor.x);
+        let y = i32::from(hands.y - self.anchor.y);
+        y > 0 && x * x + y * y <= i32::from(self.reach).pow(2)
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.anchor.x += x;
+    }
+
+    pub fn right(&self) -> i16 {
+        self.anchor.x + self.reach
+    }
+
+    // Lit up while the boy could catch hold of it.
+    pub fn draw(&self, renderer: &Renderer, hands: &Point) {
+        let color = if self.in_reach(hands) {
+            IN_REACH_COLOR
+        } else {
+            HOOK_COLOR
+        };
+        if let Err(err) = renderer.fill_circle(&self.anchor, HOOK_RADIUS, &color) {
+            log!("Could not draw swing point {:#?}", err);
+        }
+    }
+}
+
+// The boy hanging from a swing point by the leash. `grip` is how far below
+// where he is drawn his hands are.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Swing {
+    pendulum: Pendulum,
+    grip: i16,
+}
+
+impl Swing {
+    pub fn new(pendulum: Pendulum, grip: i16) -> Self {
+        Swing { pendulum, grip }
+    }
+
+    fn hands(&self) -> Point {
+        self.pendulum.position()
+    }
+
+    // Swings on for a frame. He stays put across with the world going past
+    // instead, so this hands back how far the world scrolls and how high he
+    // is drawn now.
+    pub fn update(&mut self, gravity: f64) -> (i16, i16) {
+        let before = self.hands();
+        self.pendulum = self.pendulum.swing(gravity);
+        let after = self.hands();
+        (before.x - after.x, after.y - self.grip)
+    }
+
+    pub fn velocity(&self) -> Point {
+        self.pendulum.velocity()
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.pendulum.move_horizontally(x);
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        renderer.draw_line(
+            &self.pendulum.anchor,
+            &self.hands(),
+            LEASH_WIDTH,
+            &LEASH_COLOR,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::physics::Body;
+
+    #[test]
+    fn swing_points_are_caught_from_below_within_reach() {
+        let placement: SwingPlacement = serde_json::from_str(r#"{"x": 100, "y": 150}"#).unwrap();
+        let point = SwingPoint::new(&placement, 500);
+
+        assert_eq!(point.anchor(), Point { x: 600, y: 150 });
+        assert!(point.in_reach(&Point { x: 550, y: 300 }));
+        assert!(!point.in_reach(&Point {
+            x: 600,
+            y: 150 + DEFAULT_REACH + 1
+        }));
+        assert!(!point.in_reach(&Point { x: 650, y: 100 }));
+    }
+
+    #[test]
+    fn swinging_scrolls_the_world_instead_of_moving_him_across() {
+        let body = Body {
+            position: Point { x: 200, y: 300 },
+            velocity: Point { x: 5, y: 0 },
+        };
+        let pendulum = Pendulum::attach(Point { x: 200, y: 150 }, body);
+        let mut swing = Swing::new(pendulum, 10);
+
+        let (scroll, y) = swing.update(1.0);
+        assert!(scroll < 0);
+        assert_eq!(y, swing.hands().y - 10);
+        swing.move_horizontally(scroll);
+        assert_eq!(swing.hands().x, 200);
+    }
+}
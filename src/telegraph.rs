@@ -0,0 +1,172 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Physics,
+    engine::{Color, Point, Rect, Renderer},
+    fairness::JumpEnvelope,
+    versioned::{self, Versioned},
+};
+
+pub const RUN_HISTORY_KEY: &str = "walk_the_dog.run_history";
+
+// Hints are shown for this many runs at most, fading sooner for a player who
+// already clears most obstacles.
+const ASSISTED_RUNS: u32 = 5;
+// Even full assistance stays faint, so it reads as a hint rather than a path.
+const MAX_ALPHA: f64 = 0.6;
+const TAKEOFF_COLOR: Color = Color::rgb(255, 255, 255);
+const LANDING_COLOR: Color = Color::rgb(120, 220, 120);
+const MARKER_WIDTH: i16 = 4;
+const MARKER_HEIGHT: i16 = 16;
+const LANDING_RADIUS: i16 = 6;
+
+// How the player has been doing against the obstacles hints are shown for.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct RunHistory {
+    runs: u32,
+    cleared: u32,
+    hit: u32,
+}
+
+impl Versioned for RunHistory {
+    const VERSION: u32 = 1;
+}
+
+impl RunHistory {
+    pub fn load() -> Self {
+        versioned::load(RUN_HISTORY_KEY)
+            .unwrap_or_else(|err| {
+                log!("Starting with no run history {:#?}", err);
+                None
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        versioned::save(RUN_HISTORY_KEY, self)
+    }
+
+    pub fn record_run(&mut self, cleared: u32, hit: bool) {
+        self.runs += 1;
+        self.cleared += cleared;
+        self.hit += u32::from(hit);
+    }
+
+    // From 1 for a brand new player down to 0 once they no longer need it.
+    pub fn assistance(&self) -> f64 {
+        if self.runs >= ASSISTED_RUNS {
+            return 0.0;
+        }
+        let attempts = self.cleared + self.hit;
+        if attempts == 0 {
+            1.0
+        } else {
+            f64::from(self.hit) / f64::from(attempts)
+        }
+    }
+}
+
+// Landing markers and jump-timing hints over obstacles that have to be jumped.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Telegraph {
+    assistance: f64,
+}
+
+impl Telegraph {
+    pub fn new(history: &RunHistory) -> Self {
+        Telegraph {
+            assistance: history.assistance(),
+        }
+    }
+
+    // For each obstacle still ahead of `boy`, a tick where his front should be
+    // when he jumps and a dot where he comes down.
+    pub fn draw(
+        &self,
+        renderer: &Renderer,
+        obstacles: &[Rect],
+        boy: &Rect,
+        physics: &Physics,
+        speed: i16,
+    ) {
+        if self.assistance <= 0.0 {
+            return;
+        }
+        let envelope = JumpEnvelope::new(physics, speed);
+        let hints: Vec<(i16, i16)> = obstacles
+            .iter()
+            .filter(|obstacle| obstacle.x() >= boy.right())
+            .filter_map(|obstacle| hint(&envelope, obstacle, boy))
+            .collect();
+        if hints.is_empty() {
+            return;
+        }
+        renderer.draw_with_alpha(self.assistance * MAX_ALPHA, || {
+            hints.iter().for_each(|(takeoff, landing)| {
+                renderer.fill_rect(
+                    &Rect::new_from_x_y(
+                        takeoff - MARKER_WIDTH / 2,
+                        boy.bottom() - MARKER_HEIGHT,
+                        MARKER_WIDTH,
+                        MARKER_HEIGHT,
+                    ),
+                    &TAKEOFF_COLOR,
+                );
+                let center = Point {
+                    x: *landing,
+                    y: boy.bottom() - LANDING_RADIUS,
+                };
+                if let Err(err) = renderer.fill_circle(&center, LANDING_RADIUS, &LANDING_COLOR) {
+                    log!("Could not draw landing marker {:#?}", err);
+                }
+            });
+        });
+    }
+}
+
+// Where to jump from and where he lands, jumping from the middle of the window
+// that clears `obstacle`.
+fn hint(envelope: &JumpEnvelope, obstacle: &Rect, boy: &Rect) -> Option<(i16, i16)> {
+    let (nearest, farthest) =
+        envelope.takeoff_window(obstacle.width, boy.bottom() - obstacle.y(), boy.width)?;
+    let takeoff = obstacle.x() - (nearest + farthest) / 2;
+    Some((
+        takeoff,
+        takeoff + envelope.landing_distance() - boy.width / 2,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_players_get_full_assistance() {
+        assert_eq!(RunHistory::default().assistance(), 1.0);
+    }
+
+    #[test]
+    fn assistance_fades_as_the_player_clears_more() {
+        let mut history = RunHistory::default();
+        history.record_run(1, true);
+        assert_eq!(history.assistance(), 0.5);
+
+        history.record_run(2, false);
+        assert_eq!(history.assistance(), 0.25);
+
+        (0..ASSISTED_RUNS).for_each(|_| history.record_run(0, true));
+        assert_eq!(history.assistance(), 0.0);
+    }
+
+    #[test]
+    fn hints_sit_before_the_obstacle_and_land_past_it() {
+        let envelope = JumpEnvelope::new(&Physics::default(), 4);
+        let boy = Rect::new_from_x_y(100, 480, 50, 120);
+        let stone = Rect::new_from_x_y(400, 546, 90, 54);
+        let (takeoff, landing) = hint(&envelope, &stone, &boy).unwrap();
+
+        assert!(takeoff < stone.x());
+        assert!(landing > stone.right());
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{anyhow, Result};
+use serde::{de::IgnoredAny, Deserialize};
+
+use crate::{
+    browser,
+    triggers::{self, TriggerKind, TriggerPlacement},
+};
+
+// Point objects have no size, so they become a zone as thin as a hand-placed
+// checkpoint.
+const POINT_WIDTH: f64 = 20.0;
+
+// The parts of a map exported from the Tiled editor as JSON that levels read:
+// the objects on its object layers, including those inside group layers.
+#[derive(Deserialize, Debug, Default)]
+pub struct Map {
+    #[serde(default)]
+    layers: Vec<Layer>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Layer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    objects: Vec<Object>,
+    #[serde(default)]
+    layers: Vec<Layer>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Object {
+    // Tiled 1.9 renamed `type` to `class`; maps from either are read.
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    width: f64,
+    #[serde(default)]
+    height: f64,
+    #[serde(default)]
+    properties: Vec<Property>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Property {
+    name: String,
+    value: Value,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Value {
+    Number(f64),
+    Text(String),
+    // Booleans and anything else no trigger reads.
+    Other(IgnoredAny),
+}
+
+impl Layer {
+    fn objects(&self) -> Vec<&Object> {
+        let mut objects: Vec<&Object> = match self.kind.as_str() {
+            "objectgroup" => self.objects.iter().collect(),
+            _ => vec![],
+        };
+        objects.extend(self.layers.iter().flat_map(Layer::objects));
+        objects
+    }
+}
+
+impl Object {
+    fn class(&self) -> &str {
+        if self.class.is_empty() {
+            &self.kind
+        } else {
+            &self.class
+        }
+    }
+
+    fn property(&self, name: &str) -> Option<&Value> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| &property.value)
+    }
+
+    fn number(&self, name: &str) -> Result<f64> {
+        match self.property(name) {
+            Some(Value::Number(number)) => Ok(*number),
+            _ => Err(anyhow!("{} needs a number property {}", self.class(), name)),
+        }
+    }
+
+    fn text(&self, name: &str) -> Result<String> {
+        match self.property(name) {
+            Some(Value::Text(text)) => Ok(text.clone()),
+            _ => Err(anyhow!("{} needs a text property {}", self.class(), name)),
+        }
+    }
+
+    // Objects are named for the trigger they become, with its fields as
+    // custom properties. Objects of any other class are not triggers.
+    fn trigger_kind(&self) -> Result<Option<TriggerKind>> {
+        let kind = match self.class() {
+            "SpeedBoost" => TriggerKind::SpeedBoost,
+            "CameraPan" => TriggerKind::CameraPan {
+                x: self.number("x")? as i16,
+            },
+            "Tutorial" => TriggerKind::Tutorial {
+                text: self.text("text")?,
+            },
+            "Checkpoint" => TriggerKind::Checkpoint,
+            "DogSprint" => TriggerKind::DogSprint {
+                distance: self.number("distance")? as i32,
+            },
+            "Cutscene" => TriggerKind::Cutscene {
+                script: self.text("script")?,
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(kind))
+    }
+
+    // The segment the object is placed on, the first unless it says.
+    fn segment(&self) -> Result<usize> {
+        if self.property("segment").is_none() {
+            return Ok(0);
+        }
+        let segment = self.number("segment")?;
+        if segment < 0.0 || segment.fract() != 0.0 {
+            return Err(anyhow!(
+                "{} has a segment of {}, which is not a segment index",
+                self.class(),
+                segment
+            ));
+        }
+        Ok(segment as usize)
+    }
+
+    // Objects with no height cover the full height, like zones in segment data.
+    fn placement(&self) -> Result<Option<(usize, TriggerPlacement)>> {
+        let kind = match self.trigger_kind()? {
+            Some(kind) => kind,
+            None => return Ok(None),
+        };
+        let segment = self.segment()?;
+        let width = if self.width > 0.0 {
+            self.width
+        } else {
+            POINT_WIDTH
+        };
+        let (y, height) = if self.height > 0.0 {
+            (self.y.round() as i16, self.height.round() as i16)
+        } else {
+            (0, triggers::full_height())
+        };
+        Ok(Some((
+            segment,
+            TriggerPlacement {
+                kind,
+                x: self.x.round() as i16,
+                width: width.round() as i16,
+                y,
+                height,
+            },
+        )))
+    }
+}
+
+impl Map {
+    // The trigger each object stands for, and the segment it is placed on, as
+    // given by its `segment` property. Positions are relative to the start of
+    // that segment. Objects that are not triggers are passed over, and
+    // triggers missing a property or with a bad one come back as errors.
+    pub fn placements(&self) -> (Vec<(usize, TriggerPlacement)>, Vec<anyhow::Error>) {
+        let (placements, errors): (Vec<_>, Vec<_>) = self
+            .layers
+            .iter()
+            .flat_map(Layer::objects)
+            .filter_map(|object| object.placement().transpose())
+            .partition(Result::is_ok);
+        (
+            placements.into_iter().filter_map(Result::ok).collect(),
+            errors.into_iter().filter_map(Result::err).collect(),
+        )
+    }
+}
+
+pub async fn load_map(path: &str) -> Result<Map> {
+    browser::fetch_json(path)
+        .await?
+        .into_serde()
+        .map_err(|err| anyhow!("Unrecognized Tiled map {} {:#?}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"{
+        "width": 40,
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "data": [1, 2, 3]},
+            {"type": "objectgroup", "name": "story", "objects": [
+                {"id": 1, "type": "Checkpoint", "x": 120.4, "y": 0, "width": 0, "height": 0, "point": true},
+                {"id": 2, "class": "Tutorial", "x": 0, "y": 100, "width": 300, "height": 200,
+                 "properties": [{"name": "text", "type": "string", "value": "Jump!"}]},
+                {"id": 3, "class": "Cutscene", "x": 50, "y": 0, "width": 10, "height": 0,
+                 "properties": [
+                    {"name": "script", "type": "string", "value": "Dog: Woof!"},
+                    {"name": "segment", "type": "int", "value": 2}
+                 ]}
+            ]},
+            {"type": "group", "name": "extras", "layers": [
+                {"type": "objectgroup", "name": "decor", "objects": [
+                    {"id": 4, "class": "Tree", "x": 0, "y": 0,
+                     "properties": [{"name": "evergreen", "type": "bool", "value": true}]},
+                    {"id": 5, "class": "DogSprint", "x": 0, "y": 0},
+                    {"id": 6, "class": "Checkpoint", "x": 0, "y": 0,
+                     "properties": [{"name": "segment", "type": "float", "value": 1.5}]},
+                    {"id": 7, "class": "Checkpoint", "x": 0, "y": 0,
+                     "properties": [{"name": "segment", "type": "int", "value": -1}]}
+                ]}
+            ]}
+        ]
+    }"#;
+
+    #[test]
+    fn objects_become_trigger_placements() {
+        let map: Map = serde_json::from_str(MAP).unwrap();
+        let (placements, errors) = map.placements();
+
+        assert_eq!(
+            placements,
+            vec![
+                (
+                    0,
+                    TriggerPlacement {
+                        kind: TriggerKind::Checkpoint,
+                        x: 120,
+                        width: 20,
+                        y: 0,
+                        height: triggers::full_height(),
+                    }
+                ),
+                (
+                    0,
+                    TriggerPlacement {
+                        kind: TriggerKind::Tutorial {
+                            text: "Jump!".to_string()
+                        },
+                        x: 0,
+                        width: 300,
+                        y: 100,
+                        height: 200,
+                    }
+                ),
+                (
+                    2,
+                    TriggerPlacement {
+                        kind: TriggerKind::Cutscene {
+                            script: "Dog: Woof!".to_string()
+                        },
+                        x: 50,
+                        width: 10,
+                        y: 0,
+                        height: triggers::full_height(),
+                    }
+                ),
+            ]
+        );
+        // A tree is not a trigger, a sprint needs a distance and segments
+        // are counted in whole numbers from 0.
+        assert_eq!(errors.len(), 3);
+    }
+}
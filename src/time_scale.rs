@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+
+// About 80ms at 60 frames a second.
+pub const HIT_STOP_FRAMES: u8 = 5;
+// Hits landing during a stop wait their turn, but only this many, so a
+// flurry of hits cannot freeze the game for long.
+const MAX_QUEUED_STOPS: usize = 1;
+
+// Decides which frames the world moves on. A hit-stop holds the world still
+// for a few frames while it keeps drawing, to give a hit some weight.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimeScale {
+    frozen_frames: u8,
+    queued: VecDeque<u8>,
+}
+
+impl TimeScale {
+    pub fn hit_stop(&mut self, frames: u8) {
+        if self.frozen_frames == 0 {
+            self.frozen_frames = frames;
+        } else if self.queued.len() < MAX_QUEUED_STOPS {
+            self.queued.push_back(frames);
+        }
+    }
+
+    // Called once a frame; false while a hit-stop is holding the world still.
+    pub fn should_step(&mut self) -> bool {
+        if self.frozen_frames == 0 {
+            self.frozen_frames = self.queued.pop_front().unwrap_or(0);
+        }
+        if self.frozen_frames == 0 {
+            return true;
+        }
+        self.frozen_frames -= 1;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frozen_for(time_scale: &mut TimeScale) -> usize {
+        (0..100).take_while(|_| !time_scale.should_step()).count()
+    }
+
+    #[test]
+    fn hit_stop_freezes_for_its_length() {
+        let mut time_scale = TimeScale::default();
+        assert!(time_scale.should_step());
+
+        time_scale.hit_stop(HIT_STOP_FRAMES);
+        assert_eq!(frozen_for(&mut time_scale), HIT_STOP_FRAMES as usize);
+    }
+
+    #[test]
+    fn hits_during_a_stop_queue_up_to_a_limit() {
+        let mut time_scale = TimeScale::default();
+        (0..10).for_each(|_| time_scale.hit_stop(HIT_STOP_FRAMES));
+
+        assert_eq!(
+            frozen_for(&mut time_scale),
+            HIT_STOP_FRAMES as usize * (MAX_QUEUED_STOPS + 1)
+        );
+    }
+}
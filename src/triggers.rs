@@ -0,0 +1,286 @@
+use serde::Deserialize;
+
+use crate::{
+    engine::{Align, Color, EventBus, Point, Rect, Renderer, TextStyle},
+    i18n::text,
+};
+
+const HEIGHT: i16 = 600;
+const BOOST_SPEED: i16 = 3;
+const PAN_SPEED: i16 = 2;
+const PROMPT_X: i16 = 300;
+const PROMPT_WIDTH: i16 = 400;
+const PROMPT_Y: i16 = 140;
+const CHECKPOINT_Y: i16 = 170;
+const ZONE_COLOR: Color = Color::rgb(0, 200, 255);
+const PROMPT_OUTLINE: Color = Color::rgb(255, 255, 255);
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub enum TriggerKind {
+    // The world scrolls faster while the boy is inside.
+    SpeedBoost,
+    // Shifts the view to the left by `x` pixels, to see further ahead.
+    CameraPan { x: i16 },
+    Tutorial { text: String },
+    Checkpoint,
+    // The boy hops on the dog for a sprint of `distance` pixels.
+    DogSprint { distance: i32 },
+    // Stops the run to play a scripted scene, as read by `Cutscene::from_script`.
+    Cutscene { script: String },
+}
+
+// A zone as placed in segment data, relative to the start of its segment.
+// Zones cover the full height unless told otherwise.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct TriggerPlacement {
+    pub kind: TriggerKind,
+    pub x: i16,
+    pub width: i16,
+    #[serde(default)]
+    pub y: i16,
+    #[serde(default = "full_height")]
+    pub height: i16,
+}
+
+pub fn full_height() -> i16 {
+    HEIGHT
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ZoneEvent {
+    Entered(TriggerKind),
+    Exited(TriggerKind),
+}
+
+// A rectangle the boy passes through rather than collides with.
+#[derive(Clone)]
+pub struct TriggerZone {
+    kind: TriggerKind,
+    area: Rect,
+    inside: bool,
+}
+
+impl TriggerZone {
+    pub fn new(placement: &TriggerPlacement, offset_x: i16) -> Self {
+        TriggerZone {
+            kind: placement.kind.clone(),
+            area: Rect::new_from_x_y(
+                offset_x + placement.x,
+                placement.y,
+                placement.width,
+                placement.height,
+            ),
+            inside: false,
+        }
+    }
+
+    pub fn update(&mut self, boy: &Rect, events: &mut EventBus<ZoneEvent>) {
+        let inside = self.area.intersects(boy);
+        match (self.inside, inside) {
+            (false, true) => events.publish(ZoneEvent::Entered(self.kind.clone())),
+            (true, false) => events.publish(ZoneEvent::Exited(self.kind.clone())),
+            _ => {}
+        }
+        self.inside = inside;
+    }
+
+    pub fn move_horizontally(&mut self, x: i16) {
+        self.area.set_x(self.area.x() + x);
+    }
+
+    pub fn right(&self) -> i16 {
+        self.area.right()
+    }
+
+    pub fn draw_outline(&self, renderer: &Renderer) {
+        renderer.stroke_rect(&self.area, &ZONE_COLOR);
+    }
+}
+
+// What the zones the boy has passed through are currently doing to the walk.
+#[derive(Clone, Default)]
+pub struct ZoneEffects {
+    boosted: bool,
+    camera_x: i16,
+    camera_target_x: i16,
+    prompt: Option<String>,
+    checkpoint: Option<i32>,
+    // A sprint the walk has yet to start.
+    sprint: Option<i32>,
+    // The script of a scene the walk has yet to play.
+    cutscene: Option<String>,
+}
+
+impl ZoneEffects {
+    pub fn handle(&mut self, event: &ZoneEvent, distance: i32) {
+        match event {
+            ZoneEvent::Entered(TriggerKind::SpeedBoost) => self.boosted = true,
+            ZoneEvent::Exited(TriggerKind::SpeedBoost) => self.boosted = false,
+            ZoneEvent::Entered(TriggerKind::CameraPan { x }) => self.camera_target_x = *x,
+            ZoneEvent::Exited(TriggerKind::CameraPan { .. }) => self.camera_target_x = 0,
+            ZoneEvent::Entered(TriggerKind::Tutorial { text }) => self.prompt = Some(text.clone()),
+            ZoneEvent::Exited(TriggerKind::Tutorial { .. }) => self.prompt = None,
+            ZoneEvent::Entered(TriggerKind::Checkpoint) => self.checkpoint = Some(distance),
+            ZoneEvent::Exited(TriggerKind::Checkpoint) => {}
+            ZoneEvent::Entered(TriggerKind::DogSprint { distance }) => {
+                self.sprint = Some(*distance)
+            }
+            ZoneEvent::Exited(TriggerKind::DogSprint { .. }) => {}
+            ZoneEvent::Entered(TriggerKind::Cutscene { script }) => {
+                self.cutscene = Some(script.clone())
+            }
+            ZoneEvent::Exited(TriggerKind::Cutscene { .. }) => {}
+        }
+    }
+
+    pub fn take_sprint(&mut self) -> Option<i32> {
+        self.sprint.take()
+    }
+
+    pub fn take_cutscene(&mut self) -> Option<String> {
+        self.cutscene.take()
+    }
+
+    // Eases the camera towards where the last pan zone wants it.
+    pub fn update(&mut self) {
+        let step = (self.camera_target_x - self.camera_x).clamp(-PAN_SPEED, PAN_SPEED);
+        self.camera_x += step;
+    }
+
+    // Walking speeds are negative, as the world moves left.
+    pub fn walking_speed(&self, walking_speed: i16) -> i16 {
+        if self.boosted && walking_speed != 0 {
+            walking_speed - BOOST_SPEED
+        } else {
+            walking_speed
+        }
+    }
+
+    pub fn camera_x(&self) -> i16 {
+        self.camera_x
+    }
+
+    pub fn draw(&self, renderer: &Renderer) {
+        let style = TextStyle::new()
+            .with_align(Align::Center)
+            .with_outline(PROMPT_OUTLINE, 4);
+        let mut checkpoint_y = CHECKPOINT_Y;
+        if let Some(prompt) = &self.prompt {
+            match renderer.draw_styled_text(
+                prompt,
+                &Point {
+                    x: PROMPT_X,
+                    y: PROMPT_Y,
+                },
+                &style.clone().with_max_width(PROMPT_WIDTH),
+            ) {
+                Ok(height) => checkpoint_y = checkpoint_y.max(PROMPT_Y + height + 6),
+                Err(err) => {
+                    log!("Could not draw tutorial prompt {:#?}", err);
+                }
+            }
+        }
+        if let Some(checkpoint) = self.checkpoint {
+            if let Err(err) = renderer.draw_styled_text(
+                &format!("{} {}m", text("Checkpoint"), checkpoint),
+                &Point {
+                    x: PROMPT_X,
+                    y: checkpoint_y,
+                },
+                &style,
+            ) {
+                log!("Could not draw checkpoint {:#?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(kind: TriggerKind) -> TriggerZone {
+        TriggerZone::new(
+            &TriggerPlacement {
+                kind,
+                x: 100,
+                width: 50,
+                y: 0,
+                height: HEIGHT,
+            },
+            200,
+        )
+    }
+
+    #[test]
+    fn zones_publish_once_on_entry_and_exit() {
+        let mut zone = zone(TriggerKind::Checkpoint);
+        let mut events = EventBus::new();
+        let boy = Rect::new_from_x_y(260, 400, 40, 100);
+
+        zone.update(&boy, &mut events);
+        zone.move_horizontally(-20);
+        zone.update(&boy, &mut events);
+        zone.update(&boy, &mut events);
+        assert_eq!(
+            events.drain(),
+            vec![ZoneEvent::Entered(TriggerKind::Checkpoint)]
+        );
+
+        zone.move_horizontally(-100);
+        zone.update(&boy, &mut events);
+        assert_eq!(
+            events.drain(),
+            vec![ZoneEvent::Exited(TriggerKind::Checkpoint)]
+        );
+    }
+
+    #[test]
+    fn effects_last_while_inside() {
+        let mut effects = ZoneEffects::default();
+
+        effects.handle(&ZoneEvent::Entered(TriggerKind::SpeedBoost), 0);
+        assert_eq!(effects.walking_speed(-4), -7);
+        assert_eq!(effects.walking_speed(0), 0);
+        effects.handle(&ZoneEvent::Exited(TriggerKind::SpeedBoost), 0);
+        assert_eq!(effects.walking_speed(-4), -4);
+
+        effects.handle(&ZoneEvent::Entered(TriggerKind::CameraPan { x: 5 }), 0);
+        (0..2).for_each(|_| effects.update());
+        assert_eq!(effects.camera_x(), 4);
+        (0..2).for_each(|_| effects.update());
+        assert_eq!(effects.camera_x(), 5);
+
+        effects.handle(&ZoneEvent::Entered(TriggerKind::Checkpoint), 1200);
+        assert_eq!(effects.checkpoint, Some(1200));
+
+        let sprint = TriggerKind::DogSprint { distance: 900 };
+        effects.handle(&ZoneEvent::Entered(sprint.clone()), 0);
+        effects.handle(&ZoneEvent::Exited(sprint), 0);
+        assert_eq!(effects.take_sprint(), Some(900));
+        assert_eq!(effects.take_sprint(), None);
+
+        let scene = TriggerKind::Cutscene {
+            script: "Dog: Woof!".to_string(),
+        };
+        effects.handle(&ZoneEvent::Entered(scene), 0);
+        assert_eq!(effects.take_cutscene(), Some("Dog: Woof!".to_string()));
+        assert_eq!(effects.take_cutscene(), None);
+    }
+
+    #[test]
+    fn placements_read_from_segment_data() {
+        let placement: TriggerPlacement = serde_json::from_str(
+            r#"{"kind": {"Tutorial": {"text": "Space to jump"}}, "x": 10, "width": 80}"#,
+        )
+        .unwrap();
+
+        assert_eq!(placement.height, HEIGHT);
+        assert_eq!(
+            placement.kind,
+            TriggerKind::Tutorial {
+                text: "Space to jump".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::browser;
+
+// Data files and persisted entries are written as `{"version": N, "data": ...}`.
+// Anything written before versions existed is the bare data, and reads as
+// version 0. Sprite sheets keep TexturePacker's own format and its
+// `meta.version`.
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    #[serde(default)]
+    version: u32,
+}
+
+// A format that may change between releases. When it does, `VERSION` goes up
+// and `migrate` learns to read the older one.
+pub trait Versioned: Sized + DeserializeOwned {
+    const VERSION: u32;
+
+    // Reads `json` as written by `version`, which is older than `VERSION`.
+    // Formats that have not changed since versions were added only lack the
+    // envelope.
+    fn migrate(version: u32, json: &str) -> Result<Self> {
+        match version {
+            0 => parse(json),
+            _ => Err(anyhow!("No migration from version {}", version)),
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn parse<T: DeserializeOwned>(json: &str) -> Result<T> {
+    browser::from_json(json)
+}
+
+#[cfg(test)]
+fn parse<T: DeserializeOwned>(json: &str) -> Result<T> {
+    serde_json::from_str(json).map_err(|err| anyhow!("Could not read JSON {:#?}", err))
+}
+
+#[cfg(not(test))]
+fn stringify<T: Serialize>(value: &T) -> Result<String> {
+    browser::to_json(value)
+}
+
+#[cfg(test)]
+fn stringify<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|err| anyhow!("Could not serialize {:#?}", err))
+}
+
+// Bare data that is not an object, like a setting's name, has no version.
+fn version_of(json: &str) -> u32 {
+    parse::<Header>(json).map_or(0, |header| header.version)
+}
+
+pub fn read<T: Versioned>(json: &str) -> Result<T> {
+    match version_of(json) {
+        version if version == T::VERSION => Ok(parse::<Envelope<T>>(json)?.data),
+        version if version > T::VERSION => Err(anyhow!(
+            "Version {} is newer than this game reads ({})",
+            version,
+            T::VERSION
+        )),
+        version => T::migrate(version, json),
+    }
+}
+
+pub fn write<T: Versioned + Serialize>(value: &T) -> Result<String> {
+    stringify(&Envelope {
+        version: T::VERSION,
+        data: value,
+    })
+}
+
+// Older entries are upgraded as they are read, and saved in the new format
+// the next time they change.
+pub fn load<T: Versioned>(key: &str) -> Result<Option<T>> {
+    browser::load_item(key)?
+        .map(|json| read(&json).map_err(|err| anyhow!("Could not read {} {:#?}", key, err)))
+        .transpose()
+}
+
+pub fn save<T: Versioned + Serialize>(key: &str, value: &T) -> Result<()> {
+    browser::save_item(key, &write(value)?)
+}
+
+pub async fn fetch<T: Versioned>(path: &str) -> Result<T> {
+    let json: String = js_sys::JSON::stringify(&browser::fetch_json(path).await?)
+        .map_err(|err| anyhow!("Could not stringify {} {:#?}", path, err))?
+        .into();
+    read(&json).map_err(|err| anyhow!("Could not read {} {:#?}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Score {
+        best: u32,
+    }
+
+    impl Versioned for Score {
+        const VERSION: u32 = 2;
+
+        // Version 1 called it `high`.
+        fn migrate(version: u32, json: &str) -> Result<Self> {
+            #[derive(Deserialize)]
+            struct ScoreV1 {
+                high: u32,
+            }
+            match version {
+                0 | 1 => {
+                    let old: ScoreV1 = if version == 0 {
+                        parse(json)?
+                    } else {
+                        parse::<Envelope<ScoreV1>>(json)?.data
+                    };
+                    Ok(Score { best: old.high })
+                }
+                _ => Err(anyhow!("No migration from version {}", version)),
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Setting {
+        On,
+        Off,
+    }
+
+    impl Versioned for Setting {
+        const VERSION: u32 = 1;
+    }
+
+    #[test]
+    fn current_versions_round_trip() {
+        let json = write(&Score { best: 7 }).unwrap();
+
+        assert_eq!(json, r#"{"version":2,"data":{"best":7}}"#);
+        assert_eq!(read::<Score>(&json).unwrap(), Score { best: 7 });
+    }
+
+    #[test]
+    fn older_versions_are_migrated() {
+        assert_eq!(read::<Score>(r#"{"high":3}"#).unwrap(), Score { best: 3 });
+        assert_eq!(
+            read::<Score>(r#"{"version":1,"data":{"high":4}}"#).unwrap(),
+            Score { best: 4 }
+        );
+        assert_eq!(read::<Setting>(r#""Off""#).unwrap(), Setting::Off);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        assert!(read::<Setting>(r#"{"version":2,"data":"On"}"#).is_err());
+    }
+}
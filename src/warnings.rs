@@ -0,0 +1,105 @@
+use crate::engine::{Align, Color, Point, Rect, Renderer, TextStyle};
+
+// How long before a hazard comes into view it gets flagged.
+const WARNING_FRAMES: i16 = 45;
+const EDGE_MARGIN: i16 = 28;
+const RADIUS: i16 = 16;
+const BLINK_FRAMES: u32 = 8;
+const BADGE_COLOR: Color = Color::rgb(230, 50, 40);
+const MARK_COLOR: Color = Color::rgb(255, 255, 255);
+
+// Something dangerous off to the right, closing in at `speed` pixels a frame
+// on top of however fast the world scrolls.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Threat {
+    pub bounding_box: Rect,
+    pub speed: i16,
+}
+
+impl Threat {
+    // Frames until it crosses `edge`, or `None` if it is already in view or
+    // not getting any closer.
+    fn frames_until(&self, edge: i16, scroll_speed: i16) -> Option<i16> {
+        let closing = self.speed + scroll_speed;
+        let gap = self.bounding_box.x() - edge;
+        if gap < 0 || closing <= 0 {
+            None
+        } else {
+            Some(gap / closing)
+        }
+    }
+}
+
+// The heights to flag at the screen's right `edge`: the middle of each threat
+// that will come into view within a beat.
+pub fn warning_heights(threats: &[Threat], edge: i16, scroll_speed: i16) -> Vec<i16> {
+    threats
+        .iter()
+        .filter(|threat| {
+            threat
+                .frames_until(edge, scroll_speed)
+                .map_or(false, |frames| frames <= WARNING_FRAMES)
+        })
+        .map(|threat| threat.bounding_box.y() + threat.bounding_box.height / 2)
+        .collect()
+}
+
+// A blinking exclamation mark just inside the right `edge` at each height.
+pub fn draw(renderer: &Renderer, heights: &[i16], edge: i16, frame: u32) {
+    if frame / BLINK_FRAMES % 2 == 1 {
+        return;
+    }
+    heights.iter().for_each(|y| {
+        let center = Point {
+            x: edge - EDGE_MARGIN,
+            y: *y,
+        };
+        if let Err(err) = renderer.fill_circle(&center, RADIUS, &BADGE_COLOR) {
+            log!("Could not draw warning {:#?}", err);
+        }
+        if let Err(err) = renderer.draw_styled_text(
+            "!",
+            &Point {
+                x: center.x,
+                y: center.y + RADIUS / 2,
+            },
+            &TextStyle::new()
+                .with_color(MARK_COLOR)
+                .with_align(Align::Center),
+        ) {
+            log!("Could not draw warning mark {:#?}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threat(x: i16, speed: i16) -> Threat {
+        Threat {
+            bounding_box: Rect::new_from_x_y(x, 400, 40, 60),
+            speed,
+        }
+    }
+
+    #[test]
+    fn only_threats_about_to_appear_are_flagged() {
+        let threats = [threat(640, 3), threat(900, 3), threat(500, 3)];
+
+        assert_eq!(warning_heights(&threats, 600, 0), vec![430]);
+    }
+
+    #[test]
+    fn scrolling_brings_threats_in_sooner() {
+        let threats = [threat(800, 1)];
+
+        assert!(warning_heights(&threats, 600, 0).is_empty());
+        assert_eq!(warning_heights(&threats, 600, 4), vec![430]);
+    }
+
+    #[test]
+    fn threats_moving_away_are_ignored() {
+        assert!(warning_heights(&[threat(620, -5)], 600, 3).is_empty());
+    }
+}
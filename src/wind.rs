@@ -0,0 +1,122 @@
+use serde::Deserialize;
+
+use crate::engine::{
+    physics::{Force, ForceField},
+    Color, Point, Rect, Renderer,
+};
+
+const HEIGHT: i16 = 600;
+const PARTICLES_PER_FIELD: i64 = 12;
+const PARTICLE_SPEED: i16 = 5;
+const STREAK_LENGTH: i16 = 14;
+const UPDRAFT_COLOR: Color = Color::rgba(220, 240, 255, 0.7);
+const HEADWIND_COLOR: Color = Color::rgba(255, 255, 255, 0.5);
+
+// A force field as placed in segment data, relative to the start of its
+// segment. Like trigger zones, fields cover the full height unless told
+// otherwise.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ForcePlacement {
+    pub force: Force,
+    pub x: i16,
+    pub width: i16,
+    #[serde(default)]
+    pub y: i16,
+    #[serde(default = "full_height")]
+    pub height: i16,
+}
+
+fn full_height() -> i16 {
+    HEIGHT
+}
+
+impl ForcePlacement {
+    pub fn field(&self, offset_x: i16) -> ForceField {
+        ForceField {
+            force: self.force,
+            area: Rect::new_from_x_y(offset_x + self.x, self.y, self.width, self.height),
+        }
+    }
+}
+
+// Where each particle of a field is on `frame`. They are worked out from the
+// frame rather than kept, so rewinds and replays show them the same way.
+fn particles(field: &ForceField, frame: u32) -> Vec<Point> {
+    let area = &field.area;
+    let width = i64::from(area.width.max(1));
+    let height = i64::from(area.height.max(1));
+    let travelled = i64::from(frame) * i64::from(PARTICLE_SPEED);
+    (0..PARTICLES_PER_FIELD)
+        .map(|index| {
+            // Spread out by steps that share no factor with the usual sizes.
+            let (across, down) = (index * 37, index * 53);
+            match field.force {
+                // Rising from the vent at the bottom.
+                Force::Updraft { .. } => Point {
+                    x: area.x() + (across % width) as i16,
+                    y: area.bottom() - ((travelled + down) % height) as i16,
+                },
+                // Blowing back at the boy, from right to left.
+                Force::Headwind { .. } => Point {
+                    x: area.right() - ((travelled + across) % width) as i16,
+                    y: area.y() + (down % height) as i16,
+                },
+            }
+        })
+        .collect()
+}
+
+// How many particles `draw` puts on screen for `fields` at full detail.
+#[cfg(debug_assertions)]
+pub fn particle_count(fields: &[ForceField]) -> usize {
+    fields.len() * PARTICLES_PER_FIELD as usize
+}
+
+pub fn draw(renderer: &Renderer, fields: &[ForceField], frame: u32) {
+    if !renderer.detail().particles {
+        return;
+    }
+    fields.iter().for_each(|field| {
+        particles(field, frame).iter().for_each(|particle| {
+            let (streak, color) = match field.force {
+                Force::Updraft { .. } => (Rect::new(*particle, 2, STREAK_LENGTH), UPDRAFT_COLOR),
+                Force::Headwind { .. } => (Rect::new(*particle, STREAK_LENGTH, 2), HEADWIND_COLOR),
+            };
+            renderer.fill_rect(&streak, &color);
+        })
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placements_read_from_segment_data() {
+        let placement: ForcePlacement =
+            serde_json::from_str(r#"{"force": {"Updraft": {"fall": 2}}, "x": 40, "width": 100, "y": 200, "height": 400}"#)
+                .unwrap();
+
+        assert_eq!(
+            placement.field(1000),
+            ForceField {
+                force: Force::Updraft { fall: 2 },
+                area: Rect::new_from_x_y(1040, 200, 100, 400),
+            }
+        );
+    }
+
+    #[test]
+    fn particles_stay_inside_their_field() {
+        let field = ForceField {
+            force: Force::Headwind { drag: 1 },
+            area: Rect::new_from_x_y(100, 300, 200, 100),
+        };
+
+        (0..120).for_each(|frame| {
+            assert!(particles(&field, frame).iter().all(|particle| {
+                particle.x > 100 && particle.x <= 300 && particle.y >= 300 && particle.y < 400
+            }))
+        });
+    }
+}